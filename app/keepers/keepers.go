@@ -451,6 +451,7 @@ func (ak *SecretAppKeepers) InitCustomKeepers(
 		nil,
 		nil,
 		&app.LastTxManager,
+		ak.GetSubspace(compute.ModuleName),
 	)
 	ak.ComputeKeeper = &computeKeeper
 	wasmHooks.ContractKeeper = ak.ComputeKeeper
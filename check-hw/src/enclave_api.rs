@@ -23,6 +23,8 @@ extern "C" {
         retval: *mut NodeAuthResult,
         api_key: *const u8,
         api_key_len: u32,
+        accepted_advisories: *const u8,
+        accepted_advisories_len: u32,
     ) -> sgx_status_t;
 }
 
@@ -184,7 +186,60 @@ pub extern "C" fn ocall_read_db(
     unimplemented!()
 }
 
+#[no_mangle]
+pub extern "C" fn ocall_multiple_read_db(
+    _context: Ctx,
+    _vm_error: *mut UntrustedVmError,
+    _gas_used: *mut u64,
+    _values: *mut EnclaveBuffer,
+    _keys: *const u8,
+    _keys_len: usize,
+) -> OcallReturn {
+    unimplemented!()
+}
+
+#[no_mangle]
+pub extern "C" fn ocall_db_scan(
+    _context: Ctx,
+    _vm_error: *mut UntrustedVmError,
+    _gas_used: *mut u64,
+    _iterator_id: *mut u32,
+    _start: *const u8,
+    _start_len: usize,
+    _end: *const u8,
+    _end_len: usize,
+    _order: i32,
+) -> OcallReturn {
+    unimplemented!()
+}
+
+#[no_mangle]
+pub extern "C" fn ocall_db_next(
+    _context: Ctx,
+    _vm_error: *mut UntrustedVmError,
+    _gas_used: *mut u64,
+    _iterator_id: u32,
+    _key: *mut EnclaveBuffer,
+    _value: *mut EnclaveBuffer,
+) -> OcallReturn {
+    unimplemented!()
+}
+
 #[no_mangle]
 pub extern "C" fn ocall_allocate(_buffer: *const u8, _length: usize) -> UserSpaceBuffer {
     unimplemented!()
 }
+
+#[no_mangle]
+pub extern "C" fn ocall_allocate_chunked(_total_length: usize) -> UserSpaceBuffer {
+    unimplemented!()
+}
+
+#[no_mangle]
+pub extern "C" fn ocall_append_chunk(
+    _buffer: UserSpaceBuffer,
+    _chunk: *const u8,
+    _chunk_len: usize,
+) -> OcallReturn {
+    unimplemented!()
+}
@@ -35,9 +35,21 @@ fn main() {
                 .long("testnet")
                 .help("Run in testnet mode"),
         )
+        .arg(
+            clap::Arg::with_name("accept-advisory")
+                .long("accept-advisory")
+                .value_name("ID")
+                .multiple(true)
+                .number_of_values(1)
+                .help("Accept this advisory ID on top of the enclave's whitelist, for this check only"),
+        )
         .get_matches();
 
     let is_testnet = matches.is_present("testnet");
+    let accepted_advisories = matches
+        .values_of("accept-advisory")
+        .map(|ids| ids.collect::<Vec<_>>().join(","))
+        .unwrap_or_default();
 
     println!("Creating enclave instance..");
 
@@ -75,6 +87,8 @@ fn main() {
             &mut retval,
             api_key_bytes.as_ptr(),
             api_key_bytes.len() as u32,
+            accepted_advisories.as_ptr(),
+            accepted_advisories.len() as u32,
         )
     };
 
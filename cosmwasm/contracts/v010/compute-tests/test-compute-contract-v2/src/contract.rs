@@ -1381,7 +1381,7 @@ fn send_external_query_recursion_limit<S: Storage, A: Api, Q: Querier>(
     } else {
         match result {
             Err(StdError::GenericErr { msg, .. })
-                if msg == "Querier system error: Query recursion limit exceeded" =>
+                if msg.starts_with("Querier system error: Query recursion limit exceeded") =>
             {
                 Ok(String::from("Recursion limit was correctly enforced"))
             }
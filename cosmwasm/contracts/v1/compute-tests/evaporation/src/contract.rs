@@ -1,7 +1,11 @@
 use cosmwasm_std::{
-    entry_point, DepsMut, Env, MessageInfo, Response, StdResult,
+    entry_point, to_binary, Binary, Deps, DepsMut, Env, MessageInfo, Response, StdResult,
 };
-use crate::msg::{ExecuteMsg, InstantiateMsg,};
+use crate::msg::{ExecuteMsg, InstantiateMsg, QueryMsg};
+
+extern "C" {
+    fn gas_used_in_call() -> u64;
+}
 
 #[entry_point]
 pub fn instantiate(
@@ -34,9 +38,30 @@ pub fn execute(deps: DepsMut, _env: Env, _info: MessageInfo, msg: ExecuteMsg) ->
 
             Ok(Response::default())
         }
+        ExecuteMsg::CheckGasUsedInCall { evaporate } => {
+            let before = unsafe { gas_used_in_call() };
+            deps.api.gas_evaporate(evaporate)?;
+            let after = unsafe { gas_used_in_call() };
+
+            Ok(Response::default()
+                .add_attribute("before", before.to_string())
+                .add_attribute("after", after.to_string()))
+        }
     }
 
 }
 
+#[entry_point]
+pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
+    match msg {
+        QueryMsg::Evaporate { amount } => {
+            deps.api.gas_evaporate(amount)?;
+            let gas_used: u64 = deps.api.check_gas()?;
+
+            to_binary(&gas_used)
+        }
+    }
+}
+
 
 
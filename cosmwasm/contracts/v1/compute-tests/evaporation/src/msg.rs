@@ -12,4 +12,12 @@ pub enum ExecuteMsg {
     Evaporate { amount: u32 },
     CheckGas {},
     UseExact { amount: u32 },
+    CheckGasUsedInCall { evaporate: u32 },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryMsg {
+    /// Evaporates `amount` and returns the gas used so far
+    Evaporate { amount: u32 },
 }
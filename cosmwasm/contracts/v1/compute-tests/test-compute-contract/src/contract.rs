@@ -1,14 +1,25 @@
-use core::time;
-use std::{thread, vec};
+use std::vec;
 
 use cosmwasm_std::{
     attr, coins, entry_point, from_binary, to_binary, BankMsg, Binary, CanonicalAddr, CosmosMsg,
     Coin, Deps, DepsMut, Empty, Env, Event, MessageInfo, QueryRequest, Reply, ReplyOn, Response,
     StdError, StdResult, Storage, SubMsg, SubMsgResponse, SubMsgResult, WasmMsg, WasmQuery,
 };
-use cosmwasm_storage::PrefixedStorage;
+use cosmwasm_storage::{to_length_prefixed, PrefixedStorage};
 use secp256k1::Secp256k1;
 
+use crate::imports::{
+    bls12_381_aggregate_verify_api, checked_mul_div_api, contract_all_balances_api,
+    db_read_multiple_api, derive_id_api, derive_nonce_api, enclave_block_view_api,
+    last_output_hash_api, min_gas_price_api, normalize_coins_api, predict_instantiate_address_api,
+    remaining_submsg_budget_api, require_funds_api, same_session_api, secp256k1_batch_verify_api,
+    secp256k1_verify_der_api, secp256r1_verify_api, sleep_api, sqrt_api, storage_cas_api,
+    storage_len_api, storage_map_entries_api, storage_map_get_api, storage_map_insert_api,
+    storage_map_len_api, storage_map_remove_api, storage_pop_api, storage_push_api,
+    storage_secure_remove_api, storage_wipe_prefix_api, tx_msg_position_api, verify_adr036_api,
+    verify_double_sign_evidence_api, verify_eth_personal_sign_api, verify_jwt_api,
+    verify_legacy_multisig_api, verify_signed_payload_api, verify_webauthn_api,
+};
 use crate::msg::{
     ExecuteMsg, ExternalMessages, IBCLifecycleComplete, InstantiateMsg, QueryMsg, QueryRes, SudoMsg,
 };
@@ -346,6 +357,23 @@ pub fn instantiate(
 
             Ok(Response::new())
         }
+        InstantiateMsg::ProcessOnce { request_id } => {
+            let previous_output_hash = last_output_hash_api(request_id.as_bytes());
+
+            Ok(Response::new().add_attribute(
+                "already_processed",
+                previous_output_hash.is_some().to_string(),
+            ))
+        }
+        InstantiateMsg::GasMeter {} => {
+            // busy work
+            let mut v = vec![0; 65536];
+            let mut x = 0;
+            loop {
+                x += (x + 1) % 65536;
+                v[x] = 65536 - x;
+            }
+        }
     }
 }
 
@@ -625,9 +653,38 @@ pub fn execute(deps: DepsMut, env: Env, info: MessageInfo, msg: ExecuteMsg) -> S
             }
         }
         ExecuteMsg::GasMeterProxy {} => Ok(Response::default()),
+        ExecuteMsg::OutOfGasInSubmsg {} => Ok(Response::new().add_submessage(SubMsg {
+            id: 9400,
+            msg: CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: env.contract.address.into_string(),
+                code_hash: env.contract.code_hash,
+                msg: Binary::from(r#"{"gas_meter":{}}"#.as_bytes().to_vec()),
+                funds: vec![],
+            }),
+            gas_limit: Some(100_000_u64),
+            reply_on: ReplyOn::Error,
+        })),
+        ExecuteMsg::OutOfGasInInitSubmsg { code_id, code_hash } => {
+            Ok(Response::new().add_submessage(SubMsg {
+                id: 9400,
+                msg: CosmosMsg::Wasm(WasmMsg::Instantiate {
+                    code_id,
+                    code_hash,
+                    msg: Binary::from(r#"{"gas_meter":{}}"#.as_bytes().to_vec()),
+                    funds: vec![],
+                    label: String::from("out of gas"),
+                    admin: None,
+                }),
+                gas_limit: Some(100_000_u64),
+                reply_on: ReplyOn::Error,
+            }))
+        }
         ExecuteMsg::TransferMoney { amount } => transfer_money(deps, amount),
         ExecuteMsg::RecursiveReply {} => recursive_reply(env, deps),
         ExecuteMsg::RecursiveReplyFail {} => recursive_reply_fail(env, deps),
+        ExecuteMsg::NestedRecursiveReply { depth, gas_limit } => {
+            nested_recursive_reply(env, depth, gas_limit)
+        }
         ExecuteMsg::InitNewContract {} => init_new_contract(env, deps),
         ExecuteMsg::InitNewContractWithError {} => init_new_contract_with_error(env, deps),
         ExecuteMsg::SubMsgLoop { iter } => sub_msg_loop(env, deps, iter),
@@ -942,6 +999,7 @@ pub fn execute(deps: DepsMut, env: Env, info: MessageInfo, msg: ExecuteMsg) -> S
         } => Ok(exec_with_callback_contract_error(contract_addr, code_hash)),
         ExecuteMsg::SetState { key, value } => Ok(set_state(deps, key, value)),
         ExecuteMsg::GetState { key } => Ok(get_state(deps, key)),
+        ExecuteMsg::GetStates { keys } => Ok(get_states(keys)),
         ExecuteMsg::RemoveState { key } => Ok(remove_state(deps, key)),
         ExecuteMsg::TestCanonicalizeAddressErrors {} => test_canonicalize_address_errors(deps),
         ExecuteMsg::Panic {} => panic!("panic in exec"),
@@ -1040,7 +1098,7 @@ pub fn execute(deps: DepsMut, env: Env, info: MessageInfo, msg: ExecuteMsg) -> S
             })),
         ),
         ExecuteMsg::Sleep { ms } => {
-            thread::sleep(time::Duration::from_millis(ms));
+            sleep_api(ms);
 
             Ok(Response::new())
         }
@@ -1130,6 +1188,23 @@ pub fn execute(deps: DepsMut, env: Env, info: MessageInfo, msg: ExecuteMsg) -> S
 
             return res;
         }
+        ExecuteMsg::Secp256r1Verify {
+            pubkey,
+            sig,
+            msg_hash,
+            iterations,
+        } => {
+            let mut res = Ok(Response::new());
+
+            // loop for benchmarking
+            for _ in 0..iterations {
+                let result =
+                    secp256r1_verify_api(msg_hash.as_slice(), sig.as_slice(), pubkey.as_slice())?;
+                res = Ok(Response::new().add_attribute("result", format!("{}", result)));
+            }
+
+            return res;
+        }
         ExecuteMsg::Secp256k1VerifyFromCrate {
             pubkey,
             sig,
@@ -1226,6 +1301,264 @@ pub fn execute(deps: DepsMut, env: Env, info: MessageInfo, msg: ExecuteMsg) -> S
 
             return res;
         }
+        ExecuteMsg::Bls12381AggregateVerify {
+            pubkeys,
+            msgs,
+            agg_sig,
+            iterations,
+        } => {
+            let mut res = Ok(Response::new());
+
+            // loop for benchmarking
+            for _ in 0..iterations {
+                res = bls12_381_aggregate_verify_api(
+                    pubkeys
+                        .iter()
+                        .map(|p| p.as_slice())
+                        .collect::<Vec<&[u8]>>()
+                        .as_slice(),
+                    msgs.iter()
+                        .map(|m| m.as_slice())
+                        .collect::<Vec<&[u8]>>()
+                        .as_slice(),
+                    agg_sig.as_slice(),
+                )
+                .map(|result| Response::new().add_attribute("result", format!("{}", result)));
+            }
+
+            return res;
+        }
+        ExecuteMsg::Secp256k1BatchVerify {
+            pubkeys,
+            sigs,
+            msg_hashes,
+            iterations,
+        } => {
+            let mut res = Ok(Response::new());
+
+            // loop for benchmarking
+            for _ in 0..iterations {
+                res = secp256k1_batch_verify_api(
+                    msg_hashes
+                        .iter()
+                        .map(|m| m.as_slice())
+                        .collect::<Vec<&[u8]>>()
+                        .as_slice(),
+                    sigs.iter()
+                        .map(|s| s.as_slice())
+                        .collect::<Vec<&[u8]>>()
+                        .as_slice(),
+                    pubkeys
+                        .iter()
+                        .map(|p| p.as_slice())
+                        .collect::<Vec<&[u8]>>()
+                        .as_slice(),
+                )
+                .map(|result| Response::new().add_attribute("result", format!("{}", result)));
+            }
+
+            return res;
+        }
+        ExecuteMsg::VerifyDoubleSignEvidence {
+            votes,
+            sigs,
+            pubkey,
+        } => {
+            let offender = verify_double_sign_evidence_api(
+                votes
+                    .iter()
+                    .map(|v| v.as_slice())
+                    .collect::<Vec<&[u8]>>()
+                    .as_slice(),
+                sigs.iter()
+                    .map(|s| s.as_slice())
+                    .collect::<Vec<&[u8]>>()
+                    .as_slice(),
+                pubkey.as_slice(),
+            )?;
+
+            Ok(Response::new().add_attribute("offender", Binary(offender).to_base64()))
+        }
+        ExecuteMsg::VerifyJwt { token, jwk } => {
+            let claims = verify_jwt_api(token.as_bytes(), jwk.as_bytes())?;
+
+            Ok(Response::new().add_attribute("claims", String::from_utf8_lossy(&claims)))
+        }
+        ExecuteMsg::VerifySignedPayload {
+            payload,
+            signature,
+            pubkey,
+            max_age,
+            signer_time,
+        } => {
+            let payload = verify_signed_payload_api(
+                payload.as_bytes(),
+                signature.as_slice(),
+                pubkey.as_slice(),
+                max_age,
+                signer_time,
+            )?;
+
+            Ok(Response::new().add_attribute("payload", String::from_utf8_lossy(&payload)))
+        }
+        ExecuteMsg::VerifyEthPersonalSign {
+            message,
+            signature,
+            eth_address,
+        } => {
+            let valid = verify_eth_personal_sign_api(
+                message.as_bytes(),
+                signature.as_slice(),
+                eth_address.as_slice(),
+            )?;
+
+            Ok(Response::new().add_attribute("valid", valid.to_string()))
+        }
+        ExecuteMsg::VerifyWebauthn {
+            authenticator_data,
+            client_data_json,
+            signature,
+            public_key,
+        } => {
+            let valid = verify_webauthn_api(
+                authenticator_data.as_slice(),
+                client_data_json.as_bytes(),
+                signature.as_slice(),
+                public_key.as_slice(),
+            )?;
+
+            Ok(Response::new().add_attribute("valid", valid.to_string()))
+        }
+        ExecuteMsg::VerifyAdr036 {
+            data,
+            signature,
+            pubkey,
+            signer_addr,
+        } => {
+            let valid = verify_adr036_api(
+                data.as_slice(),
+                signature.as_slice(),
+                pubkey.as_slice(),
+                &signer_addr,
+            )?;
+
+            Ok(Response::new().add_attribute("valid", valid.to_string()))
+        }
+        ExecuteMsg::SameSession {
+            ciphertext_a,
+            ciphertext_b,
+        } => {
+            let same = same_session_api(ciphertext_a.as_slice(), ciphertext_b.as_slice())?;
+
+            Ok(Response::new().add_attribute("same_session", same.to_string()))
+        }
+        ExecuteMsg::Secp256k1VerifyDer {
+            message_hash,
+            signature,
+            public_key,
+        } => {
+            let valid = secp256k1_verify_der_api(
+                message_hash.as_slice(),
+                signature.as_slice(),
+                public_key.as_slice(),
+            )?;
+
+            Ok(Response::new().add_attribute("valid", valid.to_string()))
+        }
+        ExecuteMsg::CheckedMulDiv { a, b, c } => Ok(Response::new()
+            .add_attribute("result", checked_mul_div_api(a, b, c)?.to_string())),
+        ExecuteMsg::Sqrt { x, decimal_places } => Ok(Response::new()
+            .add_attribute("result", sqrt_api(x, decimal_places)?.to_string())),
+        ExecuteMsg::ContractAllBalances {} => {
+            let balances = contract_all_balances_api()?
+                .iter()
+                .map(|coin| format!("{}{}", coin.amount, coin.denom))
+                .collect::<Vec<String>>()
+                .join(",");
+
+            Ok(Response::new().add_attribute("balances", balances))
+        }
+        ExecuteMsg::StoragePush { key, values } => {
+            let mut length = 0;
+            for value in values {
+                length = storage_push_api(key.as_bytes(), value.as_bytes());
+            }
+
+            Ok(Response::new().add_attribute("length", length.to_string()))
+        }
+        ExecuteMsg::StoragePop { key, count } => {
+            let popped = (0..count)
+                .map(|_| {
+                    storage_pop_api(key.as_bytes())
+                        .map(|value| String::from_utf8_lossy(&value).to_string())
+                        .unwrap_or_else(|| "<empty>".to_string())
+                })
+                .collect::<Vec<String>>()
+                .join(",");
+
+            Ok(Response::new().add_attribute("popped", popped))
+        }
+        ExecuteMsg::StorageSecureRemove { key } => {
+            let removed = storage_secure_remove_api(key.as_bytes());
+
+            Ok(Response::new().add_attribute("removed", removed.to_string()))
+        }
+        ExecuteMsg::StorageCas { key, expected, new } => {
+            let swapped = storage_cas_api(
+                key.as_bytes(),
+                expected.as_ref().map(|expected| expected.as_bytes()),
+                new.as_bytes(),
+            );
+
+            Ok(Response::new().add_attribute("swapped", swapped.to_string()))
+        }
+        ExecuteMsg::StorageWipePrefix { prefix } => {
+            let removed = storage_wipe_prefix_api(prefix.as_bytes());
+
+            Ok(Response::new().add_attribute("removed", removed.to_string()))
+        }
+        ExecuteMsg::StorageMapInsert { map, key, value } => {
+            let is_new = storage_map_insert_api(map.as_bytes(), key.as_bytes(), value.as_bytes());
+
+            Ok(Response::new().add_attribute("new", is_new.to_string()))
+        }
+        ExecuteMsg::StorageMapRemove { map, key } => {
+            let removed = storage_map_remove_api(map.as_bytes(), key.as_bytes())
+                .map(|value| String::from_utf8_lossy(&value).to_string())
+                .unwrap_or_else(|| "<none>".to_string());
+
+            Ok(Response::new().add_attribute("removed", removed))
+        }
+        ExecuteMsg::StorageMapEntries { map } => {
+            let entries = storage_map_entries_api(map.as_bytes())
+                .iter()
+                .map(|(key, value)| {
+                    // reading a key back must agree with iterating to it
+                    assert_eq!(
+                        storage_map_get_api(map.as_bytes(), key).as_ref(),
+                        Some(value)
+                    );
+                    format!(
+                        "{}={}",
+                        String::from_utf8_lossy(key),
+                        String::from_utf8_lossy(value)
+                    )
+                })
+                .collect::<Vec<String>>()
+                .join(",");
+
+            Ok(Response::new()
+                .add_attribute("entries", entries)
+                .add_attribute("len", storage_map_len_api(map.as_bytes()).to_string()))
+        }
+        ExecuteMsg::ProcessOnce { request_id } => {
+            let previous_output_hash = last_output_hash_api(request_id.as_bytes());
+
+            Ok(Response::new().add_attribute(
+                "already_processed",
+                previous_output_hash.is_some().to_string(),
+            ))
+        }
         ExecuteMsg::Secp256k1RecoverPubkey {
             msg_hash,
             sig,
@@ -1298,6 +1631,76 @@ pub fn execute(deps: DepsMut, env: Env, info: MessageInfo, msg: ExecuteMsg) -> S
                 amount,
             })))
         }
+        ExecuteMsg::NormalizedBankMsgSend { to, amount } => {
+            Ok(Response::new().add_message(CosmosMsg::Bank(BankMsg::Send {
+                to_address: to,
+                amount: normalize_coins_api(&amount)?,
+            })))
+        }
+        ExecuteMsg::RequireFunds { funds } => {
+            require_funds_api(&funds)?;
+            Ok(Response::new())
+        }
+        ExecuteMsg::FillSubmsgBudget { extra } => {
+            let count = remaining_submsg_budget_api()
+                .ok_or_else(|| StdError::generic_err("submessages aren't limited"))?
+                + extra;
+            let messages = (0..count).map(|_| {
+                CosmosMsg::Bank(BankMsg::Send {
+                    to_address: info.sender.to_string(),
+                    amount: coins(1, "denom"),
+                })
+            });
+            Ok(Response::new()
+                .add_messages(messages)
+                .add_attribute("submessages", count.to_string()))
+        }
+        ExecuteMsg::EmitEventType { length } => Ok(Response::new()
+            .add_event(Event::new("e".repeat(length as usize)).add_attribute("attr", "value"))),
+        ExecuteMsg::MinGasPrice {} => {
+            let min_gas_price = min_gas_price_api()?
+                .iter()
+                .map(|coin| format!("{}{}", coin.amount, coin.denom))
+                .collect::<Vec<String>>()
+                .join(",");
+            // attribute values can't be empty
+            let min_gas_price = if min_gas_price.is_empty() {
+                "none".to_string()
+            } else {
+                min_gas_price
+            };
+
+            Ok(Response::new().add_attribute("min_gas_price", min_gas_price))
+        }
+        ExecuteMsg::TxMsgPosition {} => {
+            let (msg_index, msg_count) = match tx_msg_position_api()? {
+                Some(position) => (
+                    position.msg_index.to_string(),
+                    position.msg_count.to_string(),
+                ),
+                None => ("none".to_string(), "none".to_string()),
+            };
+
+            Ok(Response::new()
+                .add_attribute("msg_index", msg_index)
+                .add_attribute("msg_count", msg_count))
+        }
+        ExecuteMsg::PredictInstantiateAddress { code_id } => {
+            let address = match predict_instantiate_address_api(code_id) {
+                Some(canonical) => deps.api.addr_humanize(&canonical)?.to_string(),
+                None => "none".to_string(),
+            };
+
+            Ok(Response::new().add_attribute("predicted_address", address))
+        }
+        ExecuteMsg::DeriveNonces { count } => {
+            let nonces = (0..count)
+                .map(|_| Binary(derive_nonce_api().unwrap_or_default()).to_base64())
+                .collect::<Vec<String>>()
+                .join(",");
+
+            Ok(Response::new().add_attribute("nonces", nonces))
+        }
         ExecuteMsg::BankMsgBurn { amount } => {
             Ok(Response::new().add_message(CosmosMsg::Bank(BankMsg::Burn { amount })))
         }
@@ -1334,6 +1737,10 @@ pub fn execute(deps: DepsMut, env: Env, info: MessageInfo, msg: ExecuteMsg) -> S
             Ok(a) => Ok(Response::new().set_data(a.as_bytes())),
             Err(_) => Ok(Response::new().set_data(to_binary("Apple")?)),
         },
+        ExecuteMsg::HumanizeAddress { canonical } => {
+            let human = deps.api.addr_humanize(&CanonicalAddr(canonical))?;
+            Ok(Response::new().set_data(human.as_bytes()))
+        }
         ExecuteMsg::GetEnv {} => Ok(Response::new()
             .add_attribute("env", serde_json_wasm::to_string(&env).unwrap())
             .add_attribute("info", serde_json_wasm::to_string(&info).unwrap())),
@@ -1449,6 +1856,25 @@ pub fn execute(deps: DepsMut, env: Env, info: MessageInfo, msg: ExecuteMsg) -> S
         }
         ExecuteMsg::Echo { data }   => {
             Ok(Response::new().set_data(data))}
+        ExecuteMsg::SendEchoWithReply {
+            to,
+            code_hash,
+            data,
+        } => Ok(
+            Response::new().add_submessage(SubMsg::reply_on_success(
+                CosmosMsg::Wasm(WasmMsg::Execute {
+                    contract_addr: to,
+                    code_hash,
+                    msg: Binary(
+                        format!(r#"{{"echo":{{"data":"{}"}}}}"#, data.to_base64())
+                            .as_bytes()
+                            .to_vec(),
+                    ),
+                    funds: vec![],
+                }),
+                9300,
+            )),
+        ),
     }
 }
 
@@ -1534,6 +1960,29 @@ pub fn recursive_reply(env: Env, _deps: DepsMut) -> StdResult<Response> {
     Ok(resp)
 }
 
+/// Calls itself `depth` times, each call waiting for a reply from the one it makes
+pub fn nested_recursive_reply(env: Env, depth: u32, gas_limit: Option<u64>) -> StdResult<Response> {
+    if depth == 0 {
+        return Ok(Response::default());
+    }
+
+    let msg = to_binary(&ExecuteMsg::NestedRecursiveReply {
+        depth: depth - 1,
+        gas_limit,
+    })?;
+    Ok(Response::new().add_submessage(SubMsg {
+        id: 1310,
+        msg: CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: env.contract.address.into_string(),
+            code_hash: env.contract.code_hash,
+            msg,
+            funds: vec![],
+        }),
+        gas_limit,
+        reply_on: ReplyOn::Success,
+    }))
+}
+
 pub fn sub_msg_loop(env: Env, _deps: DepsMut, iter: u64) -> StdResult<Response> {
     if iter == 0 {
         return Err(StdError::generic_err("stopped loop"));
@@ -2062,6 +2511,23 @@ pub fn init_new_contract_with_error(env: Env, _deps: DepsMut) -> StdResult<Respo
 pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
     match msg {
         QueryMsg::Get {} => to_binary(&get(deps, env)?),
+        QueryMsg::StorageLen { key } => to_binary(&storage_len_api(key.as_bytes())),
+        QueryMsg::EnclaveBlockView {} => {
+            let block_view = enclave_block_view_api()?;
+            if block_view.height != env.block.height || block_view.time != env.block.time {
+                return Err(StdError::generic_err(format!(
+                    "enclave block view {:?} doesn't match env {:?}",
+                    block_view, env.block
+                )));
+            }
+            to_binary(&block_view)
+        }
+        QueryMsg::DeriveIds { seed, count } => {
+            let ids: Vec<Binary> = (0..count)
+                .map(|_| Binary(derive_id_api(seed.as_bytes())))
+                .collect();
+            to_binary(&ids)
+        }
 
         // These were ported from the v0.10 test-contract:
         QueryMsg::ContractError { error_type } => Err(map_string_to_error(error_type)),
@@ -2116,6 +2582,17 @@ pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
                 .as_bytes()
                 .to_vec(),
         )),
+        QueryMsg::VerifyMultisig {
+            pubkey,
+            bitarray,
+            signatures,
+            msg,
+        } => to_binary(&verify_legacy_multisig_api(
+            pubkey.as_slice(),
+            bitarray.as_slice(),
+            signatures.as_slice(),
+            msg.as_slice(),
+        )?),
     }
 }
 
@@ -2134,6 +2611,7 @@ pub fn reply(deps: DepsMut, env: Env, reply: Reply) -> StdResult<Response> {
         }
         (1337, SubMsgResult::Ok(_)) => Err(StdError::generic_err("got wrong bank answer")),
 
+        (1310, SubMsgResult::Ok(_)) => Ok(Response::default()),
         (1304, SubMsgResult::Err(e)) => Err(StdError::generic_err(format!(
             "recursive reply failed: {}",
             e
@@ -2401,6 +2879,15 @@ pub fn reply(deps: DepsMut, env: Env, reply: Reply) -> StdResult<Response> {
             }
         },
         (9000, SubMsgResult::Err(_)) => Ok(Response::default().set_data("err".as_bytes())),
+        (9300, SubMsgResult::Ok(s)) => match s.data {
+            Some(d) => {
+                let new_data = "reply: ".to_string() + &String::from_utf8_lossy(d.as_slice());
+                Ok(Response::default().set_data(new_data.as_bytes()))
+            }
+            None => Err(StdError::generic_err("reply didn't get the submessage data")),
+        },
+        (9300, SubMsgResult::Err(_)) => Err(StdError::generic_err("echo submessage failed")),
+        (9400, SubMsgResult::Err(e)) => Ok(Response::new().add_attribute("submsg_error", e)),
         (8451, SubMsgResult::Ok(_)) => Ok(Response::new()
             .add_attribute_plaintext("attr_reply", "🦄")
             .set_data(to_binary("reply")?)),
@@ -2843,6 +3330,29 @@ fn get_state(deps: DepsMut, key: String) -> Response {
     }
 }
 
+/// Reads the keys that `set_state` writes in a single `db_read_multiple`, and lists their values
+/// as `key=value`, with `<none>` for a missing key
+fn get_states(keys: Vec<String>) -> Response {
+    let namespace = to_length_prefixed(b"my_prefix");
+    let state_keys: Vec<Vec<u8>> = keys
+        .iter()
+        .map(|key| [namespace.as_slice(), key.as_bytes()].concat())
+        .collect();
+    let state_keys: Vec<&[u8]> = state_keys.iter().map(Vec::as_slice).collect();
+
+    let values = keys
+        .iter()
+        .zip(db_read_multiple_api(&state_keys))
+        .map(|(key, value)| match value {
+            Some(value) => format!("{}={}", key, String::from_utf8_lossy(&value)),
+            None => format!("{}=<none>", key),
+        })
+        .collect::<Vec<_>>()
+        .join(",");
+
+    Response::new().add_attribute("values", values)
+}
+
 fn set_state(deps: DepsMut, key: String, value: String) -> Response {
     let mut store = PrefixedStorage::new(deps.storage, b"my_prefix");
     store.set(key.as_bytes(), value.as_bytes());
@@ -0,0 +1,847 @@
+//! Host functions that the enclave exposes but `cosmwasm-std` doesn't wrap (yet).
+
+use cosmwasm_std::{from_slice, to_vec, CanonicalAddr, Coin, StdError, StdResult, Timestamp};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Mirrors `cosmwasm_std::memory::Region`, which is not exported by the std crate.
+#[repr(C)]
+struct Region {
+    offset: u32,
+    capacity: u32,
+    length: u32,
+}
+
+extern "C" {
+    fn secp256k1_batch_verify(
+        message_hashes_ptr: u32,
+        signatures_ptr: u32,
+        public_keys_ptr: u32,
+    ) -> u32;
+
+    fn bls12_381_aggregate_verify(
+        public_keys_ptr: u32,
+        messages_ptr: u32,
+        signature_ptr: u32,
+    ) -> u32;
+
+    fn verify_double_sign_evidence(
+        votes_ptr: u32,
+        signatures_ptr: u32,
+        public_key_ptr: u32,
+    ) -> u64;
+
+    fn verify_jwt(token_ptr: u32, jwk_ptr: u32) -> u64;
+
+    fn verify_signed_payload(
+        payload_ptr: u32,
+        signature_ptr: u32,
+        pubkey_ptr: u32,
+        max_age: u64,
+        signer_time: u64,
+    ) -> u64;
+
+    fn checked_mul_div(a: u64, b: u64, c: u64) -> u64;
+
+    fn sqrt(x: u64, decimal_places: u32) -> u64;
+
+    fn contract_all_balances() -> u32;
+
+    fn enclave_block_view() -> u32;
+
+    fn storage_push(key_ptr: u32, value_ptr: u32) -> u32;
+
+    fn storage_pop(key_ptr: u32) -> u32;
+
+    fn storage_len(key_ptr: u32) -> u32;
+
+    fn storage_secure_remove(key_ptr: u32) -> u32;
+
+    fn storage_cas(key_ptr: u32, expected_ptr: u32, new_ptr: u32) -> u32;
+
+    fn storage_wipe_prefix(prefix_ptr: u32) -> u32;
+
+    fn db_read_multiple(keys_ptr: u32) -> u32;
+
+    fn storage_map_insert(map_ptr: u32, key_ptr: u32, value_ptr: u32) -> u32;
+
+    fn storage_map_remove(map_ptr: u32, key_ptr: u32) -> u32;
+
+    fn storage_map_get(map_ptr: u32, key_ptr: u32) -> u32;
+
+    fn storage_map_len(map_ptr: u32) -> u32;
+
+    fn storage_map_entry(map_ptr: u32, position: u32) -> u32;
+
+    fn derive_id(seed_ptr: u32) -> u32;
+
+    fn derive_nonce() -> u32;
+
+    fn last_output_hash(key_ptr: u32) -> u32;
+
+    fn verify_eth_personal_sign(message_ptr: u32, signature_ptr: u32, eth_address_ptr: u32) -> u32;
+
+    fn verify_webauthn(
+        auth_data_ptr: u32,
+        client_data_ptr: u32,
+        signature_ptr: u32,
+        public_key_ptr: u32,
+    ) -> u32;
+
+    fn verify_adr036(
+        data_ptr: u32,
+        signature_ptr: u32,
+        pubkey_ptr: u32,
+        signer_addr_ptr: u32,
+    ) -> u32;
+
+    fn verify_legacy_multisig(
+        pubkey_ptr: u32,
+        bitarray_ptr: u32,
+        signatures_ptr: u32,
+        msg_ptr: u32,
+    ) -> u32;
+
+    fn same_session(ciphertext_a_ptr: u32, ciphertext_b_ptr: u32) -> u32;
+
+    fn secp256k1_verify_der(message_hash_ptr: u32, signature_ptr: u32, public_key_ptr: u32) -> u32;
+
+    fn secp256r1_verify(message_hash_ptr: u32, signature_ptr: u32, public_key_ptr: u32) -> u32;
+
+    fn normalize_coins(coins_ptr: u32) -> u64;
+
+    fn require_funds(coins_ptr: u32) -> u32;
+
+    fn remaining_submsg_budget() -> i64;
+
+    fn min_gas_price() -> u32;
+
+    fn tx_msg_position() -> u32;
+
+    fn predict_instantiate_address(code_id: u64) -> u32;
+
+    fn sleep(ms: u64);
+}
+
+/// Encodes a list of byte slices the same way `cosmwasm_std` does for `ed25519_batch_verify`:
+/// each section followed by its length as a big-endian u32.
+fn encode_sections(sections: &[&[u8]]) -> Vec<u8> {
+    let mut out = Vec::new();
+    for section in sections {
+        out.extend_from_slice(section);
+        out.extend_from_slice(&(section.len() as u32).to_be_bytes());
+    }
+    out
+}
+
+/// Decodes sections encoded by `encode_sections`, which the host also uses for its outputs
+fn decode_sections(mut data: &[u8]) -> Vec<Vec<u8>> {
+    let mut sections = Vec::new();
+    while data.len() >= 4 {
+        let (rest, len) = data.split_at(data.len() - 4);
+        let len = u32::from_be_bytes([len[0], len[1], len[2], len[3]]) as usize;
+        let (rest, section) = rest.split_at(rest.len() - len);
+        sections.push(section.to_vec());
+        data = rest;
+    }
+    sections.reverse();
+    sections
+}
+
+fn region_for(data: &[u8]) -> Region {
+    Region {
+        offset: data.as_ptr() as u32,
+        capacity: data.len() as u32,
+        length: data.len() as u32,
+    }
+}
+
+/// Takes ownership of a region that the host allocated and wrote to
+fn consume_region(ptr: u32) -> Vec<u8> {
+    let region = unsafe { Box::from_raw(ptr as *mut Region) };
+    unsafe {
+        Vec::from_raw_parts(
+            region.offset as *mut u8,
+            region.length as usize,
+            region.capacity as usize,
+        )
+    }
+}
+
+pub fn secp256k1_batch_verify_api(
+    message_hashes: &[&[u8]],
+    signatures: &[&[u8]],
+    public_keys: &[&[u8]],
+) -> StdResult<bool> {
+    let message_hashes = encode_sections(message_hashes);
+    let signatures = encode_sections(signatures);
+    let public_keys = encode_sections(public_keys);
+
+    let message_hashes_region = region_for(&message_hashes);
+    let signatures_region = region_for(&signatures);
+    let public_keys_region = region_for(&public_keys);
+
+    let result = unsafe {
+        secp256k1_batch_verify(
+            &message_hashes_region as *const Region as u32,
+            &signatures_region as *const Region as u32,
+            &public_keys_region as *const Region as u32,
+        )
+    };
+
+    match result {
+        0 => Ok(true),
+        1 => Ok(false),
+        code => Err(StdError::generic_err(format!(
+            "secp256k1_batch_verify failed with error code {}",
+            code
+        ))),
+    }
+}
+
+/// Returns whether `aggregate_sig` aggregates a signature over each of `messages` by the public
+/// key at the same index, or over a single message by all of them
+pub fn bls12_381_aggregate_verify_api(
+    public_keys: &[&[u8]],
+    messages: &[&[u8]],
+    aggregate_sig: &[u8],
+) -> StdResult<bool> {
+    let public_keys = encode_sections(public_keys);
+    let messages = encode_sections(messages);
+
+    let public_keys_region = region_for(&public_keys);
+    let messages_region = region_for(&messages);
+    let aggregate_sig_region = region_for(aggregate_sig);
+
+    let result = unsafe {
+        bls12_381_aggregate_verify(
+            &public_keys_region as *const Region as u32,
+            &messages_region as *const Region as u32,
+            &aggregate_sig_region as *const Region as u32,
+        )
+    };
+
+    match result {
+        0 => Ok(true),
+        1 => Ok(false),
+        7 => Err(StdError::generic_err(
+            "bls12_381_aggregate_verify needs a message per public key, or a single message",
+        )),
+        code => Err(StdError::generic_err(format!(
+            "bls12_381_aggregate_verify failed with error code {}",
+            code
+        ))),
+    }
+}
+
+/// Returns whether the DER encoded `signature` is a signature of `message_hash` by `public_key`
+pub fn secp256k1_verify_der_api(
+    message_hash: &[u8],
+    signature: &[u8],
+    public_key: &[u8],
+) -> StdResult<bool> {
+    let message_hash_region = region_for(message_hash);
+    let signature_region = region_for(signature);
+    let public_key_region = region_for(public_key);
+
+    let result = unsafe {
+        secp256k1_verify_der(
+            &message_hash_region as *const Region as u32,
+            &signature_region as *const Region as u32,
+            &public_key_region as *const Region as u32,
+        )
+    };
+
+    match result {
+        0 => Ok(true),
+        1 => Ok(false),
+        code => Err(StdError::generic_err(format!(
+            "secp256k1_verify_der failed with error code {}",
+            code
+        ))),
+    }
+}
+
+/// Returns whether `signature` (r || s) is a secp256r1 signature of `message_hash` by `public_key`
+pub fn secp256r1_verify_api(
+    message_hash: &[u8],
+    signature: &[u8],
+    public_key: &[u8],
+) -> StdResult<bool> {
+    let message_hash_region = region_for(message_hash);
+    let signature_region = region_for(signature);
+    let public_key_region = region_for(public_key);
+
+    let result = unsafe {
+        secp256r1_verify(
+            &message_hash_region as *const Region as u32,
+            &signature_region as *const Region as u32,
+            &public_key_region as *const Region as u32,
+        )
+    };
+
+    match result {
+        0 => Ok(true),
+        1 => Ok(false),
+        code => Err(StdError::generic_err(format!(
+            "secp256r1_verify failed with error code {}",
+            code
+        ))),
+    }
+}
+
+/// Returns whether `signature` is an Ethereum `personal_sign` of `message` by `eth_address`
+pub fn verify_eth_personal_sign_api(
+    message: &[u8],
+    signature: &[u8],
+    eth_address: &[u8],
+) -> StdResult<bool> {
+    let message_region = region_for(message);
+    let signature_region = region_for(signature);
+    let eth_address_region = region_for(eth_address);
+
+    let result = unsafe {
+        verify_eth_personal_sign(
+            &message_region as *const Region as u32,
+            &signature_region as *const Region as u32,
+            &eth_address_region as *const Region as u32,
+        )
+    };
+
+    match result {
+        0 => Ok(true),
+        1 => Ok(false),
+        code => Err(StdError::generic_err(format!(
+            "verify_eth_personal_sign failed with error code {}",
+            code
+        ))),
+    }
+}
+
+/// Returns whether `signature` is a WebAuthn assertion of `authenticator_data` and
+/// `client_data_json` by `public_key`
+pub fn verify_webauthn_api(
+    authenticator_data: &[u8],
+    client_data_json: &[u8],
+    signature: &[u8],
+    public_key: &[u8],
+) -> StdResult<bool> {
+    let authenticator_data_region = region_for(authenticator_data);
+    let client_data_json_region = region_for(client_data_json);
+    let signature_region = region_for(signature);
+    let public_key_region = region_for(public_key);
+
+    let result = unsafe {
+        verify_webauthn(
+            &authenticator_data_region as *const Region as u32,
+            &client_data_json_region as *const Region as u32,
+            &signature_region as *const Region as u32,
+            &public_key_region as *const Region as u32,
+        )
+    };
+
+    match result {
+        0 => Ok(true),
+        1 => Ok(false),
+        code => Err(StdError::generic_err(format!(
+            "verify_webauthn failed with error code {}",
+            code
+        ))),
+    }
+}
+
+/// Returns whether `signature` is an ADR-036 off-chain signature of `data` by `pubkey`, the key
+/// of `signer_addr`
+pub fn verify_adr036_api(
+    data: &[u8],
+    signature: &[u8],
+    pubkey: &[u8],
+    signer_addr: &str,
+) -> StdResult<bool> {
+    let data_region = region_for(data);
+    let signature_region = region_for(signature);
+    let pubkey_region = region_for(pubkey);
+    let signer_addr_region = region_for(signer_addr.as_bytes());
+
+    let result = unsafe {
+        verify_adr036(
+            &data_region as *const Region as u32,
+            &signature_region as *const Region as u32,
+            &pubkey_region as *const Region as u32,
+            &signer_addr_region as *const Region as u32,
+        )
+    };
+
+    match result {
+        0 => Ok(true),
+        1 => Ok(false),
+        code => Err(StdError::generic_err(format!(
+            "verify_adr036 failed with error code {}",
+            code
+        ))),
+    }
+}
+
+/// Returns whether `signatures` are a multisig signature of `msg` by the protobuf encoded
+/// `LegacyAminoPubKey` `pubkey`, for the members set in the `CompactBitArray` `bitarray`
+pub fn verify_legacy_multisig_api(
+    pubkey: &[u8],
+    bitarray: &[u8],
+    signatures: &[u8],
+    msg: &[u8],
+) -> StdResult<bool> {
+    let pubkey_region = region_for(pubkey);
+    let bitarray_region = region_for(bitarray);
+    let signatures_region = region_for(signatures);
+    let msg_region = region_for(msg);
+
+    let result = unsafe {
+        verify_legacy_multisig(
+            &pubkey_region as *const Region as u32,
+            &bitarray_region as *const Region as u32,
+            &signatures_region as *const Region as u32,
+            &msg_region as *const Region as u32,
+        )
+    };
+
+    match result {
+        0 => Ok(true),
+        1 => Ok(false),
+        code => Err(StdError::generic_err(format!(
+            "verify_legacy_multisig failed with error code {}",
+            code
+        ))),
+    }
+}
+
+/// Whether `ciphertext_a` and `ciphertext_b` were encrypted with the same key, i.e. by the same
+/// sender with the same nonce
+pub fn same_session_api(ciphertext_a: &[u8], ciphertext_b: &[u8]) -> StdResult<bool> {
+    let ciphertext_a_region = region_for(ciphertext_a);
+    let ciphertext_b_region = region_for(ciphertext_b);
+
+    let result = unsafe {
+        same_session(
+            &ciphertext_a_region as *const Region as u32,
+            &ciphertext_b_region as *const Region as u32,
+        )
+    };
+
+    match result {
+        0 => Ok(true),
+        1 => Ok(false),
+        code => Err(StdError::generic_err(format!(
+            "same_session failed with error code {}",
+            code
+        ))),
+    }
+}
+
+/// Returns the offending validator's public key if the votes are valid double-sign evidence
+pub fn verify_double_sign_evidence_api(
+    votes: &[&[u8]],
+    signatures: &[&[u8]],
+    public_key: &[u8],
+) -> StdResult<Vec<u8>> {
+    let votes = encode_sections(votes);
+    let signatures = encode_sections(signatures);
+
+    let votes_region = region_for(&votes);
+    let signatures_region = region_for(&signatures);
+    let public_key_region = region_for(public_key);
+
+    let result = unsafe {
+        verify_double_sign_evidence(
+            &votes_region as *const Region as u32,
+            &signatures_region as *const Region as u32,
+            &public_key_region as *const Region as u32,
+        )
+    };
+
+    let error_code = (result >> 32) as u32;
+    let pointer = (result & 0xFFFF_FFFF) as u32;
+    match error_code {
+        0 => Ok(consume_region(pointer)),
+        code => Err(StdError::generic_err(format!(
+            "verify_double_sign_evidence failed with error code {}",
+            code
+        ))),
+    }
+}
+
+/// Returns the token's claims as JSON if it is validly signed by `jwk` and not expired
+pub fn verify_jwt_api(token: &[u8], jwk: &[u8]) -> StdResult<Vec<u8>> {
+    let token_region = region_for(token);
+    let jwk_region = region_for(jwk);
+
+    let result = unsafe {
+        verify_jwt(
+            &token_region as *const Region as u32,
+            &jwk_region as *const Region as u32,
+        )
+    };
+
+    let error_code = (result >> 32) as u32;
+    let pointer = (result & 0xFFFF_FFFF) as u32;
+    match error_code {
+        0 => Ok(consume_region(pointer)),
+        code => Err(StdError::generic_err(format!(
+            "verify_jwt failed with error code {}",
+            code
+        ))),
+    }
+}
+
+/// Verifies an oracle `payload` signed at `signer_time` (in seconds) and returns it, failing
+/// if the signature is bad or the payload is more than `max_age` seconds old
+pub fn verify_signed_payload_api(
+    payload: &[u8],
+    signature: &[u8],
+    pubkey: &[u8],
+    max_age: u64,
+    signer_time: u64,
+) -> StdResult<Vec<u8>> {
+    let payload_region = region_for(payload);
+    let signature_region = region_for(signature);
+    let pubkey_region = region_for(pubkey);
+
+    let result = unsafe {
+        verify_signed_payload(
+            &payload_region as *const Region as u32,
+            &signature_region as *const Region as u32,
+            &pubkey_region as *const Region as u32,
+            max_age,
+            signer_time,
+        )
+    };
+
+    let error_code = (result >> 32) as u32;
+    let pointer = (result & 0xFFFF_FFFF) as u32;
+    match error_code {
+        0 => Ok(consume_region(pointer)),
+        code => Err(StdError::generic_err(format!(
+            "verify_signed_payload failed with error code {}",
+            code
+        ))),
+    }
+}
+
+/// Merges coins of the same denom, drops zero amounts and sorts by denom, as the SDK expects
+pub fn normalize_coins_api(coins: &[Coin]) -> StdResult<Vec<Coin>> {
+    let coins = to_vec(coins)?;
+    let coins_region = region_for(&coins);
+
+    let result = unsafe { normalize_coins(&coins_region as *const Region as u32) };
+
+    let error_code = (result >> 32) as u32;
+    let pointer = (result & 0xFFFF_FFFF) as u32;
+    match error_code {
+        0 => from_slice(&consume_region(pointer)),
+        code => Err(StdError::generic_err(format!(
+            "normalize_coins failed with error code {}",
+            code
+        ))),
+    }
+}
+
+/// Aborts the call unless it was sent exactly `funds`
+pub fn require_funds_api(funds: &[Coin]) -> StdResult<()> {
+    let funds = to_vec(funds)?;
+    let funds_region = region_for(&funds);
+
+    match unsafe { require_funds(&funds_region as *const Region as u32) } {
+        0 => Ok(()),
+        code => Err(StdError::generic_err(format!(
+            "require_funds failed with error code {}",
+            code
+        ))),
+    }
+}
+
+/// Unpacks the big-endian u64 that the fixed-point host functions return
+fn consume_fixed_point_result(operation: &str, result: u64) -> StdResult<u64> {
+    let error_code = (result >> 32) as u32;
+    let pointer = (result & 0xFFFF_FFFF) as u32;
+    match error_code {
+        0 => {
+            let bytes = consume_region(pointer);
+            let mut value = [0u8; 8];
+            value.copy_from_slice(&bytes);
+            Ok(u64::from_be_bytes(value))
+        }
+        code => Err(StdError::generic_err(format!(
+            "{} failed with error code {}",
+            operation, code
+        ))),
+    }
+}
+
+/// Returns `a * b / c`, rounded down
+pub fn checked_mul_div_api(a: u64, b: u64, c: u64) -> StdResult<u64> {
+    let result = unsafe { checked_mul_div(a, b, c) };
+    consume_fixed_point_result("checked_mul_div", result)
+}
+
+/// Returns the square root of `x` with `decimal_places` decimal places, rounded down
+pub fn sqrt_api(x: u64, decimal_places: u32) -> StdResult<u64> {
+    let result = unsafe { sqrt(x, decimal_places) };
+    consume_fixed_point_result("sqrt", result)
+}
+
+/// Returns all the coins held by the contract, including funds sent with the current message
+pub fn contract_all_balances_api() -> StdResult<Vec<Coin>> {
+    let balances = consume_region(unsafe { contract_all_balances() });
+    from_slice(&balances)
+}
+
+/// The block the enclave believes it's executing against
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct EnclaveBlockView {
+    pub height: u64,
+    pub time: Timestamp,
+}
+
+/// Returns the block height and time the enclave is executing against
+pub fn enclave_block_view_api() -> StdResult<EnclaveBlockView> {
+    let block_view = consume_region(unsafe { enclave_block_view() });
+    from_slice(&block_view)
+}
+
+/// A decimal amount of a denom, like the chain's `DecCoin`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct DecCoin {
+    pub denom: String,
+    pub amount: String,
+}
+
+/// Returns the chain's min gas price for the current transaction, empty outside of transactions
+pub fn min_gas_price_api() -> StdResult<Vec<DecCoin>> {
+    let min_gas_price = consume_region(unsafe { min_gas_price() });
+    from_slice(&min_gas_price)
+}
+
+/// The position of the current message among its transaction's messages
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TxMsgPosition {
+    pub msg_index: u32,
+    pub msg_count: u32,
+}
+
+/// Returns the position of the current message in its transaction, `None` when the contract
+/// wasn't called by one of the transaction's messages
+pub fn tx_msg_position_api() -> StdResult<Option<TxMsgPosition>> {
+    match unsafe { tx_msg_position() } {
+        0 => Ok(None),
+        pointer => from_slice(&consume_region(pointer)).map(Some),
+    }
+}
+
+/// Returns the address the next contract of `code_id` this contract instantiates will get, as long
+/// as nothing else instantiates a contract before it. `None` if the chain didn't pass what it
+/// needs.
+pub fn predict_instantiate_address_api(code_id: u64) -> Option<CanonicalAddr> {
+    match unsafe { predict_instantiate_address(code_id) } {
+        0 => None,
+        pointer => Some(CanonicalAddr::from(consume_region(pointer))),
+    }
+}
+
+/// Returns a deterministic 16 byte id derived from `seed`, unique within the current call
+pub fn derive_id_api(seed: &[u8]) -> Vec<u8> {
+    let seed_region = region_for(seed);
+    consume_region(unsafe { derive_id(&seed_region as *const Region as u32) })
+}
+
+/// Returns a deterministic 32 byte nonce, unique within the block, for encrypting data. `None` in
+/// queries.
+pub fn derive_nonce_api() -> Option<Vec<u8>> {
+    match unsafe { derive_nonce() } {
+        0 => None,
+        pointer => Some(consume_region(pointer)),
+    }
+}
+
+/// Returns the hash of the output of the last execution that looked up `key`, if it's still
+/// logged. Looking `key` up records this execution's output under it.
+pub fn last_output_hash_api(key: &[u8]) -> Option<Vec<u8>> {
+    let key_region = region_for(key);
+
+    match unsafe { last_output_hash(&key_region as *const Region as u32) } {
+        0 => None,
+        pointer => Some(consume_region(pointer)),
+    }
+}
+
+/// Returns how many submessages the response may contain, not counting the ones already queued,
+/// or `None` if the chain doesn't limit them
+pub fn remaining_submsg_budget_api() -> Option<u32> {
+    match unsafe { remaining_submsg_budget() } {
+        -1 => None,
+        remaining => Some(remaining as u32),
+    }
+}
+
+/// Appends `value` to the list stored under `key` and returns the new length
+pub fn storage_push_api(key: &[u8], value: &[u8]) -> u32 {
+    let key_region = region_for(key);
+    let value_region = region_for(value);
+
+    unsafe {
+        storage_push(
+            &key_region as *const Region as u32,
+            &value_region as *const Region as u32,
+        )
+    }
+}
+
+/// Removes and returns the last item of the list stored under `key`
+pub fn storage_pop_api(key: &[u8]) -> Option<Vec<u8>> {
+    let key_region = region_for(key);
+
+    match unsafe { storage_pop(&key_region as *const Region as u32) } {
+        0 => None,
+        pointer => Some(consume_region(pointer)),
+    }
+}
+
+/// Returns the number of items in the list stored under `key`
+pub fn storage_len_api(key: &[u8]) -> u32 {
+    let key_region = region_for(key);
+
+    unsafe { storage_len(&key_region as *const Region as u32) }
+}
+
+/// Removes `key` and returns whether the key is confirmed to no longer exist
+pub fn storage_secure_remove_api(key: &[u8]) -> bool {
+    let key_region = region_for(key);
+
+    unsafe { storage_secure_remove(&key_region as *const Region as u32) == 1 }
+}
+
+/// Writes `new` under `key` if its value is `expected`, or if it doesn't exist when `expected` is
+/// `None`, and returns whether it did
+pub fn storage_cas_api(key: &[u8], expected: Option<&[u8]>, new: &[u8]) -> bool {
+    let key_region = region_for(key);
+    let expected_region = expected.map(region_for);
+    let new_region = region_for(new);
+
+    let expected_ptr = match &expected_region {
+        Some(region) => region as *const Region as u32,
+        None => 0,
+    };
+
+    unsafe {
+        storage_cas(
+            &key_region as *const Region as u32,
+            expected_ptr,
+            &new_region as *const Region as u32,
+        ) == 1
+    }
+}
+
+/// Removes every key that starts with `prefix` and returns how many there were
+pub fn storage_wipe_prefix_api(prefix: &[u8]) -> u32 {
+    let prefix_region = region_for(prefix);
+
+    unsafe { storage_wipe_prefix(&prefix_region as *const Region as u32) }
+}
+
+/// Reads all of `keys` at once and returns their values in the same order, `None` for a missing
+/// key
+pub fn db_read_multiple_api(keys: &[&[u8]]) -> Vec<Option<Vec<u8>>> {
+    let keys = encode_sections(keys);
+    let keys_region = region_for(&keys);
+
+    let pointer = unsafe { db_read_multiple(&keys_region as *const Region as u32) };
+
+    // an existing value comes after a 1 byte, while a missing one is an empty section
+    decode_sections(&consume_region(pointer))
+        .iter()
+        .map(|section| section.split_first().map(|(_, value)| value.to_vec()))
+        .collect()
+}
+
+/// Sets the value for `key` in the ordered map `map` and returns whether the key is new
+pub fn storage_map_insert_api(map: &[u8], key: &[u8], value: &[u8]) -> bool {
+    let map_region = region_for(map);
+    let key_region = region_for(key);
+    let value_region = region_for(value);
+
+    unsafe {
+        storage_map_insert(
+            &map_region as *const Region as u32,
+            &key_region as *const Region as u32,
+            &value_region as *const Region as u32,
+        ) == 1
+    }
+}
+
+/// Removes `key` from the ordered map `map` and returns its value
+pub fn storage_map_remove_api(map: &[u8], key: &[u8]) -> Option<Vec<u8>> {
+    let map_region = region_for(map);
+    let key_region = region_for(key);
+
+    match unsafe {
+        storage_map_remove(
+            &map_region as *const Region as u32,
+            &key_region as *const Region as u32,
+        )
+    } {
+        0 => None,
+        pointer => Some(consume_region(pointer)),
+    }
+}
+
+/// Returns the value for `key` in the ordered map `map`
+pub fn storage_map_get_api(map: &[u8], key: &[u8]) -> Option<Vec<u8>> {
+    let map_region = region_for(map);
+    let key_region = region_for(key);
+
+    match unsafe {
+        storage_map_get(
+            &map_region as *const Region as u32,
+            &key_region as *const Region as u32,
+        )
+    } {
+        0 => None,
+        pointer => Some(consume_region(pointer)),
+    }
+}
+
+/// Returns the number of entries in the ordered map `map`
+pub fn storage_map_len_api(map: &[u8]) -> u32 {
+    let map_region = region_for(map);
+
+    unsafe { storage_map_len(&map_region as *const Region as u32) }
+}
+
+/// Returns the entries of the ordered map `map`, in its iteration order
+pub fn storage_map_entries_api(map: &[u8]) -> Vec<(Vec<u8>, Vec<u8>)> {
+    let map_region = region_for(map);
+
+    let mut entries = Vec::new();
+    for position in 0.. {
+        let pointer = unsafe { storage_map_entry(&map_region as *const Region as u32, position) };
+        if pointer == 0 {
+            break;
+        }
+
+        let mut entry = decode_sections(&consume_region(pointer));
+        let value = entry.pop().unwrap_or_default();
+        let key = entry.pop().unwrap_or_default();
+        entries.push((key, value));
+    }
+    entries
+}
+
+/// Sleeps for `ms` milliseconds. The enclave doesn't let contracts sleep and traps instead, so
+/// this only sleeps in unit tests.
+#[cfg(target_arch = "wasm32")]
+pub fn sleep_api(ms: u64) {
+    unsafe { sleep(ms) }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn sleep_api(ms: u64) {
+    std::thread::sleep(std::time::Duration::from_millis(ms));
+}
@@ -95,6 +95,30 @@ pub enum InstantiateMsg {
         amount: Vec<Coin>,
         to: String,
     },
+    NormalizedBankMsgSend {
+        amount: Vec<Coin>,
+        to: String,
+    },
+    /// Fails unless it's sent exactly `funds`
+    RequireFunds {
+        funds: Vec<Coin>,
+    },
+    /// Sends the sender 1denom per message, in as many messages as the budget allows plus `extra`
+    FillSubmsgBudget {
+        extra: u32,
+    },
+    /// Emits a custom event with a type of `length` bytes
+    EmitEventType {
+        length: u32,
+    },
+    MinGasPrice {},
+    TxMsgPosition {},
+    PredictInstantiateAddress {
+        code_id: u64,
+    },
+    DeriveNonces {
+        count: u32,
+    },
     BankMsgBurn {
         amount: Vec<Coin>,
     },
@@ -111,6 +135,10 @@ pub enum InstantiateMsg {
     },
     GetEnv {},
     TestRemoveDb {},
+    ProcessOnce {
+        request_id: String,
+    },
+    GasMeter {},
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
@@ -156,11 +184,20 @@ pub enum ExecuteMsg {
     },
     GasMeter {},
     GasMeterProxy {},
+    OutOfGasInSubmsg {},
+    OutOfGasInInitSubmsg {
+        code_id: u64,
+        code_hash: String,
+    },
     TransferMoney {
         amount: u64,
     },
     RecursiveReply {},
     RecursiveReplyFail {},
+    NestedRecursiveReply {
+        depth: u32,
+        gas_limit: Option<u64>,
+    },
     InitNewContract {},
     InitNewContractWithError {},
     SubMsgLoop {
@@ -275,6 +312,9 @@ pub enum ExecuteMsg {
     GetState {
         key: String,
     },
+    GetStates {
+        keys: Vec<String>,
+    },
     RemoveState {
         key: String,
     },
@@ -343,6 +383,9 @@ pub enum ExecuteMsg {
     ValidateAddress {
         addr: String,
     },
+    HumanizeAddress {
+        canonical: Binary,
+    },
     Sleep {
         ms: u64,
     },
@@ -392,6 +435,12 @@ pub enum ExecuteMsg {
         msg_hash: Binary,
         iterations: u32,
     },
+    Secp256r1Verify {
+        pubkey: Binary,
+        sig: Binary,
+        msg_hash: Binary,
+        iterations: u32,
+    },
     Ed25519Verify {
         pubkey: Binary,
         sig: Binary,
@@ -404,6 +453,104 @@ pub enum ExecuteMsg {
         msgs: Vec<Binary>,
         iterations: u32,
     },
+    Bls12381AggregateVerify {
+        pubkeys: Vec<Binary>,
+        msgs: Vec<Binary>,
+        agg_sig: Binary,
+        iterations: u32,
+    },
+    Secp256k1BatchVerify {
+        pubkeys: Vec<Binary>,
+        sigs: Vec<Binary>,
+        msg_hashes: Vec<Binary>,
+        iterations: u32,
+    },
+    VerifyDoubleSignEvidence {
+        votes: Vec<Binary>,
+        sigs: Vec<Binary>,
+        pubkey: Binary,
+    },
+    VerifyJwt {
+        token: String,
+        jwk: String,
+    },
+    VerifySignedPayload {
+        payload: String,
+        signature: Binary,
+        pubkey: Binary,
+        max_age: u64,
+        signer_time: u64,
+    },
+    VerifyEthPersonalSign {
+        message: String,
+        signature: Binary,
+        eth_address: Binary,
+    },
+    VerifyWebauthn {
+        authenticator_data: Binary,
+        client_data_json: String,
+        signature: Binary,
+        public_key: Binary,
+    },
+    VerifyAdr036 {
+        data: Binary,
+        signature: Binary,
+        pubkey: Binary,
+        signer_addr: String,
+    },
+    SameSession {
+        ciphertext_a: Binary,
+        ciphertext_b: Binary,
+    },
+    Secp256k1VerifyDer {
+        message_hash: Binary,
+        signature: Binary,
+        public_key: Binary,
+    },
+    CheckedMulDiv {
+        a: u64,
+        b: u64,
+        c: u64,
+    },
+    Sqrt {
+        x: u64,
+        decimal_places: u32,
+    },
+    ContractAllBalances {},
+    StoragePush {
+        key: String,
+        values: Vec<String>,
+    },
+    StoragePop {
+        key: String,
+        count: u32,
+    },
+    StorageSecureRemove {
+        key: String,
+    },
+    StorageCas {
+        key: String,
+        expected: Option<String>,
+        new: String,
+    },
+    StorageWipePrefix {
+        prefix: String,
+    },
+    StorageMapInsert {
+        map: String,
+        key: String,
+        value: String,
+    },
+    StorageMapRemove {
+        map: String,
+        key: String,
+    },
+    StorageMapEntries {
+        map: String,
+    },
+    ProcessOnce {
+        request_id: String,
+    },
     Secp256k1RecoverPubkey {
         msg_hash: Binary,
         sig: Binary,
@@ -458,6 +605,11 @@ pub enum ExecuteMsg {
     Echo {
         data: Binary,
     },
+    SendEchoWithReply {
+        to: String,
+        code_hash: String,
+        data: Binary,
+    },
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -493,6 +645,14 @@ pub enum SudoMsg {
 #[serde(rename_all = "snake_case")]
 pub enum QueryMsg {
     Get {},
+    StorageLen {
+        key: String,
+    },
+    EnclaveBlockView {},
+    DeriveIds {
+        seed: String,
+        count: u8,
+    },
     // These were ported from the v0.10 test-contract:
     ContractError {
         error_type: String,
@@ -527,6 +687,12 @@ pub enum QueryMsg {
     },
     GetContractVersion {},
     GetEnv {},
+    VerifyMultisig {
+        pubkey: Binary,
+        bitarray: Binary,
+        signatures: Binary,
+        msg: Binary,
+    },
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -45,3 +45,30 @@ pub unsafe extern "C" fn ecall_submit_block_signatures(
         sgx_status_t::SGX_ERROR_ECALL_NOT_ALLOWED
     }
 }
+
+/// # Safety
+///  This function reads buffers which must be correctly initialized by the caller,
+/// see safety section of slice::[from_raw_parts](https://doc.rust-lang.org/std/slice/fn.from_raw_parts.html#safety)
+///
+#[no_mangle]
+#[allow(unused_variables)]
+pub unsafe extern "C" fn ecall_verify_header_chain(
+    in_chain: *const u8,
+    in_chain_len: u32,
+    trusted_height: &mut u64,
+) -> sgx_status_t {
+    #[cfg(feature = "light-client-validation")]
+    {
+        block_verifier::verify_header_chain::verify_header_chain_impl(
+            in_chain,
+            in_chain_len,
+            trusted_height,
+        )
+    }
+
+    // unlike submitting a block, there's no noop here: a caller would take the chain as verified
+    #[cfg(not(feature = "light-client-validation"))]
+    {
+        sgx_status_t::SGX_ERROR_ECALL_NOT_ALLOWED
+    }
+}
@@ -4,7 +4,6 @@ use core::mem;
 use enclave_crypto::KeyPair;
 use std::vec::Vec;
 
-#[cfg(feature = "SGX_MODE_HW")]
 use log::*;
 
 #[cfg(feature = "SGX_MODE_HW")]
@@ -67,10 +66,14 @@ use std::sgxfs::remove as SgxFsRemove;
 
 #[cfg(feature = "SGX_MODE_HW")]
 use super::ocalls::{
-    ocall_get_ias_socket, ocall_get_quote, ocall_get_quote_ecdsa, ocall_get_quote_ecdsa_collateral,
-    ocall_get_quote_ecdsa_params, ocall_sgx_init_quote, ocall_verify_quote_ecdsa,
+    ocall_get_ias_socket, ocall_get_quote, ocall_get_quote_ecdsa, ocall_get_quote_ecdsa_params,
+    ocall_sgx_init_quote, ocall_verify_quote_ecdsa,
 };
 
+#[cfg(feature = "SGX_MODE_HW")]
+use super::collateral::fetch_collateral;
+use super::collateral::Collateral;
+
 #[cfg(feature = "SGX_MODE_HW")]
 use super::{hex, report::EndorsedAttestationReport};
 
@@ -280,21 +283,45 @@ pub fn get_mr_enclave() -> [u8; 32] {
     rsgx_self_report().body.mr_enclave.m
 }
 
-#[cfg(not(feature = "SGX_MODE_HW"))]
+/// Verifies an ECDSA quote with its collateral, and accepts it if its TCB is up to date or only
+/// needs SW hardening
 pub fn verify_quote_ecdsa(
+    vec_quote: &[u8],
+    collateral: &Collateral,
+    time_s: i64,
+) -> Result<(sgx_report_body_t, sgx_ql_qv_result_t), sgx_status_t> {
+    let (report_body, qv_result) = verify_quote_ecdsa_status(vec_quote, collateral, time_s)?;
+
+    match qv_result {
+        sgx_ql_qv_result_t::SGX_QL_QV_RESULT_OK => {}
+        sgx_ql_qv_result_t::SGX_QL_QV_RESULT_SW_HARDENING_NEEDED => {}
+        _ => {
+            trace!("Quote verification result: {}", qv_result);
+            return Err(sgx_status_t::SGX_ERROR_UNEXPECTED);
+        }
+    };
+
+    Ok((report_body, qv_result))
+}
+
+#[cfg(not(feature = "SGX_MODE_HW"))]
+pub fn verify_quote_ecdsa_status(
     _vec_quote: &[u8],
-    _vec_coll: &[u8],
+    _collateral: &Collateral,
     _time_s: i64,
 ) -> Result<(sgx_report_body_t, sgx_ql_qv_result_t), sgx_status_t> {
     Err(sgx_status_t::SGX_ERROR_NO_DEVICE)
 }
 
+/// Like `verify_quote_ecdsa`, but returns the quote verification result of a genuine quote
+/// whatever its TCB status is, for the caller to decide on
 #[cfg(feature = "SGX_MODE_HW")]
-pub fn verify_quote_ecdsa(
+pub fn verify_quote_ecdsa_status(
     vec_quote: &[u8],
-    vec_coll: &[u8],
+    collateral: &Collateral,
     time_s: i64,
 ) -> Result<(sgx_report_body_t, sgx_ql_qv_result_t), sgx_status_t> {
+    let vec_coll = collateral.to_bytes();
     //
     // use sgx_types::sgx_ql_qv_supplemental_t;
 
@@ -335,15 +362,6 @@ pub fn verify_quote_ecdsa(
         return Err(rt);
     }
 
-    match qv_result {
-        sgx_ql_qv_result_t::SGX_QL_QV_RESULT_OK => {}
-        sgx_ql_qv_result_t::SGX_QL_QV_RESULT_SW_HARDENING_NEEDED => {}
-        _ => {
-            trace!("Quote verification result: {}", qv_result);
-            return Err(sgx_status_t::SGX_ERROR_UNEXPECTED);
-        }
-    };
-
     // verify the qve report
     if time_s != 0 {
         exp_time_s = time_s; // insist on our time, if supplied
@@ -417,7 +435,7 @@ pub fn verify_quote_ecdsa(
 }
 
 #[cfg(feature = "SGX_MODE_HW")]
-fn test_sgx_call_res(
+pub(super) fn test_sgx_call_res(
     res: sgx_status_t,
     retval: sgx_status_t,
 ) -> Result<sgx_status_t, sgx_status_t> {
@@ -433,12 +451,12 @@ fn test_sgx_call_res(
 }
 
 #[cfg(not(feature = "SGX_MODE_HW"))]
-pub fn get_quote_ecdsa(_pub_k: &[u8; 32]) -> Result<(Vec<u8>, Vec<u8>), sgx_status_t> {
+pub fn get_quote_ecdsa(_pub_k: &[u8; 32]) -> Result<(Vec<u8>, Collateral), sgx_status_t> {
     Err(sgx_status_t::SGX_ERROR_NO_DEVICE)
 }
 
 #[cfg(feature = "SGX_MODE_HW")]
-pub fn get_quote_ecdsa_untested(pub_k: &[u8; 32]) -> Result<(Vec<u8>, Vec<u8>), sgx_status_t> {
+pub fn get_quote_ecdsa_untested(pub_k: &[u8; 32]) -> Result<(Vec<u8>, Collateral), sgx_status_t> {
     let mut qe_target_info = sgx_target_info_t::default();
     let mut quote_size: u32 = 0;
     let mut rt: sgx_status_t = sgx_status_t::default();
@@ -485,57 +503,23 @@ pub fn get_quote_ecdsa_untested(pub_k: &[u8; 32]) -> Result<(Vec<u8>, Vec<u8>),
         return Err(e);
     }
 
-    let mut vec_coll: Vec<u8> = vec![0; 0x4000];
-    let mut size_coll: u32 = 0;
-
-    res = unsafe {
-        ocall_get_quote_ecdsa_collateral(
-            &mut rt as *mut sgx_status_t,
-            vec_quote.as_ptr(),
-            vec_quote.len() as u32,
-            vec_coll.as_mut_ptr(),
-            vec_coll.len() as u32,
-            &mut size_coll,
-        )
-    };
-
-    if let Err(e) = test_sgx_call_res(res, rt) {
-        trace!("ocall_get_quote_ecdsa_collateral err = {}", e);
-        return Err(e);
-    }
-
-    trace!("Collateral size = {}", size_coll);
-
-    let call_again = size_coll > vec_coll.len() as u32;
-    vec_coll.resize(size_coll as usize, 0);
-
-    if call_again {
-        res = unsafe {
-            ocall_get_quote_ecdsa_collateral(
-                &mut rt as *mut sgx_status_t,
-                vec_quote.as_ptr(),
-                vec_quote.len() as u32,
-                vec_coll.as_mut_ptr(),
-                vec_coll.len() as u32,
-                &mut size_coll,
-            )
-        };
-
-        if let Err(e) = test_sgx_call_res(res, rt) {
-            trace!("ocall_get_quote_ecdsa_collateral again err = {}", e);
+    let collateral = match fetch_collateral(&vec_quote) {
+        Ok(collateral) => collateral,
+        Err(e) => {
+            trace!("fetch_collateral err = {}", e);
             return Err(e);
         }
-    }
+    };
 
-    Ok((vec_quote, vec_coll))
+    Ok((vec_quote, collateral))
 }
 
 #[cfg(feature = "SGX_MODE_HW")]
-pub fn get_quote_ecdsa(pub_k: &[u8; 32]) -> Result<(Vec<u8>, Vec<u8>), sgx_status_t> {
-    let (vec_quote, vec_coll) = get_quote_ecdsa_untested(pub_k)?;
+pub fn get_quote_ecdsa(pub_k: &[u8; 32]) -> Result<(Vec<u8>, Collateral), sgx_status_t> {
+    let (vec_quote, collateral) = get_quote_ecdsa_untested(pub_k)?;
 
     // test self
-    match verify_quote_ecdsa(&vec_quote, &vec_coll, 0) {
+    match verify_quote_ecdsa(&vec_quote, &collateral, 0) {
         Ok(r) => {
             trace!("Self quote verified ok");
             if r.1 != sgx_ql_qv_result_t::SGX_QL_QV_RESULT_OK {
@@ -549,7 +533,7 @@ pub fn get_quote_ecdsa(pub_k: &[u8; 32]) -> Result<(Vec<u8>, Vec<u8>), sgx_statu
         }
     };
 
-    Ok((vec_quote, vec_coll))
+    Ok((vec_quote, collateral))
 }
 
 //input: pub_k: &sgx_ec256_public_t, todo: make this the pubkey of the node
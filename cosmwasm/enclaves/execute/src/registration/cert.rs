@@ -23,8 +23,12 @@ use enclave_ffi_types::NodeAuthResult;
 use crate::registration::report::AdvisoryIDs;
 
 use super::attestation::get_mr_enclave;
+use super::measurements::Measurements;
+use super::report::SgxQuoteStatus;
 #[cfg(feature = "SGX_MODE_HW")]
-use super::report::{AttestationReport, SgxQuoteStatus};
+use super::report::{current_verification_time_s, AttestationReport};
+#[cfg(feature = "SGX_MODE_HW")]
+use super::report_cache::{verify_cached, VerifiedReport};
 
 extern "C" {
     pub fn ocall_get_update_info(
@@ -268,17 +272,47 @@ pub fn get_ias_auth_config() -> (Vec<u8>, rustls::RootCertStore) {
     (ias_cert_dec, root_store)
 }
 
-#[cfg(not(feature = "SGX_MODE_HW"))]
+/// Verifies a remote attestation cert, and returns the node key it attests to. Reports this node
+/// verified recently are taken from the report cache, so the result can differ between nodes and
+/// must not decide consensus, use `verify_ra_cert_measured` there.
 pub fn verify_ra_cert(
+    cert_der: &[u8],
+    override_verify_type: Option<SigningMethod>,
+    check_tcb_version: bool,
+) -> Result<Vec<u8>, NodeAuthResult> {
+    // the master certificate is only verified at startup, so it isn't worth caching
+    #[cfg(feature = "SGX_MODE_HW")]
+    {
+        if override_verify_type.is_none() {
+            let payload =
+                get_netscape_comment(cert_der).map_err(|_| NodeAuthResult::InvalidCert)?;
+            return verify_cached(
+                &payload,
+                check_tcb_version,
+                current_verification_time_s(),
+                || verify_ra_cert_uncached(cert_der, None, check_tcb_version),
+            )
+            .map(|report| report.public_key);
+        }
+    }
+
+    verify_ra_cert_measured(cert_der, override_verify_type, check_tcb_version)
+        .map(|(public_key, _, _)| public_key)
+}
+
+/// Software mode certificates only hold the node key, so the measurements of the enclave are
+/// unknown, and there's no platform to downgrade
+#[cfg(not(feature = "SGX_MODE_HW"))]
+pub fn verify_ra_cert_measured(
     cert_der: &[u8],
     override_verify: Option<SigningMethod>,
     _check_tcb_version: bool,
-) -> Result<Vec<u8>, NodeAuthResult> {
+) -> Result<(Vec<u8>, Option<Measurements>, Option<DowngradeReason>), NodeAuthResult> {
     let payload = get_netscape_comment(cert_der).map_err(|_err| NodeAuthResult::InvalidCert)?;
 
     let pk = base64::decode(&payload).map_err(|_err| NodeAuthResult::InvalidCert)?;
 
-    Ok(pk)
+    Ok((pk, None, None))
 }
 
 pub fn verify_ra_report(
@@ -338,19 +372,47 @@ pub fn verify_ra_report(
 /// 4. Extract public key from report body
 /// 5. Verify enclave signature (mr enclave/signer)
 ///
+/// Also returns the measurements of the attested enclave, and why a vulnerable platform was
+/// admitted, if it was. The report is always verified, never taken from the report cache: whether
+/// a report is cached depends on what this node verified before, so consensus paths like
+/// `ecall_authenticate_new_node` have to use this.
 #[cfg(feature = "SGX_MODE_HW")]
-pub fn verify_ra_cert(
+pub fn verify_ra_cert_measured(
     cert_der: &[u8],
     override_verify_type: Option<SigningMethod>,
     check_tcb_version: bool,
-) -> Result<Vec<u8>, NodeAuthResult> {
-    let report = AttestationReport::from_cert(cert_der).map_err(|_| NodeAuthResult::InvalidCert)?;
+) -> Result<(Vec<u8>, Option<Measurements>, Option<DowngradeReason>), NodeAuthResult> {
+    let report = verify_ra_cert_uncached(cert_der, override_verify_type, check_tcb_version)?;
+
+    Ok((
+        report.public_key,
+        Some(report.measurements),
+        report.downgrade_reason,
+    ))
+}
+
+/// Verifies a remote attestation cert, without the report cache
+#[cfg(feature = "SGX_MODE_HW")]
+fn verify_ra_cert_uncached(
+    cert_der: &[u8],
+    override_verify_type: Option<SigningMethod>,
+    check_tcb_version: bool,
+) -> Result<VerifiedReport, NodeAuthResult> {
+    let report =
+        AttestationReport::from_cert(cert_der, None).map_err(|_| NodeAuthResult::InvalidCert)?;
 
     // this is a small hack - override_verify_type is only used when verifying the master certificate
     // and in that case we don't care about checking vulns etc. Master certificate will also have
     // a bad GID in prod, so there's no reason to verify it
+    let mut downgrade_reason = None;
     if override_verify_type.is_none() {
-        verify_quote_status(&report, &report.advisory_ids)?;
+        // every node has to reach the same verdict on a peer, so only the enclave's own whitelist
+        // applies here, never advisories accepted by the local operator
+        let (_, reason) = verify_quote_status(&report, &report.advisory_ids, None)?;
+        if let Some(reason) = &reason {
+            warn!("Admitting a node with a vulnerable platform: {:?}", reason);
+        }
+        downgrade_reason = reason;
     }
 
     let res = verify_ra_report(
@@ -370,8 +432,16 @@ pub fn verify_ra_cert(
         }
     }
 
-    let report_public_key = report.sgx_quote_body.isv_enclave_report.report_data[0..32].to_vec();
-    Ok(report_public_key)
+    let enclave_report = &report.sgx_quote_body.isv_enclave_report;
+    Ok(VerifiedReport {
+        public_key: enclave_report.report_data[0..32].to_vec(),
+        measurements: Measurements {
+            mr_enclave: enclave_report.mr_enclave,
+            mr_signer: enclave_report.mr_signer,
+        },
+        quote_status: report.sgx_quote_status,
+        downgrade_reason,
+    })
 }
 
 // fn transform_u32_to_array_of_u8(x: u32) -> [u8; 4] {
@@ -382,11 +452,77 @@ pub fn verify_ra_cert(
 //     return [b1, b2, b3, b4];
 // }
 
+/// The lowest ISV SVN of an enclave that is admitted, which is the one production enclaves are
+/// signed with in Enclave.config.prod.xml. Raising it together with the ISVSVN there keeps
+/// releases that predate a security fix out of the network.
+pub const MIN_ISV_SVN: u16 = 3;
+
+/// Why `verify_quote_status` admitted a node with a vulnerable platform. This only happens on
+/// non-production builds, which are more lenient so that joining a testnet is easier.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum DowngradeReason {
+    /// The platform is missing a security update, which testnets tolerate
+    GroupOutOfDate,
+    /// The quote status is tolerated, but the platform is affected by advisories that aren't
+    /// whitelisted
+    UnmitigatedAdvisories {
+        status: SgxQuoteStatus,
+        advisories: Vec<String>,
+    },
+    /// The enclave's security version is below `MIN_ISV_SVN`
+    StaleSvn { isv_svn: u16, min_isv_svn: u16 },
+}
+
+/// The code a registering node gets for a downgrade, since the advisories can't cross the ecall
+impl From<&DowngradeReason> for NodeAuthResult {
+    fn from(reason: &DowngradeReason) -> Self {
+        match reason {
+            DowngradeReason::GroupOutOfDate => NodeAuthResult::GroupOutOfDate,
+            DowngradeReason::UnmitigatedAdvisories { .. } => NodeAuthResult::UnmitigatedAdvisories,
+            DowngradeReason::StaleSvn { .. } => NodeAuthResult::StaleSvn,
+        }
+    }
+}
+
+/// The reason a platform with a tolerated quote `status` and the non-whitelisted `advisories` is
+/// vulnerable, if it is
+pub fn downgrade_reason(
+    status: &SgxQuoteStatus,
+    advisories: Vec<String>,
+) -> Option<DowngradeReason> {
+    if !advisories.is_empty() {
+        Some(DowngradeReason::UnmitigatedAdvisories {
+            status: status.clone(),
+            advisories,
+        })
+    } else if status == &SgxQuoteStatus::GroupOutOfDate {
+        Some(DowngradeReason::GroupOutOfDate)
+    } else {
+        None
+    }
+}
+
+/// The reason an enclave with the security version `isv_svn` is stale, if it is below
+/// `min_isv_svn`
+pub fn stale_svn_reason(isv_svn: u16, min_isv_svn: u16) -> Option<DowngradeReason> {
+    if isv_svn < min_isv_svn {
+        Some(DowngradeReason::StaleSvn {
+            isv_svn,
+            min_isv_svn,
+        })
+    } else {
+        None
+    }
+}
+
+/// Accepts a report whose quote status is tolerated, whose advisories are all whitelisted, either
+/// by the enclave or in `extra_advisories`, and whose enclave isn't older than `MIN_ISV_SVN`
 #[cfg(all(feature = "SGX_MODE_HW", feature = "production"))]
 pub fn verify_quote_status(
     report: &AttestationReport,
     advisories: &AdvisoryIDs,
-) -> Result<NodeAuthResult, NodeAuthResult> {
+    extra_advisories: Option<&AdvisoryIDs>,
+) -> Result<(NodeAuthResult, Option<DowngradeReason>), NodeAuthResult> {
     // info!(
     //     "Got GID: {:?}",
     //     transform_u32_to_array_of_u8(report.sgx_quote_body.gid)
@@ -400,13 +536,22 @@ pub fn verify_quote_status(
         return Err(NodeAuthResult::BadQuoteStatus);
     }
 
+    let isv_svn = report.sgx_quote_body.isv_enclave_report.isv_svn;
+    if stale_svn_reason(isv_svn, MIN_ISV_SVN).is_some() {
+        error!(
+            "Enclave security version {} is below the minimum {}",
+            isv_svn, MIN_ISV_SVN
+        );
+        return Err(NodeAuthResult::StaleSvn);
+    }
+
     match &report.sgx_quote_status {
         SgxQuoteStatus::OK
         | SgxQuoteStatus::SwHardeningNeeded
         | SgxQuoteStatus::ConfigurationAndSwHardeningNeeded => {
-            check_advisories(&report.sgx_quote_status, advisories)?;
+            check_advisories(&report.sgx_quote_status, advisories, extra_advisories)?;
 
-            Ok(NodeAuthResult::Success)
+            Ok((NodeAuthResult::Success, None))
         }
         _ => {
             error!(
@@ -418,30 +563,45 @@ pub fn verify_quote_status(
     }
 }
 
-// the difference here is that we allow GROUP_OUT_OF_DATE for testnet machines to make joining a bit
-// easier
+// the difference here is that we allow GROUP_OUT_OF_DATE and stale enclaves for testnet machines to
+// make joining a bit easier
 #[cfg(all(feature = "SGX_MODE_HW", not(feature = "production")))]
 pub fn verify_quote_status(
     report: &AttestationReport,
     advisories: &AdvisoryIDs,
-) -> Result<NodeAuthResult, NodeAuthResult> {
+    extra_advisories: Option<&AdvisoryIDs>,
+) -> Result<(NodeAuthResult, Option<DowngradeReason>), NodeAuthResult> {
     match &report.sgx_quote_status {
         SgxQuoteStatus::OK
         | SgxQuoteStatus::SwHardeningNeeded
         | SgxQuoteStatus::ConfigurationAndSwHardeningNeeded
         | SgxQuoteStatus::GroupOutOfDate => {
-            let results = check_advisories(&report.sgx_quote_status, advisories);
+            let results = check_advisories(&report.sgx_quote_status, advisories, extra_advisories);
+            let isv_svn = report.sgx_quote_body.isv_enclave_report.isv_svn;
+            let stale_svn = stale_svn_reason(isv_svn, MIN_ISV_SVN);
+            if stale_svn.is_some() {
+                warn!(
+                    "Enclave security version {} is below the minimum {}, which will not be approved on mainnet",
+                    isv_svn, MIN_ISV_SVN
+                );
+            }
+
+            let reason = downgrade_reason(
+                &report.sgx_quote_status,
+                advisories.vulnerable_ids(extra_advisories),
+            )
+            .or(stale_svn);
 
             if let Err(results) = results {
                 warn!("This platform has vulnerabilities that will not be approved on mainnet");
-                return Ok(results); // Allow in non-production
+                return Ok((results, reason)); // Allow in non-production
             }
 
             // if !advisories.contains_lvi_injection() {
             //     return Err(NodeAuthResult::EnclaveQuoteStatus);
             // }
 
-            Ok(NodeAuthResult::Success)
+            Ok((NodeAuthResult::Success, reason))
         }
         _ => {
             error!(
@@ -478,9 +638,10 @@ pub fn check_epid_gid_is_whitelisted(epid_gid: &u32) -> bool {
 fn check_advisories(
     quote_status: &SgxQuoteStatus,
     advisories: &AdvisoryIDs,
+    extra_advisories: Option<&AdvisoryIDs>,
 ) -> Result<(), NodeAuthResult> {
     // this checks if there are any vulnerabilities that are not on in the whitelisted list
-    let vulnerable = advisories.vulnerable();
+    let vulnerable = advisories.vulnerable(extra_advisories);
     if vulnerable.is_empty() {
         Ok(())
     } else {
@@ -500,9 +661,9 @@ pub mod tests {
 
     use enclave_ffi_types::NodeAuthResult;
 
-    use crate::registration::report::AttestationReport;
+    use crate::registration::report::{AdvisoryIDs, AttestationReport, SgxQuoteStatus};
 
-    use super::verify_ra_cert;
+    use super::{downgrade_reason, stale_svn_reason, verify_ra_cert, DowngradeReason, MIN_ISV_SVN};
 
     // #[cfg(feature = "SGX_MODE_HW")]
     // fn tls_ra_cert_der_out_of_date() -> Vec<u8> {
@@ -545,29 +706,164 @@ pub mod tests {
         cert
     }
 
+    pub fn test_downgrade_reason_per_status() {
+        let advisories = || vec!["INTEL-SA-00161".to_string()];
+
+        for status in [
+            SgxQuoteStatus::OK,
+            SgxQuoteStatus::SwHardeningNeeded,
+            SgxQuoteStatus::ConfigurationAndSwHardeningNeeded,
+            SgxQuoteStatus::GroupOutOfDate,
+        ] {
+            assert_eq!(
+                downgrade_reason(&status, advisories()),
+                Some(DowngradeReason::UnmitigatedAdvisories {
+                    status: status.clone(),
+                    advisories: advisories(),
+                })
+            );
+        }
+
+        // without advisories only an out of date platform is a downgrade
+        assert_eq!(
+            downgrade_reason(&SgxQuoteStatus::GroupOutOfDate, vec![]),
+            Some(DowngradeReason::GroupOutOfDate)
+        );
+        for status in [
+            SgxQuoteStatus::OK,
+            SgxQuoteStatus::SwHardeningNeeded,
+            SgxQuoteStatus::ConfigurationAndSwHardeningNeeded,
+        ] {
+            assert_eq!(downgrade_reason(&status, vec![]), None);
+        }
+    }
+
+    pub fn test_stale_svn_reason() {
+        assert_eq!(
+            stale_svn_reason(MIN_ISV_SVN - 1, MIN_ISV_SVN),
+            Some(DowngradeReason::StaleSvn {
+                isv_svn: MIN_ISV_SVN - 1,
+                min_isv_svn: MIN_ISV_SVN,
+            })
+        );
+        assert_eq!(stale_svn_reason(MIN_ISV_SVN, MIN_ISV_SVN), None);
+        assert_eq!(stale_svn_reason(MIN_ISV_SVN + 1, MIN_ISV_SVN), None);
+    }
+
+    pub fn test_downgrade_reason_auth_result() {
+        assert_eq!(
+            NodeAuthResult::from(&DowngradeReason::GroupOutOfDate),
+            NodeAuthResult::GroupOutOfDate
+        );
+        assert_eq!(
+            NodeAuthResult::from(&DowngradeReason::UnmitigatedAdvisories {
+                status: SgxQuoteStatus::SwHardeningNeeded,
+                advisories: vec!["INTEL-SA-00161".to_string()],
+            }),
+            NodeAuthResult::UnmitigatedAdvisories
+        );
+        assert_eq!(
+            NodeAuthResult::from(&DowngradeReason::StaleSvn {
+                isv_svn: MIN_ISV_SVN - 1,
+                min_isv_svn: MIN_ISV_SVN,
+            }),
+            NodeAuthResult::StaleSvn
+        );
+    }
+
+    #[cfg(not(feature = "SGX_MODE_HW"))]
+    pub fn test_quote_status_isv_svn() {}
+
+    #[cfg(feature = "SGX_MODE_HW")]
+    pub fn test_quote_status_isv_svn() {
+        let mut report = AttestationReport::from_cert(&tls_ra_cert_der_sw_config_needed(), None)
+            .expect("Failed to parse the report");
+        report.advisory_ids = AdvisoryIDs(vec![]);
+
+        report.sgx_quote_body.isv_enclave_report.isv_svn = MIN_ISV_SVN - 1;
+        let result = super::verify_quote_status(&report, &report.advisory_ids, None);
+        if cfg!(feature = "production") {
+            assert_eq!(result, Err(NodeAuthResult::StaleSvn));
+        } else {
+            assert_eq!(
+                result,
+                Ok((
+                    NodeAuthResult::Success,
+                    Some(DowngradeReason::StaleSvn {
+                        isv_svn: MIN_ISV_SVN - 1,
+                        min_isv_svn: MIN_ISV_SVN,
+                    })
+                ))
+            );
+        }
+
+        for isv_svn in [MIN_ISV_SVN, MIN_ISV_SVN + 1] {
+            report.sgx_quote_body.isv_enclave_report.isv_svn = isv_svn;
+            assert_eq!(
+                super::verify_quote_status(&report, &report.advisory_ids, None),
+                Ok((NodeAuthResult::Success, None))
+            );
+        }
+    }
+
     #[cfg(not(feature = "SGX_MODE_HW"))]
     pub fn test_certificate_invalid_configuration_needed() {}
 
     #[cfg(feature = "SGX_MODE_HW")]
     pub fn test_certificate_invalid_configuration_needed() {
         let tls_ra_cert = tls_ra_cert_der_sw_config_needed();
-        let report = AttestationReport::from_cert(&tls_ra_cert);
+        let report = AttestationReport::from_cert(&tls_ra_cert, None);
         assert!(report.is_ok());
 
         let res = verify_ra_cert(&tls_ra_cert, None, false);
 
-        assert!(res.is_ok());
+        // the fixture was generated by an enclave with ISVSVN 0
+        if cfg!(feature = "production") {
+            assert_eq!(res, Err(NodeAuthResult::StaleSvn));
+        } else {
+            assert!(res.is_ok());
+        }
 
         // assert_eq!(result, NodeAuthResult::SwHardeningAndConfigurationNeeded)
     }
 
+    #[cfg(not(feature = "SGX_MODE_HW"))]
+    pub fn test_quote_status_extra_advisories() {}
+
+    #[cfg(feature = "SGX_MODE_HW")]
+    pub fn test_quote_status_extra_advisories() {
+        let advisories = |ids: &[&str]| AdvisoryIDs(ids.iter().map(|id| id.to_string()).collect());
+
+        let mut report = AttestationReport::from_cert(&tls_ra_cert_der_sw_config_needed(), None)
+            .expect("Failed to parse the report");
+        // the fixture was generated by an enclave with ISVSVN 0
+        report.sgx_quote_body.isv_enclave_report.isv_svn = MIN_ISV_SVN;
+        // INTEL-SA-00334 is whitelisted, INTEL-SA-00161 isn't
+        report.advisory_ids = advisories(&["INTEL-SA-00334", "INTEL-SA-00161"]);
+
+        for extra_advisories in [None, Some(advisories(&["INTEL-SA-00219"]))] {
+            let result = super::verify_quote_status(
+                &report,
+                &report.advisory_ids,
+                extra_advisories.as_ref(),
+            );
+            assert!(!matches!(result, Ok((NodeAuthResult::Success, _))));
+        }
+
+        let extra_advisories = advisories(&["INTEL-SA-00161"]);
+        assert_eq!(
+            super::verify_quote_status(&report, &report.advisory_ids, Some(&extra_advisories)),
+            Ok((NodeAuthResult::Success, None))
+        );
+    }
+
     // #[cfg(not(feature = "SGX_MODE_HW"))]
     // pub fn test_certificate_invalid_group_out_of_date() {}
     //
     // #[cfg(feature = "SGX_MODE_HW")]
     // pub fn test_certificate_invalid_group_out_of_date() {
     //     let tls_ra_cert = tls_ra_cert_der_out_of_date();
-    //     let report = AttestationReport::from_cert(&tls_ra_cert);
+    //     let report = AttestationReport::from_cert(&tls_ra_cert, None);
     //     assert!(report.is_ok());
     //
     //     let result =
@@ -602,6 +898,13 @@ pub mod tests {
 
     pub fn test_certificate_valid() {
         let tls_ra_cert = tls_ra_cert_der_valid();
-        let _ = verify_ra_cert(&tls_ra_cert, None, false).unwrap();
+        let res = verify_ra_cert(&tls_ra_cert, None, false);
+
+        // the hardware fixture was generated by an enclave with ISVSVN 0
+        if cfg!(all(feature = "SGX_MODE_HW", feature = "production")) {
+            assert_eq!(res, Err(NodeAuthResult::StaleSvn));
+        } else {
+            let _ = res.unwrap();
+        }
     }
 }
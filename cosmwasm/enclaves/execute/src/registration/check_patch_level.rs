@@ -32,7 +32,7 @@ use crate::registration::cert::check_epid_gid_is_whitelisted;
 #[cfg(feature = "SGX_MODE_HW")]
 use crate::registration::print_report::print_platform_info;
 
-use crate::registration::report::AttestationReport;
+use crate::registration::report::{AdvisoryIDs, AttestationReport};
 
 /// # Safety
 #[no_mangle]
@@ -40,6 +40,8 @@ use crate::registration::report::AttestationReport;
 pub unsafe extern "C" fn ecall_check_patch_level(
     _api_key: *const u8,
     _api_key_len: u32,
+    _accepted_advisories: *const u8,
+    _accepted_advisories_len: u32,
 ) -> NodeAuthResult {
     panic!("unimplemented")
 }
@@ -47,8 +49,8 @@ pub unsafe extern "C" fn ecall_check_patch_level(
 #[cfg(feature = "SGX_MODE_HW")]
 unsafe fn check_patch_level_dcap(pub_k: &[u8; 32]) -> NodeAuthResult {
     match get_quote_ecdsa_untested(pub_k) {
-        Ok((vec_quote, vec_coll)) => {
-            match verify_quote_ecdsa(&vec_quote, &vec_coll, 0) {
+        Ok((vec_quote, collateral)) => {
+            match verify_quote_ecdsa(&vec_quote, &collateral, 0) {
                 Ok(r) => {
                     if r.1 != sgx_ql_qv_result_t::SGX_QL_QV_RESULT_OK {
                         println!("WARNING: {}", r.1);
@@ -61,7 +63,7 @@ unsafe fn check_patch_level_dcap(pub_k: &[u8; 32]) -> NodeAuthResult {
                     println!("DCAP quote obtained, but failed to verify it: {}", e);
 
                     let _ = write_to_untrusted(&vec_quote, "dcap_quote.bin");
-                    let _ = write_to_untrusted(&vec_coll, "dcap_collateral.bin");
+                    let _ = write_to_untrusted(&collateral.to_bytes(), "dcap_collateral.bin");
                 }
             };
         }
@@ -77,6 +79,7 @@ unsafe fn check_patch_level_epid(
     pub_k: &[u8; 32],
     api_key: *const u8,
     api_key_len: u32,
+    accepted_advisories: Option<&AdvisoryIDs>,
 ) -> NodeAuthResult {
     validate_const_ptr!(api_key, api_key_len as usize, NodeAuthResult::InvalidInput);
     if api_key_len > 100 {
@@ -112,7 +115,7 @@ unsafe fn check_patch_level_epid(
     let (_key_der, cert) = super::cert::gen_ecc_cert(payload, &prv_k, &pub_k, &ecc_handle).unwrap();
     let _result = ecc_handle.close();
 
-    let report = AttestationReport::from_cert(&cert)
+    let report = AttestationReport::from_cert(&cert, None)
         .map_err(|_| {
             error!("Failed to create report from certificate");
             NodeAuthResult::InvalidCert
@@ -147,7 +150,7 @@ unsafe fn check_patch_level_epid(
     // PERFORM STATUS CHECKS
     let node_auth_result = NodeAuthResult::from(&report.sgx_quote_status);
     // print
-    match verify_quote_status(&report, &report.advisory_ids) {
+    match verify_quote_status(&report, &report.advisory_ids, accepted_advisories) {
         Err(status) => match status {
             NodeAuthResult::SwHardeningAndConfigurationNeeded => {
                 println!("Platform status is SW_HARDENING_AND_CONFIGURATION_NEEDED. This means is updated but requires further BIOS configuration");
@@ -172,6 +175,37 @@ unsafe fn check_patch_level_epid(
     }
 }
 
+/// The advisory IDs the operator accepts on top of the enclave's whitelist, as a comma separated
+/// list. An empty list accepts nothing more.
+#[cfg(feature = "SGX_MODE_HW")]
+unsafe fn parse_accepted_advisories(
+    accepted_advisories: *const u8,
+    accepted_advisories_len: u32,
+) -> Result<Option<AdvisoryIDs>, NodeAuthResult> {
+    if accepted_advisories_len == 0 {
+        return Ok(None);
+    }
+    validate_const_ptr!(
+        accepted_advisories,
+        accepted_advisories_len as usize,
+        Err(NodeAuthResult::InvalidInput),
+    );
+
+    let raw = slice::from_raw_parts(accepted_advisories, accepted_advisories_len as usize);
+    let list = std::str::from_utf8(raw).map_err(|_| {
+        error!("Accepted advisories are not valid UTF-8");
+        NodeAuthResult::InvalidInput
+    })?;
+
+    Ok(Some(AdvisoryIDs(
+        list.split(',')
+            .map(str::trim)
+            .filter(|id| !id.is_empty())
+            .map(String::from)
+            .collect(),
+    )))
+}
+
 /// # Safety
 /// Don't forget to check the input length of api_key_len
 #[no_mangle]
@@ -179,11 +213,30 @@ unsafe fn check_patch_level_epid(
 pub unsafe extern "C" fn ecall_check_patch_level(
     api_key: *const u8,
     api_key_len: u32,
+    accepted_advisories: *const u8,
+    accepted_advisories_len: u32,
 ) -> NodeAuthResult {
+    let accepted_advisories =
+        match parse_accepted_advisories(accepted_advisories, accepted_advisories_len) {
+            Ok(accepted_advisories) => accepted_advisories,
+            Err(e) => return e,
+        };
+    if let Some(accepted_advisories) = &accepted_advisories {
+        println!(
+            "Accepting these advisories for this check only, mainnet registration does not: {:?}",
+            accepted_advisories.0
+        );
+    }
+
     let temp_key_result = enclave_crypto::KeyPair::new().unwrap();
 
     let res1 = check_patch_level_dcap(&temp_key_result.get_pubkey());
-    let res2 = check_patch_level_epid(&temp_key_result.get_pubkey(), api_key, api_key_len);
+    let res2 = check_patch_level_epid(
+        &temp_key_result.get_pubkey(),
+        api_key,
+        api_key_len,
+        accepted_advisories.as_ref(),
+    );
 
     println!("DCAP attestation: {}", res1);
     println!("EPID attestation: {}", res2);
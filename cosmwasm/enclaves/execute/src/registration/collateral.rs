@@ -0,0 +1,392 @@
+#![cfg_attr(not(feature = "SGX_MODE_HW"), allow(unused))]
+//! The collateral DCAP quotes are verified with (CRLs, TCB info, QE identity and the chains that
+//! sign them), and a cache of it.
+//!
+//! Fetching collateral goes out to the PCCS, but collateral only changes when Intel issues new TCB
+//! info for a platform. It's cached by the FMSPC (the family, model and platform the TCB info is
+//! issued for) of the quote it was fetched for, until the `nextUpdate` of its TCB info.
+
+use std::collections::HashMap;
+use std::sync::SgxMutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use chrono::TimeZone;
+use chrono::Utc as TzUtc;
+use lazy_static::lazy_static;
+use log::*;
+use sgx_types::sgx_status_t;
+
+#[cfg(feature = "SGX_MODE_HW")]
+use super::attestation::test_sgx_call_res;
+#[cfg(feature = "SGX_MODE_HW")]
+use super::ocalls::ocall_get_quote_ecdsa_collateral;
+
+/// The size of the serialized collateral header: the TEE type and the sizes of the 7 parts
+const HEADER_SIZE: usize = 8 * 4;
+/// The DER of the SGX extension OID (1.2.840.113741.1.13.1.4) that holds the FMSPC in a PCK cert
+const FMSPC_OID: [u8; 12] = [
+    0x06, 0x0a, 0x2a, 0x86, 0x48, 0x86, 0xf8, 0x4d, 0x01, 0x0d, 0x01, 0x04,
+];
+const FMSPC_SIZE: usize = 6;
+
+const PEM_CERT_BEGIN: &[u8] = b"-----BEGIN CERTIFICATE-----";
+const PEM_CERT_END: &[u8] = b"-----END CERTIFICATE-----";
+
+/// Collateral as `ocall_get_quote_ecdsa_collateral` serializes it: a header with the TEE type and
+/// the size of each part, followed by the parts in this order. The parts are PEM or JSON, and
+/// keep the NUL they end with.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Collateral {
+    pub tee_type: u32,
+    pub pck_crl_issuer_chain: Vec<u8>,
+    pub root_ca_crl: Vec<u8>,
+    pub pck_crl: Vec<u8>,
+    pub tcb_info_issuer_chain: Vec<u8>,
+    pub tcb_info: Vec<u8>,
+    pub qe_identity_issuer_chain: Vec<u8>,
+    pub qe_identity: Vec<u8>,
+}
+
+impl Collateral {
+    pub fn parse(bytes: &[u8]) -> Result<Self, sgx_status_t> {
+        if bytes.len() < HEADER_SIZE {
+            trace!("Collateral is too short for its header");
+            return Err(sgx_status_t::SGX_ERROR_UNEXPECTED);
+        }
+
+        let mut header = [0u32; 8];
+        for (i, field) in header.iter_mut().enumerate() {
+            let mut le = [0u8; 4];
+            le.copy_from_slice(&bytes[i * 4..i * 4 + 4]);
+            *field = u32::from_le_bytes(le);
+        }
+
+        let mut offs = HEADER_SIZE;
+        let mut parts = Vec::with_capacity(7);
+        for size in &header[1..] {
+            let end = offs.saturating_add(*size as usize);
+            if end > bytes.len() {
+                trace!("Collateral is shorter than its header says");
+                return Err(sgx_status_t::SGX_ERROR_UNEXPECTED);
+            }
+            parts.push(bytes[offs..end].to_vec());
+            offs = end;
+        }
+
+        let mut parts = parts.into_iter();
+        let mut next = || parts.next().unwrap(); // there are exactly 7 parts
+        Ok(Self {
+            tee_type: header[0],
+            pck_crl_issuer_chain: next(),
+            root_ca_crl: next(),
+            pck_crl: next(),
+            tcb_info_issuer_chain: next(),
+            tcb_info: next(),
+            qe_identity_issuer_chain: next(),
+            qe_identity: next(),
+        })
+    }
+
+    fn parts(&self) -> [&Vec<u8>; 7] {
+        [
+            &self.pck_crl_issuer_chain,
+            &self.root_ca_crl,
+            &self.pck_crl,
+            &self.tcb_info_issuer_chain,
+            &self.tcb_info,
+            &self.qe_identity_issuer_chain,
+            &self.qe_identity,
+        ]
+    }
+
+    /// Serializes the collateral back to what `ocall_verify_quote_ecdsa` takes
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let parts = self.parts();
+        let size = HEADER_SIZE + parts.iter().map(|part| part.len()).sum::<usize>();
+
+        let mut bytes = Vec::with_capacity(size);
+        bytes.extend_from_slice(&self.tee_type.to_le_bytes());
+        for part in &parts {
+            bytes.extend_from_slice(&(part.len() as u32).to_le_bytes());
+        }
+        for part in &parts {
+            bytes.extend_from_slice(part);
+        }
+        bytes
+    }
+
+    fn tcb_info_json(&self) -> Option<serde_json::Value> {
+        let json = self.tcb_info.split(|&b| b == 0).next()?;
+        serde_json::from_slice(json).ok()
+    }
+
+    /// The FMSPC the TCB info is issued for, in lowercase hex
+    pub fn fmspc(&self) -> Option<String> {
+        let tcb_info = self.tcb_info_json()?;
+        Some(tcb_info["tcbInfo"]["fmspc"].as_str()?.to_lowercase())
+    }
+
+    /// When Intel issues the next TCB info, in seconds since the epoch
+    pub fn next_update_s(&self) -> Option<i64> {
+        let tcb_info = self.tcb_info_json()?;
+        let next_update = tcb_info["tcbInfo"]["nextUpdate"].as_str()?;
+        match TzUtc.datetime_from_str(next_update, "%Y-%m-%dT%H:%M:%SZ") {
+            Ok(next_update) => Some(next_update.timestamp()),
+            Err(e) => {
+                warn!(
+                    "Failed to parse the TCB info nextUpdate {}: {}",
+                    next_update, e
+                );
+                None
+            }
+        }
+    }
+}
+
+/// The FMSPC of the platform that generated `quote`, in lowercase hex, read from the PCK cert in
+/// its certification data
+pub fn quote_fmspc(quote: &[u8]) -> Option<String> {
+    let begin = find(quote, PEM_CERT_BEGIN)? + PEM_CERT_BEGIN.len();
+    let end = begin + find(&quote[begin..], PEM_CERT_END)?;
+    let pem: Vec<u8> = quote[begin..end]
+        .iter()
+        .filter(|b| !b.is_ascii_whitespace())
+        .cloned()
+        .collect();
+    let pck_cert = base64::decode(&pem).ok()?;
+
+    // the OID is followed by an OCTET STRING of the FMSPC
+    let value = find(&pck_cert, &FMSPC_OID)? + FMSPC_OID.len();
+    match pck_cert.get(value..value + 2 + FMSPC_SIZE) {
+        Some([0x04, len, fmspc @ ..]) if *len as usize == FMSPC_SIZE => Some(hex::encode(fmspc)),
+        _ => None,
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+struct CachedCollateral {
+    collateral: Collateral,
+    next_update_s: i64,
+}
+
+lazy_static! {
+    static ref COLLATERAL_CACHE: SgxMutex<HashMap<String, CachedCollateral>> =
+        SgxMutex::new(HashMap::new());
+}
+
+/// Returns the collateral cached in `cache` for `fmspc` if it's still current at `now_s`, or what
+/// `fetch` returns otherwise. Fetched collateral is cached if its TCB info is for `fmspc`.
+fn fetch_cached<F>(
+    cache: &mut HashMap<String, CachedCollateral>,
+    fmspc: Option<&str>,
+    now_s: i64,
+    fetch: F,
+) -> Result<Collateral, sgx_status_t>
+where
+    F: FnOnce() -> Result<Collateral, sgx_status_t>,
+{
+    let fmspc = match fmspc {
+        Some(fmspc) => fmspc,
+        None => {
+            trace!("Quote has no FMSPC, fetching collateral without caching it");
+            return fetch();
+        }
+    };
+
+    if let Some(cached) = cache.get(fmspc) {
+        if now_s < cached.next_update_s {
+            trace!("Using cached collateral for FMSPC {}", fmspc);
+            return Ok(cached.collateral.clone());
+        }
+    }
+
+    let collateral = fetch()?;
+
+    match (collateral.fmspc(), collateral.next_update_s()) {
+        (Some(collateral_fmspc), Some(next_update_s))
+            if collateral_fmspc == fmspc && now_s < next_update_s =>
+        {
+            cache.insert(
+                fmspc.to_string(),
+                CachedCollateral {
+                    collateral: collateral.clone(),
+                    next_update_s,
+                },
+            );
+        }
+        _ => {
+            trace!("Not caching the collateral fetched for FMSPC {}", fmspc);
+            cache.remove(fmspc);
+        }
+    }
+
+    Ok(collateral)
+}
+
+/// Fetches the collateral `quote` is verified with, or returns the one cached for its platform
+/// if its TCB info is still current
+#[cfg(feature = "SGX_MODE_HW")]
+pub fn fetch_collateral(quote: &[u8]) -> Result<Collateral, sgx_status_t> {
+    let now_s = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|_| sgx_status_t::SGX_ERROR_UNEXPECTED)?
+        .as_secs() as i64;
+
+    let fmspc = quote_fmspc(quote);
+    let mut cache = COLLATERAL_CACHE.lock().unwrap();
+    fetch_cached(&mut cache, fmspc.as_deref(), now_s, || {
+        Collateral::parse(&fetch_collateral_uncached(quote)?)
+    })
+}
+
+#[cfg(feature = "SGX_MODE_HW")]
+fn fetch_collateral_uncached(quote: &[u8]) -> Result<Vec<u8>, sgx_status_t> {
+    let mut vec_coll: Vec<u8> = vec![0; 0x4000];
+    let mut size_coll: u32 = 0;
+    let mut rt: sgx_status_t = sgx_status_t::default();
+
+    let res = unsafe {
+        ocall_get_quote_ecdsa_collateral(
+            &mut rt as *mut sgx_status_t,
+            quote.as_ptr(),
+            quote.len() as u32,
+            vec_coll.as_mut_ptr(),
+            vec_coll.len() as u32,
+            &mut size_coll,
+        )
+    };
+
+    if let Err(e) = test_sgx_call_res(res, rt) {
+        trace!("ocall_get_quote_ecdsa_collateral err = {}", e);
+        return Err(e);
+    }
+
+    trace!("Collateral size = {}", size_coll);
+
+    let call_again = size_coll > vec_coll.len() as u32;
+    vec_coll.resize(size_coll as usize, 0);
+
+    if call_again {
+        let res = unsafe {
+            ocall_get_quote_ecdsa_collateral(
+                &mut rt as *mut sgx_status_t,
+                quote.as_ptr(),
+                quote.len() as u32,
+                vec_coll.as_mut_ptr(),
+                vec_coll.len() as u32,
+                &mut size_coll,
+            )
+        };
+
+        if let Err(e) = test_sgx_call_res(res, rt) {
+            trace!("ocall_get_quote_ecdsa_collateral again err = {}", e);
+            return Err(e);
+        }
+    }
+
+    Ok(vec_coll)
+}
+
+#[cfg(feature = "test")]
+pub mod tests {
+    use std::cell::Cell;
+    use std::io::Read;
+    use std::untrusted::fs::File;
+
+    use super::*;
+
+    const FIXTURE_FMSPC: &str = "00906ed50000";
+    /// The nextUpdate of the fixture's TCB info, 2021-04-30T08:46:22Z
+    const FIXTURE_NEXT_UPDATE_S: i64 = 1619772382;
+
+    fn fixture(name: &str) -> Vec<u8> {
+        let mut bytes = vec![];
+        File::open(format!("../execute/src/registration/fixtures/{}", name))
+            .unwrap()
+            .read_to_end(&mut bytes)
+            .unwrap();
+        bytes
+    }
+
+    /// Fetches the fixture collateral through `cache`, counting the fetches in `calls`
+    fn fetch(
+        cache: &mut HashMap<String, CachedCollateral>,
+        now_s: i64,
+        calls: &Cell<u32>,
+    ) -> Collateral {
+        fetch_cached(cache, Some(FIXTURE_FMSPC), now_s, || {
+            calls.set(calls.get() + 1);
+            Collateral::parse(&fixture("attestation_dcap.collateral"))
+        })
+        .unwrap()
+    }
+
+    pub fn test_collateral_parse() {
+        let bytes = fixture("attestation_dcap.collateral");
+        let collateral = Collateral::parse(&bytes).unwrap();
+
+        assert!(collateral.tcb_info.starts_with(b"{\"tcbInfo\""));
+        assert!(collateral
+            .root_ca_crl
+            .starts_with(b"-----BEGIN X509 CRL-----"));
+        assert_eq!(collateral.fmspc().as_deref(), Some(FIXTURE_FMSPC));
+        assert_eq!(collateral.next_update_s(), Some(FIXTURE_NEXT_UPDATE_S));
+        assert_eq!(collateral.to_bytes(), bytes);
+
+        assert_eq!(
+            quote_fmspc(&fixture("attestation_dcap.quote")).as_deref(),
+            Some(FIXTURE_FMSPC)
+        );
+
+        assert!(Collateral::parse(&bytes[..HEADER_SIZE - 1]).is_err());
+        assert!(Collateral::parse(&bytes[..bytes.len() - 1]).is_err());
+    }
+
+    pub fn test_fresh_collateral_is_cached() {
+        let mut cache = HashMap::new();
+        let calls = Cell::new(0);
+
+        let collateral = fetch(&mut cache, FIXTURE_NEXT_UPDATE_S - 1000, &calls);
+        assert_eq!(calls.get(), 1);
+
+        assert_eq!(
+            fetch(&mut cache, FIXTURE_NEXT_UPDATE_S - 1, &calls),
+            collateral
+        );
+        assert_eq!(calls.get(), 1);
+
+        // collateral for another platform is fetched
+        fetch_cached(
+            &mut cache,
+            Some("00606a000000"),
+            FIXTURE_NEXT_UPDATE_S - 1,
+            || {
+                calls.set(calls.get() + 1);
+                Ok(collateral.clone())
+            },
+        )
+        .unwrap();
+        assert_eq!(calls.get(), 2);
+    }
+
+    pub fn test_expired_collateral_is_refetched() {
+        let mut cache = HashMap::new();
+        let calls = Cell::new(0);
+
+        fetch(&mut cache, FIXTURE_NEXT_UPDATE_S - 1000, &calls);
+        assert_eq!(calls.get(), 1);
+
+        fetch(&mut cache, FIXTURE_NEXT_UPDATE_S, &calls);
+        assert_eq!(calls.get(), 2);
+
+        // collateral that is already stale isn't cached
+        fetch(&mut cache, FIXTURE_NEXT_UPDATE_S + 1, &calls);
+        assert_eq!(calls.get(), 3);
+        assert!(cache.is_empty());
+    }
+}
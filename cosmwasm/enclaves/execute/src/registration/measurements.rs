@@ -0,0 +1,223 @@
+//! The enclave measurements a node operator allows its peers to have.
+//!
+//! Registration only checks that a registering enclave is signed like this one, and has to, since
+//! all nodes must agree on it. Off chain, when a node imports a seed or verifies the attestation
+//! of a peer it onboards, the operator can also pin the releases it accepts: by MRENCLAVE, or at
+//! least by MRSIGNER. Nothing is pinned until the node configures it, and `allow_any` lifts the
+//! pins for an upgrade window without dropping them.
+
+use std::sync::SgxRwLock;
+
+use lazy_static::lazy_static;
+use log::*;
+use serde::Deserialize;
+
+use enclave_ffi_types::NodeAuthResult;
+
+pub const MEASUREMENT_SIZE: usize = 32;
+
+/// The measurements of an attested enclave
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Measurements {
+    pub mr_enclave: [u8; MEASUREMENT_SIZE],
+    pub mr_signer: [u8; MEASUREMENT_SIZE],
+}
+
+/// The measurements peers may have. An enclave is allowed if its MRENCLAVE or its MRSIGNER is
+/// listed.
+#[derive(Debug, Default, PartialEq)]
+pub struct AllowedMeasurements {
+    pub mr_enclaves: Vec<[u8; MEASUREMENT_SIZE]>,
+    pub mr_signers: Vec<[u8; MEASUREMENT_SIZE]>,
+    /// Allows every enclave, while keeping the lists for after an upgrade
+    pub allow_any: bool,
+}
+
+/// The JSON an operator configures `AllowedMeasurements` with, where measurements are hex
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AllowedMeasurementsConfig {
+    #[serde(default)]
+    mr_enclaves: Vec<String>,
+    #[serde(default)]
+    mr_signers: Vec<String>,
+    #[serde(default)]
+    allow_any: bool,
+}
+
+fn decode_measurements(
+    hex_measurements: &[String],
+) -> Result<Vec<[u8; MEASUREMENT_SIZE]>, NodeAuthResult> {
+    hex_measurements
+        .iter()
+        .map(|hex_measurement| {
+            let decoded = hex::decode(hex_measurement).map_err(|err| {
+                warn!("Measurement {} isn't hex: {:?}", hex_measurement, err);
+                NodeAuthResult::InvalidInput
+            })?;
+            if decoded.len() != MEASUREMENT_SIZE {
+                warn!("Measurement {} has the wrong length", hex_measurement);
+                return Err(NodeAuthResult::InvalidInput);
+            }
+
+            let mut measurement = [0u8; MEASUREMENT_SIZE];
+            measurement.copy_from_slice(&decoded);
+            Ok(measurement)
+        })
+        .collect()
+}
+
+impl AllowedMeasurements {
+    /// Parses a configuration like
+    /// `{"mr_enclaves":["<hex>"],"mr_signers":["<hex>"],"allow_any":false}`
+    pub fn from_json(config: &[u8]) -> Result<Self, NodeAuthResult> {
+        let config: AllowedMeasurementsConfig = serde_json::from_slice(config).map_err(|err| {
+            warn!("Failed to parse the allowed measurements: {:?}", err);
+            NodeAuthResult::InvalidInput
+        })?;
+
+        Ok(Self {
+            mr_enclaves: decode_measurements(&config.mr_enclaves)?,
+            mr_signers: decode_measurements(&config.mr_signers)?,
+            allow_any: config.allow_any,
+        })
+    }
+
+    /// Whether an enclave with `measurements` is allowed. Enclaves whose measurements aren't
+    /// known, like in software mode, are only allowed with `allow_any`.
+    pub fn allows(&self, measurements: Option<&Measurements>) -> bool {
+        if self.allow_any {
+            return true;
+        }
+
+        match measurements {
+            Some(measurements) => {
+                self.mr_enclaves.contains(&measurements.mr_enclave)
+                    || self.mr_signers.contains(&measurements.mr_signer)
+            }
+            None => false,
+        }
+    }
+}
+
+lazy_static! {
+    static ref ALLOWED_MEASUREMENTS: SgxRwLock<Option<AllowedMeasurements>> = SgxRwLock::new(None);
+}
+
+/// Sets the measurements peers may have. `None` allows any enclave.
+pub fn configure_allowed_measurements(allowed: Option<AllowedMeasurements>) {
+    *ALLOWED_MEASUREMENTS.write().unwrap() = allowed;
+}
+
+/// Checks an attested peer's `measurements` against the configured `AllowedMeasurements`
+pub fn check_allowed_measurements(measurements: Option<&Measurements>) -> NodeAuthResult {
+    match &*ALLOWED_MEASUREMENTS.read().unwrap() {
+        Some(allowed) if !allowed.allows(measurements) => {
+            warn!(
+                "Peer enclave with measurements {:?} isn't allowed",
+                measurements
+            );
+            NodeAuthResult::MeasurementNotAllowed
+        }
+        _ => NodeAuthResult::Success,
+    }
+}
+
+#[cfg(feature = "test")]
+pub mod tests {
+    use super::*;
+
+    const MR_ENCLAVE: [u8; 32] = [1u8; 32];
+    const MR_SIGNER: [u8; 32] = [2u8; 32];
+
+    fn measurements(mr_enclave: [u8; 32], mr_signer: [u8; 32]) -> Measurements {
+        Measurements {
+            mr_enclave,
+            mr_signer,
+        }
+    }
+
+    pub fn test_allowed_measurements_match() {
+        let by_enclave = AllowedMeasurements {
+            mr_enclaves: vec![MR_ENCLAVE],
+            ..Default::default()
+        };
+        assert!(by_enclave.allows(Some(&measurements(MR_ENCLAVE, [9u8; 32]))));
+        assert!(!by_enclave.allows(Some(&measurements([9u8; 32], MR_SIGNER))));
+
+        let by_signer = AllowedMeasurements {
+            mr_signers: vec![MR_SIGNER],
+            ..Default::default()
+        };
+        assert!(by_signer.allows(Some(&measurements([9u8; 32], MR_SIGNER))));
+        assert!(!by_signer.allows(Some(&measurements(MR_ENCLAVE, [9u8; 32]))));
+
+        // unknown measurements aren't allowed, unless anything is
+        assert!(!by_signer.allows(None));
+        let upgrading = AllowedMeasurements {
+            allow_any: true,
+            ..by_signer
+        };
+        assert!(upgrading.allows(Some(&measurements([9u8; 32], [9u8; 32]))));
+        assert!(upgrading.allows(None));
+    }
+
+    pub fn test_check_allowed_measurements() {
+        let matching = measurements(MR_ENCLAVE, MR_SIGNER);
+        let mismatching = measurements([9u8; 32], [9u8; 32]);
+
+        // nothing is pinned by default
+        configure_allowed_measurements(None);
+        assert_eq!(
+            check_allowed_measurements(Some(&mismatching)),
+            NodeAuthResult::Success
+        );
+
+        configure_allowed_measurements(Some(AllowedMeasurements {
+            mr_enclaves: vec![MR_ENCLAVE],
+            ..Default::default()
+        }));
+        assert_eq!(
+            check_allowed_measurements(Some(&matching)),
+            NodeAuthResult::Success
+        );
+        assert_eq!(
+            check_allowed_measurements(Some(&mismatching)),
+            NodeAuthResult::MeasurementNotAllowed
+        );
+
+        configure_allowed_measurements(None);
+    }
+
+    pub fn test_allowed_measurements_from_json() {
+        let config = format!(
+            r#"{{"mr_enclaves":["{}"],"mr_signers":["{}"]}}"#,
+            hex::encode(MR_ENCLAVE),
+            hex::encode(MR_SIGNER)
+        );
+        assert_eq!(
+            AllowedMeasurements::from_json(config.as_bytes()).unwrap(),
+            AllowedMeasurements {
+                mr_enclaves: vec![MR_ENCLAVE],
+                mr_signers: vec![MR_SIGNER],
+                allow_any: false,
+            }
+        );
+        assert!(
+            AllowedMeasurements::from_json(br#"{"allow_any":true}"#)
+                .unwrap()
+                .allow_any
+        );
+
+        for invalid in &[
+            r#"{"mr_enclaves":["not hex"]}"#,
+            r#"{"mr_enclaves":["0102"]}"#,
+            r#"{"mr_enclave":[]}"#,
+        ] {
+            assert_eq!(
+                AllowedMeasurements::from_json(invalid.as_bytes()),
+                Err(NodeAuthResult::InvalidInput)
+            );
+        }
+    }
+}
@@ -1,15 +1,23 @@
 pub use attestation::create_attestation_certificate;
-pub use offchain::{ecall_get_attestation_report, ecall_init_bootstrap, ecall_init_node};
+pub use offchain::{
+    ecall_configure_allowed_measurements, ecall_get_attestation_report, ecall_import_seed,
+    ecall_init_bootstrap, ecall_init_node, ecall_verify_attestation_expecting,
+};
 pub use onchain::ecall_authenticate_new_node;
+pub use state_snapshot::{ecall_export_contract_state, ecall_import_contract_state};
 
 mod attestation;
 mod cert;
+mod collateral;
 mod hex;
+mod measurements;
 mod offchain;
 mod onchain;
 mod persistency;
 mod report;
+mod report_cache;
 mod seed_exchange;
+mod state_snapshot;
 
 #[cfg(feature = "SGX_MODE_HW")]
 mod ocalls;
@@ -32,13 +40,42 @@ pub mod tests {
         count_failures!(failures, {
             report::tests::test_sgx_quote_parse_from();
             report::tests::test_attestation_report_from_cert();
+            report::tests::test_attestation_report_from_cert_verification_time();
             report::tests::test_attestation_report_from_cert_invalid();
             report::tests::test_attestation_report_from_cert_api_version_not_compatible();
             report::tests::test_attestation_report_test();
+            report::tests::test_parse_advisory_ids_present();
+            report::tests::test_parse_advisory_ids_missing();
             report::tests::test_attestation_dcap();
             report::tests::test_attestation_dcap_temper();
+            report::tests::test_dcap_attestation_report_round_trip();
+            report::tests::test_tdx_attestation_report_unsupported();
+            report::tests::test_dcap_quote_status_to_node_auth_result();
+            report_cache::tests::test_verified_report_is_cached();
+            report_cache::tests::test_verified_report_expires();
+            report_cache::tests::test_failed_verification_is_not_cached();
+            report_cache::tests::test_tolerated_quote_status_is_rechecked_in_production();
             cert::tests::test_certificate_valid();
             cert::tests::test_certificate_invalid_configuration_needed();
+            cert::tests::test_downgrade_reason_per_status();
+            cert::tests::test_quote_status_extra_advisories();
+            cert::tests::test_stale_svn_reason();
+            cert::tests::test_downgrade_reason_auth_result();
+            cert::tests::test_quote_status_isv_svn();
+            collateral::tests::test_collateral_parse();
+            collateral::tests::test_fresh_collateral_is_cached();
+            collateral::tests::test_expired_collateral_is_refetched();
+            offchain::tests::test_import_seed_valid();
+            offchain::tests::test_import_seed_bad_attestation();
+            offchain::tests::test_import_seed_hw_rejects_software_attestation();
+            offchain::tests::test_import_seed_overwrite_guard();
+            offchain::tests::test_verify_attestation_expecting_matching_key();
+            offchain::tests::test_verify_attestation_expecting_mismatching_key();
+            offchain::tests::test_verify_attestation_expecting_allowed_measurements();
+            offchain::tests::test_combined_attestation_type();
+            measurements::tests::test_allowed_measurements_match();
+            measurements::tests::test_check_allowed_measurements();
+            measurements::tests::test_allowed_measurements_from_json();
         });
 
         if failures != 0 {
@@ -17,21 +17,26 @@ use enclave_crypto::consts::{
     REGISTRATION_KEY_SEALING_PATH, REK_PATH, SEED_UPDATE_SAVE_PATH, SIGNATURE_TYPE,
 };
 
-use enclave_crypto::{KeyPair, Keychain, KEY_MANAGER, PUBLIC_KEY_SIZE};
+use enclave_contract_engine::AttestationType;
+use enclave_crypto::{KeyPair, Keychain, Seed, KEY_MANAGER, PUBLIC_KEY_SIZE};
 use enclave_utils::pointers::validate_mut_slice;
 use enclave_utils::storage::migrate_file_from_2_17_safe;
 use enclave_utils::tx_bytes::TX_BYTES_SEALING_PATH;
 use enclave_utils::validator_set::VALIDATOR_SET_SEALING_PATH;
 use enclave_utils::{validate_const_ptr, validate_mut_ptr};
 
-use enclave_ffi_types::SINGLE_ENCRYPTED_SEED_SIZE;
+use enclave_ffi_types::{NodeAuthResult, OUTPUT_ENCRYPTED_SEED_SIZE, SINGLE_ENCRYPTED_SEED_SIZE};
 
 use super::attestation::{create_attestation_certificate, get_quote_ecdsa};
+use super::measurements::{
+    check_allowed_measurements, configure_allowed_measurements, AllowedMeasurements,
+};
+use super::onchain::{combined_attestation_type, verify_combined_attestation_measured};
 
 use super::seed_service::get_next_consensus_seed_from_service;
 
 use super::persistency::{write_master_pub_keys, write_seed};
-use super::seed_exchange::{decrypt_seed, encrypt_seed, SeedType};
+use super::seed_exchange::{decrypt_seed, decrypt_seed_with_key, encrypt_seed, SeedType};
 use enclave_utils::storage::write_to_untrusted;
 
 ///
@@ -346,9 +351,250 @@ pub unsafe extern "C" fn ecall_init_node(
         return status;
     }
 
+    enclave_contract_engine::configure_attestation_type(registered_attestation_type());
+
     sgx_status_t::SGX_SUCCESS
 }
 
+/// The attestation this node registered with, read from the attestation it produced for
+/// registration (see `ecall_get_attestation_report`)
+fn registered_attestation_type() -> Option<AttestationType> {
+    let mut cert = vec![];
+    let read = File::open(CERT_COMBINED_PATH.as_str()).and_then(|mut f| f.read_to_end(&mut cert));
+    if let Err(e) = read {
+        debug!(
+            "Failed to read the attestation this node registered with: {}",
+            e
+        );
+        return None;
+    }
+
+    combined_attestation_type(&cert)
+}
+
+/// Verifies the sender's attestation and decrypts the genesis and current seeds that it encrypted
+/// for `registration_key`
+fn decrypt_imported_seed(
+    registration_key: &KeyPair,
+    encrypted_seed: &[u8],
+    sender_attestation: &[u8],
+) -> Result<(Seed, Seed), NodeAuthResult> {
+    let sender_public_key = verify_peer_attestation(sender_attestation)?;
+
+    if encrypted_seed.len() != OUTPUT_ENCRYPTED_SEED_SIZE as usize {
+        error!("Imported seed has the wrong length: {:?}", encrypted_seed.len());
+        return Err(NodeAuthResult::InvalidInput);
+    }
+
+    let decrypt = |encrypted: &[u8]| {
+        let mut single_seed_bytes = [0u8; SINGLE_ENCRYPTED_SEED_SIZE];
+        single_seed_bytes.copy_from_slice(encrypted);
+        decrypt_seed_with_key(registration_key, sender_public_key, single_seed_bytes)
+            .map_err(|_| NodeAuthResult::SeedEncryptionFailed)
+    };
+
+    let genesis_seed = decrypt(&encrypted_seed[..SINGLE_ENCRYPTED_SEED_SIZE])?;
+    let current_seed = decrypt(&encrypted_seed[SINGLE_ENCRYPTED_SEED_SIZE..])?;
+
+    Ok((genesis_seed, current_seed))
+}
+
+/// A node that already has a seed only imports another one when `force` is set, so that a peer
+/// can't swap out the seed of a running node
+fn check_seed_overwrite(seed_is_set: bool, force: bool) -> Result<(), NodeAuthResult> {
+    if seed_is_set && !force {
+        return Err(NodeAuthResult::SeedAlreadySet);
+    }
+    Ok(())
+}
+
+///
+/// `ecall_import_seed`
+///
+/// This function is used to onboard a node from an existing node, instead of getting the seed
+/// through on-chain registration.
+///
+/// The existing node encrypts the genesis and current seeds for this node's registration key
+/// (generated in [ecall_key_gen]) using Diffie-Hellman with its own attested key. The sender's
+/// attestation is verified the same way [ecall_authenticate_new_node] verifies a registering node,
+/// and the decrypted seeds are sealed and used to derive the consensus keys.
+///
+/// A node that already has a seed refuses the import, unless `force` is non-zero.
+///
+/// This function happens off-chain
+///
+/// # Safety
+///  Something should go here
+///
+#[no_mangle]
+pub unsafe extern "C" fn ecall_import_seed(
+    encrypted_seed: *const u8,
+    encrypted_seed_len: u32,
+    sender_attestation: *const u8,
+    sender_attestation_len: u32,
+    force: u8,
+) -> NodeAuthResult {
+    validate_const_ptr!(
+        encrypted_seed,
+        encrypted_seed_len as usize,
+        NodeAuthResult::InvalidInput,
+    );
+
+    validate_const_ptr!(
+        sender_attestation,
+        sender_attestation_len as usize,
+        NodeAuthResult::InvalidInput,
+    );
+
+    let encrypted_seed_slice = slice::from_raw_parts(encrypted_seed, encrypted_seed_len as usize);
+    let sender_attestation_slice =
+        slice::from_raw_parts(sender_attestation, sender_attestation_len as usize);
+
+    let mut key_manager = Keychain::new();
+
+    if let Err(e) = check_seed_overwrite(key_manager.is_consensus_seed_set(), force != 0) {
+        warn!("Refusing to import a seed: {}", e);
+        return e;
+    }
+
+    let registration_key = match key_manager.get_registration_key() {
+        Ok(key) => key,
+        Err(_e) => {
+            error!("Failed to unlock node key. Please make sure the file is accessible or reinitialize the node");
+            return NodeAuthResult::InvalidInput;
+        }
+    };
+
+    let (genesis_seed, current_seed) = match decrypt_imported_seed(
+        &registration_key,
+        encrypted_seed_slice,
+        sender_attestation_slice,
+    ) {
+        Ok(seeds) => seeds,
+        Err(e) => {
+            warn!("Failed to import seed: {}", e);
+            return e;
+        }
+    };
+
+    // this seals both seeds
+    if let Err(_e) = key_manager.set_consensus_seed(genesis_seed, current_seed) {
+        return NodeAuthResult::CantWriteToStorage;
+    }
+
+    if let Err(_e) = key_manager.generate_consensus_master_keys() {
+        return NodeAuthResult::CantWriteToStorage;
+    }
+
+    if let Err(_e) = write_master_pub_keys(&key_manager) {
+        return NodeAuthResult::CantWriteToStorage;
+    }
+
+    NodeAuthResult::Success
+}
+
+/// Verifies the attestation of a peer like a registering node's, and that the peer's enclave is
+/// allowed by this node's `AllowedMeasurements`. Returns the node key it attests to.
+fn verify_peer_attestation(attestation: &[u8]) -> Result<[u8; PUBLIC_KEY_SIZE], NodeAuthResult> {
+    let (public_key, measurements, _) = verify_combined_attestation_measured(attestation)?;
+
+    match check_allowed_measurements(measurements.as_ref()) {
+        NodeAuthResult::Success => Ok(public_key),
+        e => Err(e),
+    }
+}
+
+/// Verifies a peer's attestation, and that the node key it attests to is `expected_key`
+fn verify_attestation_expecting(
+    attestation: &[u8],
+    expected_key: &[u8; PUBLIC_KEY_SIZE],
+) -> NodeAuthResult {
+    match verify_peer_attestation(attestation) {
+        Ok(public_key) if &public_key == expected_key => NodeAuthResult::Success,
+        Ok(public_key) => {
+            warn!(
+                "Attested node key {} doesn't match the expected key {}",
+                hex::encode(public_key),
+                hex::encode(expected_key)
+            );
+            NodeAuthResult::NodeKeyMismatch
+        }
+        Err(e) => e,
+    }
+}
+
+///
+/// `ecall_verify_attestation_expecting`
+///
+/// This function is used during pairwise onboarding, where a node already knows which node key
+/// its peer should have. The peer's attestation is verified the same way
+/// [ecall_authenticate_new_node] verifies a registering node, and the node key it attests to must
+/// also be `expected_key`. Otherwise a genuine enclave that holds a different key could stand in
+/// for the expected peer.
+///
+/// This function happens off-chain
+///
+/// # Safety
+///  Something should go here
+///
+#[no_mangle]
+pub unsafe extern "C" fn ecall_verify_attestation_expecting(
+    attestation: *const u8,
+    attestation_len: u32,
+    expected_key: &[u8; PUBLIC_KEY_SIZE],
+) -> NodeAuthResult {
+    validate_const_ptr!(
+        attestation,
+        attestation_len as usize,
+        NodeAuthResult::InvalidInput,
+    );
+
+    validate_const_ptr!(
+        expected_key.as_ptr(),
+        expected_key.len(),
+        NodeAuthResult::InvalidInput,
+    );
+
+    let attestation_slice = slice::from_raw_parts(attestation, attestation_len as usize);
+
+    verify_attestation_expecting(attestation_slice, expected_key)
+}
+
+///
+/// `ecall_configure_allowed_measurements`
+///
+/// Sets the enclave measurements this node accepts from its peers when it imports a seed or
+/// verifies a peer's attestation, as JSON (see `AllowedMeasurements::from_json`). An empty
+/// configuration accepts any enclave, which is the default. Registration isn't affected, since
+/// all nodes must agree on it.
+///
+/// This function happens off-chain
+///
+/// # Safety
+///  Something should go here
+///
+#[no_mangle]
+pub unsafe extern "C" fn ecall_configure_allowed_measurements(
+    config: *const u8,
+    config_len: u32,
+) -> NodeAuthResult {
+    if config_len == 0 {
+        configure_allowed_measurements(None);
+        return NodeAuthResult::Success;
+    }
+
+    validate_const_ptr!(config, config_len as usize, NodeAuthResult::InvalidInput);
+    let config_slice = slice::from_raw_parts(config, config_len as usize);
+
+    match AllowedMeasurements::from_json(config_slice) {
+        Ok(allowed) => {
+            configure_allowed_measurements(Some(allowed));
+            NodeAuthResult::Success
+        }
+        Err(e) => e,
+    }
+}
+
 unsafe fn get_attestation_report_epid(
     api_key: *const u8,
     api_key_len: u32,
@@ -386,7 +632,7 @@ unsafe fn get_attestation_report_epid(
 pub unsafe fn get_attestation_report_dcap(
     kp: &KeyPair,
 ) -> Result<(Vec<u8>, Vec<u8>), sgx_status_t> {
-    let (vec_quote, vec_coll) = match get_quote_ecdsa(&kp.get_pubkey()) {
+    let (vec_quote, collateral) = match get_quote_ecdsa(&kp.get_pubkey()) {
         Ok(r) => r,
         Err(e) => {
             warn!("Error creating attestation report");
@@ -396,6 +642,7 @@ pub unsafe fn get_attestation_report_dcap(
 
     write_to_untrusted(&vec_quote, ATTESTATION_DCAP_PATH.as_str())?;
 
+    let vec_coll = collateral.to_bytes();
     write_to_untrusted(&vec_coll, COLLATERAL_DCAP_PATH.as_str())?;
 
     Ok((vec_quote, vec_coll))
@@ -616,3 +863,223 @@ pub unsafe extern "C" fn ecall_migrate_sealing() -> sgx_types::sgx_status_t {
 
     sgx_status_t::SGX_SUCCESS
 }
+
+#[cfg(feature = "test")]
+pub mod tests {
+    use std::io::Read;
+    use std::untrusted::fs::File;
+
+    use enclave_contract_engine::AttestationType;
+    use enclave_crypto::{AESKey, KeyPair, SIVEncryptable, Seed, PUBLIC_KEY_SIZE};
+    use enclave_ffi_types::NodeAuthResult;
+
+    use super::{check_seed_overwrite, decrypt_imported_seed, verify_attestation_expecting};
+    use crate::registration::measurements::{configure_allowed_measurements, AllowedMeasurements};
+    use crate::registration::onchain::{combined_attestation_type, verify_combined_attestation};
+
+    /// Wraps an EPID certificate in the combined attestation format
+    fn combined_attestation(cert: &[u8]) -> Vec<u8> {
+        let mut combined = vec![];
+        combined.extend_from_slice(&(cert.len() as u32).to_le_bytes());
+        combined.extend_from_slice(&0u32.to_le_bytes());
+        combined.extend_from_slice(&0u32.to_le_bytes());
+        combined.extend_from_slice(cert);
+        combined
+    }
+
+    fn fixture(name: &str) -> Vec<u8> {
+        let mut contents = vec![];
+        let mut f = File::open(format!("../execute/src/registration/fixtures/{}", name)).unwrap();
+        f.read_to_end(&mut contents).unwrap();
+        contents
+    }
+
+    fn sender_attestation() -> Vec<u8> {
+        combined_attestation(&fixture("attestation_cert_sw"))
+    }
+
+    /// The key is symmetric, so the receiving key pair can stand in for the sender here
+    fn encrypt_for(
+        registration_key: &KeyPair,
+        sender_public_key: [u8; PUBLIC_KEY_SIZE],
+        seed: &Seed,
+    ) -> Vec<u8> {
+        let shared_enc_key = registration_key.diffie_hellman(&sender_public_key);
+        let my_public_key = registration_key.get_pubkey();
+        let authenticated_data: Vec<&[u8]> = vec![&my_public_key];
+
+        AESKey::new_from_slice(&shared_enc_key)
+            .encrypt_siv(seed.as_slice(), Some(&authenticated_data))
+            .unwrap()
+    }
+
+    #[cfg(feature = "SGX_MODE_HW")]
+    pub fn test_import_seed_valid() {}
+
+    #[cfg(not(feature = "SGX_MODE_HW"))]
+    pub fn test_import_seed_valid() {
+        let registration_key = KeyPair::new().unwrap();
+        let attestation = sender_attestation();
+        let sender_public_key = verify_combined_attestation(&attestation).unwrap();
+
+        let genesis_seed = Seed::new().unwrap();
+        let current_seed = Seed::new().unwrap();
+        let mut encrypted_seed = encrypt_for(&registration_key, sender_public_key, &genesis_seed);
+        encrypted_seed.extend(encrypt_for(&registration_key, sender_public_key, &current_seed));
+
+        let (imported_genesis, imported_current) =
+            decrypt_imported_seed(&registration_key, &encrypted_seed, &attestation)
+                .unwrap_or_else(|e| panic!("import failed: {}", e));
+
+        assert_eq!(imported_genesis.as_slice(), genesis_seed.as_slice());
+        assert_eq!(imported_current.as_slice(), current_seed.as_slice());
+
+        // a seed that was encrypted for another node can't be imported
+        let other_registration_key = KeyPair::new().unwrap();
+        let result = decrypt_imported_seed(&other_registration_key, &encrypted_seed, &attestation);
+        assert!(matches!(result, Err(NodeAuthResult::SeedEncryptionFailed)));
+    }
+
+    pub fn test_import_seed_bad_attestation() {
+        let registration_key = KeyPair::new().unwrap();
+        let seed = Seed::new().unwrap();
+        let mut encrypted_seed = encrypt_for(&registration_key, [1u8; PUBLIC_KEY_SIZE], &seed);
+        encrypted_seed.extend(encrypt_for(&registration_key, [1u8; PUBLIC_KEY_SIZE], &seed));
+
+        let result = decrypt_imported_seed(&registration_key, &encrypted_seed, &[]);
+        assert!(matches!(result, Err(NodeAuthResult::InvalidCert)));
+
+        let garbage = combined_attestation(b"not a certificate");
+        let result = decrypt_imported_seed(&registration_key, &encrypted_seed, &garbage);
+        assert!(matches!(result, Err(NodeAuthResult::InvalidCert)));
+
+        let mut truncated = sender_attestation();
+        truncated.truncate(truncated.len() / 2);
+        let result = decrypt_imported_seed(&registration_key, &encrypted_seed, &truncated);
+        assert!(result.is_err());
+    }
+
+    #[cfg(not(feature = "SGX_MODE_HW"))]
+    pub fn test_import_seed_hw_rejects_software_attestation() {}
+
+    #[cfg(feature = "SGX_MODE_HW")]
+    pub fn test_import_seed_hw_rejects_software_attestation() {
+        let registration_key = KeyPair::new().unwrap();
+        let seed = Seed::new().unwrap();
+        let mut encrypted_seed = encrypt_for(&registration_key, [1u8; PUBLIC_KEY_SIZE], &seed);
+        encrypted_seed.extend(encrypt_for(
+            &registration_key,
+            [1u8; PUBLIC_KEY_SIZE],
+            &seed,
+        ));
+
+        // a peer running in simulation can't hand its seed to a hardware node
+        let result =
+            decrypt_imported_seed(&registration_key, &encrypted_seed, &sender_attestation());
+        assert!(result.is_err());
+    }
+
+    pub fn test_import_seed_overwrite_guard() {
+        assert_eq!(check_seed_overwrite(false, false), Ok(()));
+        assert_eq!(check_seed_overwrite(false, true), Ok(()));
+        assert_eq!(
+            check_seed_overwrite(true, false),
+            Err(NodeAuthResult::SeedAlreadySet)
+        );
+        assert_eq!(check_seed_overwrite(true, true), Ok(()));
+    }
+
+    #[cfg(feature = "SGX_MODE_HW")]
+    pub fn test_verify_attestation_expecting_matching_key() {}
+
+    #[cfg(not(feature = "SGX_MODE_HW"))]
+    pub fn test_verify_attestation_expecting_matching_key() {
+        let attestation = sender_attestation();
+        let node_key = verify_combined_attestation(&attestation).unwrap();
+
+        assert_eq!(
+            verify_attestation_expecting(&attestation, &node_key),
+            NodeAuthResult::Success
+        );
+    }
+
+    #[cfg(feature = "SGX_MODE_HW")]
+    pub fn test_verify_attestation_expecting_mismatching_key() {}
+
+    #[cfg(not(feature = "SGX_MODE_HW"))]
+    pub fn test_verify_attestation_expecting_mismatching_key() {
+        let attestation = sender_attestation();
+        let mut other_key = verify_combined_attestation(&attestation).unwrap();
+        other_key[0] ^= 1;
+
+        assert_eq!(
+            verify_attestation_expecting(&attestation, &other_key),
+            NodeAuthResult::NodeKeyMismatch
+        );
+
+        // an invalid attestation is rejected before the key is compared
+        let garbage = combined_attestation(b"not a certificate");
+        assert_eq!(
+            verify_attestation_expecting(&garbage, &other_key),
+            NodeAuthResult::InvalidCert
+        );
+    }
+
+    #[cfg(feature = "SGX_MODE_HW")]
+    pub fn test_verify_attestation_expecting_allowed_measurements() {}
+
+    #[cfg(not(feature = "SGX_MODE_HW"))]
+    pub fn test_verify_attestation_expecting_allowed_measurements() {
+        let attestation = sender_attestation();
+        let node_key = verify_combined_attestation(&attestation).unwrap();
+
+        // software mode attestations have no measurements, so no pinned measurement matches them
+        configure_allowed_measurements(Some(AllowedMeasurements {
+            mr_signers: vec![[1u8; 32]],
+            ..Default::default()
+        }));
+        assert_eq!(
+            verify_attestation_expecting(&attestation, &node_key),
+            NodeAuthResult::MeasurementNotAllowed
+        );
+
+        configure_allowed_measurements(Some(AllowedMeasurements {
+            mr_signers: vec![[1u8; 32]],
+            allow_any: true,
+            ..Default::default()
+        }));
+        assert_eq!(
+            verify_attestation_expecting(&attestation, &node_key),
+            NodeAuthResult::Success
+        );
+
+        configure_allowed_measurements(None);
+    }
+
+    pub fn test_combined_attestation_type() {
+        assert_eq!(
+            combined_attestation_type(&sender_attestation()),
+            Some(AttestationType::SgxEpid)
+        );
+
+        // nodes that can attest both ways are verified with DCAP
+        let cert = fixture("attestation_cert_sw");
+        let quote = fixture("attestation_dcap.quote");
+        let collateral = fixture("attestation_dcap.collateral");
+        let mut combined = vec![];
+        combined.extend_from_slice(&(cert.len() as u32).to_le_bytes());
+        combined.extend_from_slice(&(quote.len() as u32).to_le_bytes());
+        combined.extend_from_slice(&(collateral.len() as u32).to_le_bytes());
+        combined.extend_from_slice(&cert);
+        combined.extend_from_slice(&quote);
+        combined.extend_from_slice(&collateral);
+        assert_eq!(
+            combined_attestation_type(&combined),
+            Some(AttestationType::SgxDcap)
+        );
+
+        // a node that didn't produce an attestation didn't register
+        assert_eq!(combined_attestation_type(&[]), None);
+        assert_eq!(combined_attestation_type(&combined_attestation(&[])), None);
+    }
+}
@@ -4,10 +4,11 @@
 use log::*;
 use std::panic;
 
+use enclave_contract_engine::AttestationType;
 use enclave_ffi_types::NodeAuthResult;
 
-use crate::registration::attestation::verify_quote_ecdsa;
 use crate::registration::cert::verify_ra_report;
+use crate::registration::report::EndorsedDcapAttestationReport;
 use crate::registration::seed_exchange::SeedType;
 
 use enclave_crypto::consts::OUTPUT_ENCRYPTED_SEED_SIZE;
@@ -17,11 +18,10 @@ use enclave_utils::{
     validate_const_ptr, validate_mut_ptr,
 };
 
-use sgx_types::sgx_ql_qv_result_t;
-
 use enclave_crypto::consts::SigningMethod;
 
-use super::cert::verify_ra_cert;
+use super::cert::{verify_ra_cert_measured, DowngradeReason};
+use super::measurements::Measurements;
 use super::seed_exchange::encrypt_seed;
 use core::mem;
 use std::slice;
@@ -32,15 +32,16 @@ use enclave_contract_engine::check_cert_in_current_block;
 #[cfg(feature = "light-client-validation")]
 use block_verifier::VERIFIED_BLOCK_MESSAGES;
 
+/// The time of the last verified block, in seconds, or 0 if there isn't one
 #[cfg(feature = "light-client-validation")]
-fn get_current_block_time_s() -> i64 {
+pub(super) fn get_current_block_time_s() -> i64 {
     let verified_msgs = VERIFIED_BLOCK_MESSAGES.lock().unwrap();
     let tm_ns = verified_msgs.time();
     (tm_ns / 1000000000) as i64
 }
 
 #[cfg(not(feature = "light-client-validation"))]
-fn get_current_block_time_s() -> i64 {
+pub(super) fn get_current_block_time_s() -> i64 {
     return 0 as i64;
 }
 
@@ -74,9 +75,18 @@ fn split_combined_cert(cert: *const u8, cert_len: u32) -> (Vec<u8>, Vec<u8>, Vec
     (vec_cert, vec_quote, vec_coll)
 }
 
-fn verify_attestation_epid(cert_slice: &[u8], pub_key: &mut [u8; 32]) -> NodeAuthResult {
-    let pk = match verify_ra_cert(cert_slice, None, true) {
-        Ok(retval) => retval,
+fn verify_attestation_epid(
+    cert_slice: &[u8],
+    pub_key: &mut [u8; 32],
+    measurements: &mut Option<Measurements>,
+    downgrade_reason: &mut Option<DowngradeReason>,
+) -> NodeAuthResult {
+    let pk = match verify_ra_cert_measured(cert_slice, None, true) {
+        Ok((retval, retval_measurements, retval_downgrade_reason)) => {
+            *measurements = retval_measurements;
+            *downgrade_reason = retval_downgrade_reason;
+            retval
+        }
         Err(e) => {
             return e;
         }
@@ -97,25 +107,21 @@ fn verify_attestation_epid(cert_slice: &[u8], pub_key: &mut [u8; 32]) -> NodeAut
 }
 
 fn verify_attestation_dcap(
-    vec_quote: &[u8],
-    vec_coll: &[u8],
+    report: &EndorsedDcapAttestationReport,
     pub_key: &mut [u8; 32],
+    measurements: &mut Option<Measurements>,
 ) -> NodeAuthResult {
     let tm_s = get_current_block_time_s();
     trace!("Current block time: {}", tm_s);
 
     // test self
-    let report_body = match verify_quote_ecdsa(vec_quote, vec_coll, tm_s) {
-        Ok(r) => {
+    let report_body = match report.verify(tm_s) {
+        Ok(report_body) => {
             trace!("Remote quote verified ok");
-            if r.1 != sgx_ql_qv_result_t::SGX_QL_QV_RESULT_OK {
-                trace!("WARNING: {}", r.1);
-            }
-            r.0
+            report_body
         }
         Err(e) => {
-            trace!("Remote quote verification failed: {}", e);
-            return NodeAuthResult::InvalidCert;
+            return e;
         }
     };
 
@@ -129,10 +135,79 @@ fn verify_attestation_dcap(
     }
 
     pub_key.copy_from_slice(&report_body.report_data.d[..32]);
+    *measurements = Some(Measurements {
+        mr_enclave: report_body.mr_enclave.m,
+        mr_signer: report_body.mr_signer.m,
+    });
 
     NodeAuthResult::Success
 }
 
+/// Verifies a combined EPID/DCAP attestation (as produced by `ecall_get_attestation_report`)
+/// and returns the public key of the attested enclave
+pub(super) fn verify_combined_attestation(cert: &[u8]) -> Result<[u8; 32], NodeAuthResult> {
+    verify_combined_attestation_measured(cert).map(|(public_key, _, _)| public_key)
+}
+
+/// Like `verify_combined_attestation`, and also returns the measurements of the attested enclave,
+/// if the attestation has them, and why a vulnerable platform was admitted, if it was. Only EPID
+/// attestations are admitted with a downgrade.
+pub(super) fn verify_combined_attestation_measured(
+    cert: &[u8],
+) -> Result<([u8; 32], Option<Measurements>, Option<DowngradeReason>), NodeAuthResult> {
+    let mut public_key: [u8; 32] = [0u8; 32];
+    let mut measurements = None;
+    let mut downgrade_reason = None;
+
+    let (vec_cert, vec_quote, vec_coll) = split_combined_cert(cert.as_ptr(), cert.len() as u32);
+
+    if vec_quote.is_empty() || vec_coll.is_empty() {
+        if vec_cert.is_empty() {
+            warn!("No valid attestation method provided");
+            return Err(NodeAuthResult::InvalidCert);
+        }
+
+        trace!("EPID attestation");
+
+        let res = verify_attestation_epid(
+            vec_cert.as_slice(),
+            &mut public_key,
+            &mut measurements,
+            &mut downgrade_reason,
+        );
+        if NodeAuthResult::Success != res {
+            return Err(res);
+        }
+    } else {
+        trace!("DCAP attestation");
+
+        let report = EndorsedDcapAttestationReport {
+            quote: vec_quote,
+            collateral: vec_coll,
+        };
+        let res = verify_attestation_dcap(&report, &mut public_key, &mut measurements);
+        if NodeAuthResult::Success != res {
+            return Err(res);
+        }
+    }
+
+    Ok((public_key, measurements, downgrade_reason))
+}
+
+/// The attestation a combined attestation is verified with, chosen like in
+/// `verify_combined_attestation`: DCAP when it has a quote and collateral, EPID otherwise
+pub(super) fn combined_attestation_type(cert: &[u8]) -> Option<AttestationType> {
+    let (vec_cert, vec_quote, vec_coll) = split_combined_cert(cert.as_ptr(), cert.len() as u32);
+
+    if !vec_quote.is_empty() && !vec_coll.is_empty() {
+        Some(AttestationType::SgxDcap)
+    } else if !vec_cert.is_empty() {
+        Some(AttestationType::SgxEpid)
+    } else {
+        None
+    }
+}
+
 ///
 /// `ecall_authenticate_new_node`
 ///
@@ -143,6 +218,9 @@ fn verify_attestation_dcap(
 /// The seed is encrypted with a key derived from the secret master key of the chain, and the public
 /// key of the requesting chain
 ///
+/// A vulnerable platform that a non-production enclave admits anyway gets the reason in
+/// `downgrade`, which is `NodeAuthResult::Success` for a platform that isn't vulnerable
+///
 /// This function happens on-chain, so any panic here might cause the chain to go boom
 ///
 /// # Safety
@@ -153,6 +231,7 @@ pub unsafe extern "C" fn ecall_authenticate_new_node(
     cert_len: u32,
     // seed structure 1 byte - length (96 or 48) | genesis seed bytes | current seed bytes (optional)
     seed: &mut [u8; OUTPUT_ENCRYPTED_SEED_SIZE as usize],
+    downgrade: &mut NodeAuthResult,
 ) -> NodeAuthResult {
     if let Err(_err) = oom_handler::register_oom_handler() {
         error!("Could not register OOM handler!");
@@ -160,6 +239,11 @@ pub unsafe extern "C" fn ecall_authenticate_new_node(
     }
 
     validate_mut_ptr!(seed.as_mut_ptr(), seed.len(), NodeAuthResult::InvalidInput);
+    validate_mut_ptr!(
+        downgrade as *mut NodeAuthResult as _,
+        std::mem::size_of::<NodeAuthResult>(),
+        NodeAuthResult::InvalidInput
+    );
     validate_const_ptr!(cert, cert_len as usize, NodeAuthResult::InvalidInput);
 
     let cert_slice = std::slice::from_raw_parts(cert, cert_len as usize);
@@ -169,30 +253,15 @@ pub unsafe extern "C" fn ecall_authenticate_new_node(
         return NodeAuthResult::SignatureInvalid;
     }
 
-    let mut target_public_key: [u8; 32] = [0u8; 32];
-
-    let (vec_cert, vec_quote, vec_coll) = split_combined_cert(cert, cert_len);
-
-    if vec_quote.is_empty() || vec_coll.is_empty() {
-        if vec_cert.is_empty() {
-            warn!("No valid attestation method provided");
-            return NodeAuthResult::InvalidCert;
+    let target_public_key = match verify_combined_attestation_measured(cert_slice) {
+        Ok((public_key, _, downgrade_reason)) => {
+            *downgrade = downgrade_reason
+                .as_ref()
+                .map_or(NodeAuthResult::Success, NodeAuthResult::from);
+            public_key
         }
-
-        trace!("EPID attestation");
-
-        let res = verify_attestation_epid(vec_cert.as_slice(), &mut target_public_key);
-        if NodeAuthResult::Success != res {
-            return res;
-        }
-    } else {
-        trace!("DCAP attestation");
-
-        let res = verify_attestation_dcap(&vec_quote, &vec_coll, &mut target_public_key);
-        if NodeAuthResult::Success != res {
-            return res;
-        }
-    }
+        Err(e) => return e,
+    };
 
     let result = panic::catch_unwind(|| -> Result<Vec<u8>, NodeAuthResult> {
         trace!(
@@ -8,7 +8,7 @@ use log::{error, warn};
 use sgx_types::{sgx_platform_info_t, sgx_status_t, sgx_update_info_bit_t};
 
 pub fn print_local_report_info(cert: &[u8]) {
-    let report = match AttestationReport::from_cert(cert) {
+    let report = match AttestationReport::from_cert(cert, None) {
         Ok(r) => r,
         Err(_) => {
             error!("Error parsing report");
@@ -17,8 +17,8 @@ pub fn print_local_report_info(cert: &[u8]) {
     };
 
     let node_auth_result = NodeAuthResult::from(&report.sgx_quote_status);
-    // print
-    match verify_quote_status(&report, &report.advisory_ids) {
+    // print what the network will make of this report, which only goes by the enclave's whitelist
+    match verify_quote_status(&report, &report.advisory_ids, None) {
         Err(status) => match status {
             NodeAuthResult::SwHardeningAndConfigurationNeeded => {
                 println!("Platform status is SW_HARDENING_AND_CONFIGURATION_NEEDED. This means is updated but requires further BIOS configuration");
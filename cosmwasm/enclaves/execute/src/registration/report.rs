@@ -19,8 +19,12 @@ use serde_json::Value;
 use uuid::Uuid;
 
 use enclave_ffi_types::NodeAuthResult;
+use sgx_types::{sgx_ql_qv_result_t, sgx_report_body_t};
 
+use super::attestation::verify_quote_ecdsa_status;
 use super::cert::{get_ias_auth_config, get_netscape_comment};
+use super::collateral::Collateral;
+use super::onchain::get_current_block_time_s;
 
 #[cfg(feature = "test")]
 use sgx_types::sgx_quote_t;
@@ -59,6 +63,66 @@ pub struct EndorsedAttestationReport {
     pub signing_cert: Vec<u8>,
 }
 
+/// The DCAP counterpart of `EndorsedAttestationReport`: an ECDSA quote generated by the
+/// hardware, endorsed by the collateral (PCK certificates, CRLs and TCB info) it's verified with.
+#[derive(Default, Serialize, Deserialize)]
+pub struct EndorsedDcapAttestationReport {
+    /// Quote generated by the hardware and signed by the quoting enclave
+    #[serde(serialize_with = "as_base64", deserialize_with = "from_base64")]
+    pub quote: Vec<u8>,
+    /// Collateral the quote is verified with
+    #[serde(serialize_with = "as_base64", deserialize_with = "from_base64")]
+    pub collateral: Vec<u8>,
+}
+
+impl EndorsedDcapAttestationReport {
+    /// Verifies the quote with the quote verification enclave, as of `time_s` (0 for the local
+    /// time), and returns the report body it attests to
+    pub fn verify(&self, time_s: i64) -> Result<sgx_report_body_t, NodeAuthResult> {
+        let collateral = Collateral::parse(&self.collateral).map_err(|e| {
+            trace!("Remote quote collateral is malformed: {}", e);
+            NodeAuthResult::InvalidCert
+        })?;
+        let (report_body, qv_result) = verify_quote_ecdsa_status(&self.quote, &collateral, time_s)
+            .map_err(|e| {
+                trace!("Remote quote verification failed: {}", e);
+                NodeAuthResult::InvalidCert
+            })?;
+
+        match qv_result {
+            sgx_ql_qv_result_t::SGX_QL_QV_RESULT_OK => Ok(report_body),
+            sgx_ql_qv_result_t::SGX_QL_QV_RESULT_SW_HARDENING_NEEDED => {
+                trace!("WARNING: {}", qv_result);
+                Ok(report_body)
+            }
+            _ => {
+                trace!("Quote verification result: {}", qv_result);
+                Err(NodeAuthResult::from(&SgxQuoteStatus::from(qv_result)))
+            }
+        }
+    }
+}
+
+/// The TDX counterpart of `EndorsedDcapAttestationReport`: a TD quote, endorsed by the collateral
+/// it's verified with. Nodes can't register with it until the enclave can verify TD quotes.
+#[derive(Default, Serialize, Deserialize)]
+pub struct EndorsedTdxAttestationReport {
+    /// Quote generated by the TDX module and signed by the quoting enclave
+    #[serde(serialize_with = "as_base64", deserialize_with = "from_base64")]
+    pub quote: Vec<u8>,
+    /// Collateral the quote is verified with
+    #[serde(serialize_with = "as_base64", deserialize_with = "from_base64")]
+    pub collateral: Vec<u8>,
+}
+
+impl EndorsedTdxAttestationReport {
+    /// Always fails with `UnsupportedAttestationType`, as there's no ocall to verify TD quotes with
+    pub fn verify(&self, _time_s: i64) -> Result<sgx_report_body_t, NodeAuthResult> {
+        trace!("TDX attestation isn't supported yet");
+        Err(NodeAuthResult::UnsupportedAttestationType)
+    }
+}
+
 fn as_base64<S>(key: &[u8], serializer: S) -> Result<S::Ok, S::Error>
 where
     S: Serializer,
@@ -257,7 +321,7 @@ pub enum SgxEcdsaQuoteAkType {
 }
 
 /// SGX Quote status
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Eq, Debug, Clone)]
 pub enum SgxQuoteStatus {
     /// EPID signature of the ISV enclave QUOTE was verified correctly and the
     /// TCB level of the SGX platform is up-to-date.
@@ -357,6 +421,32 @@ impl From<&SgxQuoteStatus> for NodeAuthResult {
     }
 }
 
+impl From<sgx_ql_qv_result_t> for SgxQuoteStatus {
+    fn from(qv_result: sgx_ql_qv_result_t) -> Self {
+        match qv_result {
+            sgx_ql_qv_result_t::SGX_QL_QV_RESULT_OK => SgxQuoteStatus::OK,
+            sgx_ql_qv_result_t::SGX_QL_QV_RESULT_CONFIG_NEEDED => {
+                SgxQuoteStatus::ConfigurationNeeded
+            }
+            sgx_ql_qv_result_t::SGX_QL_QV_RESULT_OUT_OF_DATE => SgxQuoteStatus::OutOfDate,
+            sgx_ql_qv_result_t::SGX_QL_QV_RESULT_OUT_OF_DATE_CONFIG_NEEDED => {
+                SgxQuoteStatus::OutOfDateConfigurationNeeded
+            }
+            sgx_ql_qv_result_t::SGX_QL_QV_RESULT_INVALID_SIGNATURE => {
+                SgxQuoteStatus::SignatureInvalid
+            }
+            sgx_ql_qv_result_t::SGX_QL_QV_RESULT_REVOKED => SgxQuoteStatus::KeyRevoked,
+            sgx_ql_qv_result_t::SGX_QL_QV_RESULT_SW_HARDENING_NEEDED => {
+                SgxQuoteStatus::SwHardeningNeeded
+            }
+            sgx_ql_qv_result_t::SGX_QL_QV_RESULT_CONFIG_AND_SW_HARDENING_NEEDED => {
+                SgxQuoteStatus::ConfigurationAndSwHardeningNeeded
+            }
+            _ => SgxQuoteStatus::UnknownBadStatus,
+        }
+    }
+}
+
 impl From<&str> for SgxQuoteStatus {
     /// Convert from str status from the report to enum.
     fn from(status: &str) -> Self {
@@ -573,10 +663,25 @@ pub struct AdvisoryIDs(pub Vec<String>);
 
 #[cfg(feature = "SGX_MODE_HW")]
 impl AdvisoryIDs {
-    pub(crate) fn vulnerable(&self) -> Vec<String> {
+    /// Whether `id` is whitelisted, either by the enclave or in `extra_advisories`
+    fn is_accepted(id: &str, extra_advisories: Option<&AdvisoryIDs>) -> bool {
+        WHITELISTED_ADVISORIES.contains(&id)
+            || extra_advisories.map_or(false, |extra| extra.0.iter().any(|i| i == id))
+    }
+
+    /// The IDs of the advisories that aren't whitelisted, without their descriptions
+    pub(crate) fn vulnerable_ids(&self, extra_advisories: Option<&AdvisoryIDs>) -> Vec<String> {
+        self.0
+            .iter()
+            .filter(|i| !Self::is_accepted(i, extra_advisories))
+            .cloned()
+            .collect()
+    }
+
+    pub(crate) fn vulnerable(&self, extra_advisories: Option<&AdvisoryIDs>) -> Vec<String> {
         let mut vulnerable: Vec<String> = vec![];
         for i in self.0.iter() {
-            if !WHITELISTED_ADVISORIES.contains(&i.as_str()) {
+            if !Self::is_accepted(i, extra_advisories) {
                 vulnerable.push(i.clone());
                 if let Some(v) = ADVISORY_DESC.get(&i.as_str()) {
                     vulnerable.push((*v).to_string())
@@ -604,13 +709,75 @@ pub struct AttestationReport {
     pub tcb_eval_data_number: u16,
 }
 
+/// How a report without an `advisoryIDs` field is treated. IAS only sends the field with quote
+/// statuses that come with advisories, so a report with an `OK` status never needs it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MissingAdvisories {
+    /// Fail the verification, since the report may be hiding the platform's advisories
+    Reject,
+    /// Take the platform to have no advisories
+    AssumeNone,
+}
+
+#[cfg(feature = "production")]
+const MISSING_ADVISORIES: MissingAdvisories = MissingAdvisories::Reject;
+
+// testnets are lenient, like they are with quote statuses
+#[cfg(not(feature = "production"))]
+const MISSING_ADVISORIES: MissingAdvisories = MissingAdvisories::AssumeNone;
+
+/// The advisories in `attn_report`, a report with the quote status `status`
+fn parse_advisory_ids(
+    attn_report: &Value,
+    status: &SgxQuoteStatus,
+    missing: MissingAdvisories,
+) -> Result<AdvisoryIDs, Error> {
+    match attn_report.get("advisoryIDs") {
+        Some(raw) => serde_json::from_value(raw.clone())
+            .map(AdvisoryIDs)
+            .map_err(|_| {
+                warn!("Failed to decode advisories");
+                Error::ReportParseError
+            }),
+        None if status == &SgxQuoteStatus::OK || missing == MissingAdvisories::AssumeNone => {
+            Ok(AdvisoryIDs(vec![]))
+        }
+        None => {
+            warn!(
+                "Report with quote status {:?} is missing its advisories",
+                status
+            );
+            Err(Error::ReportValidationError)
+        }
+    }
+}
+
+/// The time that IAS certificates are validated at when no block was verified yet, e.g. while a
+/// node that isn't synced registers. Set to 09.08.24 (dd.mm.yy)
+const FALLBACK_VERIFICATION_TIME_S: u64 = 1_723_218_496;
+
+/// The time of the current block, which all nodes agree on, or `FALLBACK_VERIFICATION_TIME_S`
+/// without one
+pub(super) fn current_verification_time_s() -> u64 {
+    match get_current_block_time_s() {
+        block_time_s if block_time_s > 0 => block_time_s as u64,
+        _ => {
+            warn!("No verified block time, validating the certificate at a fixed time");
+            FALLBACK_VERIFICATION_TIME_S
+        }
+    }
+}
+
 impl AttestationReport {
     /// Construct a AttestationReport from a X509 certificate and verify
     /// attestation report with the report_ca_cert which is from the attestation
     /// service provider.
+    ///
+    /// The certificates are validated at `verification_time`, in seconds since the epoch, or at
+    /// the current block time if it's `None`
     // just unused in SW mode
     #[allow(dead_code)]
-    pub fn from_cert(cert: &[u8]) -> Result<Self, Error> {
+    pub fn from_cert(cert: &[u8], verification_time: Option<u64>) -> Result<Self, Error> {
         let payload = get_netscape_comment(cert).map_err(|_err| {
             error!("Failed to get netscape comment");
             Error::ReportParseError
@@ -635,8 +802,10 @@ impl AttestationReport {
 
         let chain: Vec<&[u8]> = vec![&ias_cert];
 
-        // set as 04.11.23(dd.mm.yy) - should be valid for the foreseeable future, and not rely on SystemTime
-        let time_stamp = webpki::Time::from_seconds_since_unix_epoch(1723218496);
+        // not relying on SystemTime, which the host controls
+        let time_stamp = webpki::Time::from_seconds_since_unix_epoch(
+            verification_time.unwrap_or_else(current_verification_time_s),
+        );
 
         // note: there's no way to not validate the time, and we don't want to write this code
         // ourselves. We also can't just ignore the error message, since that means that the rest of
@@ -714,14 +883,7 @@ impl AttestationReport {
             SgxQuote::parse_from(quote_raw.as_slice())?
         };
 
-        let advisories: Vec<String> = if let Some(raw) = attn_report.get("advisoryIDs") {
-            serde_json::from_value(raw.clone()).map_err(|_| {
-                warn!("Failed to decode advisories");
-                Error::ReportParseError
-            })?
-        } else {
-            vec![]
-        };
+        let advisory_ids = parse_advisory_ids(&attn_report, &sgx_quote_status, MISSING_ADVISORIES)?;
 
         let tcb_eval_data_number = attn_report["tcbEvaluationDataNumber"]
             .as_u64()
@@ -747,7 +909,7 @@ impl AttestationReport {
             sgx_quote_status,
             sgx_quote_body,
             platform_info_blob,
-            advisory_ids: AdvisoryIDs(advisories),
+            advisory_ids,
             tcb_eval_data_number,
         })
     }
@@ -905,7 +1067,7 @@ pub mod tests {
 
     pub fn test_attestation_report_from_cert() {
         let tls_ra_cert = tls_ra_cert_der_v4();
-        let report = AttestationReport::from_cert(&tls_ra_cert);
+        let report = AttestationReport::from_cert(&tls_ra_cert, None);
         assert!(report.is_ok());
 
         let report = report.unwrap();
@@ -914,22 +1076,74 @@ pub mod tests {
 
     pub fn test_attestation_report_from_cert_invalid() {
         let tls_ra_cert = tls_ra_cert_der_v4();
-        let report = AttestationReport::from_cert(&tls_ra_cert);
+        let report = AttestationReport::from_cert(&tls_ra_cert, None);
         assert!(report.is_ok());
 
         let report = report.unwrap();
         assert_eq!(report.sgx_quote_status, SgxQuoteStatus::GroupOutOfDate);
     }
 
+    pub fn test_attestation_report_from_cert_verification_time() {
+        let tls_ra_cert = tls_ra_cert_der_v4();
+
+        let report = AttestationReport::from_cert(&tls_ra_cert, Some(FALLBACK_VERIFICATION_TIME_S));
+        assert!(report.is_ok());
+
+        // before the IAS certificates' notBefore
+        let report = AttestationReport::from_cert(&tls_ra_cert, Some(1_262_304_000));
+        assert!(matches!(report, Err(Error::ReportValidationError)));
+    }
+
     pub fn test_attestation_report_from_cert_api_version_not_compatible() {
         let tls_ra_cert = tls_ra_cert_der_v3();
-        let report = AttestationReport::from_cert(&tls_ra_cert);
+        let report = AttestationReport::from_cert(&tls_ra_cert, None);
         assert!(report.is_err());
     }
 
+    pub fn test_parse_advisory_ids_present() {
+        let mut attn_report = attesation_report();
+        attn_report["advisoryIDs"] = json!(["INTEL-SA-00334", "INTEL-SA-00615"]);
+
+        for missing in &[MissingAdvisories::Reject, MissingAdvisories::AssumeNone] {
+            let advisories =
+                parse_advisory_ids(&attn_report, &SgxQuoteStatus::SwHardeningNeeded, *missing)
+                    .unwrap();
+            assert_eq!(advisories.0, vec!["INTEL-SA-00334", "INTEL-SA-00615"]);
+        }
+
+        attn_report["advisoryIDs"] = json!("INTEL-SA-00334");
+        assert!(matches!(
+            parse_advisory_ids(
+                &attn_report,
+                &SgxQuoteStatus::SwHardeningNeeded,
+                MissingAdvisories::AssumeNone
+            ),
+            Err(Error::ReportParseError)
+        ));
+    }
+
+    pub fn test_parse_advisory_ids_missing() {
+        let attn_report = attesation_report();
+        let status = SgxQuoteStatus::SwHardeningNeeded;
+
+        assert!(matches!(
+            parse_advisory_ids(&attn_report, &status, MissingAdvisories::Reject),
+            Err(Error::ReportValidationError)
+        ));
+        let advisories =
+            parse_advisory_ids(&attn_report, &status, MissingAdvisories::AssumeNone).unwrap();
+        assert!(advisories.0.is_empty());
+
+        // an OK quote doesn't come with advisories
+        let advisories =
+            parse_advisory_ids(&attn_report, &SgxQuoteStatus::OK, MissingAdvisories::Reject)
+                .unwrap();
+        assert!(advisories.0.is_empty());
+    }
+
     pub fn test_attestation_report_test() {
         let tls_ra_cert = tls_ra_cert_der_test();
-        let report = AttestationReport::from_cert(&tls_ra_cert);
+        let report = AttestationReport::from_cert(&tls_ra_cert, None);
 
         if report.is_err() {
             println!("err: {:?}", report)
@@ -938,7 +1152,7 @@ pub mod tests {
         assert!(report.is_ok());
     }
 
-    fn load_attestation_dcap() -> (Vec<u8>, Vec<u8>, i64) {
+    fn load_attestation_dcap() -> (Vec<u8>, Collateral, i64) {
         let mut vec_quote = vec![];
         {
             let mut f =
@@ -954,18 +1168,18 @@ pub mod tests {
             .unwrap();
             f.read_to_end(&mut vec_coll).unwrap();
         }
-        (vec_quote, vec_coll, 1709649832)
+        (vec_quote, Collateral::parse(&vec_coll).unwrap(), 1709649832)
     }
 
     pub fn test_attestation_dcap() {
-        let (vec_quote, vec_coll, time_s) = load_attestation_dcap();
+        let (vec_quote, collateral, time_s) = load_attestation_dcap();
 
-        let res = verify_quote_ecdsa(&vec_quote, &vec_coll, time_s);
+        let res = verify_quote_ecdsa(&vec_quote, &collateral, time_s);
         assert!(res.is_ok());
     }
 
     pub fn test_attestation_dcap_temper() {
-        let (mut vec_quote, mut vec_coll, time_s) = load_attestation_dcap();
+        let (mut vec_quote, collateral, time_s) = load_attestation_dcap();
 
         // tamper with quote
         let mut my_p_quote = vec_quote.as_mut_ptr() as *mut sgx_quote_t;
@@ -975,7 +1189,66 @@ pub mod tests {
             p_data.d[6] = p_data.d[6] ^ 4;
         };
 
-        let res = verify_quote_ecdsa(&vec_quote, &vec_coll, time_s);
+        let res = verify_quote_ecdsa(&vec_quote, &collateral, time_s);
         assert!(!res.is_ok());
     }
+
+    pub fn test_dcap_attestation_report_round_trip() {
+        let (quote, collateral, time_s) = load_attestation_dcap();
+        let report = EndorsedDcapAttestationReport {
+            quote,
+            collateral: collateral.to_bytes(),
+        };
+
+        let serialized = serde_json::to_vec(&report).unwrap();
+        let report: EndorsedDcapAttestationReport = serde_json::from_slice(&serialized).unwrap();
+
+        let (expected_body, _) = verify_quote_ecdsa(&report.quote, &collateral, time_s).unwrap();
+        let report_body = report.verify(time_s).unwrap();
+        assert_eq!(report_body.mr_enclave.m, expected_body.mr_enclave.m);
+        assert_eq!(report_body.report_data.d, expected_body.report_data.d);
+    }
+
+    pub fn test_tdx_attestation_report_unsupported() {
+        let report = EndorsedTdxAttestationReport {
+            quote: vec![1, 2, 3],
+            collateral: vec![4, 5],
+        };
+
+        let serialized = serde_json::to_vec(&report).unwrap();
+        let report: EndorsedTdxAttestationReport = serde_json::from_slice(&serialized).unwrap();
+        assert_eq!(report.quote, vec![1, 2, 3]);
+        assert_eq!(report.collateral, vec![4, 5]);
+
+        assert!(matches!(
+            report.verify(0),
+            Err(NodeAuthResult::UnsupportedAttestationType)
+        ));
+    }
+
+    pub fn test_dcap_quote_status_to_node_auth_result() {
+        let node_auth_result =
+            |qv_result: sgx_ql_qv_result_t| NodeAuthResult::from(&SgxQuoteStatus::from(qv_result));
+
+        assert_eq!(
+            node_auth_result(sgx_ql_qv_result_t::SGX_QL_QV_RESULT_CONFIG_NEEDED),
+            NodeAuthResult::ConfigurationNeeded
+        );
+        assert_eq!(
+            node_auth_result(sgx_ql_qv_result_t::SGX_QL_QV_RESULT_CONFIG_AND_SW_HARDENING_NEEDED),
+            NodeAuthResult::SwHardeningAndConfigurationNeeded
+        );
+        assert_eq!(
+            node_auth_result(sgx_ql_qv_result_t::SGX_QL_QV_RESULT_REVOKED),
+            NodeAuthResult::KeyRevoked
+        );
+        assert_eq!(
+            node_auth_result(sgx_ql_qv_result_t::SGX_QL_QV_RESULT_OUT_OF_DATE),
+            NodeAuthResult::BadQuoteStatus
+        );
+        assert_eq!(
+            node_auth_result(sgx_ql_qv_result_t::SGX_QL_QV_RESULT_UNSPECIFIED),
+            NodeAuthResult::BadQuoteStatus
+        );
+    }
 }
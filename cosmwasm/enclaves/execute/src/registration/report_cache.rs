@@ -0,0 +1,201 @@
+#![cfg_attr(not(feature = "SGX_MODE_HW"), allow(unused))]
+//! A cache of the IAS reports that `verify_ra_cert` accepted, so that verifying the same peer
+//! again doesn't redo the certificate chain validation with webpki.
+//!
+//! Reports are keyed by the SHA-256 of the endorsed report (the report, its signature and its
+//! signing certificate), and the cache keeps the node key the report attests to, the measurements
+//! of the enclave and the quote status. An entry expires `VERIFIED_REPORT_TTL_S` after it was
+//! verified, in verification time, so that changes to the platforms Intel revoked eventually take
+//! effect.
+//!
+//! What's cached depends on what a node verified before, so consensus paths don't use the cache.
+
+use std::sync::SgxMutex;
+
+use lazy_static::lazy_static;
+use log::*;
+use lru::LruCache;
+
+use enclave_crypto::{sha_256, HASH_SIZE};
+use enclave_ffi_types::NodeAuthResult;
+
+use super::cert::DowngradeReason;
+use super::measurements::Measurements;
+use super::report::SgxQuoteStatus;
+
+/// How long a verified report is used without verifying it again
+pub const VERIFIED_REPORT_TTL_S: u64 = 60 * 60;
+const VERIFIED_REPORT_CACHE_SIZE: usize = 256;
+
+/// What `verify_ra_cert` learns from a report it accepts
+#[derive(Clone, Debug, PartialEq)]
+pub struct VerifiedReport {
+    pub public_key: Vec<u8>,
+    pub measurements: Measurements,
+    pub quote_status: SgxQuoteStatus,
+    pub downgrade_reason: Option<DowngradeReason>,
+}
+
+struct CachedReport {
+    report: VerifiedReport,
+    verified_at_s: u64,
+}
+
+lazy_static! {
+    static ref VERIFIED_REPORTS: SgxMutex<LruCache<([u8; HASH_SIZE], bool), CachedReport>> =
+        SgxMutex::new(LruCache::new(VERIFIED_REPORT_CACHE_SIZE));
+}
+
+/// Whether a report with `quote_status` can be cached. Production nodes verify every report
+/// whose status isn't `OK` again, so platforms that were only tolerated are always re-checked.
+fn is_cacheable(quote_status: &SgxQuoteStatus) -> bool {
+    !cfg!(feature = "production") || quote_status == &SgxQuoteStatus::OK
+}
+
+/// Returns what `verify` returns for the endorsed report `payload`, calling it only if the report
+/// wasn't verified in the last `VERIFIED_REPORT_TTL_S` before `now_s`. `check_tcb_version` is
+/// part of the key, since `verify` may reject a report with it that it accepts without it.
+pub fn verify_cached<F>(
+    payload: &[u8],
+    check_tcb_version: bool,
+    now_s: u64,
+    verify: F,
+) -> Result<VerifiedReport, NodeAuthResult>
+where
+    F: FnOnce() -> Result<VerifiedReport, NodeAuthResult>,
+{
+    let key = (sha_256(payload), check_tcb_version);
+
+    if let Some(cached) = VERIFIED_REPORTS.lock().unwrap().get(&key) {
+        // a time before the report was verified is as stale as one past its expiry
+        if now_s >= cached.verified_at_s && now_s - cached.verified_at_s < VERIFIED_REPORT_TTL_S {
+            trace!(
+                "Report was already verified, status {:?}",
+                cached.report.quote_status
+            );
+            return Ok(cached.report.clone());
+        }
+    }
+
+    let report = verify()?;
+
+    let mut cache = VERIFIED_REPORTS.lock().unwrap();
+    if is_cacheable(&report.quote_status) {
+        cache.put(
+            key,
+            CachedReport {
+                report: report.clone(),
+                verified_at_s: now_s,
+            },
+        );
+    } else {
+        cache.pop(&key);
+    }
+
+    Ok(report)
+}
+
+#[cfg(feature = "test")]
+pub mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    fn verified_report(quote_status: SgxQuoteStatus) -> VerifiedReport {
+        VerifiedReport {
+            public_key: vec![7u8; 32],
+            measurements: Measurements {
+                mr_enclave: [1u8; 32],
+                mr_signer: [2u8; 32],
+            },
+            quote_status,
+            downgrade_reason: None,
+        }
+    }
+
+    /// Verifies `payload` with a `verify` that counts its calls in `calls`
+    fn verify(
+        payload: &[u8],
+        now_s: u64,
+        quote_status: SgxQuoteStatus,
+        calls: &Cell<u32>,
+    ) -> Result<VerifiedReport, NodeAuthResult> {
+        verify_cached(payload, true, now_s, || {
+            calls.set(calls.get() + 1);
+            Ok(verified_report(quote_status))
+        })
+    }
+
+    pub fn test_verified_report_is_cached() {
+        let calls = Cell::new(0);
+        let payload = b"verified report is cached";
+
+        assert_eq!(
+            verify(payload, 1000, SgxQuoteStatus::OK, &calls).unwrap(),
+            verified_report(SgxQuoteStatus::OK)
+        );
+        assert_eq!(calls.get(), 1);
+
+        // the second verification of the same bytes doesn't call into webpki
+        assert_eq!(
+            verify(payload, 1001, SgxQuoteStatus::OK, &calls).unwrap(),
+            verified_report(SgxQuoteStatus::OK)
+        );
+        assert_eq!(calls.get(), 1);
+
+        // other bytes are verified
+        verify(b"another report", 1001, SgxQuoteStatus::OK, &calls).unwrap();
+        assert_eq!(calls.get(), 2);
+
+        // and so is the same report without the tcb version check
+        verify_cached(payload, false, 1001, || {
+            calls.set(calls.get() + 1);
+            Ok(verified_report(SgxQuoteStatus::OK))
+        })
+        .unwrap();
+        assert_eq!(calls.get(), 3);
+    }
+
+    pub fn test_verified_report_expires() {
+        let calls = Cell::new(0);
+        let payload = b"verified report expires";
+
+        let expiry = 1000 + VERIFIED_REPORT_TTL_S;
+
+        verify(payload, 1000, SgxQuoteStatus::OK, &calls).unwrap();
+        verify(payload, expiry - 1, SgxQuoteStatus::OK, &calls).unwrap();
+        assert_eq!(calls.get(), 1);
+
+        verify(payload, expiry, SgxQuoteStatus::OK, &calls).unwrap();
+        assert_eq!(calls.get(), 2);
+
+        // an entry isn't used at a time before it was verified either
+        verify(payload, 999, SgxQuoteStatus::OK, &calls).unwrap();
+        assert_eq!(calls.get(), 3);
+    }
+
+    pub fn test_failed_verification_is_not_cached() {
+        let calls = Cell::new(0);
+        let payload = b"failed verification is not cached";
+        let fail = || {
+            verify_cached(payload, true, 1000, || {
+                calls.set(calls.get() + 1);
+                Err(NodeAuthResult::InvalidCert)
+            })
+        };
+
+        assert_eq!(fail(), Err(NodeAuthResult::InvalidCert));
+        assert_eq!(fail(), Err(NodeAuthResult::InvalidCert));
+        assert_eq!(calls.get(), 2);
+    }
+
+    pub fn test_tolerated_quote_status_is_rechecked_in_production() {
+        let calls = Cell::new(0);
+        let payload = b"tolerated quote status";
+
+        verify(payload, 1000, SgxQuoteStatus::SwHardeningNeeded, &calls).unwrap();
+        verify(payload, 1001, SgxQuoteStatus::SwHardeningNeeded, &calls).unwrap();
+
+        let expected_calls = if cfg!(feature = "production") { 2 } else { 1 };
+        assert_eq!(calls.get(), expected_calls);
+    }
+}
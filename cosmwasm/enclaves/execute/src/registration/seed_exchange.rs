@@ -3,7 +3,7 @@ use log::*;
 use sgx_types::{sgx_status_t, SgxResult};
 
 use enclave_crypto::{
-    AESKey, Keychain, SIVEncryptable, Seed, KEY_MANAGER, PUBLIC_KEY_SIZE, SEED_KEY_SIZE,
+    AESKey, KeyPair, Keychain, SIVEncryptable, Seed, KEY_MANAGER, PUBLIC_KEY_SIZE, SEED_KEY_SIZE,
 };
 use enclave_ffi_types::SINGLE_ENCRYPTED_SEED_SIZE;
 
@@ -66,26 +66,36 @@ pub fn decrypt_seed(
     key_manager: &Keychain,
     master_pk: [u8; PUBLIC_KEY_SIZE],
     encrypted_seed: [u8; SINGLE_ENCRYPTED_SEED_SIZE],
+) -> SgxResult<Seed> {
+    let registration_key = key_manager.get_registration_key().map_err(|_e| {
+        error!("Failed to unlock node key. Please make sure the file is accessible or reinitialize the node");
+        sgx_status_t::SGX_ERROR_UNEXPECTED
+    })?;
+
+    decrypt_seed_with_key(&registration_key, master_pk, encrypted_seed)
+}
+
+///
+/// Decrypts a seed that was encrypted for `registration_key` by the holder of `sender_pk`
+///
+pub fn decrypt_seed_with_key(
+    registration_key: &KeyPair,
+    sender_pk: [u8; PUBLIC_KEY_SIZE],
+    encrypted_seed: [u8; SINGLE_ENCRYPTED_SEED_SIZE],
 ) -> SgxResult<Seed> {
     // create shared encryption key using ECDH
-    let shared_enc_key = key_manager
-        .get_registration_key()
-        .map_err(|_e| {
-            error!("Failed to unlock node key. Please make sure the file is accessible or reinitialize the node");
-            sgx_status_t::SGX_ERROR_UNEXPECTED
-        })?
-        .diffie_hellman(&master_pk);
+    let shared_enc_key = registration_key.diffie_hellman(&sender_pk);
 
     let mut genesis_seed = Seed::default();
 
     // Create AD of encryption
-    let my_public_key = key_manager.get_registration_key().unwrap().get_pubkey();
+    let my_public_key = registration_key.get_pubkey();
     let authenticated_data: Vec<&[u8]> = vec![&my_public_key];
 
     trace!(
         "Public keys on decryption: {:?} {:?}",
         my_public_key,
-        master_pk
+        sender_pk
     );
 
     // decrypt
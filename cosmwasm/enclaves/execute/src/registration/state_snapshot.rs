@@ -0,0 +1,153 @@
+//!
+/// Off-chain export and import of a contract's state, for moving it to an attested peer for
+/// upgrades or audits. See `enclave_contract_engine::state_snapshot` for the snapshot format.
+///
+use log::*;
+use std::panic;
+use std::slice;
+
+use enclave_contract_engine::external::results::result_state_snapshot_to_result;
+use enclave_contract_engine::state_snapshot::{export_contract_state, import_contract_state};
+use enclave_ffi_types::{EnclaveError, StateSnapshotResult};
+use enclave_utils::validate_const_ptr;
+
+use super::onchain::verify_combined_attestation;
+
+const CONTRACT_KEY_LENGTH: usize = 64;
+
+type Entries = Vec<(Vec<u8>, Vec<u8>)>;
+
+fn catch_snapshot_panic(
+    name: &str,
+    result: std::thread::Result<Result<Vec<u8>, EnclaveError>>,
+) -> StateSnapshotResult {
+    match result {
+        Ok(result) => result_state_snapshot_to_result(result),
+        Err(_) => {
+            warn!("Enclave call {} panic!", name);
+            StateSnapshotResult::Failure {
+                err: EnclaveError::Panic,
+            }
+        }
+    }
+}
+
+///
+/// `ecall_export_contract_state`
+///
+/// Seals the raw state `entries` of the contract at `contract_address` for the peer whose
+/// attestation is `importer_attestation`. The entries are the contract's key/value pairs as
+/// stored on chain, JSON encoded as a list of `[key, value]` byte arrays, and every one of them
+/// has to belong to the contract with `contract_key`.
+///
+/// This function happens off-chain
+///
+/// # Safety
+///  Something should go here
+///
+#[no_mangle]
+pub unsafe extern "C" fn ecall_export_contract_state(
+    contract_address: *const u8,
+    contract_address_len: u32,
+    contract_key: &[u8; CONTRACT_KEY_LENGTH],
+    entries: *const u8,
+    entries_len: u32,
+    importer_attestation: *const u8,
+    importer_attestation_len: u32,
+) -> StateSnapshotResult {
+    let failed_call = || StateSnapshotResult::Failure {
+        err: EnclaveError::FailedFunctionCall,
+    };
+    validate_const_ptr!(
+        contract_address,
+        contract_address_len as usize,
+        failed_call()
+    );
+    validate_const_ptr!(contract_key.as_ptr(), contract_key.len(), failed_call());
+    validate_const_ptr!(entries, entries_len as usize, failed_call());
+    validate_const_ptr!(
+        importer_attestation,
+        importer_attestation_len as usize,
+        failed_call()
+    );
+
+    let contract_address = slice::from_raw_parts(contract_address, contract_address_len as usize);
+    let entries = slice::from_raw_parts(entries, entries_len as usize);
+    let importer_attestation =
+        slice::from_raw_parts(importer_attestation, importer_attestation_len as usize);
+
+    let result = panic::catch_unwind(|| -> Result<Vec<u8>, EnclaveError> {
+        let importer_key = verify_combined_attestation(importer_attestation).map_err(|e| {
+            warn!("Refusing to export state to an unattested peer: {:?}", e);
+            EnclaveError::ValidationFailure
+        })?;
+
+        let entries: Entries = serde_json::from_slice(entries).map_err(|err| {
+            warn!("Got invalid state entries to export: {:?}", err);
+            EnclaveError::FailedToDeserialize
+        })?;
+
+        export_contract_state(contract_address, contract_key, entries, &importer_key)
+    });
+
+    catch_snapshot_panic("ecall_export_contract_state", result)
+}
+
+///
+/// `ecall_import_contract_state`
+///
+/// Opens a snapshot made with [ecall_export_contract_state] by the peer whose attestation is
+/// `exporter_attestation`, and returns its entries in the same JSON encoding if it holds the state
+/// of the contract at `contract_address` with `contract_key`. Writing them to the store is up to
+/// the node.
+///
+/// This function happens off-chain
+///
+/// # Safety
+///  Something should go here
+///
+#[no_mangle]
+pub unsafe extern "C" fn ecall_import_contract_state(
+    snapshot: *const u8,
+    snapshot_len: u32,
+    contract_address: *const u8,
+    contract_address_len: u32,
+    contract_key: &[u8; CONTRACT_KEY_LENGTH],
+    exporter_attestation: *const u8,
+    exporter_attestation_len: u32,
+) -> StateSnapshotResult {
+    let failed_call = || StateSnapshotResult::Failure {
+        err: EnclaveError::FailedFunctionCall,
+    };
+    validate_const_ptr!(snapshot, snapshot_len as usize, failed_call());
+    validate_const_ptr!(
+        contract_address,
+        contract_address_len as usize,
+        failed_call()
+    );
+    validate_const_ptr!(contract_key.as_ptr(), contract_key.len(), failed_call());
+    validate_const_ptr!(
+        exporter_attestation,
+        exporter_attestation_len as usize,
+        failed_call()
+    );
+
+    let snapshot = slice::from_raw_parts(snapshot, snapshot_len as usize);
+    let contract_address = slice::from_raw_parts(contract_address, contract_address_len as usize);
+    let exporter_attestation =
+        slice::from_raw_parts(exporter_attestation, exporter_attestation_len as usize);
+
+    let result = panic::catch_unwind(|| -> Result<Vec<u8>, EnclaveError> {
+        let exporter_key = verify_combined_attestation(exporter_attestation).map_err(|e| {
+            warn!("Refusing to import state from an unattested peer: {:?}", e);
+            EnclaveError::ValidationFailure
+        })?;
+
+        let entries =
+            import_contract_state(snapshot, contract_address, contract_key, &exporter_key)?;
+
+        serde_json::to_vec(&entries).map_err(|_| EnclaveError::FailedToSerialize)
+    });
+
+    catch_snapshot_panic("ecall_import_contract_state", result)
+}
@@ -5,8 +5,8 @@ mod types;
 
 pub use types::{
     Ctx, EnclaveBuffer, EnclaveError, HandleResult, HealthCheckResult, InitResult, MigrateResult,
-    NodeAuthResult, OcallReturn, QueryResult, RuntimeConfiguration, UntrustedVmError,
-    UpdateAdminResult, UserSpaceBuffer,
+    NodeAuthResult, OcallReturn, OutputPreview, OutputVariant, QueryResult, RuntimeConfiguration,
+    StateSnapshotResult, UntrustedVmError, UpdateAdminResult, UserSpaceBuffer, OUTPUT_PREVIEW_SIZE,
 };
 
 // On input, the encrypted seed is expected to contain 3 values:
@@ -41,6 +41,12 @@ pub struct RuntimeConfiguration {
     /// This speeds up the execution of recently used modules, but has a significant
     /// memory overhead.
     pub module_cache_size: u32,
+    /// Query results larger than this many bytes are streamed out of the enclave in chunks
+    /// instead of being copied in a single ocall. 0 means use the enclave's default.
+    pub query_result_inline_threshold: u32,
+    /// How many enclave threads verify batches of signatures in parallel, bounded by the enclave's
+    /// TCS count. 0 or 1 verify serially. Results don't depend on it.
+    pub verification_threads: u32,
 }
 
 /// This struct holds a pointer to memory in userspace, that contains the storage
@@ -92,6 +98,9 @@ pub enum EnclaveError {
     /// Ran out of gas
     #[display(fmt = "execution ran out of gas")]
     OutOfGas,
+    /// The contract tried to grow its memory past the limit. It's charged as running out of gas.
+    #[display(fmt = "contract exceeded its memory limit")]
+    MemoryLimitExceeded,
     /// Calling a function in the contract failed.
     #[display(fmt = "calling a function in the contract failed for an unexpected reason")]
     FailedFunctionCall,
@@ -134,14 +143,37 @@ pub enum EnclaveError {
     FailedUnseal,
     #[display(fmt = "failed to authenticate secret contract")]
     FailedContractAuthentication,
+    /// The code hash a message was encrypted with isn't the code hash of the contract it was sent
+    /// to. A message sent to a contract that doesn't exist fails on the host before it gets here.
+    #[display(fmt = "failed to validate transaction: code hash doesn't match the contract's")]
+    CodeHashMismatch,
     #[display(fmt = "failed to deserialize data")]
     FailedToDeserialize,
+    /// The contract's output isn't any of the outputs the enclave expects. `offset` is where
+    /// parsing it as `variant_hint` failed, or 0 when it isn't shaped like any of them.
+    #[display(
+        fmt = "failed to deserialize the contract's output as {} at byte {}, output preview: {}",
+        variant_hint,
+        offset,
+        preview
+    )]
+    OutputDeserialization {
+        variant_hint: OutputVariant,
+        offset: u32,
+        preview: OutputPreview,
+    },
     #[display(fmt = "failed to serialize data")]
     FailedToSerialize,
     #[display(fmt = "failed to encrypt data")]
     EncryptionError,
     #[display(fmt = "failed to decrypt data")]
     DecryptionError,
+    /// The contract's output, or a part of it, couldn't be serialized to be encrypted or returned
+    #[display(fmt = "failed to serialize the contract's output")]
+    Serialization,
+    /// The contract's output couldn't be encrypted with the key derived for the message's sender
+    #[display(fmt = "failed to encrypt the contract's output")]
+    Aead,
     #[display(fmt = "failed to allocate memory")]
     MemoryAllocationError,
     #[display(fmt = "failed to allocate minimal safety buffer")]
@@ -155,7 +187,39 @@ pub enum EnclaveError {
     #[display(fmt = "failed to verify transaction signature")]
     FailedTxVerification,
     #[display(fmt = "contract tried to write to storage during a query")]
-    UnauthorizedWrite,
+    StorageWriteInQuery,
+    /// The contract tried to write or remove a zero-length storage key
+    #[display(fmt = "contract tried to write or remove an empty storage key")]
+    EmptyKey,
+    /// The contract tried to access a storage key the enclave keeps its own data under
+    #[display(fmt = "contract tried to access a storage key reserved for the enclave")]
+    ReservedKey,
+    /// The contract called a host function that contracts must not use, like sleep
+    #[display(fmt = "contract called a forbidden syscall")]
+    ForbiddenSyscall,
+    /// The funds sent with a call don't match the funds the contract requires
+    #[display(fmt = "the funds sent don't match the funds the contract requires")]
+    FundsMismatch,
+    #[display(fmt = "reply depth exceeded")]
+    ReplyDepthExceeded,
+    /// The contract used more gas than the per-contract gas ceiling allows
+    #[display(fmt = "contract gas ceiling exceeded")]
+    ContractGasCeilingExceeded,
+    #[display(fmt = "too many submessages in the contract's response")]
+    TooManySubmessages,
+    #[display(fmt = "event type in the contract's response is too long")]
+    EventTypeTooLong,
+    #[display(fmt = "the contract's output is nested too deeply")]
+    OutputTooDeep,
+    /// The contract opened more ranges over its storage in one call than the enclave allows
+    #[display(fmt = "contract opened too many storage ranges")]
+    TooManyRanges,
+    /// The contract's storage has keys in the old encryption format, which can't be ranged over
+    #[display(fmt = "contract storage has keys in the old format, which can't be ranged over")]
+    OldFormatKeyInRange,
+    /// The contract called a host function like derive_id more times in a call than it can count
+    #[display(fmt = "contract called a host function too many times in a single call")]
+    CounterExhausted,
 
     // serious issues
     /// The host was caught trying to disrupt the enclave.
@@ -168,11 +232,84 @@ pub enum EnclaveError {
     OutOfMemory,
     #[display(fmt = "depth of nested contract calls exceeded")]
     ExceededRecursionLimit,
+    /// A contract sent a query that was already running further up the chain of queries
+    #[display(fmt = "query cycle detected: the same query is already running")]
+    QueryCycleDetected,
     /// Unexpected Error happened, no more details available
     #[display(fmt = "unknown error")]
     Unknown,
 }
 
+/// The kind of output the enclave tried to read a contract's output as, going by its shape
+/// cbindgen:prefix-with-name
+#[repr(C)]
+#[derive(Debug, Display, Clone, Copy, PartialEq, Eq)]
+pub enum OutputVariant {
+    #[display(fmt = "an error")]
+    Err,
+    #[display(fmt = "a v0.10 query result")]
+    QueryOkV010,
+    #[display(fmt = "a v1 query result")]
+    QueryOkV1,
+    #[display(fmt = "a v0.10 response")]
+    OkV010,
+    #[display(fmt = "a v1 response")]
+    OkV1,
+    #[display(fmt = "an IBC packet receive response")]
+    OkIBCPacketReceive,
+    #[display(fmt = "an IBC channel open response")]
+    OkIBCOpenChannel,
+    /// Not JSON, or not shaped like any output
+    #[display(fmt = "any known output")]
+    Unknown,
+}
+
+pub const OUTPUT_PREVIEW_SIZE: usize = 32;
+
+/// The first bytes of a contract's output, shown as hex when it fails to deserialize. Empty in
+/// production builds, where the output can be plaintext that shouldn't end up in the logs.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutputPreview {
+    pub bytes: [u8; OUTPUT_PREVIEW_SIZE],
+    pub len: u32,
+}
+
+impl OutputPreview {
+    pub fn new(output: &[u8]) -> Self {
+        let len = core::cmp::min(output.len(), OUTPUT_PREVIEW_SIZE);
+        let mut bytes = [0u8; OUTPUT_PREVIEW_SIZE];
+        bytes[..len].copy_from_slice(&output[..len]);
+
+        Self {
+            bytes,
+            len: len as u32,
+        }
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        &self.bytes[..self.len as usize]
+    }
+}
+
+impl Default for OutputPreview {
+    fn default() -> Self {
+        Self::new(&[])
+    }
+}
+
+impl core::fmt::Display for OutputPreview {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        if self.len == 0 {
+            return write!(f, "none");
+        }
+        for byte in self.as_slice() {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
 /// This type represents the possible error conditions that can be encountered in the
 /// enclave while authenticating a new node in the network.
 /// cbindgen:prefix-with-name
@@ -219,6 +356,18 @@ pub enum NodeAuthResult {
     MemorySafetyAllocationError,
     #[display(fmt = "Enclave quote status does not match expected status")]
     EnclaveQuoteStatus,
+    #[display(fmt = "The attested node key does not match the expected node key")]
+    NodeKeyMismatch,
+    #[display(fmt = "The attestation type is not supported yet")]
+    UnsupportedAttestationType,
+    #[display(fmt = "The attested enclave's measurements are not allowed by this node")]
+    MeasurementNotAllowed,
+    #[display(fmt = "The attested enclave's security version is below the minimum")]
+    StaleSvn,
+    #[display(fmt = "The platform is affected by security advisories that aren't mitigated")]
+    UnmitigatedAdvisories,
+    #[display(fmt = "This node already has a seed, which is only replaced when forced to")]
+    SeedAlreadySet,
     #[display(
         fmt = "Unexpected panic during node authentication. Certificate may be malformed or invalid"
     )]
@@ -362,3 +511,17 @@ pub enum QueryResult {
         err: EnclaveError,
     },
 }
+
+/// This struct is returned from ecall_export_contract_state and ecall_import_contract_state.
+/// cbindgen:prefix-with-name
+#[repr(C)]
+pub enum StateSnapshotResult {
+    Success {
+        /// A pointer to the sealed snapshot, or to the imported entries
+        output: UserSpaceBuffer,
+    },
+    Failure {
+        /// The error that happened in the enclave
+        err: EnclaveError,
+    },
+}
@@ -16,6 +16,7 @@ mod txs;
 pub mod validator_whitelist;
 
 pub mod submit_block_signatures;
+pub mod verify_header_chain;
 mod verify;
 
 #[cfg(feature = "test")]
@@ -55,6 +56,14 @@ pub mod tests {
             crate::wasm_messages::tests::test_wasm_msg_tracker();
             crate::wasm_messages::tests::test_mix_wasm_bank_msg_tracker_multiple_msgs();
             crate::validator_whitelist::tests::test_parse_validators();
+            crate::verify::header_chain::tests::test_header_chain_valid();
+            crate::verify::header_chain::tests::test_header_chain_broken_link();
+            crate::verify::header_chain::tests::test_header_chain_untrusted_start();
+            crate::verify::header_chain::tests::test_header_chain_swapped_validators();
+            crate::verify::header_chain::tests::test_header_chain_untrusted_validators();
+            crate::verify::header_chain::tests::test_header_chain_bad_signatures();
+            crate::verify::header_chain::tests::test_header_chain_length_limit();
+            crate::verify::header_chain::tests::test_header_chain_malformed_bytes();
         });
 
         if failures != 0 {
@@ -0,0 +1,392 @@
+use std::convert::TryInto;
+
+use log::error;
+use sgx_types::sgx_status_t;
+use tendermint::block::signed_header::SignedHeader;
+use tendermint::validator::Set;
+use tendermint::Hash;
+use tendermint_light_client_verifier::types::UntrustedBlockState;
+use tendermint_proto::Protobuf;
+
+/// Chains longer than this are rejected without verifying the links past it
+pub const MAX_HEADER_CHAIN_LENGTH: usize = 128;
+
+/// A header, the commit that signs it, and the validator set that signed the commit
+pub struct ChainLink {
+    pub signed_header: SignedHeader,
+    pub validators: Set,
+}
+
+/// The first link that didn't verify, and why
+#[derive(Debug, PartialEq, Eq)]
+pub struct BrokenLink {
+    /// The height of the link. If it couldn't be decoded, the height it should have had, or 0 for
+    /// the first link
+    pub height: u64,
+    pub status: sgx_status_t,
+}
+
+/// Links of a header chain, each encoded as a length-prefixed proto `SignedHeader` followed by a
+/// length-prefixed proto `ValidatorSet`, with 4 byte big-endian lengths
+pub struct ChainLinks<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> ChainLinks<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes }
+    }
+
+    fn next_section(&mut self) -> Result<&'a [u8], sgx_status_t> {
+        let len = self
+            .bytes
+            .get(..4)
+            .ok_or(sgx_status_t::SGX_ERROR_INVALID_PARAMETER)?;
+        let len = 4 + u32::from_be_bytes(len.try_into().unwrap()) as usize;
+        let section = self
+            .bytes
+            .get(4..len)
+            .ok_or(sgx_status_t::SGX_ERROR_INVALID_PARAMETER)?;
+
+        self.bytes = &self.bytes[len..];
+        Ok(section)
+    }
+
+    fn next_link(&mut self) -> Result<ChainLink, sgx_status_t> {
+        let signed_header = SignedHeader::decode(self.next_section()?).map_err(|e| {
+            error!("Error parsing signed header from proto: {:?}", e);
+            sgx_status_t::SGX_ERROR_INVALID_PARAMETER
+        })?;
+        let validators = Set::decode(self.next_section()?).map_err(|e| {
+            error!("Error parsing validator set from proto: {:?}", e);
+            sgx_status_t::SGX_ERROR_INVALID_PARAMETER
+        })?;
+
+        Ok(ChainLink {
+            signed_header,
+            validators,
+        })
+    }
+}
+
+impl Iterator for ChainLinks<'_> {
+    type Item = Result<ChainLink, sgx_status_t>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.bytes.is_empty() {
+            return None;
+        }
+
+        let link = self.next_link();
+        if link.is_err() {
+            // the rest can't be split into links anymore
+            self.bytes = &[];
+        }
+
+        Some(link)
+    }
+}
+
+/// Verifies that every header is signed by its validator set, and links to the one before it
+/// through `last_block_id` and `next_validators_hash`. The first header has to be signed by the
+/// validator set whose hash is `trusted_validators_hash`. The validator set of each link has to be
+/// the one its header commits to, so the hashes that chain the headers bind the signers too.
+///
+/// Returns the height of the last header
+pub fn verify_header_chain<I, F>(
+    links: I,
+    trusted_validators_hash: Hash,
+    verify_block: F,
+) -> Result<u64, BrokenLink>
+where
+    I: IntoIterator<Item = Result<ChainLink, sgx_status_t>>,
+    F: Fn(&UntrustedBlockState) -> bool,
+{
+    let mut previous: Option<SignedHeader> = None;
+
+    for (index, link) in links.into_iter().enumerate() {
+        let expected_height = previous
+            .as_ref()
+            .map_or(0, |header| header.header.height.value() + 1);
+        let broken = |height, status| BrokenLink { height, status };
+
+        if index == MAX_HEADER_CHAIN_LENGTH {
+            error!(
+                "Header chain is longer than {} headers",
+                MAX_HEADER_CHAIN_LENGTH
+            );
+            return Err(broken(
+                expected_height,
+                sgx_status_t::SGX_ERROR_INVALID_PARAMETER,
+            ));
+        }
+
+        let link = link.map_err(|status| broken(expected_height, status))?;
+        let header = &link.signed_header.header;
+        let height = header.height.value();
+
+        let links_to_previous = match &previous {
+            None => header.validators_hash == trusted_validators_hash,
+            Some(previous) => {
+                height == expected_height
+                    && header.last_block_id.as_ref().map(|id| id.hash)
+                        == Some(previous.header.hash())
+                    && header.validators_hash == previous.header.next_validators_hash
+            }
+        };
+        if !links_to_previous {
+            error!(
+                "Header at height {} doesn't link to the trusted chain",
+                height
+            );
+            return Err(broken(height, sgx_status_t::SGX_ERROR_INVALID_SIGNATURE));
+        }
+
+        // otherwise any validator set could sign a header that links to the trusted chain
+        if link.validators.hash() != header.validators_hash {
+            error!(
+                "Validator set of the header at height {} isn't the one it commits to",
+                height
+            );
+            return Err(broken(height, sgx_status_t::SGX_ERROR_INVALID_SIGNATURE));
+        }
+
+        let untrusted_block = UntrustedBlockState {
+            signed_header: &link.signed_header,
+            validators: &link.validators,
+            next_validators: None,
+        };
+        if !verify_block(&untrusted_block) {
+            error!("Error verifying header at height {}", height);
+            return Err(broken(height, sgx_status_t::SGX_ERROR_INVALID_SIGNATURE));
+        }
+
+        previous = Some(link.signed_header);
+    }
+
+    match previous {
+        Some(last) => Ok(last.header.height.value()),
+        None => {
+            error!("Header chain is empty");
+            Err(BrokenLink {
+                height: 0,
+                status: sgx_status_t::SGX_ERROR_INVALID_PARAMETER,
+            })
+        }
+    }
+}
+
+#[cfg(feature = "test")]
+pub mod tests {
+    use std::convert::TryFrom;
+
+    use tendermint::account;
+    use tendermint::block::header::Version;
+    use tendermint::block::{self, Commit, Header, Height, Round};
+    use tendermint::{validator, vote, AppHash, PublicKey, Time};
+
+    use super::*;
+
+    /// A validator set with a single validator, whose key is one of the RFC 8032 test keys
+    fn validator_set(public_key_hex: &str) -> Set {
+        let public_key =
+            PublicKey::from_raw_ed25519(&hex::decode(public_key_hex).unwrap()).unwrap();
+        let validator = validator::Info::new(public_key, vote::Power::from(10u32));
+        Set::new(vec![validator], None)
+    }
+
+    fn validators() -> Set {
+        validator_set("d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a")
+    }
+
+    fn other_validators() -> Set {
+        validator_set("3d4017c3e843895a92b70aa74d1b7ebc9c982ccf2ec4968cc0cd55f12af4660c")
+    }
+
+    fn link(height: u64, last_block_id: Option<block::Id>) -> ChainLink {
+        link_signed_by(height, last_block_id, validators())
+    }
+
+    /// A link whose header commits to `validators`
+    fn link_signed_by(height: u64, last_block_id: Option<block::Id>, validators: Set) -> ChainLink {
+        let header = Header {
+            version: Version { block: 11, app: 0 },
+            chain_id: "secret-4".parse().unwrap(),
+            height: Height::try_from(height).unwrap(),
+            time: Time::unix_epoch(),
+            last_block_id,
+            last_commit_hash: None,
+            data_hash: None,
+            validators_hash: validators.hash(),
+            next_validators_hash: validators.hash(),
+            consensus_hash: Hash::None,
+            app_hash: AppHash::default(),
+            last_results_hash: None,
+            evidence_hash: None,
+            proposer_address: account::Id::new([0; 20]),
+        };
+        let commit = Commit {
+            height: header.height,
+            round: Round::default(),
+            block_id: block_id(&header),
+            signatures: vec![],
+        };
+
+        ChainLink {
+            signed_header: SignedHeader::new(header, commit).unwrap(),
+            validators,
+        }
+    }
+
+    fn block_id(header: &Header) -> block::Id {
+        block::Id {
+            hash: header.hash(),
+            part_set_header: Default::default(),
+        }
+    }
+
+    /// A chain of `len` headers starting at height 1, each pointing at the one before it
+    fn chain(len: u64) -> Vec<ChainLink> {
+        let mut links: Vec<ChainLink> = vec![];
+        for height in 1..=len {
+            let last_block_id = links.last().map(|l| block_id(&l.signed_header.header));
+            links.push(link(height, last_block_id));
+        }
+        links
+    }
+
+    // signatures are checked by `verify_block`, these tests are about how headers and their
+    // validator sets link
+    fn accept_all(_: &UntrustedBlockState) -> bool {
+        true
+    }
+
+    pub fn test_header_chain_valid() {
+        let links = chain(5).into_iter().map(Ok);
+
+        assert_eq!(
+            verify_header_chain(links, validators().hash(), accept_all),
+            Ok(5)
+        );
+    }
+
+    pub fn test_header_chain_broken_link() {
+        let mut links = chain(5);
+        // a header at the right height that points at some other block
+        let other_block = block_id(&link(7, None).signed_header.header);
+        links[2] = link(3, Some(other_block));
+
+        assert_eq!(
+            verify_header_chain(links.into_iter().map(Ok), validators().hash(), accept_all),
+            Err(BrokenLink {
+                height: 3,
+                status: sgx_status_t::SGX_ERROR_INVALID_SIGNATURE,
+            })
+        );
+    }
+
+    pub fn test_header_chain_untrusted_start() {
+        let links = chain(2).into_iter().map(Ok);
+
+        assert_eq!(
+            verify_header_chain(links, Hash::Sha256([1; 32]), accept_all),
+            Err(BrokenLink {
+                height: 1,
+                status: sgx_status_t::SGX_ERROR_INVALID_SIGNATURE,
+            })
+        );
+    }
+
+    pub fn test_header_chain_swapped_validators() {
+        // a header of the chain, but with a validator set it doesn't commit to
+        let mut links = chain(3);
+        links[1].validators = other_validators();
+
+        assert_eq!(
+            verify_header_chain(links.into_iter().map(Ok), validators().hash(), accept_all),
+            Err(BrokenLink {
+                height: 2,
+                status: sgx_status_t::SGX_ERROR_INVALID_SIGNATURE,
+            })
+        );
+
+        // the first header is bound to its validator set too, not only to the trusted hash
+        let mut links = chain(2);
+        links[0].validators = other_validators();
+
+        assert_eq!(
+            verify_header_chain(links.into_iter().map(Ok), validators().hash(), accept_all),
+            Err(BrokenLink {
+                height: 1,
+                status: sgx_status_t::SGX_ERROR_INVALID_SIGNATURE,
+            })
+        );
+    }
+
+    pub fn test_header_chain_untrusted_validators() {
+        // a header that commits to the validator set it carries, which isn't the one the header
+        // before it handed over to
+        let mut links = chain(2);
+        let last_block_id = block_id(&links[1].signed_header.header);
+        links.push(link_signed_by(3, Some(last_block_id), other_validators()));
+
+        assert_eq!(
+            verify_header_chain(links.into_iter().map(Ok), validators().hash(), accept_all),
+            Err(BrokenLink {
+                height: 3,
+                status: sgx_status_t::SGX_ERROR_INVALID_SIGNATURE,
+            })
+        );
+
+        // nor can a chain start at a validator set the enclave doesn't trust
+        let links = vec![link_signed_by(1, None, other_validators())];
+
+        assert_eq!(
+            verify_header_chain(links.into_iter().map(Ok), validators().hash(), accept_all),
+            Err(BrokenLink {
+                height: 1,
+                status: sgx_status_t::SGX_ERROR_INVALID_SIGNATURE,
+            })
+        );
+    }
+
+    pub fn test_header_chain_bad_signatures() {
+        let links = chain(3).into_iter().map(Ok);
+        let reject_height_2 =
+            |block: &UntrustedBlockState| block.signed_header.header.height.value() != 2;
+
+        assert_eq!(
+            verify_header_chain(links, validators().hash(), reject_height_2),
+            Err(BrokenLink {
+                height: 2,
+                status: sgx_status_t::SGX_ERROR_INVALID_SIGNATURE,
+            })
+        );
+    }
+
+    pub fn test_header_chain_length_limit() {
+        let links = chain(MAX_HEADER_CHAIN_LENGTH as u64 + 1)
+            .into_iter()
+            .map(Ok);
+
+        assert_eq!(
+            verify_header_chain(links, validators().hash(), accept_all),
+            Err(BrokenLink {
+                height: MAX_HEADER_CHAIN_LENGTH as u64 + 1,
+                status: sgx_status_t::SGX_ERROR_INVALID_PARAMETER,
+            })
+        );
+    }
+
+    pub fn test_header_chain_malformed_bytes() {
+        // a length prefix that's longer than the remaining bytes
+        let mut links = ChainLinks::new(&[0, 0, 0, 9, 1, 2, 3]);
+
+        assert_eq!(
+            links.next().map(|link| link.err()),
+            Some(Some(sgx_status_t::SGX_ERROR_INVALID_PARAMETER))
+        );
+        assert!(links.next().is_none());
+        assert!(ChainLinks::new(&[]).next().is_none());
+    }
+}
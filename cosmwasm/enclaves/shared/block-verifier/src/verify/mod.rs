@@ -1,6 +1,7 @@
 pub mod block;
 pub mod commit;
 pub mod header;
+pub mod header_chain;
 pub mod txs;
 pub mod validator_set;
 
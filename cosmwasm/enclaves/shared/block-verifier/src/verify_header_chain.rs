@@ -0,0 +1,68 @@
+use std::slice;
+
+use tendermint_proto::Protobuf;
+
+use sgx_types::sgx_status_t;
+
+use enclave_utils::{validate_const_ptr, validate_input_length, validate_mut_ptr};
+use log::{debug, error};
+
+use tendermint::validator::Set;
+
+use crate::verify::header_chain::{verify_header_chain, ChainLinks, MAX_HEADER_CHAIN_LENGTH};
+use crate::verify::validator_set::get_validator_set_for_height;
+
+// a header with its commit, and its validator set, are bound like in `submit_block_signatures`
+const MAX_LINK_LENGTH: u32 = 2 * 100_000;
+const MAX_HEADER_CHAIN_BYTES: u32 = MAX_HEADER_CHAIN_LENGTH as u32 * MAX_LINK_LENGTH;
+
+/// Verifies a chain of headers starting at the validator set the enclave currently trusts, see
+/// `verify_header_chain`. `trusted_height` is set to the height of the last header, or, if the
+/// chain is broken, to the height of the first link that didn't verify.
+#[no_mangle]
+#[allow(clippy::missing_safety_doc)]
+pub unsafe fn verify_header_chain_impl(
+    in_chain: *const u8,
+    in_chain_len: u32,
+    trusted_height: &mut u64,
+) -> sgx_status_t {
+    let failed_call = sgx_status_t::SGX_ERROR_INVALID_PARAMETER;
+
+    validate_input_length!(in_chain_len, "chain", MAX_HEADER_CHAIN_BYTES, failed_call);
+    validate_const_ptr!(in_chain, in_chain_len as usize, failed_call);
+    validate_mut_ptr!(
+        trusted_height as *mut u64 as *mut u8,
+        std::mem::size_of::<u64>(),
+        failed_call,
+    );
+
+    let chain_slice = slice::from_raw_parts(in_chain, in_chain_len as usize);
+
+    let validator_set_for_height = match get_validator_set_for_height() {
+        Ok(validator_set_for_height) => validator_set_for_height,
+        Err(e) => return e,
+    };
+    let trusted_validators = match Set::decode(validator_set_for_height.validator_set.as_slice()) {
+        Ok(validators) => validators,
+        Err(e) => {
+            error!("Error parsing validator set from proto: {:?}", e);
+            return sgx_status_t::SGX_ERROR_UNEXPECTED;
+        }
+    };
+
+    match verify_header_chain(
+        ChainLinks::new(chain_slice),
+        trusted_validators.hash(),
+        crate::verify::block::verify_block,
+    ) {
+        Ok(height) => {
+            debug!("Done verifying header chain up to height: {:?}", height);
+            *trusted_height = height;
+            sgx_status_t::SGX_SUCCESS
+        }
+        Err(broken_link) => {
+            *trusted_height = broken_link.height;
+            broken_link.status
+        }
+    }
+}
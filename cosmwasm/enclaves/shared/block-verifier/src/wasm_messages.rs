@@ -16,6 +16,7 @@ pub struct VerifiedBlockMessages {
     messages: VecDeque<Vec<u8>>,
     height: u64,
     time: i128,
+    hash: Vec<u8>,
 }
 
 impl VerifiedBlockMessages {
@@ -33,9 +34,10 @@ impl VerifiedBlockMessages {
         }
     }
 
-    pub fn set_block_info(&mut self, height: u64, time: i128) {
+    pub fn set_block_info(&mut self, height: u64, time: i128, hash: &[u8]) {
         self.height = height;
         self.time = time;
+        self.hash = hash.to_vec();
     }
 
     pub fn height(&self) -> u64 {
@@ -44,6 +46,10 @@ impl VerifiedBlockMessages {
     pub fn time(&self) -> i128 {
         self.time
     }
+    /// The hash of the verified block header, empty until a block was verified
+    pub fn hash(&self) -> &[u8] {
+        &self.hash
+    }
 
     pub fn clear(&mut self) {
         self.messages.clear()
@@ -0,0 +1,117 @@
+//! Verification of ADR-036 off-chain signatures, backing the `verify_adr036` host function, so
+//! contracts can authenticate Cosmos wallets without a transaction.
+//!
+//! ADR-036 signs an amino JSON sign doc holding a single `sign/MsgSignData` message, with an empty
+//! chain id and a zero fee, account number and sequence, so it can never be a valid transaction.
+//! This is the format wallets' `signArbitrary` produces. Only secp256k1 keys are supported.
+
+use log::*;
+
+use cw_types_v010::types::{CanonicalAddr, HumanAddr};
+use enclave_cosmos_types::traits::CosmosAminoPubkey;
+use enclave_crypto::secp256k1::Secp256k1PubKey;
+use enclave_crypto::{sha_256, WasmApiCryptoError};
+
+/// Compressed secp256k1 public keys
+pub const ADR036_PUBKEY_SIZE: usize = 33;
+/// r (32 bytes) || s (32 bytes)
+pub const ADR036_SIGNATURE_SIZE: usize = 64;
+
+/// The canonical (sorted, no whitespace) amino JSON sign doc for `data` signed by `signer`.
+/// `signer` must be a bech32 address, which never needs escaping.
+pub fn adr036_sign_doc(data: &[u8], signer: &str) -> Vec<u8> {
+    format!(
+        r#"{{"account_number":"0","chain_id":"","fee":{{"amount":[],"gas":"0"}},"memo":"","msgs":[{{"type":"sign/MsgSignData","value":{{"data":"{}","signer":"{}"}}}}],"sequence":"0"}}"#,
+        base64::encode(data),
+        signer
+    )
+    .into_bytes()
+}
+
+/// Returns whether `signature` is an ADR-036 signature of `data` by `pubkey`, and `pubkey` is the
+/// key of `signer_addr`. Malformed inputs are errors, while a valid signature by another key or
+/// for another address is `Ok(false)`.
+pub fn verify_adr036(
+    data: &[u8],
+    signature: &[u8],
+    pubkey: &[u8],
+    signer_addr: &[u8],
+) -> Result<bool, WasmApiCryptoError> {
+    if signature.len() != ADR036_SIGNATURE_SIZE {
+        return Err(WasmApiCryptoError::InvalidSignatureFormat);
+    }
+    if pubkey.len() != ADR036_PUBKEY_SIZE {
+        return Err(WasmApiCryptoError::InvalidPubkeyFormat);
+    }
+
+    let signer = std::str::from_utf8(signer_addr).map_err(|_| {
+        debug!("verify_adr036() got a signer address that isn't utf8");
+        WasmApiCryptoError::InvalidPubkeyFormat
+    })?;
+    let signer_canonical =
+        CanonicalAddr::from_human(&HumanAddr(signer.to_string())).map_err(|err| {
+            debug!("verify_adr036() got a malformed signer address: {:?}", err);
+            WasmApiCryptoError::InvalidPubkeyFormat
+        })?;
+
+    let public_key = secp256k1::PublicKey::from_slice(pubkey)
+        .map_err(|_| WasmApiCryptoError::InvalidPubkeyFormat)?;
+    let signature = secp256k1::ecdsa::Signature::from_compact(signature)
+        .map_err(|_| WasmApiCryptoError::InvalidSignatureFormat)?;
+
+    // the signature only binds the signer's address, so it has to be the key's
+    if Secp256k1PubKey::new(pubkey.to_vec()).get_address() != signer_canonical {
+        return Ok(false);
+    }
+
+    let message = secp256k1::Message::from_slice(&sha_256(&adr036_sign_doc(data, signer)))
+        .map_err(|_| WasmApiCryptoError::GenericErr)?;
+
+    // high-S signatures are rejected, like the SDK does for transactions
+    Ok(secp256k1::Secp256k1::verification_only()
+        .verify_ecdsa(&message, &signature, &public_key)
+        .is_ok())
+}
+
+#[cfg(feature = "test")]
+pub mod tests {
+    use super::*;
+
+    // A signArbitrary signature, as produced by offline signing with a wallet
+    const SIGNER: &str = "secret1ss3eqwuf2e4prm0d2u9dhtn75s5xdzqdj9aun8";
+    const PUBKEY: &str = "023b342c880a81aaffdcdd9b907c87e6b04b8f0cab8940a043b48fa447bc28aac9";
+    const DATA: &[u8] = b"Sign in to example.com at block 1234";
+    const SIGNATURE: &str = "d889fcb608a8c2878de499f91dec0e3c2f8b1d554af6a752db8da67fb09195c31e5ffa233d7cd9887f72dc891e301338ef17a9dcf27bb934927043f9da2faf73";
+
+    pub fn test_verify_adr036_wallet_signature() {
+        let pubkey = hex::decode(PUBKEY).unwrap();
+        let signature = hex::decode(SIGNATURE).unwrap();
+
+        assert!(verify_adr036(DATA, &signature, &pubkey, SIGNER.as_bytes()).unwrap());
+        assert!(!verify_adr036(b"other data", &signature, &pubkey, SIGNER.as_bytes()).unwrap());
+
+        // the key doesn't belong to another address, even though the signature is valid
+        let other_signer = HumanAddr::from_canonical(&CanonicalAddr::from_vec(vec![1u8; 20]))
+            .unwrap()
+            .0;
+        assert!(!verify_adr036(DATA, &signature, &pubkey, other_signer.as_bytes()).unwrap());
+    }
+
+    pub fn test_verify_adr036_malformed_inputs() {
+        let pubkey = hex::decode(PUBKEY).unwrap();
+        let signature = hex::decode(SIGNATURE).unwrap();
+
+        assert!(matches!(
+            verify_adr036(DATA, &signature[..63], &pubkey, SIGNER.as_bytes()),
+            Err(WasmApiCryptoError::InvalidSignatureFormat)
+        ));
+        assert!(matches!(
+            verify_adr036(DATA, &signature, &pubkey[..32], SIGNER.as_bytes()),
+            Err(WasmApiCryptoError::InvalidPubkeyFormat)
+        ));
+        assert!(matches!(
+            verify_adr036(DATA, &signature, &pubkey, b"not an address"),
+            Err(WasmApiCryptoError::InvalidPubkeyFormat)
+        ));
+    }
+}
@@ -0,0 +1,51 @@
+//! The block the enclave believes it's executing against, backing the `enclave_block_view`
+//! host function. Operators can compare it with the node's view of the chain to debug
+//! consensus issues, so it's returned to the contract as plaintext JSON.
+//!
+//! Contracts can call this in transactions, so the view only holds what every node agrees on.
+//! The block hash is left out: the enclave only knows it when the light client verified the
+//! block, which depends on the build and on the call.
+
+use serde::{Deserialize, Serialize};
+
+use cw_types_v1::types::Timestamp;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct BlockView {
+    pub height: u64,
+    /// Nanoseconds since the UNIX epoch, like `env.block.time`
+    pub time: Timestamp,
+}
+
+/// Builds the view from the height and time the engine put in the contract's env
+pub fn current_block_view(height: u64, time: u64) -> BlockView {
+    BlockView {
+        height,
+        time: Timestamp::from_nanos(time),
+    }
+}
+
+#[cfg(feature = "test")]
+pub mod tests {
+    use super::*;
+    use cw_types_generic::{BaseEnv, CosmWasmApiVersion, CwEnv};
+
+    pub fn test_block_view_matches_env() {
+        let base_env: BaseEnv = serde_json::from_str(
+            r#"{"block":{"height":12345,"time":1571797419879305533,"chain_id":"test"},"message":{"sender":"sender","sent_funds":[]},"contract":{"address":"contract"},"contract_key":null}"#,
+        )
+        .unwrap();
+
+        let view = current_block_view(base_env.0.block.height, base_env.0.block.time);
+
+        let env = match base_env.into_versioned_env(&CosmWasmApiVersion::V1) {
+            CwEnv::V1Env { env, .. } => env,
+            CwEnv::V010Env { .. } => panic!("expected a v1 env"),
+        };
+        assert_eq!(view.height, env.block.height);
+        assert_eq!(view.time, env.block.time);
+
+        let json = serde_json::to_string(&view).unwrap();
+        assert_eq!(json, r#"{"height":12345,"time":"1571797419879305533"}"#);
+    }
+}
@@ -0,0 +1,237 @@
+//! BLS12-381 aggregate signatures, backing the `bls12_381_aggregate_verify` host function.
+//!
+//! This is the basic scheme of draft-irtf-cfrg-bls-signature with minimal-pubkey-size
+//! parameters (as used by Ethereum and most consensus protocols): public keys are compressed G1
+//! points, signatures are compressed G2 points and messages are hashed to G2 with the
+//! `BLS_SIG_BLS12381G2_XMD:SHA-256_SSWU_RO_NUL_` ciphersuite. The basic scheme defends against
+//! rogue key attacks by requiring every message in an aggregate to be distinct.
+//!
+//! An aggregate of a single message signed by every key (e.g. a validator set signing a header)
+//! is also accepted, by aggregating the keys. This gives up the distinct messages defense, so it's
+//! only safe for keys the caller already trusts, e.g. ones whose possession was proven when they
+//! were registered.
+
+use std::collections::HashSet;
+
+use bls12_381::hash_to_curve::{ExpandMsgXmd, HashToCurve};
+use bls12_381::{
+    multi_miller_loop, G1Affine, G1Projective, G2Affine, G2Prepared, G2Projective, Gt,
+};
+use log::*;
+
+use enclave_crypto::WasmApiCryptoError;
+
+pub const BLS_PUBKEY_SIZE: usize = 48;
+pub const BLS_SIGNATURE_SIZE: usize = 96;
+
+const BLS_DST: &[u8] = b"BLS_SIG_BLS12381G2_XMD:SHA-256_SSWU_RO_NUL_";
+
+/// The number of pairings needed to verify an aggregate over `msg_count` messages
+pub fn aggregate_verify_pairings(msg_count: usize) -> u64 {
+    msg_count as u64 + 1
+}
+
+/// Hashes `msg` to a G2 point with the ciphersuite's domain separation tag
+pub fn hash_to_g2(msg: &[u8]) -> G2Projective {
+    <G2Projective as HashToCurve<ExpandMsgXmd<sha2_v09::Sha256>>>::hash_to_curve(msg, BLS_DST)
+}
+
+fn parse_pubkey(data: &[u8]) -> Result<G1Affine, WasmApiCryptoError> {
+    if data.len() != BLS_PUBKEY_SIZE {
+        return Err(WasmApiCryptoError::InvalidPubkeyFormat);
+    }
+    let mut bytes = [0u8; BLS_PUBKEY_SIZE];
+    bytes.copy_from_slice(data);
+
+    // from_compressed checks that the point is on the curve and in the G1 subgroup
+    match Option::<G1Affine>::from(G1Affine::from_compressed(&bytes)) {
+        Some(pubkey) if !bool::from(pubkey.is_identity()) => Ok(pubkey),
+        _ => Err(WasmApiCryptoError::InvalidPubkeyFormat),
+    }
+}
+
+fn parse_signature(data: &[u8]) -> Result<G2Affine, WasmApiCryptoError> {
+    if data.len() != BLS_SIGNATURE_SIZE {
+        return Err(WasmApiCryptoError::InvalidSignatureFormat);
+    }
+    let mut bytes = [0u8; BLS_SIGNATURE_SIZE];
+    bytes.copy_from_slice(data);
+
+    Option::from(G2Affine::from_compressed(&bytes))
+        .ok_or(WasmApiCryptoError::InvalidSignatureFormat)
+}
+
+/// Verifies that `aggregate_sig` aggregates a signature over `msgs[i]` by `pubkeys[i]` for every
+/// `i`, or over `msgs[0]` by every key if there's a single message. Malformed inputs, including
+/// mismatched numbers of keys and messages, are errors, while a well-formed aggregate that doesn't
+/// verify (including one over repeated messages) is `Ok(false)`.
+pub fn bls12_381_aggregate_verify(
+    pubkeys: &[Vec<u8>],
+    msgs: &[Vec<u8>],
+    aggregate_sig: &[u8],
+) -> Result<bool, WasmApiCryptoError> {
+    if pubkeys.is_empty() || (pubkeys.len() != msgs.len() && msgs.len() != 1) {
+        debug!(
+            "bls12_381_aggregate_verify() mismatched number of public keys ({}) / messages ({})",
+            pubkeys.len(),
+            msgs.len(),
+        );
+
+        return Err(WasmApiCryptoError::BatchErr);
+    }
+
+    let pubkeys = pubkeys
+        .iter()
+        .map(|pubkey| parse_pubkey(pubkey))
+        .collect::<Result<Vec<G1Affine>, _>>()?;
+    let signature = parse_signature(aggregate_sig)?;
+
+    let pubkeys = if msgs.len() == 1 {
+        // e(pubkey_0, H(msg)) * ... * e(pubkey_n, H(msg)) == e(pubkey_0 + ... + pubkey_n, H(msg))
+        let aggregate_pubkey = G1Affine::from(
+            pubkeys
+                .iter()
+                .fold(G1Projective::identity(), |sum, pubkey| sum + pubkey),
+        );
+        if bool::from(aggregate_pubkey.is_identity()) {
+            debug!("bls12_381_aggregate_verify() public keys add up to the identity");
+            return Ok(false);
+        }
+        vec![aggregate_pubkey]
+    } else {
+        let distinct_msgs: HashSet<&[u8]> = msgs.iter().map(|msg| msg.as_slice()).collect();
+        if distinct_msgs.len() != msgs.len() {
+            debug!("bls12_381_aggregate_verify() got repeated messages");
+            return Ok(false);
+        }
+        pubkeys
+    };
+
+    // e(g1, aggregate_sig) == e(pubkey_0, H(msg_0)) * ... * e(pubkey_n, H(msg_n)), checked as
+    // e(-g1, aggregate_sig) * e(pubkey_0, H(msg_0)) * ... == 1 so there is a single final
+    // exponentiation
+    let neg_generator = -G1Affine::generator();
+    let mut g2_terms = vec![G2Prepared::from(signature)];
+    g2_terms.extend(
+        msgs.iter()
+            .map(|msg| G2Prepared::from(G2Affine::from(hash_to_g2(msg)))),
+    );
+
+    let mut terms = vec![(&neg_generator, &g2_terms[0])];
+    terms.extend(pubkeys.iter().zip(g2_terms[1..].iter()));
+
+    Ok(multi_miller_loop(&terms).final_exponentiation() == Gt::identity())
+}
+
+#[cfg(feature = "test")]
+pub mod tests {
+    use super::*;
+    use bls12_381::Scalar;
+
+    const MSGS: [&[u8]; 3] = [b"block 1", b"block 2", b"block 3"];
+
+    /// Signs each of `MSGS` with a different key and aggregates the signatures
+    fn aggregate() -> (Vec<Vec<u8>>, Vec<Vec<u8>>, Vec<u8>) {
+        let mut pubkeys = vec![];
+        let mut aggregate_sig = G2Projective::identity();
+        for (i, msg) in MSGS.iter().enumerate() {
+            let secret_key = Scalar::from(0x5ec2e7_u64 * (i as u64 + 1));
+            pubkeys.push(
+                G1Affine::from(G1Projective::generator() * secret_key)
+                    .to_compressed()
+                    .to_vec(),
+            );
+            aggregate_sig += hash_to_g2(msg) * secret_key;
+        }
+
+        let msgs = MSGS.iter().map(|msg| msg.to_vec()).collect();
+        let aggregate_sig = G2Affine::from(aggregate_sig).to_compressed().to_vec();
+
+        (pubkeys, msgs, aggregate_sig)
+    }
+
+    /// Signs `MSGS[0]` with the keys of `aggregate` and aggregates the signatures
+    fn same_message_aggregate() -> (Vec<Vec<u8>>, Vec<Vec<u8>>, Vec<u8>) {
+        let mut pubkeys = vec![];
+        let mut aggregate_sig = G2Projective::identity();
+        for i in 0..MSGS.len() {
+            let secret_key = Scalar::from(0x5ec2e7_u64 * (i as u64 + 1));
+            pubkeys.push(
+                G1Affine::from(G1Projective::generator() * secret_key)
+                    .to_compressed()
+                    .to_vec(),
+            );
+            aggregate_sig += hash_to_g2(MSGS[0]) * secret_key;
+        }
+
+        let aggregate_sig = G2Affine::from(aggregate_sig).to_compressed().to_vec();
+
+        (pubkeys, vec![MSGS[0].to_vec()], aggregate_sig)
+    }
+
+    pub fn test_bls12_381_aggregate_verify_valid_aggregate() {
+        let (pubkeys, msgs, aggregate_sig) = aggregate();
+        assert!(bls12_381_aggregate_verify(&pubkeys, &msgs, &aggregate_sig).unwrap());
+        assert_eq!(aggregate_verify_pairings(pubkeys.len()), 4);
+    }
+
+    pub fn test_bls12_381_aggregate_verify_tampered_message() {
+        let (pubkeys, mut msgs, aggregate_sig) = aggregate();
+        msgs[1] = b"block 2'".to_vec();
+        assert!(!bls12_381_aggregate_verify(&pubkeys, &msgs, &aggregate_sig).unwrap());
+
+        // swapping messages between signers breaks the aggregate too
+        let (pubkeys, mut msgs, aggregate_sig) = aggregate();
+        msgs.swap(0, 2);
+        assert!(!bls12_381_aggregate_verify(&pubkeys, &msgs, &aggregate_sig).unwrap());
+    }
+
+    pub fn test_bls12_381_aggregate_verify_same_message() {
+        let (pubkeys, msgs, aggregate_sig) = same_message_aggregate();
+        assert!(bls12_381_aggregate_verify(&pubkeys, &msgs, &aggregate_sig).unwrap());
+        assert_eq!(aggregate_verify_pairings(msgs.len()), 2);
+
+        // a signer missing from the aggregate
+        assert!(!bls12_381_aggregate_verify(&pubkeys[..2], &msgs, &aggregate_sig).unwrap());
+        assert!(
+            !bls12_381_aggregate_verify(&pubkeys, &[MSGS[1].to_vec()], &aggregate_sig).unwrap()
+        );
+
+        // keys that cancel out verify the identity signature, so they're rejected
+        let pubkey = G1Affine::from(G1Projective::generator() * Scalar::from(7_u64));
+        let cancelling = vec![
+            pubkey.to_compressed().to_vec(),
+            (-pubkey).to_compressed().to_vec(),
+        ];
+        let identity_sig = G2Affine::identity().to_compressed().to_vec();
+        assert!(!bls12_381_aggregate_verify(&cancelling, &msgs, &identity_sig).unwrap());
+    }
+
+    pub fn test_bls12_381_aggregate_verify_malformed_inputs() {
+        let (pubkeys, msgs, aggregate_sig) = aggregate();
+
+        assert!(matches!(
+            bls12_381_aggregate_verify(&pubkeys[..2], &msgs, &aggregate_sig),
+            Err(WasmApiCryptoError::BatchErr)
+        ));
+        assert!(matches!(
+            bls12_381_aggregate_verify(&pubkeys, &msgs[..2], &aggregate_sig),
+            Err(WasmApiCryptoError::BatchErr)
+        ));
+        assert!(matches!(
+            bls12_381_aggregate_verify(&[], &[], &aggregate_sig),
+            Err(WasmApiCryptoError::BatchErr)
+        ));
+        assert!(matches!(
+            bls12_381_aggregate_verify(&pubkeys, &msgs, &aggregate_sig[..95]),
+            Err(WasmApiCryptoError::InvalidSignatureFormat)
+        ));
+
+        let mut identity_pubkey = pubkeys.clone();
+        identity_pubkey[0] = G1Affine::identity().to_compressed().to_vec();
+        assert!(matches!(
+            bls12_381_aggregate_verify(&identity_pubkey, &msgs, &aggregate_sig),
+            Err(WasmApiCryptoError::InvalidPubkeyFormat)
+        ));
+    }
+}
@@ -0,0 +1,155 @@
+//! Validation and normalization of coin lists, backing the `normalize_coins` host function.
+//!
+//! The Cosmos SDK expects `sdk.Coins` to be sorted by denom, without duplicates or zero amounts,
+//! and rejects bank messages that aren't. Normalizing the funds before building a message also
+//! keeps the serialized funds (which callback signatures cover) identical to what the chain sees.
+//!
+//! Normalized lists are also what `require_funds` compares, when a contract checks that it was
+//! sent exactly the funds it requires.
+
+use std::collections::BTreeMap;
+
+use log::*;
+
+use cw_types_v1::coins::Coin;
+use cw_types_v1::math::Uint128;
+
+/// Error codes returned to contracts in the high half of the result
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CoinsError {
+    /// The input isn't a JSON list of coins
+    InvalidCoins = 1,
+    InvalidDenom = 2,
+    Overflow = 3,
+}
+
+/// Whether `denom` is accepted by the SDK: `[a-zA-Z][a-zA-Z0-9/:._-]{2,127}`
+pub fn is_valid_denom(denom: &str) -> bool {
+    let bytes = denom.as_bytes();
+    if bytes.len() < 3 || bytes.len() > 128 || !bytes[0].is_ascii_alphabetic() {
+        return false;
+    }
+
+    bytes[1..]
+        .iter()
+        .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'/' | b':' | b'.' | b'_' | b'-'))
+}
+
+/// Merges coins of the same denom, drops zero amounts and sorts the result by denom
+pub fn normalize_coins(coins: Vec<Coin>) -> Result<Vec<Coin>, CoinsError> {
+    let mut totals: BTreeMap<String, u128> = BTreeMap::new();
+    for coin in coins {
+        if !is_valid_denom(&coin.denom) {
+            debug!("normalize_coins() got invalid denom {:?}", coin.denom);
+            return Err(CoinsError::InvalidDenom);
+        }
+
+        let total = totals.entry(coin.denom).or_default();
+        *total = total
+            .checked_add(coin.amount.u128())
+            .ok_or(CoinsError::Overflow)?;
+    }
+
+    Ok(totals
+        .into_iter()
+        .filter(|(_, amount)| *amount != 0)
+        .map(|(denom, amount)| Coin {
+            denom,
+            amount: Uint128::new(amount),
+        })
+        .collect())
+}
+
+/// Whether `sent` are exactly the `required` funds. Both are normalized first, so the order of the
+/// coins and zero amounts don't matter.
+pub fn funds_match(required: Vec<Coin>, sent: &[Coin]) -> Result<bool, CoinsError> {
+    let required = normalize_coins(required)?;
+    // the chain only accepts valid funds, so the sent ones can't fail normalizing
+    Ok(normalize_coins(sent.to_vec()).map_or(false, |sent| sent == required))
+}
+
+/// Like `normalize_coins`, on the JSON the contract passes in and gets back
+pub fn normalize_coins_json(coins: &[u8]) -> Result<Vec<u8>, CoinsError> {
+    let coins: Vec<Coin> = serde_json::from_slice(coins).map_err(|err| {
+        debug!("normalize_coins() got malformed coins: {:?}", err);
+        CoinsError::InvalidCoins
+    })?;
+
+    serde_json::to_vec(&normalize_coins(coins)?).map_err(|_| CoinsError::InvalidCoins)
+}
+
+#[cfg(feature = "test")]
+pub mod tests {
+    use super::*;
+
+    pub fn test_normalize_coins_merges_and_drops_zeros() {
+        let coins = vec![
+            Coin::new(5, "uscrt"),
+            Coin::new(0, "uatom"),
+            Coin::new(3, "denom"),
+            Coin::new(7, "uscrt"),
+            Coin::new(0, "ibc/27394FB092D2ECCD56123C74F36E4C1F926001CEADA9CA97EA622B25F41E5EB2"),
+        ];
+
+        assert_eq!(
+            normalize_coins(coins).unwrap(),
+            vec![Coin::new(3, "denom"), Coin::new(12, "uscrt")]
+        );
+        assert!(normalize_coins(vec![]).unwrap().is_empty());
+        assert!(normalize_coins(vec![Coin::new(0, "uscrt")]).unwrap().is_empty());
+
+        let json = br#"[{"denom":"uscrt","amount":"1"},{"denom":"denom","amount":"2"}]"#;
+        assert_eq!(
+            normalize_coins_json(json).unwrap(),
+            br#"[{"denom":"denom","amount":"2"},{"denom":"uscrt","amount":"1"}]"#.to_vec()
+        );
+    }
+
+    pub fn test_normalize_coins_rejects_invalid_coins() {
+        assert_eq!(
+            normalize_coins(vec![Coin::new(1, "u")]),
+            Err(CoinsError::InvalidDenom)
+        );
+        assert_eq!(
+            normalize_coins(vec![Coin::new(1, "1denom")]),
+            Err(CoinsError::InvalidDenom)
+        );
+        assert_eq!(
+            normalize_coins(vec![Coin::new(u128::MAX, "uscrt"), Coin::new(1, "uscrt")]),
+            Err(CoinsError::Overflow)
+        );
+        assert_eq!(
+            normalize_coins_json(br#"[{"denom":"uscrt","amount":1}]"#),
+            Err(CoinsError::InvalidCoins)
+        );
+    }
+
+    pub fn test_funds_match() {
+        let sent = [Coin::new(2, "uatom"), Coin::new(5, "uscrt")];
+
+        assert_eq!(
+            funds_match(vec![Coin::new(5, "uscrt"), Coin::new(2, "uatom")], &sent),
+            Ok(true)
+        );
+        let required_in_parts = vec![
+            Coin::new(3, "uscrt"),
+            Coin::new(2, "uatom"),
+            Coin::new(2, "uscrt"),
+        ];
+        assert_eq!(funds_match(required_in_parts, &sent), Ok(true));
+        // a partial payment, and the right amounts in the wrong denoms
+        assert_eq!(funds_match(vec![Coin::new(5, "uscrt")], &sent), Ok(false));
+        assert_eq!(
+            funds_match(vec![Coin::new(5, "uatom"), Coin::new(2, "uscrt")], &sent),
+            Ok(false)
+        );
+        // nothing is required, and zero amounts count as nothing
+        assert_eq!(funds_match(vec![Coin::new(0, "uscrt")], &[]), Ok(true));
+        assert_eq!(funds_match(vec![], &sent), Ok(false));
+
+        assert_eq!(
+            funds_match(vec![Coin::new(1, "u")], &sent),
+            Err(CoinsError::InvalidDenom)
+        );
+    }
+}
@@ -0,0 +1,181 @@
+//! Compression of contract results before they're encrypted, as set by the chain's
+//! `OutputCompression` param. Outputs are part of consensus, so whether and how they're compressed
+//! comes from the chain, which passes it in the env of every call, rather than from how the node
+//! was built.
+//!
+//! Version 0 encrypts outputs as they are, like before. From version 1, every compressible output
+//! starts with a marker byte, inside the encrypted envelope, which says whether the rest is
+//! deflated or stored as is. Outputs below the param's threshold, and outputs that deflate doesn't
+//! shrink (e.g. data that's already compressed), are stored. The compression level is fixed per
+//! version, so a new level or algorithm is a new version.
+
+use log::*;
+
+use cw_types_v010::types::OutputCompression;
+use enclave_ffi_types::EnclaveError;
+
+/// Outputs are encrypted as they are
+pub const COMPRESSION_VERSION_NONE: u32 = 0;
+/// Outputs start with a marker byte, and are deflated at `DEFLATE_LEVEL`
+pub const COMPRESSION_VERSION_DEFLATE: u32 = 1;
+
+/// Inflating an output stops past this size, so a corrupt output can't exhaust enclave memory
+const MAX_DECOMPRESSED_SIZE: usize = 16 * 1024 * 1024;
+const DEFLATE_LEVEL: u8 = 6;
+
+const MARKER_STORED: u8 = 0;
+const MARKER_DEFLATE: u8 = 1;
+
+/// `marker ++ payload`, where the payload is `plaintext` deflated if it's at least `threshold`
+/// bytes and deflating makes it smaller
+fn compress(plaintext: &[u8], threshold: u64) -> Vec<u8> {
+    if plaintext.len() as u64 >= threshold {
+        let deflated = miniz_oxide::deflate::compress_to_vec(plaintext, DEFLATE_LEVEL);
+        if deflated.len() < plaintext.len() {
+            return [&[MARKER_DEFLATE][..], &deflated].concat();
+        }
+    }
+
+    [&[MARKER_STORED][..], plaintext].concat()
+}
+
+fn decompress(payload: &[u8]) -> Result<Vec<u8>, EnclaveError> {
+    match payload.split_first() {
+        Some((&MARKER_STORED, plaintext)) => Ok(plaintext.to_vec()),
+        Some((&MARKER_DEFLATE, deflated)) => {
+            miniz_oxide::inflate::decompress_to_vec_with_limit(deflated, MAX_DECOMPRESSED_SIZE)
+                .map_err(|err| {
+                    warn!("failed to inflate a compressed output: {:?}", err);
+                    EnclaveError::FailedToDeserialize
+                })
+        }
+        marker => {
+            warn!("output has an unknown compression marker {:?}", marker);
+            Err(EnclaveError::FailedToDeserialize)
+        }
+    }
+}
+
+/// Prepares a plaintext output for encryption
+pub fn compress_output(
+    plaintext: Vec<u8>,
+    compression: &OutputCompression,
+) -> Result<Vec<u8>, EnclaveError> {
+    match compression.version {
+        COMPRESSION_VERSION_NONE => Ok(plaintext),
+        COMPRESSION_VERSION_DEFLATE => Ok(compress(&plaintext, compression.threshold)),
+        version => {
+            warn!("unknown output compression version {}", version);
+            Err(EnclaveError::FailedToSerialize)
+        }
+    }
+}
+
+/// Reverses `compress_output` on a decrypted output, with the same `compression` it was
+/// compressed with
+pub fn decompress_output(
+    plaintext: Vec<u8>,
+    compression: &OutputCompression,
+) -> Result<Vec<u8>, EnclaveError> {
+    match compression.version {
+        COMPRESSION_VERSION_NONE => Ok(plaintext),
+        COMPRESSION_VERSION_DEFLATE => decompress(&plaintext),
+        version => {
+            warn!("unknown output compression version {}", version);
+            Err(EnclaveError::FailedToDeserialize)
+        }
+    }
+}
+
+#[cfg(feature = "test")]
+pub mod tests {
+    use super::*;
+    use enclave_crypto::sha_256;
+
+    const THRESHOLD: u64 = 512;
+
+    fn deflate(threshold: u64) -> OutputCompression {
+        OutputCompression {
+            version: COMPRESSION_VERSION_DEFLATE,
+            threshold,
+        }
+    }
+
+    fn round_trip(plaintext: &[u8]) -> Vec<u8> {
+        let compression = deflate(THRESHOLD);
+        let compressed = compress_output(plaintext.to_vec(), &compression).unwrap();
+        assert_eq!(
+            decompress_output(compressed.clone(), &compression).unwrap(),
+            plaintext
+        );
+        compressed
+    }
+
+    pub fn test_compression_round_trip_empty() {
+        assert_eq!(round_trip(&[]), vec![MARKER_STORED]);
+    }
+
+    pub fn test_compression_round_trip_compressible() {
+        let plaintext = br#"{"balance":{"amount":"1000000"}}"#.repeat(64);
+        let compressed = round_trip(&plaintext);
+
+        assert_eq!(compressed[0], MARKER_DEFLATE);
+        assert!(compressed.len() < plaintext.len() / 4);
+        // every node has to produce the same output
+        assert_eq!(round_trip(&plaintext), compressed);
+    }
+
+    pub fn test_compression_round_trip_already_compressed() {
+        // hashes don't deflate, like data that's compressed already
+        let mut plaintext = vec![];
+        let mut block = sha_256(b"already compressed");
+        while plaintext.len() < 4 * THRESHOLD as usize {
+            plaintext.extend_from_slice(&block);
+            block = sha_256(&block);
+        }
+
+        let compressed = round_trip(&plaintext);
+        assert_eq!(compressed[0], MARKER_STORED);
+        assert_eq!(compressed.len(), plaintext.len() + 1);
+    }
+
+    pub fn test_compression_skipped_below_threshold() {
+        let plaintext = b"a".repeat(THRESHOLD as usize - 1);
+        let compressed = round_trip(&plaintext);
+
+        assert_eq!(compressed[0], MARKER_STORED);
+        assert_eq!(&compressed[1..], plaintext.as_slice());
+
+        // the threshold is the chain's
+        let compressed = compress_output(plaintext.clone(), &deflate(64)).unwrap();
+        assert_eq!(compressed[0], MARKER_DEFLATE);
+    }
+
+    pub fn test_compression_follows_version() {
+        let plaintext = b"a".repeat(4 * THRESHOLD as usize);
+        let none = OutputCompression::default();
+
+        // version 0 leaves outputs as they were before compression, without a marker
+        assert_eq!(
+            compress_output(plaintext.clone(), &none).unwrap(),
+            plaintext
+        );
+        assert_eq!(
+            decompress_output(plaintext.clone(), &none).unwrap(),
+            plaintext
+        );
+
+        let unknown = OutputCompression {
+            version: COMPRESSION_VERSION_DEFLATE + 1,
+            threshold: THRESHOLD,
+        };
+        assert!(compress_output(plaintext.clone(), &unknown).is_err());
+        assert!(decompress_output(plaintext, &unknown).is_err());
+    }
+
+    pub fn test_decompression_rejects_unknown_marker() {
+        assert!(decompress(&[]).is_err());
+        assert!(decompress(&[2, 1, 2, 3]).is_err());
+        assert!(decompress(&[MARKER_DEFLATE, 1, 2, 3]).is_err());
+    }
+}
@@ -0,0 +1,45 @@
+//! Contract address prediction backing the `predict_instantiate_address` host function.
+//!
+//! The chain derives the address of a new contract from the code id, its global instance counter
+//! and the creator: `ripemd160(sha256(be_u64(code_id << 32 + instance_id) ++ creator))`. The label
+//! isn't part of it, so there's nothing else to predict from. The chain passes the next instance
+//! id in the env, which makes the prediction right for the first contract instantiated after the
+//! call, as long as nothing else instantiates a contract before it.
+
+use cw_types_v010::types::CanonicalAddr;
+use enclave_crypto::hash::ripemd::ripemd160;
+use enclave_crypto::sha_256;
+
+/// The address the chain gives to a contract of `code_id` instantiated by `creator`, if it's the
+/// `instance_id`th contract instantiated on the chain
+pub fn predict_contract_address(code_id: u64, instance_id: u64, creator: &[u8]) -> CanonicalAddr {
+    // the chain shifts without checking, so a code id past 32 bits wraps the same way
+    let contract_id = code_id.wrapping_shl(32).wrapping_add(instance_id);
+
+    let mut data = Vec::with_capacity(8 + creator.len());
+    data.extend_from_slice(&contract_id.to_be_bytes());
+    data.extend_from_slice(creator);
+
+    CanonicalAddr::from_vec(ripemd160(&sha_256(&data)).to_vec())
+}
+
+#[cfg(feature = "test")]
+pub mod tests {
+    use super::*;
+
+    pub fn test_predict_contract_address_matches_chain() {
+        let creator: Vec<u8> = (1..=20).collect();
+
+        // computed like `contractAddress` in the compute keeper
+        assert_eq!(
+            hex::encode(predict_contract_address(1, 1, &creator).as_slice()),
+            "108d1a68787b1036296e63e50af0a85acb7f953d"
+        );
+
+        // each of the inputs changes the address
+        let address = predict_contract_address(1, 1, &creator);
+        assert_ne!(predict_contract_address(2, 1, &creator), address);
+        assert_ne!(predict_contract_address(1, 2, &creator), address);
+        assert_ne!(predict_contract_address(1, 1, &creator[1..]), address);
+    }
+}
@@ -1,12 +1,16 @@
 use serde::{Deserialize, Serialize};
+use std::sync::SgxRwLock;
+
+use lazy_static::lazy_static;
 
 #[cfg(feature = "random")]
-use cw_types_generic::{ContractFeature, CwEnv};
+use cw_types_generic::CwEnv;
 
-use cw_types_generic::{BaseAddr, BaseEnv};
+use cw_types_generic::{BaseAddr, BaseEnv, ContractFeature};
 
 use cw_types_v010::encoding::Binary;
 use cw_types_v010::types::CanonicalAddr;
+use cw_types_v1::types::AttestationType;
 
 use enclave_cosmos_types::types::{ContractCode, HandleType, SigInfo, VerifyParamsType};
 use enclave_crypto::Ed25519PublicKey;
@@ -25,7 +29,7 @@ use crate::external::results::{
     HandleSuccess, InitSuccess, MigrateSuccess, QuerySuccess, UpdateAdminSuccess,
 };
 use crate::message::{is_ibc_msg, parse_message};
-use crate::types::ParsedMessage;
+use crate::types::{ParsedMessage, TxPosition};
 
 use crate::random::update_msg_counter;
 
@@ -38,14 +42,30 @@ use crate::hardcoded_admins::is_hardcoded_contract_admin;
 
 use super::contract_validation::{
     generate_contract_key, validate_contract_key, validate_msg, verify_params, ContractKey,
+    TxMsgPosition,
 };
-use super::gas::WasmCosts;
+use super::gas::{submsg_gas_limit, WasmCosts, QUERY_RESULT_BYTE_GAS};
 use super::io::{
-    finalize_raw_output, manipulate_callback_sig_for_plaintext, post_process_output,
-    set_all_logs_to_plaintext,
+    cap_submsg_gas_limits, check_event_types, finalize_raw_output,
+    manipulate_callback_sig_for_plaintext, out_of_gas_output, post_process_output,
+    set_all_logs_to_plaintext, sort_deterministic_attributes, strip_plaintext_ack_prefix,
 };
 use super::types::{IoNonce, SecretMessage};
 
+lazy_static! {
+    /// How this node registered, exposed to v1 queries as `env.enclave.attestation_type`
+    static ref ATTESTATION_TYPE: SgxRwLock<Option<AttestationType>> = SgxRwLock::new(None);
+}
+
+/// Records how this node registered, which differs between nodes, so only queries may see it
+pub fn configure_attestation_type(attestation_type: Option<AttestationType>) {
+    *ATTESTATION_TYPE.write().unwrap() = attestation_type;
+}
+
+fn attestation_type() -> Option<AttestationType> {
+    *ATTESTATION_TYPE.read().unwrap()
+}
+
 /*
 Each contract is compiled with these functions already implemented in wasm:
 fn cosmwasm_api_0_6() -> i32;  // Seems unused, but we should support it anyways
@@ -119,7 +139,7 @@ pub fn init(
     let secret_msg = SecretMessage::from_slice(msg)?;
 
     //let start = Instant::now();
-    verify_params(
+    let tx_msg_position = verify_params(
         &parsed_sig_info,
         sent_funds,
         &canonical_sender_address,
@@ -143,6 +163,8 @@ pub fn init(
     let ValidatedMessage {
         validated_msg,
         reply_params,
+        gas_limit: declared_gas_limit,
+        reply_depth,
     } = validate_msg(
         &canonical_contract_address,
         &decrypted_msg,
@@ -153,10 +175,13 @@ pub fn init(
     // let duration = start.elapsed();
     // trace!("Time elapsed in validate_msg: {:?}", duration);
 
+    let gas_limit = submsg_gas_limit(gas_limit, declared_gas_limit);
+
     //let start = Instant::now();
     let mut engine = start_engine(
         context,
         gas_limit,
+        base_env.get_contract_gas_ceiling(),
         &contract_code,
         &og_contract_key,
         ContractOperation::Init,
@@ -168,11 +193,20 @@ pub fn init(
     // let duration = start.elapsed();
     // trace!("Time elapsed in start_engine: {:?}", duration);
 
+    let output_compression = base_env.get_output_compression();
+    let submsg_limit = base_env.get_submsg_limit();
+    engine.set_min_gas_price(base_env.get_min_gas_price());
+    engine.set_output_compression(&output_compression);
+    engine.set_max_query_depth(base_env.get_max_query_depth());
+    engine.set_next_instance_id(base_env.get_next_instance_id());
+    engine.set_submsg_limit(submsg_limit.clone());
+    engine.set_tx_msg_position(tx_msg_position);
     let mut versioned_env = base_env
         .clone()
         .into_versioned_env(&engine.get_api_version());
 
     versioned_env.set_contract_hash(&contract_hash);
+    set_tx_msg_position_in_env(&mut versioned_env, tx_msg_position);
 
     #[cfg(feature = "random")]
     set_random_in_env(
@@ -182,7 +216,10 @@ pub fn init(
         &mut versioned_env,
     );
 
-    update_msg_counter(block_height);
+    let msg_counter = update_msg_counter(block_height);
+    engine.set_call_info(&canonical_contract_address.0 .0, block_height, &validated_msg);
+    engine.set_msg_counter(msg_counter);
+    engine.set_sent_funds(sent_funds);
     //let start = Instant::now();
     let result = engine.init(&versioned_env, validated_msg);
     // let duration = start.elapsed();
@@ -190,16 +227,34 @@ pub fn init(
 
     *used_gas = engine.gas_used();
 
-    let output = result?;
+    let output = match result {
+        // Like in handle, a caller that handles this submessage's reply gets running out of gas
+        // as an error. Nothing the contract wrote is flushed.
+        Err(EnclaveError::OutOfGas) | Err(EnclaveError::ContractGasCeilingExceeded)
+            if reply_params.is_some() =>
+        {
+            debug!("submessage ran out of gas, returning the error to the caller's reply");
+            out_of_gas_output()
+        }
+        result => {
+            let output = result?;
 
-    #[cfg(not(feature = "random"))]
-    let random: Option<Binary> = None;
-    #[cfg(feature = "random")]
-    let random = versioned_env.get_random();
+            let output_recording = engine.record_output_hash(&output);
+            *used_gas = engine.gas_used();
+            output_recording?;
 
-    engine
-        .flush_cache(random)
-        .map_err(|_| EnclaveError::FailedFunctionCall)?;
+            #[cfg(not(feature = "random"))]
+            let random: Option<Binary> = None;
+            #[cfg(feature = "random")]
+            let random = versioned_env.get_random();
+
+            engine
+                .flush_cache(random)
+                .map_err(|_| EnclaveError::FailedFunctionCall)?;
+
+            output
+        }
+    };
 
     // TODO: copy cosmwasm's structures to enclave
     // TODO: ref: https://github.com/CosmWasm/cosmwasm/blob/b971c037a773bf6a5f5d08a88485113d9b9e8e7b/packages/std/src/init_handle.rs#L129
@@ -212,9 +267,16 @@ pub fn init(
         &canonical_contract_address,
         versioned_env.get_contract_hash(),
         reply_params,
+        reply_depth,
         &canonical_sender_address,
         false,
         false,
+        Some(gas_limit.saturating_sub(*used_gas)),
+        submsg_limit.as_ref(),
+        base_env.get_max_event_type_length(),
+        TxPosition::from_env(&base_env),
+        pads_output(&engine),
+        &output_compression,
     )?;
 
     // let duration = start.elapsed();
@@ -325,7 +387,7 @@ pub fn migrate(
     let secret_msg = SecretMessage::from_slice(msg)?;
 
     //let start = Instant::now();
-    verify_params(
+    let tx_msg_position = verify_params(
         &parsed_sig_info,
         sent_funds,
         &canonical_sender_address,
@@ -349,6 +411,8 @@ pub fn migrate(
     let ValidatedMessage {
         validated_msg,
         reply_params,
+        gas_limit: declared_gas_limit,
+        reply_depth,
     } = validate_msg(
         &canonical_contract_address,
         &decrypted_msg,
@@ -359,10 +423,13 @@ pub fn migrate(
     // let duration = start.elapsed();
     // trace!("Time elapsed in validate_msg: {:?}", duration);
 
+    let gas_limit = submsg_gas_limit(gas_limit, declared_gas_limit);
+
     //let start = Instant::now();
     let mut engine = start_engine(
         context,
         gas_limit,
+        base_env.get_contract_gas_ceiling(),
         &contract_code,
         &og_contract_key,
         ContractOperation::Migrate,
@@ -374,9 +441,20 @@ pub fn migrate(
     // let duration = start.elapsed();
     // trace!("Time elapsed in start_engine: {:?}", duration);
 
+    let output_compression = base_env.get_output_compression();
+    let submsg_limit = base_env.get_submsg_limit();
+    engine.set_min_gas_price(base_env.get_min_gas_price());
+    engine.set_output_compression(&output_compression);
+    engine.set_max_query_depth(base_env.get_max_query_depth());
+    engine.set_next_instance_id(base_env.get_next_instance_id());
+    engine.set_submsg_limit(submsg_limit.clone());
+    engine.set_tx_msg_position(tx_msg_position);
+    let tx_position = TxPosition::from_env(&base_env);
+    let max_event_type_length = base_env.get_max_event_type_length();
     let mut versioned_env = base_env.into_versioned_env(&engine.get_api_version());
 
     versioned_env.set_contract_hash(&contract_hash);
+    set_tx_msg_position_in_env(&mut versioned_env, tx_msg_position);
 
     let new_contract_key = generate_contract_key(
         &canonical_sender_address,
@@ -394,13 +472,20 @@ pub fn migrate(
         &mut versioned_env,
     );
 
-    update_msg_counter(block_height);
+    let msg_counter = update_msg_counter(block_height);
+    engine.set_call_info(&canonical_contract_address.0 .0, block_height, &validated_msg);
+    engine.set_msg_counter(msg_counter);
+    engine.set_sent_funds(sent_funds);
     let result = engine.migrate(&versioned_env, validated_msg);
 
     *used_gas = engine.gas_used();
 
     let output = result?;
 
+    let output_recording = engine.record_output_hash(&output);
+    *used_gas = engine.gas_used();
+    output_recording?;
+
     let random = versioned_env.get_random();
 
     engine
@@ -413,9 +498,16 @@ pub fn migrate(
         &canonical_contract_address,
         versioned_env.get_contract_hash(),
         reply_params,
+        reply_depth,
         &canonical_sender_address,
         false,
         false,
+        Some(gas_limit.saturating_sub(*used_gas)),
+        submsg_limit.as_ref(),
+        max_event_type_length,
+        tx_position,
+        pads_output(&engine),
+        &output_compression,
     )?;
 
     // let duration = start.elapsed();
@@ -553,6 +645,7 @@ pub fn handle(
 
     trace!("Handle type is {:?}", parsed_handle_type);
 
+    let output_compression = base_env.get_output_compression();
     let ParsedMessage {
         should_verify_sig_info,
         should_verify_input,
@@ -561,7 +654,12 @@ pub fn handle(
         secret_msg,
         decrypted_msg,
         data_for_validation,
-    } = parse_message(msg, &parsed_handle_type)?;
+    } = parse_message(
+        msg,
+        &parsed_handle_type,
+        TxPosition::from_env(&base_env),
+        &output_compression,
+    )?;
 
     let canonical_sender_address = match to_canonical(sender) {
         Ok(can) => can,
@@ -574,7 +672,7 @@ pub fn handle(
     // - Plaintext replies (resulting from an IBC call)
     // - IBC WASM Hooks
     // - (In the future:) ICA
-    verify_params(
+    let tx_msg_position = verify_params(
         &parsed_sig_info,
         sent_funds,
         &canonical_sender_address,
@@ -589,6 +687,8 @@ pub fn handle(
 
     let mut validated_msg = decrypted_msg.clone();
     let mut reply_params: Option<Vec<ReplyParams>> = None;
+    let mut declared_gas_limit: Option<u64> = None;
+    let mut reply_depth = 0;
     if was_msg_encrypted {
         let x = validate_msg(
             &canonical_contract_address,
@@ -599,7 +699,10 @@ pub fn handle(
         )?;
         validated_msg = x.validated_msg;
         reply_params = x.reply_params;
+        declared_gas_limit = x.gas_limit;
+        reply_depth = x.reply_depth;
     }
+    let gas_limit = submsg_gas_limit(gas_limit, declared_gas_limit);
 
     let og_contract_key = base_env.get_og_contract_key()?;
 
@@ -609,6 +712,7 @@ pub fn handle(
     let mut engine = start_engine(
         context,
         gas_limit,
+        base_env.get_contract_gas_ceiling(),
         &contract_code,
         &og_contract_key,
         ContractOperation::Handle,
@@ -618,6 +722,13 @@ pub fn handle(
         base_env.0.block.time,
     )?;
 
+    let submsg_limit = base_env.get_submsg_limit();
+    engine.set_min_gas_price(base_env.get_min_gas_price());
+    engine.set_output_compression(&output_compression);
+    engine.set_max_query_depth(base_env.get_max_query_depth());
+    engine.set_next_instance_id(base_env.get_next_instance_id());
+    engine.set_submsg_limit(submsg_limit.clone());
+    engine.set_tx_msg_position(tx_msg_position);
     let mut versioned_env = base_env
         .clone()
         .into_versioned_env(&engine.get_api_version());
@@ -657,22 +768,44 @@ pub fn handle(
     }
 
     versioned_env.set_contract_hash(&contract_hash);
+    set_tx_msg_position_in_env(&mut versioned_env, tx_msg_position);
 
-    update_msg_counter(block_height);
+    let msg_counter = update_msg_counter(block_height);
+    engine.set_call_info(&canonical_contract_address.0 .0, block_height, &validated_msg);
+    engine.set_msg_counter(msg_counter);
+    engine.set_sent_funds(sent_funds);
 
     let result = engine.handle(&versioned_env, validated_msg, &parsed_handle_type);
 
     *used_gas = engine.gas_used();
 
-    let mut output = result?;
+    let mut output = match result {
+        // A caller that handles this submessage's reply gets running out of gas as an error,
+        // instead of its whole tx aborting. Nothing the contract wrote is flushed.
+        Err(EnclaveError::OutOfGas) | Err(EnclaveError::ContractGasCeilingExceeded)
+            if reply_params.is_some() =>
+        {
+            debug!("submessage ran out of gas, returning the error to the caller's reply");
+            out_of_gas_output()
+        }
+        result => {
+            let output = result?;
 
-    let random = versioned_env.get_random();
+            let output_recording = engine.record_output_hash(&output);
+            *used_gas = engine.gas_used();
+            output_recording?;
 
-    // This gets refunded because it will get charged later by the sdk
-    let refund_cache_gas = engine
-        .flush_cache(random)
-        .map_err(|_| EnclaveError::FailedFunctionCall)?;
-    *used_gas = used_gas.saturating_sub(refund_cache_gas);
+            let random = versioned_env.get_random();
+
+            // This gets refunded because it will get charged later by the sdk
+            let refund_cache_gas = engine
+                .flush_cache(random)
+                .map_err(|_| EnclaveError::FailedFunctionCall)?;
+            *used_gas = used_gas.saturating_sub(refund_cache_gas);
+
+            output
+        }
+    };
 
     debug!(
         "(2) nonce just before encrypt_output: nonce = {:x?} pubkey = {:x?}",
@@ -685,21 +818,45 @@ pub fn handle(
             &canonical_contract_address,
             versioned_env.get_contract_hash(),
             reply_params,
+            reply_depth,
             &canonical_sender_address,
             false,
             is_ibc_msg(parsed_handle_type),
+            Some(gas_limit.saturating_sub(*used_gas)),
+            submsg_limit.as_ref(),
+            base_env.get_max_event_type_length(),
+            TxPosition::from_env(&base_env),
+            pads_output(&engine),
+            &output_compression,
         )?;
     } else {
         let mut raw_output =
             manipulate_callback_sig_for_plaintext(&canonical_contract_address, output)?;
+        check_event_types(&raw_output, base_env.get_max_event_type_length())?;
+        cap_submsg_gas_limits(&mut raw_output, gas_limit.saturating_sub(*used_gas));
+        sort_deterministic_attributes(&mut raw_output);
         set_all_logs_to_plaintext(&mut raw_output);
+        strip_plaintext_ack_prefix(&mut raw_output);
 
-        output = finalize_raw_output(raw_output, false, is_ibc_msg(parsed_handle_type), false)?;
+        output = finalize_raw_output(
+            raw_output,
+            false,
+            is_ibc_msg(parsed_handle_type),
+            false,
+            None,
+        )?;
     }
 
     Ok(HandleSuccess { output })
 }
 
+/// Whether the contract asked for its encrypted outputs to be padded, see `padding`
+fn pads_output(engine: &Engine) -> bool {
+    engine
+        .supported_features()
+        .contains(&ContractFeature::PaddedOutput)
+}
+
 #[cfg(feature = "random")]
 fn set_random_in_env(
     block_height: u64,
@@ -720,6 +877,15 @@ fn set_random_in_env(
     }
 }
 
+/// Sets `env.transaction.msg_index` and `msg_count`, or clears them when the contract isn't called
+/// by one of the transaction's messages
+fn set_tx_msg_position_in_env(versioned_env: &mut CwEnv, tx_msg_position: Option<TxMsgPosition>) {
+    versioned_env.set_tx_msg_position(
+        tx_msg_position.map(|position| position.msg_index),
+        tx_msg_position.map(|position| position.msg_count),
+    );
+}
+
 fn extract_sig_info(sig_info: &[u8]) -> Result<SigInfo, EnclaveError> {
     serde_json::from_slice(sig_info).map_err(|err| {
         warn!(
@@ -747,7 +913,7 @@ pub fn query(
     let base_env: BaseEnv = extract_base_env(env)?;
     let query_depth = extract_query_depth(env)?;
 
-    let (_, contract_address, _, _) = base_env.get_verification_params();
+    let (_, contract_address, block_height, _) = base_env.get_verification_params();
 
     let canonical_contract_address = to_canonical(contract_address)?;
 
@@ -769,6 +935,7 @@ pub fn query(
     let mut engine = start_engine(
         context,
         gas_limit,
+        base_env.get_contract_gas_ceiling(),
         &contract_code,
         &og_contract_key,
         ContractOperation::Query,
@@ -778,11 +945,19 @@ pub fn query(
         base_env.0.block.time,
     )?;
 
+    let output_compression = base_env.get_output_compression();
+    engine.set_min_gas_price(base_env.get_min_gas_price());
+    engine.set_output_compression(&output_compression);
+    engine.set_max_query_depth(base_env.get_max_query_depth());
+    engine.set_next_instance_id(base_env.get_next_instance_id());
     let mut versioned_env = base_env
         .clone()
         .into_versioned_env(&engine.get_api_version());
 
     versioned_env.set_contract_hash(&contract_hash);
+    versioned_env.set_enclave_info(attestation_type());
+    set_tx_msg_position_in_env(&mut versioned_env, None);
+    engine.set_call_info(&canonical_contract_address.0 .0, block_height, &validated_msg);
 
     let result = engine.query(&versioned_env, validated_msg);
     *used_gas = engine.gas_used();
@@ -794,18 +969,54 @@ pub fn query(
         &CanonicalAddr(Binary(Vec::new())), // Not used for queries (can't init a new contract from a query)
         "",   // Not used for queries (can't call a sub-message from a query),
         None, // Not used for queries (Query response is not replied to the caller),
+        0,    // Not used for queries (can't call a sub-message from a query)
         &CanonicalAddr(Binary(Vec::new())), // Not used for queries (used only for replies)
         true,
         false,
+        None,
+        None, // Not used for queries (can't call a sub-message from a query)
+        0,    // Not used for queries (queries don't emit events)
+        TxPosition::from_env(&base_env),
+        pads_output(&engine),
+        &output_compression,
     )?;
 
+    charge_query_output(query_depth, output.len(), used_gas, gas_limit)?;
+
     Ok(QuerySuccess { output })
 }
 
+/// The depth of the queries the chain's gRPC API sends, as opposed to the queries contracts send
+/// each other
+const TOP_LEVEL_QUERY_DEPTH: u32 = 1;
+
+/// Charges `QUERY_RESULT_BYTE_GAS` for each byte of the output of a top level query. Queries that
+/// contracts send each other run inside transactions, so their gas is part of consensus and stays
+/// as it was.
+fn charge_query_output(
+    query_depth: u32,
+    output_len: usize,
+    used_gas: &mut u64,
+    gas_limit: u64,
+) -> Result<(), EnclaveError> {
+    if query_depth != TOP_LEVEL_QUERY_DEPTH {
+        return Ok(());
+    }
+
+    *used_gas = used_gas.saturating_add((output_len as u64).saturating_mul(QUERY_RESULT_BYTE_GAS));
+    if *used_gas > gas_limit {
+        warn!("query output of {} bytes exceeds the gas limit", output_len);
+        return Err(EnclaveError::OutOfGas);
+    }
+
+    Ok(())
+}
+
 #[allow(clippy::too_many_arguments)]
 fn start_engine(
     context: Ctx,
     gas_limit: u64,
+    contract_gas_ceiling: u64,
     contract_code: &ContractCode,
     og_contract_key: &ContractKey,
     operation: ContractOperation,
@@ -817,6 +1028,7 @@ fn start_engine(
     crate::wasm3::Engine::new(
         context,
         gas_limit,
+        contract_gas_ceiling,
         WasmCosts::default(),
         contract_code,
         *og_contract_key,
@@ -869,3 +1081,105 @@ fn extract_query_depth(env: &[u8]) -> Result<u32, EnclaveError> {
             env.query_depth
         })
 }
+
+#[cfg(feature = "test")]
+pub mod tests {
+    use super::*;
+    use cw_types_generic::CosmWasmApiVersion;
+    use cw_types_v1::types::EnclaveInfo;
+
+    fn env_json(
+        api_version: CosmWasmApiVersion,
+        attestation_type: Option<AttestationType>,
+    ) -> serde_json::Value {
+        let base_env: BaseEnv = serde_json::from_str(
+            r#"{"block":{"height":1,"time":1000000000,"chain_id":"test"},"message":{"sender":"sender","sent_funds":[]},"contract":{"address":"contract"},"contract_key":null}"#,
+        )
+        .unwrap();
+
+        let mut versioned_env = base_env.into_versioned_env(&api_version);
+        versioned_env.set_enclave_info(attestation_type);
+
+        let (env_bytes, _) = versioned_env.get_wasm_ptrs().unwrap();
+        serde_json::from_slice(&env_bytes).unwrap()
+    }
+
+    pub fn test_env_has_no_build_dependent_fields() {
+        // the build mode differs between nodes, so nothing in a tx env may depend on it
+        let env = env_json(CosmWasmApiVersion::V1, None);
+        assert!(env.get("enclave").is_none());
+
+        // v0.10 contracts don't get the field at all
+        let env = env_json(CosmWasmApiVersion::V010, Some(AttestationType::SgxDcap));
+        assert!(env.get("enclave").is_none());
+    }
+
+    pub fn test_env_attestation_type() {
+        let env = env_json(CosmWasmApiVersion::V1, Some(AttestationType::SgxDcap));
+        assert_eq!(env["enclave"]["attestation_type"], "sgx_dcap");
+
+        let env = env_json(CosmWasmApiVersion::V1, Some(AttestationType::SgxEpid));
+        assert_eq!(env["enclave"]["attestation_type"], "sgx_epid");
+
+        // transactions get no attestation type, and the field is left out
+        let env = env_json(CosmWasmApiVersion::V1, None);
+        assert!(env.get("enclave").is_none());
+    }
+
+    pub fn test_attestation_type_round_trip() {
+        for attestation_type in &[
+            AttestationType::SgxEpid,
+            AttestationType::SgxDcap,
+            AttestationType::IntelTdx,
+        ] {
+            let env = env_json(CosmWasmApiVersion::V1, Some(*attestation_type));
+            let enclave_info: EnclaveInfo = serde_json::from_value(env["enclave"].clone()).unwrap();
+            assert_eq!(enclave_info.attestation_type, *attestation_type);
+        }
+    }
+
+    pub fn test_query_output_gas_is_top_level_only() {
+        let mut used_gas = 1000;
+        charge_query_output(TOP_LEVEL_QUERY_DEPTH, 10, &mut used_gas, 10_000).unwrap();
+        assert_eq!(used_gas, 1000 + 10 * QUERY_RESULT_BYTE_GAS);
+
+        // a query a contract sent is charged like before
+        let mut used_gas = 1000;
+        charge_query_output(TOP_LEVEL_QUERY_DEPTH + 1, 10, &mut used_gas, 1000).unwrap();
+        assert_eq!(used_gas, 1000);
+
+        let mut used_gas = 1000;
+        assert!(matches!(
+            charge_query_output(TOP_LEVEL_QUERY_DEPTH, 10, &mut used_gas, 1000),
+            Err(EnclaveError::OutOfGas)
+        ));
+    }
+
+    pub fn test_env_tx_msg_position() {
+        let tx_env = |tx_msg_position: Option<TxMsgPosition>| {
+            // the host can't choose the position
+            let base_env: BaseEnv = serde_json::from_str(
+                r#"{"block":{"height":1,"time":1000000000,"chain_id":"test"},"message":{"sender":"sender","sent_funds":[]},"contract":{"address":"contract"},"contract_key":null,"transaction":{"index":0,"msg_index":5,"msg_count":9}}"#,
+            )
+            .unwrap();
+
+            let mut versioned_env = base_env.into_versioned_env(&CosmWasmApiVersion::V1);
+            set_tx_msg_position_in_env(&mut versioned_env, tx_msg_position);
+
+            let (env_bytes, _) = versioned_env.get_wasm_ptrs().unwrap();
+            serde_json::from_slice::<serde_json::Value>(&env_bytes).unwrap()
+        };
+
+        let env = tx_env(Some(TxMsgPosition {
+            msg_index: 1,
+            msg_count: 3,
+        }));
+        assert_eq!(env["transaction"]["msg_index"], 1);
+        assert_eq!(env["transaction"]["msg_count"], 3);
+
+        // calls that aren't one of the transaction's messages get no position
+        let env = tx_env(None);
+        assert!(env["transaction"].get("msg_index").is_none());
+        assert!(env["transaction"].get("msg_count").is_none());
+    }
+}
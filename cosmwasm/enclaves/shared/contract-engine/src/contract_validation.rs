@@ -1,6 +1,9 @@
 use cw_types_v1::ibc::IbcPacketReceiveMsg;
-use cw_types_v1::results::REPLY_ENCRYPTION_MAGIC_BYTES;
+use cw_types_v1::results::{
+    ReplyOn, REPLY_DEPTH_MAGIC_BYTES, REPLY_ENCRYPTION_MAGIC_BYTES, SUBMSG_GAS_LIMIT_MAGIC_BYTES,
+};
 use log::*;
+use serde::Serialize;
 
 use cw_types_generic::BaseEnv;
 
@@ -20,13 +23,15 @@ use crate::input_validation::contract_address_validation::verify_contract_addres
 use crate::input_validation::msg_validation::verify_and_get_sdk_msg;
 use crate::input_validation::send_funds_validations::verify_sent_funds;
 use crate::input_validation::sender_validation::verify_sender;
-use crate::io::create_callback_signature;
+use crate::io::{create_callback_signature, verify_callback_sig, CallbackContext};
 use crate::message::is_ibc_msg;
 use crate::types::SecretMessage;
 
 #[cfg(feature = "light-client-validation")]
 use block_verifier::VERIFIED_BLOCK_MESSAGES;
 
+use std::convert::TryFrom;
+
 extern crate hex;
 
 pub type ContractKey = [u8; CONTRACT_KEY_LENGTH];
@@ -35,6 +40,7 @@ pub const CONTRACT_KEY_LENGTH: usize = HASH_SIZE + HASH_SIZE;
 
 const HEX_ENCODED_HASH_SIZE: usize = HASH_SIZE * 2;
 const SIZE_OF_U64: usize = 8;
+const SIZE_OF_U32: usize = 4;
 
 #[cfg(feature = "light-client-validation")]
 fn is_subslice(larger: &[u8], smaller: &[u8]) -> bool {
@@ -361,15 +367,134 @@ pub fn generate_contract_key_proof(
     contract_key_proof_secret.sign_sha_256(data_to_sign.as_slice())
 }
 
+/// Domain separator for execution receipts, so a receipt can never be mistaken for
+/// (or replayed as) any other signature produced by the enclave.
+pub const EXECUTION_RECEIPT_DOMAIN: &[u8] = b"secret_execution_receipt_v1";
+
+pub const EXECUTION_RECEIPT_SIZE: usize = 64;
+
+fn execution_receipt_sign_bytes(
+    contract_address: &[u8],
+    input_hash: &[u8; HASH_SIZE],
+    output_hash: &[u8; HASH_SIZE],
+    block_height: u64,
+) -> [u8; HASH_SIZE] {
+    let mut data_to_sign = vec![];
+    data_to_sign.extend_from_slice(EXECUTION_RECEIPT_DOMAIN);
+    data_to_sign.extend_from_slice(&(contract_address.len() as u64).to_be_bytes());
+    data_to_sign.extend_from_slice(contract_address);
+    data_to_sign.extend_from_slice(input_hash);
+    data_to_sign.extend_from_slice(output_hash);
+    data_to_sign.extend_from_slice(&block_height.to_be_bytes());
+
+    sha_256(data_to_sign.as_slice())
+}
+
+fn execution_receipt_signing_key(receipt_secret: &AESKey) -> ed25519_zebra::SigningKey {
+    ed25519_zebra::SigningKey::from(*receipt_secret.get())
+}
+
+/// The public key clients use to verify execution receipts. It is derived from the consensus
+/// seed, so it's the same on every node.
+pub fn execution_receipt_public_key(receipt_secret: &AESKey) -> [u8; 32] {
+    ed25519_zebra::VerificationKey::from(&execution_receipt_signing_key(receipt_secret)).into()
+}
+
+pub fn sign_execution_receipt(
+    receipt_secret: &AESKey,
+    contract_address: &[u8],
+    input_hash: &[u8; HASH_SIZE],
+    output_hash: &[u8; HASH_SIZE],
+    block_height: u64,
+) -> [u8; EXECUTION_RECEIPT_SIZE] {
+    let sign_bytes =
+        execution_receipt_sign_bytes(contract_address, input_hash, output_hash, block_height);
+
+    execution_receipt_signing_key(receipt_secret)
+        .sign(&sign_bytes)
+        .into()
+}
+
+pub fn verify_execution_receipt(
+    public_key: &[u8; 32],
+    receipt: &[u8],
+    contract_address: &[u8],
+    input_hash: &[u8; HASH_SIZE],
+    output_hash: &[u8; HASH_SIZE],
+    block_height: u64,
+) -> bool {
+    let verification_key = match ed25519_zebra::VerificationKey::try_from(&public_key[..]) {
+        Ok(key) => key,
+        Err(_) => return false,
+    };
+    let signature = match ed25519_zebra::Signature::try_from(receipt) {
+        Ok(sig) => sig,
+        Err(_) => return false,
+    };
+
+    let sign_bytes =
+        execution_receipt_sign_bytes(contract_address, input_hash, output_hash, block_height);
+
+    verification_key.verify(&signature, &sign_bytes).is_ok()
+}
+
+/// Sign a receipt binding the output of this execution to its input, using a key that is
+/// derived separately from the callback secret.
+pub fn generate_execution_receipt(
+    contract_address: &[u8],
+    input_hash: &[u8; HASH_SIZE],
+    output_hash: &[u8; HASH_SIZE],
+    block_height: u64,
+) -> Result<[u8; EXECUTION_RECEIPT_SIZE], EnclaveError> {
+    let receipt_secret = KEY_MANAGER.get_execution_receipt_secret().map_err(|_| {
+        warn!("execution receipt secret is not available");
+        EnclaveError::FailedFunctionCall
+    })?;
+
+    Ok(sign_execution_receipt(
+        &receipt_secret,
+        contract_address,
+        input_hash,
+        output_hash,
+        block_height,
+    ))
+}
+
+/// The public key that verifies the receipts `generate_execution_receipt` signs
+pub fn get_execution_receipt_public_key() -> Result<[u8; 32], EnclaveError> {
+    let receipt_secret = KEY_MANAGER.get_execution_receipt_secret().map_err(|_| {
+        warn!("execution receipt secret is not available");
+        EnclaveError::FailedFunctionCall
+    })?;
+
+    Ok(execution_receipt_public_key(&receipt_secret))
+}
+
 pub struct ValidatedMessage {
     pub validated_msg: Vec<u8>,
     pub reply_params: Option<Vec<ReplyParams>>,
+    /// The gas limit (in sdk gas) the calling contract sent this message with as a submessage
+    pub gas_limit: Option<u64>,
+    /// How many contracts up the call stack wait for a reply, see `REPLY_DEPTH_MAGIC_BYTES`
+    pub reply_depth: u32,
 }
 
 #[derive(Debug)]
 pub struct ReplyParams {
     pub recipient_contract_hash: Vec<u8>,
     pub sub_msg_id: u64,
+    /// When the recipient is replied to, from the header it sent the submessage with
+    pub reply_on: ReplyOn,
+}
+
+impl ReplyParams {
+    /// The magic bytes to start this reply header with when it's passed on
+    pub fn header_magic_bytes(&self) -> &'static [u8] {
+        // reply params are only parsed from headers, which `Never` doesn't have
+        self.reply_on
+            .reply_header_magic_bytes()
+            .unwrap_or(REPLY_ENCRYPTION_MAGIC_BYTES)
+    }
 }
 
 /// Validate that the message sent to the enclave (after decryption) was actually addressed to this contract.
@@ -425,6 +550,8 @@ pub fn validate_ibc_msg(
                     EnclaveError::FailedToSerialize
                 })?,
                 reply_params: validated_msg.reply_params,
+                gas_limit: validated_msg.gas_limit,
+                reply_depth: validated_msg.reply_depth,
             })
         }
         _ => {
@@ -448,15 +575,14 @@ pub fn validate_basic_msg(
     let mut received_contract_hash: [u8; HEX_ENCODED_HASH_SIZE] = [0u8; HEX_ENCODED_HASH_SIZE];
     let mut validated_msg: Vec<u8>;
     let mut reply_params: Option<Vec<ReplyParams>> = None;
+    let mut gas_limit: Option<u64> = None;
+    let reply_depth: u32;
 
     match data_for_validation {
         Some(c) => {
             received_contract_hash.copy_from_slice(&c.as_slice()[0..HEX_ENCODED_HASH_SIZE]);
             let mut partial_msg = c[HEX_ENCODED_HASH_SIZE..].to_vec();
-            while partial_msg.len() >= REPLY_ENCRYPTION_MAGIC_BYTES.len()
-                && partial_msg[0..(REPLY_ENCRYPTION_MAGIC_BYTES.len())]
-                    == *REPLY_ENCRYPTION_MAGIC_BYTES
-            {
+            while let Some(reply_on) = ReplyOn::from_reply_header(&partial_msg) {
                 if reply_params.is_none() {
                     reply_params = Some(vec![]);
                 }
@@ -477,16 +603,21 @@ pub fn validate_basic_msg(
                 reply_params.as_mut().unwrap().push(ReplyParams {
                     recipient_contract_hash: reply_recipient_contract_hash.to_vec(),
                     sub_msg_id,
+                    reply_on,
                 });
 
                 partial_msg = partial_msg[HEX_ENCODED_HASH_SIZE..].to_vec();
             }
+            // the depth the reply handler runs at, which is its caller's depth again
+            reply_depth = take_reply_depth_header(&mut partial_msg)?;
 
             validated_msg = msg.to_vec();
         }
         None => {
             received_contract_hash.copy_from_slice(&msg[0..HEX_ENCODED_HASH_SIZE]);
             validated_msg = msg[HEX_ENCODED_HASH_SIZE..].to_vec();
+            gas_limit = take_gas_limit_header(&mut validated_msg)?;
+            reply_depth = take_reply_depth_header(&mut validated_msg)?;
         }
     }
 
@@ -501,15 +632,13 @@ pub fn validate_basic_msg(
             warn!("Message contains mismatched contract hash, but it's allowed");
         } else {
             warn!("Message contains mismatched contract hash, and it's not allowed");
-            return Err(EnclaveError::ValidationFailure);
+            return Err(EnclaveError::CodeHashMismatch);
         }
 
-        return Err(EnclaveError::ValidationFailure);
+        return Err(EnclaveError::CodeHashMismatch);
     }
 
-    while validated_msg.len() >= REPLY_ENCRYPTION_MAGIC_BYTES.len()
-        && validated_msg[0..(REPLY_ENCRYPTION_MAGIC_BYTES.len())] == *REPLY_ENCRYPTION_MAGIC_BYTES
-    {
+    while let Some(reply_on) = ReplyOn::from_reply_header(&validated_msg) {
         if reply_params.is_none() {
             reply_params = Some(vec![]);
         }
@@ -529,6 +658,7 @@ pub fn validate_basic_msg(
         reply_params.as_mut().unwrap().push(ReplyParams {
             recipient_contract_hash: reply_recipient_contract_hash.to_vec(),
             sub_msg_id,
+            reply_on,
         });
 
         validated_msg = validated_msg[HEX_ENCODED_HASH_SIZE..].to_vec();
@@ -537,9 +667,62 @@ pub fn validate_basic_msg(
     Ok(ValidatedMessage {
         validated_msg,
         reply_params,
+        gas_limit,
+        reply_depth,
     })
 }
 
+/// Removes the gas limit header that a submessage starts with (after its code hash) if it has one,
+/// and returns the gas limit
+fn take_gas_limit_header(msg: &mut Vec<u8>) -> Result<Option<u64>, EnclaveError> {
+    if !msg.starts_with(SUBMSG_GAS_LIMIT_MAGIC_BYTES) {
+        return Ok(None);
+    }
+
+    let header_size = SUBMSG_GAS_LIMIT_MAGIC_BYTES.len() + SIZE_OF_U64;
+    if msg.len() < header_size {
+        warn!("Malformed message - the gas limit header is truncated");
+        return Err(EnclaveError::ValidationFailure);
+    }
+
+    let mut gas_limit_serialized: [u8; SIZE_OF_U64] = [0u8; SIZE_OF_U64];
+    gas_limit_serialized.copy_from_slice(&msg[SUBMSG_GAS_LIMIT_MAGIC_BYTES.len()..header_size]);
+    msg.drain(..header_size);
+
+    Ok(Some(u64::from_be_bytes(gas_limit_serialized)))
+}
+
+/// Removes the reply depth header that a submessage (after its gas limit header) or the data of a
+/// reply (after its reply headers) starts with if it has one, and returns the depth, which is 0
+/// without the header
+pub fn take_reply_depth_header(msg: &mut Vec<u8>) -> Result<u32, EnclaveError> {
+    if !msg.starts_with(REPLY_DEPTH_MAGIC_BYTES) {
+        return Ok(0);
+    }
+
+    let header_size = REPLY_DEPTH_MAGIC_BYTES.len() + SIZE_OF_U32;
+    if msg.len() < header_size {
+        warn!("Malformed message - the reply depth header is truncated");
+        return Err(EnclaveError::ValidationFailure);
+    }
+
+    let mut reply_depth_serialized: [u8; SIZE_OF_U32] = [0u8; SIZE_OF_U32];
+    reply_depth_serialized.copy_from_slice(&msg[REPLY_DEPTH_MAGIC_BYTES.len()..header_size]);
+    msg.drain(..header_size);
+
+    Ok(u32::from_be_bytes(reply_depth_serialized))
+}
+
+/// Where the message a contract was called with is among its transaction's messages
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+pub struct TxMsgPosition {
+    pub msg_index: u32,
+    pub msg_count: u32,
+}
+
+/// Verifies that the contract is called by the signer of a message in the transaction, or by the
+/// contract that signed the callback. Returns the position of the verified message in its
+/// transaction, which is only known when the input is verified against the transaction.
 #[allow(clippy::too_many_arguments)]
 pub fn verify_params(
     sig_info: &SigInfo,
@@ -552,21 +735,29 @@ pub fn verify_params(
     verify_params_type: VerifyParamsType,
     current_admin: Option<&CanonicalAddr>,
     new_admin: Option<&CanonicalAddr>,
-) -> Result<(), EnclaveError> {
+) -> Result<Option<TxMsgPosition>, EnclaveError> {
     if should_verify_sig_info {
         debug!("Verifying message signatures for: {:?}", sig_info);
 
         if let Some(callback_sig) = &sig_info.callback_sig {
             // We return here if there's a callback signature.
             // The sender is another contract in the same transaction, so there aren't any signed_bytes to verify or tx_bytes to check in the signed block.
-            return verify_callback_sig(callback_sig.as_slice(), sender, secret_msg, sent_funds);
+            verify_msg_callback_sig(
+                callback_sig.as_slice(),
+                sender,
+                secret_msg,
+                sent_funds,
+                verify_params_type,
+            )?;
+            return Ok(None);
         }
 
         verify_signature(sig_info, sender)?;
     }
 
+    let mut tx_msg_position = None;
     if should_verify_input {
-        verify_input(
+        tx_msg_position = Some(verify_input(
             sig_info,
             sent_funds,
             sender,
@@ -575,12 +766,12 @@ pub fn verify_params(
             verify_params_type,
             current_admin,
             new_admin,
-        )?;
+        )?);
     }
 
     info!("Parameters verified successfully");
 
-    Ok(())
+    Ok(tx_msg_position)
 }
 
 fn verify_signature(sig_info: &SigInfo, sender: &CanonicalAddr) -> Result<(), EnclaveError> {
@@ -621,12 +812,12 @@ fn verify_input(
     verify_params_types: VerifyParamsType,
     current_admin: Option<&CanonicalAddr>,
     new_admin: Option<&CanonicalAddr>,
-) -> Result<(), EnclaveError> {
+) -> Result<TxMsgPosition, EnclaveError> {
     let sdk_messages = get_sdk_messages_from_sign_bytes(sig_info)?;
 
     verify_tx_bytes(sig_info, &sdk_messages)?;
 
-    let is_verified = verify_input_params(
+    let msg_index = verify_input_params(
         #[cfg(feature = "light-client-validation")]
         sig_info,
         &sdk_messages,
@@ -639,12 +830,16 @@ fn verify_input(
         new_admin,
     )?;
 
-    if !is_verified {
-        warn!("Parameter verification failed");
-        return Err(EnclaveError::FailedTxVerification);
+    match msg_index {
+        Some(msg_index) => Ok(TxMsgPosition {
+            msg_index: msg_index as u32,
+            msg_count: sdk_messages.len() as u32,
+        }),
+        None => {
+            warn!("Parameter verification failed");
+            Err(EnclaveError::FailedTxVerification)
+        }
     }
-
-    Ok(())
 }
 
 fn get_signer(sign_info: &SigInfo, sender: &CanonicalAddr) -> Result<CosmosPubKey, EnclaveError> {
@@ -818,13 +1013,20 @@ fn verify_tx_bytes(
 /// Verify that the callback sig is appropriate.
 ///
 ///This is used when contracts send callbacks to each other.
-fn verify_callback_sig(
+fn verify_msg_callback_sig(
     callback_signature: &[u8],
     sender: &CanonicalAddr,
     secret_msg: &SecretMessage,
     sent_funds: &[Coin],
+    verify_params_type: VerifyParamsType,
 ) -> Result<(), EnclaveError> {
-    if verify_callback_sig_impl(callback_signature, sender, secret_msg, sent_funds) {
+    if verify_msg_callback_sig_impl(
+        callback_signature,
+        sender,
+        secret_msg,
+        sent_funds,
+        verify_params_type,
+    )? {
         info!("Message verified! msg.sender is the calling contract");
         return Ok(());
     }
@@ -833,29 +1035,42 @@ fn verify_callback_sig(
     Err(EnclaveError::FailedTxVerification)
 }
 
-fn verify_callback_sig_impl(
+fn verify_msg_callback_sig_impl(
     callback_signature: &[u8],
     sender: &CanonicalAddr,
     secret_msg: &SecretMessage,
     sent_funds: &[Coin],
-) -> bool {
+    verify_params_type: VerifyParamsType,
+) -> Result<bool, EnclaveError> {
     if callback_signature.is_empty() {
-        return false;
+        return Ok(false);
     }
 
-    let callback_sig = create_callback_signature(sender, &secret_msg.msg, sent_funds);
+    // a signature is only accepted for the kind of message it was created for
+    let context = match CallbackContext::from_verify_params_type(verify_params_type) {
+        Some(context) => context,
+        None => {
+            warn!(
+                "got a callback signature for {:?}, which contracts can't send",
+                verify_params_type
+            );
+            return Ok(false);
+        }
+    };
 
-    if callback_signature != callback_sig {
+    let callback_sig = create_callback_signature(sender, context, &secret_msg.msg, sent_funds)?;
+
+    if !verify_callback_sig(&callback_sig, callback_signature) {
         trace!(
             "Contract signature does not match with the one sent: {:?}. Expected message to be signed: {:?}",
             callback_signature,
             String::from_utf8_lossy(secret_msg.msg.as_slice())
         );
 
-        return false;
+        return Ok(false);
     }
 
-    true
+    Ok(true)
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -869,7 +1084,7 @@ fn verify_input_params(
     verify_params_types: VerifyParamsType,
     current_admin: Option<&CanonicalAddr>,
     new_admin: Option<&CanonicalAddr>,
-) -> Result<bool, EnclaveError> {
+) -> Result<Option<usize>, EnclaveError> {
     info!("Verifying sdk message against wasm input...");
     // If msg is not found (is None) then it means message verification failed,
     // since it didn't find a matching signed message
@@ -893,9 +1108,13 @@ fn verify_input_params(
                 sender,
                 sdk_messages
             );
-            return Ok(false);
+            return Ok(None);
         }
     };
+    // A message that's in the transaction twice, byte for byte, gets the index of the first one
+    let msg_index = sdk_messages
+        .iter()
+        .position(|msg| std::ptr::eq(msg, sdk_msg));
 
     #[cfg(feature = "light-client-validation")]
     if !check_tx_in_current_block(sig_info.tx_bytes.as_slice()) {
@@ -904,20 +1123,107 @@ fn verify_input_params(
 
     info!("Verifying message sender...");
     if let Some(value) = verify_sender(sdk_msg, sender) {
-        return Ok(value);
+        return Ok(msg_index.filter(|_| value));
     }
 
     info!("Verifying contract address...");
     if !verify_contract_address(sdk_msg, contract_address) {
         warn!("Contract address verification failed!");
-        return Ok(false);
+        return Ok(None);
     }
 
     info!("Verifying sent funds...");
     if !verify_sent_funds(sdk_msg, sent_funds) {
         warn!("Funds verification failed!");
-        return Ok(false);
+        return Ok(None);
     }
 
-    Ok(true)
+    Ok(msg_index)
+}
+
+#[cfg(feature = "test")]
+pub mod tests {
+    use cw_types_v010::encoding::Binary;
+
+    use super::*;
+
+    fn msg_with_code_hash(code_hash: &[u8]) -> Vec<u8> {
+        let mut msg = hex::encode(code_hash).into_bytes();
+        msg.extend_from_slice(br#"{"nop":{}}"#);
+        msg
+    }
+
+    pub fn test_validate_msg_matching_code_hash() {
+        let contract_hash = sha_256(b"code");
+
+        let validated = validate_basic_msg(
+            &CanonicalAddr(Binary(vec![1; 20])),
+            &msg_with_code_hash(&contract_hash),
+            &contract_hash,
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(validated.validated_msg, br#"{"nop":{}}"#.to_vec());
+    }
+
+    pub fn test_validate_msg_code_hash_mismatch() {
+        let contract_hash = sha_256(b"code");
+        let contract_address = CanonicalAddr(Binary(vec![1; 20]));
+
+        let result = validate_basic_msg(
+            &contract_address,
+            &msg_with_code_hash(&sha_256(b"other code")),
+            &contract_hash,
+            None,
+        );
+        assert!(matches!(result, Err(EnclaveError::CodeHashMismatch)));
+
+        // a hash that isn't even hex is malformed, not a mismatch
+        let mut malformed = msg_with_code_hash(&contract_hash);
+        malformed[0] = b'z';
+        let result = validate_basic_msg(&contract_address, &malformed, &contract_hash, None);
+        assert!(matches!(result, Err(EnclaveError::ValidationFailure)));
+    }
+
+    pub fn test_validate_msg_gas_limit_header() {
+        let contract_hash = sha_256(b"code");
+        let contract_address = CanonicalAddr(Binary(vec![1; 20]));
+        let hex_hash = hex::encode(contract_hash).into_bytes();
+        let with_headers = |headers: &[&[u8]]| {
+            let mut msg = hex_hash.clone();
+            for header in headers {
+                msg.extend_from_slice(header);
+            }
+            msg.extend_from_slice(br#"{"nop":{}}"#);
+            msg
+        };
+        let gas_limit_header = [SUBMSG_GAS_LIMIT_MAGIC_BYTES, &50_000u64.to_be_bytes()].concat();
+        let reply_header = [REPLY_ENCRYPTION_MAGIC_BYTES, &7u64.to_be_bytes(), &hex_hash].concat();
+
+        // the gas limit comes before the reply headers
+        let validated = validate_basic_msg(
+            &contract_address,
+            &with_headers(&[&gas_limit_header, &reply_header]),
+            &contract_hash,
+            None,
+        )
+        .unwrap();
+        assert_eq!(validated.gas_limit, Some(50_000));
+        assert_eq!(validated.reply_params.unwrap()[0].sub_msg_id, 7);
+        assert_eq!(validated.validated_msg, br#"{"nop":{}}"#.to_vec());
+
+        let validated =
+            validate_basic_msg(&contract_address, &with_headers(&[]), &contract_hash, None)
+                .unwrap();
+        assert_eq!(validated.gas_limit, None);
+
+        let result = validate_basic_msg(
+            &contract_address,
+            &[&hex_hash[..], SUBMSG_GAS_LIMIT_MAGIC_BYTES, &[0u8; 3]].concat(),
+            &contract_hash,
+            None,
+        );
+        assert!(matches!(result, Err(EnclaveError::ValidationFailure)));
+    }
 }
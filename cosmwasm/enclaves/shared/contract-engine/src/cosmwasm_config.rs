@@ -7,6 +7,7 @@ pub mod api_marker {
 
 pub mod features {
     pub const RANDOM: &str = "requires_random";
+    pub const PADDED_OUTPUT: &str = "requires_padded_output";
 }
 
 /// Right now ContractOperation is used to detect queris and prevent state changes
@@ -16,6 +16,7 @@ use enclave_utils::kv_cache::KvCache;
 
 use super::contract_validation::ContractKey;
 use super::errors::WasmEngineError;
+use super::storage_range::{in_range, Order, KV};
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize)]
@@ -158,6 +159,99 @@ pub fn read_from_encrypted_state(
     encryption_salt: &[u8],
 ) -> Result<(Option<Vec<u8>>, u64), WasmEngineError> {
     // Try reading with the new encryption format
+    let (encrypted_key, encrypted_key_bytes) = encrypted_key_for(plaintext_key, contract_key)?;
+
+    let (maybe_encrypted_value_bytes, gas_used_first_read) =
+        read_db(context, &encrypted_key_bytes)?;
+
+    if let Some(encrypted_value_bytes) = maybe_encrypted_value_bytes {
+        let plaintext_value = decrypt_read_value(
+            &encrypted_key,
+            &encrypted_key_bytes,
+            &encrypted_value_bytes,
+            contract_key,
+        )?;
+        return Ok((Some(plaintext_value), gas_used_first_read));
+    }
+
+    // Key doesn't exist, try reading with the old encryption format
+    let (maybe_plaintext_value, gas_used_old_format) = read_from_old_encrypted_state(
+        plaintext_key,
+        context,
+        contract_key,
+        has_write_permissions,
+        kv_cache,
+        encryption_salt,
+    )?;
+
+    Ok((
+        maybe_plaintext_value,
+        gas_used_first_read + gas_used_old_format,
+    ))
+}
+
+/// Like `read_from_encrypted_state`, for several keys at once. The keys are read in the new
+/// encryption format in a single ocall, and only the ones missing there are read one by one in
+/// the old format. Returns the values in the order of `plaintext_keys`, along with the summed gas
+/// of the reads.
+pub fn read_multiple_from_encrypted_state(
+    plaintext_keys: &[Vec<u8>],
+    context: &Ctx,
+    contract_key: &ContractKey,
+    has_write_permissions: bool,
+    kv_cache: &mut KvCache,
+    encryption_salt: &[u8],
+) -> Result<(Vec<Option<Vec<u8>>>, u64), WasmEngineError> {
+    let encrypted_keys = plaintext_keys
+        .iter()
+        .map(|plaintext_key| encrypted_key_for(plaintext_key, contract_key))
+        .collect::<Result<Vec<_>, _>>()?;
+    let encrypted_key_bytes: Vec<Vec<u8>> = encrypted_keys
+        .iter()
+        .map(|(_, encrypted_key_bytes)| encrypted_key_bytes.clone())
+        .collect();
+
+    let (maybe_encrypted_values, mut gas_used) = read_multiple_db(context, &encrypted_key_bytes)?;
+
+    let mut values = Vec::with_capacity(plaintext_keys.len());
+    for ((plaintext_key, (encrypted_key, encrypted_key_bytes)), maybe_encrypted_value_bytes) in
+        plaintext_keys
+            .iter()
+            .zip(&encrypted_keys)
+            .zip(maybe_encrypted_values)
+    {
+        let value = match maybe_encrypted_value_bytes {
+            Some(encrypted_value_bytes) => Some(decrypt_read_value(
+                encrypted_key,
+                encrypted_key_bytes,
+                &encrypted_value_bytes,
+                contract_key,
+            )?),
+            None => {
+                let (maybe_plaintext_value, gas_used_old_format) = read_from_old_encrypted_state(
+                    plaintext_key,
+                    context,
+                    contract_key,
+                    has_write_permissions,
+                    kv_cache,
+                    encryption_salt,
+                )?;
+                gas_used += gas_used_old_format;
+                maybe_plaintext_value
+            }
+        };
+        values.push(value);
+    }
+
+    Ok((values, gas_used))
+}
+
+/// The key under which the value of `plaintext_key` is stored in the new encryption format, and
+/// its serialized bytes
+fn encrypted_key_for(
+    plaintext_key: &[u8],
+    contract_key: &ContractKey,
+) -> Result<(EncryptedKey, Vec<u8>), WasmEngineError> {
     let encrypted_key = EncryptedKey {
         magic_bytes: ENCRYPTED_KEY_MAGIC_BYTES.to_vec(),
         consensus_seed_version: CONSENSUS_SEED_VERSION,
@@ -166,43 +260,44 @@ pub fn read_from_encrypted_state(
     };
     let encrypted_key_bytes = bincode2::serialize(&encrypted_key).unwrap();
 
-    let mut maybe_plaintext_value: Option<Vec<u8>>;
-    let gas_used_first_read: u64;
-    (maybe_plaintext_value, gas_used_first_read) = match read_db(context, &encrypted_key_bytes) {
-        Ok((maybe_encrypted_value_bytes, gas_used)) => match maybe_encrypted_value_bytes {
-            Some(encrypted_value_bytes) => {
-                let encrypted_value: EncryptedValue = bincode2::deserialize(&encrypted_value_bytes).map_err(|err| {
-                    warn!(
-                        "read_db() got an error while trying to read_from_encrypted_state the value {:?} for key {:?}, stopping wasm: {:?}",
-                        encrypted_value_bytes,
-                        encrypted_key_bytes,
-                        err.to_string()
-                    );
-                    WasmEngineError::DecryptionError
-                })?;
-
-                match decrypt_value_new(
-                    &encrypted_key.data,
-                    &encrypted_value.data,
-                    contract_key,
-                    &encrypted_value.salt,
-                ) {
-                    Ok(plaintext_value) => Ok((Some(plaintext_value), gas_used)),
-                    // This error case is why we have all the matches here.
-                    // If we successfully collected a value, but failed to decrypt it, then we propagate that error.
-                    Err(err) => Err(err),
-                }
-            }
-            None => Ok((None, gas_used)),
-        },
-        Err(err) => Err(err),
-    }?;
+    Ok((encrypted_key, encrypted_key_bytes))
+}
 
-    if let Some(plaintext_value) = maybe_plaintext_value {
-        return Ok((Some(plaintext_value), gas_used_first_read));
-    }
+/// Decrypts a value that was read in the new encryption format
+fn decrypt_read_value(
+    encrypted_key: &EncryptedKey,
+    encrypted_key_bytes: &[u8],
+    encrypted_value_bytes: &[u8],
+    contract_key: &ContractKey,
+) -> Result<Vec<u8>, WasmEngineError> {
+    let encrypted_value: EncryptedValue = bincode2::deserialize(encrypted_value_bytes).map_err(|err| {
+        warn!(
+            "read_db() got an error while trying to read_from_encrypted_state the value {:?} for key {:?}, stopping wasm: {:?}",
+            encrypted_value_bytes,
+            encrypted_key_bytes,
+            err.to_string()
+        );
+        WasmEngineError::DecryptionError
+    })?;
 
-    // Key doesn't exist, try reading with the old encryption format
+    decrypt_value_new(
+        &encrypted_key.data,
+        &encrypted_value.data,
+        contract_key,
+        &encrypted_value.salt,
+    )
+}
+
+/// Reads `plaintext_key` in the old encryption format, once it is known to be missing in the new
+/// one. A key found there is rewritten in the new format if `has_write_permissions`.
+fn read_from_old_encrypted_state(
+    plaintext_key: &[u8],
+    context: &Ctx,
+    contract_key: &ContractKey,
+    has_write_permissions: bool,
+    kv_cache: &mut KvCache,
+    encryption_salt: &[u8],
+) -> Result<(Option<Vec<u8>>, u64), WasmEngineError> {
     let scrambled_field_name = field_name_digest(plaintext_key, contract_key);
 
     trace!(
@@ -210,8 +305,7 @@ pub fn read_from_encrypted_state(
         scrambled_field_name
     );
 
-    let gas_used_second_read: u64;
-    (maybe_plaintext_value, gas_used_second_read) = match read_db(context, &scrambled_field_name) {
+    let (maybe_plaintext_value, gas_used_read) = match read_db(context, &scrambled_field_name) {
         Ok((encrypted_value, gas_used)) => match encrypted_value {
             Some(plaintext_value) => {
                 match decrypt_value_old(&scrambled_field_name, &plaintext_value, contract_key) {
@@ -243,10 +337,7 @@ pub fn read_from_encrypted_state(
         }
     }
 
-    Ok((
-        maybe_plaintext_value,
-        gas_used_first_read + gas_used_second_read + gas_used_write,
-    ))
+    Ok((maybe_plaintext_value, gas_used_read + gas_used_write))
 }
 
 pub fn remove_from_encrypted_state(
@@ -290,6 +381,100 @@ pub fn remove_from_encrypted_state(
     Ok(gas_used_first_remove + gas_used_second_remove)
 }
 
+/// The entries of the contract's state with plaintext keys in `[start, end)`, in no particular
+/// order, along with the gas of scanning it. The encrypted keys don't sort like their plaintexts,
+/// so the whole state is scanned and decrypted.
+///
+/// Only digests of the keys of entries in the old format are stored, so their plaintext keys
+/// can't be recovered. Rather than leaving them out of the range, the scan fails with
+/// `OldFormatKeyInRange` when it finds one. Reading a key in a transaction rewrites it in the new
+/// format.
+///
+/// The scan isn't bounded, since the bounds only apply to the plaintext keys. Instead
+/// `charge_entry` is called for each entry before it's decrypted, so a scan that runs out of gas
+/// stops there instead of decrypting the rest of the state.
+pub fn range_from_encrypted_state<F>(
+    start: Option<&[u8]>,
+    end: Option<&[u8]>,
+    context: &Ctx,
+    contract_key: &ContractKey,
+    mut charge_entry: F,
+) -> Result<(Vec<KV>, u64), WasmEngineError>
+where
+    F: FnMut() -> Result<(), WasmEngineError>,
+{
+    let (iterator_id, mut gas_used) = scan_db(context, None, None, Order::Ascending)?;
+
+    let mut entries = vec![];
+    loop {
+        let (entry, gas_used_next) = next_db(context, iterator_id)?;
+        gas_used += gas_used_next;
+
+        let (encrypted_key_bytes, encrypted_value_bytes) = match entry {
+            Some(entry) => entry,
+            None => break,
+        };
+
+        charge_entry()?;
+        let encrypted_key = match bincode2::deserialize::<EncryptedKey>(&encrypted_key_bytes) {
+            Ok(encrypted_key) if encrypted_key.magic_bytes == ENCRYPTED_KEY_MAGIC_BYTES => {
+                encrypted_key
+            }
+            _ => {
+                warn!("range_from_encrypted_state() found a key in the old format, stopping wasm");
+                return Err(WasmEngineError::OldFormatKeyInRange);
+            }
+        };
+
+        let plaintext_key = decrypt_key_new(&encrypted_key.data, contract_key)?;
+        if !in_range(&plaintext_key, start, end) {
+            continue;
+        }
+
+        let encrypted_value: EncryptedValue = bincode2::deserialize(&encrypted_value_bytes)
+            .map_err(|err| {
+                warn!(
+                    "range_from_encrypted_state() got an error while deserializing the value for key {:?}, stopping wasm: {:?}",
+                    encrypted_key_bytes,
+                    err.to_string()
+                );
+                WasmEngineError::DecryptionError
+            })?;
+        let plaintext_value = decrypt_value_new(
+            &encrypted_key.data,
+            &encrypted_value.data,
+            contract_key,
+            &encrypted_value.salt,
+        )?;
+
+        entries.push((plaintext_key, plaintext_value));
+    }
+
+    Ok((entries, gas_used))
+}
+
+/// Whether a raw key/value pair from the chain's state was written by the contract with
+/// `contract_key`, in either the new or the old encryption format
+pub fn is_contract_entry(key: &[u8], value: &[u8], contract_key: &ContractKey) -> bool {
+    if let (Ok(encrypted_key), Ok(encrypted_value)) = (
+        bincode2::deserialize::<EncryptedKey>(key),
+        bincode2::deserialize::<EncryptedValue>(value),
+    ) {
+        if encrypted_key.magic_bytes == ENCRYPTED_KEY_MAGIC_BYTES {
+            return decrypt_value_new(
+                &encrypted_key.data,
+                &encrypted_value.data,
+                contract_key,
+                &encrypted_value.salt,
+            )
+            .is_ok();
+        }
+    }
+
+    // the old format stores the value under a scrambled 32 byte field name, prefixed by its ad
+    key.len() == 32 && value.len() > 32 && decrypt_value_old(key, value, contract_key).is_ok()
+}
+
 fn field_name_digest(field_name: &[u8], contract_key: &ContractKey) -> [u8; 32] {
     let mut data = field_name.to_vec();
     data.extend_from_slice(contract_key);
@@ -340,6 +525,203 @@ fn read_db(context: &Ctx, key: &[u8]) -> Result<(Option<Vec<u8>>, u64), WasmEngi
     Ok((value, gas_used))
 }
 
+/// Safe wrapper around batched reads from the contract storage. Reads all `keys` in a single
+/// ocall and returns their values in the same order, `None` for keys that don't exist, along
+/// with the summed gas of the reads.
+fn read_multiple_db(
+    context: &Ctx,
+    keys: &[Vec<u8>],
+) -> Result<(Vec<Option<Vec<u8>>>, u64), WasmEngineError> {
+    if keys.is_empty() {
+        return Ok((vec![], 0));
+    }
+
+    let encoded_keys = encode_read_keys(keys);
+
+    let mut ocall_return = OcallReturn::Success;
+    let mut enclave_buffer = std::mem::MaybeUninit::<EnclaveBuffer>::uninit();
+    let mut vm_err = UntrustedVmError::default();
+    let mut gas_used = 0_u64;
+
+    let encoded_values = unsafe {
+        let status = ocalls::ocall_multiple_read_db(
+            (&mut ocall_return) as *mut _,
+            context.unsafe_clone(),
+            (&mut vm_err) as *mut _,
+            (&mut gas_used) as *mut _,
+            enclave_buffer.as_mut_ptr(),
+            encoded_keys.as_ptr(),
+            encoded_keys.len(),
+        );
+        match status {
+            sgx_status_t::SGX_SUCCESS => { /* continue */ }
+            error_status => {
+                warn!(
+                    "read_multiple_db() got an error from ocall_multiple_read_db, stopping wasm: {:?}",
+                    error_status
+                );
+                return Err(WasmEngineError::FailedOcall(vm_err));
+            }
+        }
+
+        match ocall_return {
+            OcallReturn::Success => {
+                let enclave_buffer = enclave_buffer.assume_init();
+                ecalls::recover_buffer(enclave_buffer)?.unwrap_or_default()
+            }
+            OcallReturn::Failure => {
+                return Err(WasmEngineError::FailedOcall(vm_err));
+            }
+            OcallReturn::Panic => return Err(WasmEngineError::Panic),
+        }
+    };
+
+    let values = decode_read_values(&encoded_values, keys.len()).ok_or_else(|| {
+        warn!("read_multiple_db() got malformed values from ocall_multiple_read_db, stopping wasm");
+        WasmEngineError::DeserializationError
+    })?;
+
+    Ok((values, gas_used))
+}
+
+/// Each key prefixed by its 4 byte big-endian length, as `ocall_multiple_read_db` expects them
+fn encode_read_keys(keys: &[Vec<u8>]) -> Vec<u8> {
+    let mut bytes = vec![];
+
+    for key in keys {
+        bytes.extend_from_slice(&(key.len() as u32).to_be_bytes());
+        bytes.extend_from_slice(key);
+    }
+
+    bytes
+}
+
+/// Decodes the values `ocall_multiple_read_db` returns, one per key. A missing key is a single 0
+/// byte, an existing one a 1 byte followed by the value's 4 byte big-endian length and the value.
+/// Returns `None` unless there are exactly `count` values.
+fn decode_read_values(mut bytes: &[u8], count: usize) -> Option<Vec<Option<Vec<u8>>>> {
+    let mut values = Vec::with_capacity(count);
+
+    while !bytes.is_empty() {
+        match bytes[0] {
+            0 => {
+                values.push(None);
+                bytes = &bytes[1..];
+            }
+            1 => {
+                let len = bytes.get(1..5)?;
+                let len = 5 + u32::from_be_bytes([len[0], len[1], len[2], len[3]]) as usize;
+                values.push(Some(bytes.get(5..len)?.to_vec()));
+                bytes = &bytes[len..];
+            }
+            _ => return None,
+        }
+    }
+
+    if values.len() != count {
+        return None;
+    }
+
+    Some(values)
+}
+
+/// Safe wrapper around opening a range over the contract storage, in the order of the stored
+/// keys. A `None` bound is open. Returns the id `next_db` reads the range with.
+fn scan_db(
+    context: &Ctx,
+    start: Option<&[u8]>,
+    end: Option<&[u8]>,
+    order: Order,
+) -> Result<(u32, u64), WasmEngineError> {
+    let mut ocall_return = OcallReturn::Success;
+    let mut vm_err = UntrustedVmError::default();
+    let mut gas_used = 0_u64;
+    let mut iterator_id = 0_u32;
+
+    // open bounds are passed as null pointers, so that they differ from empty ones
+    let (start_ptr, start_len) = start.map_or((std::ptr::null(), 0), |s| (s.as_ptr(), s.len()));
+    let (end_ptr, end_len) = end.map_or((std::ptr::null(), 0), |e| (e.as_ptr(), e.len()));
+
+    match unsafe {
+        ocalls::ocall_db_scan(
+            (&mut ocall_return) as *mut _,
+            context.unsafe_clone(),
+            (&mut vm_err) as *mut _,
+            (&mut gas_used) as *mut _,
+            (&mut iterator_id) as *mut _,
+            start_ptr,
+            start_len,
+            end_ptr,
+            end_len,
+            order as i32,
+        )
+    } {
+        sgx_status_t::SGX_SUCCESS => { /* continue */ }
+        error_status => {
+            warn!(
+                "scan_db() got an error from ocall_db_scan, stopping wasm: {:?}",
+                error_status
+            );
+            return Err(WasmEngineError::FailedOcall(vm_err));
+        }
+    }
+
+    match ocall_return {
+        OcallReturn::Success => Ok((iterator_id, gas_used)),
+        OcallReturn::Failure => Err(WasmEngineError::FailedOcall(vm_err)),
+        OcallReturn::Panic => Err(WasmEngineError::Panic),
+    }
+}
+
+/// Safe wrapper around reading the next entry of a range `scan_db` opened. Returns `None` past
+/// the end of the range.
+fn next_db(context: &Ctx, iterator_id: u32) -> Result<(Option<KV>, u64), WasmEngineError> {
+    let mut ocall_return = OcallReturn::Success;
+    let mut key_buffer = std::mem::MaybeUninit::<EnclaveBuffer>::uninit();
+    let mut value_buffer = std::mem::MaybeUninit::<EnclaveBuffer>::uninit();
+    let mut vm_err = UntrustedVmError::default();
+    let mut gas_used = 0_u64;
+
+    let entry = unsafe {
+        let status = ocalls::ocall_db_next(
+            (&mut ocall_return) as *mut _,
+            context.unsafe_clone(),
+            (&mut vm_err) as *mut _,
+            (&mut gas_used) as *mut _,
+            iterator_id,
+            key_buffer.as_mut_ptr(),
+            value_buffer.as_mut_ptr(),
+        );
+        match status {
+            sgx_status_t::SGX_SUCCESS => { /* continue */ }
+            error_status => {
+                warn!(
+                    "next_db() got an error from ocall_db_next, stopping wasm: {:?}",
+                    error_status
+                );
+                return Err(WasmEngineError::FailedOcall(vm_err));
+            }
+        }
+
+        match ocall_return {
+            OcallReturn::Success => {
+                let key = ecalls::recover_buffer(key_buffer.assume_init())?;
+                let value = ecalls::recover_buffer(value_buffer.assume_init())?;
+                match key {
+                    Some(key) => Some((key, value.unwrap_or_default())),
+                    None => None,
+                }
+            }
+            OcallReturn::Failure => {
+                return Err(WasmEngineError::FailedOcall(vm_err));
+            }
+            OcallReturn::Panic => return Err(WasmEngineError::Panic),
+        }
+    };
+
+    Ok((entry, gas_used))
+}
+
 /// Safe wrapper around reads from the contract storage
 fn remove_db(context: &Ctx, key: &[u8]) -> Result<u64, WasmEngineError> {
     let mut ocall_return = OcallReturn::Success;
@@ -490,3 +872,50 @@ fn encrypt_key_new(
             WasmEngineError::EncryptionError
     })
 }
+
+/// encrypted_state_key is without the header
+fn decrypt_key_new(
+    encrypted_state_key: &[u8],
+    contract_key: &ContractKey,
+) -> Result<Vec<u8>, WasmEngineError> {
+    let decryption_key = get_symmetrical_key_new(contract_key);
+
+    decryption_key.decrypt_siv(encrypted_state_key, Some(&[])).map_err(|err| {
+        warn!(
+            "range_from_encrypted_state() got an error while trying to decrypt_key_new the key {:?}, stopping wasm: {:?}",
+            encrypted_state_key,
+            err
+        );
+        WasmEngineError::DecryptionError
+    })
+}
+
+#[cfg(feature = "test")]
+pub mod tests {
+    use super::*;
+
+    pub fn test_multiple_read_keys_encoding() {
+        let keys = vec![b"a".to_vec(), vec![], b"bc".to_vec()];
+
+        assert_eq!(
+            encode_read_keys(&keys),
+            vec![0, 0, 0, 1, b'a', 0, 0, 0, 0, 0, 0, 0, 2, b'b', b'c']
+        );
+    }
+
+    pub fn test_multiple_read_values_decoding() {
+        // an existing value, a missing key and an empty value
+        let values = [1, 0, 0, 0, 2, b'a', b'b', 0, 1, 0, 0, 0, 0];
+        assert_eq!(
+            decode_read_values(&values, 3),
+            Some(vec![Some(b"ab".to_vec()), None, Some(vec![])])
+        );
+
+        // a value for every key, no more and no less
+        assert_eq!(decode_read_values(&values, 2), None);
+        assert_eq!(decode_read_values(&values, 4), None);
+        // truncated values and unknown markers
+        assert_eq!(decode_read_values(&[1, 0, 0, 0, 2, b'a'], 1), None);
+        assert_eq!(decode_read_values(&[2], 1), None);
+    }
+}
@@ -0,0 +1,86 @@
+//! Deterministic nonces backing the `derive_nonce` host function, for contracts that encrypt data
+//! themselves.
+//!
+//! A nonce is a domain-separated sha256 over the contract address, the block height, the block's
+//! message counter, the hash of the call's input and a per-call counter. The message counter is
+//! the one `env.random` is derived from: it's bumped on every init, execute and migrate in the
+//! block, so no two calls in a block, in the same transaction or not, share it. The per-call
+//! counter starts at 0 and is bumped on each `derive_nonce`, so nonces are unique within a call
+//! too. Every node executing the block derives the same nonces, so replaying a call reproduces
+//! them. Queries don't bump the message counter, so they don't get nonces.
+
+use enclave_crypto::{sha_256, HASH_SIZE};
+
+pub const NONCE_SIZE: usize = HASH_SIZE;
+
+const DERIVE_NONCE_DOMAIN: &[u8] = b"secret_derive_nonce_v1";
+
+/// The parts of a call that its nonces are derived from
+pub struct NonceSource<'a> {
+    pub contract_address: &'a [u8],
+    pub block_height: u64,
+    pub msg_counter: u64,
+    pub input_hash: &'a [u8; HASH_SIZE],
+}
+
+/// Derives the nonce for the `counter`th `derive_nonce` call of a contract call
+pub fn derive_nonce(source: &NonceSource, counter: u32) -> [u8; NONCE_SIZE] {
+    let mut data = Vec::with_capacity(128);
+    data.extend_from_slice(DERIVE_NONCE_DOMAIN);
+    // the address is length prefixed so it can't bleed into the fields after it
+    data.extend_from_slice(&(source.contract_address.len() as u32).to_be_bytes());
+    data.extend_from_slice(source.contract_address);
+    data.extend_from_slice(&source.block_height.to_be_bytes());
+    data.extend_from_slice(&source.msg_counter.to_be_bytes());
+    data.extend_from_slice(source.input_hash);
+    data.extend_from_slice(&counter.to_be_bytes());
+
+    sha_256(&data)
+}
+
+#[cfg(feature = "test")]
+pub mod tests {
+    use super::*;
+
+    fn source(msg_counter: u64, input_hash: &[u8; HASH_SIZE]) -> NonceSource {
+        NonceSource {
+            contract_address: b"contract",
+            block_height: 100,
+            msg_counter,
+            input_hash,
+        }
+    }
+
+    pub fn test_derive_nonce_unique_within_transaction() {
+        let input_hash = sha_256(b"msg");
+
+        // two encryptions in one call
+        let first = derive_nonce(&source(0, &input_hash), 0);
+        let second = derive_nonce(&source(0, &input_hash), 1);
+        assert_ne!(first, second);
+
+        // the same message sent twice in a transaction is two calls with different counters
+        assert_ne!(first, derive_nonce(&source(1, &input_hash), 0));
+    }
+
+    pub fn test_derive_nonce_is_deterministic() {
+        let input_hash = sha_256(b"msg");
+
+        // replaying the call derives the same nonces
+        for counter in 0..3 {
+            assert_eq!(
+                derive_nonce(&source(5, &input_hash), counter),
+                derive_nonce(&source(5, &input_hash), counter)
+            );
+        }
+
+        let nonce = derive_nonce(&source(5, &input_hash), 0);
+        let mut other = source(5, &input_hash);
+        other.contract_address = b"other";
+        assert_ne!(nonce, derive_nonce(&other, 0));
+        other = source(5, &input_hash);
+        other.block_height = 101;
+        assert_ne!(nonce, derive_nonce(&other, 0));
+        assert_ne!(nonce, derive_nonce(&source(5, &sha_256(b"other msg")), 0));
+    }
+}
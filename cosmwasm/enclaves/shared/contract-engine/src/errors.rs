@@ -33,7 +33,23 @@ pub enum WasmEngineError {
     MemoryReadError,
     MemoryWriteError,
     /// The contract attempted to write to storage during a query
-    UnauthorizedWrite,
+    StorageWriteInQuery,
+    /// The contract tried to write or remove a zero-length storage key
+    EmptyKey,
+    /// The contract tried to access a storage key the enclave keeps its own data under
+    ReservedKey,
+    /// The contract called a host function that contracts must not use
+    ForbiddenSyscall,
+    /// The funds sent with the call don't match the funds the contract requires
+    FundsMismatch,
+    /// The contract sent a query that's already running further up the chain of queries
+    QueryCycleDetected,
+    /// The contract opened more ranges with db_scan than a call may have open
+    TooManyRanges,
+    /// The contract ranged over a state that still has keys in the old encryption format
+    OldFormatKeyInRange,
+    /// The contract called a host function that counts its calls more times than the counter fits
+    CounterExhausted,
 
     /// The contract tried calling an unrecognized function
     NonExistentImportFunction,
@@ -57,7 +73,15 @@ impl From<WasmEngineError> for EnclaveError {
             MemoryAllocationError => EnclaveError::MemoryAllocationError,
             MemoryReadError => EnclaveError::MemoryReadError,
             MemoryWriteError => EnclaveError::MemoryWriteError,
-            UnauthorizedWrite => EnclaveError::UnauthorizedWrite,
+            StorageWriteInQuery => EnclaveError::StorageWriteInQuery,
+            EmptyKey => EnclaveError::EmptyKey,
+            ReservedKey => EnclaveError::ReservedKey,
+            ForbiddenSyscall => EnclaveError::ForbiddenSyscall,
+            FundsMismatch => EnclaveError::FundsMismatch,
+            QueryCycleDetected => EnclaveError::QueryCycleDetected,
+            TooManyRanges => EnclaveError::TooManyRanges,
+            OldFormatKeyInRange => EnclaveError::OldFormatKeyInRange,
+            CounterExhausted => EnclaveError::CounterExhausted,
             HostMisbehavior => EnclaveError::HostMisbehavior,
             // Unexpected WasmEngineError variant
             _other => EnclaveError::Unknown,
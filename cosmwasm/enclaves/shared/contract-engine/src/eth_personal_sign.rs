@@ -0,0 +1,125 @@
+//! Verification of Ethereum `personal_sign` (EIP-191 version 0x45) signatures, backing the
+//! `verify_eth_personal_sign` host function, so contracts can authenticate EVM wallets.
+
+use log::*;
+use sha3::{Digest, Keccak256};
+
+use enclave_crypto::WasmApiCryptoError;
+
+pub const ETH_ADDRESS_SIZE: usize = 20;
+/// r (32 bytes) || s (32 bytes) || v (1 byte)
+pub const ETH_SIGNATURE_SIZE: usize = 65;
+
+const PERSONAL_SIGN_PREFIX: &[u8] = b"\x19Ethereum Signed Message:\n";
+
+/// keccak256("\x19Ethereum Signed Message:\n" ++ len(msg) ++ msg), with the length in decimal
+pub fn personal_sign_hash(msg: &[u8]) -> [u8; 32] {
+    let mut hasher = Keccak256::new();
+    hasher.update(PERSONAL_SIGN_PREFIX);
+    hasher.update(msg.len().to_string().as_bytes());
+    hasher.update(msg);
+
+    let mut hash = [0u8; 32];
+    hash.copy_from_slice(&hasher.finalize());
+    hash
+}
+
+/// The last 20 bytes of the keccak256 of the uncompressed public key, without its 0x04 prefix
+pub fn eth_address(public_key: &secp256k1::PublicKey) -> [u8; ETH_ADDRESS_SIZE] {
+    let hash = Keccak256::digest(&public_key.serialize_uncompressed()[1..]);
+
+    let mut address = [0u8; ETH_ADDRESS_SIZE];
+    address.copy_from_slice(&hash[32 - ETH_ADDRESS_SIZE..]);
+    address
+}
+
+/// Returns whether `signature` is a `personal_sign` of `msg` by the account `eth_addr`.
+/// Malformed inputs are errors, while a valid signature by another account is `Ok(false)`.
+pub fn verify_eth_personal_sign(
+    msg: &[u8],
+    signature: &[u8],
+    eth_addr: &[u8],
+) -> Result<bool, WasmApiCryptoError> {
+    if signature.len() != ETH_SIGNATURE_SIZE {
+        return Err(WasmApiCryptoError::InvalidSignatureFormat);
+    }
+    if eth_addr.len() != ETH_ADDRESS_SIZE {
+        return Err(WasmApiCryptoError::InvalidPubkeyFormat);
+    }
+
+    // wallets use either 27/28 (legacy) or 0/1
+    let recovery_param = match signature[64] {
+        27 | 28 => signature[64] - 27,
+        0 | 1 => signature[64],
+        v => {
+            debug!("verify_eth_personal_sign() got invalid v {}", v);
+            return Err(WasmApiCryptoError::InvalidRecoveryParam);
+        }
+    };
+    let recovery_id = secp256k1::ecdsa::RecoveryId::from_i32(recovery_param as i32)
+        .map_err(|_| WasmApiCryptoError::InvalidRecoveryParam)?;
+
+    let signature =
+        secp256k1::ecdsa::RecoverableSignature::from_compact(&signature[..64], recovery_id)
+            .map_err(|err| {
+                debug!("verify_eth_personal_sign() malformed signature: {:?}", err);
+                WasmApiCryptoError::InvalidSignatureFormat
+            })?;
+
+    let message = secp256k1::Message::from_slice(&personal_sign_hash(msg))
+        .map_err(|_| WasmApiCryptoError::GenericErr)?;
+
+    match secp256k1::Secp256k1::verification_only().recover_ecdsa(&message, &signature) {
+        Err(err) => {
+            debug!("verify_eth_personal_sign() failed to recover pubkey: {:?}", err);
+            Ok(false)
+        }
+        Ok(public_key) => Ok(eth_address(&public_key) == eth_addr),
+    }
+}
+
+#[cfg(feature = "test")]
+pub mod tests {
+    use super::*;
+
+    // Well known development account (the first Hardhat/Anvil account)
+    const ADDRESS: &str = "f39fd6e51aad88f6f4ce6ab8827279cfffb92266";
+    const MESSAGE: &[u8] = b"Sign in to Secret Network";
+    const SIGNATURE: &str = "ed25c1c74368cc1ebcb97e4ec250d0630beabb308746681d6068452f1ce059847f47b41b9da0404308cd979ce357d616bc8f273a2ce67173806b0ff5e9c556cc1c";
+
+    pub fn test_verify_eth_personal_sign_known_account() {
+        let address = hex::decode(ADDRESS).unwrap();
+        let mut signature = hex::decode(SIGNATURE).unwrap();
+
+        assert!(verify_eth_personal_sign(MESSAGE, &signature, &address).unwrap());
+        assert!(!verify_eth_personal_sign(b"another message", &signature, &address).unwrap());
+        assert!(
+            !verify_eth_personal_sign(MESSAGE, &signature, &[1u8; ETH_ADDRESS_SIZE]).unwrap()
+        );
+
+        // v as 0/1 is accepted too
+        signature[64] -= 27;
+        assert!(verify_eth_personal_sign(MESSAGE, &signature, &address).unwrap());
+    }
+
+    pub fn test_verify_eth_personal_sign_malformed_inputs() {
+        let address = hex::decode(ADDRESS).unwrap();
+        let signature = hex::decode(SIGNATURE).unwrap();
+
+        assert!(matches!(
+            verify_eth_personal_sign(MESSAGE, &signature[..64], &address),
+            Err(WasmApiCryptoError::InvalidSignatureFormat)
+        ));
+        assert!(matches!(
+            verify_eth_personal_sign(MESSAGE, &signature, &address[..19]),
+            Err(WasmApiCryptoError::InvalidPubkeyFormat)
+        ));
+
+        let mut bad_v = signature;
+        bad_v[64] = 29;
+        assert!(matches!(
+            verify_eth_personal_sign(MESSAGE, &bad_v, &address),
+            Err(WasmApiCryptoError::InvalidRecoveryParam)
+        ));
+    }
+}
@@ -0,0 +1,253 @@
+use std::convert::TryFrom;
+
+use log::*;
+use protobuf::wire_format::WireType;
+use protobuf::CodedInputStream;
+
+use enclave_crypto::WasmApiCryptoError;
+
+/// Prevotes and precommits are the only votes a validator can equivocate on
+/// https://github.com/cometbft/cometbft/blob/v0.34.27/proto/tendermint/types/types.proto#L14-L19
+const PREVOTE_TYPE: u64 = 1;
+const PRECOMMIT_TYPE: u64 = 2;
+
+/// The fields of a `tendermint.types.CanonicalVote` that decide whether two votes conflict
+/// https://github.com/cometbft/cometbft/blob/v0.34.27/proto/tendermint/types/canonical.proto#L24-L31
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct CanonicalVote {
+    pub vote_type: u64,
+    pub height: i64,
+    pub round: i64,
+    /// The encoded `CanonicalBlockID`, empty for a vote for nil
+    pub block_id: Vec<u8>,
+    pub chain_id: String,
+}
+
+/// Parses the sign bytes of a vote, which are a length-delimited `CanonicalVote`
+pub fn parse_vote_sign_bytes(sign_bytes: &[u8]) -> Result<CanonicalVote, WasmApiCryptoError> {
+    let malformed = |err| {
+        debug!("failed to parse vote sign bytes: {:?}", err);
+        WasmApiCryptoError::InvalidEvidence
+    };
+
+    let mut input = CodedInputStream::from_bytes(sign_bytes);
+    let length = input.read_raw_varint32().map_err(malformed)? as u64;
+    if input.pos() + length != sign_bytes.len() as u64 {
+        debug!("vote sign bytes length prefix doesn't match the vote length");
+        return Err(WasmApiCryptoError::InvalidEvidence);
+    }
+
+    let mut vote = CanonicalVote::default();
+    while !input.eof().map_err(malformed)? {
+        let (field_number, wire_type) = input.read_tag_unpack().map_err(malformed)?;
+        match (field_number, wire_type) {
+            (1, WireType::WireTypeVarint) => {
+                vote.vote_type = input.read_uint64().map_err(malformed)?
+            }
+            (2, WireType::WireTypeFixed64) => {
+                vote.height = input.read_sfixed64().map_err(malformed)?
+            }
+            (3, WireType::WireTypeFixed64) => {
+                vote.round = input.read_sfixed64().map_err(malformed)?
+            }
+            (4, WireType::WireTypeLengthDelimited) => {
+                vote.block_id = input.read_bytes().map_err(malformed)?
+            }
+            (6, WireType::WireTypeLengthDelimited) => {
+                vote.chain_id = input.read_string().map_err(malformed)?
+            }
+            (_, wire_type) => input.skip_field(wire_type).map_err(malformed)?,
+        }
+    }
+
+    Ok(vote)
+}
+
+/// Checks that two signed votes are double-sign evidence against the validator with `pubkey`:
+/// both are validly signed by it, for the same chain, height, round and vote type,
+/// but for different blocks.
+///
+/// Returns the offending validator's public key on success.
+pub fn verify_double_sign_evidence(
+    vote_a: &[u8],
+    signature_a: &[u8],
+    vote_b: &[u8],
+    signature_b: &[u8],
+    pubkey: &[u8],
+) -> Result<Vec<u8>, WasmApiCryptoError> {
+    let verification_key = ed25519_zebra::VerificationKey::try_from(pubkey).map_err(|err| {
+        debug!("double sign evidence has a malformed pubkey: {:?}", err);
+        WasmApiCryptoError::InvalidPubkeyFormat
+    })?;
+
+    let parse_signature = |signature: &[u8]| {
+        ed25519_zebra::Signature::try_from(signature).map_err(|err| {
+            debug!("double sign evidence has a malformed signature: {:?}", err);
+            WasmApiCryptoError::InvalidSignatureFormat
+        })
+    };
+    let signature_a = parse_signature(signature_a)?;
+    let signature_b = parse_signature(signature_b)?;
+
+    let parsed_a = parse_vote_sign_bytes(vote_a)?;
+    let parsed_b = parse_vote_sign_bytes(vote_b)?;
+
+    if parsed_a.vote_type != PREVOTE_TYPE && parsed_a.vote_type != PRECOMMIT_TYPE {
+        debug!("double sign evidence has an invalid vote type {}", parsed_a.vote_type);
+        return Err(WasmApiCryptoError::InvalidEvidence);
+    }
+
+    if parsed_a.vote_type != parsed_b.vote_type
+        || parsed_a.height != parsed_b.height
+        || parsed_a.round != parsed_b.round
+        || parsed_a.chain_id != parsed_b.chain_id
+    {
+        debug!(
+            "double sign evidence votes are not for the same step: {:?} vs {:?}",
+            parsed_a, parsed_b
+        );
+        return Err(WasmApiCryptoError::InvalidEvidence);
+    }
+
+    if parsed_a.block_id == parsed_b.block_id {
+        debug!("double sign evidence votes are for the same block");
+        return Err(WasmApiCryptoError::InvalidEvidence);
+    }
+
+    for (vote, signature) in [(vote_a, signature_a), (vote_b, signature_b)] {
+        if let Err(err) = verification_key.verify(&signature, vote) {
+            debug!("double sign evidence has an invalid signature: {:?}", err);
+            return Err(WasmApiCryptoError::InvalidEvidence);
+        }
+    }
+
+    Ok(pubkey.to_vec())
+}
+
+#[cfg(feature = "test")]
+pub mod tests {
+    use super::*;
+    use protobuf::CodedOutputStream;
+
+    const CHAIN_ID: &str = "secret-4";
+
+    fn encode_vote(vote_type: u64, height: i64, round: i64, block_hash: &[u8]) -> Vec<u8> {
+        let mut vote = vec![];
+        {
+            let mut out = CodedOutputStream::vec(&mut vote);
+            out.write_uint64(1, vote_type).unwrap();
+            out.write_sfixed64(2, height).unwrap();
+            out.write_sfixed64(3, round).unwrap();
+            if !block_hash.is_empty() {
+                // CanonicalBlockID { hash = 1 }
+                let mut block_id = vec![];
+                {
+                    let mut block_id_out = CodedOutputStream::vec(&mut block_id);
+                    block_id_out.write_bytes(1, block_hash).unwrap();
+                    block_id_out.flush().unwrap();
+                }
+                out.write_bytes(4, &block_id).unwrap();
+            }
+            out.write_string(6, CHAIN_ID).unwrap();
+            out.flush().unwrap();
+        }
+
+        let mut sign_bytes = vec![];
+        {
+            let mut out = CodedOutputStream::vec(&mut sign_bytes);
+            out.write_raw_varint32(vote.len() as u32).unwrap();
+            out.write_raw_bytes(&vote).unwrap();
+            out.flush().unwrap();
+        }
+        sign_bytes
+    }
+
+    fn signer(seed: u8) -> (ed25519_zebra::SigningKey, Vec<u8>) {
+        let signing_key = ed25519_zebra::SigningKey::from([seed; 32]);
+        let pubkey: [u8; 32] = ed25519_zebra::VerificationKey::from(&signing_key).into();
+        (signing_key, pubkey.to_vec())
+    }
+
+    fn sign(signing_key: &ed25519_zebra::SigningKey, vote: &[u8]) -> Vec<u8> {
+        let signature: [u8; 64] = signing_key.sign(vote).into();
+        signature.to_vec()
+    }
+
+    pub fn test_parse_vote_sign_bytes() {
+        let vote = encode_vote(PRECOMMIT_TYPE, 100, 2, b"block");
+        let parsed = parse_vote_sign_bytes(&vote).unwrap();
+        assert_eq!(parsed.vote_type, PRECOMMIT_TYPE);
+        assert_eq!(parsed.height, 100);
+        assert_eq!(parsed.round, 2);
+        assert!(!parsed.block_id.is_empty());
+        assert_eq!(parsed.chain_id, CHAIN_ID);
+
+        // truncated input
+        assert!(parse_vote_sign_bytes(&vote[..vote.len() - 1]).is_err());
+    }
+
+    pub fn test_double_sign_evidence_conflicting_votes() {
+        let (signing_key, pubkey) = signer(1);
+        let vote_a = encode_vote(PREVOTE_TYPE, 100, 0, b"block a");
+        let vote_b = encode_vote(PREVOTE_TYPE, 100, 0, b"block b");
+
+        let result = verify_double_sign_evidence(
+            &vote_a,
+            &sign(&signing_key, &vote_a),
+            &vote_b,
+            &sign(&signing_key, &vote_b),
+            &pubkey,
+        );
+        assert_eq!(result.unwrap(), pubkey);
+
+        // a vote for nil conflicts with a vote for a block
+        let vote_nil = encode_vote(PREVOTE_TYPE, 100, 0, b"");
+        let result = verify_double_sign_evidence(
+            &vote_a,
+            &sign(&signing_key, &vote_a),
+            &vote_nil,
+            &sign(&signing_key, &vote_nil),
+            &pubkey,
+        );
+        assert_eq!(result.unwrap(), pubkey);
+    }
+
+    pub fn test_double_sign_evidence_rejects_non_conflicting_votes() {
+        let (signing_key, pubkey) = signer(1);
+        let check = |vote_a: &[u8], vote_b: &[u8]| {
+            verify_double_sign_evidence(
+                vote_a,
+                &sign(&signing_key, vote_a),
+                vote_b,
+                &sign(&signing_key, vote_b),
+                &pubkey,
+            )
+        };
+
+        let vote = encode_vote(PRECOMMIT_TYPE, 100, 0, b"block a");
+        // same block
+        assert!(check(&vote, &vote).is_err());
+        // different height
+        assert!(check(&vote, &encode_vote(PRECOMMIT_TYPE, 101, 0, b"block b")).is_err());
+        // different round
+        assert!(check(&vote, &encode_vote(PRECOMMIT_TYPE, 100, 1, b"block b")).is_err());
+        // different vote type
+        assert!(check(&vote, &encode_vote(PREVOTE_TYPE, 100, 0, b"block b")).is_err());
+    }
+
+    pub fn test_double_sign_evidence_rejects_other_signer() {
+        let (signing_key, pubkey) = signer(1);
+        let (other_signing_key, _) = signer(2);
+        let vote_a = encode_vote(PREVOTE_TYPE, 100, 0, b"block a");
+        let vote_b = encode_vote(PREVOTE_TYPE, 100, 0, b"block b");
+
+        let result = verify_double_sign_evidence(
+            &vote_a,
+            &sign(&signing_key, &vote_a),
+            &vote_b,
+            &sign(&other_signing_key, &vote_b),
+            &pubkey,
+        );
+        assert!(matches!(result, Err(WasmApiCryptoError::InvalidEvidence)));
+    }
+}
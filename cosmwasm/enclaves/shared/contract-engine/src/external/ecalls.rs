@@ -14,6 +14,7 @@ use enclave_ffi_types::{
 
 use enclave_utils::{oom_handler, validate_const_ptr, validate_input_length, validate_mut_ptr};
 
+use crate::contract_validation::get_execution_receipt_public_key;
 use crate::external::results::{
     result_handle_success_to_handleresult, result_init_success_to_initresult,
     result_migrate_success_to_result, result_query_success_to_queryresult,
@@ -105,6 +106,10 @@ fn ecall_configure_runtime_impl(config: RuntimeConfiguration) -> sgx_status_t {
         config.module_cache_size
     );
     crate::wasm3::module_cache::configure_module_cache(config.module_cache_size as usize);
+    crate::external::results::configure_query_result_inline_threshold(
+        config.query_result_inline_threshold,
+    );
+    enclave_utils::parallel::configure_verification_threads(config.verification_threads);
     sgx_status_t::SGX_SUCCESS
 }
 
@@ -580,6 +585,30 @@ pub unsafe extern "C" fn ecall_health_check() -> HealthCheckResult {
     HealthCheckResult::Success
 }
 
+/// Writes the public key that verifies the receipts of the `execution_receipt` host function to
+/// `public_key`. It's derived from the consensus seed, so every node returns the same key.
+///
+/// # Safety
+/// Always use protection
+#[no_mangle]
+pub unsafe extern "C" fn ecall_get_execution_receipt_public_key(
+    public_key: &mut [u8; 32],
+) -> sgx_status_t {
+    validate_mut_ptr!(
+        public_key.as_mut_ptr(),
+        public_key.len(),
+        sgx_status_t::SGX_ERROR_UNEXPECTED
+    );
+
+    match get_execution_receipt_public_key() {
+        Ok(key) => {
+            public_key.copy_from_slice(&key);
+            sgx_status_t::SGX_SUCCESS
+        }
+        Err(_) => sgx_status_t::SGX_ERROR_UNEXPECTED,
+    }
+}
+
 #[cfg(feature = "test")]
 pub mod tests {
     use super::*;
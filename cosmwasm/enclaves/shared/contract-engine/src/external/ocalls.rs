@@ -10,6 +10,18 @@ extern "C" {
         length: usize,
     ) -> sgx_status_t;
 
+    pub fn ocall_allocate_chunked(
+        retval: *mut UserSpaceBuffer,
+        total_length: usize,
+    ) -> sgx_status_t;
+
+    pub fn ocall_append_chunk(
+        retval: *mut OcallReturn,
+        buffer: UserSpaceBuffer,
+        chunk: *const u8,
+        chunk_len: usize,
+    ) -> sgx_status_t;
+
     pub fn ocall_read_db(
         retval: *mut OcallReturn,
         context: Ctx,
@@ -20,6 +32,39 @@ extern "C" {
         key_len: usize,
     ) -> sgx_status_t;
 
+    pub fn ocall_multiple_read_db(
+        retval: *mut OcallReturn,
+        context: Ctx,
+        vm_error: *mut UntrustedVmError,
+        gas_used: *mut u64,
+        values: *mut EnclaveBuffer,
+        keys: *const u8,
+        keys_len: usize,
+    ) -> sgx_status_t;
+
+    pub fn ocall_db_scan(
+        retval: *mut OcallReturn,
+        context: Ctx,
+        vm_error: *mut UntrustedVmError,
+        gas_used: *mut u64,
+        iterator_id: *mut u32,
+        start: *const u8,
+        start_len: usize,
+        end: *const u8,
+        end_len: usize,
+        order: i32,
+    ) -> sgx_status_t;
+
+    pub fn ocall_db_next(
+        retval: *mut OcallReturn,
+        context: Ctx,
+        vm_error: *mut UntrustedVmError,
+        gas_used: *mut u64,
+        iterator_id: u32,
+        key: *mut EnclaveBuffer,
+        value: *mut EnclaveBuffer,
+    ) -> sgx_status_t;
+
     pub fn ocall_query_chain(
         retval: *mut OcallReturn,
         context: Ctx,
@@ -1,11 +1,15 @@
+use std::slice::Chunks;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use log::warn;
 use sgx_types::sgx_status_t;
 
 use enclave_ffi_types::{
-    EnclaveError, HandleResult, InitResult, MigrateResult, QueryResult, UntrustedVmError,
-    UpdateAdminResult, UserSpaceBuffer,
+    EnclaveError, HandleResult, InitResult, MigrateResult, OcallReturn, QueryResult,
+    StateSnapshotResult, UntrustedVmError, UpdateAdminResult, UserSpaceBuffer,
 };
 
-use crate::external::ocalls::ocall_allocate;
+use crate::external::ocalls::{ocall_allocate, ocall_allocate_chunked, ocall_append_chunk};
 
 /// This struct is returned from module initialization.
 pub struct InitSuccess {
@@ -148,24 +152,133 @@ pub fn result_query_success_to_queryresult(
 ) -> QueryResult {
     match result {
         Ok(QuerySuccess { output }) => {
-            let user_buffer = unsafe {
-                let mut user_buffer = std::mem::MaybeUninit::<UserSpaceBuffer>::uninit();
-                match ocall_allocate(user_buffer.as_mut_ptr(), output.as_ptr(), output.len()) {
-                    sgx_status_t::SGX_SUCCESS => { /* continue */ }
-                    _ => {
-                        return QueryResult::Failure {
-                            err: EnclaveError::FailedOcall {
-                                vm_error: UntrustedVmError::default(),
-                            },
-                        }
-                    }
-                }
-                user_buffer.assume_init()
+            let threshold = QUERY_RESULT_INLINE_THRESHOLD.load(Ordering::Relaxed);
+            let user_buffer = match query_output_chunks(&output, threshold) {
+                None => allocate_inline(&output),
+                Some(chunks) => allocate_chunked(output.len(), chunks),
             };
-            QueryResult::Success {
-                output: user_buffer,
+            match user_buffer {
+                Ok(user_buffer) => QueryResult::Success {
+                    output: user_buffer,
+                },
+                Err(err) => QueryResult::Failure { err },
             }
         }
         Err(err) => QueryResult::Failure { err },
     }
 }
+
+pub fn result_state_snapshot_to_result(
+    result: Result<Vec<u8>, EnclaveError>,
+) -> StateSnapshotResult {
+    match result.and_then(|output| allocate_inline(&output)) {
+        Ok(user_buffer) => StateSnapshotResult::Success {
+            output: user_buffer,
+        },
+        Err(err) => StateSnapshotResult::Failure { err },
+    }
+}
+
+/// Query outputs up to this many bytes are copied to the host in a single `ocall_allocate`
+/// by default. The node can change this with `RuntimeConfiguration`.
+pub const DEFAULT_QUERY_RESULT_INLINE_THRESHOLD: usize = 1024 * 1024;
+/// Size of each chunk when streaming a query output that is above the threshold
+pub const QUERY_RESULT_CHUNK_SIZE: usize = 256 * 1024;
+
+static QUERY_RESULT_INLINE_THRESHOLD: AtomicUsize =
+    AtomicUsize::new(DEFAULT_QUERY_RESULT_INLINE_THRESHOLD);
+
+/// Sets the size above which query outputs are streamed to the host in chunks.
+/// A threshold of 0 keeps the default.
+pub fn configure_query_result_inline_threshold(threshold: u32) {
+    let threshold = match threshold {
+        0 => DEFAULT_QUERY_RESULT_INLINE_THRESHOLD,
+        t => t as usize,
+    };
+    QUERY_RESULT_INLINE_THRESHOLD.store(threshold, Ordering::Relaxed);
+}
+
+/// Returns the chunks to stream `output` in, or `None` if it's small enough to copy inline.
+fn query_output_chunks(output: &[u8], threshold: usize) -> Option<Chunks<u8>> {
+    if output.len() <= threshold {
+        None
+    } else {
+        Some(output.chunks(QUERY_RESULT_CHUNK_SIZE))
+    }
+}
+
+fn allocate_inline(output: &[u8]) -> Result<UserSpaceBuffer, EnclaveError> {
+    let mut user_buffer = std::mem::MaybeUninit::<UserSpaceBuffer>::uninit();
+    match unsafe { ocall_allocate(user_buffer.as_mut_ptr(), output.as_ptr(), output.len()) } {
+        sgx_status_t::SGX_SUCCESS => Ok(unsafe { user_buffer.assume_init() }),
+        _ => Err(EnclaveError::FailedOcall {
+            vm_error: UntrustedVmError::default(),
+        }),
+    }
+}
+
+/// Streams the output to the host chunk by chunk, so that a huge output doesn't have to be
+/// marshalled across the enclave boundary in one piece.
+/// The host-side buffer is returned as the handle, same as with `ocall_allocate`.
+fn allocate_chunked(
+    total_length: usize,
+    chunks: Chunks<u8>,
+) -> Result<UserSpaceBuffer, EnclaveError> {
+    let failed_ocall = || EnclaveError::FailedOcall {
+        vm_error: UntrustedVmError::default(),
+    };
+
+    let mut user_buffer = std::mem::MaybeUninit::<UserSpaceBuffer>::uninit();
+    let status = unsafe { ocall_allocate_chunked(user_buffer.as_mut_ptr(), total_length) };
+    let user_buffer = match status {
+        sgx_status_t::SGX_SUCCESS => unsafe { user_buffer.assume_init() },
+        _ => return Err(failed_ocall()),
+    };
+
+    for chunk in chunks {
+        let mut ocall_return = OcallReturn::Success;
+        let status = unsafe {
+            ocall_append_chunk(
+                &mut ocall_return,
+                UserSpaceBuffer {
+                    ptr: user_buffer.ptr,
+                },
+                chunk.as_ptr(),
+                chunk.len(),
+            )
+        };
+        if status != sgx_status_t::SGX_SUCCESS || !matches!(ocall_return, OcallReturn::Success) {
+            warn!("failed to stream a query result chunk to the host");
+            return Err(failed_ocall());
+        }
+    }
+
+    Ok(user_buffer)
+}
+
+#[cfg(feature = "test")]
+pub mod tests {
+    use super::{query_output_chunks, QUERY_RESULT_CHUNK_SIZE};
+
+    pub fn test_query_output_below_threshold_is_inline() {
+        let output = vec![7u8; 1000];
+        assert!(query_output_chunks(&output, 1000).is_none());
+        assert!(query_output_chunks(&output, 5000).is_none());
+        assert!(query_output_chunks(&[], 0).is_none());
+    }
+
+    pub fn test_query_output_above_threshold_is_chunked() {
+        let output: Vec<u8> = (0..(QUERY_RESULT_CHUNK_SIZE * 2 + 10))
+            .map(|i| i as u8)
+            .collect();
+
+        let chunks: Vec<&[u8]> = query_output_chunks(&output, 1000).unwrap().collect();
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(chunks[0].len(), QUERY_RESULT_CHUNK_SIZE);
+        assert_eq!(chunks[2].len(), 10);
+        assert_eq!(chunks.concat(), output);
+
+        // one byte above the threshold is enough to switch to chunks
+        assert_eq!(query_output_chunks(&output[..1001], 1000).unwrap().count(), 1);
+    }
+}
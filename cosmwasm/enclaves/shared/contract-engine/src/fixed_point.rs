@@ -0,0 +1,129 @@
+//! Integer and fixed-point arithmetic for contracts, which can't use floats.
+//!
+//! Decimals are represented by their atomics, e.g. `1.5` with 18 decimal places is
+//! `1_500_000_000_000_000_000`. All results are rounded down.
+
+/// `cosmwasm_std::Decimal` uses 18 decimal places, so that's the most we support
+pub const MAX_DECIMAL_PLACES: u32 = 18;
+
+/// Error codes returned to contracts in the high half of the result
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FixedPointError {
+    Overflow = 1,
+    /// A non-zero result was rounded down to zero
+    Underflow = 2,
+    DivideByZero = 3,
+    InvalidDecimalPlaces = 4,
+}
+
+/// Computes `a * b / c` with a 128-bit intermediate, so the product can't overflow
+pub fn checked_mul_div(a: u64, b: u64, c: u64) -> Result<u64, FixedPointError> {
+    if c == 0 {
+        return Err(FixedPointError::DivideByZero);
+    }
+
+    let product = a as u128 * b as u128;
+    let result = product / c as u128;
+
+    if result > u64::MAX as u128 {
+        return Err(FixedPointError::Overflow);
+    }
+    if result == 0 && product != 0 {
+        return Err(FixedPointError::Underflow);
+    }
+
+    Ok(result as u64)
+}
+
+/// Computes the square root of `x` with `decimal_places` decimal places.
+/// With 0 decimal places this is the integer square root.
+pub fn sqrt(x: u64, decimal_places: u32) -> Result<u64, FixedPointError> {
+    if decimal_places > MAX_DECIMAL_PLACES {
+        return Err(FixedPointError::InvalidDecimalPlaces);
+    }
+
+    // sqrt(x / 10^d) * 10^d == sqrt(x * 10^d)
+    // u64::MAX * 10^18 < u128::MAX, so this can't overflow
+    let scaled = x as u128 * 10u128.pow(decimal_places);
+    // the root is at most sqrt(u64::MAX * 10^18) < u64::MAX
+    Ok(isqrt(scaled) as u64)
+}
+
+/// Integer square root using Newton's method, rounded down
+fn isqrt(n: u128) -> u128 {
+    if n < 2 {
+        return n;
+    }
+
+    // start from a power of two that is known to be >= sqrt(n)
+    let bits = 128 - n.leading_zeros();
+    let mut x = 1u128 << ((bits + 1) / 2);
+    loop {
+        let next = (x + n / x) / 2;
+        if next >= x {
+            return x;
+        }
+        x = next;
+    }
+}
+
+#[cfg(feature = "test")]
+pub mod tests {
+    use super::*;
+
+    const DECIMAL_ONE: u64 = 1_000_000_000_000_000_000;
+
+    pub fn test_checked_mul_div() {
+        assert_eq!(checked_mul_div(6, 7, 3), Ok(14));
+        // rounds down
+        assert_eq!(checked_mul_div(10, 1, 3), Ok(3));
+        // the intermediate product overflows u64 but the result doesn't
+        assert_eq!(checked_mul_div(u64::MAX, u64::MAX, u64::MAX), Ok(u64::MAX));
+        assert_eq!(checked_mul_div(u64::MAX, 2, 4), Ok(u64::MAX / 2));
+        // multiplying decimals: 1.5 * 2.5 == 3.75
+        assert_eq!(
+            checked_mul_div(DECIMAL_ONE * 3 / 2, DECIMAL_ONE * 5 / 2, DECIMAL_ONE),
+            Ok(DECIMAL_ONE * 15 / 4)
+        );
+        assert_eq!(checked_mul_div(0, 5, 7), Ok(0));
+    }
+
+    pub fn test_checked_mul_div_errors() {
+        assert_eq!(checked_mul_div(1, 1, 0), Err(FixedPointError::DivideByZero));
+        assert_eq!(checked_mul_div(u64::MAX, 2, 1), Err(FixedPointError::Overflow));
+        assert_eq!(
+            checked_mul_div(u64::MAX, u64::MAX, u64::MAX - 1),
+            Err(FixedPointError::Overflow)
+        );
+        assert_eq!(checked_mul_div(1, 1, 2), Err(FixedPointError::Underflow));
+        assert_eq!(
+            checked_mul_div(1, DECIMAL_ONE - 1, DECIMAL_ONE),
+            Err(FixedPointError::Underflow)
+        );
+    }
+
+    pub fn test_sqrt() {
+        assert_eq!(sqrt(0, 0), Ok(0));
+        assert_eq!(sqrt(1, 0), Ok(1));
+        assert_eq!(sqrt(15, 0), Ok(3));
+        assert_eq!(sqrt(16, 0), Ok(4));
+        assert_eq!(sqrt(u64::MAX, 0), Ok(u32::MAX as u64));
+        // decimals: sqrt(2.0) == 1.414213562373095048...
+        assert_eq!(sqrt(2 * DECIMAL_ONE, 18), Ok(1_414_213_562_373_095_048));
+        assert_eq!(sqrt(4 * DECIMAL_ONE, 18), Ok(2 * DECIMAL_ONE));
+        // sqrt(0.25) == 0.5
+        assert_eq!(sqrt(25, 2), Ok(50));
+        // the largest input doesn't overflow the intermediate value
+        assert_eq!(sqrt(u64::MAX, 18), Ok(4_294_967_295_999_999_999));
+        assert_eq!(sqrt(1, 19), Err(FixedPointError::InvalidDecimalPlaces));
+    }
+
+    pub fn test_isqrt_is_exact() {
+        for n in [2u128, 3, 99, 100, 101, u64::MAX as u128, u128::MAX] {
+            let root = isqrt(n);
+            assert!(root * root <= n);
+            let next_square = root.checked_add(1).and_then(|r| r.checked_mul(r));
+            assert!(next_square.map_or(true, |square| square > n));
+        }
+    }
+}
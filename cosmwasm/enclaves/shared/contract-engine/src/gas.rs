@@ -4,6 +4,38 @@ pub use pwasm_utils::{inject_gas_counter, rules};
 //pub const OCALL_BASE_GAS: u64 = 2_000_000;
 pub const WRITE_BASE_GAS: u64 = 2_000;
 pub const READ_BASE_GAS: u64 = 1_000;
+/// Charged per byte of (encrypted) output of the queries sent through the gRPC API, whether
+/// it's returned inline or in chunks
+pub const QUERY_RESULT_BYTE_GAS: u64 = 30;
+/// How much (WASM) gas a unit of sdk gas is worth
+pub const GAS_MULTIPLIER: u64 = 1000; // (cosmwasm gas : sdk gas)
+
+/// Returns the gas limit a contract call runs with, given the gas left in the tx and the most (sdk)
+/// gas governance lets a single contract call use, regardless of the tx gas limit. A ceiling of 0
+/// means contracts are only bound by the tx gas limit.
+pub fn contract_gas_limit(tx_gas_limit: u64, contract_gas_ceiling: u64) -> u64 {
+    apply_gas_ceiling(
+        tx_gas_limit,
+        contract_gas_ceiling.saturating_mul(GAS_MULTIPLIER),
+    )
+}
+
+pub fn apply_gas_ceiling(tx_gas_limit: u64, ceiling: u64) -> u64 {
+    match ceiling {
+        0 => tx_gas_limit,
+        ceiling => tx_gas_limit.min(ceiling),
+    }
+}
+
+/// Returns the gas limit a call runs with, given the gas the host gave it and the limit (in sdk
+/// gas) its caller declared for it as a submessage, if any. The declared limit travels inside the
+/// encrypted message, so the host can't raise it.
+pub fn submsg_gas_limit(gas_limit: u64, declared_gas_limit: Option<u64>) -> u64 {
+    match declared_gas_limit {
+        Some(declared) => gas_limit.min(declared.saturating_mul(GAS_MULTIPLIER)),
+        None => gas_limit,
+    }
+}
 
 /// Wasm cost table
 pub struct WasmCosts {
@@ -23,6 +55,9 @@ pub struct WasmCosts {
     pub initial_mem: u32,
     /// Grow memory cost, per page (64kb)
     pub grow_mem: u32,
+    /// Most memory (in 64kb pages) a contract can have during a call. Whether an allocation
+    /// succeeds is part of consensus, so this is the same for all nodes.
+    pub max_memory_pages: u32,
     // /// Memory copy cost, per byte
     // pub memcpy: u32,
     // /// Max stack height (native WebAssembly stack limiter)
@@ -47,12 +82,87 @@ pub struct WasmCosts {
     pub external_ed25519_batch_verify_base: u32,
     /// Cost invoking ed25519_batch_verify from WASM
     pub external_ed25519_batch_verify_each: u32,
+    /// Cost invoking secp256k1_batch_verify from WASM
+    pub external_secp256k1_batch_verify_base: u32,
+    /// Cost invoking secp256k1_batch_verify from WASM
+    pub external_secp256k1_batch_verify_each: u32,
+    /// Cost invoking verify_double_sign_evidence from WASM
+    pub external_verify_double_sign_evidence: u32,
+    /// Cost invoking verify_jwt from WASM
+    pub external_verify_jwt: u32,
     /// Cost invoking secp256k1_sign from WASM
     pub external_secp256k1_sign: u32,
     /// Cost invoking ed25519_sign from WASM
     pub external_ed25519_sign: u32,
     pub external_check_gas_used: u32,
     pub external_minimum_gas_evaporate: u32,
+    /// Cost invoking execution_receipt from WASM
+    pub external_execution_receipt: u32,
+    /// Cost invoking gas_used_in_call from WASM
+    pub external_gas_used_in_call: u32,
+    /// Cost invoking checked_mul_div from WASM
+    pub external_checked_mul_div: u32,
+    /// Cost invoking sqrt from WASM
+    pub external_sqrt: u32,
+    /// Cost invoking enclave_block_view from WASM
+    pub external_enclave_block_view: u32,
+    /// Cost invoking derive_id from WASM
+    pub external_derive_id: u32,
+    /// Cost invoking derive_nonce from WASM
+    pub external_derive_nonce: u32,
+    /// Cost invoking verify_eth_personal_sign from WASM
+    pub external_verify_eth_personal_sign: u32,
+    /// Cost invoking verify_webauthn from WASM
+    pub external_verify_webauthn: u32,
+    /// Cost invoking verify_adr036 from WASM
+    pub external_verify_adr036: u32,
+    /// Cost invoking verify_legacy_multisig from WASM
+    pub external_verify_legacy_multisig_base: u32,
+    /// Cost invoking verify_legacy_multisig from WASM
+    pub external_verify_legacy_multisig_each: u32,
+    /// Cost invoking normalize_coins from WASM
+    pub external_normalize_coins: u32,
+    /// Cost invoking require_funds from WASM
+    pub external_require_funds: u32,
+    /// Cost invoking remaining_submsg_budget from WASM
+    pub external_remaining_submsg_budget: u32,
+    /// Cost invoking bls12_381_aggregate_verify from WASM
+    pub external_bls12_381_aggregate_verify_base: u32,
+    /// Cost of each pairing in bls12_381_aggregate_verify
+    pub external_bls12_381_aggregate_verify_per_pairing: u32,
+    /// Cost of each message in bls12_381_aggregate_verify, for hashing it to the curve
+    pub external_bls12_381_aggregate_verify_per_message: u32,
+    /// Cost of each public key in bls12_381_aggregate_verify, for decompressing and checking it
+    pub external_bls12_381_aggregate_verify_per_pubkey: u32,
+    /// Cost invoking groth16_verify from WASM
+    pub external_groth16_verify_base: u32,
+    /// Cost of each public input in groth16_verify
+    pub external_groth16_verify_per_input: u32,
+    /// Cost invoking verify_signed_payload from WASM
+    pub external_verify_signed_payload: u32,
+    /// Cost invoking secp256k1_verify_der from WASM
+    pub external_secp256k1_verify_der: u32,
+    /// Cost invoking secp256r1_verify from WASM
+    pub external_secp256r1_verify: u32,
+    /// Cost invoking parse_ibc_denom from WASM, on top of the gas used by the query
+    pub external_parse_ibc_denom: u32,
+    /// Cost invoking last_output_hash from WASM, on top of the gas used to read the log
+    pub external_last_output_hash: u32,
+    /// Cost invoking same_session from WASM
+    pub external_same_session: u32,
+    /// Cost invoking min_gas_price from WASM
+    pub external_min_gas_price: u32,
+    /// Cost invoking tx_msg_position from WASM
+    pub external_tx_msg_position: u32,
+    /// Cost invoking predict_instantiate_address from WASM
+    pub external_predict_instantiate_address: u32,
+    /// Cost per key removed by storage_wipe_prefix, on top of the gas used by the removals
+    pub external_storage_wipe_prefix_per_key: u32,
+    /// Cost per entry a range over the contract's storage decrypts, which is every entry of its
+    /// state whatever the bounds, on top of the gas the host charges for reading them
+    pub external_db_scan_per_entry: u32,
+    /// Cost per byte of the key and value db_next returns
+    pub external_db_next_per_byte: u32,
 }
 
 impl Default for WasmCosts {
@@ -66,6 +176,7 @@ impl Default for WasmCosts {
             // static_address: 40,
             initial_mem: 8192,
             grow_mem: 8192,
+            max_memory_pages: 192, // 12 MiB
             // memcpy: 1,
             // max_stack_height: 64 * 1024,
             // opcodes_mul: 3,
@@ -78,10 +189,47 @@ impl Default for WasmCosts {
             external_ed25519_verify: 73728,
             external_ed25519_batch_verify_base: 5000,
             external_ed25519_batch_verify_each: 70000,
+            external_secp256k1_batch_verify_base: 5000,
+            external_secp256k1_batch_verify_each: 98304,
+            external_verify_double_sign_evidence: 147456,
+            external_verify_jwt: 98304,
             external_secp256k1_sign: 100000,
             external_ed25519_sign: 75000,
             external_check_gas_used: 8192,
             external_minimum_gas_evaporate: 8000,
+            external_execution_receipt: 75000,
+            external_gas_used_in_call: 8192,
+            external_checked_mul_div: 2048,
+            external_sqrt: 4096,
+            external_enclave_block_view: 8192,
+            external_derive_id: 2048,
+            external_derive_nonce: 2048,
+            external_verify_eth_personal_sign: 98304,
+            external_verify_webauthn: 98304,
+            external_verify_adr036: 98304,
+            external_verify_legacy_multisig_base: 5000,
+            external_verify_legacy_multisig_each: 98304,
+            external_normalize_coins: 4096,
+            external_require_funds: 4096,
+            external_remaining_submsg_budget: 1024,
+            external_bls12_381_aggregate_verify_base: 300000,
+            external_bls12_381_aggregate_verify_per_pairing: 400000,
+            external_bls12_381_aggregate_verify_per_message: 200000,
+            external_bls12_381_aggregate_verify_per_pubkey: 30000,
+            external_groth16_verify_base: 2700000,
+            external_groth16_verify_per_input: 100000,
+            external_verify_signed_payload: 98304,
+            external_secp256k1_verify_der: 98304,
+            external_secp256r1_verify: 98304,
+            external_parse_ibc_denom: 8192,
+            external_last_output_hash: 2048,
+            external_same_session: 2048,
+            external_min_gas_price: 1024,
+            external_tx_msg_position: 1024,
+            external_predict_instantiate_address: 2048,
+            external_storage_wipe_prefix_per_key: 1024,
+            external_db_scan_per_entry: 1024,
+            external_db_next_per_byte: 30,
         }
     }
 }
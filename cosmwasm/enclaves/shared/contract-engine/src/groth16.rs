@@ -0,0 +1,261 @@
+//! Groth16 zk-SNARK verification over BLS12-381, backing the `groth16_verify` host function.
+//!
+//! Points use the same compressed encodings as the `bls12_381_aggregate_verify` host function:
+//! - the verifying key is `alpha_g1 ++ beta_g2 ++ gamma_g2 ++ delta_g2 ++ ic[0] ++ ... ++ ic[n]`,
+//!   with one `ic` point per public input plus one
+//! - the proof is `a (G1) ++ b (G2) ++ c (G1)`
+//! - the public inputs are `n` scalars of 32 bytes each, little-endian like bellman encodes them
+//!
+//! Points are checked to be in their prime order subgroups when they are parsed.
+
+use bls12_381::{multi_miller_loop, G1Affine, G1Projective, G2Affine, G2Prepared, Gt, Scalar};
+use log::*;
+
+use enclave_crypto::WasmApiCryptoError;
+
+const G1_SIZE: usize = 48;
+const G2_SIZE: usize = 96;
+const SCALAR_SIZE: usize = 32;
+
+pub const GROTH16_PROOF_SIZE: usize = G1_SIZE + G2_SIZE + G1_SIZE;
+/// The size of a verifying key without its `ic` points
+const VK_FIXED_SIZE: usize = G1_SIZE + 3 * G2_SIZE;
+
+/// The number of public inputs a verifying key of `vk_len` bytes takes, if it's well-formed
+pub fn groth16_public_input_count(vk_len: usize) -> Option<usize> {
+    if vk_len < VK_FIXED_SIZE + G1_SIZE || (vk_len - VK_FIXED_SIZE) % G1_SIZE != 0 {
+        return None;
+    }
+    Some((vk_len - VK_FIXED_SIZE) / G1_SIZE - 1)
+}
+
+fn parse_g1(data: &[u8]) -> Option<G1Affine> {
+    let mut bytes = [0u8; G1_SIZE];
+    bytes.copy_from_slice(data);
+    Option::from(G1Affine::from_compressed(&bytes))
+}
+
+fn parse_g2(data: &[u8]) -> Option<G2Affine> {
+    let mut bytes = [0u8; G2_SIZE];
+    bytes.copy_from_slice(data);
+    Option::from(G2Affine::from_compressed(&bytes))
+}
+
+struct VerifyingKey {
+    alpha_g1: G1Affine,
+    beta_g2: G2Affine,
+    gamma_g2: G2Affine,
+    delta_g2: G2Affine,
+    ic: Vec<G1Affine>,
+}
+
+fn parse_verifying_key(vk: &[u8]) -> Option<VerifyingKey> {
+    groth16_public_input_count(vk.len())?;
+
+    let (alpha_g1, rest) = vk.split_at(G1_SIZE);
+    let (beta_g2, rest) = rest.split_at(G2_SIZE);
+    let (gamma_g2, rest) = rest.split_at(G2_SIZE);
+    let (delta_g2, ic) = rest.split_at(G2_SIZE);
+
+    Some(VerifyingKey {
+        alpha_g1: parse_g1(alpha_g1)?,
+        beta_g2: parse_g2(beta_g2)?,
+        gamma_g2: parse_g2(gamma_g2)?,
+        delta_g2: parse_g2(delta_g2)?,
+        ic: ic
+            .chunks(G1_SIZE)
+            .map(parse_g1)
+            .collect::<Option<Vec<G1Affine>>>()?,
+    })
+}
+
+fn parse_public_inputs(public_inputs: &[u8]) -> Option<Vec<Scalar>> {
+    if public_inputs.len() % SCALAR_SIZE != 0 {
+        return None;
+    }
+
+    public_inputs
+        .chunks(SCALAR_SIZE)
+        .map(|input| {
+            let mut bytes = [0u8; SCALAR_SIZE];
+            bytes.copy_from_slice(input);
+            // from_bytes rejects non-canonical encodings, i.e. values that aren't below the modulus
+            Option::from(Scalar::from_bytes(&bytes))
+        })
+        .collect()
+}
+
+/// Verifies that `proof` proves the statement with `public_inputs` for the circuit of `vk`.
+/// Malformed inputs are errors, while a well-formed proof that doesn't verify is `Ok(false)`.
+pub fn groth16_verify(
+    vk: &[u8],
+    public_inputs: &[u8],
+    proof: &[u8],
+) -> Result<bool, WasmApiCryptoError> {
+    let vk = parse_verifying_key(vk).ok_or_else(|| {
+        debug!("groth16_verify() got a malformed verifying key");
+        WasmApiCryptoError::InvalidVerifyingKey
+    })?;
+
+    let public_inputs = parse_public_inputs(public_inputs).ok_or_else(|| {
+        debug!("groth16_verify() got malformed public inputs");
+        WasmApiCryptoError::InvalidPublicInputs
+    })?;
+    if public_inputs.len() + 1 != vk.ic.len() {
+        debug!(
+            "groth16_verify() got {} public inputs for a circuit that takes {}",
+            public_inputs.len(),
+            vk.ic.len() - 1
+        );
+        return Err(WasmApiCryptoError::InvalidPublicInputs);
+    }
+
+    if proof.len() != GROTH16_PROOF_SIZE {
+        return Err(WasmApiCryptoError::InvalidProofFormat);
+    }
+    let (a, rest) = proof.split_at(G1_SIZE);
+    let (b, c) = rest.split_at(G2_SIZE);
+    let (a, b, c) = match (parse_g1(a), parse_g2(b), parse_g1(c)) {
+        (Some(a), Some(b), Some(c)) => (a, b, c),
+        _ => return Err(WasmApiCryptoError::InvalidProofFormat),
+    };
+
+    // ic[0] + input[0] * ic[1] + ... + input[n-1] * ic[n]
+    let inputs_g1 = public_inputs
+        .iter()
+        .zip(vk.ic[1..].iter())
+        .fold(G1Projective::from(vk.ic[0]), |acc, (input, ic)| acc + ic * input);
+
+    // e(a, b) == e(alpha, beta) * e(inputs, gamma) * e(c, delta), checked as
+    // e(a, b) * e(-alpha, beta) * e(-inputs, gamma) * e(-c, delta) == 1 so there is a single
+    // final exponentiation
+    let neg_alpha = -vk.alpha_g1;
+    let neg_inputs = -G1Affine::from(inputs_g1);
+    let neg_c = -c;
+    let b = G2Prepared::from(b);
+    let beta = G2Prepared::from(vk.beta_g2);
+    let gamma = G2Prepared::from(vk.gamma_g2);
+    let delta = G2Prepared::from(vk.delta_g2);
+
+    let terms = [
+        (&a, &b),
+        (&neg_alpha, &beta),
+        (&neg_inputs, &gamma),
+        (&neg_c, &delta),
+    ];
+
+    Ok(multi_miller_loop(&terms).final_exponentiation() == Gt::identity())
+}
+
+#[cfg(feature = "test")]
+pub mod tests {
+    use super::*;
+    use bls12_381::G2Projective;
+
+    /// A circuit with a single public input, where proofs are made with the trapdoor of the
+    /// setup: a proof (a, b, c) is valid for `x` when
+    /// `a * b == alpha * beta + (u0 + x * u1) * gamma + c * delta` over the scalars
+    struct Setup {
+        alpha: Scalar,
+        beta: Scalar,
+        gamma: Scalar,
+        delta: Scalar,
+        u0: Scalar,
+        u1: Scalar,
+    }
+
+    fn setup() -> Setup {
+        Setup {
+            alpha: Scalar::from(11u64),
+            beta: Scalar::from(13u64),
+            gamma: Scalar::from(17u64),
+            delta: Scalar::from(19u64),
+            u0: Scalar::from(23u64),
+            u1: Scalar::from(29u64),
+        }
+    }
+
+    fn g1(scalar: Scalar) -> Vec<u8> {
+        G1Affine::from(G1Projective::generator() * scalar)
+            .to_compressed()
+            .to_vec()
+    }
+
+    fn g2(scalar: Scalar) -> Vec<u8> {
+        G2Affine::from(G2Projective::generator() * scalar)
+            .to_compressed()
+            .to_vec()
+    }
+
+    fn verifying_key() -> Vec<u8> {
+        let setup = setup();
+        [
+            g1(setup.alpha),
+            g2(setup.beta),
+            g2(setup.gamma),
+            g2(setup.delta),
+            g1(setup.u0),
+            g1(setup.u1),
+        ]
+        .concat()
+    }
+
+    fn prove(x: u64) -> Vec<u8> {
+        let setup = setup();
+        let (a, b) = (Scalar::from(31u64), Scalar::from(37u64));
+        let inputs = setup.u0 + Scalar::from(x) * setup.u1;
+        let c = (a * b - setup.alpha * setup.beta - inputs * setup.gamma)
+            * setup.delta.invert().unwrap();
+
+        [g1(a), g2(b), g1(c)].concat()
+    }
+
+    fn public_input(x: u64) -> Vec<u8> {
+        Scalar::from(x).to_bytes().to_vec()
+    }
+
+    pub fn test_groth16_verify_valid_proof() {
+        assert_eq!(groth16_public_input_count(verifying_key().len()), Some(1));
+        assert!(groth16_verify(&verifying_key(), &public_input(42), &prove(42)).unwrap());
+    }
+
+    pub fn test_groth16_verify_invalid_proof() {
+        // a proof for another statement
+        assert!(!groth16_verify(&verifying_key(), &public_input(43), &prove(42)).unwrap());
+
+        // a proof with c swapped for another point
+        let mut proof = prove(42);
+        let c_start = GROTH16_PROOF_SIZE - G1_SIZE;
+        proof[c_start..].copy_from_slice(&g1(Scalar::from(1u64)));
+        assert!(!groth16_verify(&verifying_key(), &public_input(42), &proof).unwrap());
+    }
+
+    pub fn test_groth16_verify_malformed_inputs() {
+        let vk = verifying_key();
+        let proof = prove(42);
+
+        assert!(matches!(
+            groth16_verify(&vk[..vk.len() - 1], &public_input(42), &proof),
+            Err(WasmApiCryptoError::InvalidVerifyingKey)
+        ));
+        assert!(matches!(
+            groth16_verify(&vk[..VK_FIXED_SIZE], &[], &proof),
+            Err(WasmApiCryptoError::InvalidVerifyingKey)
+        ));
+        assert!(matches!(
+            groth16_verify(&vk, &[public_input(42), public_input(1)].concat(), &proof),
+            Err(WasmApiCryptoError::InvalidPublicInputs)
+        ));
+        // the modulus itself isn't a canonical scalar
+        let mut modulus = (-Scalar::one()).to_bytes();
+        modulus[0] += 1;
+        assert!(matches!(
+            groth16_verify(&vk, &modulus, &proof),
+            Err(WasmApiCryptoError::InvalidPublicInputs)
+        ));
+        assert!(matches!(
+            groth16_verify(&vk, &public_input(42), &proof[..GROTH16_PROOF_SIZE - 1]),
+            Err(WasmApiCryptoError::InvalidProofFormat)
+        ));
+    }
+}
@@ -1,5 +1,11 @@
+use log::*;
+use protobuf::wire_format::WireType;
+use protobuf::{CodedInputStream, CodedOutputStream};
+use serde::Serialize;
 use sha2::{Digest, Sha256};
 
+use crate::errors::WasmEngineError;
+
 /// ReceiverChainIsSource returns true if the denomination originally came
 /// from the receiving chain and false otherwise.
 pub fn receiver_chain_is_source(source_port: &str, source_channel: &str, denom: &str) -> bool {
@@ -18,6 +24,7 @@ pub fn get_denom_prefix(port_id: &str, channel_id: &str) -> String {
 
 /// DenomTrace contains the base denomination for ICS20 fungible tokens and the
 /// source tracing information path.
+#[derive(Serialize, Debug, Default, PartialEq, Eq)]
 pub struct DenomTrace {
     /// path defines the chain of port/channel identifiers used for tracing the
     /// source of the fungible token.
@@ -128,3 +135,175 @@ pub fn is_valid_channel_id(channel_id: &str) -> bool {
 pub fn parse_channel_sequence(channel_id: &str) -> Option<&str> {
     channel_id.strip_prefix("channel-")
 }
+
+/// The gRPC query that resolves the hash of an `ibc/<hash>` denom to its trace.
+/// It's on the chain's Stargate query allowlist.
+pub const DENOM_TRACE_QUERY_PATH: &str = "/ibc.applications.transfer.v1.Query/DenomTrace";
+
+/// Error codes returned to contracts by `parse_ibc_denom` in the high half of the result
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DenomTraceError {
+    /// The denom isn't of the form `ibc/<hash>`
+    InvalidDenom = 1,
+    /// The chain doesn't have a trace with this hash
+    UnknownHash = 2,
+}
+
+/// Returns the hex encoded hash of an `ibc/<hash>` denom, in upper case like ibc-go
+pub fn ibc_denom_hash(denom: &str) -> Result<String, DenomTraceError> {
+    match denom.strip_prefix("ibc/") {
+        Some(hash) if hash.len() == 64 && hex::decode(hash).is_ok() => Ok(hash.to_uppercase()),
+        _ => Err(DenomTraceError::InvalidDenom),
+    }
+}
+
+/// Encodes a `QueryDenomTraceRequest`
+/// https://github.com/cosmos/ibc-go/blob/v4.3.0/proto/ibc/applications/transfer/v1/query.proto#L35-L39
+pub fn encode_denom_trace_request(hash: &str) -> Vec<u8> {
+    let mut request = vec![];
+    {
+        let mut out = CodedOutputStream::vec(&mut request);
+        // writing to a vec can't fail
+        out.write_string(1, hash).unwrap();
+        out.flush().unwrap();
+    }
+    request
+}
+
+/// Decodes a `QueryDenomTraceResponse`, which holds a single `DenomTrace`
+/// https://github.com/cosmos/ibc-go/blob/v4.3.0/proto/ibc/applications/transfer/v1/query.proto#L41-L46
+pub fn decode_denom_trace_response(response: &[u8]) -> Result<DenomTrace, WasmEngineError> {
+    let malformed = |err| {
+        debug!("failed to decode the denom trace response: {:?}", err);
+        WasmEngineError::HostMisbehavior
+    };
+
+    let mut input = CodedInputStream::from_bytes(response);
+    let mut encoded_trace = vec![];
+    while !input.eof().map_err(malformed)? {
+        match input.read_tag_unpack().map_err(malformed)? {
+            (1, WireType::WireTypeLengthDelimited) => {
+                encoded_trace = input.read_bytes().map_err(malformed)?
+            }
+            (_, wire_type) => input.skip_field(wire_type).map_err(malformed)?,
+        }
+    }
+
+    let mut input = CodedInputStream::from_bytes(&encoded_trace);
+    let mut trace = DenomTrace::default();
+    while !input.eof().map_err(malformed)? {
+        match input.read_tag_unpack().map_err(malformed)? {
+            (1, WireType::WireTypeLengthDelimited) => {
+                trace.path = input.read_string().map_err(malformed)?
+            }
+            (2, WireType::WireTypeLengthDelimited) => {
+                trace.base_denom = input.read_string().map_err(malformed)?
+            }
+            (_, wire_type) => input.skip_field(wire_type).map_err(malformed)?,
+        }
+    }
+
+    Ok(trace)
+}
+
+/// Resolves an `ibc/<hash>` denom to its trace. `query_denom_trace` runs the encoded
+/// `QueryDenomTraceRequest` against the chain, and returns `None` if the query failed.
+///
+/// The answer comes from the host, so the trace is only accepted if it hashes back to the denom.
+pub fn resolve_ibc_denom<F>(
+    denom: &str,
+    query_denom_trace: F,
+) -> Result<Result<DenomTrace, DenomTraceError>, WasmEngineError>
+where
+    F: FnOnce(Vec<u8>) -> Result<Option<Vec<u8>>, WasmEngineError>,
+{
+    let hash = match ibc_denom_hash(denom) {
+        Ok(hash) => hash,
+        Err(err) => return Ok(Err(err)),
+    };
+
+    let response = match query_denom_trace(encode_denom_trace_request(&hash))? {
+        Some(response) => response,
+        None => return Ok(Err(DenomTraceError::UnknownHash)),
+    };
+
+    let trace = decode_denom_trace_response(&response)?;
+    if trace.path.is_empty() || hex::encode_upper(trace.hash()) != hash {
+        debug!(
+            "the chain resolved {} to the trace {:?}, which has another hash",
+            denom, trace
+        );
+        return Err(WasmEngineError::HostMisbehavior);
+    }
+
+    Ok(Ok(trace))
+}
+
+#[cfg(feature = "test")]
+pub mod tests {
+    use super::*;
+
+    /// ATOM sent over channel-0
+    const ATOM_DENOM: &str = "ibc/27394FB092D2ECCD56123C74F36E4C1F926001CEADA9CA97EA622B25F41E5EB2";
+
+    fn encode_denom_trace_response(path: &str, base_denom: &str) -> Vec<u8> {
+        let mut trace = vec![];
+        {
+            let mut out = CodedOutputStream::vec(&mut trace);
+            out.write_string(1, path).unwrap();
+            out.write_string(2, base_denom).unwrap();
+            out.flush().unwrap();
+        }
+
+        let mut response = vec![];
+        {
+            let mut out = CodedOutputStream::vec(&mut response);
+            out.write_bytes(1, &trace).unwrap();
+            out.flush().unwrap();
+        }
+        response
+    }
+
+    pub fn test_resolve_ibc_denom_known_hash() {
+        // the hash is case insensitive, and is always queried in upper case
+        let trace = resolve_ibc_denom(&ATOM_DENOM.to_lowercase(), |request| {
+            assert_eq!(request, encode_denom_trace_request(&ATOM_DENOM[4..]));
+            Ok(Some(encode_denom_trace_response(
+                "transfer/channel-0",
+                "uatom",
+            )))
+        })
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(
+            trace,
+            DenomTrace {
+                path: "transfer/channel-0".to_string(),
+                base_denom: "uatom".to_string(),
+            }
+        );
+        assert_eq!(trace.ibc_denom(), ATOM_DENOM.to_lowercase());
+    }
+
+    pub fn test_resolve_ibc_denom_unknown_hash() {
+        let result = resolve_ibc_denom(ATOM_DENOM, |_| Ok(None)).unwrap();
+        assert_eq!(result, Err(DenomTraceError::UnknownHash));
+
+        // a trace that doesn't match the hash means the host is lying
+        let result = resolve_ibc_denom(ATOM_DENOM, |_| {
+            Ok(Some(encode_denom_trace_response(
+                "transfer/channel-1",
+                "uatom",
+            )))
+        });
+        assert!(matches!(result, Err(WasmEngineError::HostMisbehavior)));
+    }
+
+    pub fn test_resolve_ibc_denom_invalid_denom() {
+        for denom in ["uscrt", "ibc/", "ibc/27394FB092D2ECCD", &ATOM_DENOM.replace('2', "G")] {
+            let result = resolve_ibc_denom(denom, |_| panic!("queried the chain for {}", denom));
+            assert_eq!(result.unwrap(), Err(DenomTraceError::InvalidDenom));
+        }
+    }
+}
@@ -1,9 +1,21 @@
 use crate::message_utils::try_get_decrypted_secret_msg;
-use crate::types::{ParsedMessage, SecretMessage};
+use crate::types::{DecryptedSecretMessage, ParsedMessage, SecretMessage};
 use cw_types_v1::ibc::IbcPacketReceiveMsg;
 use enclave_ffi_types::EnclaveError;
 use log::{trace, warn};
 
+/// Inbound packet data that starts with this marker carries a Secret encryption envelope: the
+/// marker is followed by a `SecretMessage` (nonce || user public key || encrypted data), which is
+/// decrypted before the packet reaches the contract. The marker is optional: packet data without
+/// it is still decrypted if it's an envelope, and passed to the contract as is otherwise, like
+/// before the marker existed.
+pub const SECRET_IBC_ENVELOPE_MARKER: &[u8] = b"secret_ibc_enc_v1:";
+
+/// Returns the encryption envelope of packet data that carries the Secret marker
+pub fn secret_ibc_envelope(data: &[u8]) -> Option<&[u8]> {
+    data.strip_prefix(SECRET_IBC_ENVELOPE_MARKER)
+}
+
 pub fn parse_plaintext_ibc_protocol_message(
     plaintext_message: &[u8],
 ) -> Result<ParsedMessage, EnclaveError> {
@@ -23,7 +35,16 @@ pub fn parse_plaintext_ibc_protocol_message(
 }
 
 pub fn parse_ibc_receive_message(message: &[u8]) -> Result<ParsedMessage, EnclaveError> {
-    // TODO: Maybe mark whether the message was encrypted or not.
+    parse_ibc_receive_message_with(message, try_get_decrypted_secret_msg)
+}
+
+fn parse_ibc_receive_message_with<F>(
+    message: &[u8],
+    decrypt_envelope: F,
+) -> Result<ParsedMessage, EnclaveError>
+where
+    F: Fn(&[u8]) -> Option<DecryptedSecretMessage>,
+{
     let mut parsed_encrypted_ibc_packet: IbcPacketReceiveMsg =
      serde_json::from_slice(message).map_err(|err| {
          warn!(
@@ -34,38 +55,46 @@ pub fn parse_ibc_receive_message(message: &[u8]) -> Result<ParsedMessage, Enclav
          EnclaveError::FailedToDeserialize
      })?;
 
-    let (was_msg_encrypted, secret_msg) =
-        match try_get_decrypted_secret_msg(parsed_encrypted_ibc_packet.packet.data.as_slice()) {
-            Some(decrypted_msg) => {
-                // IBC packet was encrypted
-
-                trace!(
-                    "ibc_packet_receive data before decryption: {:?}",
-                    base64::encode(message)
-                );
-
-                parsed_encrypted_ibc_packet.packet.data =
-                    decrypted_msg.decrypted_msg.as_slice().into();
-                (true, decrypted_msg.secret_msg)
-            }
-            None => {
-                // Assume data is not encrypted
-
-                trace!(
-                    "ibc_packet_receive data was plaintext: {:?}",
-                    base64::encode(message)
-                );
-
-                (
-                    false,
-                    SecretMessage {
-                        nonce: [0; 32],
-                        user_public_key: [0; 32],
-                        msg: message.into(),
-                    },
-                )
-            }
-        };
+    let packet_data = parsed_encrypted_ibc_packet.packet.data.as_slice();
+    let decrypted_msg = match secret_ibc_envelope(packet_data) {
+        Some(envelope) => Some(decrypt_envelope(envelope).ok_or_else(|| {
+            warn!("ibc_packet_receive data is marked as encrypted but failed to decrypt");
+            EnclaveError::DecryptionError
+        })?),
+        // packets from senders that don't mark their envelopes
+        None => decrypt_envelope(packet_data),
+    };
+
+    let (was_msg_encrypted, secret_msg) = match decrypted_msg {
+        Some(decrypted_msg) => {
+            // IBC packet was encrypted
+
+            trace!(
+                "ibc_packet_receive data before decryption: {:?}",
+                base64::encode(message)
+            );
+
+            parsed_encrypted_ibc_packet.packet.data = decrypted_msg.decrypted_msg.as_slice().into();
+            (true, decrypted_msg.secret_msg)
+        }
+        None => {
+            // Assume data is not encrypted
+
+            trace!(
+                "ibc_packet_receive data was plaintext: {:?}",
+                base64::encode(message)
+            );
+
+            (
+                false,
+                SecretMessage {
+                    nonce: [0; 32],
+                    user_public_key: [0; 32],
+                    msg: message.into(),
+                },
+            )
+        }
+    };
 
     Ok(ParsedMessage {
         should_verify_sig_info: false,
@@ -103,3 +132,90 @@ pub fn parse_plaintext_ibc_validated_message(
         data_for_validation: None,
     })
 }
+
+#[cfg(feature = "test")]
+pub mod tests {
+    use super::*;
+    use enclave_crypto::{AESKey, SIVEncryptable};
+
+    fn packet_msg(data: &[u8]) -> Vec<u8> {
+        let mut msg = IbcPacketReceiveMsg::default();
+        msg.packet.data = data.into();
+        serde_json::to_vec(&msg).unwrap()
+    }
+
+    fn packet_data(parsed: &ParsedMessage) -> Vec<u8> {
+        let msg: IbcPacketReceiveMsg = serde_json::from_slice(&parsed.decrypted_msg).unwrap();
+        msg.packet.data.0
+    }
+
+    /// Stands in for the consensus io key, which isn't available in tests
+    fn decrypt_with_test_key(envelope: &[u8]) -> Option<DecryptedSecretMessage> {
+        let secret_msg = SecretMessage::from_slice(envelope).ok()?;
+        let decrypted_msg = AESKey::new_from_slice(&[7u8; 32])
+            .decrypt_siv(&secret_msg.msg, None)
+            .ok()?;
+
+        Some(DecryptedSecretMessage {
+            secret_msg,
+            decrypted_msg,
+        })
+    }
+
+    /// An envelope of `{"hello":{}}`, encrypted with the test key
+    fn envelope() -> Vec<u8> {
+        let ciphertext = AESKey::new_from_slice(&[7u8; 32])
+            .encrypt_siv(br#"{"hello":{}}"#, None)
+            .unwrap();
+        let mut envelope = vec![1u8; 32]; // nonce
+        envelope.extend_from_slice(&[2u8; 32]); // user public key
+        envelope.extend_from_slice(&ciphertext);
+        envelope
+    }
+
+    pub fn test_ibc_receive_encrypted_packet() {
+        let mut data = SECRET_IBC_ENVELOPE_MARKER.to_vec();
+        data.extend_from_slice(&envelope());
+
+        let parsed =
+            parse_ibc_receive_message_with(&packet_msg(&data), decrypt_with_test_key).unwrap();
+
+        assert!(parsed.was_msg_encrypted);
+        assert!(parsed.should_encrypt_output);
+        assert_eq!(parsed.secret_msg.nonce, [1u8; 32]);
+        assert_eq!(parsed.secret_msg.user_public_key, [2u8; 32]);
+        assert_eq!(packet_data(&parsed), br#"{"hello":{}}"#.to_vec());
+
+        // marked data that doesn't decrypt is an error rather than being passed on as plaintext
+        let mut tampered = data;
+        let last = tampered.len() - 1;
+        tampered[last] ^= 1;
+        assert!(matches!(
+            parse_ibc_receive_message_with(&packet_msg(&tampered), decrypt_with_test_key),
+            Err(EnclaveError::DecryptionError)
+        ));
+    }
+
+    pub fn test_ibc_receive_unmarked_encrypted_packet() {
+        let parsed =
+            parse_ibc_receive_message_with(&packet_msg(&envelope()), decrypt_with_test_key)
+                .unwrap();
+
+        assert!(parsed.was_msg_encrypted);
+        assert!(parsed.should_encrypt_output);
+        assert_eq!(parsed.secret_msg.nonce, [1u8; 32]);
+        assert_eq!(packet_data(&parsed), br#"{"hello":{}}"#.to_vec());
+    }
+
+    pub fn test_ibc_receive_plaintext_packet() {
+        let data = br#"{"hello":{}}"#;
+        let message = packet_msg(data);
+
+        let parsed = parse_ibc_receive_message_with(&message, decrypt_with_test_key).unwrap();
+
+        assert!(!parsed.was_msg_encrypted);
+        assert!(!parsed.should_encrypt_output);
+        assert_eq!(parsed.secret_msg.msg, message);
+        assert_eq!(packet_data(&parsed), data.to_vec());
+    }
+}
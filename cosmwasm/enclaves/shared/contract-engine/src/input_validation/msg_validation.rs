@@ -11,6 +11,7 @@ use enclave_cosmos_types::types::{
 
 use log::*;
 
+use crate::ibc_message::secret_ibc_envelope;
 use crate::types::SecretMessage;
 
 /// Get the cosmwasm message that contains the encrypted message
@@ -146,7 +147,9 @@ pub fn verify_ibc_packet_recv(sent_msg: &SecretMessage, packet: &Packet) -> bool
         trace!("get_verified_msg HANDLE_TYPE_IBC_PACKET_RECEIVE: sent_msg.msg cannot be parsed as IbcPacketReceiveMsg: {:?} Error: {:?}", String::from_utf8_lossy(&sent_msg.msg), parsed_sent_msg.err());
 
         trace!("Checking if sent_msg & data are encrypted");
-        return &sent_msg.to_vec() == data;
+        // the marker is optional
+        let envelope = secret_ibc_envelope(data).unwrap_or(data.as_slice());
+        return sent_msg.to_vec() == envelope;
     }
     let parsed = parsed_sent_msg.unwrap();
 
@@ -1,20 +1,31 @@
+use crate::compression::{compress_output, decompress_output};
 use crate::contract_validation::ReplyParams;
+use crate::gas::GAS_MULTIPLIER;
+use crate::padding::{pad, unpad};
+use crate::reply_message::HEX_ENCODED_HASH_SIZE;
 use core::fmt;
 
 /// This contains all the user-facing functions. In these functions we will be using
 /// the consensus_io_exchange_keypair and a user-generated key to create a symmetric key
 /// that is unique to the user and the enclave
 ///
-use super::types::{IoNonce, SecretMessage};
+use super::types::{IoNonce, SecretMessage, TxPosition};
 use cw_types_v010::encoding::Binary;
-use cw_types_v010::types::{CanonicalAddr, Coin, LogAttribute};
-use cw_types_v1::results::{Event, Reply, ReplyOn, SubMsg, SubMsgResponse, SubMsgResult};
+use cw_types_v010::types::{CanonicalAddr, Coin, LogAttribute, OutputCompression, SubmsgLimit};
+use cw_types_v1::results::{
+    Event, Reply, ReplyOn, ReplyPropagation, SubMsg, SubMsgResponse, SubMsgResult,
+};
 
-use enclave_ffi_types::EnclaveError;
+use enclave_cosmos_types::types::{HandleType, VerifyParamsType};
+use enclave_ffi_types::{EnclaveError, OutputPreview, OutputVariant};
 
-use enclave_crypto::{AESKey, Ed25519PublicKey, Kdf, SIVEncryptable, KEY_MANAGER};
+use enclave_crypto::key_manager::SeedsHolder;
+use enclave_crypto::{
+    AESKey, CryptoError, Ed25519PublicKey, Kdf, SIVEncryptable, SivBatchEncryptor, KEY_MANAGER,
+};
 
 use log::*;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use serde_json::Value;
@@ -109,6 +120,10 @@ pub struct QueryOutput {
     pub ok: Option<String>,
     #[serde(rename = "Err")]
     pub err: Option<Value>,
+    /// The (public) nonce of the encrypted query, so clients retrying a query can match a response
+    /// to its request without decrypting it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<Binary>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default)]
@@ -131,23 +146,58 @@ pub fn calc_encryption_key(nonce: &IoNonce, user_public_key: &Ed25519PublicKey)
     AESKey::new_from_slice(&tx_encryption_ikm).derive_key_from_this(nonce)
 }
 
+fn serialize_for_encryption<T>(val: &T) -> Result<String, EnclaveError>
+where
+    T: ?Sized + Serialize,
+{
+    let serialized: String = serde_json::to_string(val).map_err(|err| {
+        debug!("got an error while trying to encrypt output error {}", err);
+        EnclaveError::Serialization
+    })?;
+
+    Ok(serialized
+        .trim_start_matches('"')
+        .trim_end_matches('"')
+        .to_string())
+}
+
 fn encrypt_serializable<T>(
     key: &AESKey,
     val: &T,
     reply_params: &Option<Vec<ReplyParams>>,
     should_append_all_reply_params: bool,
+    pad_output: bool,
 ) -> Result<String, EnclaveError>
 where
     T: ?Sized + Serialize,
 {
-    let serialized: String = serde_json::to_string(val).map_err(|err| {
-        debug!("got an error while trying to encrypt output error {}", err);
-        EnclaveError::EncryptionError
-    })?;
+    let trimmed = serialize_for_encryption(val)?;
+
+    encrypt_preserialized_string(
+        key,
+        &trimmed,
+        reply_params,
+        should_append_all_reply_params,
+        pad_output,
+    )
+}
 
-    let trimmed = serialized.trim_start_matches('"').trim_end_matches('"');
+/// Like `encrypt_serializable`, for the results of contracts (`data` and query results), which
+/// are compressed as the chain's `compression` says before they're encrypted
+fn encrypt_serializable_result<T>(
+    key: &AESKey,
+    val: &T,
+    reply_params: &Option<Vec<ReplyParams>>,
+    pad_output: bool,
+    compression: &OutputCompression,
+) -> Result<String, EnclaveError>
+where
+    T: ?Sized + Serialize,
+{
+    let trimmed = serialize_for_encryption(val)?;
+    let plaintext = with_reply_params(&trimmed, reply_params, false);
 
-    encrypt_preserialized_string(key, trimmed, reply_params, should_append_all_reply_params)
+    encrypt_plaintext(key, &compress_output(plaintext, compression)?, pad_output)
 }
 
 // use this to encrypt a String that has already been serialized.  When that is the case, if
@@ -158,32 +208,107 @@ fn encrypt_preserialized_string(
     val: &str,
     reply_params: &Option<Vec<ReplyParams>>,
     should_append_all_reply_params: bool,
+    pad_output: bool,
 ) -> Result<String, EnclaveError> {
-    let serialized = match reply_params {
-        Some(v) => {
-            let mut ser = vec![];
-            ser.extend_from_slice(&v[0].recipient_contract_hash);
-            if should_append_all_reply_params {
-                for item in v.iter().skip(1) {
-                    ser.extend_from_slice(cw_types_v1::results::REPLY_ENCRYPTION_MAGIC_BYTES);
-                    ser.extend_from_slice(&item.sub_msg_id.to_be_bytes());
-                    ser.extend_from_slice(item.recipient_contract_hash.as_slice());
-                }
-            }
+    let serialized = with_reply_params(val, reply_params, should_append_all_reply_params);
+
+    encrypt_plaintext(key, &serialized, pad_output)
+}
+
+fn with_reply_params(
+    val: &str,
+    reply_params: &Option<Vec<ReplyParams>>,
+    should_append_all_reply_params: bool,
+) -> Vec<u8> {
+    match split_reply_chain(reply_params) {
+        Some((caller, ancestors)) => {
+            let mut ser = reply_chain_prefix(caller, ancestors, should_append_all_reply_params);
             ser.extend_from_slice(val.as_bytes());
             ser
         }
         None => val.as_bytes().to_vec(),
+    }
+}
+
+/// The frame of the contract that called this one, which this contract's output goes back to, and
+/// the frames of the callers before it, from the innermost outwards. A caller only propagates its
+/// ancestors' frames along with its own (see `attach_reply_headers_to_v1_wasm_msg`), so the first
+/// frame is always the direct caller's.
+fn split_reply_chain(
+    reply_params: &Option<Vec<ReplyParams>>,
+) -> Option<(&ReplyParams, &[ReplyParams])> {
+    reply_params
+        .as_ref()
+        .and_then(|reply_params| reply_params.split_first())
+}
+
+/// What a plaintext for `caller` starts with: its code hash, and if `should_append_all_reply_params`
+/// is set, the headers of its ancestors, so that the caller's reply knows its own place in the
+/// chain
+fn reply_chain_prefix(
+    caller: &ReplyParams,
+    ancestors: &[ReplyParams],
+    should_append_all_reply_params: bool,
+) -> Vec<u8> {
+    let mut prefix = caller.recipient_contract_hash.clone();
+    // the ancestors are only the part of the chain our caller propagated to us, which it chooses
+    // per submessage (see `ReplyPropagation`)
+    if should_append_all_reply_params {
+        for item in ancestors {
+            prefix.extend_from_slice(item.header_magic_bytes());
+            prefix.extend_from_slice(&item.sub_msg_id.to_be_bytes());
+            prefix.extend_from_slice(item.recipient_contract_hash.as_slice());
+        }
+    }
+    prefix
+}
+
+/// The id of the reply `caller` gets for this contract's output, encrypted along with the chain
+/// the caller's reply continues with and the reply depth it runs at, `caller_depth`
+fn encrypt_reply_id(
+    key: &AESKey,
+    caller: &ReplyParams,
+    ancestors: &[ReplyParams],
+    should_append_all_reply_params: bool,
+    caller_depth: u32,
+) -> Result<Binary, EnclaveError> {
+    let mut plaintext = reply_chain_prefix(caller, ancestors, should_append_all_reply_params);
+    // the depth always goes along, the caller's ancestors may have been left out of the chain
+    plaintext.extend_from_slice(&reply_depth_header(caller_depth));
+    plaintext.extend_from_slice(caller.sub_msg_id.to_string().as_bytes());
+
+    Ok(Binary::from_base64(&encrypt_plaintext(
+        key, &plaintext, false,
+    )?)?)
+}
+
+/// Encrypts `plaintext`, padding it first if `pad_output` is set (see `padding`). Padding goes
+/// outside of compression, so it's the first thing removed after decryption.
+fn encrypt_plaintext(
+    key: &AESKey,
+    plaintext: &[u8],
+    pad_output: bool,
+) -> Result<String, EnclaveError> {
+    let padded;
+    let plaintext = if pad_output {
+        padded = pad(plaintext);
+        &padded
+    } else {
+        plaintext
     };
-    let encrypted_data = key
-        .encrypt_siv(serialized.as_slice(), None)
-        .map_err(|err| {
-            debug!(
-                "got an error while trying to encrypt output error {:?}: {}",
-                err, err
-            );
-            EnclaveError::EncryptionError
-        })?;
+
+    encode_ciphertext(key.encrypt_siv(plaintext, None))
+}
+
+/// The base64 of an encrypted part of the output, or `EnclaveError::Aead` if encrypting it failed
+fn encode_ciphertext(encrypted_data: Result<Vec<u8>, CryptoError>) -> Result<String, EnclaveError> {
+    let encrypted_data = encrypted_data.map_err(|err| {
+        debug!(
+            "got an error while trying to encrypt output error {:?}: {}",
+            err, err
+        );
+        EnclaveError::Aead
+    })?;
 
     Ok(b64_encode(encrypted_data.as_slice()))
 }
@@ -199,26 +324,70 @@ pub fn post_process_output(
     contract_addr: &CanonicalAddr,
     contract_hash: &str,
     reply_params: Option<Vec<ReplyParams>>,
+    reply_depth: u32,
     sender_addr: &CanonicalAddr,
     is_query_output: bool,
     is_ibc_output: bool,
+    remaining_gas: Option<u64>,
+    submsg_limit: Option<&SubmsgLimit>,
+    max_event_type_length: u32,
+    tx_position: TxPosition,
+    pad_output: bool,
+    compression: &OutputCompression,
 ) -> Result<Vec<u8>, EnclaveError> {
     let mut raw_output = deserialize_output(output)?;
-    raw_output = attach_reply_headers_to_submsgs(raw_output, contract_hash, &reply_params)?;
+    check_submsg_count(&raw_output, submsg_limit)?;
+    check_event_types(&raw_output, max_event_type_length)?;
+    if let Some(remaining_gas) = remaining_gas {
+        cap_submsg_gas_limits(&mut raw_output, remaining_gas);
+    }
+    raw_output =
+        attach_reply_headers_to_submsgs(raw_output, contract_hash, &reply_params, reply_depth)?;
+    sort_deterministic_attributes(&mut raw_output);
     raw_output = encrypt_output(
         raw_output,
         secret_msg,
         contract_addr,
         &reply_params,
         is_ibc_output,
+        pad_output,
+        compression,
     )?;
     raw_output = create_callback_sig_for_submsgs(raw_output, contract_addr)?;
-    raw_output = adapt_output_for_reply(raw_output, &reply_params, secret_msg, sender_addr)?;
+    raw_output = adapt_output_for_reply(
+        raw_output,
+        &reply_params,
+        reply_depth,
+        secret_msg,
+        sender_addr,
+        tx_position,
+    )?;
+
+    let query_nonce = if is_query_output {
+        Some(Binary(secret_msg.nonce.to_vec()))
+    } else {
+        None
+    };
 
-    let output = finalize_raw_output(raw_output, is_query_output, is_ibc_output, true)?;
+    let output = finalize_raw_output(
+        raw_output,
+        is_query_output,
+        is_ibc_output,
+        true,
+        query_nonce,
+    )?;
     Ok(output)
 }
 
+/// The output of a submessage that ran out of gas, standing in for the contract's output so the
+/// caller's reply gets `SubMsgResult::Err` like for any other error.
+///
+/// The error isn't a `StdError`, so a contract can't return the same error itself. A caller sees
+/// it as `{"out_of_gas":{}}` once it's decrypted for its reply.
+pub fn out_of_gas_output() -> Vec<u8> {
+    json!({ "Err": { "out_of_gas": {} } }).to_string().into_bytes()
+}
+
 /// Converts a RawWasmOutput to the final interface that the go code expects, WasmOutput
 /// (serialized to bytes)
 ///
@@ -228,6 +397,7 @@ pub fn post_process_output(
 /// * `is_query_output` - A boolean indicating whether the output is a query output.
 /// * `is_ibc` - A boolean indicating whether the output is related to IBC.
 /// * `is_msg_encrypted` - A boolean indicating whether the message is encrypted.
+/// * `query_nonce` - The nonce to echo in a query output, if any.
 ///
 /// # Returns
 ///
@@ -237,6 +407,7 @@ pub fn finalize_raw_output(
     is_query_output: bool,
     is_ibc: bool,
     is_msg_encrypted: bool,
+    query_nonce: Option<Binary>,
 ) -> Result<Vec<u8>, EnclaveError> {
     let mut wasm_output = WasmOutput::default();
 
@@ -250,6 +421,7 @@ pub fn finalize_raw_output(
                 wasm_output.query = Some(QueryOutput {
                     ok: None,
                     err: Some(err),
+                    nonce: query_nonce,
                 });
             } else {
                 wasm_output.v010 = Some(V010WasmOutput {
@@ -305,6 +477,7 @@ pub fn finalize_raw_output(
             wasm_output.query = Some(QueryOutput {
                 ok: Some(ok),
                 err: None,
+                nonce: query_nonce,
             });
         }
         RawWasmOutput::OkIBCPacketReceive { ok } => {
@@ -331,7 +504,7 @@ pub fn finalize_raw_output(
             "got an error while trying to serialize wasm_output into json bytes {:?}: {}",
             wasm_output, err
         );
-        EnclaveError::FailedToSerialize
+        EnclaveError::Serialization
     })?;
 
     Ok(serialized_output)
@@ -351,6 +524,7 @@ pub fn manipulate_callback_sig_for_plaintext(
         RawWasmOutput::OkV1 { ok, .. } => {
             for sub_msg in &mut ok.messages {
                 if let cw_types_v1::results::CosmosMsg::Wasm(wasm_msg) = &mut sub_msg.msg {
+                    let context = CallbackContext::of_v1_msg(wasm_msg);
                     match wasm_msg {
                         cw_types_v1::results::WasmMsg::Execute {
                             callback_sig,
@@ -366,6 +540,7 @@ pub fn manipulate_callback_sig_for_plaintext(
                         } => {
                             *callback_sig = Some(create_callback_signature(
                                 contract_addr,
+                                context,
                                 &msg.0,
                                 &funds
                                     .iter()
@@ -374,21 +549,26 @@ pub fn manipulate_callback_sig_for_plaintext(
                                         amount: cw_types_v010::math::Uint128(coin.amount.u128()),
                                     })
                                     .collect::<Vec<cw_types_v010::types::Coin>>()[..],
-                            ));
+                            )?);
                         }
                         cw_types_v1::results::WasmMsg::Migrate {
                             callback_sig, msg, ..
                         } => {
                             *callback_sig = Some(create_callback_signature(
                                 contract_addr,
+                                context,
                                 &msg.as_slice().to_vec(),
                                 &[],
-                            ));
+                            )?);
                         }
                         cw_types_v1::results::WasmMsg::ClearAdmin { callback_sig, .. }
                         | cw_types_v1::results::WasmMsg::UpdateAdmin { callback_sig, .. } => {
-                            *callback_sig =
-                                Some(create_callback_signature(contract_addr, &vec![], &[]));
+                            *callback_sig = Some(create_callback_signature(
+                                contract_addr,
+                                context,
+                                &vec![],
+                                &[],
+                            )?);
                         }
                     }
                 }
@@ -397,6 +577,7 @@ pub fn manipulate_callback_sig_for_plaintext(
         RawWasmOutput::OkIBCPacketReceive { ok } => {
             for sub_msg in &mut ok.messages {
                 if let cw_types_v1::results::CosmosMsg::Wasm(wasm_msg) = &mut sub_msg.msg {
+                    let context = CallbackContext::of_v1_msg(wasm_msg);
                     match wasm_msg {
                         cw_types_v1::results::WasmMsg::Execute {
                             callback_sig,
@@ -412,6 +593,7 @@ pub fn manipulate_callback_sig_for_plaintext(
                         } => {
                             *callback_sig = Some(create_callback_signature(
                                 contract_addr,
+                                context,
                                 &msg.as_slice().to_vec(),
                                 &funds
                                     .iter()
@@ -420,21 +602,26 @@ pub fn manipulate_callback_sig_for_plaintext(
                                         amount: cw_types_v010::math::Uint128(coin.amount.u128()),
                                     })
                                     .collect::<Vec<Coin>>()[..],
-                            ));
+                            )?);
                         }
                         cw_types_v1::results::WasmMsg::Migrate {
                             callback_sig, msg, ..
                         } => {
                             *callback_sig = Some(create_callback_signature(
                                 contract_addr,
+                                context,
                                 &msg.as_slice().to_vec(),
                                 &[],
-                            ));
+                            )?);
                         }
                         cw_types_v1::results::WasmMsg::ClearAdmin { callback_sig, .. }
                         | cw_types_v1::results::WasmMsg::UpdateAdmin { callback_sig, .. } => {
-                            *callback_sig =
-                                Some(create_callback_signature(contract_addr, &vec![], &[]));
+                            *callback_sig = Some(create_callback_signature(
+                                contract_addr,
+                                context,
+                                &vec![],
+                                &[],
+                            )?);
                         }
                     }
                 }
@@ -470,16 +657,147 @@ pub fn set_all_logs_to_plaintext(raw_output: &mut RawWasmOutput) {
     }
 }
 
+/// The deepest nesting of JSON arrays and objects allowed in a contract's output. Deserializing
+/// recurses once per level on the enclave's stack, so deeper outputs are rejected before parsing.
+pub const MAX_OUTPUT_JSON_DEPTH: usize = 64;
+
+/// Checks how deeply arrays and objects are nested in `output`, without parsing it. Brackets in
+/// strings are skipped. Malformed JSON is left for the deserializer to reject.
+fn check_output_depth(output: &[u8]) -> Result<(), EnclaveError> {
+    let mut depth: usize = 0;
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for &byte in output {
+        if in_string {
+            match byte {
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                b'"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+
+        match byte {
+            b'"' => in_string = true,
+            b'[' | b'{' => {
+                depth += 1;
+                if depth > MAX_OUTPUT_JSON_DEPTH {
+                    warn!(
+                        "contract output is nested deeper than {} levels",
+                        MAX_OUTPUT_JSON_DEPTH
+                    );
+                    return Err(EnclaveError::OutputTooDeep);
+                }
+            }
+            b']' | b'}' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// A single variant of `RawWasmOutput`, parsed on its own to find where an output goes wrong
+#[derive(Deserialize)]
+struct SingleVariantOutput<T> {
+    #[serde(rename = "Ok", alias = "ok", alias = "Err")]
+    _result: T,
+    #[serde(rename = "internal_reply_enclave_sig")]
+    _internal_reply_enclave_sig: Option<Binary>,
+    #[serde(rename = "internal_msg_id")]
+    _internal_msg_id: Option<Binary>,
+}
+
+/// The variant of `RawWasmOutput` that `output` was most likely meant to be, going by its keys
+fn output_variant_hint(output: &[u8]) -> OutputVariant {
+    let output: serde_json::Map<String, Value> = match serde_json::from_slice(output) {
+        Ok(output) => output,
+        Err(_) => return OutputVariant::Unknown,
+    };
+
+    if output.contains_key("Err") {
+        return OutputVariant::Err;
+    }
+    if output.contains_key("ok") {
+        return OutputVariant::QueryOkV1;
+    }
+    match output.get("Ok") {
+        Some(Value::String(_)) => OutputVariant::QueryOkV010,
+        Some(Value::Object(ok)) if ok.contains_key("log") => OutputVariant::OkV010,
+        Some(Value::Object(ok)) if ok.contains_key("acknowledgement") => {
+            OutputVariant::OkIBCPacketReceive
+        }
+        Some(Value::Object(ok)) if ok.contains_key("version") => OutputVariant::OkIBCOpenChannel,
+        Some(_) => OutputVariant::OkV1,
+        None => OutputVariant::Unknown,
+    }
+}
+
+/// Parses `output` as `variant` alone. The untagged `RawWasmOutput` only reports that no variant
+/// matched, this finds the actual error.
+fn variant_error(output: &[u8], variant: OutputVariant) -> Option<serde_json::Error> {
+    fn parse<T: DeserializeOwned>(output: &[u8]) -> Option<serde_json::Error> {
+        serde_json::from_slice::<SingleVariantOutput<T>>(output).err()
+    }
+
+    match variant {
+        OutputVariant::Err => parse::<Value>(output),
+        OutputVariant::QueryOkV010 | OutputVariant::QueryOkV1 => parse::<String>(output),
+        OutputVariant::OkV010 => parse::<cw_types_v010::types::ContractResult>(output),
+        OutputVariant::OkV1 => parse::<cw_types_v1::results::Response>(output),
+        OutputVariant::OkIBCPacketReceive => parse::<cw_types_v1::ibc::IbcReceiveResponse>(output),
+        OutputVariant::OkIBCOpenChannel => {
+            parse::<cw_types_v1::ibc::IbcChannelOpenResponse>(output)
+        }
+        OutputVariant::Unknown => serde_json::from_slice::<Value>(output).err(),
+    }
+}
+
+/// The byte offset in `input` just past where `err` happened. serde_json only reports a line and
+/// column, and neither when the error has no position.
+fn error_offset(input: &[u8], err: &serde_json::Error) -> u32 {
+    if err.line() == 0 {
+        return 0;
+    }
+
+    let line_start: usize = input
+        .split(|&byte| byte == b'\n')
+        .take(err.line() - 1)
+        .map(|line| line.len() + 1)
+        .sum();
+    (line_start + err.column()) as u32
+}
+
+fn output_deserialization_error(output: &[u8], err: serde_json::Error) -> EnclaveError {
+    let variant_hint = output_variant_hint(output);
+    let err = variant_error(output, variant_hint).unwrap_or(err);
+
+    #[cfg(not(feature = "production"))]
+    let preview = OutputPreview::new(output);
+    #[cfg(feature = "production")]
+    let preview = OutputPreview::default();
+
+    EnclaveError::OutputDeserialization {
+        variant_hint,
+        offset: error_offset(output, &err),
+        preview,
+    }
+}
+
 fn deserialize_output(output: Vec<u8>) -> Result<RawWasmOutput, EnclaveError> {
     trace!(
         "output as received from contract: {:?}",
         String::from_utf8_lossy(&output)
     );
 
+    check_output_depth(&output)?;
+
     let output: RawWasmOutput = serde_json::from_slice(&output).map_err(|err| {
         warn!("got an error while trying to deserialize output bytes from json");
         debug!("output: {:?} error: {:?}", output, err);
-        EnclaveError::FailedToDeserialize
+        output_deserialization_error(&output, err)
     })?;
 
     trace!("Output after deserialization: {:?}", output);
@@ -500,15 +818,14 @@ fn deserialize_output(output: Vec<u8>) -> Result<RawWasmOutput, EnclaveError> {
 /// * `reply_params` - An optional vector describing the caller chain. Needed because the
 ///         immediate caller to this contract will be appended to every field.
 fn encrypt_output(
-    mut output: RawWasmOutput,
+    output: RawWasmOutput,
     secret_msg: &SecretMessage,
     contract_addr: &CanonicalAddr,
     reply_params: &Option<Vec<ReplyParams>>,
     is_ibc_output: bool,
+    pad_output: bool,
+    compression: &OutputCompression,
 ) -> Result<RawWasmOutput, EnclaveError> {
-    // The output we receive from a contract could be a reply to a caller contract (via the "reply" endpoint).
-    // Therefore if reply_recipient_contract_hash is "Some", we append it to any encrypted data besides submessages that are irrelevant for replies.
-    // More info in: https://github.com/CosmWasm/cosmwasm/blob/v1.0.0/packages/std/src/results/submessages.rs#L192-L198
     let encryption_key = calc_encryption_key(&secret_msg.nonce, &secret_msg.user_public_key);
     trace!(
         "message nonce and public key for encryption: {:?} {:?}",
@@ -516,13 +833,47 @@ fn encrypt_output(
         secret_msg.user_public_key
     );
 
+    encrypt_output_with_key(
+        output,
+        secret_msg,
+        &encryption_key,
+        contract_addr,
+        reply_params,
+        is_ibc_output,
+        pad_output,
+        compression,
+    )
+}
+
+/// Like `encrypt_output`, with the encryption key given instead of derived from the consensus io
+/// key, which tests don't have
+fn encrypt_output_with_key(
+    mut output: RawWasmOutput,
+    secret_msg: &SecretMessage,
+    encryption_key: &AESKey,
+    contract_addr: &CanonicalAddr,
+    reply_params: &Option<Vec<ReplyParams>>,
+    is_ibc_output: bool,
+    pad_output: bool,
+    compression: &OutputCompression,
+) -> Result<RawWasmOutput, EnclaveError> {
+    // The output we receive from a contract could be a reply to a caller contract (via the "reply" endpoint).
+    // Therefore if reply_recipient_contract_hash is "Some", we append it to any encrypted data besides submessages that are irrelevant for replies.
+    // More info in: https://github.com/CosmWasm/cosmwasm/blob/v1.0.0/packages/std/src/results/submessages.rs#L192-L198
     match &mut output {
         RawWasmOutput::Err { err, .. } => {
-            let encrypted_err = encrypt_serializable(&encryption_key, err, reply_params, false)?;
+            let encrypted_err =
+                encrypt_serializable(encryption_key, err, reply_params, false, pad_output)?;
             *err = format_generic_error_message(Value::String(encrypted_err));
         }
         RawWasmOutput::QueryOkV010 { ok } | RawWasmOutput::QueryOkV1 { ok } => {
-            *ok = encrypt_serializable(&encryption_key, ok, reply_params, false)?;
+            *ok = encrypt_serializable_result(
+                encryption_key,
+                ok,
+                reply_params,
+                pad_output,
+                compression,
+            )?;
         }
         RawWasmOutput::OkV010 { ok, .. } => {
             for msg in &mut ok.messages {
@@ -538,18 +889,15 @@ fn encrypt_output(
             }
 
             // v0.10: The logs that will be emitted as part of a "wasm" event.
-            for log in ok.log.iter_mut().filter(|log| log.encrypted) {
-                log.key = encrypt_preserialized_string(&encryption_key, &log.key, &None, false)?;
-                log.value =
-                    encrypt_preserialized_string(&encryption_key, &log.value, &None, false)?;
-            }
+            encrypt_attributes_batch(encryption_key, &mut ok.log)?;
 
             if let Some(data) = &mut ok.data {
-                *data = Binary::from_base64(&encrypt_serializable(
-                    &encryption_key,
+                *data = Binary::from_base64(&encrypt_serializable_result(
+                    encryption_key,
                     data,
                     reply_params,
-                    false,
+                    pad_output,
+                    compression,
                 )?)?;
             }
         }
@@ -559,6 +907,7 @@ fn encrypt_output(
                 &mut ok.attributes,
                 &mut ok.events,
                 secret_msg,
+                encryption_key,
             )?;
             if let Some(data) = &mut ok.data {
                 if is_ibc_output {
@@ -566,12 +915,15 @@ fn encrypt_output(
                     return Err(EnclaveError::InternalError);
                 }
 
-                *data = Binary::from_base64(&encrypt_serializable(
-                    &encryption_key,
-                    data,
-                    reply_params,
-                    false,
-                )?)?;
+                if !ok.plaintext_data {
+                    *data = Binary::from_base64(&encrypt_serializable_result(
+                        encryption_key,
+                        data,
+                        reply_params,
+                        pad_output,
+                        compression,
+                    )?)?;
+                }
             }
         }
         RawWasmOutput::OkIBCPacketReceive { ok } => {
@@ -580,14 +932,18 @@ fn encrypt_output(
                 &mut ok.attributes,
                 &mut ok.events,
                 secret_msg,
+                encryption_key,
             )?;
 
-            ok.acknowledgement = Binary::from_base64(&encrypt_serializable(
-                &encryption_key,
-                &ok.acknowledgement,
-                reply_params,
-                false,
-            )?)?;
+            if !take_plaintext_ack_prefix(&mut ok.acknowledgement) {
+                ok.acknowledgement = Binary::from_base64(&encrypt_serializable(
+                    encryption_key,
+                    &ok.acknowledgement,
+                    reply_params,
+                    false,
+                    pad_output,
+                )?)?;
+            }
         }
         RawWasmOutput::OkIBCOpenChannel { ok: _ } => {}
     };
@@ -595,109 +951,525 @@ fn encrypt_output(
     Ok(output)
 }
 
+/// An acknowledgement that starts with this prefix is emitted in plaintext, without the prefix.
+/// The counterparty chain can't decrypt acknowledgements, so IBC apps whose acknowledgements it
+/// has to read (e.g. ICS-20 transfer wrappers) mark them with it.
+pub const PLAINTEXT_ACK_PREFIX: &[u8] = b"secret-plaintext-ack:";
+
+/// Removes `PLAINTEXT_ACK_PREFIX` from `acknowledgement` and returns whether it was there
+fn take_plaintext_ack_prefix(acknowledgement: &mut Binary) -> bool {
+    if !acknowledgement.0.starts_with(PLAINTEXT_ACK_PREFIX) {
+        return false;
+    }
+
+    acknowledgement.0.drain(..PLAINTEXT_ACK_PREFIX.len());
+    true
+}
+
+/// Removes `PLAINTEXT_ACK_PREFIX` from the acknowledgement of an output that isn't encrypted, so
+/// the counterparty gets the same acknowledgement either way
+pub fn strip_plaintext_ack_prefix(raw_output: &mut RawWasmOutput) {
+    if let RawWasmOutput::OkIBCPacketReceive { ok } = raw_output {
+        take_plaintext_ack_prefix(&mut ok.acknowledgement);
+    }
+}
+
 fn encrypt_v1_non_result_fields<T: Clone + fmt::Debug + PartialEq>(
     messages: &mut [SubMsg<T>],
-    attributes: &mut [LogAttribute],
+    attributes: &mut Vec<LogAttribute>,
     events: &mut [Event],
     secret_msg: &SecretMessage,
+    encryption_key: &AESKey,
 ) -> Result<(), EnclaveError> {
-    let encryption_key = calc_encryption_key(&secret_msg.nonce, &secret_msg.user_public_key);
-
     for sub_msg in messages.iter_mut() {
         encrypt_wasm_submsg(sub_msg, secret_msg)?;
     }
 
     // v1: The attributes that will be emitted as part of a "wasm" event.
-    for attr in attributes.iter_mut().filter(|attr| attr.encrypted) {
-        attr.key = encrypt_preserialized_string(&encryption_key, &attr.key, &None, false)?;
-        attr.value = encrypt_preserialized_string(&encryption_key, &attr.value, &None, false)?;
-    }
+    encrypt_v1_attributes(encryption_key, attributes)?;
 
     // v1: Extra, custom events separate from the main wasm one. These will have "wasm-"" prepended to the type.
     for event in events.iter_mut() {
-        for attr in event.attributes.iter_mut().filter(|attr| attr.encrypted) {
-            attr.key = encrypt_preserialized_string(&encryption_key, &attr.key, &None, false)?;
-            attr.value = encrypt_preserialized_string(&encryption_key, &attr.value, &None, false)?;
-        }
+        encrypt_v1_attributes(encryption_key, &mut event.attributes)?;
     }
 
     Ok(())
 }
 
-fn encrypt_wasm_submsg<T: Clone + fmt::Debug + PartialEq>(
-    sub_msg: &mut SubMsg<T>,
-    secret_msg: &SecretMessage,
-) -> Result<(), EnclaveError> {
-    // Messages other than Wasm (Bank, Staking, etc.) are kept plaintext
-    if let cw_types_v1::results::CosmosMsg::Wasm(wasm_msg) = &mut sub_msg.msg {
-        match wasm_msg {
-            cw_types_v1::results::WasmMsg::Instantiate { msg, .. }
-            | cw_types_v1::results::WasmMsg::Execute { msg, .. }
-            | cw_types_v1::results::WasmMsg::Migrate { msg, .. } => {
-                let mut msg_to_encrypt = SecretMessage {
-                    msg: msg.as_slice().to_vec(),
-                    nonce: secret_msg.nonce,
-                    user_public_key: secret_msg.user_public_key,
-                };
-                msg_to_encrypt.encrypt_in_place()?;
-                *msg = Binary::from(msg_to_encrypt.to_vec().as_slice());
-            }
-            cw_types_v1::results::WasmMsg::ClearAdmin { .. }
-            | cw_types_v1::results::WasmMsg::UpdateAdmin { .. } => {}
-        }
+/// Sorts the attributes of a v1 response, and of each of its events, by key and then by value if
+/// the contract set `deterministic_attributes`. The sort is stable, so identical attributes keep
+/// their order. Otherwise attributes are emitted in the order the contract added them.
+pub fn sort_deterministic_attributes(raw_output: &mut RawWasmOutput) {
+    let ok = match raw_output {
+        RawWasmOutput::OkV1 { ok, .. } if ok.deterministic_attributes => ok,
+        _ => return,
     };
 
-    Ok(())
+    let by_key_and_value =
+        |a: &LogAttribute, b: &LogAttribute| (&a.key, &a.value).cmp(&(&b.key, &b.value));
+    ok.attributes.sort_by(by_key_and_value);
+    for event in ok.events.iter_mut() {
+        event.attributes.sort_by(by_key_and_value);
+    }
 }
 
-/// Attaches reply headers to submessages of a Contract output.
-/// We cannot use the normal field msg_id of the submessage since it is private data that goes
-/// out of the contract, therefore we encrypt it and put it inside the data of the submessage itself,
-/// along with all other msg_ids in the call chain. This ensures only the calling contract knows the
-/// reply's msg_id.
-///
-/// # Arguments
-///
-/// * `output` - A `RawWasmOutput` that represents the output of a Contract.
-/// * `reply_params` - An optional vector describing the caller chain.
-/// * `contract_hash` - The hash of the code of the contract whose output we are processing.
-fn attach_reply_headers_to_submsgs(
-    mut output: RawWasmOutput,
-    contract_hash: &str,
-    reply_params: &Option<Vec<ReplyParams>>,
-) -> Result<RawWasmOutput, EnclaveError> {
-    let sub_msgs = match &mut output {
-        RawWasmOutput::OkV1 { ok, .. } => &mut ok.messages,
-        RawWasmOutput::OkIBCPacketReceive { ok } => &mut ok.messages,
-        _ => return Ok(output),
+/// Checks that the custom events of a contract response have types of at most
+/// `max_event_type_length` bytes, before "wasm-" is prepended to them, if the chain sets a limit.
+/// Event types end up in the transaction's events and their index, so this bounds the output size.
+/// Plaintext outputs are checked too, as they're emitted the same way.
+pub fn check_event_types(
+    output: &RawWasmOutput,
+    max_event_type_length: u32,
+) -> Result<(), EnclaveError> {
+    if max_event_type_length == 0 {
+        return Ok(());
+    }
+
+    let events = match output {
+        RawWasmOutput::OkV1 { ok, .. } => &ok.events,
+        RawWasmOutput::OkIBCPacketReceive { ok } => &ok.events,
+        _ => return Ok(()),
     };
 
-    for sub_msg in sub_msgs {
-        if let cw_types_v1::results::CosmosMsg::Wasm(wasm_msg) = &mut sub_msg.msg {
-            attach_reply_headers_to_v1_wasm_msg(
-                wasm_msg,
-                &sub_msg.reply_on,
-                sub_msg.id,
-                contract_hash,
-                reply_params,
-            )?;
+    let max_event_type_length = max_event_type_length as usize;
+    if let Some(event) = events.iter().find(|e| e.ty.len() > max_event_type_length) {
+        warn!(
+            "contract returned an event type of {} bytes, the limit is {}",
+            event.ty.len(),
+            max_event_type_length
+        );
+        return Err(EnclaveError::EventTypeTooLong);
+    }
 
-            // The ID can be extracted from the encrypted wasm msg
-            // We don't encrypt it here to remain with the same type (u64)
-            sub_msg.id = 0;
+    Ok(())
+}
+
+/// Encrypts the attributes that are marked as encrypted. Attributes that are also marked with
+/// `plaintext_copy` are emitted twice: first in plaintext, then encrypted.
+fn encrypt_v1_attributes(
+    encryption_key: &AESKey,
+    attributes: &mut Vec<LogAttribute>,
+) -> Result<(), EnclaveError> {
+    let copies = attributes
+        .iter()
+        .filter(|attr| attr.encrypted && attr.plaintext_copy)
+        .count();
+    let mut processed = Vec::with_capacity(attributes.len() + copies);
+
+    for attr in attributes.drain(..) {
+        if attr.encrypted && attr.plaintext_copy {
+            processed.push(LogAttribute {
+                encrypted: false,
+                plaintext_copy: false,
+                ..attr.clone()
+            });
         }
+        processed.push(attr);
+    }
 
-        sub_msg.was_msg_encrypted = true;
+    encrypt_attributes_batch(encryption_key, &mut processed)?;
+
+    *attributes = processed;
+    Ok(())
+}
+
+/// Encrypts the keys and values of the attributes that are marked as encrypted, like
+/// `encrypt_preserialized_string` would without reply params, but sets the cipher up once for
+/// all of them and encodes each ciphertext straight into a buffer of its final size.
+fn encrypt_attributes_batch(key: &AESKey, attrs: &mut [LogAttribute]) -> Result<(), EnclaveError> {
+    let mut encryptor = SivBatchEncryptor::new(key);
+    let mut encrypt = |plaintext: &[u8]| -> Result<String, EnclaveError> {
+        let encrypted_data = encryptor.encrypt(plaintext, None).map_err(|err| {
+            debug!(
+                "got an error while trying to encrypt output error {:?}: {}",
+                err, err
+            );
+            EnclaveError::Aead
+        })?;
+
+        let mut encoded = String::with_capacity((encrypted_data.len() + 2) / 3 * 4);
+        base64::encode_config_buf(&encrypted_data, base64::STANDARD, &mut encoded);
+        Ok(encoded)
+    };
+
+    for attr in attrs.iter_mut().filter(|attr| attr.encrypted) {
+        attr.key = encrypt(attr.key.as_bytes())?;
+        attr.value = encrypt(attr.value.as_bytes())?;
     }
 
-    Ok(output)
+    Ok(())
 }
 
-/// Attaches callback signatures to every submessage of a Contract output.
-/// These are needed to prove to the callee contract that the execution message it receives
-/// was authored by this contract, inside the enclave.
+/// Reverses `encrypt_output` for the sender of a message, so tests and off-chain tooling can check
+/// that an encrypted output decrypts back to what the contract returned. `encrypted` is the JSON
+/// of the encrypted `RawWasmOutput`.
 ///
-/// # Arguments
+/// Submessages are left as they are, since they're encrypted for the contracts they're sent to.
+/// Whether an attribute was encrypted isn't part of the output, so attributes that don't decrypt
+/// are taken as plaintext, and a plaintext attribute followed by its encrypted copy is merged
+/// back into one attribute with `plaintext_copy`. Likewise, an acknowledgement that doesn't
+/// decrypt is taken as one marked with `PLAINTEXT_ACK_PREFIX`, which is gone from the output.
+/// `compression` is the chain's param when the output was made.
+pub fn decrypt_output(
+    encrypted: &[u8],
+    nonce: IoNonce,
+    user_public_key: Ed25519PublicKey,
+    compression: &OutputCompression,
+) -> Result<RawWasmOutput, EnclaveError> {
+    let encryption_key = calc_encryption_key(&nonce, &user_public_key);
+
+    decrypt_output_with_key(encrypted, &encryption_key, compression)
+}
+
+/// Like `decrypt_output`, with the encryption key given, see `encrypt_output_with_key`
+fn decrypt_output_with_key(
+    encrypted: &[u8],
+    encryption_key: &AESKey,
+    compression: &OutputCompression,
+) -> Result<RawWasmOutput, EnclaveError> {
+    let mut output = deserialize_output(encrypted.to_vec())?;
+
+    match &mut output {
+        RawWasmOutput::Err {
+            err,
+            internal_msg_id,
+            ..
+        } => {
+            let encrypted_err = err["generic_err"]["msg"].as_str().ok_or_else(|| {
+                warn!("encrypted error output isn't a generic error");
+                EnclaveError::FailedToDeserialize
+            })?;
+            let plaintext = decrypt_b64(encryption_key, encrypted_err)?;
+            *err = deserialize_decrypted(without_reply_header(plaintext, internal_msg_id)?)?;
+        }
+        RawWasmOutput::QueryOkV010 { ok } | RawWasmOutput::QueryOkV1 { ok } => {
+            let plaintext = decompress_output(decrypt_b64(encryption_key, ok)?, compression)?;
+            *ok = deserialize_decrypted(plaintext)?;
+        }
+        RawWasmOutput::OkV010 {
+            ok,
+            internal_msg_id,
+            ..
+        } => {
+            decrypt_attributes(encryption_key, &mut ok.log)?;
+
+            if let Some(data) = &mut ok.data {
+                *data = decrypt_result(encryption_key, data, internal_msg_id, compression)?;
+            }
+        }
+        RawWasmOutput::OkV1 {
+            ok,
+            internal_msg_id,
+            ..
+        } => {
+            decrypt_v1_attributes(encryption_key, &mut ok.attributes)?;
+            for event in &mut ok.events {
+                decrypt_v1_attributes(encryption_key, &mut event.attributes)?;
+            }
+
+            if let Some(data) = &mut ok.data {
+                if !ok.plaintext_data {
+                    *data = decrypt_result(encryption_key, data, internal_msg_id, compression)?;
+                }
+            }
+        }
+        RawWasmOutput::OkIBCPacketReceive { ok } => {
+            decrypt_v1_attributes(encryption_key, &mut ok.attributes)?;
+            for event in &mut ok.events {
+                decrypt_v1_attributes(encryption_key, &mut event.attributes)?;
+            }
+
+            // an acknowledgement that doesn't decrypt was marked as plaintext
+            if let Ok(plaintext) = decrypt_ciphertext(encryption_key, ok.acknowledgement.as_slice())
+            {
+                ok.acknowledgement = deserialize_decrypted(plaintext)?;
+            }
+        }
+        RawWasmOutput::OkIBCOpenChannel { ok: _ } => {}
+    };
+
+    Ok(output)
+}
+
+/// Reverses `encrypt_serializable_result`
+fn decrypt_result(
+    key: &AESKey,
+    encrypted: &Binary,
+    internal_msg_id: &Option<Binary>,
+    compression: &OutputCompression,
+) -> Result<Binary, EnclaveError> {
+    let plaintext = decompress_output(decrypt_ciphertext(key, encrypted.as_slice())?, compression)?;
+
+    deserialize_decrypted(without_reply_header(plaintext, internal_msg_id)?)
+}
+
+/// Removes the code hash that `with_reply_params` prepends to the results of an output that goes
+/// back to its caller's reply, which are the outputs with an `internal_msg_id`
+fn without_reply_header(
+    mut plaintext: Vec<u8>,
+    internal_msg_id: &Option<Binary>,
+) -> Result<Vec<u8>, EnclaveError> {
+    if internal_msg_id.is_none() {
+        return Ok(plaintext);
+    }
+
+    if plaintext.len() < HEX_ENCODED_HASH_SIZE {
+        warn!("decrypted output is too short to have a reply header");
+        return Err(EnclaveError::FailedToDeserialize);
+    }
+
+    Ok(plaintext.split_off(HEX_ENCODED_HASH_SIZE))
+}
+
+/// Parses a decrypted value. `serialize_for_encryption` strips the quotes off strings, so a
+/// value that doesn't parse as is is parsed as a string.
+fn deserialize_decrypted<T: DeserializeOwned>(plaintext: Vec<u8>) -> Result<T, EnclaveError> {
+    serde_json::from_slice(&plaintext)
+        .or_else(|_| serde_json::from_slice(&[&b"\""[..], &plaintext, b"\""].concat()))
+        .map_err(|err| {
+            warn!("got an error while trying to deserialize a decrypted output");
+            debug!("output: {:?} error: {:?}", plaintext, err);
+            EnclaveError::FailedToDeserialize
+        })
+}
+
+fn decrypt_ciphertext(key: &AESKey, ciphertext: &[u8]) -> Result<Vec<u8>, EnclaveError> {
+    key.decrypt_siv(ciphertext, None).map_err(|err| {
+        debug!("got an error while trying to decrypt output {:?}", err);
+        EnclaveError::DecryptionError
+    })
+}
+
+fn decrypt_b64(key: &AESKey, encrypted: &str) -> Result<Vec<u8>, EnclaveError> {
+    let ciphertext = base64::decode(encrypted).map_err(|err| {
+        debug!(
+            "got an error while trying to decode encrypted output {:?}",
+            err
+        );
+        EnclaveError::DecryptionError
+    })?;
+
+    unpad(decrypt_ciphertext(key, &ciphertext)?)
+}
+
+/// Reverses `encrypt_attributes_batch`, marking the attributes that decrypt as encrypted
+fn decrypt_attributes(key: &AESKey, attrs: &mut [LogAttribute]) -> Result<(), EnclaveError> {
+    let utf8 = |plaintext: Vec<u8>| {
+        String::from_utf8(plaintext).map_err(|err| {
+            warn!("decrypted attribute isn't valid utf-8: {:?}", err);
+            EnclaveError::FailedToDeserialize
+        })
+    };
+
+    for attr in attrs.iter_mut() {
+        match decrypt_b64(key, &attr.key) {
+            Ok(decrypted_key) => {
+                attr.key = utf8(decrypted_key)?;
+                attr.value = utf8(decrypt_b64(key, &attr.value)?)?;
+                attr.encrypted = true;
+            }
+            Err(_) => attr.encrypted = false,
+        }
+        attr.plaintext_copy = false;
+    }
+
+    Ok(())
+}
+
+/// Reverses `encrypt_v1_attributes`, merging plaintext copies back into their encrypted attributes
+fn decrypt_v1_attributes(
+    key: &AESKey,
+    attributes: &mut Vec<LogAttribute>,
+) -> Result<(), EnclaveError> {
+    decrypt_attributes(key, attributes)?;
+
+    let mut merged: Vec<LogAttribute> = Vec::with_capacity(attributes.len());
+    for attr in attributes.drain(..) {
+        match merged.last_mut() {
+            Some(copy)
+                if attr.encrypted
+                    && !copy.encrypted
+                    && copy.key == attr.key
+                    && copy.value == attr.value =>
+            {
+                *copy = LogAttribute {
+                    plaintext_copy: true,
+                    ..attr
+                };
+            }
+            _ => merged.push(attr),
+        }
+    }
+
+    *attributes = merged;
+    Ok(())
+}
+
+fn encrypt_wasm_submsg<T: Clone + fmt::Debug + PartialEq>(
+    sub_msg: &mut SubMsg<T>,
+    secret_msg: &SecretMessage,
+) -> Result<(), EnclaveError> {
+    // Messages other than Wasm (Bank, Staking, etc.) are kept plaintext
+    if let cw_types_v1::results::CosmosMsg::Wasm(wasm_msg) = &mut sub_msg.msg {
+        match wasm_msg {
+            cw_types_v1::results::WasmMsg::Instantiate { msg, .. }
+            | cw_types_v1::results::WasmMsg::Execute { msg, .. }
+            | cw_types_v1::results::WasmMsg::Migrate { msg, .. } => {
+                // By now the msg is prefixed with the recipient's code hash, so it can only be
+                // empty if the code hash is empty too. The recipient couldn't parse that (see
+                // SecretMessage::from_slice), so fail here with a clear error instead.
+                if msg.as_slice().is_empty() {
+                    warn!("contract returned a wasm submessage with an empty msg and code hash");
+                    return Err(EnclaveError::EncryptionError);
+                }
+
+                let mut msg_to_encrypt = SecretMessage {
+                    msg: msg.as_slice().to_vec(),
+                    nonce: secret_msg.nonce,
+                    user_public_key: secret_msg.user_public_key,
+                };
+                msg_to_encrypt.encrypt_in_place()?;
+                *msg = Binary::from(msg_to_encrypt.to_vec().as_slice());
+            }
+            cw_types_v1::results::WasmMsg::ClearAdmin { .. }
+            | cw_types_v1::results::WasmMsg::UpdateAdmin { .. } => {}
+        }
+    };
+
+    Ok(())
+}
+
+/// Checks that a contract response doesn't contain more submessages than the transaction has left
+/// under the chain's limit, if it sets one. Contracts can check how many they have left with the
+/// `remaining_submsg_budget` host function.
+fn check_submsg_count(
+    output: &RawWasmOutput,
+    submsg_limit: Option<&SubmsgLimit>,
+) -> Result<(), EnclaveError> {
+    let submsg_limit = match submsg_limit {
+        Some(submsg_limit) => submsg_limit,
+        None => return Ok(()),
+    };
+
+    let submsg_count = match output {
+        RawWasmOutput::OkV010 { ok, .. } => ok.messages.len(),
+        RawWasmOutput::OkV1 { ok, .. } => ok.messages.len(),
+        RawWasmOutput::OkIBCPacketReceive { ok } => ok.messages.len(),
+        _ => return Ok(()),
+    };
+
+    if submsg_count > submsg_limit.remaining() as usize {
+        warn!(
+            "contract returned {} submessages, the transaction has {} of {} left",
+            submsg_count,
+            submsg_limit.remaining(),
+            submsg_limit.max
+        );
+        return Err(EnclaveError::TooManySubmessages);
+    }
+
+    Ok(())
+}
+
+/// Caps the gas limit of every submessage to the gas the contract has left (in WASM gas), since a
+/// submessage can't use more than its caller had in the first place. A capped limit is what the
+/// callee enforces, see `SUBMSG_GAS_LIMIT_MAGIC_BYTES`.
+pub fn cap_submsg_gas_limits(output: &mut RawWasmOutput, remaining_gas: u64) {
+    let sub_msgs = match output {
+        RawWasmOutput::OkV1 { ok, .. } => &mut ok.messages,
+        RawWasmOutput::OkIBCPacketReceive { ok } => &mut ok.messages,
+        _ => return,
+    };
+
+    let max_gas_limit = remaining_gas / GAS_MULTIPLIER;
+    for sub_msg in sub_msgs {
+        if let Some(gas_limit) = sub_msg.gas_limit.as_mut() {
+            if *gas_limit > max_gas_limit {
+                debug!(
+                    "capping the gas limit of submessage {} from {} to {}",
+                    sub_msg.id, gas_limit, max_gas_limit
+                );
+                *gas_limit = max_gas_limit;
+            }
+        }
+    }
+}
+
+/// The maximum reply depth of a submessage's callee, i.e. how many contracts up the call stack can
+/// be waiting for a reply at once. The depth travels with the messages of a transaction and their
+/// replies, so this bounds recursive reply chains (like a contract that keeps calling itself with
+/// `reply_on` set) per transaction. It counts every contract that waits, whether or not its reply
+/// header is propagated (see `ReplyPropagation`), so dropping the chain doesn't reset it. It's
+/// separate from the query depth limit.
+pub const MAX_REPLY_DEPTH: usize = 16;
+
+/// What carries `reply_depth` in a submessage or a reply id, nothing for 0 so that messages sent
+/// outside of reply chains are the same as before the depth was carried
+fn reply_depth_header(reply_depth: u32) -> Vec<u8> {
+    if reply_depth == 0 {
+        return vec![];
+    }
+
+    [
+        cw_types_v1::results::REPLY_DEPTH_MAGIC_BYTES,
+        &reply_depth.to_be_bytes(),
+    ]
+    .concat()
+}
+
+/// Attaches reply headers to submessages of a Contract output.
+/// We cannot use the normal field msg_id of the submessage since it is private data that goes
+/// out of the contract, therefore we encrypt it and put it inside the data of the submessage itself,
+/// along with all other msg_ids in the call chain. This ensures only the calling contract knows the
+/// reply's msg_id.
+///
+/// # Arguments
+///
+/// * `output` - A `RawWasmOutput` that represents the output of a Contract.
+/// * `reply_params` - An optional vector describing the caller chain.
+/// * `contract_hash` - The hash of the code of the contract whose output we are processing.
+/// * `reply_depth` - How many contracts up the call stack wait for a reply, see `MAX_REPLY_DEPTH`.
+fn attach_reply_headers_to_submsgs(
+    mut output: RawWasmOutput,
+    contract_hash: &str,
+    reply_params: &Option<Vec<ReplyParams>>,
+    reply_depth: u32,
+) -> Result<RawWasmOutput, EnclaveError> {
+    let sub_msgs = match &mut output {
+        RawWasmOutput::OkV1 { ok, .. } => &mut ok.messages,
+        RawWasmOutput::OkIBCPacketReceive { ok } => &mut ok.messages,
+        _ => return Ok(output),
+    };
+
+    for sub_msg in sub_msgs {
+        if let cw_types_v1::results::CosmosMsg::Wasm(wasm_msg) = &mut sub_msg.msg {
+            attach_reply_headers_to_v1_wasm_msg(
+                wasm_msg,
+                &sub_msg.reply_on,
+                sub_msg.id,
+                sub_msg.gas_limit,
+                contract_hash,
+                reply_params,
+                reply_depth,
+                sub_msg.reply_propagation,
+            )?;
+
+            // The ID can be extracted from the encrypted wasm msg
+            // We don't encrypt it here to remain with the same type (u64)
+            sub_msg.id = 0;
+        }
+
+        sub_msg.was_msg_encrypted = true;
+    }
+
+    Ok(output)
+}
+
+/// Attaches callback signatures to every submessage of a Contract output.
+/// These are needed to prove to the callee contract that the execution message it receives
+/// was authored by this contract, inside the enclave.
+///
+/// # Arguments
 ///
 /// * `output` - A `RawWasmOutput` that represents the output of a Contract.
 /// * `contract_addr` - The address of the contract whose output we are processing.
@@ -713,6 +1485,7 @@ fn create_callback_sig_for_submsgs(
 
     for sub_msg in sub_msgs {
         if let cw_types_v1::results::CosmosMsg::Wasm(wasm_msg) = &mut sub_msg.msg {
+            let context = CallbackContext::of_v1_msg(wasm_msg);
             match wasm_msg {
                 cw_types_v1::results::WasmMsg::Execute {
                     msg,
@@ -728,6 +1501,7 @@ fn create_callback_sig_for_submsgs(
                 } => {
                     *callback_sig = Some(create_callback_signature(
                         contract_addr,
+                        context,
                         &SecretMessage::from_slice(msg.as_slice())?.msg,
                         &funds
                             .iter()
@@ -736,20 +1510,26 @@ fn create_callback_sig_for_submsgs(
                                 amount: cw_types_v010::math::Uint128(coin.amount.u128()),
                             })
                             .collect::<Vec<Coin>>()[..],
-                    ));
+                    )?);
                 }
                 cw_types_v1::results::WasmMsg::Migrate {
                     msg, callback_sig, ..
                 } => {
                     *callback_sig = Some(create_callback_signature(
                         contract_addr,
+                        context,
                         &SecretMessage::from_slice(msg.as_slice())?.msg,
                         &[],
-                    ));
+                    )?);
                 }
                 cw_types_v1::results::WasmMsg::ClearAdmin { callback_sig, .. }
                 | cw_types_v1::results::WasmMsg::UpdateAdmin { callback_sig, .. } => {
-                    *callback_sig = Some(create_callback_signature(contract_addr, &vec![], &[]));
+                    *callback_sig = Some(create_callback_signature(
+                        contract_addr,
+                        context,
+                        &vec![],
+                        &[],
+                    )?);
                 }
             }
         }
@@ -774,49 +1554,39 @@ fn create_callback_sig_for_submsgs(
 fn adapt_output_for_reply(
     mut output: RawWasmOutput,
     reply_params: &Option<Vec<ReplyParams>>,
+    reply_depth: u32,
     secret_msg: &SecretMessage,
     sender_addr: &CanonicalAddr,
+    tx_position: TxPosition,
 ) -> Result<RawWasmOutput, EnclaveError> {
-    if reply_params.is_none() {
+    let (caller, ancestors) = match split_reply_chain(reply_params) {
+        Some(frames) => frames,
         // This message was not called from another contract,
         // no need to adapt output as a reply
+        None => return Ok(output),
+    };
+
+    let (output_result, should_append_reply_params) = match reply_result_for_output(&output) {
+        Some(reply_result) => reply_result,
+        None => return Ok(output),
+    };
+
+    // The caller only gets a reply for the results it asked for, e.g. a failure of a submessage
+    // that's only replied to on success is returned as the caller's own error instead. Don't
+    // build reply material that won't be used.
+    if !caller
+        .reply_on
+        .replies_to(matches!(output_result, SubMsgResult::Ok(_)))
+    {
+        trace!(
+            "caller isn't replied to on this result (reply on {:?})",
+            caller.reply_on
+        );
         return Ok(output);
     }
 
     let encryption_key = calc_encryption_key(&secret_msg.nonce, &secret_msg.user_public_key);
-
-    let output_result;
-    let should_append_reply_params;
-
-    match &output {
-        RawWasmOutput::Err { err, .. } => {
-            let mut encrypted_error_message = err["generic_err"]["msg"].to_string();
-
-            // remove surrounding quotes
-            encrypted_error_message.pop();
-            encrypted_error_message.remove(0);
-
-            output_result = SubMsgResult::Err(encrypted_error_message);
-            should_append_reply_params = true;
-        }
-        RawWasmOutput::OkV010 { ok, .. } => {
-            output_result = SubMsgResult::Ok(SubMsgResponse {
-                events: vec![],
-                data: ok.data.clone(),
-            });
-
-            should_append_reply_params = false;
-        }
-        RawWasmOutput::OkV1 { ok, .. } => {
-            output_result = SubMsgResult::Ok(SubMsgResponse {
-                events: vec![],
-                data: ok.data.clone(),
-            });
-
-            should_append_reply_params = true;
-        }
-        _ => return Ok(output),
-    }
+    let is_data_plaintext = matches!(&output, RawWasmOutput::OkV1 { ok, .. } if ok.plaintext_data);
 
     match &mut output {
         RawWasmOutput::Err {
@@ -836,10 +1606,15 @@ fn adapt_output_for_reply(
         } => {
             let (msg_id, callback_sig) = get_reply_info_for_output(
                 output_result,
-                reply_params,
+                caller,
+                ancestors,
+                // the caller waited for this contract, so it's one level up
+                reply_depth.saturating_sub(1),
                 encryption_key,
                 sender_addr,
                 should_append_reply_params,
+                is_data_plaintext,
+                tx_position,
             )?;
 
             *internal_msg_id = Some(msg_id);
@@ -851,26 +1626,84 @@ fn adapt_output_for_reply(
     Ok(output)
 }
 
+/// The `SubMsgResult` the caller will get as a reply for `output`, and whether the whole chain of
+/// reply params should be appended to the reply id. `None` for outputs that can't be replied to.
+///
+/// Error replies never carry data. In CosmWasm v1 both the contract's `ContractResult::Err` and
+/// the reply's `SubMsgResult::Err` are just a string, and the Go side builds the reply the same
+/// way, so it has to match what the callback signature is created over. Anything a contract puts
+/// next to its error, like a `data` field, is dropped here. A contract that wants to send data
+/// with a failure should return `Ok` with the data and its own error indication.
+fn reply_result_for_output(output: &RawWasmOutput) -> Option<(SubMsgResult, bool)> {
+    match output {
+        RawWasmOutput::Err { err, .. } => {
+            let mut encrypted_error_message = err["generic_err"]["msg"].to_string();
+
+            // remove surrounding quotes
+            encrypted_error_message.pop();
+            encrypted_error_message.remove(0);
+
+            Some((SubMsgResult::Err(encrypted_error_message), true))
+        }
+        RawWasmOutput::OkV010 { ok, .. } => Some((
+            SubMsgResult::Ok(SubMsgResponse {
+                events: vec![],
+                data: ok.data.clone(),
+            }),
+            false,
+        )),
+        RawWasmOutput::OkV1 { ok, .. } => Some((
+            SubMsgResult::Ok(SubMsgResponse {
+                events: vec![],
+                data: ok.data.clone(),
+            }),
+            true,
+        )),
+        _ => None,
+    }
+}
+
+/// Binds an encrypted reply to the transaction it's signed in, so its callback signature can't be
+/// replayed in another one. The caller has to bind it to its own transaction the same way before
+/// the signature is verified.
+pub fn set_reply_tx_position(reply: &mut Reply, tx_position: TxPosition) {
+    reply.block_height = Some(tx_position.block_height);
+    reply.tx_index = tx_position.tx_index;
+}
+
+/// The encrypted id and the callback signature of the reply `caller` gets for this contract's
+/// output. `ancestors` are the frames of the callers before it, see `split_reply_chain`, and
+/// `caller_depth` the reply depth the caller's reply runs at.
+#[allow(clippy::too_many_arguments)]
 fn get_reply_info_for_output(
     output_result: SubMsgResult,
-    reply_params: &Option<Vec<ReplyParams>>,
+    caller: &ReplyParams,
+    ancestors: &[ReplyParams],
+    caller_depth: u32,
     encryption_key: AESKey,
     sender_addr: &CanonicalAddr,
     should_append_all_reply_params: bool,
+    is_data_plaintext: bool,
+    tx_position: TxPosition,
 ) -> Result<(Binary, Binary), EnclaveError> {
-    let encrypted_id = Binary::from_base64(&encrypt_preserialized_string(
+    let encrypted_id = encrypt_reply_id(
         &encryption_key,
-        &reply_params.as_ref().unwrap()[0].sub_msg_id.to_string(),
-        reply_params,
+        caller,
+        ancestors,
         should_append_all_reply_params,
-    )?)?;
+        caller_depth,
+    )?;
 
-    let reply = Reply {
+    let mut reply = Reply {
         id: encrypted_id.clone(),
         result: output_result,
         was_orig_msg_encrypted: true,
         is_encrypted: true,
+        is_data_plaintext,
+        block_height: None,
+        tx_index: None,
     };
+    set_reply_tx_position(&mut reply, tx_position);
 
     let reply_json = serde_json::to_vec(&reply).map_err(|err| {
         warn!(
@@ -880,7 +1713,10 @@ fn get_reply_info_for_output(
         EnclaveError::FailedToSerialize
     })?;
 
-    let sig = Binary::from(create_callback_signature(sender_addr, &reply_json, &[]).as_slice());
+    let sig = Binary::from(
+        create_callback_signature(sender_addr, CallbackContext::Reply, &reply_json, &[])?
+            .as_slice(),
+    );
 
     trace!(
         "Generated internal callback signature for msg {:?} signature is: {:?}",
@@ -897,6 +1733,7 @@ fn encrypt_v010_wasm_msg(
     user_public_key: Ed25519PublicKey,
     contract_addr: &CanonicalAddr,
 ) -> Result<(), EnclaveError> {
+    let context = CallbackContext::of_v010_msg(wasm_msg);
     match wasm_msg {
         cw_types_v010::types::WasmMsg::Execute {
             msg,
@@ -926,9 +1763,10 @@ fn encrypt_v010_wasm_msg(
 
             *callback_sig = Some(create_callback_signature(
                 contract_addr,
+                context,
                 &msg_to_pass.msg,
                 send,
-            ));
+            )?);
         }
         cw_types_v010::types::WasmMsg::Migrate {
             msg,
@@ -950,17 +1788,19 @@ fn encrypt_v010_wasm_msg(
 
             *callback_sig = Some(create_callback_signature(
                 contract_addr,
+                context,
                 &msg_to_pass.msg,
                 &[],
-            ));
+            )?);
         }
         cw_types_v010::types::WasmMsg::UpdateAdmin { callback_sig, .. }
         | cw_types_v010::types::WasmMsg::ClearAdmin { callback_sig, .. } => {
             *callback_sig = Some(create_callback_signature(
                 contract_addr,
+                context,
                 &vec![], /* must be empty vec for callback_sig verification */
                 &[],
-            ));
+            )?);
         }
     }
 
@@ -972,8 +1812,11 @@ fn attach_reply_headers_to_v1_wasm_msg(
     wasm_msg: &mut cw_types_v1::results::WasmMsg,
     reply_on: &ReplyOn,
     msg_id: u64, // In every submessage there is a field called "id", currently used only by "reply".
+    gas_limit: Option<u64>,
     reply_recipient_contract_hash: &str,
     reply_params: &Option<Vec<ReplyParams>>,
+    reply_depth: u32,
+    reply_propagation: ReplyPropagation,
 ) -> Result<(), EnclaveError> {
     match wasm_msg {
         cw_types_v1::results::WasmMsg::Execute { msg, code_hash, .. }
@@ -985,20 +1828,46 @@ fn attach_reply_headers_to_v1_wasm_msg(
             // In this context, we prepend the message with both hashes to signal to the next wasm call that its output is going to be an input to this contract as a "Reply".
             // When decrypting the input, the enclave will try to parse the message as usual, but if the message (after reading the first code-hash) can't be parsed into json,
             // then it will treat the next 64 bytes as a recipient code-hash and prepend this code-hash to its output.
+            // The callee's depth counts this contract if it waits for the reply, and every
+            // contract that waits above it, whatever part of the chain is propagated below
+            let callee_depth = match reply_on.reply_header_magic_bytes() {
+                Some(_) => reply_depth.saturating_add(1),
+                None => reply_depth,
+            };
+            if callee_depth as usize > MAX_REPLY_DEPTH {
+                warn!(
+                    "submessage would have a reply depth of {}, the maximum is {}",
+                    callee_depth, MAX_REPLY_DEPTH
+                );
+                return Err(EnclaveError::ReplyDepthExceeded);
+            }
+
             let mut hash_appended_msg = code_hash.as_bytes().to_vec();
-            if *reply_on != ReplyOn::Never {
+            // The gas limit goes first, so the callee can enforce it whether or not it's a reply
+            if let Some(gas_limit) = gas_limit {
                 hash_appended_msg
-                    .extend_from_slice(cw_types_v1::results::REPLY_ENCRYPTION_MAGIC_BYTES);
+                    .extend_from_slice(cw_types_v1::results::SUBMSG_GAS_LIMIT_MAGIC_BYTES);
+                hash_appended_msg.extend_from_slice(&gas_limit.to_be_bytes());
+            }
+            hash_appended_msg.extend_from_slice(&reply_depth_header(callee_depth));
+            // The header says which results are replied to, so the callee only builds the reply
+            // material that will be used
+            if let Some(magic_bytes) = reply_on.reply_header_magic_bytes() {
+                hash_appended_msg.extend_from_slice(magic_bytes);
                 hash_appended_msg.extend_from_slice(&msg_id.to_be_bytes());
                 hash_appended_msg.extend_from_slice(reply_recipient_contract_hash.as_bytes());
-            }
 
-            if let Some(r) = reply_params {
-                for param in r.iter() {
-                    hash_appended_msg
-                        .extend_from_slice(cw_types_v1::results::REPLY_ENCRYPTION_MAGIC_BYTES);
-                    hash_appended_msg.extend_from_slice(&param.sub_msg_id.to_be_bytes());
-                    hash_appended_msg.extend_from_slice(param.recipient_contract_hash.as_slice());
+                // The callers' part of the chain is only propagated if the contract allows it for
+                // this submessage. See `ReplyPropagation` for the security implications.
+                // It's only of use to the callee through this contract's reply, and without this
+                // contract's header the callee would take the first of them for its caller.
+                if let (Some(r), ReplyPropagation::All) = (reply_params, reply_propagation) {
+                    for param in r.iter() {
+                        hash_appended_msg.extend_from_slice(param.header_magic_bytes());
+                        hash_appended_msg.extend_from_slice(&param.sub_msg_id.to_be_bytes());
+                        hash_appended_msg
+                            .extend_from_slice(param.recipient_contract_hash.as_slice());
+                    }
                 }
             }
 
@@ -1013,25 +1882,1535 @@ fn attach_reply_headers_to_v1_wasm_msg(
     Ok(())
 }
 
+/// What a callback signature is created for. The signature is bound to it, so that one created
+/// for e.g. an execute message isn't accepted for an instantiate message or a reply.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CallbackContext {
+    Execute,
+    Instantiate,
+    Migrate,
+    /// Updating and clearing the admin
+    UpdateAdmin,
+    Reply,
+}
+
+impl CallbackContext {
+    /// The label the signature is bound to the context with
+    pub fn label(&self) -> &'static [u8] {
+        match self {
+            CallbackContext::Execute => b"execute",
+            CallbackContext::Instantiate => b"instantiate",
+            CallbackContext::Migrate => b"migrate",
+            CallbackContext::UpdateAdmin => b"update_admin",
+            CallbackContext::Reply => b"reply",
+        }
+    }
+
+    /// The context of a callback signature for a message that's verified as `verify_params_type`.
+    /// Other handle types aren't sent by contracts, so they don't have callback signatures.
+    pub fn from_verify_params_type(verify_params_type: VerifyParamsType) -> Option<Self> {
+        match verify_params_type {
+            VerifyParamsType::HandleType(HandleType::HANDLE_TYPE_EXECUTE) => {
+                Some(CallbackContext::Execute)
+            }
+            VerifyParamsType::HandleType(HandleType::HANDLE_TYPE_REPLY) => {
+                Some(CallbackContext::Reply)
+            }
+            VerifyParamsType::HandleType(_) => None,
+            VerifyParamsType::Init => Some(CallbackContext::Instantiate),
+            VerifyParamsType::Migrate => Some(CallbackContext::Migrate),
+            VerifyParamsType::UpdateAdmin => Some(CallbackContext::UpdateAdmin),
+        }
+    }
+
+    fn of_v1_msg(wasm_msg: &cw_types_v1::results::WasmMsg) -> Self {
+        match wasm_msg {
+            cw_types_v1::results::WasmMsg::Execute { .. } => CallbackContext::Execute,
+            cw_types_v1::results::WasmMsg::Instantiate { .. } => CallbackContext::Instantiate,
+            cw_types_v1::results::WasmMsg::Migrate { .. } => CallbackContext::Migrate,
+            cw_types_v1::results::WasmMsg::UpdateAdmin { .. }
+            | cw_types_v1::results::WasmMsg::ClearAdmin { .. } => CallbackContext::UpdateAdmin,
+        }
+    }
+
+    fn of_v010_msg(wasm_msg: &cw_types_v010::types::WasmMsg) -> Self {
+        match wasm_msg {
+            cw_types_v010::types::WasmMsg::Execute { .. } => CallbackContext::Execute,
+            cw_types_v010::types::WasmMsg::Instantiate { .. } => CallbackContext::Instantiate,
+            cw_types_v010::types::WasmMsg::Migrate { .. } => CallbackContext::Migrate,
+            cw_types_v010::types::WasmMsg::UpdateAdmin { .. }
+            | cw_types_v010::types::WasmMsg::ClearAdmin { .. } => CallbackContext::UpdateAdmin,
+        }
+    }
+}
+
 pub fn create_callback_signature(
     _sender: &CanonicalAddr,
+    context: CallbackContext,
     msg_to_pass: &Vec<u8>,
     sent_funds: &[Coin],
-) -> Vec<u8> {
-    // sha256(enclave_secret | msg_to_pass | sent_funds)
-    let mut callback_sig_bytes = KEY_MANAGER
-        .get_consensus_callback_secret()
-        .unwrap()
-        .current
-        .get()
-        .to_vec();
+) -> Result<Vec<u8>, EnclaveError> {
+    create_callback_signature_with_secret(
+        KEY_MANAGER.get_consensus_callback_secret(),
+        context,
+        msg_to_pass,
+        sent_funds,
+    )
+}
+
+fn create_callback_signature_with_secret(
+    callback_secret: Result<SeedsHolder<AESKey>, CryptoError>,
+    context: CallbackContext,
+    msg_to_pass: &[u8],
+    sent_funds: &[Coin],
+) -> Result<Vec<u8>, EnclaveError> {
+    // the callback secret is only derived once the node is registered and has the consensus seed
+    let callback_secret = callback_secret.map_err(|err| {
+        warn!("callback secret is unavailable: {:?}", err);
+        EnclaveError::FailedUnseal
+    })?;
+
+    // sha256(enclave_secret | len(label) | label | msg_to_pass | sent_funds)
+    let mut callback_sig_bytes = callback_secret.current.get().to_vec();
 
-    callback_sig_bytes.extend(msg_to_pass.as_slice());
-    callback_sig_bytes.extend(serde_json::to_vec(sent_funds).unwrap());
+    let label = context.label();
+    callback_sig_bytes.push(label.len() as u8);
+    callback_sig_bytes.extend_from_slice(label);
+    callback_sig_bytes.extend(msg_to_pass);
+    callback_sig_bytes.extend(serde_json::to_vec(sent_funds).map_err(|err| {
+        warn!("got an error while trying to serialize sent funds {:?}", err);
+        EnclaveError::FailedToSerialize
+    })?);
+
+    Ok(sha2::Sha256::digest(callback_sig_bytes.as_slice()).to_vec())
+}
 
-    sha2::Sha256::digest(callback_sig_bytes.as_slice()).to_vec()
+/// Whether `actual` is the callback signature `expected`. The comparison takes the same time
+/// wherever the signatures differ, so it doesn't tell how much of a forged signature is right.
+/// Only the lengths are compared directly, and those are public.
+pub fn verify_callback_sig(expected: &[u8], actual: &[u8]) -> bool {
+    ring::constant_time::verify_slices_are_equal(expected, actual).is_ok()
 }
 
 pub fn format_generic_error_message(encrypted_err: Value) -> Value {
     json!({"generic_err":{"msg":encrypted_err}})
 }
+
+#[cfg(feature = "test")]
+pub mod tests {
+    use super::*;
+    use crate::contract_validation::{validate_basic_msg, ValidatedMessage};
+    use crate::reply_message::split_reply_id;
+
+    /// A v1 output that only sends `messages`, a comma separated list of submessages
+    fn raw_output_json(messages: &str) -> Vec<u8> {
+        format!(
+            r#"{{"Ok":{{"messages":[{}],"attributes":[],"events":[],"data":null}},"internal_reply_enclave_sig":null,"internal_msg_id":null}}"#,
+            messages
+        )
+        .into_bytes()
+    }
+
+    /// Callback secrets with a genesis seed that differs from the current one
+    fn test_seeds() -> SeedsHolder<AESKey> {
+        SeedsHolder {
+            genesis: AESKey::new_from_slice(&[1u8; 32]),
+            current: AESKey::new_from_slice(&[2u8; 32]),
+        }
+    }
+
+    pub fn test_callback_signature_without_secret() {
+        let result = create_callback_signature_with_secret(
+            Err(CryptoError::ParsingError),
+            CallbackContext::Execute,
+            b"msg",
+            &[],
+        );
+        assert!(matches!(result, Err(EnclaveError::FailedUnseal)));
+    }
+
+    pub fn test_callback_signature_with_secret() {
+        let secret = test_seeds();
+
+        let sig = create_callback_signature_with_secret(
+            Ok(secret),
+            CallbackContext::Execute,
+            b"msg",
+            &[],
+        )
+        .unwrap();
+        assert_eq!(sig.len(), 32);
+        // signing is deterministic and uses the current secret
+        assert_eq!(
+            create_callback_signature_with_secret(
+                Ok(secret),
+                CallbackContext::Execute,
+                b"msg",
+                &[]
+            )
+            .unwrap(),
+            sig
+        );
+        let other_secret = SeedsHolder {
+            genesis: secret.genesis,
+            current: secret.genesis,
+        };
+        assert_ne!(
+            create_callback_signature_with_secret(
+                Ok(other_secret),
+                CallbackContext::Execute,
+                b"msg",
+                &[]
+            )
+            .unwrap(),
+            sig
+        );
+    }
+
+    pub fn test_callback_signature_domain_separation() {
+        let secret = test_seeds();
+        let sign = |context: CallbackContext| {
+            create_callback_signature_with_secret(Ok(secret), context, b"msg", &[]).unwrap()
+        };
+        let contexts = [
+            CallbackContext::Execute,
+            CallbackContext::Instantiate,
+            CallbackContext::Migrate,
+            CallbackContext::UpdateAdmin,
+            CallbackContext::Reply,
+        ];
+
+        // the same message and funds get a different signature in each context
+        for (i, context) in contexts.iter().enumerate() {
+            for other in &contexts[i + 1..] {
+                assert_ne!(sign(*context), sign(*other));
+            }
+        }
+
+        // so an execute signature isn't accepted for an instantiate message
+        let execute_sig = sign(CallbackContext::Execute);
+        let instantiate_context =
+            CallbackContext::from_verify_params_type(VerifyParamsType::Init).unwrap();
+        assert!(!verify_callback_sig(
+            &sign(instantiate_context),
+            &execute_sig
+        ));
+        assert!(verify_callback_sig(
+            &sign(
+                CallbackContext::from_verify_params_type(VerifyParamsType::HandleType(
+                    HandleType::HANDLE_TYPE_EXECUTE
+                ))
+                .unwrap()
+            ),
+            &execute_sig
+        ));
+
+        // messages contracts don't send have no callback signatures
+        assert_eq!(
+            CallbackContext::from_verify_params_type(VerifyParamsType::HandleType(
+                HandleType::HANDLE_TYPE_IBC_PACKET_RECEIVE
+            )),
+            None
+        );
+    }
+
+    pub fn test_verify_callback_sig() {
+        let secret = test_seeds();
+        let sig = create_callback_signature_with_secret(
+            Ok(secret),
+            CallbackContext::Execute,
+            b"msg",
+            &[],
+        )
+        .unwrap();
+
+        assert!(verify_callback_sig(&sig, &sig.clone()));
+
+        // one byte off, wherever it is
+        for i in [0, sig.len() / 2, sig.len() - 1] {
+            let mut forged = sig.clone();
+            forged[i] ^= 1;
+            assert!(!verify_callback_sig(&sig, &forged));
+        }
+        assert!(!verify_callback_sig(&sig, &sig[..sig.len() - 1]));
+        assert!(!verify_callback_sig(&sig, &[]));
+    }
+
+    pub fn test_reply_signature_bound_to_tx() {
+        let secret = test_seeds();
+        let sign = |block_height: u64, tx_index: Option<u32>| {
+            let mut reply = Reply {
+                id: Binary(b"id".to_vec()),
+                result: SubMsgResult::Ok(SubMsgResponse {
+                    events: vec![],
+                    data: Some(Binary::from(&b"data"[..])),
+                }),
+                was_orig_msg_encrypted: true,
+                is_encrypted: true,
+                is_data_plaintext: false,
+                block_height: None,
+                tx_index: None,
+            };
+            set_reply_tx_position(
+                &mut reply,
+                TxPosition {
+                    block_height,
+                    tx_index,
+                },
+            );
+            let reply_json = serde_json::to_vec(&reply).unwrap();
+            create_callback_signature_with_secret(
+                Ok(secret),
+                CallbackContext::Reply,
+                &reply_json,
+                &[],
+            )
+            .unwrap()
+        };
+
+        // an identical reply signed at another height, or in another tx of the block, can't be
+        // passed off as this one
+        assert_ne!(sign(100, Some(0)), sign(101, Some(0)));
+        assert_ne!(sign(100, Some(0)), sign(100, Some(1)));
+        assert_ne!(sign(100, Some(0)), sign(100, None));
+        assert_eq!(sign(100, Some(0)), sign(100, Some(0)));
+    }
+
+    pub fn test_reply_propagation_per_submsg() {
+        let output: RawWasmOutput = serde_json::from_slice(&raw_output_json(
+            r#"
+                {"id":1,"msg":{"wasm":{"execute":{"contract_addr":"a","code_hash":"callee","msg":"","send":[]}}},"gas_limit":null,"reply_on":"always"},
+                {"id":2,"msg":{"wasm":{"execute":{"contract_addr":"b","code_hash":"callee","msg":"","send":[]}}},"gas_limit":null,"reply_on":"always","reply_propagation":"immediate"},
+                {"id":3,"msg":{"wasm":{"execute":{"contract_addr":"c","code_hash":"callee","msg":"","send":[]}}},"gas_limit":null,"reply_on":"never","reply_propagation":"immediate"}
+            "#,
+        ))
+        .unwrap();
+        let caller_chain = Some(vec![ReplyParams {
+            recipient_contract_hash: b"caller".to_vec(),
+            sub_msg_id: 7,
+            reply_on: ReplyOn::Always,
+        }]);
+
+        let output = attach_reply_headers_to_submsgs(output, "this", &caller_chain, 1).unwrap();
+        let msgs: Vec<Vec<u8>> = match output {
+            RawWasmOutput::OkV1 { ok, .. } => ok
+                .messages
+                .into_iter()
+                .map(|sub_msg| match sub_msg.msg {
+                    cw_types_v1::results::CosmosMsg::Wasm(
+                        cw_types_v1::results::WasmMsg::Execute { msg, .. },
+                    ) => msg.0,
+                    other => panic!("unexpected message {:?}", other),
+                })
+                .collect(),
+            _ => panic!("expected a v1 output"),
+        };
+
+        let header = |id: u64, hash: &[u8]| {
+            let mut header = cw_types_v1::results::REPLY_ENCRYPTION_MAGIC_BYTES.to_vec();
+            header.extend_from_slice(&id.to_be_bytes());
+            header.extend_from_slice(hash);
+            header
+        };
+
+        // the default propagates the whole chain
+        assert_eq!(
+            msgs[0],
+            [
+                b"callee".to_vec(),
+                reply_depth_header(2),
+                header(1, b"this"),
+                header(7, b"caller")
+            ]
+            .concat()
+        );
+        // only this contract's header, but the depth still counts the caller
+        assert_eq!(
+            msgs[1],
+            [
+                b"callee".to_vec(),
+                reply_depth_header(2),
+                header(2, b"this")
+            ]
+            .concat()
+        );
+        // no reply expected and nothing propagated, but the caller still waits
+        assert_eq!(
+            msgs[2],
+            [b"callee".to_vec(), reply_depth_header(1)].concat()
+        );
+    }
+
+    pub fn test_reply_depth_limit() {
+        let output = || -> RawWasmOutput {
+            serde_json::from_slice(&raw_output_json(
+                r#"{"id":1,"msg":{"wasm":{"execute":{"contract_addr":"a","code_hash":"callee","msg":"","send":[]}}},"gas_limit":null,"reply_on":"success"}"#,
+            ))
+            .unwrap()
+        };
+        let max_depth = MAX_REPLY_DEPTH as u32;
+
+        // the submessage's callee is a level deeper than the contract that waits for it, whether
+        // or not the chain it's called with is propagated
+        assert!(attach_reply_headers_to_submsgs(output(), "self", &None, max_depth - 1).is_ok());
+        assert!(matches!(
+            attach_reply_headers_to_submsgs(output(), "self", &None, max_depth),
+            Err(EnclaveError::ReplyDepthExceeded)
+        ));
+    }
+
+    pub fn test_reply_headers_per_reply_on() {
+        let output: RawWasmOutput = serde_json::from_slice(&raw_output_json(
+            r#"
+                {"id":1,"msg":{"wasm":{"execute":{"contract_addr":"a","code_hash":"callee","msg":"","send":[]}}},"gas_limit":null,"reply_on":"always"},
+                {"id":2,"msg":{"wasm":{"execute":{"contract_addr":"b","code_hash":"callee","msg":"","send":[]}}},"gas_limit":null,"reply_on":"success"},
+                {"id":3,"msg":{"wasm":{"execute":{"contract_addr":"c","code_hash":"callee","msg":"","send":[]}}},"gas_limit":null,"reply_on":"error"},
+                {"id":4,"msg":{"wasm":{"execute":{"contract_addr":"d","code_hash":"callee","msg":"","send":[]}}},"gas_limit":null,"reply_on":"never"}
+            "#,
+        ))
+        .unwrap();
+        // the caller's header keeps the reply mode it was sent with
+        let caller_chain = Some(vec![ReplyParams {
+            recipient_contract_hash: b"caller".to_vec(),
+            sub_msg_id: 7,
+            reply_on: ReplyOn::Error,
+        }]);
+
+        let output = attach_reply_headers_to_submsgs(output, "this", &caller_chain, 1).unwrap();
+        let msgs: Vec<Vec<u8>> = match output {
+            RawWasmOutput::OkV1 { ok, .. } => ok
+                .messages
+                .into_iter()
+                .map(|sub_msg| match sub_msg.msg {
+                    cw_types_v1::results::CosmosMsg::Wasm(
+                        cw_types_v1::results::WasmMsg::Execute { msg, .. },
+                    ) => msg.0,
+                    other => panic!("unexpected message {:?}", other),
+                })
+                .collect(),
+            _ => panic!("expected a v1 output"),
+        };
+
+        let header = |magic_bytes: &[u8], id: u64, hash: &[u8]| {
+            [magic_bytes, &id.to_be_bytes(), hash].concat()
+        };
+        let caller_header = header(
+            cw_types_v1::results::REPLY_ON_ERROR_ENCRYPTION_MAGIC_BYTES,
+            7,
+            b"caller",
+        );
+
+        assert_eq!(
+            msgs[0],
+            [
+                b"callee".to_vec(),
+                reply_depth_header(2),
+                header(
+                    cw_types_v1::results::REPLY_ENCRYPTION_MAGIC_BYTES,
+                    1,
+                    b"this"
+                ),
+                caller_header.clone(),
+            ]
+            .concat()
+        );
+        assert_eq!(
+            msgs[1],
+            [
+                b"callee".to_vec(),
+                reply_depth_header(2),
+                header(
+                    cw_types_v1::results::REPLY_ON_SUCCESS_ENCRYPTION_MAGIC_BYTES,
+                    2,
+                    b"this"
+                ),
+                caller_header.clone(),
+            ]
+            .concat()
+        );
+        assert_eq!(
+            msgs[2],
+            [
+                b"callee".to_vec(),
+                reply_depth_header(2),
+                header(
+                    cw_types_v1::results::REPLY_ON_ERROR_ENCRYPTION_MAGIC_BYTES,
+                    3,
+                    b"this"
+                ),
+                caller_header.clone(),
+            ]
+            .concat()
+        );
+        // without a reply the callee has no use for the caller's chain, only for its depth
+        assert_eq!(
+            msgs[3],
+            [b"callee".to_vec(), reply_depth_header(1)].concat()
+        );
+
+        // and the callee reads the mode back from the header
+        for reply_on in &[ReplyOn::Always, ReplyOn::Success, ReplyOn::Error] {
+            let magic_bytes = reply_on.reply_header_magic_bytes().unwrap();
+            assert_eq!(
+                magic_bytes.len(),
+                cw_types_v1::results::REPLY_ENCRYPTION_MAGIC_BYTES.len()
+            );
+            assert_eq!(
+                ReplyOn::from_reply_header(&header(magic_bytes, 1, b"this")).as_ref(),
+                Some(reply_on)
+            );
+        }
+        assert_eq!(ReplyOn::Never.reply_header_magic_bytes(), None);
+        assert_eq!(ReplyOn::from_reply_header(b"callee"), None);
+    }
+
+    pub fn test_three_level_reply_chain() {
+        let contract_address = CanonicalAddr(Binary(vec![1; 20]));
+        let key = AESKey::new_from_slice(&[7u8; 32]);
+        let code_hash = |code: &[u8]| enclave_crypto::sha_256(code);
+        let (hash_a, hash_b, hash_c) = (code_hash(b"a"), code_hash(b"b"), code_hash(b"c"));
+
+        // `sender` sends a submessage with `id` and `reply_on` to `recipient`, and `recipient`
+        // validates it into the reply params of its own call
+        let call = |sender: &[u8; 32],
+                    reply_params: &Option<Vec<ReplyParams>>,
+                    reply_depth: u32,
+                    id: u64,
+                    reply_on: &str,
+                    recipient: &[u8; 32]| {
+            let output: RawWasmOutput = serde_json::from_slice(&raw_output_json(&format!(
+                r#"{{"id":{},"msg":{{"wasm":{{"execute":{{"contract_addr":"x","code_hash":"{}","msg":"","send":[]}}}}}},"gas_limit":null,"reply_on":"{}"}}"#,
+                id,
+                hex::encode(recipient),
+                reply_on
+            )))
+            .unwrap();
+            let output = attach_reply_headers_to_submsgs(
+                output,
+                &hex::encode(sender),
+                reply_params,
+                reply_depth,
+            )
+            .unwrap();
+            let msg = match output {
+                RawWasmOutput::OkV1 { mut ok, .. } => match ok.messages.remove(0).msg {
+                    cw_types_v1::results::CosmosMsg::Wasm(
+                        cw_types_v1::results::WasmMsg::Execute { msg, .. },
+                    ) => msg.0,
+                    other => panic!("unexpected message {:?}", other),
+                },
+                _ => panic!("expected a v1 output"),
+            };
+            let validated = validate_basic_msg(&contract_address, &msg, recipient, None).unwrap();
+            (validated.reply_params, validated.reply_depth)
+        };
+        // the reply for an output with `reply_params` reaches the contract with `code_hash`, and
+        // is validated into the reply params of its reply handler
+        let reply = |reply_params: &Option<Vec<ReplyParams>>,
+                     reply_depth: u32,
+                     code_hash: &[u8; 32]| {
+            let (caller, ancestors) = split_reply_chain(reply_params).unwrap();
+            let encrypted_id =
+                encrypt_reply_id(&key, caller, ancestors, true, reply_depth - 1).unwrap();
+            let (id, data_for_validation) =
+                split_reply_id(decrypt_ciphertext(&key, encrypted_id.as_slice()).unwrap()).unwrap();
+            let validated = validate_basic_msg(
+                &contract_address,
+                b"{}",
+                code_hash,
+                Some(data_for_validation),
+            )
+            .unwrap();
+            (id, validated.reply_params, validated.reply_depth)
+        };
+
+        // a calls b with id 1, and b calls c with id 2
+        let (b_params, b_depth) = call(&hash_a, &None, 0, 1, "always", &hash_b);
+        let (c_params, c_depth) = call(&hash_b, &b_params, b_depth, 2, "error", &hash_c);
+        assert_eq!((b_depth, c_depth), (1, 2));
+        let frames = |params: &Option<Vec<ReplyParams>>| {
+            params
+                .as_ref()
+                .unwrap()
+                .iter()
+                .map(|frame| (frame.sub_msg_id, frame.recipient_contract_hash.clone()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            frames(&c_params),
+            vec![
+                (2, hex::encode(hash_b).into_bytes()),
+                (1, hex::encode(hash_a).into_bytes())
+            ]
+        );
+
+        // c's error goes back to b with id 2, and b's reply handler still knows it's replied to a
+        let (id, b_reply_params, b_reply_depth) = reply(&c_params, c_depth, &hash_b);
+        assert_eq!(id, 2);
+        assert_eq!(b_reply_depth, b_depth);
+        assert_eq!(frames(&b_reply_params), frames(&b_params));
+        assert_eq!(
+            b_reply_params.as_ref().unwrap()[0].reply_on,
+            ReplyOn::Always
+        );
+
+        // which gets b's output with id 1, at the end of the chain
+        let (id, a_reply_params, a_reply_depth) = reply(&b_reply_params, b_reply_depth, &hash_a);
+        assert_eq!(id, 1);
+        assert!(a_reply_params.is_none());
+        assert_eq!(a_reply_depth, 0);
+
+        // if b doesn't ask for c's reply, c's caller isn't mistaken for a
+        // and c still runs at b's depth, as b doesn't wait for it
+        let (c_params, c_depth) = call(&hash_b, &b_params, b_depth, 2, "never", &hash_c);
+        assert!(c_params.is_none());
+        assert_eq!(c_depth, b_depth);
+    }
+
+    pub fn test_reply_depth_across_reply_modes() {
+        let contract_address = CanonicalAddr(Binary(vec![1; 20]));
+        let code_hash = enclave_crypto::sha_256(b"self");
+
+        // the contract calls itself with `reply_on` and `reply_propagation`, and validates the
+        // submessage into the reply params and depth of its next call
+        let call = |reply_params: &Option<Vec<ReplyParams>>,
+                    reply_depth: u32,
+                    reply_on: &str,
+                    reply_propagation: &str|
+         -> Result<(Option<Vec<ReplyParams>>, u32), EnclaveError> {
+            let output: RawWasmOutput = serde_json::from_slice(&raw_output_json(&format!(
+                r#"{{"id":1,"msg":{{"wasm":{{"execute":{{"contract_addr":"x","code_hash":"{}","msg":"","send":[]}}}}}},"gas_limit":null,"reply_on":"{}","reply_propagation":"{}"}}"#,
+                hex::encode(code_hash),
+                reply_on,
+                reply_propagation
+            )))
+            .unwrap();
+            let output = attach_reply_headers_to_submsgs(
+                output,
+                &hex::encode(code_hash),
+                reply_params,
+                reply_depth,
+            )?;
+            let msg = match output {
+                RawWasmOutput::OkV1 { mut ok, .. } => match ok.messages.remove(0).msg {
+                    cw_types_v1::results::CosmosMsg::Wasm(
+                        cw_types_v1::results::WasmMsg::Execute { msg, .. },
+                    ) => msg.0,
+                    other => panic!("unexpected message {:?}", other),
+                },
+                _ => panic!("expected a v1 output"),
+            };
+            let validated = validate_basic_msg(&contract_address, &msg, &code_hash, None)?;
+            Ok((validated.reply_params, validated.reply_depth))
+        };
+
+        // dropping the chain with `immediate` or not waiting with `never` doesn't reset the depth
+        let modes = [
+            ("always", "immediate"),
+            ("never", "immediate"),
+            ("success", "all"),
+            ("never", "all"),
+            ("error", "immediate"),
+        ];
+        let (mut reply_params, mut reply_depth) = (None, 0u32);
+        let mut waiting = 0u32;
+        for (reply_on, reply_propagation) in modes.iter().cycle() {
+            if *reply_on != "never" {
+                waiting += 1;
+            }
+
+            match call(&reply_params, reply_depth, reply_on, reply_propagation) {
+                Ok((params, depth)) => {
+                    assert_eq!(depth, waiting);
+                    // the immediate mode leaves at most the contract's own header in the chain
+                    if *reply_propagation == "immediate" {
+                        assert!(params.as_ref().map_or(0, Vec::len) <= 1);
+                    }
+                    reply_params = params;
+                    reply_depth = depth;
+                }
+                Err(err) => {
+                    assert!(matches!(err, EnclaveError::ReplyDepthExceeded));
+                    assert_eq!(waiting as usize, MAX_REPLY_DEPTH + 1);
+                    break;
+                }
+            }
+        }
+        assert_eq!(reply_depth as usize, MAX_REPLY_DEPTH);
+    }
+
+    pub fn test_reply_depth_across_immediate_replies() {
+        let contract_address = CanonicalAddr(Binary(vec![1; 20]));
+        let key = AESKey::new_from_slice(&[7u8; 32]);
+        let code_hash = enclave_crypto::sha_256(b"self");
+
+        let call = |reply_params: &Option<Vec<ReplyParams>>,
+                    reply_depth: u32|
+         -> Result<ValidatedMessage, EnclaveError> {
+            let output: RawWasmOutput = serde_json::from_slice(&raw_output_json(&format!(
+                r#"{{"id":1,"msg":{{"wasm":{{"execute":{{"contract_addr":"x","code_hash":"{}","msg":"","send":[]}}}}}},"gas_limit":null,"reply_on":"always","reply_propagation":"immediate"}}"#,
+                hex::encode(code_hash)
+            )))
+            .unwrap();
+            let output = attach_reply_headers_to_submsgs(
+                output,
+                &hex::encode(code_hash),
+                reply_params,
+                reply_depth,
+            )?;
+            let msg = match output {
+                RawWasmOutput::OkV1 { mut ok, .. } => match ok.messages.remove(0).msg {
+                    cw_types_v1::results::CosmosMsg::Wasm(
+                        cw_types_v1::results::WasmMsg::Execute { msg, .. },
+                    ) => msg.0,
+                    other => panic!("unexpected message {:?}", other),
+                },
+                _ => panic!("expected a v1 output"),
+            };
+            validate_basic_msg(&contract_address, &msg, &code_hash, None)
+        };
+
+        // the contract keeps calling itself, only ever propagating its own header
+        let (mut reply_params, mut reply_depth) = (None, 0u32);
+        for _ in 0..MAX_REPLY_DEPTH {
+            let validated = call(&reply_params, reply_depth).unwrap();
+            reply_params = validated.reply_params;
+            reply_depth = validated.reply_depth;
+        }
+        assert_eq!(reply_depth as usize, MAX_REPLY_DEPTH);
+        assert_eq!(reply_params.as_ref().unwrap().len(), 1);
+
+        // the deepest contract replies to its caller, whose reply handler has no chain left
+        let (caller, ancestors) = split_reply_chain(&reply_params).unwrap();
+        assert!(ancestors.is_empty());
+        let encrypted_id =
+            encrypt_reply_id(&key, caller, ancestors, false, reply_depth - 1).unwrap();
+        let (id, data_for_validation) =
+            split_reply_id(decrypt_ciphertext(&key, encrypted_id.as_slice()).unwrap()).unwrap();
+        assert_eq!(id, 1);
+        let validated = validate_basic_msg(
+            &contract_address,
+            b"{}",
+            &code_hash,
+            Some(data_for_validation),
+        )
+        .unwrap();
+        assert!(validated.reply_params.is_none());
+
+        // but the reply handler still runs at its caller's depth, so it can't go any deeper than
+        // the contract it replied to
+        assert_eq!(validated.reply_depth as usize, MAX_REPLY_DEPTH - 1);
+        let validated = call(&validated.reply_params, validated.reply_depth).unwrap();
+        assert_eq!(validated.reply_depth as usize, MAX_REPLY_DEPTH);
+        assert!(matches!(
+            call(&validated.reply_params, validated.reply_depth),
+            Err(EnclaveError::ReplyDepthExceeded)
+        ));
+    }
+
+    pub fn test_reply_material_only_for_replied_results() {
+        let secret_msg = SecretMessage {
+            nonce: [1u8; 32],
+            user_public_key: [2u8; 32],
+            msg: vec![],
+        };
+        let sender_addr = CanonicalAddr::from_vec(vec![3u8; 20]);
+        let adapt = |output: &[u8], reply_on: ReplyOn| {
+            let reply_params = Some(vec![ReplyParams {
+                recipient_contract_hash: b"ab".repeat(32),
+                sub_msg_id: 1,
+                reply_on,
+            }]);
+            adapt_output_for_reply(
+                serde_json::from_slice(output).unwrap(),
+                &reply_params,
+                1,
+                &secret_msg,
+                &sender_addr,
+                TxPosition {
+                    block_height: 1,
+                    tx_index: Some(0),
+                },
+            )
+            .unwrap()
+        };
+        let has_reply_material = |output: &RawWasmOutput| match output {
+            RawWasmOutput::Err {
+                internal_msg_id,
+                internal_reply_enclave_sig,
+                ..
+            }
+            | RawWasmOutput::OkV1 {
+                internal_msg_id,
+                internal_reply_enclave_sig,
+                ..
+            } => internal_msg_id.is_some() || internal_reply_enclave_sig.is_some(),
+            other => panic!("unexpected output {:?}", other),
+        };
+
+        let err = br#"{"Err":{"generic_err":{"msg":"failed"}},"internal_msg_id":null,"internal_reply_enclave_sig":null}"#;
+        let ok = raw_output_json("");
+
+        // a caller that's only replied to on success doesn't get an error reply, and vice versa
+        assert!(!has_reply_material(&adapt(err, ReplyOn::Success)));
+        assert!(!has_reply_material(&adapt(&ok, ReplyOn::Error)));
+
+        assert!(ReplyOn::Always.replies_to(true) && ReplyOn::Always.replies_to(false));
+        assert!(ReplyOn::Success.replies_to(true) && !ReplyOn::Success.replies_to(false));
+        assert!(!ReplyOn::Error.replies_to(true) && ReplyOn::Error.replies_to(false));
+        assert!(!ReplyOn::Never.replies_to(true) && !ReplyOn::Never.replies_to(false));
+    }
+
+    pub fn test_output_encryption_errors() {
+        let key = AESKey::new_from_slice(&[7u8; 32]);
+
+        // JSON object keys have to be strings
+        let unserializable: std::collections::BTreeMap<Vec<u8>, u8> =
+            vec![(vec![1u8], 1u8)].into_iter().collect();
+        assert!(matches!(
+            encrypt_serializable(&key, &unserializable, &None, false, false),
+            Err(EnclaveError::Serialization)
+        ));
+        assert!(matches!(
+            encrypt_serializable_result(
+                &key,
+                &unserializable,
+                &None,
+                false,
+                &OutputCompression::default()
+            ),
+            Err(EnclaveError::Serialization)
+        ));
+
+        // a 32 byte key can't fail to set up AES-SIV, so the failure is injected like
+        // `create_callback_signature_with_secret` injects a missing secret
+        assert!(matches!(
+            encode_ciphertext(Err(CryptoError::EncryptionError)),
+            Err(EnclaveError::Aead)
+        ));
+        assert_eq!(encode_ciphertext(Ok(vec![1, 2])).unwrap(), "AQI=");
+        assert!(encrypt_serializable(&key, "value", &None, false, false).is_ok());
+    }
+
+    pub fn test_submsg_gas_limits_are_capped() {
+        let mut output: RawWasmOutput = serde_json::from_slice(&raw_output_json(
+            r#"
+                {"id":1,"msg":{"wasm":{"execute":{"contract_addr":"a","code_hash":"callee","msg":"","send":[]}}},"gas_limit":900000,"reply_on":"never"},
+                {"id":2,"msg":{"wasm":{"execute":{"contract_addr":"b","code_hash":"callee","msg":"","send":[]}}},"gas_limit":1000,"reply_on":"never"},
+                {"id":3,"msg":{"wasm":{"execute":{"contract_addr":"c","code_hash":"callee","msg":"","send":[]}}},"gas_limit":null,"reply_on":"never"}
+            "#,
+        ))
+        .unwrap();
+
+        // 300,000 sdk gas left
+        cap_submsg_gas_limits(&mut output, 300_000 * GAS_MULTIPLIER + 999);
+        let gas_limits = match &output {
+            RawWasmOutput::OkV1 { ok, .. } => ok
+                .messages
+                .iter()
+                .map(|sub_msg| sub_msg.gas_limit)
+                .collect::<Vec<_>>(),
+            _ => panic!("expected a v1 output"),
+        };
+        assert_eq!(gas_limits, vec![Some(300_000), Some(1000), None]);
+
+        // the limit travels with the message, ahead of any reply headers
+        let output = attach_reply_headers_to_submsgs(output, "this", &None, 0).unwrap();
+        let msgs: Vec<Vec<u8>> = match output {
+            RawWasmOutput::OkV1 { ok, .. } => ok
+                .messages
+                .into_iter()
+                .map(|sub_msg| match sub_msg.msg {
+                    cw_types_v1::results::CosmosMsg::Wasm(
+                        cw_types_v1::results::WasmMsg::Execute { msg, .. },
+                    ) => msg.0,
+                    other => panic!("unexpected message {:?}", other),
+                })
+                .collect(),
+            _ => panic!("expected a v1 output"),
+        };
+        let header = |gas_limit: u64| {
+            let mut header = cw_types_v1::results::SUBMSG_GAS_LIMIT_MAGIC_BYTES.to_vec();
+            header.extend_from_slice(&gas_limit.to_be_bytes());
+            header
+        };
+        assert_eq!(msgs[0], [b"callee".to_vec(), header(300_000)].concat());
+        assert_eq!(msgs[1], [b"callee".to_vec(), header(1000)].concat());
+        assert_eq!(msgs[2], b"callee".to_vec());
+    }
+
+    pub fn test_query_output_echoes_nonce() {
+        let nonce: IoNonce = [7u8; 32];
+
+        let output = finalize_raw_output(
+            RawWasmOutput::QueryOkV1 {
+                ok: "encrypted".to_string(),
+            },
+            true,
+            false,
+            true,
+            Some(Binary(nonce.to_vec())),
+        )
+        .unwrap();
+        let output: WasmOutput = serde_json::from_slice(&output).unwrap();
+        assert_eq!(output.query.unwrap().nonce, Some(Binary(nonce.to_vec())));
+
+        // outputs that aren't queries have no nonce, and nothing is added for them
+        let output = finalize_raw_output(
+            RawWasmOutput::QueryOkV1 {
+                ok: "encrypted".to_string(),
+            },
+            true,
+            false,
+            true,
+            None,
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            r#"{"v010":null,"v1":null,"ibc_basic":null,"ibc_packet_receive":null,"ibc_open_channel":null,"query":{"Ok":"encrypted","Err":null},"internal_reply_enclave_sig":null,"internal_msg_id":null}"#
+        );
+    }
+
+    pub fn test_attribute_plaintext_copy() {
+        let key = AESKey::new_from_slice(&[1u8; 32]);
+        let mut attributes: Vec<LogAttribute> = serde_json::from_str(
+            r#"[
+                {"key":"action","value":"swap","encrypted":true,"plaintext_copy":true},
+                {"key":"amount","value":"100"},
+                {"key":"public","value":"yes","encrypted":false,"plaintext_copy":true}
+            ]"#,
+        )
+        .unwrap();
+
+        encrypt_v1_attributes(&key, &mut attributes).unwrap();
+
+        let decrypt = |encrypted: &str| {
+            let decrypted = key
+                .decrypt_siv(&base64::decode(encrypted).unwrap(), None)
+                .unwrap();
+            String::from_utf8(decrypted).unwrap()
+        };
+
+        // the marked attribute appears once in plaintext and once encrypted
+        assert_eq!(attributes.len(), 4);
+        assert_eq!(attributes[0].key, "action");
+        assert_eq!(attributes[0].value, "swap");
+        assert_ne!(attributes[1].key, "action");
+        assert_eq!(decrypt(&attributes[1].key), "action");
+        assert_eq!(decrypt(&attributes[1].value), "swap");
+
+        // unmarked attributes are only encrypted, and plaintext ones aren't duplicated
+        assert_eq!(decrypt(&attributes[2].key), "amount");
+        assert_eq!(attributes[3].key, "public");
+        assert_eq!(attributes[3].value, "yes");
+
+        // the flag doesn't leak up to the Go layers
+        assert!(!serde_json::to_string(&attributes).unwrap().contains("plaintext_copy"));
+    }
+
+    pub fn test_deterministic_attributes_are_sorted() {
+        let key = AESKey::new_from_slice(&[1u8; 32]);
+        let secret_msg = SecretMessage {
+            nonce: [0u8; 32],
+            user_public_key: [0u8; 32],
+            msg: vec![],
+        };
+        let output = |attributes: &str, deterministic: bool| -> RawWasmOutput {
+            serde_json::from_str(&format!(
+                r#"{{"Ok":{{"messages":[],"attributes":{0},"events":[{{"type":"swap","attributes":{0}}}],"data":null,"deterministic_attributes":{1}}},"internal_reply_enclave_sig":null,"internal_msg_id":null}}"#,
+                attributes, deterministic
+            ))
+            .unwrap()
+        };
+        let encrypt = |output: RawWasmOutput| {
+            encrypt_output_with_key(
+                output,
+                &secret_msg,
+                &key,
+                &CanonicalAddr(Binary(vec![])),
+                &None,
+                false,
+                false,
+                &OutputCompression::default(),
+            )
+            .unwrap()
+        };
+        let pairs = |attributes: &[LogAttribute]| {
+            attributes
+                .iter()
+                .map(|attr| (attr.key.clone(), attr.value.clone()))
+                .collect::<Vec<_>>()
+        };
+
+        // the same attributes, in the order two nodes iterating a HashMap might see them
+        let shuffled = [
+            r#"[{"key":"b","value":"2"},{"key":"a","value":"9"},{"key":"c","value":"1"},{"key":"a","value":"1"}]"#,
+            r#"[{"key":"a","value":"1"},{"key":"c","value":"1"},{"key":"a","value":"9"},{"key":"b","value":"2"}]"#,
+        ];
+
+        let mut sorted = output(shuffled[0], true);
+        sort_deterministic_attributes(&mut sorted);
+        match &sorted {
+            RawWasmOutput::OkV1 { ok, .. } => {
+                let expected: Vec<LogAttribute> = serde_json::from_str(
+                    r#"[{"key":"a","value":"1"},{"key":"a","value":"9"},{"key":"b","value":"2"},{"key":"c","value":"1"}]"#,
+                )
+                .unwrap();
+                let expected = pairs(&expected);
+                assert_eq!(pairs(&ok.attributes), expected);
+                assert_eq!(pairs(&ok.events[0].attributes), expected);
+            }
+            other => panic!("expected a v1 output, got {:?}", other),
+        }
+
+        // so replicas converge on the same encrypted output
+        let encrypted: Vec<_> = shuffled
+            .iter()
+            .map(|attributes| {
+                let mut output = output(attributes, true);
+                sort_deterministic_attributes(&mut output);
+                encrypt(output)
+            })
+            .collect();
+        assert_eq!(encrypted[0], encrypted[1]);
+
+        // without the flag, the contract's order is kept
+        let mut unsorted = output(shuffled[0], false);
+        sort_deterministic_attributes(&mut unsorted);
+        assert_eq!(unsorted, output(shuffled[0], false));
+        assert_ne!(
+            encrypt(output(shuffled[0], false)),
+            encrypt(output(shuffled[1], false))
+        );
+
+        // the flag isn't passed up to the Go layers
+        assert!(!serde_json::to_string(&sorted)
+            .unwrap()
+            .contains("deterministic_attributes"));
+    }
+
+    pub fn test_encrypt_attributes_batch() {
+        let key = AESKey::new_from_slice(&[1u8; 32]);
+        let attributes: Vec<LogAttribute> = (0..500)
+            .map(|i| LogAttribute {
+                key: format!("key_{}", i),
+                value: format!("value_{}", i),
+                encrypted: i % 10 != 0,
+                plaintext_copy: false,
+            })
+            .collect();
+
+        // what encrypt_v1_attributes used to do for each attribute
+        let mut per_attribute = attributes.clone();
+        for attr in per_attribute.iter_mut().filter(|attr| attr.encrypted) {
+            attr.key = encrypt_preserialized_string(&key, &attr.key, &None, false, false).unwrap();
+            attr.value =
+                encrypt_preserialized_string(&key, &attr.value, &None, false, false).unwrap();
+        }
+
+        let mut batch = attributes.clone();
+        encrypt_attributes_batch(&key, &mut batch).unwrap();
+
+        // the ciphertexts are the same, so anything that decrypted them before still can
+        assert_eq!(batch, per_attribute);
+        for (encrypted, plaintext) in batch.iter().zip(attributes.iter()) {
+            if !plaintext.encrypted {
+                assert_eq!(encrypted.key, plaintext.key);
+                continue;
+            }
+
+            let decrypted = key
+                .decrypt_siv(&base64::decode(&encrypted.value).unwrap(), None)
+                .unwrap();
+            assert_eq!(decrypted, plaintext.value.as_bytes());
+        }
+    }
+
+    pub fn test_submsg_count_limit() {
+        let output = |count: usize| -> RawWasmOutput {
+            let messages = vec![
+                r#"{"id":0,"msg":{"bank":{"send":{"to_address":"a","amount":[]}}},"gas_limit":null,"reply_on":"never"}"#;
+                count
+            ];
+            serde_json::from_slice(&raw_output_json(&messages.join(","))).unwrap()
+        };
+
+        let limit = |emitted: u32| SubmsgLimit { max: 512, emitted };
+
+        assert!(check_submsg_count(&output(0), Some(&limit(0))).is_ok());
+        assert!(check_submsg_count(&output(512), Some(&limit(0))).is_ok());
+        assert!(matches!(
+            check_submsg_count(&output(513), Some(&limit(0))),
+            Err(EnclaveError::TooManySubmessages)
+        ));
+
+        // what the transaction already sent counts against the limit
+        assert!(check_submsg_count(&output(12), Some(&limit(500))).is_ok());
+        assert!(matches!(
+            check_submsg_count(&output(13), Some(&limit(500))),
+            Err(EnclaveError::TooManySubmessages)
+        ));
+        assert!(check_submsg_count(&output(0), Some(&limit(600))).is_ok());
+        assert!(matches!(
+            check_submsg_count(&output(1), Some(&limit(600))),
+            Err(EnclaveError::TooManySubmessages)
+        ));
+
+        // without the chain's limit there's none
+        assert!(check_submsg_count(&output(513), None).is_ok());
+    }
+
+    pub fn test_submsg_empty_msg_is_rejected() {
+        let secret_msg = SecretMessage {
+            nonce: [1u8; 32],
+            user_public_key: [2u8; 32],
+            msg: vec![],
+        };
+        let sub_msg = |msg: &str| -> SubMsg {
+            serde_json::from_str(&format!(
+                r#"{{"id":0,"msg":{},"gas_limit":null,"reply_on":"never"}}"#,
+                msg
+            ))
+            .unwrap()
+        };
+
+        for wasm_msg in [
+            r#"{"wasm":{"execute":{"contract_addr":"a","code_hash":"","msg":"","send":[]}}}"#,
+            r#"{"wasm":{"instantiate":{"admin":null,"code_id":1,"code_hash":"","msg":"","send":[],"label":"l"}}}"#,
+            r#"{"wasm":{"migrate":{"contract_addr":"a","code_hash":"","code_id":1,"msg":""}}}"#,
+        ] {
+            assert!(matches!(
+                encrypt_wasm_submsg(&mut sub_msg(wasm_msg), &secret_msg),
+                Err(EnclaveError::EncryptionError)
+            ));
+        }
+
+        // messages that aren't encrypted are left alone
+        let mut bank_msg = sub_msg(r#"{"bank":{"send":{"to_address":"a","amount":[]}}}"#);
+        let expected = bank_msg.clone();
+        encrypt_wasm_submsg(&mut bank_msg, &secret_msg).unwrap();
+        assert_eq!(bank_msg, expected);
+    }
+
+    pub fn test_out_of_gas_output_is_an_error() {
+        match deserialize_output(out_of_gas_output()).unwrap() {
+            RawWasmOutput::Err {
+                err,
+                internal_msg_id,
+                internal_reply_enclave_sig,
+            } => {
+                assert_eq!(err, json!({ "out_of_gas": {} }));
+                assert_eq!(internal_msg_id, None);
+                assert_eq!(internal_reply_enclave_sig, None);
+            }
+            output => panic!("out of gas output parsed as {:?}", output),
+        }
+    }
+
+    pub fn test_event_type_length_limit() {
+        let output = |lengths: &[usize]| -> RawWasmOutput {
+            let events: Vec<String> = lengths
+                .iter()
+                .map(|length| format!(r#"{{"type":"{}","attributes":[]}}"#, "e".repeat(*length)))
+                .collect();
+            serde_json::from_str(&format!(
+                r#"{{"Ok":{{"messages":[],"attributes":[],"events":[{}],"data":null}},"internal_reply_enclave_sig":null,"internal_msg_id":null}}"#,
+                events.join(",")
+            ))
+            .unwrap()
+        };
+
+        assert!(check_event_types(&output(&[]), 128).is_ok());
+        assert!(check_event_types(&output(&[1, 128]), 128).is_ok());
+        assert!(matches!(
+            check_event_types(&output(&[1, 129]), 128),
+            Err(EnclaveError::EventTypeTooLong)
+        ));
+
+        // the limit follows the chain's param
+        assert!(check_event_types(&output(&[129]), 256).is_ok());
+        assert!(matches!(
+            check_event_types(&output(&[17]), 16),
+            Err(EnclaveError::EventTypeTooLong)
+        ));
+
+        // without the chain's limit there's none
+        assert!(check_event_types(&output(&[4096]), 0).is_ok());
+    }
+
+    pub fn test_output_depth_limit() {
+        let nested_err = |depth: usize| -> Vec<u8> {
+            // the outer object is the first level
+            let err = "[".repeat(depth - 1) + &"]".repeat(depth - 1);
+            format!(r#"{{"Err":{}}}"#, err).into_bytes()
+        };
+
+        assert!(check_output_depth(b"").is_ok());
+        assert!(deserialize_output(nested_err(MAX_OUTPUT_JSON_DEPTH)).is_ok());
+        assert!(matches!(
+            deserialize_output(nested_err(MAX_OUTPUT_JSON_DEPTH + 1)),
+            Err(EnclaveError::OutputTooDeep)
+        ));
+        // adversarial outputs are rejected before serde recurses into them
+        assert!(matches!(
+            deserialize_output(nested_err(100_000)),
+            Err(EnclaveError::OutputTooDeep)
+        ));
+
+        // brackets in strings, including after escaped quotes, aren't nesting
+        let brackets = "[{".repeat(MAX_OUTPUT_JSON_DEPTH);
+        let in_string = format!(r#"{{"Err":"\"{}"}}"#, brackets).into_bytes();
+        assert!(check_output_depth(&in_string).is_ok());
+        assert!(deserialize_output(in_string).is_ok());
+    }
+
+    pub fn test_output_deserialization_error() {
+        for (output, expected_variant, expected_offset) in &[
+            (&b"not json"[..], OutputVariant::Unknown, 2),
+            (br#"{"Ok":"x"} trailing"#, OutputVariant::Unknown, 12),
+            // JSON, but not shaped like any output, has no position
+            (b"5", OutputVariant::Unknown, 0),
+            (br#"{"Ok":5}"#, OutputVariant::OkV1, 7),
+            (
+                br#"{"Ok":{"messages":[],"log":5,"data":null}}"#,
+                OutputVariant::OkV010,
+                28,
+            ),
+            (
+                br#"{"Ok":{"messages":[],"attributes":[],"events":5,"data":null}}"#,
+                OutputVariant::OkV1,
+                47,
+            ),
+            (
+                br#"{"Ok":{"acknowledgement":5}}"#,
+                OutputVariant::OkIBCPacketReceive,
+                26,
+            ),
+            (
+                br#"{"Ok":{"version":5}}"#,
+                OutputVariant::OkIBCOpenChannel,
+                18,
+            ),
+            // the offset counts the bytes of earlier lines
+            (
+                b"{\n\"Ok\":{\"version\":\n5}}",
+                OutputVariant::OkIBCOpenChannel,
+                20,
+            ),
+        ] {
+            match deserialize_output(output.to_vec()) {
+                Err(EnclaveError::OutputDeserialization {
+                    variant_hint,
+                    offset,
+                    preview,
+                }) => {
+                    assert_eq!(variant_hint, *expected_variant);
+                    assert_eq!(offset, *expected_offset);
+                    if cfg!(feature = "production") {
+                        assert!(preview.as_slice().is_empty());
+                    } else {
+                        assert_eq!(preview.as_slice(), *output);
+                    }
+                }
+                other => panic!("unexpected result for {:?}: {:?}", output, other),
+            }
+        }
+
+        // errors and query results always match a variant, their hints are checked directly
+        let err = br#"{"Err":"oops","internal_msg_id":5}"#;
+        assert_eq!(output_variant_hint(err), OutputVariant::Err);
+        assert_eq!(
+            error_offset(err, &variant_error(err, OutputVariant::Err).unwrap()),
+            33
+        );
+        let query_ok = br#"{"ok":5}"#;
+        assert_eq!(output_variant_hint(query_ok), OutputVariant::QueryOkV1);
+        assert_eq!(
+            error_offset(
+                query_ok,
+                &variant_error(query_ok, OutputVariant::QueryOkV1).unwrap()
+            ),
+            7
+        );
+
+        // the preview is truncated
+        let long_output = format!(r#"{{"Ok":{{"log":"{}"}}}}"#, "a".repeat(100));
+        match deserialize_output(long_output.clone().into_bytes()) {
+            Err(EnclaveError::OutputDeserialization { preview, .. }) => {
+                if !cfg!(feature = "production") {
+                    assert_eq!(
+                        preview.as_slice(),
+                        &long_output.as_bytes()[..enclave_ffi_types::OUTPUT_PREVIEW_SIZE]
+                    );
+                }
+            }
+            other => panic!("unexpected result for a long output: {:?}", other),
+        }
+    }
+
+    pub fn test_decrypt_output_round_trip() {
+        let key = AESKey::new_from_slice(&[7u8; 32]);
+        let secret_msg = SecretMessage {
+            nonce: [1u8; 32],
+            user_public_key: [2u8; 32],
+            msg: vec![],
+        };
+        let contract_addr = CanonicalAddr::from_vec(vec![3u8; 20]);
+
+        // compresses every result, however short
+        let deflate = OutputCompression {
+            version: crate::compression::COMPRESSION_VERSION_DEFLATE,
+            threshold: 0,
+        };
+
+        let round_trip = |output: &str, reply_params: &Option<Vec<ReplyParams>>| {
+            let output: RawWasmOutput = serde_json::from_str(output).unwrap();
+            for pad_output in &[false, true] {
+                for compression in &[OutputCompression::default(), deflate.clone()] {
+                    let encrypted = encrypt_output_with_key(
+                        output.clone(),
+                        &secret_msg,
+                        &key,
+                        &contract_addr,
+                        reply_params,
+                        false,
+                        *pad_output,
+                        compression,
+                    )
+                    .unwrap();
+                    assert_ne!(encrypted, output);
+
+                    let encrypted = serde_json::to_vec(&encrypted).unwrap();
+                    assert_eq!(
+                        decrypt_output_with_key(&encrypted, &key, compression).unwrap(),
+                        output
+                    );
+                }
+            }
+        };
+
+        let outputs = [
+            r#"{"Err":{"generic_err":{"msg":"failed"}}}"#,
+            r#"{"ok":"eyJiYWxhbmNlIjoxfQ=="}"#,
+            r#"{"Ok":{"messages":[],"log":[{"key":"a","value":"1"},{"key":"b","value":"2","encrypted":false}],"data":"ZGF0YQ=="}}"#,
+            r#"{"Ok":{"messages":[{"id":0,"msg":{"bank":{"send":{"to_address":"a","amount":[]}}},"gas_limit":null,"reply_on":"never"}],"attributes":[{"key":"action","value":"swap","encrypted":true,"plaintext_copy":true},{"key":"amount","value":"100"},{"key":"public","value":"yes","encrypted":false}],"events":[{"type":"transfer","attributes":[{"key":"to","value":"b"}]}],"data":"ZGF0YQ=="}}"#,
+            r#"{"Ok":{"acknowledgement":"YWNr","messages":[],"attributes":[{"key":"a","value":"1"}],"events":[]}}"#,
+        ];
+        for output in outputs {
+            round_trip(output, &None);
+        }
+
+        // outputs that go back to a caller's reply have its code hash prepended to their results
+        let reply_params = Some(vec![ReplyParams {
+            recipient_contract_hash: b"ab".repeat(32),
+            sub_msg_id: 1,
+            reply_on: ReplyOn::Always,
+        }]);
+        let reply_outputs = [
+            r#"{"Err":{"generic_err":{"msg":"failed"}},"internal_msg_id":"AQ==","internal_reply_enclave_sig":null}"#,
+            r#"{"Ok":{"messages":[],"log":[],"data":"ZGF0YQ=="},"internal_msg_id":"AQ==","internal_reply_enclave_sig":null}"#,
+            r#"{"Ok":{"messages":[],"attributes":[],"events":[],"data":"ZGF0YQ=="},"internal_msg_id":"AQ==","internal_reply_enclave_sig":null}"#,
+        ];
+        for output in reply_outputs {
+            round_trip(output, &reply_params);
+        }
+    }
+
+    pub fn test_padded_output_lengths() {
+        let key = AESKey::new_from_slice(&[7u8; 32]);
+        let encrypt = |secret: &str, pad_output: bool| {
+            encrypt_serializable(&key, secret, &None, false, pad_output).unwrap()
+        };
+
+        let short = "short secret";
+        let long = "a much longer secret, which is still in the same bucket";
+        assert_ne!(encrypt(short, false).len(), encrypt(long, false).len());
+        assert_eq!(encrypt(short, true).len(), encrypt(long, true).len());
+
+        for secret in &[short, long] {
+            assert_eq!(
+                decrypt_b64(&key, &encrypt(secret, true)).unwrap(),
+                secret.as_bytes()
+            );
+        }
+    }
+
+    pub fn test_ibc_ack_encryption() {
+        let key = AESKey::new_from_slice(&[7u8; 32]);
+        let secret_msg = SecretMessage {
+            nonce: [1u8; 32],
+            user_public_key: [2u8; 32],
+            msg: vec![],
+        };
+        let contract_addr = CanonicalAddr::from_vec(vec![3u8; 20]);
+        let receive_output = |acknowledgement: &[u8]| -> RawWasmOutput {
+            serde_json::from_value(json!({"Ok": {
+                "acknowledgement": Binary(acknowledgement.to_vec()).to_base64(),
+                "messages": [],
+                "attributes": [],
+                "events": [],
+            }}))
+            .unwrap()
+        };
+        let acknowledgement = |output: &RawWasmOutput| match output {
+            RawWasmOutput::OkIBCPacketReceive { ok } => ok.acknowledgement.0.clone(),
+            other => panic!("expected an ibc receive output, got {:?}", other),
+        };
+        let encrypt = |output: RawWasmOutput| {
+            encrypt_output_with_key(
+                output,
+                &secret_msg,
+                &key,
+                &contract_addr,
+                &None,
+                true,
+                false,
+                &OutputCompression::default(),
+            )
+            .unwrap()
+        };
+
+        // by default the acknowledgement is encrypted
+        let encrypted = encrypt(receive_output(br#"{"result":"AQ=="}"#));
+        assert_ne!(
+            acknowledgement(&encrypted),
+            br#"{"result":"AQ=="}"#.to_vec()
+        );
+        let decrypted = decrypt_output_with_key(
+            &serde_json::to_vec(&encrypted).unwrap(),
+            &key,
+            &OutputCompression::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            acknowledgement(&decrypted),
+            br#"{"result":"AQ=="}"#.to_vec()
+        );
+
+        // a marked acknowledgement is emitted verbatim, without the prefix
+        let marked = [PLAINTEXT_ACK_PREFIX, br#"{"result":"AQ=="}"#].concat();
+        let plaintext = encrypt(receive_output(&marked));
+        assert_eq!(
+            acknowledgement(&plaintext),
+            br#"{"result":"AQ=="}"#.to_vec()
+        );
+        let decrypted = decrypt_output_with_key(
+            &serde_json::to_vec(&plaintext).unwrap(),
+            &key,
+            &OutputCompression::default(),
+        )
+        .unwrap();
+        assert_eq!(decrypted, plaintext);
+
+        // the prefix is also removed from outputs that aren't encrypted
+        let mut unencrypted = receive_output(&marked);
+        strip_plaintext_ack_prefix(&mut unencrypted);
+        assert_eq!(
+            acknowledgement(&unencrypted),
+            br#"{"result":"AQ=="}"#.to_vec()
+        );
+    }
+
+    pub fn test_plaintext_data() {
+        let key = AESKey::new_from_slice(&[7u8; 32]);
+        let secret_msg = SecretMessage {
+            nonce: [1u8; 32],
+            user_public_key: [2u8; 32],
+            msg: vec![],
+        };
+        let contract_addr = CanonicalAddr::from_vec(vec![3u8; 20]);
+        let reply_params = Some(vec![ReplyParams {
+            recipient_contract_hash: b"ab".repeat(32),
+            sub_msg_id: 1,
+            reply_on: ReplyOn::Always,
+        }]);
+
+        for reply_params in &[None, reply_params] {
+            let output: RawWasmOutput = serde_json::from_str(
+                r#"{"Ok":{"messages":[],"attributes":[{"key":"a","value":"1"}],"events":[],"data":"ZGF0YQ==","plaintext_data":true},"internal_msg_id":null,"internal_reply_enclave_sig":null}"#,
+            )
+            .unwrap();
+            let encrypted = encrypt_output_with_key(
+                output.clone(),
+                &secret_msg,
+                &key,
+                &contract_addr,
+                reply_params,
+                false,
+                false,
+                &OutputCompression::default(),
+            )
+            .unwrap();
+
+            // the data is emitted as is, while the attributes are still encrypted
+            match &encrypted {
+                RawWasmOutput::OkV1 { ok, .. } => {
+                    assert_eq!(ok.data, Some(Binary::from(&b"data"[..])));
+                    assert_ne!(ok.attributes[0].key, "a");
+                }
+                other => panic!("expected a v1 output, got {:?}", other),
+            }
+
+            // the flag is passed up with the output, so it survives a round trip
+            let encrypted = serde_json::to_vec(&encrypted).unwrap();
+            assert_eq!(
+                decrypt_output_with_key(&encrypted, &key, &OutputCompression::default()).unwrap(),
+                output
+            );
+        }
+
+        // the reply's callback signature is created over the flag, but only when it's set, so
+        // other replies are signed the same as before
+        let reply = |is_data_plaintext| Reply {
+            id: Binary(b"id".to_vec()),
+            result: SubMsgResult::Ok(SubMsgResponse {
+                events: vec![],
+                data: Some(Binary::from(&b"data"[..])),
+            }),
+            was_orig_msg_encrypted: true,
+            is_encrypted: true,
+            is_data_plaintext,
+            block_height: None,
+            tx_index: None,
+        };
+        assert_eq!(
+            serde_json::to_value(&reply(false)).unwrap(),
+            json!({"id":"aWQ=","result":{"ok":{"events":[],"data":"ZGF0YQ=="}},"was_orig_msg_encrypted":true,"is_encrypted":true})
+        );
+        assert_eq!(
+            serde_json::to_value(&reply(true)).unwrap()["is_data_plaintext"],
+            json!(true)
+        );
+    }
+
+    pub fn test_error_reply_drops_data() {
+        // a contract that sets data next to its error
+        let output: RawWasmOutput = serde_json::from_str(
+            r#"{"Err":{"generic_err":{"msg":"failed"}},"data":"ZGF0YQ==","internal_msg_id":null,"internal_reply_enclave_sig":null}"#,
+        )
+        .unwrap();
+        assert!(matches!(output, RawWasmOutput::Err { .. }));
+
+        let (result, should_append_reply_params) = reply_result_for_output(&output).unwrap();
+        assert_eq!(result, SubMsgResult::Err("failed".to_string()));
+        assert!(should_append_reply_params);
+        // the reply the callback signature is created over is the same as without the data
+        assert_eq!(
+            serde_json::to_value(&result).unwrap(),
+            json!({"error": "failed"})
+        );
+
+        let ok_output: RawWasmOutput = serde_json::from_str(
+            r#"{"Ok":{"messages":[],"attributes":[],"events":[],"data":"ZGF0YQ=="},"internal_msg_id":null,"internal_reply_enclave_sig":null}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            reply_result_for_output(&ok_output).unwrap().0,
+            SubMsgResult::Ok(SubMsgResponse {
+                events: vec![],
+                data: Some(Binary::from(&b"data"[..])),
+            })
+        );
+    }
+}
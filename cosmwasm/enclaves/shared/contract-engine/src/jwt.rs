@@ -0,0 +1,251 @@
+use std::convert::TryFrom;
+
+use log::*;
+use ring::signature::{UnparsedPublicKey, ECDSA_P256_SHA256_FIXED};
+use serde_json::Value;
+
+use enclave_crypto::WasmApiCryptoError;
+
+const ES256: &str = "ES256";
+const EDDSA: &str = "EdDSA";
+
+const NANOS_PER_SECOND: u64 = 1_000_000_000;
+
+fn decode_segment(segment: &str) -> Result<Vec<u8>, WasmApiCryptoError> {
+    base64::decode_config(segment, base64::URL_SAFE_NO_PAD).map_err(|err| {
+        debug!("jwt segment is not valid base64url: {:?}", err);
+        WasmApiCryptoError::InvalidJwt
+    })
+}
+
+fn parse_json_segment(segment: &str) -> Result<Value, WasmApiCryptoError> {
+    serde_json::from_slice(&decode_segment(segment)?).map_err(|err| {
+        debug!("jwt segment is not valid json: {:?}", err);
+        WasmApiCryptoError::InvalidJwt
+    })
+}
+
+fn jwk_field<'a>(jwk: &'a Value, field: &str) -> Result<&'a str, WasmApiCryptoError> {
+    jwk.get(field).and_then(Value::as_str).ok_or_else(|| {
+        debug!("jwk is missing the {:?} field", field);
+        WasmApiCryptoError::InvalidPubkeyFormat
+    })
+}
+
+fn jwk_coordinate(jwk: &Value, field: &str, len: usize) -> Result<Vec<u8>, WasmApiCryptoError> {
+    let coordinate = base64::decode_config(jwk_field(jwk, field)?, base64::URL_SAFE_NO_PAD)
+        .map_err(|_| WasmApiCryptoError::InvalidPubkeyFormat)?;
+    if coordinate.len() != len {
+        debug!("jwk {:?} has the wrong length {}", field, coordinate.len());
+        return Err(WasmApiCryptoError::InvalidPubkeyFormat);
+    }
+    Ok(coordinate)
+}
+
+fn verify_signature(
+    alg: &str,
+    jwk: &Value,
+    signing_input: &[u8],
+    signature: &[u8],
+) -> Result<(), WasmApiCryptoError> {
+    if let Some(jwk_alg) = jwk.get("alg").and_then(Value::as_str) {
+        if jwk_alg != alg {
+            debug!("jwk is for {:?} but the token uses {:?}", jwk_alg, alg);
+            return Err(WasmApiCryptoError::InvalidPubkeyFormat);
+        }
+    }
+
+    match alg {
+        ES256 => {
+            if jwk_field(jwk, "kty")? != "EC" || jwk_field(jwk, "crv")? != "P-256" {
+                return Err(WasmApiCryptoError::InvalidPubkeyFormat);
+            }
+            if signature.len() != 64 {
+                return Err(WasmApiCryptoError::InvalidSignatureFormat);
+            }
+
+            // uncompressed SEC1 point
+            let mut pubkey = vec![0x04];
+            pubkey.extend(jwk_coordinate(jwk, "x", 32)?);
+            pubkey.extend(jwk_coordinate(jwk, "y", 32)?);
+
+            UnparsedPublicKey::new(&ECDSA_P256_SHA256_FIXED, &pubkey)
+                .verify(signing_input, signature)
+                .map_err(|_| WasmApiCryptoError::JwtVerificationFailed)
+        }
+        EDDSA => {
+            if jwk_field(jwk, "kty")? != "OKP" || jwk_field(jwk, "crv")? != "Ed25519" {
+                return Err(WasmApiCryptoError::InvalidPubkeyFormat);
+            }
+
+            let pubkey = ed25519_zebra::VerificationKey::try_from(
+                jwk_coordinate(jwk, "x", 32)?.as_slice(),
+            )
+            .map_err(|_| WasmApiCryptoError::InvalidPubkeyFormat)?;
+            let signature = ed25519_zebra::Signature::try_from(signature)
+                .map_err(|_| WasmApiCryptoError::InvalidSignatureFormat)?;
+
+            pubkey
+                .verify(&signature, signing_input)
+                .map_err(|_| WasmApiCryptoError::JwtVerificationFailed)
+        }
+        other => {
+            // "none" and the HMAC algorithms are deliberately not supported
+            debug!("jwt uses an unsupported algorithm {:?}", other);
+            Err(WasmApiCryptoError::InvalidJwt)
+        }
+    }
+}
+
+/// Verifies a compact JWS `token` against `jwk` and returns its claims as JSON.
+///
+/// Supports ES256 and EdDSA (Ed25519). The `exp` and `nbf` claims, if present, are checked
+/// against `block_time`, which is in nanoseconds like `env.block.time`.
+pub fn verify_jwt(
+    token: &[u8],
+    jwk: &[u8],
+    block_time: u64,
+) -> Result<Vec<u8>, WasmApiCryptoError> {
+    let token = std::str::from_utf8(token).map_err(|_| WasmApiCryptoError::InvalidJwt)?;
+    let jwk: Value =
+        serde_json::from_slice(jwk).map_err(|_| WasmApiCryptoError::InvalidPubkeyFormat)?;
+
+    let segments: Vec<&str> = token.split('.').collect();
+    if segments.len() != 3 {
+        debug!("jwt has {} segments instead of 3", segments.len());
+        return Err(WasmApiCryptoError::InvalidJwt);
+    }
+
+    let header = parse_json_segment(segments[0])?;
+    let alg = header
+        .get("alg")
+        .and_then(Value::as_str)
+        .ok_or(WasmApiCryptoError::InvalidJwt)?;
+
+    let signing_input_len = segments[0].len() + 1 + segments[1].len();
+    let signing_input = &token.as_bytes()[..signing_input_len];
+    let signature = decode_segment(segments[2])?;
+    verify_signature(alg, &jwk, signing_input, &signature)?;
+
+    let claims_bytes = decode_segment(segments[1])?;
+    let claims: Value = serde_json::from_slice(&claims_bytes).map_err(|err| {
+        debug!("jwt claims are not valid json: {:?}", err);
+        WasmApiCryptoError::InvalidJwt
+    })?;
+
+    let now = block_time / NANOS_PER_SECOND;
+    let numeric_date = |claim: &str| match claims.get(claim) {
+        None => Ok(None),
+        Some(value) => value.as_u64().map(Some).ok_or_else(|| {
+            debug!("jwt {:?} claim is not a numeric date", claim);
+            WasmApiCryptoError::InvalidJwt
+        }),
+    };
+
+    if let Some(exp) = numeric_date("exp")? {
+        if now >= exp {
+            debug!("jwt expired at {} (block time {})", exp, now);
+            return Err(WasmApiCryptoError::JwtExpired);
+        }
+    }
+    if let Some(nbf) = numeric_date("nbf")? {
+        if now < nbf {
+            debug!("jwt is not valid before {} (block time {})", nbf, now);
+            return Err(WasmApiCryptoError::JwtNotYetValid);
+        }
+    }
+
+    Ok(claims_bytes)
+}
+
+#[cfg(feature = "test")]
+pub mod tests {
+    use super::*;
+    use ring::rand::SystemRandom;
+    use ring::signature::{EcdsaKeyPair, KeyPair, ECDSA_P256_SHA256_FIXED_SIGNING};
+
+    const BLOCK_TIME: u64 = 1_700_000_000 * NANOS_PER_SECOND;
+
+    fn encode(data: &[u8]) -> String {
+        base64::encode_config(data, base64::URL_SAFE_NO_PAD)
+    }
+
+    fn signing_input(alg: &str, claims: &str) -> String {
+        let header = format!(r#"{{"alg":"{}","typ":"JWT"}}"#, alg);
+        format!("{}.{}", encode(header.as_bytes()), encode(claims.as_bytes()))
+    }
+
+    fn eddsa_token(claims: &str) -> (String, String) {
+        let signing_key = ed25519_zebra::SigningKey::from([3u8; 32]);
+        let pubkey: [u8; 32] = ed25519_zebra::VerificationKey::from(&signing_key).into();
+        let jwk = format!(r#"{{"kty":"OKP","crv":"Ed25519","x":"{}"}}"#, encode(&pubkey));
+
+        let input = signing_input(EDDSA, claims);
+        let signature: [u8; 64] = signing_key.sign(input.as_bytes()).into();
+        (format!("{}.{}", input, encode(&signature)), jwk)
+    }
+
+    fn es256_token(claims: &str) -> (String, String) {
+        let rng = SystemRandom::new();
+        let pkcs8 = EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &rng).unwrap();
+        let key_pair =
+            EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, pkcs8.as_ref()).unwrap();
+        let pubkey = key_pair.public_key().as_ref();
+        let jwk = format!(
+            r#"{{"kty":"EC","crv":"P-256","x":"{}","y":"{}"}}"#,
+            encode(&pubkey[1..33]),
+            encode(&pubkey[33..65])
+        );
+
+        let input = signing_input(ES256, claims);
+        let signature = key_pair.sign(&rng, input.as_bytes()).unwrap();
+        (format!("{}.{}", input, encode(signature.as_ref())), jwk)
+    }
+
+    pub fn test_verify_jwt_valid() {
+        let claims = r#"{"sub":"alice","nbf":1600000000,"exp":1800000000}"#;
+
+        for (token, jwk) in [eddsa_token(claims), es256_token(claims)] {
+            let result = verify_jwt(token.as_bytes(), jwk.as_bytes(), BLOCK_TIME).unwrap();
+            assert_eq!(result, claims.as_bytes());
+        }
+    }
+
+    pub fn test_verify_jwt_expired() {
+        let claims = r#"{"sub":"alice","exp":1600000000}"#;
+
+        for (token, jwk) in [eddsa_token(claims), es256_token(claims)] {
+            let result = verify_jwt(token.as_bytes(), jwk.as_bytes(), BLOCK_TIME);
+            assert!(matches!(result, Err(WasmApiCryptoError::JwtExpired)));
+        }
+
+        let (token, jwk) = eddsa_token(r#"{"sub":"alice","nbf":1800000000}"#);
+        let result = verify_jwt(token.as_bytes(), jwk.as_bytes(), BLOCK_TIME);
+        assert!(matches!(result, Err(WasmApiCryptoError::JwtNotYetValid)));
+    }
+
+    pub fn test_verify_jwt_rejects_tampering() {
+        let (token, jwk) = eddsa_token(r#"{"sub":"alice"}"#);
+        let (_, other_jwk) = es256_token(r#"{"sub":"alice"}"#);
+
+        // claims swapped for different ones
+        let segments: Vec<&str> = token.split('.').collect();
+        let tampered = format!(
+            "{}.{}.{}",
+            segments[0],
+            encode(br#"{"sub":"mallory"}"#),
+            segments[2]
+        );
+        let result = verify_jwt(tampered.as_bytes(), jwk.as_bytes(), BLOCK_TIME);
+        assert!(matches!(result, Err(WasmApiCryptoError::JwtVerificationFailed)));
+
+        // key of the wrong type for the algorithm
+        let result = verify_jwt(token.as_bytes(), other_jwk.as_bytes(), BLOCK_TIME);
+        assert!(matches!(result, Err(WasmApiCryptoError::InvalidPubkeyFormat)));
+
+        // unsigned token
+        let unsigned = format!("{}.", signing_input("none", r#"{"sub":"alice"}"#));
+        let result = verify_jwt(unsigned.as_bytes(), jwk.as_bytes(), BLOCK_TIME);
+        assert!(matches!(result, Err(WasmApiCryptoError::InvalidJwt)));
+    }
+}
@@ -0,0 +1,382 @@
+//! Verification of Cosmos SDK legacy amino multisig signatures, backing the
+//! `verify_legacy_multisig` host function, so contracts can authenticate multisig accounts.
+//!
+//! The inputs are the protobuf encodings the SDK uses: a `LegacyAminoPubKey` with the threshold
+//! and the member keys, a `CompactBitArray` marking which members signed, and a `MultiSignature`
+//! with one signature per set bit, in the order of the bits. Only secp256k1 members are supported,
+//! and every member signs the same message.
+
+use log::*;
+
+use protobuf::Message;
+
+use cosmos_proto::crypto::multisig::{CompactBitArray, LegacyAminoPubKey, MultiSignature};
+use cosmos_proto::crypto::secp256k1::PubKey;
+use enclave_crypto::{sha_256, WasmApiCryptoError};
+
+const TYPE_URL_SECP256K1_PUBKEY: &str = "/cosmos.crypto.secp256k1.PubKey";
+/// r (32 bytes) || s (32 bytes)
+pub const MULTISIG_MEMBER_SIGNATURE_SIZE: usize = 64;
+
+/// A parsed multisig signature, checked for consistency with the multisig key but not verified
+pub struct LegacyMultisig {
+    threshold: usize,
+    members: Vec<secp256k1::PublicKey>,
+    /// The indexes of the members that signed, in the order of their signatures
+    signers: Vec<usize>,
+    signatures: Vec<Vec<u8>>,
+}
+
+impl LegacyMultisig {
+    /// Parses the protobuf encoded `LegacyAminoPubKey`, `CompactBitArray` and `MultiSignature`.
+    /// Keys and signatures that don't match each other are errors.
+    pub fn parse(
+        pubkey: &[u8],
+        bitarray: &[u8],
+        signatures: &[u8],
+    ) -> Result<Self, WasmApiCryptoError> {
+        let pubkey = LegacyAminoPubKey::parse_from_bytes(pubkey).map_err(|err| {
+            debug!("verify_legacy_multisig() got a malformed pubkey: {:?}", err);
+            WasmApiCryptoError::InvalidPubkeyFormat
+        })?;
+        let bitarray = CompactBitArray::parse_from_bytes(bitarray).map_err(|err| {
+            debug!(
+                "verify_legacy_multisig() got a malformed bitarray: {:?}",
+                err
+            );
+            WasmApiCryptoError::InvalidSignatureFormat
+        })?;
+        let signatures = MultiSignature::parse_from_bytes(signatures).map_err(|err| {
+            debug!(
+                "verify_legacy_multisig() got a malformed multisignature: {:?}",
+                err
+            );
+            WasmApiCryptoError::InvalidSignatureFormat
+        })?;
+
+        let members = parse_members(&pubkey)?;
+        let threshold = pubkey.threshold as usize;
+        if threshold == 0 || threshold > members.len() {
+            debug!(
+                "verify_legacy_multisig() got a threshold of {} for {} members",
+                threshold,
+                members.len()
+            );
+            return Err(WasmApiCryptoError::InvalidPubkeyFormat);
+        }
+
+        let signers = set_bits(&bitarray, members.len())?;
+        let signatures = signatures.signatures.into_vec();
+        if signers.len() != signatures.len() {
+            debug!(
+                "verify_legacy_multisig() got {} signatures for {} set bits",
+                signatures.len(),
+                signers.len()
+            );
+            return Err(WasmApiCryptoError::InvalidSignatureFormat);
+        }
+        if let Some(signature) = signatures
+            .iter()
+            .find(|signature| signature.len() != MULTISIG_MEMBER_SIGNATURE_SIZE)
+        {
+            debug!(
+                "verify_legacy_multisig() got a signature of {} bytes",
+                signature.len()
+            );
+            return Err(WasmApiCryptoError::InvalidSignatureFormat);
+        }
+
+        Ok(Self {
+            threshold,
+            members,
+            signers,
+            signatures,
+        })
+    }
+
+    /// The number of member signatures that verifying checks
+    pub fn signature_count(&self) -> usize {
+        self.signatures.len()
+    }
+
+    /// Returns whether at least the threshold of members signed `msg`, and all the signatures are
+    /// valid
+    pub fn verify(&self, msg: &[u8]) -> Result<bool, WasmApiCryptoError> {
+        if self.signers.len() < self.threshold {
+            return Ok(false);
+        }
+
+        let message = secp256k1::Message::from_slice(&sha_256(msg))
+            .map_err(|_| WasmApiCryptoError::GenericErr)?;
+        let secp = secp256k1::Secp256k1::verification_only();
+
+        for (signer, signature) in self.signers.iter().zip(&self.signatures) {
+            let signature = match secp256k1::ecdsa::Signature::from_compact(signature) {
+                Ok(signature) => signature,
+                Err(_) => return Err(WasmApiCryptoError::InvalidSignatureFormat),
+            };
+            // high-S signatures are rejected, like the SDK does for transactions
+            if secp
+                .verify_ecdsa(&message, &signature, &self.members[*signer])
+                .is_err()
+            {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+}
+
+/// Returns whether `signatures` are a valid multisig signature of `msg` by `pubkey`, for the
+/// members marked in `bitarray`. Inputs that are malformed or don't match each other are errors,
+/// while invalid signatures or too few signers are `Ok(false)`.
+pub fn verify_legacy_multisig(
+    pubkey: &[u8],
+    bitarray: &[u8],
+    signatures: &[u8],
+    msg: &[u8],
+) -> Result<bool, WasmApiCryptoError> {
+    LegacyMultisig::parse(pubkey, bitarray, signatures)?.verify(msg)
+}
+
+/// The secp256k1 member keys of `pubkey`. A member that appears twice could sign for both of its
+/// bits and count twice towards the threshold, so such keys are rejected.
+fn parse_members(
+    pubkey: &LegacyAminoPubKey,
+) -> Result<Vec<secp256k1::PublicKey>, WasmApiCryptoError> {
+    let mut members: Vec<secp256k1::PublicKey> = Vec::with_capacity(pubkey.public_keys.len());
+
+    for member in pubkey.public_keys.iter() {
+        if member.type_url != TYPE_URL_SECP256K1_PUBKEY {
+            debug!(
+                "verify_legacy_multisig() got a member of unsupported type {}",
+                member.type_url
+            );
+            return Err(WasmApiCryptoError::InvalidPubkeyFormat);
+        }
+        let member = PubKey::parse_from_bytes(&member.value)
+            .ok()
+            .and_then(|member| secp256k1::PublicKey::from_slice(&member.key).ok())
+            .ok_or_else(|| {
+                debug!("verify_legacy_multisig() got a malformed secp256k1 member");
+                WasmApiCryptoError::InvalidPubkeyFormat
+            })?;
+
+        if members.contains(&member) {
+            debug!("verify_legacy_multisig() got a key with a duplicate member");
+            return Err(WasmApiCryptoError::InvalidPubkeyFormat);
+        }
+        members.push(member);
+    }
+
+    Ok(members)
+}
+
+/// The indexes of the set bits of `bitarray`, which must have a bit for each of `member_count`
+/// members. Bits are stored most significant first, as the SDK does.
+fn set_bits(
+    bitarray: &CompactBitArray,
+    member_count: usize,
+) -> Result<Vec<usize>, WasmApiCryptoError> {
+    let extra_bits = bitarray.extra_bits_stored as usize;
+    let bit_count = match (bitarray.elems.len(), extra_bits) {
+        (elems, 0) => elems * 8,
+        (elems, extra) if elems > 0 && extra < 8 => (elems - 1) * 8 + extra,
+        _ => {
+            debug!(
+                "verify_legacy_multisig() got a bitarray of {} bytes with {} extra bits",
+                bitarray.elems.len(),
+                extra_bits
+            );
+            return Err(WasmApiCryptoError::InvalidSignatureFormat);
+        }
+    };
+    if bit_count != member_count {
+        debug!(
+            "verify_legacy_multisig() got a bitarray of {} bits for {} members",
+            bit_count, member_count
+        );
+        return Err(WasmApiCryptoError::InvalidSignatureFormat);
+    }
+
+    let is_set = |index: usize| bitarray.elems[index / 8] & (0x80 >> (index % 8)) != 0;
+
+    // the padding of the last byte isn't part of the array
+    if (bit_count..bitarray.elems.len() * 8).any(is_set) {
+        debug!("verify_legacy_multisig() got a bitarray with padding bits set");
+        return Err(WasmApiCryptoError::InvalidSignatureFormat);
+    }
+
+    Ok((0..bit_count).filter(|index| is_set(*index)).collect())
+}
+
+#[cfg(feature = "test")]
+pub mod tests {
+    use super::*;
+
+    use protobuf::well_known_types::Any;
+    use protobuf::RepeatedField;
+
+    const MSG: &[u8] = b"a message for the multisig";
+
+    fn secret_key(i: u8) -> secp256k1::SecretKey {
+        secp256k1::SecretKey::from_slice(&[i; 32]).unwrap()
+    }
+
+    fn member(secret_key: &secp256k1::SecretKey) -> Any {
+        let secp = secp256k1::Secp256k1::signing_only();
+        let mut key = PubKey::new();
+        key.key = secp256k1::PublicKey::from_secret_key(&secp, secret_key)
+            .serialize()
+            .to_vec();
+
+        let mut member = Any::new();
+        member.type_url = TYPE_URL_SECP256K1_PUBKEY.to_string();
+        member.value = key.write_to_bytes().unwrap();
+        member
+    }
+
+    fn multisig_key(threshold: u32, members: Vec<Any>) -> Vec<u8> {
+        let mut pubkey = LegacyAminoPubKey::new();
+        pubkey.threshold = threshold;
+        pubkey.public_keys = RepeatedField::from_vec(members);
+        pubkey.write_to_bytes().unwrap()
+    }
+
+    fn bitarray(extra_bits_stored: u32, elems: Vec<u8>) -> Vec<u8> {
+        let mut bitarray = CompactBitArray::new();
+        bitarray.extra_bits_stored = extra_bits_stored;
+        bitarray.elems = elems;
+        bitarray.write_to_bytes().unwrap()
+    }
+
+    fn sign(secret_key: &secp256k1::SecretKey, msg: &[u8]) -> Vec<u8> {
+        let message = secp256k1::Message::from_slice(&sha_256(msg)).unwrap();
+        secp256k1::Secp256k1::signing_only()
+            .sign_ecdsa(&message, secret_key)
+            .serialize_compact()
+            .to_vec()
+    }
+
+    fn multisignature(signatures: Vec<Vec<u8>>) -> Vec<u8> {
+        let mut multisignature = MultiSignature::new();
+        multisignature.signatures = RepeatedField::from_vec(signatures);
+        multisignature.write_to_bytes().unwrap()
+    }
+
+    /// A 2-of-3 key and the signatures of its first and third members on `MSG`, with the
+    /// bitarray 0b101
+    fn two_of_three() -> (Vec<u8>, Vec<u8>, Vec<u8>) {
+        let pubkey = multisig_key(2, (1..=3).map(|i| member(&secret_key(i))).collect());
+        let signatures = multisignature(vec![sign(&secret_key(1), MSG), sign(&secret_key(3), MSG)]);
+
+        (pubkey, bitarray(3, vec![0b1010_0000]), signatures)
+    }
+
+    pub fn test_verify_legacy_multisig_two_of_three() {
+        let (pubkey, bits, signatures) = two_of_three();
+
+        assert!(verify_legacy_multisig(&pubkey, &bits, &signatures, MSG).unwrap());
+        assert!(!verify_legacy_multisig(&pubkey, &bits, &signatures, b"other message").unwrap());
+
+        // the signatures are attributed to the members by the bits
+        assert!(!verify_legacy_multisig(
+            &pubkey,
+            &bitarray(3, vec![0b0110_0000]),
+            &signatures,
+            MSG
+        )
+        .unwrap());
+
+        // all three members is more than enough
+        let all = multisignature((1..=3).map(|i| sign(&secret_key(i), MSG)).collect());
+        assert!(
+            verify_legacy_multisig(&pubkey, &bitarray(3, vec![0b1110_0000]), &all, MSG).unwrap()
+        );
+
+        // a single member is below the threshold, even with a valid signature
+        let one = multisignature(vec![sign(&secret_key(2), MSG)]);
+        assert!(
+            !verify_legacy_multisig(&pubkey, &bitarray(3, vec![0b0100_0000]), &one, MSG).unwrap()
+        );
+    }
+
+    pub fn test_verify_legacy_multisig_malformed_inputs() {
+        let (pubkey, bits, signatures) = two_of_three();
+
+        // a bitarray with a bit too many or too few for the 3 members
+        for bits in [
+            bitarray(4, vec![0b1010_0000]),
+            bitarray(2, vec![0b1000_0000]),
+            bitarray(0, vec![0b1010_0000]),
+            bitarray(3, vec![]),
+            bitarray(8, vec![0b1010_0000]),
+        ] {
+            assert!(matches!(
+                verify_legacy_multisig(&pubkey, &bits, &signatures, MSG),
+                Err(WasmApiCryptoError::InvalidSignatureFormat)
+            ));
+        }
+        // a set padding bit past the members
+        assert!(matches!(
+            verify_legacy_multisig(&pubkey, &bitarray(3, vec![0b1010_1000]), &signatures, MSG),
+            Err(WasmApiCryptoError::InvalidSignatureFormat)
+        ));
+        // set bits and signatures that don't match up
+        assert!(matches!(
+            verify_legacy_multisig(&pubkey, &bitarray(3, vec![0b1110_0000]), &signatures, MSG),
+            Err(WasmApiCryptoError::InvalidSignatureFormat)
+        ));
+        let short = multisignature(vec![sign(&secret_key(1), MSG), vec![0u8; 63]]);
+        assert!(matches!(
+            verify_legacy_multisig(&pubkey, &bits, &short, MSG),
+            Err(WasmApiCryptoError::InvalidSignatureFormat)
+        ));
+
+        // thresholds the members can't meet
+        let members = || (1..=3).map(|i| member(&secret_key(i))).collect();
+        for threshold in [0, 4] {
+            assert!(matches!(
+                verify_legacy_multisig(
+                    &multisig_key(threshold, members()),
+                    &bits,
+                    &signatures,
+                    MSG
+                ),
+                Err(WasmApiCryptoError::InvalidPubkeyFormat)
+            ));
+        }
+
+        // a member twice would let it sign for two of the bits
+        let duplicate = multisig_key(
+            2,
+            vec![
+                member(&secret_key(1)),
+                member(&secret_key(2)),
+                member(&secret_key(1)),
+            ],
+        );
+        let twice = multisignature(vec![sign(&secret_key(1), MSG), sign(&secret_key(1), MSG)]);
+        assert!(matches!(
+            verify_legacy_multisig(&duplicate, &bits, &twice, MSG),
+            Err(WasmApiCryptoError::InvalidPubkeyFormat)
+        ));
+
+        // members that aren't secp256k1 keys
+        let mut unsupported = member(&secret_key(3));
+        unsupported.type_url = "/cosmos.crypto.ed25519.PubKey".to_string();
+        let unsupported = multisig_key(
+            2,
+            vec![member(&secret_key(1)), member(&secret_key(2)), unsupported],
+        );
+        assert!(matches!(
+            verify_legacy_multisig(&unsupported, &bits, &signatures, MSG),
+            Err(WasmApiCryptoError::InvalidPubkeyFormat)
+        ));
+        assert!(matches!(
+            verify_legacy_multisig(b"\xff", &bits, &signatures, MSG),
+            Err(WasmApiCryptoError::InvalidPubkeyFormat)
+        ));
+    }
+}
@@ -8,35 +8,76 @@ extern crate sgx_tstd as std;
 extern crate sgx_rand;
 extern crate sgx_types;
 
+mod adr036;
+mod block_view;
+mod bls;
+mod coins;
+mod compression;
+mod contract_address;
 mod contract_operations;
 mod contract_validation;
 mod cosmwasm_config;
 mod db;
+mod derive_id;
+mod derive_nonce;
 mod errors;
+mod eth_personal_sign;
+mod evidence;
 mod execute_message;
 pub mod external;
+mod fixed_point;
 mod gas;
+mod groth16;
 mod ibc_denom_utils;
 mod ibc_message;
 mod input_validation;
 mod io;
+mod jwt;
+mod legacy_multisig;
 mod message;
 mod message_utils;
+mod namespace;
+mod output_log;
+mod padding;
+mod query_cache;
+mod query_path;
 mod query_chain;
 mod random;
 mod reply_message;
+mod same_session;
+mod secp256k1_der;
+mod secp256r1;
+mod secure_remove;
+mod signed_payload;
+pub mod state_snapshot;
+mod storage_cas;
+mod storage_list;
+mod storage_map;
+mod storage_range;
+mod storage_wipe;
 mod hardcoded_admins;
 pub(crate) mod types;
 #[cfg(feature = "wasm3")]
 pub mod wasm3;
+mod webauthn;
 
-pub use contract_operations::{handle, init, query};
+pub use contract_operations::{configure_attestation_type, handle, init, query};
+pub use cw_types_v1::types::AttestationType;
+pub use io::{decrypt_output, RawWasmOutput};
 #[cfg(feature = "light-client-validation")]
 pub use contract_validation::{check_cert_in_current_block, check_tx_in_current_block};
 
 #[cfg(feature = "test")]
 pub mod tests {
-    use crate::types;
+    use crate::external::results;
+    use crate::{
+        adr036, block_view, bls, coins, compression, contract_address, contract_operations,
+        contract_validation, db, derive_id, derive_nonce, eth_personal_sign, evidence, fixed_point,
+        groth16, ibc_denom_utils, ibc_message, io, jwt, legacy_multisig, namespace, output_log,
+        padding, query_cache, query_chain, query_path, same_session, secp256k1_der, secp256r1,
+        secure_remove, signed_payload, state_snapshot, storage_cas, storage_list, storage_map,
+        storage_range, storage_wipe, types, webauthn,
+    };
 
     /// Catch failures like the standard test runner, and print similar information per test.
     /// Tests can only fail by panicking, not by returning a `Result` type.
@@ -62,6 +103,147 @@ pub mod tests {
 
         count_failures!(failures, {
             types::tests::test_new_from_slice();
+            query_chain::tests::test_recursion_limit_error_includes_contract();
+            query_chain::tests::test_recursion_limit_boundary();
+            query_chain::tests::test_recursion_limit_configured();
+            query_chain::tests::test_recursion_limit_error_without_contract_is_unchanged();
+            results::tests::test_query_output_below_threshold_is_inline();
+            results::tests::test_query_output_above_threshold_is_chunked();
+            evidence::tests::test_parse_vote_sign_bytes();
+            evidence::tests::test_double_sign_evidence_conflicting_votes();
+            evidence::tests::test_double_sign_evidence_rejects_non_conflicting_votes();
+            evidence::tests::test_double_sign_evidence_rejects_other_signer();
+            ibc_denom_utils::tests::test_resolve_ibc_denom_known_hash();
+            ibc_denom_utils::tests::test_resolve_ibc_denom_unknown_hash();
+            ibc_denom_utils::tests::test_resolve_ibc_denom_invalid_denom();
+            jwt::tests::test_verify_jwt_valid();
+            jwt::tests::test_verify_jwt_expired();
+            jwt::tests::test_verify_jwt_rejects_tampering();
+            namespace::tests::test_namespace_matches_prefixed_storage();
+            namespace::tests::test_namespaces_dont_collide();
+            namespace::tests::test_empty_keys_and_values();
+            padding::tests::test_padding_buckets();
+            padding::tests::test_padding_round_trip();
+            secp256k1_der::tests::test_secp256k1_verify_der_valid();
+            secp256k1_der::tests::test_secp256k1_verify_der_malformed();
+            secp256k1_der::tests::test_secp256k1_verify_der_high_s();
+            secp256r1::tests::test_secp256r1_verify_rfc6979_vectors();
+            secp256r1::tests::test_secp256r1_verify_rejects_other_message();
+            secp256r1::tests::test_secp256r1_verify_malformed_inputs();
+            signed_payload::tests::test_verify_signed_payload_fresh();
+            signed_payload::tests::test_verify_signed_payload_stale();
+            signed_payload::tests::test_verify_signed_payload_bad_signature();
+            state_snapshot::tests::test_state_snapshot_round_trip();
+            state_snapshot::tests::test_state_snapshot_rejects_other_nodes();
+            state_snapshot::tests::test_state_snapshot_binds_contract();
+            fixed_point::tests::test_checked_mul_div();
+            fixed_point::tests::test_checked_mul_div_errors();
+            fixed_point::tests::test_sqrt();
+            fixed_point::tests::test_isqrt_is_exact();
+            storage_list::tests::test_storage_list_push_pop_ordering();
+            storage_list::tests::test_storage_list_len();
+            storage_list::tests::test_storage_list_raw_compatibility();
+            storage_list::tests::test_storage_list_names_dont_collide();
+            storage_map::tests::test_storage_map_insertion_order();
+            storage_map::tests::test_storage_map_remove();
+            storage_map::tests::test_storage_map_stable_iteration_order();
+            storage_map::tests::test_storage_map_names_dont_collide();
+            storage_map::tests::test_storage_map_encode_entry();
+            storage_range::tests::test_storage_range_order();
+            storage_range::tests::test_storage_range_bounds();
+            storage_range::tests::test_storage_range_merges_unflushed_writes();
+            secure_remove::tests::test_secure_remove_key_is_absent_after();
+            secure_remove::tests::test_secure_remove_reports_leftover_key();
+            storage_cas::tests::test_storage_cas_swaps_expected_value();
+            storage_cas::tests::test_storage_cas_keeps_unexpected_value();
+            storage_wipe::tests::test_storage_wipe_prefix_end();
+            storage_wipe::tests::test_storage_wipe_prefix_removes_all_keys();
+            storage_wipe::tests::test_storage_wipe_prefix_charges_before_removing();
+            contract_operations::tests::test_env_has_no_build_dependent_fields();
+            contract_operations::tests::test_env_attestation_type();
+            contract_operations::tests::test_attestation_type_round_trip();
+            contract_operations::tests::test_env_tx_msg_position();
+            contract_operations::tests::test_query_output_gas_is_top_level_only();
+            contract_validation::tests::test_validate_msg_matching_code_hash();
+            contract_validation::tests::test_validate_msg_code_hash_mismatch();
+            contract_validation::tests::test_validate_msg_gas_limit_header();
+            io::tests::test_callback_signature_without_secret();
+            io::tests::test_callback_signature_with_secret();
+            io::tests::test_callback_signature_domain_separation();
+            io::tests::test_verify_callback_sig();
+            io::tests::test_reply_signature_bound_to_tx();
+            io::tests::test_reply_propagation_per_submsg();
+            io::tests::test_reply_depth_limit();
+            io::tests::test_reply_headers_per_reply_on();
+            io::tests::test_reply_material_only_for_replied_results();
+            io::tests::test_three_level_reply_chain();
+            io::tests::test_reply_depth_across_reply_modes();
+            io::tests::test_reply_depth_across_immediate_replies();
+            io::tests::test_output_encryption_errors();
+            io::tests::test_submsg_gas_limits_are_capped();
+            io::tests::test_query_output_echoes_nonce();
+            io::tests::test_attribute_plaintext_copy();
+            io::tests::test_deterministic_attributes_are_sorted();
+            io::tests::test_encrypt_attributes_batch();
+            compression::tests::test_compression_round_trip_empty();
+            compression::tests::test_compression_round_trip_compressible();
+            compression::tests::test_compression_round_trip_already_compressed();
+            compression::tests::test_compression_skipped_below_threshold();
+            compression::tests::test_compression_follows_version();
+            compression::tests::test_decompression_rejects_unknown_marker();
+            io::tests::test_submsg_count_limit();
+            io::tests::test_submsg_empty_msg_is_rejected();
+            io::tests::test_out_of_gas_output_is_an_error();
+            io::tests::test_event_type_length_limit();
+            io::tests::test_output_depth_limit();
+            io::tests::test_output_deserialization_error();
+            io::tests::test_decrypt_output_round_trip();
+            io::tests::test_padded_output_lengths();
+            io::tests::test_ibc_ack_encryption();
+            io::tests::test_plaintext_data();
+            io::tests::test_error_reply_drops_data();
+            db::tests::test_multiple_read_keys_encoding();
+            db::tests::test_multiple_read_values_decoding();
+            query_cache::tests::test_query_cache_hits_same_query();
+            query_cache::tests::test_query_cache_charges_like_uncached_queries();
+            query_cache::tests::test_query_cache_misses_over_gas_limit();
+            query_cache::tests::test_query_cache_skips_errors();
+            query_path::tests::test_query_path_detects_cycles();
+            query_path::tests::test_query_path_ids_are_framed();
+            ibc_message::tests::test_ibc_receive_encrypted_packet();
+            ibc_message::tests::test_ibc_receive_unmarked_encrypted_packet();
+            ibc_message::tests::test_ibc_receive_plaintext_packet();
+            block_view::tests::test_block_view_matches_env();
+            derive_id::tests::test_derive_id_counter_makes_ids_unique();
+            derive_id::tests::test_derive_id_is_deterministic();
+            contract_address::tests::test_predict_contract_address_matches_chain();
+            derive_nonce::tests::test_derive_nonce_unique_within_transaction();
+            derive_nonce::tests::test_derive_nonce_is_deterministic();
+            output_log::tests::test_output_log_detects_reprocessing();
+            output_log::tests::test_output_log_evicts_least_recently_recorded();
+            same_session::tests::test_same_session_same_key();
+            same_session::tests::test_same_session_distinct_messages();
+            same_session::tests::test_same_session_different_keys();
+            same_session::tests::test_same_session_malformed_ciphertext();
+            eth_personal_sign::tests::test_verify_eth_personal_sign_known_account();
+            eth_personal_sign::tests::test_verify_eth_personal_sign_malformed_inputs();
+            webauthn::tests::test_verify_webauthn_valid_assertion();
+            webauthn::tests::test_verify_webauthn_rejects_tampering();
+            webauthn::tests::test_verify_webauthn_malformed_inputs();
+            adr036::tests::test_verify_adr036_wallet_signature();
+            adr036::tests::test_verify_adr036_malformed_inputs();
+            legacy_multisig::tests::test_verify_legacy_multisig_two_of_three();
+            legacy_multisig::tests::test_verify_legacy_multisig_malformed_inputs();
+            bls::tests::test_bls12_381_aggregate_verify_valid_aggregate();
+            bls::tests::test_bls12_381_aggregate_verify_tampered_message();
+            bls::tests::test_bls12_381_aggregate_verify_same_message();
+            bls::tests::test_bls12_381_aggregate_verify_malformed_inputs();
+            groth16::tests::test_groth16_verify_valid_proof();
+            groth16::tests::test_groth16_verify_invalid_proof();
+            groth16::tests::test_groth16_verify_malformed_inputs();
+            coins::tests::test_normalize_coins_merges_and_drops_zeros();
+            coins::tests::test_normalize_coins_rejects_invalid_coins();
+            coins::tests::test_funds_match();
         });
 
         if failures != 0 {
@@ -1,5 +1,6 @@
 use log::trace;
 
+use cw_types_v010::types::OutputCompression;
 use enclave_cosmos_types::types::HandleType;
 use enclave_ffi_types::EnclaveError;
 
@@ -9,16 +10,18 @@ use crate::ibc_message::{
     parse_plaintext_ibc_validated_message,
 };
 use crate::reply_message::parse_reply_message;
-use crate::types::ParsedMessage;
+use crate::types::{ParsedMessage, TxPosition};
 
 // Parse the message that was passed to handle (Based on the assumption that it might be a reply or IBC as well)
 pub fn parse_message(
     message: &[u8],
     handle_type: &HandleType,
+    tx_position: TxPosition,
+    compression: &OutputCompression,
 ) -> Result<ParsedMessage, EnclaveError> {
     return match handle_type {
         HandleType::HANDLE_TYPE_EXECUTE => parse_execute_message(message),
-        HandleType::HANDLE_TYPE_REPLY => parse_reply_message(message),
+        HandleType::HANDLE_TYPE_REPLY => parse_reply_message(message, tx_position, compression),
         HandleType::HANDLE_TYPE_IBC_CHANNEL_OPEN
         | HandleType::HANDLE_TYPE_IBC_CHANNEL_CONNECT
         | HandleType::HANDLE_TYPE_IBC_CHANNEL_CLOSE => {
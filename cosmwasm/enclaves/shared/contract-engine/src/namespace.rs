@@ -0,0 +1,169 @@
+//! Length-prefixed namespaces for keys that share the contract's storage, in the scheme of
+//! cosmwasm-storage's `PrefixedStorage`.
+//!
+//! Concatenating a namespace and a key without framing is ambiguous: namespace `ab` with key `c`
+//! and namespace `a` with key `bc` are both stored under `abc`. Here the namespace is preceded by
+//! its length as a big-endian u16, so the namespace can always be told apart from the key, and
+//! keys the host builds line up with the ones contracts build with `PrefixedStorage`.
+
+use log::*;
+
+use crate::errors::{WasmEngineError, WasmEngineResult};
+use crate::storage_list::{check_key, KeyValueStore};
+
+const NAMESPACE_LENGTH_SIZE: usize = 2;
+
+/// The namespace preceded by its length as a big-endian u16. Namespaces longer than `u16::MAX`
+/// can't be framed.
+pub fn to_length_prefixed(namespace: &[u8]) -> WasmEngineResult<Vec<u8>> {
+    if namespace.len() > u16::MAX as usize {
+        debug!(
+            "namespace has {} bytes, more than can be length-prefixed",
+            namespace.len()
+        );
+        return Err(WasmEngineError::SerializationError);
+    }
+
+    let mut prefixed = Vec::with_capacity(NAMESPACE_LENGTH_SIZE + namespace.len());
+    prefixed.extend_from_slice(&(namespace.len() as u16).to_be_bytes());
+    prefixed.extend_from_slice(namespace);
+    Ok(prefixed)
+}
+
+/// The key under which `key` of `namespace` is stored
+pub fn namespaced_key(namespace: &[u8], key: &[u8]) -> WasmEngineResult<Vec<u8>> {
+    let mut namespaced_key = to_length_prefixed(namespace)?;
+    namespaced_key.extend_from_slice(key);
+    Ok(namespaced_key)
+}
+
+/// A store whose keys are all under a length-prefixed namespace of another store
+pub struct PrefixedStore<'a, S: KeyValueStore> {
+    store: &'a mut S,
+    prefix: Vec<u8>,
+}
+
+impl<'a, S: KeyValueStore> PrefixedStore<'a, S> {
+    pub fn new(store: &'a mut S, namespace: &[u8]) -> WasmEngineResult<Self> {
+        Ok(Self {
+            store,
+            prefix: to_length_prefixed(namespace)?,
+        })
+    }
+
+    fn key(&self, key: &[u8]) -> Vec<u8> {
+        let mut prefixed_key = Vec::with_capacity(self.prefix.len() + key.len());
+        prefixed_key.extend_from_slice(&self.prefix);
+        prefixed_key.extend_from_slice(key);
+        prefixed_key
+    }
+}
+
+impl<'a, S: KeyValueStore> KeyValueStore for PrefixedStore<'a, S> {
+    fn get(&mut self, key: &[u8]) -> WasmEngineResult<Option<Vec<u8>>> {
+        let key = self.key(key);
+        self.store.get(&key)
+    }
+
+    fn set(&mut self, key: &[u8], value: &[u8]) -> WasmEngineResult<()> {
+        // the namespace alone isn't a key of the namespace
+        check_key(key)?;
+        let key = self.key(key);
+        self.store.set(&key, value)
+    }
+
+    fn remove(&mut self, key: &[u8]) -> WasmEngineResult<()> {
+        check_key(key)?;
+        let key = self.key(key);
+        self.store.remove(&key)
+    }
+}
+
+#[cfg(feature = "test")]
+pub mod tests {
+    use super::*;
+    use crate::storage_list::tests::MemoryStore;
+
+    pub fn test_namespace_matches_prefixed_storage() {
+        // the layout of PrefixedStorage::new(storage, b"my_prefix")
+        assert_eq!(
+            namespaced_key(b"my_prefix", b"key").unwrap(),
+            b"\x00\x09my_prefixkey".to_vec()
+        );
+        assert_eq!(to_length_prefixed(b"").unwrap(), vec![0, 0]);
+
+        let longest = vec![b'a'; u16::MAX as usize];
+        assert_eq!(
+            to_length_prefixed(&longest).unwrap()[..NAMESPACE_LENGTH_SIZE],
+            [0xff, 0xff]
+        );
+        assert!(to_length_prefixed(&[b'a'; u16::MAX as usize + 1]).is_err());
+    }
+
+    pub fn test_namespaces_dont_collide() {
+        let mut store = MemoryStore::default();
+
+        // both pairs concatenate to "my_prefixkey"
+        PrefixedStore::new(&mut store, b"my_prefix")
+            .unwrap()
+            .set(b"key", b"short prefix")
+            .unwrap();
+        PrefixedStore::new(&mut store, b"my_prefixk")
+            .unwrap()
+            .set(b"ey", b"long prefix")
+            .unwrap();
+        assert_eq!(store.0.len(), 2);
+
+        assert_eq!(
+            PrefixedStore::new(&mut store, b"my_prefix")
+                .unwrap()
+                .get(b"key")
+                .unwrap(),
+            Some(b"short prefix".to_vec())
+        );
+        assert_eq!(
+            PrefixedStore::new(&mut store, b"my_prefixk")
+                .unwrap()
+                .get(b"ey")
+                .unwrap(),
+            Some(b"long prefix".to_vec())
+        );
+
+        // and neither is the unprefixed key
+        assert_eq!(store.get(b"my_prefixkey").unwrap(), None);
+
+        PrefixedStore::new(&mut store, b"my_prefixk")
+            .unwrap()
+            .remove(b"ey")
+            .unwrap();
+        assert_eq!(
+            store.0.get(&namespaced_key(b"my_prefix", b"key").unwrap()),
+            Some(&b"short prefix".to_vec())
+        );
+    }
+
+    /// Checks the handling of empty keys and values on `store`
+    fn check_empty_keys_and_values<S: KeyValueStore>(store: &mut S) {
+        assert_eq!(store.get(b"missing").unwrap(), None);
+
+        // an empty value exists, unlike a missing one
+        store.set(b"empty", b"").unwrap();
+        assert_eq!(store.get(b"empty").unwrap(), Some(vec![]));
+        store.remove(b"empty").unwrap();
+        assert_eq!(store.get(b"empty").unwrap(), None);
+
+        assert!(matches!(
+            store.set(b"", b"value"),
+            Err(WasmEngineError::EmptyKey)
+        ));
+        assert!(matches!(store.remove(b""), Err(WasmEngineError::EmptyKey)));
+        assert_eq!(store.get(b"").unwrap(), None);
+    }
+
+    pub fn test_empty_keys_and_values() {
+        let mut store = MemoryStore::default();
+        check_empty_keys_and_values(&mut store);
+        check_empty_keys_and_values(&mut PrefixedStore::new(&mut store, b"my_prefix").unwrap());
+        assert!(store.0.is_empty());
+    }
+}
@@ -0,0 +1,102 @@
+//! A per-contract log of recent execution outputs, backing the `last_output_hash` host function,
+//! so contracts can detect requests they already processed with idempotency keys of their own.
+//!
+//! Looking a key up with `last_output_hash` also claims it for the current execution: if the
+//! execution succeeds, the hash of its (plaintext) output is recorded under the key. The log lives
+//! in the contract's state under `OUTPUT_LOG_KEY`, encrypted like the rest of the state, and only
+//! keeps the `OUTPUT_LOG_CAPACITY` most recently recorded keys, dropping the least recently
+//! recorded one when it's full. Keys are stored hashed so the log's size doesn't depend on them.
+
+use log::*;
+use serde::{Deserialize, Serialize};
+
+use enclave_crypto::{sha_256, HASH_SIZE};
+
+use crate::errors::{WasmEngineError, WasmEngineResult};
+
+/// The state key of the log. The storage host functions refuse it, so contracts can neither read
+/// the log nor overwrite it.
+pub const OUTPUT_LOG_KEY: &[u8] = b"\x00secret_output_log_v1";
+
+pub const OUTPUT_LOG_CAPACITY: usize = 64;
+
+#[derive(Serialize, Deserialize, Default, Debug, PartialEq)]
+pub struct OutputLog {
+    /// Pairs of (hash of the key, hash of the output), least recently recorded first
+    entries: Vec<([u8; HASH_SIZE], [u8; HASH_SIZE])>,
+}
+
+impl OutputLog {
+    pub fn from_slice(data: &[u8]) -> WasmEngineResult<Self> {
+        bincode2::deserialize(data).map_err(|err| {
+            debug!("output log is malformed, was its key overwritten? {:?}", err);
+            WasmEngineError::DeserializationError
+        })
+    }
+
+    pub fn to_vec(&self) -> WasmEngineResult<Vec<u8>> {
+        bincode2::serialize(self).map_err(|err| {
+            debug!("failed to serialize the output log: {:?}", err);
+            WasmEngineError::SerializationError
+        })
+    }
+
+    /// The hash of the output last recorded under `key`, if it's still in the log
+    pub fn get(&self, key: &[u8]) -> Option<[u8; HASH_SIZE]> {
+        let key_hash = sha_256(key);
+        self.entries
+            .iter()
+            .find(|(entry_key, _)| *entry_key == key_hash)
+            .map(|(_, output_hash)| *output_hash)
+    }
+
+    /// Records `output_hash` under `key`, making it the most recently recorded key
+    pub fn record(&mut self, key: &[u8], output_hash: [u8; HASH_SIZE]) {
+        let key_hash = sha_256(key);
+        self.entries.retain(|(entry_key, _)| *entry_key != key_hash);
+        self.entries.push((key_hash, output_hash));
+
+        if self.entries.len() > OUTPUT_LOG_CAPACITY {
+            let evicted = self.entries.len() - OUTPUT_LOG_CAPACITY;
+            self.entries.drain(..evicted);
+        }
+    }
+}
+
+#[cfg(feature = "test")]
+pub mod tests {
+    use super::*;
+
+    pub fn test_output_log_detects_reprocessing() {
+        let mut log = OutputLog::default();
+        assert_eq!(log.get(b"request 1"), None);
+
+        // the first execution with the key records its output
+        let output_hash = sha_256(b"output of request 1");
+        log.record(b"request 1", output_hash);
+
+        // a second execution with the same key sees the first one's output, after a round trip
+        // through storage
+        let log = OutputLog::from_slice(&log.to_vec().unwrap()).unwrap();
+        assert_eq!(log.get(b"request 1"), Some(output_hash));
+        assert_eq!(log.get(b"request 2"), None);
+    }
+
+    pub fn test_output_log_evicts_least_recently_recorded() {
+        let mut log = OutputLog::default();
+        let key = |i: usize| format!("request {}", i).into_bytes();
+
+        for i in 0..OUTPUT_LOG_CAPACITY {
+            log.record(&key(i), sha_256(&key(i)));
+        }
+        // recording the first key again makes it the most recent
+        log.record(&key(0), sha_256(b"retried"));
+        log.record(&key(OUTPUT_LOG_CAPACITY), sha_256(b"new"));
+
+        assert_eq!(log.entries.len(), OUTPUT_LOG_CAPACITY);
+        assert_eq!(log.get(&key(0)), Some(sha_256(b"retried")));
+        assert_eq!(log.get(&key(1)), None);
+        assert_eq!(log.get(&key(2)), Some(sha_256(&key(2))));
+        assert_eq!(log.get(&key(OUTPUT_LOG_CAPACITY)), Some(sha_256(b"new")));
+    }
+}
@@ -0,0 +1,102 @@
+//! Padding of contract outputs before they're encrypted, for contracts that export
+//! `requires_padded_output`. SIV ciphertexts are as long as their plaintexts, so without padding
+//! an observer can tell outputs apart by their length alone, like two errors a contract returns
+//! from different branches.
+//!
+//! A padded output is `PADDED_MARKER ++ len ++ plaintext ++ zeros`, where `len` is the length of
+//! the plaintext as a big-endian u32, and the zeros fill the whole up to the next power of two, at
+//! least `MIN_PADDED_SIZE`. Past `MAX_PADDED_BUCKET` the buckets stop doubling, and outputs are
+//! padded to a multiple of it instead. Outputs that aren't padded are serialized JSON, or start
+//! with a compression marker, so they never start with `PADDED_MARKER`, which isn't valid UTF-8.
+
+use log::*;
+
+use enclave_ffi_types::EnclaveError;
+
+/// The smallest padded output
+pub const MIN_PADDED_SIZE: usize = 64;
+/// The largest bucket, larger outputs are padded to a multiple of it
+pub const MAX_PADDED_BUCKET: usize = 4096;
+
+const PADDED_MARKER: u8 = 0xff;
+const HEADER_SIZE: usize = 1 + 4;
+
+/// The size `plaintext_len` bytes are padded to, header included
+fn padded_size(plaintext_len: usize) -> usize {
+    let size = plaintext_len + HEADER_SIZE;
+    if size > MAX_PADDED_BUCKET {
+        let buckets = (size + MAX_PADDED_BUCKET - 1) / MAX_PADDED_BUCKET;
+        buckets * MAX_PADDED_BUCKET
+    } else {
+        size.next_power_of_two().max(MIN_PADDED_SIZE)
+    }
+}
+
+/// Prepares a plaintext output for encryption
+pub fn pad(plaintext: &[u8]) -> Vec<u8> {
+    let size = padded_size(plaintext.len());
+    let mut padded = Vec::with_capacity(size);
+    padded.push(PADDED_MARKER);
+    padded.extend_from_slice(&(plaintext.len() as u32).to_be_bytes());
+    padded.extend_from_slice(plaintext);
+    padded.resize(size, 0);
+    padded
+}
+
+/// Reverses `pad` on a decrypted output. Outputs that weren't padded are returned as they are.
+pub fn unpad(plaintext: Vec<u8>) -> Result<Vec<u8>, EnclaveError> {
+    if plaintext.first() != Some(&PADDED_MARKER) {
+        return Ok(plaintext);
+    }
+    if plaintext.len() < HEADER_SIZE {
+        warn!("padded output is too short for its header");
+        return Err(EnclaveError::FailedToDeserialize);
+    }
+
+    let mut len = [0u8; 4];
+    len.copy_from_slice(&plaintext[1..HEADER_SIZE]);
+    let end = HEADER_SIZE + u32::from_be_bytes(len) as usize;
+    if end > plaintext.len() {
+        warn!("padded output is shorter than its length says");
+        return Err(EnclaveError::FailedToDeserialize);
+    }
+
+    Ok(plaintext[HEADER_SIZE..end].to_vec())
+}
+
+#[cfg(feature = "test")]
+pub mod tests {
+    use super::*;
+
+    pub fn test_padding_buckets() {
+        assert_eq!(pad(b"").len(), MIN_PADDED_SIZE);
+        assert_eq!(
+            pad(&[b'a'; MIN_PADDED_SIZE - HEADER_SIZE]).len(),
+            MIN_PADDED_SIZE
+        );
+        assert_eq!(pad(&[b'a'; MIN_PADDED_SIZE]).len(), 2 * MIN_PADDED_SIZE);
+        assert_eq!(pad(&[b'a'; 1000]).len(), 1024);
+
+        // past the largest bucket, outputs grow a bucket at a time
+        assert_eq!(pad(&[b'a'; MAX_PADDED_BUCKET]).len(), 2 * MAX_PADDED_BUCKET);
+        assert_eq!(
+            pad(&[b'a'; 2 * MAX_PADDED_BUCKET]).len(),
+            3 * MAX_PADDED_BUCKET
+        );
+    }
+
+    pub fn test_padding_round_trip() {
+        for plaintext in &[&b""[..], b"\"ok\"", &[b'a'; 5000], &[0u8, 1, 2]] {
+            assert_eq!(unpad(pad(plaintext)).unwrap(), plaintext.to_vec());
+        }
+
+        // outputs that weren't padded are left alone
+        assert_eq!(unpad(b"{}".to_vec()).unwrap(), b"{}".to_vec());
+        assert_eq!(unpad(vec![]).unwrap(), Vec::<u8>::new());
+
+        let mut truncated = pad(b"secret");
+        truncated.truncate(HEADER_SIZE + 2);
+        assert!(unpad(truncated).is_err());
+        assert!(unpad(vec![PADDED_MARKER, 0]).is_err());
+    }
+}
@@ -0,0 +1,201 @@
+//! Memoizes the answers to a contract's queries for the rest of its call.
+//!
+//! Nothing a query can read changes while a contract runs: the contract's own writes are only
+//! flushed when the call ends, and the messages it sends run after it. So the same query asked
+//! twice in one call gets the same answer, and the second one doesn't need to cross the enclave
+//! boundary and run the queried contract again. Every call starts with an empty cache, because
+//! state does change between the calls of a transaction.
+//!
+//! A hit is charged the gas the query took, so cached and uncached runs use the same gas. The chain
+//! only charges the queries it runs, so the gas of hits is left for the engine to charge, see
+//! `take_uncharged_gas`.
+
+use std::collections::HashMap;
+
+use enclave_crypto::{sha_256, HASH_SIZE};
+
+use crate::errors::WasmEngineError;
+
+/// Answers are no longer cached once they take up this many bytes
+pub const MAX_QUERY_CACHE_BYTES: usize = 1024 * 1024;
+
+struct CachedAnswer {
+    answer: Vec<u8>,
+    gas_used: u64,
+}
+
+#[derive(Default)]
+pub struct QueryCache {
+    /// Keyed by the hash of the encrypted query, which includes the queried contract's address
+    /// and code hash
+    answers: HashMap<[u8; HASH_SIZE], CachedAnswer>,
+    size: usize,
+    /// The gas of the hits since the last `take_uncharged_gas`
+    uncharged_gas: u64,
+}
+
+impl QueryCache {
+    /// Returns the cached answer to `encrypted_query`, or runs `query` and caches its answer. Only
+    /// a query that runs sets `gas_used`, the gas it took the first time is added to the uncharged
+    /// gas on a hit.
+    ///
+    /// An answer that took more gas than `gas_limit` isn't used, running the query again is what
+    /// fails the same way. Errors aren't cached.
+    pub fn get_or_query<F>(
+        &mut self,
+        encrypted_query: &[u8],
+        gas_limit: u64,
+        gas_used: &mut u64,
+        query: F,
+    ) -> Result<Vec<u8>, WasmEngineError>
+    where
+        F: FnOnce(&mut u64) -> Result<Vec<u8>, WasmEngineError>,
+    {
+        let key = sha_256(encrypted_query);
+
+        if let Some(cached) = self.answers.get(&key) {
+            if cached.gas_used <= gas_limit {
+                self.uncharged_gas = self.uncharged_gas.saturating_add(cached.gas_used);
+                return Ok(cached.answer.clone());
+            }
+        }
+
+        let answer = query(gas_used)?;
+
+        if self.size + answer.len() <= MAX_QUERY_CACHE_BYTES {
+            self.size += answer.len();
+            let previous = self.answers.insert(
+                key,
+                CachedAnswer {
+                    answer: answer.clone(),
+                    gas_used: *gas_used,
+                },
+            );
+            if let Some(previous) = previous {
+                self.size -= previous.answer.len();
+            }
+        }
+
+        Ok(answer)
+    }
+
+    /// The gas of the answers that came from the cache, which the chain didn't charge since it
+    /// didn't run their queries
+    pub fn take_uncharged_gas(&mut self) -> u64 {
+        std::mem::take(&mut self.uncharged_gas)
+    }
+}
+
+#[cfg(feature = "test")]
+pub mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+
+    pub fn test_query_cache_hits_same_query() {
+        let mut cache = QueryCache::default();
+        let queries = Cell::new(0);
+        let query = |gas_used: &mut u64| {
+            queries.set(queries.get() + 1);
+            *gas_used = 300;
+            Ok(b"answer".to_vec())
+        };
+
+        let mut first_gas = 0;
+        let first = cache
+            .get_or_query(b"query", 1000, &mut first_gas, &query)
+            .unwrap();
+        assert_eq!(cache.take_uncharged_gas(), 0);
+        let mut second_gas = 0;
+        let second = cache
+            .get_or_query(b"query", 1000, &mut second_gas, &query)
+            .unwrap();
+
+        assert_eq!(queries.get(), 1);
+        assert_eq!(first, second);
+        // the chain charged the query but not the hit, which is left to charge
+        assert_eq!((first_gas, second_gas), (300, 0));
+        assert_eq!(cache.take_uncharged_gas(), 300);
+        assert_eq!(cache.take_uncharged_gas(), 0);
+
+        let mut other_gas = 0;
+        cache
+            .get_or_query(b"other query", 1000, &mut other_gas, &query)
+            .unwrap();
+        assert_eq!(queries.get(), 2);
+    }
+
+    pub fn test_query_cache_charges_like_uncached_queries() {
+        let query = |gas_used: &mut u64| {
+            *gas_used = 300;
+            Ok(b"answer".to_vec())
+        };
+        // the gas charged by the chain plus the gas left to charge, for asking `query` `times`
+        let total_gas = |cache: Option<&mut QueryCache>, times: usize| {
+            let mut total = 0u64;
+            match cache {
+                Some(cache) => {
+                    for _ in 0..times {
+                        let mut gas_used = 0;
+                        cache
+                            .get_or_query(b"query", 1000, &mut gas_used, &query)
+                            .unwrap();
+                        total += gas_used + cache.take_uncharged_gas();
+                    }
+                }
+                None => {
+                    for _ in 0..times {
+                        let mut gas_used = 0;
+                        query(&mut gas_used).unwrap();
+                        total += gas_used;
+                    }
+                }
+            }
+            total
+        };
+
+        assert_eq!(
+            total_gas(Some(&mut QueryCache::default()), 4),
+            total_gas(None, 4)
+        );
+    }
+
+    pub fn test_query_cache_misses_over_gas_limit() {
+        let mut cache = QueryCache::default();
+        let queries = Cell::new(0);
+        let query = |gas_used: &mut u64| {
+            queries.set(queries.get() + 1);
+            *gas_used = 300;
+            Ok(b"answer".to_vec())
+        };
+
+        let mut gas_used = 0;
+        cache
+            .get_or_query(b"query", 1000, &mut gas_used, &query)
+            .unwrap();
+        cache
+            .get_or_query(b"query", 299, &mut gas_used, &query)
+            .unwrap();
+
+        assert_eq!(queries.get(), 2);
+    }
+
+    pub fn test_query_cache_skips_errors() {
+        let mut cache = QueryCache::default();
+        let queries = Cell::new(0);
+        let query = |_: &mut u64| {
+            queries.set(queries.get() + 1);
+            Err(WasmEngineError::HostMisbehavior)
+        };
+
+        let mut gas_used = 0;
+        assert!(cache
+            .get_or_query(b"query", 1000, &mut gas_used, &query)
+            .is_err());
+        assert!(cache
+            .get_or_query(b"query", 1000, &mut gas_used, &query)
+            .is_err());
+
+        assert_eq!(queries.get(), 2);
+    }
+}
@@ -1,3 +1,4 @@
+use bech32::ToBase32;
 use log::*;
 
 use sgx_types::sgx_status_t;
@@ -9,25 +10,153 @@ use enclave_utils::recursion_depth;
 
 use super::errors::WasmEngineError;
 use crate::external::{ecalls, ocalls};
+use crate::query_cache::QueryCache;
+use crate::query_path;
 use crate::types::{IoNonce, SecretMessage};
 
 use cw_types_v010::{
+    consts::BECH32_PREFIX_ACC_ADDR,
     encoding::Binary,
-    query::{QueryRequest, WasmQuery},
+    query::{AllBalanceResponse, BankQuery, QueryRequest, WasmQuery},
     std_error::{StdError, StdResult},
     system_error::{SystemError, SystemResult},
+    types::{CanonicalAddr, HumanAddr, OutputCompression},
 };
 
+/// Queries the bank module for all the coins held by `contract_address`, including funds sent
+/// with the current message, and returns them as a JSON encoded `Vec<Coin>`
+#[allow(clippy::too_many_arguments)]
+pub fn query_all_balances(
+    query_depth: u32,
+    max_query_depth: u32,
+    contract_address: &[u8],
+    context: &Ctx,
+    nonce: IoNonce,
+    user_public_key: Ed25519PublicKey,
+    gas_used: &mut u64,
+    gas_limit: u64,
+    compression: &OutputCompression,
+) -> Result<Vec<u8>, WasmEngineError> {
+    let address = bech32::encode(BECH32_PREFIX_ACC_ADDR, contract_address.to_base32())
+        .map_err(|err| {
+            debug!(
+                "query_all_balances() failed to encode contract address {:?} as bech32: {:?}",
+                contract_address, err
+            );
+            WasmEngineError::SerializationError
+        })?;
+
+    let query = serde_json::to_vec(&QueryRequest::Bank(BankQuery::AllBalances {
+        address: HumanAddr(address),
+    }))
+    .map_err(|err| {
+        debug!("query_all_balances() failed to serialize the query: {:?}", err);
+        WasmEngineError::SerializationError
+    })?;
+
+    let answer = encrypt_and_query_chain(
+        &query,
+        query_depth,
+        max_query_depth,
+        contract_address,
+        context,
+        nonce,
+        user_public_key,
+        gas_used,
+        gas_limit,
+        compression,
+        None,
+    )?;
+
+    // querying our own balance can only fail if the host is misbehaving
+    let response = match serde_json::from_slice::<SystemResult<StdResult<Binary>>>(&answer) {
+        Ok(Ok(Ok(response))) => response,
+        other => {
+            debug!("query_all_balances() got an unexpected answer: {:?}", other);
+            return Err(WasmEngineError::HostMisbehavior);
+        }
+    };
+
+    let balances: AllBalanceResponse = serde_json::from_slice(response.as_slice()).map_err(|err| {
+        debug!("query_all_balances() failed to deserialize the balances: {:?}", err);
+        WasmEngineError::HostMisbehavior
+    })?;
+
+    serde_json::to_vec(&balances.amount).map_err(|err| {
+        debug!("query_all_balances() failed to serialize the balances: {:?}", err);
+        WasmEngineError::SerializationError
+    })
+}
+
+/// Runs the Stargate (gRPC) query `path` with the protobuf encoded request `data`, and returns the
+/// protobuf encoded response, or `None` if the chain returned an error
+#[allow(clippy::too_many_arguments)]
+pub fn query_stargate(
+    path: &str,
+    data: Vec<u8>,
+    query_depth: u32,
+    max_query_depth: u32,
+    contract_address: &[u8],
+    context: &Ctx,
+    nonce: IoNonce,
+    user_public_key: Ed25519PublicKey,
+    gas_used: &mut u64,
+    gas_limit: u64,
+    compression: &OutputCompression,
+) -> Result<Option<Vec<u8>>, WasmEngineError> {
+    let query = serde_json::to_vec(&QueryRequest::Stargate {
+        path: path.to_string(),
+        data: Binary(data),
+    })
+    .map_err(|err| {
+        debug!("query_stargate() failed to serialize the query: {:?}", err);
+        WasmEngineError::SerializationError
+    })?;
+
+    let answer = encrypt_and_query_chain(
+        &query,
+        query_depth,
+        max_query_depth,
+        contract_address,
+        context,
+        nonce,
+        user_public_key,
+        gas_used,
+        gas_limit,
+        compression,
+        None,
+    )?;
+
+    match serde_json::from_slice::<SystemResult<StdResult<Binary>>>(&answer) {
+        Ok(Ok(Ok(response))) => Ok(Some(response.0)),
+        Ok(other) => {
+            debug!("query_stargate() {} failed: {:?}", path, other);
+            Ok(None)
+        }
+        Err(err) => {
+            debug!("query_stargate() got an unexpected answer: {:?}", err);
+            Err(WasmEngineError::HostMisbehavior)
+        }
+    }
+}
+
+/// Runs `query` for a contract and returns the decrypted answer. With a `query_cache`, a query the
+/// contract already asked in this call is answered from the cache, see `QueryCache`.
+#[allow(clippy::too_many_arguments)]
 pub fn encrypt_and_query_chain(
     query: &[u8],
     query_depth: u32,
+    max_query_depth: u32,
+    contract_address: &[u8],
     context: &Ctx,
     nonce: IoNonce,
     user_public_key: Ed25519PublicKey,
     gas_used: &mut u64,
     gas_limit: u64,
+    compression: &OutputCompression,
+    query_cache: Option<&mut QueryCache>,
 ) -> Result<Vec<u8>, WasmEngineError> {
-    if let Some(answer) = check_recursion_limit(query_depth) {
+    if let Some(answer) = check_recursion_limit(query_depth, max_query_depth, contract_address) {
         return serialize_error_response(&answer);
     }
     let new_query_depth = query_depth + 1;
@@ -40,6 +169,14 @@ pub fn encrypt_and_query_chain(
         }
     };
 
+    // the query stays on the path until it returns, so a cycle back to it stops right away
+    let _query_path = match &query_struct {
+        QueryRequest::Wasm(WasmQuery::Smart {
+            contract_addr, msg, ..
+        }) => Some(query_path::enter(contract_addr.as_str(), msg.as_slice())?),
+        _ => None,
+    };
+
     let is_encrypted = encrypt_query_request(&mut query_struct, nonce, user_public_key)?;
 
     let encrypted_query = serde_json::to_vec(&query_struct).map_err(|err| {
@@ -52,13 +189,40 @@ pub fn encrypt_and_query_chain(
         WasmEngineError::SerializationError
     })?;
 
-    // Call query_chain (this bubbles up to x/compute via ocalls and FFI to Go code)
-    // This returns the answer from x/compute
-    let (result, query_used_gas) =
-        query_chain(context, &encrypted_query, new_query_depth, gas_limit);
-    *gas_used = query_used_gas;
-    let encrypted_answer_as_vec = result?;
+    let run_query = |gas_used: &mut u64| {
+        // Call query_chain (this bubbles up to x/compute via ocalls and FFI to Go code)
+        // This returns the answer from x/compute
+        let (result, query_used_gas) =
+            query_chain(context, &encrypted_query, new_query_depth, gas_limit);
+        *gas_used = query_used_gas;
+
+        decrypt_answer(
+            query,
+            result?,
+            is_encrypted,
+            nonce,
+            user_public_key,
+            compression,
+        )
+    };
+
+    match query_cache {
+        Some(query_cache) => {
+            query_cache.get_or_query(&encrypted_query, gas_limit, gas_used, run_query)
+        }
+        None => run_query(gas_used),
+    }
+}
 
+/// Decrypts the answer x/compute returned for a query, if the query was encrypted
+fn decrypt_answer(
+    query: &[u8],
+    encrypted_answer_as_vec: Vec<u8>,
+    is_encrypted: bool,
+    nonce: IoNonce,
+    user_public_key: Ed25519PublicKey,
+    compression: &OutputCompression,
+) -> Result<Vec<u8>, WasmEngineError> {
     if !is_encrypted {
         return Ok(encrypted_answer_as_vec);
     }
@@ -77,8 +241,8 @@ pub fn encrypt_and_query_chain(
     };
 
     debug!(
-        "encrypt_and_query_chain() got encrypted answer with gas {}: {:?}",
-        gas_used, encrypted_answer
+        "encrypt_and_query_chain() got encrypted answer: {:?}",
+        encrypted_answer
     );
 
     // decrypt query response
@@ -86,7 +250,8 @@ pub fn encrypt_and_query_chain(
         Err(_) => encrypted_answer,
         // normal response from contract
         Ok(Ok(result)) => {
-            let decrypted = decrypt_query_response(query, result.0, nonce, user_public_key)?;
+            let decrypted =
+                decrypt_query_response(query, result.0, nonce, user_public_key, compression)?;
             Ok(Ok(Binary(decrypted)))
         }
         // error response from contract, or critical error in called VM
@@ -201,12 +366,19 @@ fn query_chain(
 ///
 /// We make sure that a recursion limit is in place in order to
 /// mitigate cases where the enclave runs out of memory.
-fn check_recursion_limit(query_depth: u32) -> Option<SystemResult<StdResult<Binary>>> {
-    if recursion_depth::limit_reached(query_depth) {
+fn check_recursion_limit(
+    query_depth: u32,
+    max_query_depth: u32,
+    contract_address: &[u8],
+) -> Option<SystemResult<StdResult<Binary>>> {
+    if recursion_depth::limit_reached(query_depth, max_query_depth) {
+        let contract_addr =
+            HumanAddr::from_canonical(&CanonicalAddr::from_vec(contract_address.to_vec())).ok();
         debug!(
-            "Recursion limit reached while performing nested queries. Returning error to contract."
+            "Recursion limit reached while performing nested queries from {:?}. Returning error to contract.",
+            contract_addr
         );
-        Some(Err(SystemError::ExceededRecursionLimit {}))
+        Some(Err(SystemError::ExceededRecursionLimit { contract_addr }))
     } else {
         None
     }
@@ -301,6 +473,7 @@ fn decrypt_query_response(
     response: Vec<u8>,
     nonce: IoNonce,
     user_public_key: Ed25519PublicKey,
+    compression: &OutputCompression,
 ) -> Result<Vec<u8>, WasmEngineError> {
     // query response returns without nonce and user_public_key appended to it
     // because the sender is supposed to have them already
@@ -310,7 +483,7 @@ fn decrypt_query_response(
         msg: response,
     };
 
-    let b64_decrypted = as_secret_msg.decrypt().map_err(|err| {
+    let b64_decrypted = as_secret_msg.decrypt_output(compression).map_err(|err| {
         debug!(
             "encrypt_and_query_chain() got an error while trying to decrypt the result for query {:?}, stopping wasm: {:?}",
             String::from_utf8_lossy(query),
@@ -340,7 +513,7 @@ fn decrypt_query_response_error(
         msg: error,
     };
 
-    error_msg.decrypt().map_err(|err| {
+    error_msg.decrypt_error().map_err(|err| {
         debug!(
             "encrypt_and_query_chain() got an error while trying to decrypt the inner error for query {:?}, stopping wasm: {:?}",
             String::from_utf8_lossy(query),
@@ -349,3 +522,66 @@ fn decrypt_query_response_error(
         WasmEngineError::DecryptionError
     })
 }
+
+#[cfg(feature = "test")]
+pub mod tests {
+    use super::*;
+
+    const RECURSION_LIMIT_PREFIX: &str = "Query recursion limit exceeded";
+
+    pub fn test_recursion_limit_error_includes_contract() {
+        let contract_address = vec![7u8; 20];
+        let human = HumanAddr::from_canonical(&CanonicalAddr::from_vec(contract_address.clone()))
+            .unwrap();
+
+        let answer = check_recursion_limit(u32::MAX, 0, &contract_address).unwrap();
+        let err = answer.unwrap_err();
+        let msg = err.to_string();
+
+        assert!(msg.starts_with(RECURSION_LIMIT_PREFIX));
+        assert!(msg.contains(human.as_str()));
+
+        let serialized = String::from_utf8(serialize_error_response(&Err(err)).unwrap()).unwrap();
+        assert!(serialized.contains("exceeded_recursion_limit"));
+        assert!(serialized.contains(human.as_str()));
+    }
+
+    pub fn test_recursion_limit_boundary() {
+        let contract_address = vec![7u8; 20];
+        let limit = recursion_depth::DEFAULT_RECURSION_LIMIT;
+
+        // one level below the limit still runs, the next one gets the structured error
+        assert!(check_recursion_limit(limit - 1, 0, &contract_address).is_none());
+        let answer = check_recursion_limit(limit, 0, &contract_address).unwrap();
+        assert!(matches!(
+            answer,
+            Err(SystemError::ExceededRecursionLimit {
+                contract_addr: Some(_)
+            })
+        ));
+    }
+
+    pub fn test_recursion_limit_configured() {
+        let contract_address = vec![7u8; 20];
+
+        assert!(check_recursion_limit(14, 15, &contract_address).is_none());
+        assert!(check_recursion_limit(15, 15, &contract_address).is_some());
+
+        // the chain can't push the limit past what the enclave's stack allows
+        let max = recursion_depth::MAX_RECURSION_LIMIT;
+        assert_eq!(recursion_depth::recursion_limit(u32::MAX), max);
+        assert!(check_recursion_limit(max, u32::MAX, &contract_address).is_some());
+    }
+
+    pub fn test_recursion_limit_error_without_contract_is_unchanged() {
+        let err = SystemError::ExceededRecursionLimit {
+            contract_addr: None,
+        };
+
+        assert_eq!(err.to_string(), RECURSION_LIMIT_PREFIX);
+        assert_eq!(
+            serde_json::to_string(&err).unwrap(),
+            r#"{"exceeded_recursion_limit":{}}"#
+        );
+    }
+}
@@ -0,0 +1,108 @@
+//! The contract queries that are running on this thread, to stop query cycles early.
+//!
+//! A contract that sends the query it's answering, to itself or through other contracts, would
+//! query in a loop until it hits the recursion limit or runs out of gas, which looks just like a
+//! heavy computation. A nested query runs on the thread of the call that sent it, since the
+//! enclave is re-entered from the query's ocall, so the queries that are still running form a
+//! per-thread stack. It's empty again when the transaction's call returns.
+
+use std::cell::RefCell;
+
+use log::*;
+
+use enclave_crypto::{sha_256, HASH_SIZE};
+
+use crate::errors::WasmEngineError;
+
+thread_local! {
+    /// The hashes of the running queries, the most recent last
+    static QUERY_PATH: RefCell<Vec<[u8; HASH_SIZE]>> = RefCell::new(vec![]);
+}
+
+/// Identifies a query to `contract_addr` with `msg`. The address is length-prefixed, so it can't
+/// be told apart from the message.
+fn query_id(contract_addr: &str, msg: &[u8]) -> [u8; HASH_SIZE] {
+    let mut data = Vec::with_capacity(4 + contract_addr.len() + msg.len());
+    data.extend_from_slice(&(contract_addr.len() as u32).to_be_bytes());
+    data.extend_from_slice(contract_addr.as_bytes());
+    data.extend_from_slice(msg);
+    sha_256(&data)
+}
+
+/// Keeps a query on the path until it's dropped
+pub struct QueryPathGuard {
+    _private: (),
+}
+
+impl Drop for QueryPathGuard {
+    fn drop(&mut self) {
+        QUERY_PATH.with(|path| {
+            path.borrow_mut().pop();
+        });
+    }
+}
+
+/// Adds the query to `contract_addr` with `msg` to the path for as long as the returned guard
+/// lives, or fails with `QueryCycleDetected` if the same query is already running
+pub fn enter(contract_addr: &str, msg: &[u8]) -> Result<QueryPathGuard, WasmEngineError> {
+    let id = query_id(contract_addr, msg);
+
+    QUERY_PATH.with(|path| {
+        let mut path = path.borrow_mut();
+        if path.contains(&id) {
+            debug!(
+                "the same query to {} is already running, stopping the query cycle",
+                contract_addr
+            );
+            return Err(WasmEngineError::QueryCycleDetected);
+        }
+
+        path.push(id);
+        Ok(QueryPathGuard { _private: () })
+    })
+}
+
+#[cfg(feature = "test")]
+pub mod tests {
+    use super::*;
+
+    fn depth() -> usize {
+        QUERY_PATH.with(|path| path.borrow().len())
+    }
+
+    pub fn test_query_path_detects_cycles() {
+        let outer = enter("secret1a", br#"{"loop":{}}"#).unwrap();
+        let inner = enter("secret1b", br#"{"loop":{}}"#).unwrap();
+        assert_eq!(depth(), 2);
+
+        // a query that's running anywhere on the path, not just the last one
+        assert!(matches!(
+            enter("secret1a", br#"{"loop":{}}"#),
+            Err(WasmEngineError::QueryCycleDetected)
+        ));
+        assert!(matches!(
+            enter("secret1b", br#"{"loop":{}}"#),
+            Err(WasmEngineError::QueryCycleDetected)
+        ));
+        // a rejected query doesn't change the path
+        assert_eq!(depth(), 2);
+
+        // the same contract with another message isn't a cycle
+        let other = enter("secret1a", br#"{"get":{}}"#).unwrap();
+        assert_eq!(depth(), 3);
+
+        drop(other);
+        drop(inner);
+        // a query that returned can be sent again
+        let again = enter("secret1b", br#"{"loop":{}}"#).unwrap();
+
+        drop(again);
+        drop(outer);
+        assert_eq!(depth(), 0);
+    }
+
+    pub fn test_query_path_ids_are_framed() {
+        assert_ne!(query_id("secret1a", b"bc"), query_id("secret1ab", b"c"));
+        assert_eq!(query_id("secret1a", b"bc"), query_id("secret1a", b"bc"));
+    }
+}
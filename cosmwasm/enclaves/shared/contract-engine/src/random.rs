@@ -49,7 +49,8 @@ pub fn derive_random(seed: &Binary, contract_key: &ContractKey, height: u64) ->
     )
 }
 
-pub fn update_msg_counter(height: u64) {
+/// Bumps the block's message counter for a new call, and returns it
+pub fn update_msg_counter(height: u64) -> u64 {
     let mut counter = MSG_COUNTER.lock().unwrap();
 
     if counter.height != height {
@@ -60,4 +61,5 @@ pub fn update_msg_counter(height: u64) {
     }
 
     trace!("counter incremented to: {:?}", counter);
+    counter.counter
 }
@@ -1,13 +1,17 @@
-use crate::types::{ParsedMessage, SecretMessage};
+use crate::io::set_reply_tx_position;
+use crate::types::{ParsedMessage, SecretMessage, TxPosition};
 use cw_types_v010::encoding::Binary;
+use cw_types_v010::types::OutputCompression;
 use cw_types_v1::results::{
-    DecryptedReply, Event, Reply, SubMsgResponse, SubMsgResult, REPLY_ENCRYPTION_MAGIC_BYTES,
+    DecryptedReply, Event, Reply, ReplyOn, SubMsgResponse, SubMsgResult, REPLY_DEPTH_MAGIC_BYTES,
+    REPLY_ENCRYPTION_MAGIC_BYTES,
 };
 use enclave_ffi_types::EnclaveError;
 use log::{trace, warn};
 
-const HEX_ENCODED_HASH_SIZE: usize = 64;
+pub const HEX_ENCODED_HASH_SIZE: usize = 64;
 const SIZE_OF_U64: usize = 8;
+const SIZE_OF_U32: usize = 4;
 
 fn redact_custom_events(reply: &mut Reply) {
     reply.result = match &reply.result {
@@ -45,11 +49,17 @@ fn redact_custom_events(reply: &mut Reply) {
     };
 }
 
+/// The data returned by the submessage was encrypted for the parent contract's user,
+/// so it is decrypted here before it's passed into the parent's `reply` as plaintext.
+/// Data the submessage emitted in plaintext is passed as is.
 fn get_data_from_reply(
     input_msg: &SecretMessage,
     response: SubMsgResponse,
+    is_data_plaintext: bool,
+    compression: &OutputCompression,
 ) -> Result<Option<Binary>, EnclaveError> {
     match response.data {
+        Some(data) if is_data_plaintext => Ok(Some(data)),
         Some(data) => {
             let tmp_secret_msg_data = SecretMessage {
                 nonce: input_msg.nonce,
@@ -57,7 +67,8 @@ fn get_data_from_reply(
                 msg: data.as_slice().to_vec(),
             };
 
-            let base64_data = tmp_secret_msg_data.decrypt()?[HEX_ENCODED_HASH_SIZE..].to_vec();
+            let base64_data =
+                tmp_secret_msg_data.decrypt_output(compression)?[HEX_ENCODED_HASH_SIZE..].to_vec();
 
             Ok(Some(Binary::from_base64(
                 String::from_utf8(base64_data.clone())
@@ -87,14 +98,16 @@ fn parse_message_id_of_encrypted_reply(
         msg: parsed_reply.id.as_slice().to_vec(),
     };
 
-    let mut tmp_decrypted_msg_id = tmp_secret_msg_id.decrypt()?;
+    split_reply_id(tmp_secret_msg_id.decrypt()?)
+}
 
+/// Splits a decrypted reply id into the id of the submessage and the data to validate the reply
+/// with: the code hash of the contract it's for, the headers of the chain it continues with, and
+/// the reply depth of the contract it's for
+pub fn split_reply_id(mut tmp_decrypted_msg_id: Vec<u8>) -> Result<(u64, Vec<u8>), EnclaveError> {
     let mut data_for_validation: Vec<u8> = tmp_decrypted_msg_id[..HEX_ENCODED_HASH_SIZE].to_vec();
     tmp_decrypted_msg_id = tmp_decrypted_msg_id[HEX_ENCODED_HASH_SIZE..].to_vec();
-    while tmp_decrypted_msg_id.len() >= REPLY_ENCRYPTION_MAGIC_BYTES.len()
-        && tmp_decrypted_msg_id[0..(REPLY_ENCRYPTION_MAGIC_BYTES.len())]
-            == *REPLY_ENCRYPTION_MAGIC_BYTES
-    {
+    while ReplyOn::from_reply_header(&tmp_decrypted_msg_id).is_some() {
         data_for_validation.extend_from_slice(
             &tmp_decrypted_msg_id
                 [0..(REPLY_ENCRYPTION_MAGIC_BYTES.len() + SIZE_OF_U64 + HEX_ENCODED_HASH_SIZE)],
@@ -104,6 +117,16 @@ fn parse_message_id_of_encrypted_reply(
             [(REPLY_ENCRYPTION_MAGIC_BYTES.len() + SIZE_OF_U64 + HEX_ENCODED_HASH_SIZE)..]
             .to_vec();
     }
+    if tmp_decrypted_msg_id.starts_with(REPLY_DEPTH_MAGIC_BYTES) {
+        let header_size = REPLY_DEPTH_MAGIC_BYTES.len() + SIZE_OF_U32;
+        if tmp_decrypted_msg_id.len() < header_size {
+            warn!("reply id has a truncated reply depth header");
+            return Err(EnclaveError::FailedToDeserialize);
+        }
+
+        data_for_validation.extend_from_slice(&tmp_decrypted_msg_id[..header_size]);
+        tmp_decrypted_msg_id = tmp_decrypted_msg_id[header_size..].to_vec();
+    }
 
     let msg_id = String::from_utf8(tmp_decrypted_msg_id.clone()).map_err(|err| {
         warn!(
@@ -170,8 +193,14 @@ fn parse_encrypted_ok_reply(
     input_msg: &SecretMessage,
     parsed_reply: &mut Reply,
     response: SubMsgResponse,
+    compression: &OutputCompression,
 ) -> Result<ParsedMessage, EnclaveError> {
-    let decrypted_msg_data = get_data_from_reply(input_msg, response.clone())?;
+    let decrypted_msg_data = get_data_from_reply(
+        input_msg,
+        response.clone(),
+        parsed_reply.is_data_plaintext,
+        compression,
+    )?;
 
     // Now we need to create synthetic SecretMessage to fit the API in "handle"
     let result = SubMsgResult::Ok(SubMsgResponse {
@@ -204,7 +233,7 @@ fn parse_encrypted_error_reply(
         })?,
     };
 
-    let decrypted_error = secret_msg.decrypt()?;
+    let decrypted_error = secret_msg.decrypt_error()?;
 
     // Now we need to create synthetic SecretMessage to fit the API in "handle"
     let result = SubMsgResult::Err(
@@ -224,9 +253,12 @@ fn parse_encrypted_error_reply(
 fn parse_encrypted_reply_message(
     input_msg: &SecretMessage,
     parsed_reply: &mut Reply,
+    compression: &OutputCompression,
 ) -> Result<ParsedMessage, EnclaveError> {
     match parsed_reply.result.clone() {
-        SubMsgResult::Ok(response) => parse_encrypted_ok_reply(input_msg, parsed_reply, response),
+        SubMsgResult::Ok(response) => {
+            parse_encrypted_ok_reply(input_msg, parsed_reply, response, compression)
+        }
         SubMsgResult::Err(response) => {
             parse_encrypted_error_reply(input_msg, parsed_reply, response)
         }
@@ -296,7 +328,11 @@ fn parse_plaintext_reply_message(
     })
 }
 
-pub fn parse_reply_message(encrypted_message: &[u8]) -> Result<ParsedMessage, EnclaveError> {
+pub fn parse_reply_message(
+    encrypted_message: &[u8],
+    tx_position: TxPosition,
+    compression: &OutputCompression,
+) -> Result<ParsedMessage, EnclaveError> {
     let orig_secret_msg = SecretMessage::from_slice(encrypted_message)?;
     let mut parsed_reply: Reply = serde_json::from_slice(&orig_secret_msg.msg).map_err(|err| {
         warn!(
@@ -321,5 +357,8 @@ pub fn parse_reply_message(encrypted_message: &[u8]) -> Result<ParsedMessage, En
         base64::encode(encrypted_message)
     );
 
-    parse_encrypted_reply_message(&orig_secret_msg, &mut parsed_reply)
+    // The callback signature only verifies if the reply was signed in this transaction
+    set_reply_tx_position(&mut parsed_reply, tx_position);
+
+    parse_encrypted_reply_message(&orig_secret_msg, &mut parsed_reply, compression)
 }
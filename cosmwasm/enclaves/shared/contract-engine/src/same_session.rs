@@ -0,0 +1,73 @@
+//! Comparison of the senders of encrypted messages, backing the `same_session` host function, so
+//! contracts can check that two messages came from the same sender session without decrypting
+//! either of them.
+//!
+//! A session is the key pair the sender encrypts its messages with. Each message is encrypted with
+//! a key derived from the session's public key and a fresh nonce (see `calc_encryption_key`), so
+//! two messages of the same session have different nonces and different keys. The public key is
+//! sent in the clear ahead of the ciphertext, and it's all that's compared: the keys themselves
+//! are never derived, and contracts only learn whether the two match.
+
+use enclave_crypto::WasmApiCryptoError;
+
+use crate::types::SecretMessage;
+
+/// Whether `ciphertext_a` and `ciphertext_b` were encrypted by the same sender session, meaning
+/// with the same `user_public_key`. Both are encrypted messages like the ones sent to contracts,
+/// `nonce ++ user_public_key ++ ciphertext`.
+pub fn same_session(ciphertext_a: &[u8], ciphertext_b: &[u8]) -> Result<bool, WasmApiCryptoError> {
+    let msg_a = SecretMessage::from_slice(ciphertext_a)
+        .map_err(|_| WasmApiCryptoError::InvalidCiphertext)?;
+    let msg_b = SecretMessage::from_slice(ciphertext_b)
+        .map_err(|_| WasmApiCryptoError::InvalidCiphertext)?;
+
+    // the public keys are in the clear, so comparing them doesn't need to be constant time
+    Ok(msg_a.user_public_key == msg_b.user_public_key)
+}
+
+#[cfg(feature = "test")]
+pub mod tests {
+    use super::*;
+
+    fn ciphertext(nonce: u8, user_public_key: u8, msg: &[u8]) -> Vec<u8> {
+        [&[nonce; 32][..], &[user_public_key; 32][..], msg].concat()
+    }
+
+    pub fn test_same_session_same_key() {
+        let a = ciphertext(1, 2, &[3u8; 32]);
+        let b = ciphertext(1, 2, &[4u8; 48]);
+
+        assert!(same_session(&a, &b).unwrap());
+        assert!(same_session(&a, &a).unwrap());
+    }
+
+    pub fn test_same_session_distinct_messages() {
+        // two messages the same sender encrypted with fresh nonces, as every message is
+        let a = ciphertext(1, 2, &[3u8; 32]);
+        let b = ciphertext(5, 2, &[4u8; 48]);
+
+        assert!(same_session(&a, &b).unwrap());
+        assert!(same_session(&b, &a).unwrap());
+    }
+
+    pub fn test_same_session_different_keys() {
+        let a = ciphertext(1, 2, &[3u8; 32]);
+
+        // the same nonce and message from another sender
+        assert!(!same_session(&a, &ciphertext(1, 5, &[3u8; 32])).unwrap());
+        assert!(!same_session(&a, &ciphertext(5, 5, &[4u8; 48])).unwrap());
+    }
+
+    pub fn test_same_session_malformed_ciphertext() {
+        let a = ciphertext(1, 2, &[3u8; 32]);
+
+        assert!(matches!(
+            same_session(&a, &a[..64]),
+            Err(WasmApiCryptoError::InvalidCiphertext)
+        ));
+        assert!(matches!(
+            same_session(&[], &a),
+            Err(WasmApiCryptoError::InvalidCiphertext)
+        ));
+    }
+}
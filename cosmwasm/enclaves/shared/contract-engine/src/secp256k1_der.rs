@@ -0,0 +1,135 @@
+//! Verification of DER encoded secp256k1 ECDSA signatures, backing the `secp256k1_verify_der`
+//! host function, for interop with data signed by OpenSSL and other tools that don't produce
+//! compact signatures.
+//!
+//! `secp256k1_verify` only accepts low-S signatures, like the rest of Cosmos. External signers
+//! don't always normalize S, so the signature is normalized here before it's verified. This makes
+//! both S values of a signature valid, which is fine as long as contracts don't use DER signatures
+//! as unique identifiers.
+
+use log::*;
+
+use enclave_crypto::WasmApiCryptoError;
+
+/// Returns whether the DER encoded `der_sig` is a signature of `msg_hash` by `pubkey`.
+/// Malformed inputs are errors, while a valid signature by another key is `Ok(false)`.
+pub fn secp256k1_verify_der(
+    msg_hash: &[u8],
+    der_sig: &[u8],
+    pubkey: &[u8],
+) -> Result<bool, WasmApiCryptoError> {
+    let message = secp256k1::Message::from_slice(msg_hash).map_err(|err| {
+        debug!("secp256k1_verify_der() got an invalid message hash: {:?}", err);
+        WasmApiCryptoError::InvalidHashFormat
+    })?;
+
+    let mut signature = secp256k1::ecdsa::Signature::from_der(der_sig).map_err(|err| {
+        debug!("secp256k1_verify_der() malformed DER signature: {:?}", err);
+        WasmApiCryptoError::InvalidSignatureFormat
+    })?;
+    signature.normalize_s();
+
+    let pubkey = secp256k1::PublicKey::from_slice(pubkey).map_err(|err| {
+        debug!("secp256k1_verify_der() malformed pubkey: {:?}", err);
+        WasmApiCryptoError::InvalidPubkeyFormat
+    })?;
+
+    match secp256k1::Secp256k1::verification_only().verify_ecdsa(&message, &signature, &pubkey) {
+        Err(err) => {
+            debug!("secp256k1_verify_der() failed to verify signature: {:?}", err);
+            Ok(false)
+        }
+        Ok(()) => Ok(true),
+    }
+}
+
+#[cfg(feature = "test")]
+pub mod tests {
+    use super::*;
+    use enclave_crypto::sha_256;
+
+    /// The order of the secp256k1 curve
+    const CURVE_ORDER: [u8; 32] = [
+        0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+        0xfe, 0xba, 0xae, 0xdc, 0xe6, 0xaf, 0x48, 0xa0, 0x3b, 0xbf, 0xd2, 0x5e, 0x8c, 0xd0, 0x36,
+        0x41, 0x41,
+    ];
+
+    fn sign(msg_hash: &[u8; 32]) -> (secp256k1::ecdsa::Signature, Vec<u8>) {
+        let secp = secp256k1::Secp256k1::new();
+        let secret_key = secp256k1::SecretKey::from_slice(&[7u8; 32]).unwrap();
+        let pubkey = secp256k1::PublicKey::from_secret_key(&secp, &secret_key);
+        let message = secp256k1::Message::from_slice(msg_hash).unwrap();
+
+        (
+            secp.sign_ecdsa(&message, &secret_key),
+            pubkey.serialize().to_vec(),
+        )
+    }
+
+    /// The same signature with S replaced by n - S
+    fn high_s(signature: &secp256k1::ecdsa::Signature) -> secp256k1::ecdsa::Signature {
+        let mut compact = signature.serialize_compact();
+        let mut borrow = 0i16;
+        for i in (0..32).rev() {
+            let diff = CURVE_ORDER[i] as i16 - compact[32 + i] as i16 - borrow;
+            borrow = if diff < 0 { 1 } else { 0 };
+            compact[32 + i] = (diff + (borrow << 8)) as u8;
+        }
+
+        secp256k1::ecdsa::Signature::from_compact(&compact).unwrap()
+    }
+
+    pub fn test_secp256k1_verify_der_valid() {
+        let msg_hash = sha_256(b"signed by openssl");
+        let (signature, pubkey) = sign(&msg_hash);
+        let der_sig = signature.serialize_der();
+
+        assert!(secp256k1_verify_der(&msg_hash, &der_sig, &pubkey).unwrap());
+
+        let other_hash = sha_256(b"not signed by openssl");
+        assert!(!secp256k1_verify_der(&other_hash, &der_sig, &pubkey).unwrap());
+    }
+
+    pub fn test_secp256k1_verify_der_malformed() {
+        let msg_hash = sha_256(b"signed by openssl");
+        let (signature, pubkey) = sign(&msg_hash);
+        let der_sig = signature.serialize_der();
+
+        // truncated DER, and a compact signature passed as DER
+        assert!(matches!(
+            secp256k1_verify_der(&msg_hash, &der_sig[..der_sig.len() - 1], &pubkey),
+            Err(WasmApiCryptoError::InvalidSignatureFormat)
+        ));
+        assert!(matches!(
+            secp256k1_verify_der(&msg_hash, &signature.serialize_compact(), &pubkey),
+            Err(WasmApiCryptoError::InvalidSignatureFormat)
+        ));
+
+        assert!(matches!(
+            secp256k1_verify_der(&msg_hash[..31], &der_sig, &pubkey),
+            Err(WasmApiCryptoError::InvalidHashFormat)
+        ));
+        assert!(matches!(
+            secp256k1_verify_der(&msg_hash, &der_sig, &pubkey[..32]),
+            Err(WasmApiCryptoError::InvalidPubkeyFormat)
+        ));
+    }
+
+    pub fn test_secp256k1_verify_der_high_s() {
+        let msg_hash = sha_256(b"signed by openssl");
+        let (signature, pubkey) = sign(&msg_hash);
+        let high_s_signature = high_s(&signature);
+        assert_ne!(high_s_signature, signature);
+
+        // a plain verification rejects the high-S form
+        let message = secp256k1::Message::from_slice(&msg_hash).unwrap();
+        let public_key = secp256k1::PublicKey::from_slice(&pubkey).unwrap();
+        assert!(secp256k1::Secp256k1::verification_only()
+            .verify_ecdsa(&message, &high_s_signature, &public_key)
+            .is_err());
+
+        let der_sig = high_s_signature.serialize_der();
+        assert!(secp256k1_verify_der(&msg_hash, &der_sig, &pubkey).unwrap());
+    }
+}
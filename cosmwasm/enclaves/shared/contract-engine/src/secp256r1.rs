@@ -0,0 +1,124 @@
+//! Verification of secp256r1 (NIST P-256) ECDSA signatures, backing the `secp256r1_verify` host
+//! function, for WebAuthn/passkey and other P-256 signers.
+//!
+//! Inputs and error codes are the same as `secp256k1_verify`: a 32 byte message hash, a 64 byte
+//! r || s signature, and a SEC1 encoded public key. Unlike `secp256k1_verify`, high-S signatures
+//! are valid, because P-256 signers (authenticators in particular) don't normalize S.
+//!
+//! This uses `p256` rather than `ring`, which verifies the JWT and WebAuthn signatures, because
+//! `ring` hashes the message itself and takes only uncompressed public keys.
+
+use std::convert::TryFrom;
+
+use log::*;
+
+use enclave_crypto::WasmApiCryptoError;
+use p256::ecdsa::signature::hazmat::PrehashVerifier;
+use p256::ecdsa::{Signature, VerifyingKey};
+
+/// Returns whether `signature` is a signature of `msg_hash` by `pubkey`.
+/// Malformed inputs are errors, while a valid signature by another key is `Ok(false)`.
+pub fn secp256r1_verify(
+    msg_hash: &[u8],
+    signature: &[u8],
+    pubkey: &[u8],
+) -> Result<bool, WasmApiCryptoError> {
+    if msg_hash.len() != 32 {
+        return Err(WasmApiCryptoError::InvalidHashFormat);
+    }
+    if signature.len() != 64 {
+        return Err(WasmApiCryptoError::InvalidSignatureFormat);
+    }
+    if !match pubkey.first() {
+        // compressed
+        Some(0x02) | Some(0x03) => pubkey.len() == 33,
+        // uncompressed
+        Some(0x04) => pubkey.len() == 65,
+        _ => false,
+    } {
+        return Err(WasmApiCryptoError::InvalidPubkeyFormat);
+    }
+
+    let signature = Signature::try_from(signature).map_err(|err| {
+        debug!("secp256r1_verify() malformed signature: {:?}", err);
+        WasmApiCryptoError::GenericErr
+    })?;
+    let pubkey = VerifyingKey::from_sec1_bytes(pubkey).map_err(|err| {
+        debug!("secp256r1_verify() malformed pubkey: {:?}", err);
+        WasmApiCryptoError::GenericErr
+    })?;
+
+    match pubkey.verify_prehash(msg_hash, &signature) {
+        Err(err) => {
+            debug!("secp256r1_verify() failed to verify signature: {:?}", err);
+            Ok(false)
+        }
+        Ok(()) => Ok(true),
+    }
+}
+
+#[cfg(feature = "test")]
+pub mod tests {
+    use super::*;
+    use enclave_crypto::sha_256;
+
+    // RFC 6979, A.2.5: ECDSA with P-256 and SHA-256
+    const PUBKEY: &str = "0460fed4ba255a9d31c961eb74c6356d68c049b8923b61fa6ce669622e60f29fb67903fe1008b8bc99a41ae9e95628bc64f2f1b20c2d7e9f5177a3c294d4462299";
+    const COMPRESSED_PUBKEY: &str =
+        "0360fed4ba255a9d31c961eb74c6356d68c049b8923b61fa6ce669622e60f29fb6";
+    /// The signature of "sample", which has a high S
+    const SAMPLE_SIGNATURE: &str = "efd48b2aacb6a8fd1140dd9cd45e81d69d2c877b56aaf991c34d0ea84eaf3716f7cb1c942d657c41d436c7a1b6e29f65f3e900dbb9aff4064dc4ab2f843acda8";
+    /// The signature of "test"
+    const TEST_SIGNATURE: &str = "f1abb023518351cd71d881567b1ea663ed3efcf6c5132b354f28d3b0b7d38367019f4113742a2b14bd25926b49c649155f267e60d3814b4c0cc84250e46f0083";
+
+    pub fn test_secp256r1_verify_rfc6979_vectors() {
+        for pubkey in &[PUBKEY, COMPRESSED_PUBKEY] {
+            let pubkey = hex::decode(pubkey).unwrap();
+            for (message, signature) in &[("sample", SAMPLE_SIGNATURE), ("test", TEST_SIGNATURE)] {
+                let signature = hex::decode(signature).unwrap();
+
+                assert!(
+                    secp256r1_verify(&sha_256(message.as_bytes()), &signature, &pubkey).unwrap()
+                );
+            }
+        }
+    }
+
+    pub fn test_secp256r1_verify_rejects_other_message() {
+        let pubkey = hex::decode(PUBKEY).unwrap();
+        let signature = hex::decode(SAMPLE_SIGNATURE).unwrap();
+
+        assert!(!secp256r1_verify(&sha_256(b"test"), &signature, &pubkey).unwrap());
+    }
+
+    pub fn test_secp256r1_verify_malformed_inputs() {
+        let msg_hash = sha_256(b"sample");
+        let pubkey = hex::decode(PUBKEY).unwrap();
+        let signature = hex::decode(SAMPLE_SIGNATURE).unwrap();
+
+        assert!(matches!(
+            secp256r1_verify(&msg_hash[..31], &signature, &pubkey),
+            Err(WasmApiCryptoError::InvalidHashFormat)
+        ));
+        assert!(matches!(
+            secp256r1_verify(&msg_hash, &signature[..63], &pubkey),
+            Err(WasmApiCryptoError::InvalidSignatureFormat)
+        ));
+        assert!(matches!(
+            secp256r1_verify(&msg_hash, &signature, &pubkey[..33]),
+            Err(WasmApiCryptoError::InvalidPubkeyFormat)
+        ));
+        // r and s can't be zero
+        assert!(matches!(
+            secp256r1_verify(&msg_hash, &[0u8; 64], &pubkey),
+            Err(WasmApiCryptoError::GenericErr)
+        ));
+        // not a point on the curve
+        let mut off_curve = pubkey.clone();
+        off_curve[64] ^= 1;
+        assert!(matches!(
+            secp256r1_verify(&msg_hash, &signature, &off_curve),
+            Err(WasmApiCryptoError::GenericErr)
+        ));
+    }
+}
@@ -0,0 +1,67 @@
+//! Removal with confirmation, backing the `storage_secure_remove` host function.
+//!
+//! The key is removed and then read back, so a contract that must not leave a secret behind
+//! (like a one-time code or a revoked viewing key) can assert that it is really gone instead of
+//! trusting that `db_remove` succeeded. Both the removal and the read are charged like the
+//! regular `db_remove` and `db_read`.
+
+use log::*;
+
+use crate::errors::WasmEngineResult;
+use crate::storage_list::KeyValueStore;
+
+/// Removes `key` and returns whether a follow-up read confirms it no longer exists
+pub fn secure_remove<S: KeyValueStore>(store: &mut S, key: &[u8]) -> WasmEngineResult<bool> {
+    store.remove(key)?;
+
+    let removed = store.get(key)?.is_none();
+    if !removed {
+        warn!("storage_secure_remove found the key still present after removing it");
+    }
+
+    Ok(removed)
+}
+
+#[cfg(feature = "test")]
+pub mod tests {
+    use super::*;
+    use crate::storage_list::tests::MemoryStore;
+
+    /// A store that ignores removals
+    #[derive(Default)]
+    struct StickyStore(MemoryStore);
+
+    impl KeyValueStore for StickyStore {
+        fn get(&mut self, key: &[u8]) -> WasmEngineResult<Option<Vec<u8>>> {
+            self.0.get(key)
+        }
+
+        fn set(&mut self, key: &[u8], value: &[u8]) -> WasmEngineResult<()> {
+            self.0.set(key, value)
+        }
+
+        fn remove(&mut self, _key: &[u8]) -> WasmEngineResult<()> {
+            Ok(())
+        }
+    }
+
+    pub fn test_secure_remove_key_is_absent_after() {
+        let mut store = MemoryStore::default();
+        store.set(b"secret", b"value").unwrap();
+        store.set(b"other", b"value").unwrap();
+
+        assert!(secure_remove(&mut store, b"secret").unwrap());
+        assert_eq!(store.get(b"secret").unwrap(), None);
+        assert_eq!(store.get(b"other").unwrap(), Some(b"value".to_vec()));
+
+        // removing a missing key is confirmed too
+        assert!(secure_remove(&mut store, b"missing").unwrap());
+    }
+
+    pub fn test_secure_remove_reports_leftover_key() {
+        let mut store = StickyStore::default();
+        store.set(b"secret", b"value").unwrap();
+
+        assert!(!secure_remove(&mut store, b"secret").unwrap());
+    }
+}
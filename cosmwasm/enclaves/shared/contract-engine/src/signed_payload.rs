@@ -0,0 +1,157 @@
+//! Verification of signed oracle feeds, backing the `verify_signed_payload` host function.
+//!
+//! The signer signs `signer_time ++ payload`, with `signer_time` in seconds as a big-endian u64,
+//! so an old payload can't be passed off as fresh by pairing it with a newer timestamp. A feed is
+//! accepted if the signature is valid and it is at most `max_age` seconds older than the block.
+//! Ed25519 keys (32 bytes) and compressed secp256k1 keys (33 bytes, signing the sha256 of the
+//! message) are supported.
+
+use std::convert::TryFrom;
+
+use log::*;
+
+use enclave_crypto::{sha_256, WasmApiCryptoError};
+
+const NANOS_PER_SECOND: u64 = 1_000_000_000;
+
+/// The bytes the signer signs for `payload` at `signer_time`
+pub fn signed_bytes(payload: &[u8], signer_time: u64) -> Vec<u8> {
+    let mut message = Vec::with_capacity(8 + payload.len());
+    message.extend_from_slice(&signer_time.to_be_bytes());
+    message.extend_from_slice(payload);
+    message
+}
+
+fn verify_signature(
+    message: &[u8],
+    signature: &[u8],
+    pubkey: &[u8],
+) -> Result<(), WasmApiCryptoError> {
+    match pubkey.len() {
+        32 => {
+            let pubkey = ed25519_zebra::VerificationKey::try_from(pubkey)
+                .map_err(|_| WasmApiCryptoError::InvalidPubkeyFormat)?;
+            let signature = ed25519_zebra::Signature::try_from(signature)
+                .map_err(|_| WasmApiCryptoError::InvalidSignatureFormat)?;
+
+            pubkey
+                .verify(&signature, message)
+                .map_err(|_| WasmApiCryptoError::BadSignature)
+        }
+        33 => {
+            let pubkey = secp256k1::PublicKey::from_slice(pubkey)
+                .map_err(|_| WasmApiCryptoError::InvalidPubkeyFormat)?;
+            let signature = secp256k1::ecdsa::Signature::from_compact(signature)
+                .map_err(|_| WasmApiCryptoError::InvalidSignatureFormat)?;
+            let message = secp256k1::Message::from_slice(&sha_256(message))
+                .map_err(|_| WasmApiCryptoError::GenericErr)?;
+
+            secp256k1::Secp256k1::verification_only()
+                .verify_ecdsa(&message, &signature, &pubkey)
+                .map_err(|_| WasmApiCryptoError::BadSignature)
+        }
+        len => {
+            debug!("verify_signed_payload() got a public key of {} bytes", len);
+            Err(WasmApiCryptoError::InvalidPubkeyFormat)
+        }
+    }
+}
+
+/// Verifies that `payload` was signed by `pubkey` at `signer_time` and that it is at most
+/// `max_age` seconds old at `block_time`, which is in nanoseconds like `env.block.time`.
+/// Returns the payload so contracts can use it straight away.
+pub fn verify_signed_payload(
+    payload: &[u8],
+    signature: &[u8],
+    pubkey: &[u8],
+    max_age: u64,
+    signer_time: u64,
+    block_time: u64,
+) -> Result<Vec<u8>, WasmApiCryptoError> {
+    verify_signature(&signed_bytes(payload, signer_time), signature, pubkey)?;
+
+    // a feed from after the block can't have been observed yet, so it isn't accepted either
+    let now = block_time / NANOS_PER_SECOND;
+    if signer_time > now || now - signer_time > max_age {
+        debug!(
+            "verify_signed_payload() feed signed at {} is stale at block time {} (max age {})",
+            signer_time, now, max_age
+        );
+        return Err(WasmApiCryptoError::StaleFeed);
+    }
+
+    Ok(payload.to_vec())
+}
+
+#[cfg(feature = "test")]
+pub mod tests {
+    use super::*;
+
+    const BLOCK_TIME: u64 = 1_700_000_000 * NANOS_PER_SECOND;
+    const NOW: u64 = BLOCK_TIME / NANOS_PER_SECOND;
+    const PAYLOAD: &[u8] = br#"{"symbol":"SCRT","price":"0.42"}"#;
+
+    fn ed25519_sign(payload: &[u8], signer_time: u64) -> (Vec<u8>, Vec<u8>) {
+        let signing_key = ed25519_zebra::SigningKey::from([5u8; 32]);
+        let pubkey: [u8; 32] = ed25519_zebra::VerificationKey::from(&signing_key).into();
+        let signature: [u8; 64] = signing_key.sign(&signed_bytes(payload, signer_time)).into();
+        (signature.to_vec(), pubkey.to_vec())
+    }
+
+    fn secp256k1_sign(payload: &[u8], signer_time: u64) -> (Vec<u8>, Vec<u8>) {
+        let secp = secp256k1::Secp256k1::new();
+        let secret_key = secp256k1::SecretKey::from_slice(&[6u8; 32]).unwrap();
+        let pubkey = secp256k1::PublicKey::from_secret_key(&secp, &secret_key);
+        let message =
+            secp256k1::Message::from_slice(&sha_256(&signed_bytes(payload, signer_time))).unwrap();
+        let signature = secp.sign_ecdsa(&message, &secret_key);
+        (signature.serialize_compact().to_vec(), pubkey.serialize().to_vec())
+    }
+
+    pub fn test_verify_signed_payload_fresh() {
+        for sign in [ed25519_sign, secp256k1_sign] {
+            let (signature, pubkey) = sign(PAYLOAD, NOW - 30);
+            let result =
+                verify_signed_payload(PAYLOAD, &signature, &pubkey, 60, NOW - 30, BLOCK_TIME);
+            assert_eq!(result.unwrap(), PAYLOAD);
+
+            // exactly max_age old is still fresh
+            let (signature, pubkey) = sign(PAYLOAD, NOW - 60);
+            let result =
+                verify_signed_payload(PAYLOAD, &signature, &pubkey, 60, NOW - 60, BLOCK_TIME);
+            assert_eq!(result.unwrap(), PAYLOAD);
+        }
+    }
+
+    pub fn test_verify_signed_payload_stale() {
+        for sign in [ed25519_sign, secp256k1_sign] {
+            let (signature, pubkey) = sign(PAYLOAD, NOW - 61);
+            let result =
+                verify_signed_payload(PAYLOAD, &signature, &pubkey, 60, NOW - 61, BLOCK_TIME);
+            assert!(matches!(result, Err(WasmApiCryptoError::StaleFeed)));
+
+            let (signature, pubkey) = sign(PAYLOAD, NOW + 1);
+            let result =
+                verify_signed_payload(PAYLOAD, &signature, &pubkey, 60, NOW + 1, BLOCK_TIME);
+            assert!(matches!(result, Err(WasmApiCryptoError::StaleFeed)));
+        }
+    }
+
+    pub fn test_verify_signed_payload_bad_signature() {
+        for sign in [ed25519_sign, secp256k1_sign] {
+            let (signature, pubkey) = sign(PAYLOAD, NOW - 61);
+
+            // an old feed re-timestamped as fresh
+            let result = verify_signed_payload(PAYLOAD, &signature, &pubkey, 60, NOW, BLOCK_TIME);
+            assert!(matches!(result, Err(WasmApiCryptoError::BadSignature)));
+
+            let result =
+                verify_signed_payload(b"{}", &signature, &pubkey, 60, NOW - 61, BLOCK_TIME);
+            assert!(matches!(result, Err(WasmApiCryptoError::BadSignature)));
+        }
+
+        let (signature, pubkey) = ed25519_sign(PAYLOAD, NOW);
+        let result = verify_signed_payload(PAYLOAD, &signature, &pubkey[..31], 60, NOW, BLOCK_TIME);
+        assert!(matches!(result, Err(WasmApiCryptoError::InvalidPubkeyFormat)));
+    }
+}
@@ -0,0 +1,383 @@
+//! Sealed snapshots of a contract's state, for moving it to another node for upgrades or audits.
+//!
+//! A snapshot holds the contract's key/value pairs exactly as they are stored on chain, so they
+//! stay encrypted with the contract's state key. The snapshot is then sealed for a single peer,
+//! like the seed is in `ecall_import_seed`: it's encrypted with a Diffie-Hellman key between this
+//! node's registration key and the peer's attested registration key. Finally, it's signed with a
+//! key derived from the consensus seed, so the peer knows an enclave of the same network made it.
+//!
+//! Every entry has to decrypt with the contract's key to be exported, so a snapshot can't carry
+//! state of other contracts. The contract's address and key are signed along with the sealed
+//! state, and the importing node checks them against the contract it's restoring before it
+//! decrypts anything.
+
+use std::convert::TryFrom;
+
+use log::*;
+use serde::{Deserialize, Serialize};
+
+use enclave_crypto::{
+    sha_256, AESKey, KeyPair, SIVEncryptable, HASH_SIZE, KEY_MANAGER, PUBLIC_KEY_SIZE,
+};
+use enclave_ffi_types::EnclaveError;
+
+use crate::contract_validation::ContractKey;
+use crate::db::is_contract_entry;
+
+const STATE_SNAPSHOT_DOMAIN: &[u8] = b"secret_state_snapshot_v1";
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct StateSnapshot {
+    pub contract_address: Vec<u8>,
+    pub contract_key: Vec<u8>,
+    /// Raw key/value pairs, still encrypted with the contract's state key
+    pub entries: Vec<(Vec<u8>, Vec<u8>)>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SealedSnapshot {
+    exporter_key: [u8; PUBLIC_KEY_SIZE],
+    importer_key: [u8; PUBLIC_KEY_SIZE],
+    contract_address: Vec<u8>,
+    contract_key: Vec<u8>,
+    sealed_state: Vec<u8>,
+    signature: Vec<u8>,
+}
+
+fn snapshot_signing_key(snapshot_secret: &AESKey) -> ed25519_zebra::SigningKey {
+    ed25519_zebra::SigningKey::from(*snapshot_secret.get())
+}
+
+fn snapshot_sign_bytes(
+    exporter_key: &[u8; PUBLIC_KEY_SIZE],
+    importer_key: &[u8; PUBLIC_KEY_SIZE],
+    contract_address: &[u8],
+    contract_key: &[u8],
+    sealed_state: &[u8],
+) -> [u8; HASH_SIZE] {
+    let mut data_to_sign = vec![];
+    data_to_sign.extend_from_slice(STATE_SNAPSHOT_DOMAIN);
+    data_to_sign.extend_from_slice(exporter_key);
+    data_to_sign.extend_from_slice(importer_key);
+    // length-prefixed, so the address can't be told apart from the key
+    for field in &[contract_address, contract_key] {
+        data_to_sign.extend_from_slice(&(field.len() as u32).to_be_bytes());
+        data_to_sign.extend_from_slice(field);
+    }
+    data_to_sign.extend_from_slice(sealed_state);
+
+    sha_256(data_to_sign.as_slice())
+}
+
+/// Encrypts `snapshot` for the holder of `importer_key` and signs it with `snapshot_secret`
+pub fn seal_snapshot(
+    snapshot: &StateSnapshot,
+    exporter: &KeyPair,
+    importer_key: &[u8; PUBLIC_KEY_SIZE],
+    snapshot_secret: &AESKey,
+) -> Result<Vec<u8>, EnclaveError> {
+    let serialized = bincode2::serialize(snapshot).map_err(|err| {
+        warn!("failed to serialize the state snapshot: {:?}", err);
+        EnclaveError::FailedToSerialize
+    })?;
+
+    let sealing_key = AESKey::new_from_slice(&exporter.diffie_hellman(importer_key));
+    let sealed_state = sealing_key
+        .encrypt_siv(&serialized, Some(&[importer_key.as_slice()]))
+        .map_err(|_| EnclaveError::EncryptionError)?;
+
+    let exporter_key = exporter.get_pubkey();
+    let signature: [u8; 64] = snapshot_signing_key(snapshot_secret)
+        .sign(&snapshot_sign_bytes(
+            &exporter_key,
+            importer_key,
+            &snapshot.contract_address,
+            &snapshot.contract_key,
+            &sealed_state,
+        ))
+        .into();
+
+    bincode2::serialize(&SealedSnapshot {
+        exporter_key,
+        importer_key: *importer_key,
+        contract_address: snapshot.contract_address.clone(),
+        contract_key: snapshot.contract_key.clone(),
+        sealed_state,
+        signature: signature.to_vec(),
+    })
+    .map_err(|_| EnclaveError::FailedToSerialize)
+}
+
+/// Verifies that `sealed` was signed with `snapshot_secret` and sealed for `importer` by the
+/// holder of `exporter_key`, for the contract at `contract_address` with `contract_key`, and
+/// decrypts it
+pub fn open_snapshot(
+    sealed: &[u8],
+    importer: &KeyPair,
+    exporter_key: &[u8; PUBLIC_KEY_SIZE],
+    contract_address: &[u8],
+    contract_key: &[u8],
+    snapshot_secret: &AESKey,
+) -> Result<StateSnapshot, EnclaveError> {
+    let sealed: SealedSnapshot = bincode2::deserialize(sealed).map_err(|err| {
+        warn!("failed to deserialize the sealed state snapshot: {:?}", err);
+        EnclaveError::FailedToDeserialize
+    })?;
+
+    let importer_key = importer.get_pubkey();
+    if &sealed.exporter_key != exporter_key || sealed.importer_key != importer_key {
+        warn!("state snapshot was sealed between other nodes");
+        return Err(EnclaveError::ValidationFailure);
+    }
+    if sealed.contract_address != contract_address || sealed.contract_key != contract_key {
+        warn!("state snapshot is for a different contract");
+        return Err(EnclaveError::ValidationFailure);
+    }
+
+    let verification_key =
+        ed25519_zebra::VerificationKey::from(&snapshot_signing_key(snapshot_secret));
+    let signature = ed25519_zebra::Signature::try_from(sealed.signature.as_slice())
+        .map_err(|_| EnclaveError::ValidationFailure)?;
+    verification_key
+        .verify(
+            &signature,
+            &snapshot_sign_bytes(
+                exporter_key,
+                &importer_key,
+                contract_address,
+                contract_key,
+                &sealed.sealed_state,
+            ),
+        )
+        .map_err(|_| {
+            warn!("state snapshot signature is invalid");
+            EnclaveError::ValidationFailure
+        })?;
+
+    let sealing_key = AESKey::new_from_slice(&importer.diffie_hellman(exporter_key));
+    let serialized = sealing_key
+        .decrypt_siv(&sealed.sealed_state, Some(&[importer_key.as_slice()]))
+        .map_err(|_| EnclaveError::DecryptionError)?;
+
+    let snapshot: StateSnapshot =
+        bincode2::deserialize(&serialized).map_err(|_| EnclaveError::FailedToDeserialize)?;
+
+    // the exporter put the same address and key in the signed header and the sealed state
+    if snapshot.contract_address != contract_address || snapshot.contract_key != contract_key {
+        warn!("sealed state of the snapshot is for a different contract");
+        return Err(EnclaveError::ValidationFailure);
+    }
+
+    Ok(snapshot)
+}
+
+fn check_entries(contract_key: &ContractKey, entries: &[(Vec<u8>, Vec<u8>)]) -> bool {
+    entries
+        .iter()
+        .all(|(key, value)| is_contract_entry(key, value, contract_key))
+}
+
+/// Exports the state of the contract at `contract_address` for the node with the registration
+/// key `importer_key`, which the caller has to have verified the attestation of
+pub fn export_contract_state(
+    contract_address: &[u8],
+    contract_key: &ContractKey,
+    entries: Vec<(Vec<u8>, Vec<u8>)>,
+    importer_key: &[u8; PUBLIC_KEY_SIZE],
+) -> Result<Vec<u8>, EnclaveError> {
+    if !check_entries(contract_key, &entries) {
+        warn!("refusing to export state entries that don't belong to the contract");
+        return Err(EnclaveError::ValidationFailure);
+    }
+
+    let exporter = KEY_MANAGER
+        .get_registration_key()
+        .map_err(|_| EnclaveError::FailedUnseal)?;
+    let snapshot_secret = KEY_MANAGER
+        .get_state_snapshot_secret()
+        .map_err(|_| EnclaveError::FailedUnseal)?;
+
+    let snapshot = StateSnapshot {
+        contract_address: contract_address.to_vec(),
+        contract_key: contract_key.to_vec(),
+        entries,
+    };
+
+    seal_snapshot(&snapshot, &exporter, importer_key, &snapshot_secret)
+}
+
+/// Opens a snapshot exported for this node by the node with the (attested) registration key
+/// `exporter_key`, and returns its entries if it holds the state of the contract at
+/// `contract_address` with `contract_key`
+pub fn import_contract_state(
+    sealed: &[u8],
+    contract_address: &[u8],
+    contract_key: &ContractKey,
+    exporter_key: &[u8; PUBLIC_KEY_SIZE],
+) -> Result<Vec<(Vec<u8>, Vec<u8>)>, EnclaveError> {
+    let importer = KEY_MANAGER
+        .get_registration_key()
+        .map_err(|_| EnclaveError::FailedUnseal)?;
+    let snapshot_secret = KEY_MANAGER
+        .get_state_snapshot_secret()
+        .map_err(|_| EnclaveError::FailedUnseal)?;
+
+    let snapshot = open_snapshot(
+        sealed,
+        &importer,
+        exporter_key,
+        contract_address,
+        contract_key,
+        &snapshot_secret,
+    )?;
+
+    if !check_entries(contract_key, &snapshot.entries) {
+        warn!("state snapshot has entries that don't belong to the contract");
+        return Err(EnclaveError::ValidationFailure);
+    }
+
+    Ok(snapshot.entries)
+}
+
+#[cfg(feature = "test")]
+pub mod tests {
+    use super::*;
+
+    fn snapshot() -> StateSnapshot {
+        StateSnapshot {
+            contract_address: b"contract".to_vec(),
+            contract_key: vec![3u8; 64],
+            entries: vec![
+                (b"encrypted key 1".to_vec(), b"encrypted value 1".to_vec()),
+                (b"encrypted key 2".to_vec(), b"encrypted value 2".to_vec()),
+            ],
+        }
+    }
+
+    /// Opens `sealed` for the contract of `snapshot()`
+    fn open(
+        sealed: &[u8],
+        importer: &KeyPair,
+        exporter_key: &[u8; PUBLIC_KEY_SIZE],
+        snapshot_secret: &AESKey,
+    ) -> Result<StateSnapshot, EnclaveError> {
+        let snapshot = snapshot();
+        open_snapshot(
+            sealed,
+            importer,
+            exporter_key,
+            &snapshot.contract_address,
+            &snapshot.contract_key,
+            snapshot_secret,
+        )
+    }
+
+    /// Two simulated enclaves of the same network: separate registration keys, and the same
+    /// consensus derived snapshot secret
+    pub fn test_state_snapshot_round_trip() {
+        let snapshot_secret = AESKey::new_from_slice(&[1u8; 32]);
+        let exporter = KeyPair::new().unwrap();
+        let importer = KeyPair::new().unwrap();
+
+        let sealed = seal_snapshot(
+            &snapshot(),
+            &exporter,
+            &importer.get_pubkey(),
+            &snapshot_secret,
+        )
+        .unwrap();
+        let opened = open(&sealed, &importer, &exporter.get_pubkey(), &snapshot_secret).unwrap();
+
+        assert_eq!(opened, snapshot());
+    }
+
+    pub fn test_state_snapshot_rejects_other_nodes() {
+        let snapshot_secret = AESKey::new_from_slice(&[1u8; 32]);
+        let exporter = KeyPair::new().unwrap();
+        let importer = KeyPair::new().unwrap();
+        let other = KeyPair::new().unwrap();
+
+        let sealed = seal_snapshot(
+            &snapshot(),
+            &exporter,
+            &importer.get_pubkey(),
+            &snapshot_secret,
+        )
+        .unwrap();
+
+        // only the peer it was sealed for can open it
+        assert!(matches!(
+            open(&sealed, &other, &exporter.get_pubkey(), &snapshot_secret),
+            Err(EnclaveError::ValidationFailure)
+        ));
+        // and only if it came from the attested exporter
+        assert!(matches!(
+            open(&sealed, &importer, &other.get_pubkey(), &snapshot_secret),
+            Err(EnclaveError::ValidationFailure)
+        ));
+
+        // a snapshot signed by another network is rejected
+        let other_secret = AESKey::new_from_slice(&[2u8; 32]);
+        assert!(matches!(
+            open(&sealed, &importer, &exporter.get_pubkey(), &other_secret),
+            Err(EnclaveError::ValidationFailure)
+        ));
+
+        // and so is a tampered one
+        let mut tampered = sealed;
+        let last = tampered.len() - 70;
+        tampered[last] ^= 1;
+        assert!(open(
+            &tampered,
+            &importer,
+            &exporter.get_pubkey(),
+            &snapshot_secret
+        )
+        .is_err());
+    }
+
+    pub fn test_state_snapshot_binds_contract() {
+        let snapshot_secret = AESKey::new_from_slice(&[1u8; 32]);
+        let exporter = KeyPair::new().unwrap();
+        let importer = KeyPair::new().unwrap();
+        let open_for = |sealed: &[u8], contract_address: &[u8], contract_key: &[u8]| {
+            open_snapshot(
+                sealed,
+                &importer,
+                &exporter.get_pubkey(),
+                contract_address,
+                contract_key,
+                &snapshot_secret,
+            )
+        };
+
+        let sealed = seal_snapshot(
+            &snapshot(),
+            &exporter,
+            &importer.get_pubkey(),
+            &snapshot_secret,
+        )
+        .unwrap();
+
+        // restoring it into another contract, or one with another key, fails
+        assert!(matches!(
+            open_for(&sealed, b"other contract", &[3u8; 64]),
+            Err(EnclaveError::ValidationFailure)
+        ));
+        assert!(matches!(
+            open_for(&sealed, b"contract", &[4u8; 64]),
+            Err(EnclaveError::ValidationFailure)
+        ));
+
+        // and so does relabeling it, since the signature covers the address and the key
+        let mut relabeled: SealedSnapshot = bincode2::deserialize(&sealed).unwrap();
+        relabeled.contract_address = b"other contract".to_vec();
+        let relabeled = bincode2::serialize(&relabeled).unwrap();
+        assert!(matches!(
+            open_for(&relabeled, b"other contract", &[3u8; 64]),
+            Err(EnclaveError::ValidationFailure)
+        ));
+
+        assert!(open_for(&sealed, b"contract", &[3u8; 64]).is_ok());
+    }
+}
@@ -0,0 +1,56 @@
+//! Compare-and-swap of a single key, backing the `storage_cas` host function.
+//!
+//! A contract runs alone for the whole of an execution, so a read and a write in the same call are
+//! already atomic. What a contract can't do with `db_read` and `db_write` is express "only if the
+//! value is still the one I saw", e.g. when a counter's expected value comes from a message that
+//! was built off-chain. The read and the write are charged like the regular `db_read` and
+//! `db_write`.
+
+use crate::errors::WasmEngineResult;
+use crate::storage_list::KeyValueStore;
+
+/// Writes `new` under `key` if its current value is `expected`, where `None` expects the key not
+/// to exist, and returns whether it did
+pub fn compare_and_swap<S: KeyValueStore>(
+    store: &mut S,
+    key: &[u8],
+    expected: Option<&[u8]>,
+    new: &[u8],
+) -> WasmEngineResult<bool> {
+    if store.get(key)?.as_deref() != expected {
+        return Ok(false);
+    }
+
+    store.set(key, new)?;
+
+    Ok(true)
+}
+
+#[cfg(feature = "test")]
+pub mod tests {
+    use super::*;
+    use crate::storage_list::tests::MemoryStore;
+
+    pub fn test_storage_cas_swaps_expected_value() {
+        let mut store = MemoryStore::default();
+
+        // a missing key is expected with None
+        assert!(compare_and_swap(&mut store, b"counter", None, b"1").unwrap());
+        assert_eq!(store.get(b"counter").unwrap(), Some(b"1".to_vec()));
+
+        assert!(compare_and_swap(&mut store, b"counter", Some(b"1"), b"2").unwrap());
+        assert_eq!(store.get(b"counter").unwrap(), Some(b"2".to_vec()));
+    }
+
+    pub fn test_storage_cas_keeps_unexpected_value() {
+        let mut store = MemoryStore::default();
+
+        assert!(!compare_and_swap(&mut store, b"counter", Some(b"1"), b"2").unwrap());
+        assert_eq!(store.get(b"counter").unwrap(), None);
+
+        store.set(b"counter", b"5").unwrap();
+        assert!(!compare_and_swap(&mut store, b"counter", Some(b"1"), b"2").unwrap());
+        assert!(!compare_and_swap(&mut store, b"counter", None, b"2").unwrap());
+        assert_eq!(store.get(b"counter").unwrap(), Some(b"5".to_vec()));
+    }
+}
@@ -0,0 +1,250 @@
+//! An append-only list stored under a single key, backing the `storage_push`, `storage_pop` and
+//! `storage_len` host functions.
+//!
+//! The layout is plain key-value entries so contracts can still use raw `db_read`/`db_write`.
+//! Lists are kept in the `storage_list` namespace, under `key` nested in it the way `namespace`
+//! frames names: the list's length is stored under the nested key, and the item at `index` under
+//! the nested key followed by `index`, with lengths and indexes as big-endian u32s. Framing the
+//! key with its length keeps the items of one list from landing on the keys of another, like the
+//! item at index 0 of `"a"` and the length of `"a\0\0\0"` would without it.
+
+use log::*;
+
+use crate::errors::{WasmEngineError, WasmEngineResult};
+use crate::namespace::{namespaced_key, to_length_prefixed};
+
+const LENGTH_SIZE: usize = 4;
+const LIST_NAMESPACE: &[u8] = b"storage_list";
+
+/// The storage operations the list is built on
+pub trait KeyValueStore {
+    fn get(&mut self, key: &[u8]) -> WasmEngineResult<Option<Vec<u8>>>;
+    fn set(&mut self, key: &[u8], value: &[u8]) -> WasmEngineResult<()>;
+    fn remove(&mut self, key: &[u8]) -> WasmEngineResult<()>;
+}
+
+/// Rejects empty keys, which can't be written or removed. An empty value on the other hand is a
+/// value like any other, and reads back as `Some(vec![])` rather than as a missing key.
+pub fn check_key(key: &[u8]) -> WasmEngineResult<()> {
+    if key.is_empty() {
+        debug!("contract tried to write or remove an empty storage key");
+        return Err(WasmEngineError::EmptyKey);
+    }
+    Ok(())
+}
+
+/// The key under which the length of the list at `key` is stored
+pub fn length_key(key: &[u8]) -> WasmEngineResult<Vec<u8>> {
+    namespaced_key(LIST_NAMESPACE, &to_length_prefixed(key)?)
+}
+
+/// The key under which the item at `index` of the list at `key` is stored
+pub fn item_key(key: &[u8], index: u32) -> WasmEngineResult<Vec<u8>> {
+    Ok(item_key_at(&length_key(key)?, index))
+}
+
+/// Like `item_key`, for the list whose length is stored under `length_key`
+pub fn item_key_at(length_key: &[u8], index: u32) -> Vec<u8> {
+    let mut item_key = Vec::with_capacity(length_key.len() + LENGTH_SIZE);
+    item_key.extend_from_slice(length_key);
+    item_key.extend_from_slice(&index.to_be_bytes());
+    item_key
+}
+
+/// Returns the number of items in the list at `key`, or 0 if there is no list there
+pub fn len<S: KeyValueStore>(store: &mut S, key: &[u8]) -> WasmEngineResult<u32> {
+    len_at(store, &length_key(key)?)
+}
+
+/// Appends `value` to the list at `key` and returns the new length
+pub fn push<S: KeyValueStore>(store: &mut S, key: &[u8], value: &[u8]) -> WasmEngineResult<u32> {
+    check_key(key)?;
+    push_at(store, &length_key(key)?, value)
+}
+
+/// Removes and returns the last item of the list at `key`, or `None` if it is empty
+pub fn pop<S: KeyValueStore>(store: &mut S, key: &[u8]) -> WasmEngineResult<Option<Vec<u8>>> {
+    check_key(key)?;
+    pop_at(store, &length_key(key)?)
+}
+
+/// Like `len`, for the list whose length is stored under `length_key`. Lets other structures keep
+/// their lists in a part of the key space that contracts' own lists can't reach.
+pub fn len_at<S: KeyValueStore>(store: &mut S, length_key: &[u8]) -> WasmEngineResult<u32> {
+    match store.get(length_key)? {
+        None => Ok(0),
+        Some(length) if length.len() == LENGTH_SIZE => {
+            let mut bytes = [0u8; LENGTH_SIZE];
+            bytes.copy_from_slice(&length);
+            Ok(u32::from_be_bytes(bytes))
+        }
+        Some(length) => {
+            debug!(
+                "storage list length has {} bytes instead of {}, was the key overwritten?",
+                length.len(),
+                LENGTH_SIZE
+            );
+            Err(WasmEngineError::DeserializationError)
+        }
+    }
+}
+
+/// Like `push`, for the list whose length is stored under `length_key`
+pub fn push_at<S: KeyValueStore>(
+    store: &mut S,
+    length_key: &[u8],
+    value: &[u8],
+) -> WasmEngineResult<u32> {
+    let length = len_at(store, length_key)?;
+    let new_length = length.checked_add(1).ok_or_else(|| {
+        debug!("storage list is full");
+        WasmEngineError::SerializationError
+    })?;
+
+    store.set(&item_key_at(length_key, length), value)?;
+    store.set(length_key, &new_length.to_be_bytes())?;
+
+    Ok(new_length)
+}
+
+/// Like `pop`, for the list whose length is stored under `length_key`
+pub fn pop_at<S: KeyValueStore>(
+    store: &mut S,
+    length_key: &[u8],
+) -> WasmEngineResult<Option<Vec<u8>>> {
+    let length = len_at(store, length_key)?;
+    if length == 0 {
+        return Ok(None);
+    }
+
+    let last_key = item_key_at(length_key, length - 1);
+    let value = store.get(&last_key)?;
+    store.remove(&last_key)?;
+
+    // an emptied list leaves nothing behind in storage
+    if length == 1 {
+        store.remove(length_key)?;
+    } else {
+        store.set(length_key, &(length - 1).to_be_bytes())?;
+    }
+
+    // the item can only be missing if it was removed with raw db_remove
+    Ok(Some(value.unwrap_or_default()))
+}
+
+#[cfg(feature = "test")]
+pub mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[derive(Default)]
+    pub struct MemoryStore(pub HashMap<Vec<u8>, Vec<u8>>);
+
+    impl KeyValueStore for MemoryStore {
+        fn get(&mut self, key: &[u8]) -> WasmEngineResult<Option<Vec<u8>>> {
+            Ok(self.0.get(key).cloned())
+        }
+
+        fn set(&mut self, key: &[u8], value: &[u8]) -> WasmEngineResult<()> {
+            check_key(key)?;
+            self.0.insert(key.to_vec(), value.to_vec());
+            Ok(())
+        }
+
+        fn remove(&mut self, key: &[u8]) -> WasmEngineResult<()> {
+            check_key(key)?;
+            self.0.remove(key);
+            Ok(())
+        }
+    }
+
+    pub fn test_storage_list_push_pop_ordering() {
+        let mut store = MemoryStore::default();
+
+        assert_eq!(push(&mut store, b"list", b"a").unwrap(), 1);
+        assert_eq!(push(&mut store, b"list", b"b").unwrap(), 2);
+        assert_eq!(push(&mut store, b"list", b"c").unwrap(), 3);
+
+        assert_eq!(pop(&mut store, b"list").unwrap(), Some(b"c".to_vec()));
+        assert_eq!(push(&mut store, b"list", b"d").unwrap(), 3);
+        assert_eq!(pop(&mut store, b"list").unwrap(), Some(b"d".to_vec()));
+        assert_eq!(pop(&mut store, b"list").unwrap(), Some(b"b".to_vec()));
+        assert_eq!(pop(&mut store, b"list").unwrap(), Some(b"a".to_vec()));
+        assert_eq!(pop(&mut store, b"list").unwrap(), None);
+
+        // nothing is left behind
+        assert!(store.0.is_empty());
+    }
+
+    pub fn test_storage_list_len() {
+        let mut store = MemoryStore::default();
+        assert_eq!(len(&mut store, b"list").unwrap(), 0);
+
+        for i in 0..10u8 {
+            push(&mut store, b"list", &[i]).unwrap();
+        }
+        assert_eq!(len(&mut store, b"list").unwrap(), 10);
+
+        // lists under other keys are independent
+        push(&mut store, b"other", b"x").unwrap();
+        assert_eq!(len(&mut store, b"other").unwrap(), 1);
+        assert_eq!(len(&mut store, b"list").unwrap(), 10);
+
+        pop(&mut store, b"list").unwrap();
+        assert_eq!(len(&mut store, b"list").unwrap(), 9);
+    }
+
+    pub fn test_storage_list_raw_compatibility() {
+        let mut store = MemoryStore::default();
+        push(&mut store, b"list", b"a").unwrap();
+        push(&mut store, b"list", b"b").unwrap();
+
+        // the layout can be read with raw gets
+        assert_eq!(
+            store.0.get(&b"\0\x0cstorage_list\0\x04list"[..]),
+            Some(&2u32.to_be_bytes().to_vec())
+        );
+        assert_eq!(
+            store.0.get(&b"\0\x0cstorage_list\0\x04list\0\0\0\x01"[..]),
+            Some(&b"b".to_vec())
+        );
+
+        // and items written with raw sets are picked up
+        store.set(&item_key(b"list", 2).unwrap(), b"c").unwrap();
+        store
+            .set(&length_key(b"list").unwrap(), &3u32.to_be_bytes())
+            .unwrap();
+        assert_eq!(pop(&mut store, b"list").unwrap(), Some(b"c".to_vec()));
+
+        // a key that doesn't hold a list length is rejected
+        store
+            .set(&length_key(b"not a list").unwrap(), b"some value")
+            .unwrap();
+        assert!(len(&mut store, b"not a list").is_err());
+        assert!(push(&mut store, b"not a list", b"a").is_err());
+
+        // and the list's own key is left for the contract
+        assert_eq!(store.0.get(&b"list"[..]), None);
+    }
+
+    pub fn test_storage_list_names_dont_collide() {
+        let mut store = MemoryStore::default();
+
+        // without the length prefix, item 0 of "a" would be the length of "a\0\0\0"
+        push(&mut store, b"a", b"item").unwrap();
+        assert_eq!(len(&mut store, b"a\0\0\0").unwrap(), 0);
+        push(&mut store, b"a\0\0\0", b"other").unwrap();
+
+        assert_eq!(len(&mut store, b"a").unwrap(), 1);
+        assert_eq!(pop(&mut store, b"a").unwrap(), Some(b"item".to_vec()));
+        assert_eq!(
+            pop(&mut store, b"a\0\0\0").unwrap(),
+            Some(b"other".to_vec())
+        );
+        assert!(store.0.is_empty());
+
+        // an empty name can't hold a list, and a name too long to frame can't either
+        assert!(push(&mut store, b"", b"a").is_err());
+        assert!(push(&mut store, &[b'a'; u16::MAX as usize + 1], b"a").is_err());
+    }
+}
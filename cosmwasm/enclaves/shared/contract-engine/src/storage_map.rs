@@ -0,0 +1,315 @@
+//! A map stored under a single key whose iteration order is part of its state, backing the
+//! `storage_map_*` host functions.
+//!
+//! Contracts that iterate a `HashMap`, or a JSON object, can end up depending on an order that
+//! differs between nodes. Entries of this map are iterated by position instead: in insertion
+//! order, except that removing an entry moves the last entry into its position. Every node
+//! applies the same operations, so every node iterates the same way.
+//!
+//! The layout is plain key-value entries, like `storage_list`. Maps are kept in the
+//! `storage_map` namespace, and everything of a map is stored under its name nested in it the way
+//! `namespace` frames names: the map's keys as a list whose length is under `... ++ 0x00`, and
+//! the entry for `key` under `... ++ 0x01 ++ key` as its position, a big-endian u32, followed by
+//! its value. Framing the name with its length keeps one map's entries apart from another's, and
+//! the namespace keeps them apart from `storage_list` keys, which are in a namespace of their own.
+
+use log::*;
+
+use crate::errors::{WasmEngineError, WasmEngineResult};
+use crate::namespace::{namespaced_key, to_length_prefixed};
+use crate::storage_list::{self, item_key_at, KeyValueStore};
+
+const POSITION_SIZE: usize = 4;
+const MAP_NAMESPACE: &[u8] = b"storage_map";
+
+/// The prefix of every key of the map at `map`
+fn map_prefix(map: &[u8]) -> WasmEngineResult<Vec<u8>> {
+    namespaced_key(MAP_NAMESPACE, &to_length_prefixed(map)?)
+}
+
+/// The key under which the length of the list of the map's keys is stored
+fn keys_key(map: &[u8]) -> WasmEngineResult<Vec<u8>> {
+    let mut keys_key = map_prefix(map)?;
+    keys_key.push(0);
+    Ok(keys_key)
+}
+
+/// The key under which the entry for `key` is stored
+pub fn entry_key(map: &[u8], key: &[u8]) -> WasmEngineResult<Vec<u8>> {
+    let mut entry_key = map_prefix(map)?;
+    entry_key.push(1);
+    entry_key.extend_from_slice(key);
+    Ok(entry_key)
+}
+
+/// Returns the position and value of the entry for `key`
+fn get_entry<S: KeyValueStore>(
+    store: &mut S,
+    map: &[u8],
+    key: &[u8],
+) -> WasmEngineResult<Option<(u32, Vec<u8>)>> {
+    let entry = match store.get(&entry_key(map, key)?)? {
+        Some(entry) => entry,
+        None => return Ok(None),
+    };
+    if entry.len() < POSITION_SIZE {
+        debug!("storage map entry is too short, was the key overwritten?");
+        return Err(WasmEngineError::DeserializationError);
+    }
+
+    let mut position = [0u8; POSITION_SIZE];
+    position.copy_from_slice(&entry[..POSITION_SIZE]);
+    Ok(Some((
+        u32::from_be_bytes(position),
+        entry[POSITION_SIZE..].to_vec(),
+    )))
+}
+
+fn set_entry<S: KeyValueStore>(
+    store: &mut S,
+    map: &[u8],
+    key: &[u8],
+    position: u32,
+    value: &[u8],
+) -> WasmEngineResult<()> {
+    let mut entry = Vec::with_capacity(POSITION_SIZE + value.len());
+    entry.extend_from_slice(&position.to_be_bytes());
+    entry.extend_from_slice(value);
+    store.set(&entry_key(map, key)?, &entry)
+}
+
+/// Returns the number of entries in the map at `map`
+pub fn len<S: KeyValueStore>(store: &mut S, map: &[u8]) -> WasmEngineResult<u32> {
+    storage_list::len_at(store, &keys_key(map)?)
+}
+
+/// Returns the value for `key` in the map at `map`
+pub fn get<S: KeyValueStore>(
+    store: &mut S,
+    map: &[u8],
+    key: &[u8],
+) -> WasmEngineResult<Option<Vec<u8>>> {
+    Ok(get_entry(store, map, key)?.map(|(_, value)| value))
+}
+
+/// Sets the value for `key` in the map at `map`. A new key goes last, while an existing key keeps
+/// its position. Returns whether the key is new.
+pub fn insert<S: KeyValueStore>(
+    store: &mut S,
+    map: &[u8],
+    key: &[u8],
+    value: &[u8],
+) -> WasmEngineResult<bool> {
+    if let Some((position, _)) = get_entry(store, map, key)? {
+        set_entry(store, map, key, position, value)?;
+        return Ok(false);
+    }
+
+    let new_len = storage_list::push_at(store, &keys_key(map)?, key)?;
+    set_entry(store, map, key, new_len - 1, value)?;
+
+    Ok(true)
+}
+
+/// Removes `key` from the map at `map` and returns its value. The last entry takes the removed
+/// entry's position.
+pub fn remove<S: KeyValueStore>(
+    store: &mut S,
+    map: &[u8],
+    key: &[u8],
+) -> WasmEngineResult<Option<Vec<u8>>> {
+    let (position, value) = match get_entry(store, map, key)? {
+        Some(entry) => entry,
+        None => return Ok(None),
+    };
+    store.remove(&entry_key(map, key)?)?;
+
+    let keys_key = keys_key(map)?;
+    let last_key = storage_list::pop_at(store, &keys_key)?.ok_or_else(|| {
+        debug!("storage map has an entry but no keys, was the key overwritten?");
+        WasmEngineError::DeserializationError
+    })?;
+
+    if last_key != key {
+        let (_, last_value) = get_entry(store, map, &last_key)?.ok_or_else(|| {
+            debug!("storage map is missing the entry of one of its keys");
+            WasmEngineError::DeserializationError
+        })?;
+        store.set(&item_key_at(&keys_key, position), &last_key)?;
+        set_entry(store, map, &last_key, position, &last_value)?;
+    }
+
+    Ok(Some(value))
+}
+
+/// Returns the key and value at `position` in the map at `map`, or `None` past its end
+pub fn entry_at<S: KeyValueStore>(
+    store: &mut S,
+    map: &[u8],
+    position: u32,
+) -> WasmEngineResult<Option<(Vec<u8>, Vec<u8>)>> {
+    let keys_key = keys_key(map)?;
+    if position >= storage_list::len_at(store, &keys_key)? {
+        return Ok(None);
+    }
+
+    let key = store
+        .get(&item_key_at(&keys_key, position))?
+        .ok_or_else(|| {
+            debug!("storage map is missing one of its keys");
+            WasmEngineError::DeserializationError
+        })?;
+    let value = get(store, map, &key)?.ok_or_else(|| {
+        debug!("storage map is missing the entry of one of its keys");
+        WasmEngineError::DeserializationError
+    })?;
+
+    Ok(Some((key, value)))
+}
+
+/// Encodes an entry as sections, the way contracts pass lists to the host: each section followed
+/// by its length as a big-endian u32
+pub fn encode_entry(key: &[u8], value: &[u8]) -> Vec<u8> {
+    let mut encoded = Vec::with_capacity(key.len() + value.len() + 2 * POSITION_SIZE);
+    for section in &[key, value] {
+        encoded.extend_from_slice(section);
+        encoded.extend_from_slice(&(section.len() as u32).to_be_bytes());
+    }
+    encoded
+}
+
+#[cfg(feature = "test")]
+pub mod tests {
+    use super::*;
+    use crate::storage_list::tests::MemoryStore;
+
+    fn entries(store: &mut MemoryStore, map: &[u8]) -> Vec<(Vec<u8>, Vec<u8>)> {
+        let mut entries = vec![];
+        let mut position = 0;
+        while let Some(entry) = entry_at(store, map, position).unwrap() {
+            entries.push(entry);
+            position += 1;
+        }
+        assert_eq!(entries.len() as u32, len(store, map).unwrap());
+        entries
+    }
+
+    fn entry(key: &str, value: &str) -> (Vec<u8>, Vec<u8>) {
+        (key.as_bytes().to_vec(), value.as_bytes().to_vec())
+    }
+
+    pub fn test_storage_map_insertion_order() {
+        let mut store = MemoryStore::default();
+
+        assert!(insert(&mut store, b"map", b"zebra", b"1").unwrap());
+        assert!(insert(&mut store, b"map", b"apple", b"2").unwrap());
+        assert!(insert(&mut store, b"map", b"mango", b"3").unwrap());
+        // replacing a value keeps its position
+        assert!(!insert(&mut store, b"map", b"zebra", b"4").unwrap());
+
+        assert_eq!(
+            entries(&mut store, b"map"),
+            vec![
+                entry("zebra", "4"),
+                entry("apple", "2"),
+                entry("mango", "3")
+            ]
+        );
+        assert_eq!(
+            get(&mut store, b"map", b"apple").unwrap(),
+            Some(b"2".to_vec())
+        );
+        assert_eq!(get(&mut store, b"map", b"kiwi").unwrap(), None);
+    }
+
+    pub fn test_storage_map_remove() {
+        let mut store = MemoryStore::default();
+        for key in &["a", "b", "c", "d"] {
+            insert(&mut store, b"map", key.as_bytes(), b"v").unwrap();
+        }
+
+        // the last entry takes the removed entry's position
+        assert_eq!(
+            remove(&mut store, b"map", b"b").unwrap(),
+            Some(b"v".to_vec())
+        );
+        assert_eq!(
+            entries(&mut store, b"map"),
+            vec![entry("a", "v"), entry("d", "v"), entry("c", "v")]
+        );
+
+        // removing the last entry moves nothing
+        assert!(remove(&mut store, b"map", b"c").unwrap().is_some());
+        assert_eq!(remove(&mut store, b"map", b"c").unwrap(), None);
+        assert_eq!(
+            entries(&mut store, b"map"),
+            vec![entry("a", "v"), entry("d", "v")]
+        );
+
+        // a removed key goes last when it's inserted again
+        insert(&mut store, b"map", b"b", b"w").unwrap();
+        assert_eq!(
+            entries(&mut store, b"map"),
+            vec![entry("a", "v"), entry("d", "v"), entry("b", "w")]
+        );
+
+        for key in &["a", "b", "d"] {
+            remove(&mut store, b"map", key.as_bytes()).unwrap();
+        }
+        // nothing is left behind
+        assert!(entries(&mut store, b"map").is_empty());
+        assert!(store.0.is_empty());
+    }
+
+    pub fn test_storage_map_stable_iteration_order() {
+        let run = || {
+            let mut store = MemoryStore::default();
+            for i in 0..50u8 {
+                insert(&mut store, b"map", &[i.wrapping_mul(37)], &[i]).unwrap();
+            }
+            for i in (0..50u8).step_by(3) {
+                remove(&mut store, b"map", &[i.wrapping_mul(37)]).unwrap();
+            }
+            entries(&mut store, b"map")
+        };
+
+        // unlike the HashMap the store is backed by, the order doesn't change between runs
+        let first = run();
+        for _ in 0..5 {
+            assert_eq!(run(), first);
+        }
+    }
+
+    pub fn test_storage_map_names_dont_collide() {
+        let mut store = MemoryStore::default();
+
+        // without the length prefix both entries would be stored under "a\x01\x01b"
+        insert(&mut store, b"a", b"\x01b", b"first").unwrap();
+        insert(&mut store, b"a\x01", b"b", b"second").unwrap();
+        assert_eq!(
+            get(&mut store, b"a", b"\x01b").unwrap(),
+            Some(b"first".to_vec())
+        );
+        assert_eq!(
+            get(&mut store, b"a\x01", b"b").unwrap(),
+            Some(b"second".to_vec())
+        );
+
+        // and a list under the map's name doesn't land on its entries
+        insert(&mut store, b"map", b"key", b"value").unwrap();
+        storage_list::push(&mut store, b"map", b"item").unwrap();
+        assert_eq!(len(&mut store, b"map").unwrap(), 1);
+        assert_eq!(storage_list::len(&mut store, b"map").unwrap(), 1);
+        assert_eq!(
+            entries(&mut store, b"map"),
+            vec![(b"key".to_vec(), b"value".to_vec())]
+        );
+    }
+
+    pub fn test_storage_map_encode_entry() {
+        assert_eq!(
+            encode_entry(b"key", b"value"),
+            [&b"key"[..], &[0, 0, 0, 3], b"value", &[0, 0, 0, 5]].concat()
+        );
+    }
+}
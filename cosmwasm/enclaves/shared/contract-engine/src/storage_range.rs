@@ -0,0 +1,167 @@
+//! Ranges over the plaintext keys of a contract's state, for `db_scan` and `db_next`.
+//!
+//! State keys are encrypted with SIV, so the order the chain stores them in says nothing about the
+//! order of their plaintexts, and a range over the plaintext keys can't be pushed down to the
+//! chain. Instead the whole state of the contract is scanned, decrypted, merged with the writes
+//! that weren't flushed yet, and the range is cut and ordered here.
+
+use std::collections::BTreeMap;
+
+/// A key and its value
+pub type KV = (Vec<u8>, Vec<u8>);
+
+/// The order of a range, with the values CosmWasm contracts pass it as
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Order {
+    Ascending = 1,
+    Descending = 2,
+}
+
+impl Order {
+    pub fn from_i32(order: i32) -> Option<Self> {
+        match order {
+            1 => Some(Order::Ascending),
+            2 => Some(Order::Descending),
+            _ => None,
+        }
+    }
+}
+
+/// Whether `key` is in `[start, end)`, where a missing bound is open
+pub fn in_range(key: &[u8], start: Option<&[u8]>, end: Option<&[u8]>) -> bool {
+    start.map_or(true, |start| key >= start) && end.map_or(true, |end| key < end)
+}
+
+/// The entries of `[start, end)` in `order`. `stored` are the entries in the chain's state, and
+/// `written` the writes that weren't flushed to it yet and override them.
+pub fn merge_range<'a>(
+    stored: Vec<KV>,
+    written: impl Iterator<Item = (&'a Vec<u8>, &'a Vec<u8>)>,
+    start: Option<&[u8]>,
+    end: Option<&[u8]>,
+    order: Order,
+) -> Vec<KV> {
+    let mut entries: BTreeMap<Vec<u8>, Vec<u8>> = stored
+        .into_iter()
+        .filter(|(key, _)| in_range(key, start, end))
+        .collect();
+
+    for (key, value) in written {
+        if in_range(key, start, end) {
+            entries.insert(key.clone(), value.clone());
+        }
+    }
+
+    match order {
+        Order::Ascending => entries.into_iter().collect(),
+        Order::Descending => entries.into_iter().rev().collect(),
+    }
+}
+
+#[cfg(feature = "test")]
+pub mod tests {
+    use super::*;
+
+    fn kv(key: &str, value: &str) -> KV {
+        (key.as_bytes().to_vec(), value.as_bytes().to_vec())
+    }
+
+    /// The keyspace the tests range over, in the arbitrary order of encrypted keys
+    fn stored() -> Vec<KV> {
+        vec![
+            kv("c", "3"),
+            kv("a", "1"),
+            kv("e", "5"),
+            kv("b", "2"),
+            kv("d", "4"),
+        ]
+    }
+
+    fn keys(entries: &[KV]) -> Vec<&str> {
+        entries
+            .iter()
+            .map(|(key, _)| std::str::from_utf8(key).unwrap())
+            .collect()
+    }
+
+    fn range(start: Option<&str>, end: Option<&str>, order: Order) -> Vec<KV> {
+        merge_range(
+            stored(),
+            std::iter::empty(),
+            start.map(str::as_bytes),
+            end.map(str::as_bytes),
+            order,
+        )
+    }
+
+    pub fn test_storage_range_order() {
+        assert_eq!(
+            range(None, None, Order::Ascending),
+            vec![
+                kv("a", "1"),
+                kv("b", "2"),
+                kv("c", "3"),
+                kv("d", "4"),
+                kv("e", "5")
+            ]
+        );
+        assert_eq!(
+            keys(&range(None, None, Order::Descending)),
+            vec!["e", "d", "c", "b", "a"]
+        );
+
+        assert_eq!(Order::from_i32(1), Some(Order::Ascending));
+        assert_eq!(Order::from_i32(2), Some(Order::Descending));
+        assert_eq!(Order::from_i32(0), None);
+    }
+
+    pub fn test_storage_range_bounds() {
+        // start is inclusive and end exclusive, in both orders
+        assert_eq!(
+            keys(&range(Some("b"), Some("d"), Order::Ascending)),
+            vec!["b", "c"]
+        );
+        assert_eq!(
+            keys(&range(Some("b"), Some("d"), Order::Descending)),
+            vec!["c", "b"]
+        );
+
+        // open ended ranges
+        assert_eq!(
+            keys(&range(Some("c"), None, Order::Ascending)),
+            vec!["c", "d", "e"]
+        );
+        assert_eq!(
+            keys(&range(None, Some("c"), Order::Descending)),
+            vec!["b", "a"]
+        );
+        // bounds that aren't keys themselves
+        assert_eq!(
+            keys(&range(Some("bb"), Some("dd"), Order::Ascending)),
+            vec!["c", "d"]
+        );
+
+        // empty ranges
+        assert!(range(Some("d"), Some("b"), Order::Ascending).is_empty());
+        assert!(range(Some("c"), Some("c"), Order::Descending).is_empty());
+        assert!(range(Some("f"), None, Order::Ascending).is_empty());
+    }
+
+    pub fn test_storage_range_merges_unflushed_writes() {
+        let written: BTreeMap<Vec<u8>, Vec<u8>> =
+            vec![kv("b", "two"), kv("bb", "new"), kv("f", "6")]
+                .into_iter()
+                .collect();
+
+        assert_eq!(
+            merge_range(
+                stored(),
+                written.iter(),
+                Some(&b"b"[..]),
+                Some(&b"e"[..]),
+                Order::Ascending,
+            ),
+            vec![kv("b", "two"), kv("bb", "new"), kv("c", "3"), kv("d", "4")]
+        );
+    }
+}
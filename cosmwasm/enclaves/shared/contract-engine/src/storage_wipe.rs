@@ -0,0 +1,120 @@
+//! Removal of every key under a prefix, backing the `storage_wipe_prefix` host function.
+//!
+//! Keys are stored encrypted, each one on its own, so the chain can't list the keys that start
+//! with a prefix. The keys are found like `db_scan` finds them instead, by ranging over the
+//! decrypted state of the calling contract from `prefix` up to `prefix_end(prefix)`. That state
+//! only ever holds the contract's own keys, so a contract can't wipe another contract's prefix.
+//! Every removal is charged like a regular `db_remove`, on top of the per key cost the caller
+//! charges up front.
+
+use crate::errors::WasmEngineResult;
+use crate::storage_list::KeyValueStore;
+
+/// The exclusive end of the range of keys that start with `prefix`, or `None` if that range has
+/// no end, as when `prefix` is all `0xff`
+pub fn prefix_end(prefix: &[u8]) -> Option<Vec<u8>> {
+    let last = prefix.iter().rposition(|byte| *byte != 0xff)?;
+
+    let mut end = prefix[..=last].to_vec();
+    end[last] += 1;
+    Some(end)
+}
+
+/// Removes `keys`, the keys found under a prefix, and returns how many there were. `charge` is
+/// called with that count before anything is removed, so a wipe that runs out of gas removes
+/// nothing.
+pub fn wipe_keys<S, F>(store: &mut S, keys: &[Vec<u8>], charge: F) -> WasmEngineResult<u32>
+where
+    S: KeyValueStore,
+    F: FnOnce(u32) -> WasmEngineResult<()>,
+{
+    // a contract's keys come from wasm memory, which is addressed with u32, so the count fits
+    let count = keys.len() as u32;
+    charge(count)?;
+
+    for key in keys {
+        store.remove(key)?;
+    }
+
+    Ok(count)
+}
+
+#[cfg(feature = "test")]
+pub mod tests {
+    use super::*;
+    use crate::errors::WasmEngineError;
+    use crate::storage_list::tests::MemoryStore;
+    use crate::storage_range::{merge_range, Order};
+
+    fn keys_under(store: &MemoryStore, prefix: &[u8]) -> Vec<Vec<u8>> {
+        let stored = store
+            .0
+            .iter()
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+        let end = prefix_end(prefix);
+
+        merge_range(
+            stored,
+            std::iter::empty(),
+            None,
+            Some(prefix),
+            end.as_deref(),
+            Order::Ascending,
+        )
+        .into_iter()
+        .map(|(key, _)| key)
+        .collect()
+    }
+
+    pub fn test_storage_wipe_prefix_end() {
+        assert_eq!(prefix_end(b"my_prefix"), Some(b"my_prefiy".to_vec()));
+        assert_eq!(prefix_end(b"a\xff\xff"), Some(b"b".to_vec()));
+        assert_eq!(prefix_end(b"\xff\xff"), None);
+        assert_eq!(prefix_end(b""), None);
+    }
+
+    pub fn test_storage_wipe_prefix_removes_all_keys() {
+        let mut store = MemoryStore::default();
+        for key in &[
+            &b"my_prefix"[..],
+            b"my_prefix/a",
+            b"my_prefix/b",
+            b"my_prefix\xff",
+        ] {
+            store.set(key, b"value").unwrap();
+        }
+        // right next to the prefix, but not under it
+        for key in &[&b"my_prefiw"[..], b"my_prefiy", b"my_prefi"] {
+            store.set(key, b"value").unwrap();
+        }
+
+        let keys = keys_under(&store, b"my_prefix");
+        let mut charged = None;
+        let removed = wipe_keys(&mut store, &keys, |count| {
+            charged = Some(count);
+            Ok(())
+        })
+        .unwrap();
+
+        assert_eq!(removed, 4);
+        assert_eq!(charged, Some(4));
+        assert!(keys_under(&store, b"my_prefix").is_empty());
+        assert_eq!(store.0.len(), 3);
+
+        // wiping again finds nothing
+        let keys = keys_under(&store, b"my_prefix");
+        assert_eq!(wipe_keys(&mut store, &keys, |_| Ok(())).unwrap(), 0);
+    }
+
+    pub fn test_storage_wipe_prefix_charges_before_removing() {
+        let mut store = MemoryStore::default();
+        store.set(b"my_prefix/a", b"value").unwrap();
+
+        let keys = keys_under(&store, b"my_prefix");
+        let result = wipe_keys(&mut store, &keys, |_| Err(WasmEngineError::OutOfGas));
+
+        assert!(result.is_err());
+        assert_eq!(store.0.len(), 1);
+    }
+}
@@ -1,10 +1,14 @@
 use log::*;
 use serde::{Deserialize, Serialize};
 
+use cw_types_generic::BaseEnv;
+use cw_types_v010::types::OutputCompression;
 use enclave_crypto::{AESKey, Ed25519PublicKey, SIVEncryptable};
 use enclave_ffi_types::EnclaveError;
 
+use super::compression::decompress_output;
 use super::io::calc_encryption_key;
+use super::padding::unpad;
 
 pub type IoNonce = [u8; 32];
 #[derive(Serialize, Deserialize, PartialEq, Debug)]
@@ -25,6 +29,24 @@ pub struct ParsedMessage {
     pub data_for_validation: Option<Vec<u8>>,
 }
 
+/// The transaction a call is made in. Replies are signed and verified for it, see
+/// `Reply::block_height`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TxPosition {
+    pub block_height: u64,
+    /// `None` outside of transactions
+    pub tx_index: Option<u32>,
+}
+
+impl TxPosition {
+    pub fn from_env(env: &BaseEnv) -> Self {
+        TxPosition {
+            block_height: env.0.block.height,
+            tx_index: env.get_tx_index(),
+        }
+    }
+}
+
 pub struct DecryptedSecretMessage {
     pub secret_msg: SecretMessage,
     pub decrypted_msg: Vec<u8>,
@@ -37,7 +59,7 @@ impl SecretMessage {
             .encrypt_siv(self.msg.as_slice(), None)
             .map_err(|err| {
                 error!("got an error while trying to encrypt the msg: {:?}", err);
-                EnclaveError::EncryptionError
+                EnclaveError::Aead
             })?;
 
         Ok(())
@@ -69,6 +91,17 @@ impl SecretMessage {
         }
     }
 
+    /// Decrypts an output of a contract, which may have been compressed as `compression` says or
+    /// padded before it was encrypted
+    pub fn decrypt_output(&self, compression: &OutputCompression) -> Result<Vec<u8>, EnclaveError> {
+        decompress_output(unpad(self.decrypt()?)?, compression)
+    }
+
+    /// Decrypts an error returned by a contract, which may have been padded before it was encrypted
+    pub fn decrypt_error(&self) -> Result<Vec<u8>, EnclaveError> {
+        unpad(self.decrypt()?)
+    }
+
     pub fn encryption_key(&self) -> AESKey {
         calc_encryption_key(&self.nonce, &self.user_public_key)
     }
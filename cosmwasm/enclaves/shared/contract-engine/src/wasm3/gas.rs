@@ -14,6 +14,8 @@ use enclave_ffi_types::EnclaveError;
 pub const EXPORT_GAS_LIMIT: &str = "gas_limit";
 /// Name of the exported global that holds the gas limit exhausted flag.
 pub const EXPORT_GAS_LIMIT_EXHAUSTED: &str = "gas_limit_exhausted";
+/// Name of the exported global that holds the memory limit exceeded flag.
+pub const EXPORT_MEMORY_LIMIT_EXCEEDED: &str = "memory_limit_exceeded";
 
 /// Configures the gas limit on the given instance.
 pub fn set_gas_limit<C>(instance: &wasm3::Instance<C>, gas_limit: u64) -> Result<(), EnclaveError> {
@@ -34,6 +36,14 @@ pub fn get_exhausted_amount<C>(instance: &wasm3::Instance<C>) -> u64 {
         .unwrap_or_default()
 }
 
+/// Returns whether the contract tried to grow its memory past the limit.
+pub fn memory_limit_exceeded<C>(instance: &wasm3::Instance<C>) -> bool {
+    let exceeded: u64 = instance
+        .get_global(EXPORT_MEMORY_LIMIT_EXCEEDED)
+        .unwrap_or_default();
+    exceeded != 0
+}
+
 /// Attempts to use the given amount of gas.
 pub fn use_gas<C>(instance: &wasm3::Instance<C>, amount: u64) -> WasmEngineResult<()> {
     debug!("external service used gas: {}", amount);
@@ -60,16 +70,24 @@ pub fn add_metering(module: &mut Module, gas_costs: &WasmCosts) {
         module
             .globals
             .add_local(ValType::I64, true, InitExpr::Value(Value::I64(0)));
+    let memory_limit_exceeded_global =
+        module
+            .globals
+            .add_local(ValType::I64, true, InitExpr::Value(Value::I64(0)));
     module.exports.add(EXPORT_GAS_LIMIT, gas_limit_global);
     module
         .exports
         .add(EXPORT_GAS_LIMIT_EXHAUSTED, gas_limit_exhausted_global);
+    module
+        .exports
+        .add(EXPORT_MEMORY_LIMIT_EXCEEDED, memory_limit_exceeded_global);
 
     let memory_grow_meter = create_memory_grow_meter(
         module,
         gas_costs,
         gas_limit_global,
         gas_limit_exhausted_global,
+        memory_limit_exceeded_global,
     );
 
     for (_, func) in module.funcs.iter_local_mut() {
@@ -187,15 +205,19 @@ fn create_memory_grow_meter(
     gas_costs: &WasmCosts,
     gas_limit_global: GlobalId,
     gas_limit_exhausted_global: GlobalId,
+    memory_limit_exceeded_global: GlobalId,
 ) -> FunctionId {
+    // modules have at most one memory, and without one there's nothing to grow
+    let memory = module.memories.iter().next().map(|memory| memory.id());
     // function input
     let num_pages = module.locals.add(ValType::I32);
     // cache cost of memory grow
     let grow_cost = module.locals.add(ValType::I64);
 
     let mut func = FunctionBuilder::new(&mut module.types, &[ValType::I32], &[ValType::I32]);
+    let mut body = func.func_body();
 
-    func.func_body()
+    body
         // multiply the number of pages by the grow cost
         .local_get(num_pages)
         // num_pages as i64
@@ -222,10 +244,37 @@ fn create_memory_grow_meter(
         .global_get(gas_limit_global)
         .local_get(grow_cost)
         .binop(BinaryOp::I64Sub)
-        .global_set(gas_limit_global)
-        // return the original number of pages for the MemoryGrow instruction
-        // right after this function call.
-        .local_get(num_pages);
+        .global_set(gas_limit_global);
+
+    // Growing past the limit would only make MemoryGrow return -1, which allocators turn into an
+    // abort. Trap here instead, so the error says what happened, and charge it as running out of
+    // gas.
+    if let Some(memory) = memory {
+        body
+            // if unsigned(memory_size + num_pages) > max_memory_pages { throw(); }
+            .memory_size(memory)
+            .unop(UnaryOp::I64ExtendUI32)
+            .local_get(num_pages)
+            .unop(UnaryOp::I64ExtendUI32)
+            .binop(BinaryOp::I64Add)
+            .i64_const(gas_costs.max_memory_pages as i64)
+            .binop(BinaryOp::I64GtU)
+            .if_else(
+                None,
+                |then| {
+                    then.i64_const(0)
+                        .global_set(gas_limit_global)
+                        .i64_const(1)
+                        .global_set(memory_limit_exceeded_global)
+                        .unreachable();
+                },
+                |_else| {},
+            );
+    }
+
+    // return the original number of pages for the MemoryGrow instruction
+    // right after this function call.
+    body.local_get(num_pages);
 
     // register the function
     func.finish(vec![num_pages], &mut module.funcs)
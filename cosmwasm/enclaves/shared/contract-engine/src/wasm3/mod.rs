@@ -5,6 +5,7 @@ use log::*;
 
 use bech32::{FromBase32, ToBase32};
 use cw_types_generic::{ContractFeature, CosmWasmApiVersion, CwEnv};
+use lazy_static::lazy_static;
 use rand_chacha::ChaChaRng;
 use rand_core::SeedableRng;
 use sgx_rand::Rng;
@@ -14,20 +15,48 @@ use wasm3::{Instance, Memory, Trap};
 use cw_types_v010::consts::BECH32_PREFIX_ACC_ADDR;
 use cw_types_v010::encoding::Binary;
 use enclave_cosmos_types::types::{ContractCode, HandleType};
-use enclave_crypto::{sha_256, Ed25519PublicKey, WasmApiCryptoError};
+use enclave_crypto::{sha_256, Ed25519PublicKey, WasmApiCryptoError, HASH_SIZE};
 use enclave_ffi_types::{Ctx, EnclaveError};
-
-use crate::contract_validation::ContractKey;
+use enclave_utils::parallel::{parallel_verify, verification_threads};
+
+use crate::adr036::verify_adr036;
+use crate::block_view::current_block_view;
+use crate::bls::{aggregate_verify_pairings, bls12_381_aggregate_verify};
+use crate::coins::{funds_match, normalize_coins_json, CoinsError};
+use crate::contract_address::predict_contract_address;
+use crate::contract_validation::{generate_execution_receipt, ContractKey, TxMsgPosition};
 use crate::cosmwasm_config::ContractOperation;
-use crate::db::read_from_encrypted_state;
-use crate::db::{remove_from_encrypted_state, write_multiple_keys};
+use crate::db::{range_from_encrypted_state, remove_from_encrypted_state, write_multiple_keys};
+use crate::db::{read_from_encrypted_state, read_multiple_from_encrypted_state};
+use crate::derive_id::derive_id;
+use crate::derive_nonce::{derive_nonce, NonceSource};
 use crate::errors::{ToEnclaveError, ToEnclaveResult, WasmEngineError, WasmEngineResult};
-use crate::gas::{WasmCosts, READ_BASE_GAS, WRITE_BASE_GAS};
-use crate::query_chain::encrypt_and_query_chain;
+use crate::eth_personal_sign::verify_eth_personal_sign;
+use crate::evidence::verify_double_sign_evidence;
+use crate::fixed_point::{checked_mul_div, sqrt, FixedPointError};
+use crate::gas::{contract_gas_limit, WasmCosts, GAS_MULTIPLIER, READ_BASE_GAS, WRITE_BASE_GAS};
+use crate::groth16::{groth16_public_input_count, groth16_verify};
+use crate::ibc_denom_utils::{resolve_ibc_denom, DenomTraceError, DENOM_TRACE_QUERY_PATH};
+use crate::jwt::verify_jwt;
+use crate::legacy_multisig::LegacyMultisig;
+use crate::output_log::{OutputLog, OUTPUT_LOG_KEY};
+use crate::query_cache::QueryCache;
+use crate::query_chain::{encrypt_and_query_chain, query_all_balances, query_stargate};
 use crate::random::MSG_COUNTER;
+use crate::same_session::same_session;
+use crate::secp256k1_der::secp256k1_verify_der;
+use crate::secp256r1::secp256r1_verify;
+use crate::secure_remove::secure_remove;
+use crate::signed_payload::verify_signed_payload;
+use crate::storage_cas::compare_and_swap;
+use crate::storage_list::{self, KeyValueStore};
+use crate::storage_map;
+use crate::storage_range::{self, Order, KV};
+use crate::storage_wipe::{prefix_end, wipe_keys};
 use crate::types::IoNonce;
+use crate::webauthn::verify_webauthn;
 
-use gas::{get_exhausted_amount, get_remaining_gas, use_gas};
+use gas::{get_exhausted_amount, get_remaining_gas, memory_limit_exceeded, use_gas};
 use module_cache::create_module_instance;
 
 mod gas;
@@ -110,9 +139,13 @@ impl<'env, C> Wasm3RuntimeEx for wasm3::Runtime<'env, C> {
 pub struct Context {
     context: Ctx,
     gas_limit: u64,
+    /// The gas left in the tx, which `gas_limit` is capped from by the contract gas ceiling
+    tx_gas_limit: u64,
     gas_used_externally: u64,
     gas_costs: WasmCosts,
     query_depth: u32,
+    /// The chain's `max_query_depth` param, 0 if it isn't set
+    max_query_depth: u32,
     operation: ContractOperation,
     og_contract_key: ContractKey,
     user_nonce: IoNonce,
@@ -120,6 +153,33 @@ pub struct Context {
     kv_cache: KvCache,
     last_error: Option<WasmEngineError>,
     timestamp: u64,
+    contract_address: Vec<u8>,
+    block_height: u64,
+    input_hash: [u8; HASH_SIZE],
+    derive_id_counter: u32,
+    /// The block's message counter for this call, `None` in queries
+    msg_counter: Option<u64>,
+    derive_nonce_counter: u32,
+    /// The output log, once `last_output_hash` loaded it
+    output_log: Option<OutputLog>,
+    /// The keys `last_output_hash` looked up, to record the call's output under
+    output_log_claims: Vec<Vec<u8>>,
+    /// The funds sent with the call, for `require_funds`
+    sent_funds: Vec<cw_types_v1::coins::Coin>,
+    /// The transaction's `min_gas_price`, for `min_gas_price`
+    min_gas_price: Vec<cw_types_v010::types::DecCoin>,
+    /// The chain's output compression, to decompress the answers to the contract's queries
+    output_compression: cw_types_v010::types::OutputCompression,
+    /// The chain's limit on the transaction's submessages, for `remaining_submsg_budget`
+    submsg_limit: Option<cw_types_v010::types::SubmsgLimit>,
+    /// The position of the message the contract was called with, for `tx_msg_position`
+    tx_msg_position: Option<TxMsgPosition>,
+    /// The chain's next contract instance id, for `predict_instantiate_address`
+    next_instance_id: Option<u64>,
+    /// Answers to the queries the contract already asked in this call
+    query_cache: QueryCache,
+    /// The ranges `db_scan` opened, each at the id it returned minus one, see `MAX_OPEN_RANGES`
+    ranges: Vec<std::vec::IntoIter<KV>>,
 }
 
 impl Context {
@@ -205,16 +265,38 @@ fn check_execution_result<T>(
                 get_remaining_gas(instance),
                 get_exhausted_amount(instance)
             );
-            EnclaveError::OutOfGas
+            out_of_gas_error(context.gas_limit, context.tx_gas_limit)
+        }
+        // If Unreachable was executed after the memory grow meter flagged the limit, the contract
+        // asked for more memory than it may have.
+        wasm3::Error::UnreachableExecuted if memory_limit_exceeded(instance) => {
+            debug!(
+                "Detected memory limit exceeded! Limit: {} pages",
+                context.gas_costs.max_memory_pages
+            );
+            EnclaveError::MemoryLimitExceeded
         }
         // Otherwise, check if a hook set an error, in which case we propagate it.
         err => match context.take_last_error() {
+            Some(WasmEngineError::OutOfGas) => {
+                out_of_gas_error(context.gas_limit, context.tx_gas_limit)
+            }
             Some(err) => err.into(),
             None => err.to_enclave_error(),
         },
     })
 }
 
+/// Running out of gas is reported as hitting the contract gas ceiling when the ceiling is what
+/// limited the call, so that raising the tx gas limit isn't mistaken for a fix.
+fn out_of_gas_error(gas_limit: u64, tx_gas_limit: u64) -> EnclaveError {
+    if gas_limit < tx_gas_limit {
+        EnclaveError::ContractGasCeilingExceeded
+    } else {
+        EnclaveError::OutOfGas
+    }
+}
+
 pub struct Engine {
     context: Context,
     gas_limit: u64,
@@ -231,6 +313,7 @@ impl Engine {
     pub fn new(
         context: Ctx,
         gas_limit: u64,
+        contract_gas_ceiling: u64,
         gas_costs: WasmCosts,
         contract_code: &ContractCode,
         og_contract_key: ContractKey,
@@ -242,10 +325,14 @@ impl Engine {
     ) -> Result<Engine, EnclaveError> {
         let versioned_code = create_module_instance(contract_code, &gas_costs, operation)?;
         let kv_cache = KvCache::new();
+        let tx_gas_limit = gas_limit;
+        let gas_limit = contract_gas_limit(tx_gas_limit, contract_gas_ceiling);
         let context = Context {
             context,
             query_depth,
+            max_query_depth: 0,
             gas_limit,
+            tx_gas_limit,
             gas_used_externally: 0,
             gas_costs,
             operation,
@@ -255,6 +342,22 @@ impl Engine {
             kv_cache,
             last_error: None,
             timestamp,
+            contract_address: vec![],
+            block_height: 0,
+            input_hash: [0u8; HASH_SIZE],
+            derive_id_counter: 0,
+            msg_counter: None,
+            derive_nonce_counter: 0,
+            output_log: None,
+            output_log_claims: vec![],
+            sent_funds: vec![],
+            min_gas_price: vec![],
+            output_compression: Default::default(),
+            submsg_limit: None,
+            tx_msg_position: None,
+            next_instance_id: None,
+            query_cache: QueryCache::default(),
+            ranges: vec![],
         };
 
         debug!("setting up runtime");
@@ -283,7 +386,7 @@ impl Engine {
         // let start = Instant::now();
         let runtime = self
             .environment
-            .new_runtime::<Context>(1024 * 60, Some(192 /* 12 MiB */))
+            .new_runtime::<Context>(1024 * 60, Some(self.context.gas_costs.max_memory_pages))
             .to_enclave_result()?;
         // let duration = start.elapsed();
         // trace!("Time elapsed in environment.new_runtime is: {:?}", duration);
@@ -336,8 +439,22 @@ impl Engine {
 
     fn link_host_functions(instance: &mut wasm3::Instance<Context>) -> Wasm3RsResult<()> {
         link_fn(instance, "db_read", host_read_db)?;
+        link_fn(instance, "db_read_multiple", host_read_multiple_db)?;
         link_fn(instance, "db_write", host_write_db)?;
         link_fn(instance, "db_remove", host_remove_db)?;
+        link_fn(instance, "db_scan", host_db_scan)?;
+        link_fn(instance, "db_next", host_db_next)?;
+        link_fn(instance, "storage_push", host_storage_push)?;
+        link_fn(instance, "storage_pop", host_storage_pop)?;
+        link_fn(instance, "storage_len", host_storage_len)?;
+        link_fn(instance, "storage_map_insert", host_storage_map_insert)?;
+        link_fn(instance, "storage_map_remove", host_storage_map_remove)?;
+        link_fn(instance, "storage_map_get", host_storage_map_get)?;
+        link_fn(instance, "storage_map_len", host_storage_map_len)?;
+        link_fn(instance, "storage_map_entry", host_storage_map_entry)?;
+        link_fn(instance, "storage_secure_remove", host_storage_secure_remove)?;
+        link_fn(instance, "storage_wipe_prefix", host_storage_wipe_prefix)?;
+        link_fn(instance, "storage_cas", host_storage_cas)?;
         link_fn(instance, "canonicalize_address", host_canonicalize_address)?;
         link_fn(instance, "humanize_address", host_humanize_address)?;
         link_fn(instance, "query_chain", host_query_chain)?;
@@ -354,10 +471,47 @@ impl Engine {
         link_fn(instance, "secp256k1_recover_pubkey", host_secp256k1_recover_pubkey)?;
         link_fn(instance, "ed25519_verify", host_ed25519_verify)?;
         link_fn(instance, "ed25519_batch_verify", host_ed25519_batch_verify)?;
+        link_fn(instance, "secp256k1_batch_verify", host_secp256k1_batch_verify)?;
+        link_fn(instance, "secp256k1_verify_der", host_secp256k1_verify_der)?;
+        link_fn(instance, "secp256r1_verify", host_secp256r1_verify)?;
+        #[rustfmt::skip]
+        link_fn(instance, "bls12_381_aggregate_verify", host_bls12_381_aggregate_verify)?;
+        link_fn(instance, "groth16_verify", host_groth16_verify)?;
+        #[rustfmt::skip]
+        link_fn(instance, "verify_double_sign_evidence", host_verify_double_sign_evidence)?;
+        link_fn(instance, "verify_jwt", host_verify_jwt)?;
+        link_fn(instance, "verify_signed_payload", host_verify_signed_payload)?;
+        link_fn(instance, "verify_eth_personal_sign", host_verify_eth_personal_sign)?;
+        link_fn(instance, "verify_webauthn", host_verify_webauthn)?;
+        link_fn(instance, "verify_adr036", host_verify_adr036)?;
+        #[rustfmt::skip]
+        link_fn(instance, "verify_legacy_multisig", host_verify_legacy_multisig)?;
+        link_fn(instance, "same_session", host_same_session)?;
+        link_fn(instance, "checked_mul_div", host_checked_mul_div)?;
+        link_fn(instance, "sqrt", host_sqrt)?;
+        link_fn(instance, "normalize_coins", host_normalize_coins)?;
+        link_fn(instance, "require_funds", host_require_funds)?;
+        link_fn(instance, "parse_ibc_denom", host_parse_ibc_denom)?;
         link_fn(instance, "secp256k1_sign", host_secp256k1_sign)?;
         link_fn(instance, "ed25519_sign", host_ed25519_sign)?;
+        link_fn(instance, "sleep", host_sleep)?;
         link_fn_no_args(instance, "check_gas", host_check_gas_used)?;
+        link_fn_no_args(instance, "gas_used_in_call", host_gas_used_in_call)?;
+        link_fn_no_args(instance, "contract_all_balances", host_contract_all_balances)?;
+        link_fn_no_args(instance, "enclave_block_view", host_enclave_block_view)?;
+        link_fn_no_args(instance, "remaining_submsg_budget", host_remaining_submsg_budget)?;
+        link_fn_no_args(instance, "min_gas_price", host_min_gas_price)?;
+        link_fn_no_args(instance, "tx_msg_position", host_tx_msg_position)?;
+        link_fn(
+            instance,
+            "predict_instantiate_address",
+            host_predict_instantiate_address,
+        )?;
+        link_fn(instance, "derive_id", host_derive_id)?;
+        link_fn_no_args(instance, "derive_nonce", host_derive_nonce)?;
+        link_fn(instance, "last_output_hash", host_last_output_hash)?;
         link_fn(instance, "gas_evaporate", host_gas_evaporate)?;
+        link_fn(instance, "execution_receipt", host_execution_receipt)?;
 
         //    DbReadIndex = 0,
         //     DbWriteIndex = 1,
@@ -382,6 +536,87 @@ impl Engine {
         Ok(())
     }
 
+    /// Bind the engine to the contract call it's about to run, so host functions
+    /// can refer to the contract, the block and the (decrypted) input.
+    pub fn set_call_info(&mut self, contract_address: &[u8], block_height: u64, input: &[u8]) {
+        self.context.contract_address = contract_address.to_vec();
+        self.context.block_height = block_height;
+        self.context.input_hash = sha_256(input);
+        self.context.derive_id_counter = 0;
+        self.context.msg_counter = None;
+        self.context.derive_nonce_counter = 0;
+        self.context.output_log = None;
+        self.context.output_log_claims.clear();
+    }
+
+    /// Sets the block's message counter for the call, which `derive_nonce` needs. Queries don't
+    /// have one.
+    pub fn set_msg_counter(&mut self, msg_counter: u64) {
+        self.context.msg_counter = Some(msg_counter);
+    }
+
+    pub fn set_sent_funds(&mut self, sent_funds: &[cw_types_v010::types::Coin]) {
+        self.context.sent_funds = sent_funds
+            .iter()
+            .map(|coin| cw_types_v1::coins::Coin::new(coin.amount.u128(), coin.denom.clone()))
+            .collect();
+    }
+
+    pub fn set_min_gas_price(&mut self, min_gas_price: &[cw_types_v010::types::DecCoin]) {
+        self.context.min_gas_price = min_gas_price.to_vec();
+    }
+
+    pub fn set_output_compression(
+        &mut self,
+        output_compression: &cw_types_v010::types::OutputCompression,
+    ) {
+        self.context.output_compression = output_compression.clone();
+    }
+
+    pub fn set_max_query_depth(&mut self, max_query_depth: u32) {
+        self.context.max_query_depth = max_query_depth;
+    }
+
+    pub fn set_submsg_limit(&mut self, submsg_limit: Option<cw_types_v010::types::SubmsgLimit>) {
+        self.context.submsg_limit = submsg_limit;
+    }
+
+    pub fn set_tx_msg_position(&mut self, tx_msg_position: Option<TxMsgPosition>) {
+        self.context.tx_msg_position = tx_msg_position;
+    }
+
+    pub fn set_next_instance_id(&mut self, next_instance_id: Option<u64>) {
+        self.context.next_instance_id = next_instance_id;
+    }
+
+    /// Records the hash of the contract's `output` under the keys it looked up with
+    /// `last_output_hash`, for every operation that writes state: init, handle and migrate. Call
+    /// it before flushing the cache, as the log is written through it.
+    pub fn record_output_hash(&mut self, output: &[u8]) -> Result<(), EnclaveError> {
+        if self.context.output_log_claims.is_empty() {
+            return Ok(());
+        }
+
+        let mut log = self.context.output_log.take().unwrap_or_default();
+        let output_hash = sha_256(output);
+        for key in self.context.output_log_claims.drain(..) {
+            log.record(&key, output_hash);
+        }
+        let log = log.to_vec().map_err(|_| EnclaveError::FailedToSerialize)?;
+
+        // charged like a write by the contract, and the pseudo gas is refunded by flush_cache
+        let (_, pseudo_cost_for_write) = self.context.kv_cache.write(OUTPUT_LOG_KEY, &log);
+        self.used_gas = self
+            .used_gas
+            .saturating_add(WRITE_BASE_GAS)
+            .saturating_add(pseudo_cost_for_write);
+        if self.used_gas > self.gas_limit {
+            return Err(EnclaveError::OutOfGas);
+        }
+
+        Ok(())
+    }
+
     /// get the amount of gas used by the last contract execution
     pub fn gas_used(&self) -> u64 {
         self.used_gas
@@ -635,6 +870,8 @@ impl Engine {
         })
     }
 
+    /// Writes what the contract stored during the call to the chain state with a single
+    /// `ocall_multiple_write_db`, and returns the pseudo gas charged for the writes, to refund it
     pub fn flush_cache(&mut self, random: Option<Binary>) -> Result<u64, EnclaveError> {
         use crate::db::create_encrypted_key_value;
 
@@ -897,46 +1134,31 @@ fn to_low_half(data: u32) -> u64 {
     data.into()
 }
 
-fn host_read_db(
+/// Reads a key from the cache or, if it's missing there, from the encrypted state
+fn read_db(
     context: &mut Context,
     instance: &wasm3::Instance<Context>,
-    state_key_region_ptr: i32,
-) -> WasmEngineResult<i32> {
+    state_key_name: &[u8],
+) -> WasmEngineResult<Option<Vec<u8>>> {
     // todo: time this
     use_gas(instance, READ_BASE_GAS)?;
 
-    let state_key_name = read_from_memory(instance, state_key_region_ptr as u32).map_err(
-        debug_err!(err => "db_read failed to extract vector from state_key_region_ptr: {err}"),
-    )?;
-
-    debug!("db_read reading key {}", show_bytes(&state_key_name));
+    debug!("db_read reading key {}", show_bytes(state_key_name));
 
-    let value = context.kv_cache.read(&state_key_name);
+    let value = context.kv_cache.read(state_key_name);
 
-    if let Some(unwrapped) = value {
+    if value.is_some() {
         debug!("Got value from cache");
-        let ptr_to_region_in_wasm_vm = write_to_memory(instance, &unwrapped).map_err(|err| {
-            debug!(
-                "read_db() error while trying to allocate {} bytes for the value",
-                unwrapped.len(),
-            );
-            err
-        })?;
-
-        return Ok(ptr_to_region_in_wasm_vm as i32);
+        return Ok(value);
     }
 
     debug!("Missed value in cache");
+
     let (value, used_gas) = read_from_encrypted_state(
-        &state_key_name,
+        state_key_name,
         &context.context,
         &context.og_contract_key,
-        match context.operation {
-            ContractOperation::Init => true,
-            ContractOperation::Handle => true,
-            ContractOperation::Query => false,
-            ContractOperation::Migrate => true,
-        },
+        rewrites_old_keys(context),
         &mut context.kv_cache,
         &get_encryption_salt(context.timestamp),
     )
@@ -948,204 +1170,728 @@ fn host_read_db(
         value.as_ref().map(|v| show_bytes(v))
     );
 
-    let value = match value {
-        // Return 0 (null ponter) if value is empty
-        Some(value) => value,
-        None => return Ok(0),
-    };
-
-    let region_ptr = write_to_memory(instance, &value)?;
-
-    Ok(region_ptr as i32)
+    Ok(value)
 }
 
-fn host_remove_db(
+/// Like `read_db`, for several keys at once. The keys missing from the cache are read from the
+/// encrypted state in a single ocall.
+fn read_multiple_db(
     context: &mut Context,
     instance: &wasm3::Instance<Context>,
-    state_key_region_ptr: i32,
-) -> WasmEngineResult<()> {
-    if context.operation.is_query() {
-        debug!("db_remove was called while in query mode");
-        return Err(WasmEngineError::UnauthorizedWrite);
+    state_key_names: &[Vec<u8>],
+) -> WasmEngineResult<Vec<Option<Vec<u8>>>> {
+    use_gas(instance, READ_BASE_GAS * state_key_names.len() as u64)?;
+
+    let mut values = Vec::with_capacity(state_key_names.len());
+    let mut missed_keys = vec![];
+    let mut missed_positions = vec![];
+    for (position, state_key_name) in state_key_names.iter().enumerate() {
+        debug!(
+            "db_read_multiple reading key {}",
+            show_bytes(state_key_name)
+        );
+
+        let value = context.kv_cache.read(state_key_name);
+        if value.is_none() {
+            missed_keys.push(state_key_name.clone());
+            missed_positions.push(position);
+        }
+
+        values.push(value);
     }
 
-    let state_key_name = read_from_memory(instance, state_key_region_ptr as u32).map_err(
-        debug_err!(err => "db_remove failed to extract vector from state_key_region_ptr: {err}"),
-    )?;
+    if missed_keys.is_empty() {
+        return Ok(values);
+    }
+
+    debug!("Missed {} values in cache", missed_keys.len());
+
+    let (missed_values, used_gas) = read_multiple_from_encrypted_state(
+        &missed_keys,
+        &context.context,
+        &context.og_contract_key,
+        rewrites_old_keys(context),
+        &mut context.kv_cache,
+        &get_encryption_salt(context.timestamp),
+    )
+    .map_err(debug_err!(
+        "db_read_multiple failed to read keys from storage"
+    ))?;
+    context.use_gas_externally(used_gas);
+
+    for (position, value) in missed_positions.into_iter().zip(missed_values) {
+        values[position] = value;
+    }
+
+    Ok(values)
+}
 
-    debug!("db_remove removing key {}", show_bytes(&state_key_name));
+/// Whether keys found in the old encryption format are rewritten in the new one when read, which
+/// needs write access to the state
+fn rewrites_old_keys(context: &Context) -> bool {
+    match context.operation {
+        ContractOperation::Init => true,
+        ContractOperation::Handle => true,
+        ContractOperation::Query => false,
+        ContractOperation::Migrate => true,
+    }
+}
+
+fn remove_db(context: &mut Context, state_key_name: &[u8]) -> WasmEngineResult<()> {
+    storage_list::check_key(state_key_name)?;
+
+    debug!("db_remove removing key {}", show_bytes(state_key_name));
 
     // Also remove the key from the cache to avoid rewriting it
-    context.kv_cache.remove(&state_key_name);
+    context.kv_cache.remove(state_key_name);
 
     let used_gas =
-        remove_from_encrypted_state(&state_key_name, &context.context, &context.og_contract_key)?;
+        remove_from_encrypted_state(state_key_name, &context.context, &context.og_contract_key)?;
     context.use_gas_externally(used_gas);
 
     Ok(())
 }
 
-fn host_write_db(
+fn write_db(
     context: &mut Context,
     instance: &wasm3::Instance<Context>,
-    (state_key_region_ptr, value_region_ptr): (i32, i32),
+    state_key_name: &[u8],
+    value: &[u8],
 ) -> WasmEngineResult<()> {
-    if context.operation.is_query() {
-        debug!("db_write was called while in query mode");
-        return Err(WasmEngineError::UnauthorizedWrite);
-    }
-
+    storage_list::check_key(state_key_name)?;
     use_gas(instance, WRITE_BASE_GAS)?;
 
-    let state_key_name = read_from_memory(instance, state_key_region_ptr as u32).map_err(
-        debug_err!(err => "db_write failed to extract vector from state_key_region_ptr: {err}"),
-    )?;
-    let value = read_from_memory(instance, value_region_ptr as u32).map_err(
-        debug_err!(err => "db_write failed to extract vector from value_region_ptr: {err}"),
-    )?;
-
     debug!(
         "db_write writing key: {}, value: {}",
-        show_bytes(&state_key_name),
-        show_bytes(&value)
+        show_bytes(state_key_name),
+        show_bytes(value)
     );
 
-    let (_, pseudo_cost_for_write) = context.kv_cache.write(&state_key_name, &value);
+    let (_, pseudo_cost_for_write) = context.kv_cache.write(state_key_name, value);
     use_gas(instance, pseudo_cost_for_write)?; // Use gas now, refund later
 
     Ok(())
 }
 
-fn host_canonicalize_address(
+fn host_read_db(
     context: &mut Context,
     instance: &wasm3::Instance<Context>,
-    (human_region_ptr, canonical_region_ptr): (i32, i32),
+    state_key_region_ptr: i32,
 ) -> WasmEngineResult<i32> {
-    let used_gas = context.gas_costs.external_canonicalize_address as u64;
-    use_gas(instance, used_gas)?;
-
-    let human = read_from_memory(instance, human_region_ptr as u32)
-        .map_err(debug_err!(err => "canonicalize_address failed to extract vector from human_region_ptr: {err}"))?;
+    let state_key_name = read_from_memory(instance, state_key_region_ptr as u32).map_err(
+        debug_err!(err => "db_read failed to extract vector from state_key_region_ptr: {err}"),
+    )?;
 
-    let mut human_addr_str = match std::str::from_utf8(&human) {
-        Ok(addr) => addr,
-        Err(_err) => {
-            debug!(
-                "canonicalize_address input was not valid UTF-8: {}",
-                show_bytes(&human)
-            );
-            return write_to_memory(instance, b"input is not valid UTF-8")
-                .map(|n| n as i32)
-                .map_err(debug_err!("failed to write error message to contract"));
-        }
+    let value = match ContractStorage::new(context, instance).get(&state_key_name)? {
+        // Return 0 (null ponter) if value is empty
+        Some(value) => value,
+        None => return Ok(0),
     };
-    human_addr_str = human_addr_str.trim();
-    if human_addr_str.is_empty() {
-        debug!("canonicalize_address input was empty");
-        return write_to_memory(instance, b"input is empty")
-            .map(|n| n as i32)
-            .map_err(debug_err!("failed to write error message to contract"));
-    }
 
-    //debug!("canonicalize_address was called with {:?}", human_addr_str);
+    let region_ptr = write_to_memory(instance, &value).map_err(|err| {
+        debug!(
+            "read_db() error while trying to allocate {} bytes for the value",
+            value.len(),
+        );
+        err
+    })?;
 
-    let (decoded_prefix, data) = match bech32::decode(human_addr_str) {
-        Ok(ret) => ret,
-        Err(err) => {
-            debug!(
-                "canonicalize_address failed to parse input as bech32: {:?}",
-                err
-            );
-            return write_to_memory(instance, err.to_string().as_bytes())
-                .map(|n| n as i32)
-                .map_err(debug_err!("failed to write error message to contract"));
-        }
-    };
+    Ok(region_ptr as i32)
+}
 
-    if decoded_prefix != BECH32_PREFIX_ACC_ADDR {
-        debug!("canonicalize_address was called with an unexpected address prefix");
-        return write_to_memory(
-            instance,
-            format!("wrong address prefix: {:?}", decoded_prefix).as_bytes(),
-        )
-        .map(|n| n as i32)
-        .map_err(debug_err!("failed to write error message to contract"));
+/// Reads the keys in `state_keys_region_ptr`, encoded as sections. Returns their values in the
+/// same order, encoded as sections too: an empty section for a missing key, or a 1 byte followed
+/// by the value, so that an empty value is distinguishable from a missing key.
+fn host_read_multiple_db(
+    context: &mut Context,
+    instance: &wasm3::Instance<Context>,
+    state_keys_region_ptr: i32,
+) -> WasmEngineResult<i32> {
+    let state_key_names = decode_sections_from_memory(instance, state_keys_region_ptr as u32)
+        .map_err(debug_err!(err => "db_read_multiple failed to extract keys from state_keys_region_ptr: {err}"))?;
+
+    for state_key_name in &state_key_names {
+        check_not_reserved(state_key_name)?;
     }
 
-    let canonical = Vec::<u8>::from_base32(&data).map_err(|err| {
-        // Assaf: From reading https://docs.rs/bech32/0.7.2/src/bech32/lib.rs.html#607
-        // and https://docs.rs/bech32/0.7.2/src/bech32/lib.rs.html#228 I don't think this can fail that way
-        debug!("canonicalize_address failed to parse base32: {}", err);
-        WasmEngineError::Base32Error
+    let mut encoded_values = vec![];
+    for value in read_multiple_db(context, instance, &state_key_names)? {
+        let section_len = match value {
+            None => 0,
+            Some(value) => {
+                encoded_values.push(1);
+                encoded_values.extend_from_slice(&value);
+                value.len() + 1
+            }
+        };
+        encoded_values.extend_from_slice(&(section_len as u32).to_be_bytes());
+    }
+
+    let region_ptr = write_to_memory(instance, &encoded_values).map_err(|err| {
+        debug!(
+            "db_read_multiple() error while trying to allocate {} bytes for the values",
+            encoded_values.len(),
+        );
+        err
     })?;
 
+    Ok(region_ptr as i32)
+}
+
+fn host_remove_db(
+    context: &mut Context,
+    instance: &wasm3::Instance<Context>,
+    state_key_region_ptr: i32,
+) -> WasmEngineResult<()> {
+    let state_key_name = read_from_memory(instance, state_key_region_ptr as u32).map_err(
+        debug_err!(err => "db_remove failed to extract vector from state_key_region_ptr: {err}"),
+    )?;
+
+    ContractStorage::new(context, instance).remove(&state_key_name)
+}
+
+/// Reads the entries of `[start, end)` in `order`, from the cache and the encrypted state
+fn range_db(
+    context: &mut Context,
+    instance: &wasm3::Instance<Context>,
+    start: Option<&[u8]>,
+    end: Option<&[u8]>,
+    order: Order,
+) -> WasmEngineResult<Vec<KV>> {
+    use_gas(instance, READ_BASE_GAS)?;
+
     debug!(
-        "canonicalize_address returning address {}",
-        hex::encode(human_addr_str)
+        "db_scan scanning from {:?} to {:?} in {:?} order",
+        start.map(show_bytes),
+        end.map(show_bytes),
+        order
     );
 
-    write_to_allocated_memory(instance, canonical_region_ptr as u32, &canonical)?;
+    let per_entry_cost = context.gas_costs.external_db_scan_per_entry as u64;
+    let (stored, used_gas) = range_from_encrypted_state(
+        start,
+        end,
+        &context.context,
+        &context.og_contract_key,
+        || use_gas(instance, per_entry_cost),
+    )
+    .map_err(debug_err!("db_scan failed to scan the storage"))?;
+    context.use_gas_externally(used_gas);
 
-    // return 0 == ok
-    Ok(0)
+    let mut entries =
+        storage_range::merge_range(stored, context.kv_cache.writes(), start, end, order);
+    // the output log is kept in the contract's state, but isn't the contract's to read
+    entries.retain(|(key, _)| key != OUTPUT_LOG_KEY);
+
+    Ok(entries)
 }
 
-fn host_addr_canonicalize(
+/// Opens a range over the contract's storage and returns its id for `db_next`. A bound whose
+/// region is 0 is open, and `order` is 1 for ascending and 2 for descending.
+fn host_db_scan(
     context: &mut Context,
     instance: &wasm3::Instance<Context>,
-    (human_region_ptr, canonical_region_ptr): (i32, i32),
+    (start_region_ptr, end_region_ptr, order): (i32, i32, i32),
 ) -> WasmEngineResult<i32> {
-    let used_gas = context.gas_costs.external_canonicalize_address as u64;
-    use_gas(instance, used_gas)?;
-
-    let human = read_from_memory(instance, human_region_ptr as u32)
-        .map_err(debug_err!(err => "addr_canonicalize failed to extract vector from human_region_ptr: {err}"))?;
-
-    let human_addr_str = match std::str::from_utf8(&human) {
-        Ok(addr) => addr,
-        Err(_err) => {
-            debug!(
-                "addr_canonicalize input was not valid UTF-8: {}",
-                show_bytes(&human)
-            );
-            return write_to_memory(instance, b"input is not valid UTF-8")
-                .map(|n| n as i32)
-                .map_err(debug_err!("failed to write error message to contract"));
+    let read_bound = |region_ptr: i32| -> WasmEngineResult<Option<Vec<u8>>> {
+        if region_ptr == 0 {
+            return Ok(None);
         }
+        read_from_memory(instance, region_ptr as u32)
+            .map(Some)
+            .map_err(debug_err!(err => "db_scan failed to extract a bound: {err}"))
     };
-    if human_addr_str.is_empty() {
-        debug!("addr_canonicalize input was empty");
-        return write_to_memory(instance, b"Input is empty")
-            .map(|n| n as i32)
-            .map_err(debug_err!("failed to write error message to contract"));
-    }
+    let start = read_bound(start_region_ptr)?;
+    let end = read_bound(end_region_ptr)?;
 
-    debug!("addr_canonicalize was called with {:?}", human_addr_str);
+    let order = Order::from_i32(order).ok_or_else(|| {
+        debug!("db_scan was called with an unknown order {}", order);
+        WasmEngineError::DeserializationError
+    })?;
 
-    let (decoded_prefix, data) = match bech32::decode(human_addr_str) {
-        Ok(ret) => ret,
-        Err(err) => {
-            debug!(
-                "addr_canonicalize failed to parse input as bech32: {:?}",
-                err
-            );
-            return write_to_memory(instance, err.to_string().as_bytes())
-                .map(|n| n as i32)
-                .map_err(debug_err!("failed to write error message to contract"));
-        }
-    };
+    // fail before scanning, rather than after decrypting the whole state
+    check_open_ranges(context.ranges.len())?;
 
-    if decoded_prefix != BECH32_PREFIX_ACC_ADDR {
-        debug!("addr_canonicalize was called with an unexpected address prefix");
-        return write_to_memory(
-            instance,
-            format!("wrong address prefix: {:?}", decoded_prefix).as_bytes(),
-        )
-        .map(|n| n as i32)
-        .map_err(debug_err!("failed to write error message to contract"));
-    }
+    let entries = range_db(context, instance, start.as_deref(), end.as_deref(), order)?;
+    context.ranges.push(entries.into_iter());
 
-    let canonical = Vec::<u8>::from_base32(&data).map_err(|err| {
+    Ok(context.ranges.len() as i32)
+}
+
+/// How many ranges `db_scan` can open in one call
+const MAX_OPEN_RANGES: usize = 32;
+
+/// Fails if a call that already opened `open_ranges` ranges can't open another. Ranges stay open,
+/// holding their entries, until the call ends.
+fn check_open_ranges(open_ranges: usize) -> WasmEngineResult<()> {
+    if open_ranges >= MAX_OPEN_RANGES {
+        debug!(
+            "db_scan was called with {} ranges open, the maximum is {}",
+            open_ranges, MAX_OPEN_RANGES
+        );
+        return Err(WasmEngineError::TooManyRanges);
+    }
+    Ok(())
+}
+
+/// Returns the next entry of a range `db_scan` opened, encoded as the sections key and value.
+/// Past the end of the range both are empty.
+fn host_db_next(
+    context: &mut Context,
+    instance: &wasm3::Instance<Context>,
+    range_id: i32,
+) -> WasmEngineResult<i32> {
+    let per_byte_cost = context.gas_costs.external_db_next_per_byte as u64;
+    let range = (range_id as usize)
+        .checked_sub(1)
+        .and_then(|index| context.ranges.get_mut(index))
+        .ok_or_else(|| {
+            debug!("db_next was called with an unknown range {}", range_id);
+            WasmEngineError::DeserializationError
+        })?;
+
+    let (key, value) = range.next().unwrap_or_default();
+    use_gas(
+        instance,
+        per_byte_cost.saturating_mul((key.len() + value.len()) as u64),
+    )?;
+
+    write_to_memory(instance, &storage_map::encode_entry(&key, &value))
+        .map(|region_ptr| region_ptr as i32)
+}
+
+fn host_write_db(
+    context: &mut Context,
+    instance: &wasm3::Instance<Context>,
+    (state_key_region_ptr, value_region_ptr): (i32, i32),
+) -> WasmEngineResult<()> {
+    let state_key_name = read_from_memory(instance, state_key_region_ptr as u32).map_err(
+        debug_err!(err => "db_write failed to extract vector from state_key_region_ptr: {err}"),
+    )?;
+    let value = read_from_memory(instance, value_region_ptr as u32).map_err(
+        debug_err!(err => "db_write failed to extract vector from value_region_ptr: {err}"),
+    )?;
+
+    ContractStorage::new(context, instance).set(&state_key_name, &value)
+}
+
+/// The contract's storage, as seen by the storage host functions. Queries get a read-only view,
+/// so a contract that writes during a query fails however it reaches the storage.
+struct ContractStorage<'a> {
+    context: &'a mut Context,
+    instance: &'a wasm3::Instance<Context>,
+    read_only: bool,
+}
+
+impl<'a> ContractStorage<'a> {
+    fn new(context: &'a mut Context, instance: &'a wasm3::Instance<Context>) -> Self {
+        let read_only = context.operation.is_query();
+        Self {
+            context,
+            instance,
+            read_only,
+        }
+    }
+
+    fn check_writable(&self) -> WasmEngineResult<()> {
+        if self.read_only {
+            debug!("contract tried to write to storage during a query");
+            return Err(WasmEngineError::StorageWriteInQuery);
+        }
+        Ok(())
+    }
+}
+
+/// The output log is kept in the contract's state, but only the engine reads and writes it
+fn check_not_reserved(key: &[u8]) -> WasmEngineResult<()> {
+    if key == OUTPUT_LOG_KEY {
+        debug!("contract tried to access the output log's key");
+        return Err(WasmEngineError::ReservedKey);
+    }
+    Ok(())
+}
+
+impl KeyValueStore for ContractStorage<'_> {
+    fn get(&mut self, key: &[u8]) -> WasmEngineResult<Option<Vec<u8>>> {
+        check_not_reserved(key)?;
+        read_db(self.context, self.instance, key)
+    }
+
+    fn set(&mut self, key: &[u8], value: &[u8]) -> WasmEngineResult<()> {
+        self.check_writable()?;
+        check_not_reserved(key)?;
+        write_db(self.context, self.instance, key, value)
+    }
+
+    fn remove(&mut self, key: &[u8]) -> WasmEngineResult<()> {
+        self.check_writable()?;
+        check_not_reserved(key)?;
+        remove_db(self.context, key)
+    }
+}
+
+fn host_storage_push(
+    context: &mut Context,
+    instance: &wasm3::Instance<Context>,
+    (key_region_ptr, value_region_ptr): (i32, i32),
+) -> WasmEngineResult<u32> {
+    let key = read_from_memory(instance, key_region_ptr as u32).map_err(
+        debug_err!(err => "storage_push failed to extract vector from key_region_ptr: {err}"),
+    )?;
+    let value = read_from_memory(instance, value_region_ptr as u32).map_err(
+        debug_err!(err => "storage_push failed to extract vector from value_region_ptr: {err}"),
+    )?;
+
+    storage_list::push(&mut ContractStorage::new(context, instance), &key, &value)
+}
+
+fn host_storage_pop(
+    context: &mut Context,
+    instance: &wasm3::Instance<Context>,
+    key_region_ptr: i32,
+) -> WasmEngineResult<i32> {
+    let key = read_from_memory(instance, key_region_ptr as u32).map_err(
+        debug_err!(err => "storage_pop failed to extract vector from key_region_ptr: {err}"),
+    )?;
+
+    let value = match storage_list::pop(&mut ContractStorage::new(context, instance), &key)? {
+        // Return 0 (null ponter) if the list is empty
+        Some(value) => value,
+        None => return Ok(0),
+    };
+
+    write_to_memory(instance, &value).map(|region_ptr| region_ptr as i32)
+}
+
+fn host_storage_len(
+    context: &mut Context,
+    instance: &wasm3::Instance<Context>,
+    key_region_ptr: i32,
+) -> WasmEngineResult<u32> {
+    let key = read_from_memory(instance, key_region_ptr as u32).map_err(
+        debug_err!(err => "storage_len failed to extract vector from key_region_ptr: {err}"),
+    )?;
+
+    storage_list::len(&mut ContractStorage::new(context, instance), &key)
+}
+
+/// Returns 1 if `key` is new to the map, 0 if its value was replaced
+fn host_storage_map_insert(
+    context: &mut Context,
+    instance: &wasm3::Instance<Context>,
+    (map_region_ptr, key_region_ptr, value_region_ptr): (i32, i32, i32),
+) -> WasmEngineResult<i32> {
+    let map = read_from_memory(instance, map_region_ptr as u32).map_err(
+        debug_err!(err => "storage_map_insert failed to extract vector from map_region_ptr: {err}"),
+    )?;
+    let key = read_from_memory(instance, key_region_ptr as u32).map_err(
+        debug_err!(err => "storage_map_insert failed to extract vector from key_region_ptr: {err}"),
+    )?;
+    let value = read_from_memory(instance, value_region_ptr as u32).map_err(
+        debug_err!(err => "storage_map_insert failed to extract vector from value_region_ptr: {err}"),
+    )?;
+
+    let is_new = storage_map::insert(
+        &mut ContractStorage::new(context, instance),
+        &map,
+        &key,
+        &value,
+    )?;
+
+    Ok(is_new as i32)
+}
+
+fn host_storage_map_remove(
+    context: &mut Context,
+    instance: &wasm3::Instance<Context>,
+    (map_region_ptr, key_region_ptr): (i32, i32),
+) -> WasmEngineResult<i32> {
+    let map = read_from_memory(instance, map_region_ptr as u32).map_err(
+        debug_err!(err => "storage_map_remove failed to extract vector from map_region_ptr: {err}"),
+    )?;
+    let key = read_from_memory(instance, key_region_ptr as u32).map_err(
+        debug_err!(err => "storage_map_remove failed to extract vector from key_region_ptr: {err}"),
+    )?;
+
+    let mut storage = ContractStorage::new(context, instance);
+    let value = match storage_map::remove(&mut storage, &map, &key)? {
+        // Return 0 (null ponter) if the key isn't in the map
+        Some(value) => value,
+        None => return Ok(0),
+    };
+
+    write_to_memory(instance, &value).map(|region_ptr| region_ptr as i32)
+}
+
+fn host_storage_map_get(
+    context: &mut Context,
+    instance: &wasm3::Instance<Context>,
+    (map_region_ptr, key_region_ptr): (i32, i32),
+) -> WasmEngineResult<i32> {
+    let map = read_from_memory(instance, map_region_ptr as u32).map_err(
+        debug_err!(err => "storage_map_get failed to extract vector from map_region_ptr: {err}"),
+    )?;
+    let key = read_from_memory(instance, key_region_ptr as u32).map_err(
+        debug_err!(err => "storage_map_get failed to extract vector from key_region_ptr: {err}"),
+    )?;
+
+    let value = match storage_map::get(&mut ContractStorage::new(context, instance), &map, &key)? {
+        // Return 0 (null ponter) if the key isn't in the map
+        Some(value) => value,
+        None => return Ok(0),
+    };
+
+    write_to_memory(instance, &value).map(|region_ptr| region_ptr as i32)
+}
+
+fn host_storage_map_len(
+    context: &mut Context,
+    instance: &wasm3::Instance<Context>,
+    map_region_ptr: i32,
+) -> WasmEngineResult<i32> {
+    let map = read_from_memory(instance, map_region_ptr as u32).map_err(
+        debug_err!(err => "storage_map_len failed to extract vector from map_region_ptr: {err}"),
+    )?;
+
+    let length = storage_map::len(&mut ContractStorage::new(context, instance), &map)?;
+
+    Ok(length as i32)
+}
+
+/// Returns the entry at `position` of the map, encoded as the sections key and value, or 0 past
+/// the end of the map
+fn host_storage_map_entry(
+    context: &mut Context,
+    instance: &wasm3::Instance<Context>,
+    (map_region_ptr, position): (i32, i32),
+) -> WasmEngineResult<i32> {
+    let map = read_from_memory(instance, map_region_ptr as u32).map_err(
+        debug_err!(err => "storage_map_entry failed to extract vector from map_region_ptr: {err}"),
+    )?;
+
+    let entry = storage_map::entry_at(
+        &mut ContractStorage::new(context, instance),
+        &map,
+        position as u32,
+    )?;
+    let (key, value) = match entry {
+        Some(entry) => entry,
+        None => return Ok(0),
+    };
+
+    write_to_memory(instance, &storage_map::encode_entry(&key, &value))
+        .map(|region_ptr| region_ptr as i32)
+}
+
+/// Returns 1 if the key no longer exists after removing it, 0 otherwise
+fn host_storage_secure_remove(
+    context: &mut Context,
+    instance: &wasm3::Instance<Context>,
+    key_region_ptr: i32,
+) -> WasmEngineResult<i32> {
+    let key = read_from_memory(instance, key_region_ptr as u32).map_err(
+        debug_err!(err => "storage_secure_remove failed to extract vector from key_region_ptr: {err}"),
+    )?;
+
+    let removed = secure_remove(&mut ContractStorage::new(context, instance), &key)?;
+
+    Ok(removed as i32)
+}
+
+/// Removes every key of the contract that starts with `prefix`, see `storage_wipe`, and returns
+/// how many there were
+fn host_storage_wipe_prefix(
+    context: &mut Context,
+    instance: &wasm3::Instance<Context>,
+    prefix_region_ptr: i32,
+) -> WasmEngineResult<u32> {
+    let prefix = read_from_memory(instance, prefix_region_ptr as u32).map_err(
+        debug_err!(err => "storage_wipe_prefix failed to extract vector from prefix_region_ptr: {err}"),
+    )?;
+
+    // an empty prefix would wipe the contract's whole state
+    storage_list::check_key(&prefix)?;
+
+    let end = prefix_end(&prefix);
+    let keys: Vec<Vec<u8>> = range_db(
+        context,
+        instance,
+        Some(&prefix),
+        end.as_deref(),
+        Order::Ascending,
+    )?
+    .into_iter()
+    .map(|(key, _)| key)
+    .collect();
+
+    let per_key_cost = context.gas_costs.external_storage_wipe_prefix_per_key as u64;
+    wipe_keys(
+        &mut ContractStorage::new(context, instance),
+        &keys,
+        |count| use_gas(instance, per_key_cost.saturating_mul(count as u64)),
+    )
+}
+
+/// Returns 1 if `new` was written, 0 if the current value wasn't the expected one.
+/// A null `expected_region_ptr` expects the key not to exist.
+fn host_storage_cas(
+    context: &mut Context,
+    instance: &wasm3::Instance<Context>,
+    (key_region_ptr, expected_region_ptr, new_region_ptr): (i32, i32, i32),
+) -> WasmEngineResult<i32> {
+    let key = read_from_memory(instance, key_region_ptr as u32).map_err(
+        debug_err!(err => "storage_cas failed to extract vector from key_region_ptr: {err}"),
+    )?;
+    let expected = match expected_region_ptr {
+        0 => None,
+        ptr => Some(read_from_memory(instance, ptr as u32).map_err(
+            debug_err!(err => "storage_cas failed to extract vector from expected_region_ptr: {err}"),
+        )?),
+    };
+    let new = read_from_memory(instance, new_region_ptr as u32).map_err(
+        debug_err!(err => "storage_cas failed to extract vector from new_region_ptr: {err}"),
+    )?;
+
+    let swapped = compare_and_swap(
+        &mut ContractStorage::new(context, instance),
+        &key,
+        expected.as_deref(),
+        &new,
+    )?;
+
+    Ok(swapped as i32)
+}
+
+fn host_canonicalize_address(
+    context: &mut Context,
+    instance: &wasm3::Instance<Context>,
+    (human_region_ptr, canonical_region_ptr): (i32, i32),
+) -> WasmEngineResult<i32> {
+    let used_gas = context.gas_costs.external_canonicalize_address as u64;
+    use_gas(instance, used_gas)?;
+
+    let human = read_from_memory(instance, human_region_ptr as u32)
+        .map_err(debug_err!(err => "canonicalize_address failed to extract vector from human_region_ptr: {err}"))?;
+
+    let mut human_addr_str = match std::str::from_utf8(&human) {
+        Ok(addr) => addr,
+        Err(_err) => {
+            debug!(
+                "canonicalize_address input was not valid UTF-8: {}",
+                show_bytes(&human)
+            );
+            return write_to_memory(instance, b"input is not valid UTF-8")
+                .map(|n| n as i32)
+                .map_err(debug_err!("failed to write error message to contract"));
+        }
+    };
+    human_addr_str = human_addr_str.trim();
+    if human_addr_str.is_empty() {
+        debug!("canonicalize_address input was empty");
+        return write_to_memory(instance, b"input is empty")
+            .map(|n| n as i32)
+            .map_err(debug_err!("failed to write error message to contract"));
+    }
+
+    //debug!("canonicalize_address was called with {:?}", human_addr_str);
+
+    let (decoded_prefix, data) = match bech32::decode(human_addr_str) {
+        Ok(ret) => ret,
+        Err(err) => {
+            debug!(
+                "canonicalize_address failed to parse input as bech32: {:?}",
+                err
+            );
+            return write_to_memory(instance, err.to_string().as_bytes())
+                .map(|n| n as i32)
+                .map_err(debug_err!("failed to write error message to contract"));
+        }
+    };
+
+    if decoded_prefix != BECH32_PREFIX_ACC_ADDR {
+        debug!("canonicalize_address was called with an unexpected address prefix");
+        return write_to_memory(
+            instance,
+            format!("wrong address prefix: {:?}", decoded_prefix).as_bytes(),
+        )
+        .map(|n| n as i32)
+        .map_err(debug_err!("failed to write error message to contract"));
+    }
+
+    let canonical = Vec::<u8>::from_base32(&data).map_err(|err| {
+        // Assaf: From reading https://docs.rs/bech32/0.7.2/src/bech32/lib.rs.html#607
+        // and https://docs.rs/bech32/0.7.2/src/bech32/lib.rs.html#228 I don't think this can fail that way
+        debug!("canonicalize_address failed to parse base32: {}", err);
+        WasmEngineError::Base32Error
+    })?;
+
+    debug!(
+        "canonicalize_address returning address {}",
+        hex::encode(human_addr_str)
+    );
+
+    write_to_allocated_memory(instance, canonical_region_ptr as u32, &canonical)?;
+
+    // return 0 == ok
+    Ok(0)
+}
+
+fn host_addr_canonicalize(
+    context: &mut Context,
+    instance: &wasm3::Instance<Context>,
+    (human_region_ptr, canonical_region_ptr): (i32, i32),
+) -> WasmEngineResult<i32> {
+    let used_gas = context.gas_costs.external_canonicalize_address as u64;
+    use_gas(instance, used_gas)?;
+
+    let human = read_from_memory(instance, human_region_ptr as u32)
+        .map_err(debug_err!(err => "addr_canonicalize failed to extract vector from human_region_ptr: {err}"))?;
+
+    let human_addr_str = match std::str::from_utf8(&human) {
+        Ok(addr) => addr,
+        Err(_err) => {
+            debug!(
+                "addr_canonicalize input was not valid UTF-8: {}",
+                show_bytes(&human)
+            );
+            return write_to_memory(instance, b"input is not valid UTF-8")
+                .map(|n| n as i32)
+                .map_err(debug_err!("failed to write error message to contract"));
+        }
+    };
+    if human_addr_str.is_empty() {
+        debug!("addr_canonicalize input was empty");
+        return write_to_memory(instance, b"Input is empty")
+            .map(|n| n as i32)
+            .map_err(debug_err!("failed to write error message to contract"));
+    }
+
+    debug!("addr_canonicalize was called with {:?}", human_addr_str);
+
+    let (decoded_prefix, data) = match bech32::decode(human_addr_str) {
+        Ok(ret) => ret,
+        Err(err) => {
+            debug!(
+                "addr_canonicalize failed to parse input as bech32: {:?}",
+                err
+            );
+            return write_to_memory(instance, err.to_string().as_bytes())
+                .map(|n| n as i32)
+                .map_err(debug_err!("failed to write error message to contract"));
+        }
+    };
+
+    if decoded_prefix != BECH32_PREFIX_ACC_ADDR {
+        debug!("addr_canonicalize was called with an unexpected address prefix");
+        return write_to_memory(
+            instance,
+            format!("wrong address prefix: {:?}", decoded_prefix).as_bytes(),
+        )
+        .map(|n| n as i32)
+        .map_err(debug_err!("failed to write error message to contract"));
+    }
+
+    let canonical = Vec::<u8>::from_base32(&data).map_err(|err| {
         // Assaf: From reading https://docs.rs/bech32/0.7.2/src/bech32/lib.rs.html#607
         // and https://docs.rs/bech32/0.7.2/src/bech32/lib.rs.html#228 I don't think this can fail that way
         debug!("addr_canonicalize failed to parse base32: {}", err);
@@ -1242,6 +1988,16 @@ fn host_humanize_address(
         hex::encode(&canonical)
     );
 
+    if !is_valid_canonical_length(canonical.len()) {
+        debug!(
+            "humanize_address was called with a canonical address of invalid length {}",
+            canonical.len()
+        );
+        return write_to_memory(instance, b"invalid length")
+            .map(|n| n as i32)
+            .map_err(debug_err!("failed to write error message to contract"));
+    }
+
     let human_addr_str = match bech32::encode(BECH32_PREFIX_ACC_ADDR, canonical.to_base32()) {
         Ok(addr) => addr,
         Err(err) => {
@@ -1262,6 +2018,12 @@ fn host_humanize_address(
     Ok(0)
 }
 
+/// Canonical addresses are 20 bytes for accounts and contracts, or 32 bytes for
+/// module-derived addresses
+fn is_valid_canonical_length(len: usize) -> bool {
+    len == 20 || len == 32
+}
+
 fn host_query_chain(
     context: &mut Context,
     instance: &wasm3::Instance<Context>,
@@ -1275,16 +2037,286 @@ fn host_query_chain(
     let answer = encrypt_and_query_chain(
         &query_buffer,
         context.query_depth,
+        context.max_query_depth,
+        &context.contract_address,
         &context.context,
         context.user_nonce,
         context.user_public_key,
         &mut used_gas,
         get_remaining_gas(instance),
+        &context.output_compression,
+        Some(&mut context.query_cache),
+    )?;
+
+    context.use_gas_externally(used_gas);
+    // an answer from the cache wasn't charged by the chain
+    use_gas(instance, context.query_cache.take_uncharged_gas())?;
+
+    write_to_memory(instance, &answer).map(|region_ptr| region_ptr as i32)
+}
+
+/// Returns the JSON encoded `Vec<Coin>` held by the contract, without the contract having to
+/// build and parse a bank query for every denom
+fn host_contract_all_balances(
+    context: &mut Context,
+    instance: &wasm3::Instance<Context>,
+) -> WasmEngineResult<i32> {
+    let mut used_gas: u64 = 0;
+    let balances = query_all_balances(
+        context.query_depth,
+        context.max_query_depth,
+        &context.contract_address,
+        &context.context,
+        context.user_nonce,
+        context.user_public_key,
+        &mut used_gas,
+        get_remaining_gas(instance),
+        &context.output_compression,
+    )?;
+
+    context.use_gas_externally(used_gas);
+
+    write_to_memory(instance, &balances).map(|region_ptr| region_ptr as i32)
+}
+
+/// Resolves an `ibc/<hash>` denom to its JSON encoded `DenomTrace` with the chain's transfer
+/// module. Returns an error code from `DenomTraceError` in the high half for invalid denoms and
+/// unknown hashes, and a pointer to the trace in the low half otherwise.
+fn host_parse_ibc_denom(
+    context: &mut Context,
+    instance: &wasm3::Instance<Context>,
+    denom_ptr: i32,
+) -> WasmEngineResult<i64> {
+    use_gas(instance, context.gas_costs.external_parse_ibc_denom as u64)?;
+
+    let denom = read_from_memory(instance, denom_ptr as u32)
+        .map_err(debug_err!(err => "parse_ibc_denom error while trying to read denom from wasm memory: {err}"))?;
+    let denom = match String::from_utf8(denom) {
+        Ok(denom) => denom,
+        Err(_) => return Ok(to_high_half(DenomTraceError::InvalidDenom as u32) as i64),
+    };
+
+    let mut used_gas: u64 = 0;
+    let gas_limit = get_remaining_gas(instance);
+    let result = resolve_ibc_denom(&denom, |request| {
+        query_stargate(
+            DENOM_TRACE_QUERY_PATH,
+            request,
+            context.query_depth,
+            context.max_query_depth,
+            &context.contract_address,
+            &context.context,
+            context.user_nonce,
+            context.user_public_key,
+            &mut used_gas,
+            gas_limit,
+            &context.output_compression,
+        )
+    });
+    context.use_gas_externally(used_gas);
+
+    match result? {
+        Err(err) => {
+            debug!("parse_ibc_denom() failed for {}: {:?}", denom, err);
+            Ok(to_high_half(err as u32) as i64)
+        }
+        Ok(trace) => {
+            let trace = serde_json::to_vec(&trace).map_err(|err| {
+                debug!("parse_ibc_denom failed to serialize the denom trace: {:?}", err);
+                WasmEngineError::SerializationError
+            })?;
+            let ptr_to_region_in_wasm_vm = write_to_memory(instance, &trace).map_err(
+                debug_err!(err => "parse_ibc_denom error while trying to write the denom trace to wasm memory: {err}"),
+            )?;
+
+            Ok(to_low_half(ptr_to_region_in_wasm_vm) as i64)
+        }
+    }
+}
+
+/// Returns the plaintext JSON `BlockView` of the block this call is executing against
+fn host_enclave_block_view(
+    context: &mut Context,
+    instance: &wasm3::Instance<Context>,
+) -> WasmEngineResult<i32> {
+    use_gas(instance, context.gas_costs.external_enclave_block_view as u64)?;
+
+    let block_view = current_block_view(context.block_height, context.timestamp);
+    let block_view = serde_json::to_vec(&block_view).map_err(|err| {
+        debug!("enclave_block_view failed to serialize the block view: {:?}", err);
+        WasmEngineError::SerializationError
+    })?;
+
+    write_to_memory(instance, &block_view).map(|region_ptr| region_ptr as i32)
+}
+
+/// Returns how many more submessages the contract's response may contain: the chain's limit less
+/// what the transaction already sent, or `-1` if the chain doesn't limit submessages. It returns
+/// an i64 so that every u32 budget fits next to the `-1`.
+/// The response is built inside wasm and only reaches the engine when the contract returns, so
+/// contracts subtract what they've queued in this call themselves.
+fn host_remaining_submsg_budget(
+    context: &mut Context,
+    instance: &wasm3::Instance<Context>,
+) -> WasmEngineResult<i64> {
+    use_gas(instance, context.gas_costs.external_remaining_submsg_budget as u64)?;
+
+    let remaining = context
+        .submsg_limit
+        .as_ref()
+        .map_or(-1, |submsg_limit| submsg_limit.remaining() as i64);
+    Ok(remaining)
+}
+
+/// Returns the JSON encoded `env.transaction.min_gas_price`, an empty list outside of transactions.
+/// Contracts can call this even if their `Env` type doesn't have the field yet.
+fn host_min_gas_price(
+    context: &mut Context,
+    instance: &wasm3::Instance<Context>,
+) -> WasmEngineResult<i32> {
+    use_gas(instance, context.gas_costs.external_min_gas_price as u64)?;
+
+    let min_gas_price = serde_json::to_vec(&context.min_gas_price).map_err(|err| {
+        debug!("min_gas_price failed to serialize the gas price: {:?}", err);
+        WasmEngineError::SerializationError
+    })?;
+
+    write_to_memory(instance, &min_gas_price).map(|region_ptr| region_ptr as i32)
+}
+
+/// Returns `{"msg_index":..,"msg_count":..}`, the position of the message the contract was called
+/// with among its transaction's messages, or 0 when it wasn't called by one of them (in queries,
+/// submessages and replies). Contracts can call this even if their `Env` type doesn't have the
+/// fields yet.
+fn host_tx_msg_position(
+    context: &mut Context,
+    instance: &wasm3::Instance<Context>,
+) -> WasmEngineResult<i32> {
+    use_gas(instance, context.gas_costs.external_tx_msg_position as u64)?;
+
+    let tx_msg_position = match &context.tx_msg_position {
+        Some(tx_msg_position) => tx_msg_position,
+        None => return Ok(0),
+    };
+    let tx_msg_position = serde_json::to_vec(tx_msg_position).map_err(|err| {
+        debug!("tx_msg_position failed to serialize: {:?}", err);
+        WasmEngineError::SerializationError
+    })?;
+
+    write_to_memory(instance, &tx_msg_position).map(|region_ptr| region_ptr as i32)
+}
+
+/// Returns a 16 byte id derived from the seed, unique within this call. See `derive_id`.
+fn host_derive_id(
+    context: &mut Context,
+    instance: &wasm3::Instance<Context>,
+    seed_region_ptr: i32,
+) -> WasmEngineResult<i32> {
+    use_gas(instance, context.gas_costs.external_derive_id as u64)?;
+
+    let seed = read_from_memory(instance, seed_region_ptr as u32).map_err(
+        debug_err!(err => "derive_id failed to extract vector from seed_region_ptr: {err}"),
+    )?;
+
+    let id = derive_id(
+        &context.contract_address,
+        context.block_height,
+        context.derive_id_counter,
+        &seed,
+    );
+    context.derive_id_counter = context.derive_id_counter.checked_add(1).ok_or_else(|| {
+        debug!("derive_id was called too many times in a single call");
+        WasmEngineError::CounterExhausted
+    })?;
+
+    write_to_memory(instance, &id).map(|region_ptr| region_ptr as i32)
+}
+
+/// Returns the canonical address the next contract of `code_id` the contract instantiates will
+/// get, as long as nothing else instantiates a contract before it, or 0 if the chain didn't pass
+/// its next instance id. See `predict_contract_address`.
+fn host_predict_instantiate_address(
+    context: &mut Context,
+    instance: &wasm3::Instance<Context>,
+    code_id: i64,
+) -> WasmEngineResult<i32> {
+    use_gas(
+        instance,
+        context.gas_costs.external_predict_instantiate_address as u64,
+    )?;
+
+    let next_instance_id = match context.next_instance_id {
+        Some(next_instance_id) => next_instance_id,
+        None => return Ok(0),
+    };
+
+    let address =
+        predict_contract_address(code_id as u64, next_instance_id, &context.contract_address);
+    write_to_memory(instance, address.as_slice()).map(|region_ptr| region_ptr as i32)
+}
+
+/// Returns a 32 byte nonce for the contract's own encryption, unique within the block, or 0 in
+/// queries. See `derive_nonce`.
+fn host_derive_nonce(
+    context: &mut Context,
+    instance: &wasm3::Instance<Context>,
+) -> WasmEngineResult<i32> {
+    use_gas(instance, context.gas_costs.external_derive_nonce as u64)?;
+
+    let msg_counter = match context.msg_counter {
+        Some(msg_counter) => msg_counter,
+        None => return Ok(0),
+    };
+
+    let nonce = derive_nonce(
+        &NonceSource {
+            contract_address: &context.contract_address,
+            block_height: context.block_height,
+            msg_counter,
+            input_hash: &context.input_hash,
+        },
+        context.derive_nonce_counter,
+    );
+    context.derive_nonce_counter =
+        context.derive_nonce_counter.checked_add(1).ok_or_else(|| {
+            debug!("derive_nonce was called too many times in a single call");
+            WasmEngineError::CounterExhausted
+        })?;
+
+    write_to_memory(instance, &nonce).map(|region_ptr| region_ptr as i32)
+}
+
+fn host_last_output_hash(
+    context: &mut Context,
+    instance: &wasm3::Instance<Context>,
+    key_region_ptr: i32,
+) -> WasmEngineResult<i32> {
+    use_gas(instance, context.gas_costs.external_last_output_hash as u64)?;
+
+    let key = read_from_memory(instance, key_region_ptr as u32).map_err(
+        debug_err!(err => "last_output_hash failed to extract vector from key_region_ptr: {err}"),
     )?;
 
-    context.use_gas_externally(used_gas);
+    if context.output_log.is_none() {
+        let log = match read_db(context, instance, OUTPUT_LOG_KEY)? {
+            Some(log) => OutputLog::from_slice(&log)?,
+            None => OutputLog::default(),
+        };
+        context.output_log = Some(log);
+    }
 
-    write_to_memory(instance, &answer).map(|region_ptr| region_ptr as i32)
+    // queries don't record their output, so they can only look keys up
+    if !context.operation.is_query() && !context.output_log_claims.contains(&key) {
+        context.output_log_claims.push(key.clone());
+    }
+
+    let output_hash = match context.output_log.as_ref().and_then(|log| log.get(&key)) {
+        Some(output_hash) => output_hash,
+        // Return 0 (null pointer) if no output was recorded under the key
+        None => return Ok(0),
+    };
+
+    write_to_memory(instance, &output_hash).map(|region_ptr| region_ptr as i32)
 }
 
 #[cfg(feature = "debug-print")]
@@ -1654,82 +2686,598 @@ fn host_ed25519_batch_verify(
     let used_gas = base_cost + (signatures.len() as u64) * each_cost;
     use_gas(instance, used_gas)?;
 
-    let mut batch = ed25519_zebra::batch::Verifier::new();
-    for i in 0..signatures.len() {
-        let signature = match ed25519_zebra::Signature::try_from(signatures[i]) {
-            Ok(x) => x,
-            Err(err) => {
-                debug!(
-                    "ed25519_batch_verify() failed to create an ed25519 signature from signatures[{}]: {:?}",
-                    i, err
-                );
+    let mut batch = ed25519_zebra::batch::Verifier::new();
+    for i in 0..signatures.len() {
+        let signature = match ed25519_zebra::Signature::try_from(signatures[i]) {
+            Ok(x) => x,
+            Err(err) => {
+                debug!(
+                    "ed25519_batch_verify() failed to create an ed25519 signature from signatures[{}]: {:?}",
+                    i, err
+                );
+
+                // https://github.com/CosmWasm/cosmwasm/blob/v1.0.0-beta5/packages/crypto/src/errors.rs#L94
+                return Ok(WasmApiCryptoError::InvalidSignatureFormat as i32);
+            }
+        };
+
+        let pubkey = match ed25519_zebra::VerificationKeyBytes::try_from(pubkeys[i]) {
+            Ok(x) => x,
+            Err(err) => {
+                debug!(
+                        "ed25519_batch_verify() failed to create an ed25519 VerificationKey from public_keys[{}]: {:?}",
+                        i, err
+                    );
+
+                // https://github.com/CosmWasm/cosmwasm/blob/v1.0.0-beta5/packages/crypto/src/errors.rs#L95
+                return Ok(WasmApiCryptoError::InvalidPubkeyFormat as i32);
+            }
+        };
+
+        batch.queue((pubkey, signature, messages[i]));
+    }
+
+    // Assaf:
+    // To verify a batch of ed25519 signatures we need to provide an RNG source.
+    // In theory this doesn't have to be deterministic because the same signatures
+    // should produce the same output (true/false) regardless of the RNG being used.
+    // In practice I'm too afraid to do something non-deterministic in concensus code
+    // So I've decided to use a PRNG instead.
+    // For entropy I'm using the entire ed25519 batch verify input data + the gas consumed
+    // up until now in this WASM call. This will be deterministic, but also kinda-random in
+    // different situations. Note that the gas includes every WASM opcode and
+    // every WASM memory allocation up until now.
+    // Secret data from the enclave can also be used here but I'm not sure if that's necessary.
+    // A few more notes:
+    // 1. The vanilla CosmWasm v1 implementation is using RNG from the OS,
+    // meaning that different values are used in differents nodes for the same operation inside
+    // consensus code, but the output should be the same (https://github.com/CosmWasm/cosmwasm/blob/v1.0.0-beta5/packages/crypto/src/ed25519.rs#L108)
+    // 2. In Zcash (zebra) this is also used with RNG from the OS, however Zcash is a PoW chain
+    // and therefore there's no risk of consensus breaking (https://github.com/ZcashFoundation/zebra/blob/00aa5d96a30539a609bfdd17146b223c4e6cf424/tower-batch/tests/ed25519.rs#L72-L83).
+    // 3. In dalek-ed25519 they warn agains using deterministic RNG, as an attacker can derive a falsy signature from the right signature. For me this is an acceptable risk compared to breaking consensus (https://docs.rs/ed25519-dalek/1.0.1/ed25519_dalek/fn.verify_batch.html#on-deterministic-nonces and https://github.com/dalek-cryptography/ed25519-dalek/pull/147).
+    let mut rng_entropy: Vec<u8> = vec![];
+    rng_entropy.append(&mut messages_data.into_iter().flatten().collect());
+    rng_entropy.append(&mut signatures_data.into_iter().flatten().collect());
+    rng_entropy.append(&mut pubkeys_data.into_iter().flatten().collect());
+    let remaining_gas = get_remaining_gas(instance);
+    let used_gas = context.gas_limit.saturating_sub(remaining_gas);
+    rng_entropy.append(&mut used_gas.to_be_bytes().to_vec());
+
+    let rng_seed: [u8; 32] = sha_256(&rng_entropy);
+    let mut rng = ChaChaRng::from_seed(rng_seed);
+
+    match batch.verify(&mut rng) {
+        Err(err) => {
+            debug!(
+                "ed25519_batch_verify() failed to verify signatures: {:?}",
+                err
+            );
+
+            // return 1 == failed, invalid signature
+            // https://github.com/CosmWasm/cosmwasm/blob/v1.0.0-beta5/packages/vm/src/imports.rs#L329
+            Ok(1)
+        }
+        Ok(()) => {
+            // return 0 == success, valid signature
+            // https://github.com/CosmWasm/cosmwasm/blob/v1.0.0-beta5/packages/vm/src/imports.rs#L329
+            Ok(0)
+        }
+    }
+}
+
+fn host_secp256k1_batch_verify(
+    context: &mut Context,
+    instance: &wasm3::Instance<Context>,
+    (message_hashes_ptr, signatures_ptr, public_keys_ptr): (i32, i32, i32),
+) -> WasmEngineResult<i32> {
+    let message_hashes_data = decode_sections_from_memory(instance, message_hashes_ptr as u32)
+        .map_err(debug_err!(err => "secp256k1_batch_verify error while trying to read message_hashes from wasm memory: {err}"))?;
+
+    let signatures_data = decode_sections_from_memory(instance, signatures_ptr as u32)
+        .map_err(debug_err!(err => "secp256k1_batch_verify error while trying to read signatures from wasm memory: {err}"))?;
+
+    let pubkeys_data = decode_sections_from_memory(instance, public_keys_ptr as u32)
+        .map_err(debug_err!(err => "secp256k1_batch_verify error while trying to read public_keys from wasm memory: {err}"))?;
+
+    let base_cost = context.gas_costs.external_secp256k1_batch_verify_base as u64;
+    let each_cost = context.gas_costs.external_secp256k1_batch_verify_each as u64;
+    let used_gas = base_cost + (signatures_data.len() as u64) * each_cost;
+    use_gas(instance, used_gas)?;
+
+    match secp256k1_batch_verify(&message_hashes_data, &signatures_data, &pubkeys_data) {
+        Err(err) => Ok(err as i32),
+        Ok(false) => {
+            // return 1 == failed, invalid signature
+            // https://github.com/CosmWasm/cosmwasm/blob/v1.0.0-beta5/packages/vm/src/imports.rs#L329
+            Ok(1)
+        }
+        Ok(true) => {
+            // return 0 == success, valid signature
+            // https://github.com/CosmWasm/cosmwasm/blob/v1.0.0-beta5/packages/vm/src/imports.rs#L329
+            Ok(0)
+        }
+    }
+}
+
+fn host_bls12_381_aggregate_verify(
+    context: &mut Context,
+    instance: &wasm3::Instance<Context>,
+    (public_keys_ptr, messages_ptr, signature_ptr): (i32, i32, i32),
+) -> WasmEngineResult<i32> {
+    let pubkeys_data = decode_sections_from_memory(instance, public_keys_ptr as u32)
+        .map_err(debug_err!(err => "bls12_381_aggregate_verify error while trying to read public_keys from wasm memory: {err}"))?;
+
+    let messages_data = decode_sections_from_memory(instance, messages_ptr as u32)
+        .map_err(debug_err!(err => "bls12_381_aggregate_verify error while trying to read messages from wasm memory: {err}"))?;
+
+    let signature_data = read_from_memory(instance, signature_ptr as u32)
+        .map_err(debug_err!(err => "bls12_381_aggregate_verify error while trying to read signature from wasm memory: {err}"))?;
+
+    let base_cost = context.gas_costs.external_bls12_381_aggregate_verify_base as u64;
+    let pairing_cost = context.gas_costs.external_bls12_381_aggregate_verify_per_pairing as u64;
+    let message_cost = context.gas_costs.external_bls12_381_aggregate_verify_per_message as u64;
+    let pubkey_cost = context.gas_costs.external_bls12_381_aggregate_verify_per_pubkey as u64;
+    let used_gas = base_cost
+        + aggregate_verify_pairings(messages_data.len()) * pairing_cost
+        + messages_data.len() as u64 * message_cost
+        + pubkeys_data.len() as u64 * pubkey_cost;
+    use_gas(instance, used_gas)?;
+
+    // same convention as secp256k1_verify: 0 == valid, 1 == invalid, otherwise an error code
+    match bls12_381_aggregate_verify(&pubkeys_data, &messages_data, &signature_data) {
+        Err(err) => Ok(err as i32),
+        Ok(true) => Ok(0),
+        Ok(false) => Ok(1),
+    }
+}
+
+fn host_groth16_verify(
+    context: &mut Context,
+    instance: &wasm3::Instance<Context>,
+    (vk_ptr, public_inputs_ptr, proof_ptr): (i32, i32, i32),
+) -> WasmEngineResult<i32> {
+    let vk_data = read_from_memory(instance, vk_ptr as u32)
+        .map_err(debug_err!(err => "groth16_verify error while trying to read vk from wasm memory: {err}"))?;
+
+    let public_inputs_data = read_from_memory(instance, public_inputs_ptr as u32)
+        .map_err(debug_err!(err => "groth16_verify error while trying to read public_inputs from wasm memory: {err}"))?;
+
+    let proof_data = read_from_memory(instance, proof_ptr as u32)
+        .map_err(debug_err!(err => "groth16_verify error while trying to read proof from wasm memory: {err}"))?;
+
+    // charge for the inputs the key takes, which is what the verification costs
+    let input_count = groth16_public_input_count(vk_data.len()).unwrap_or(0) as u64;
+    let used_gas = context.gas_costs.external_groth16_verify_base as u64
+        + input_count * context.gas_costs.external_groth16_verify_per_input as u64;
+    use_gas(instance, used_gas)?;
+
+    match groth16_verify(&vk_data, &public_inputs_data, &proof_data) {
+        Err(err) => Ok(err as i32),
+        Ok(true) => Ok(0),
+        Ok(false) => Ok(1),
+    }
+}
+
+lazy_static! {
+    /// Shared by the verification threads of `secp256k1_batch_verify`
+    static ref SECP256K1_VERIFIER: secp256k1::Secp256k1<secp256k1::VerifyOnly> =
+        secp256k1::Secp256k1::verification_only();
+}
+
+/// Verifies a batch of secp256k1 signatures, succeeding only if all of them are valid.
+///
+/// Like `ed25519_batch_verify`, a single message hash or a single public key is applied to
+/// every signature in the batch. ECDSA has no batch verification equation, so this
+/// verifies one signature at a time, but shares a single verification context and parses
+/// a repeated public key only once. The signatures are spread over the verification threads
+/// the node configured.
+fn secp256k1_batch_verify(
+    message_hashes: &[Vec<u8>],
+    signatures: &[Vec<u8>],
+    pubkeys: &[Vec<u8>],
+) -> Result<bool, WasmApiCryptoError> {
+    secp256k1_batch_verify_on_threads(message_hashes, signatures, pubkeys, verification_threads())
+}
+
+fn secp256k1_batch_verify_on_threads(
+    message_hashes: &[Vec<u8>],
+    signatures: &[Vec<u8>],
+    pubkeys: &[Vec<u8>],
+    threads: usize,
+) -> Result<bool, WasmApiCryptoError> {
+    let count = signatures.len();
+    let lengths_match = (message_hashes.len() == count || message_hashes.len() == 1)
+        && (pubkeys.len() == count || pubkeys.len() == 1)
+        && !(message_hashes.len() == 1 && pubkeys.len() == 1 && count != 1);
+
+    if !lengths_match {
+        debug!(
+            "secp256k1_batch_verify() mismatched number of message hashes ({}) / signatures ({}) / public keys ({})",
+            message_hashes.len(),
+            count,
+            pubkeys.len(),
+        );
+
+        // https://github.com/CosmWasm/cosmwasm/blob/v1.0.0-beta5/packages/crypto/src/errors.rs#L97
+        return Err(WasmApiCryptoError::BatchErr);
+    }
+
+    let parse_message = |data: &[u8]| {
+        if data.len() != 32 {
+            // https://github.com/CosmWasm/cosmwasm/blob/v1.0.0-beta5/packages/crypto/src/errors.rs#L93
+            return Err(WasmApiCryptoError::InvalidHashFormat);
+        }
+
+        secp256k1::Message::from_slice(data).map_err(|err| {
+            debug!(
+                "secp256k1_batch_verify() failed to create a secp256k1 message from message_hash: {:?}",
+                err
+            );
+            WasmApiCryptoError::GenericErr
+        })
+    };
+
+    let parse_pubkey = |data: &[u8]| {
+        if !match data.first() {
+            // compressed
+            Some(0x02) | Some(0x03) => data.len() == 33,
+            // uncompressed
+            Some(0x04) => data.len() == 65,
+            // hybrid
+            Some(0x06) | Some(0x07) => data.len() == 65,
+            _ => false,
+        } {
+            // https://github.com/CosmWasm/cosmwasm/blob/v1.0.0-beta5/packages/crypto/src/errors.rs#L95
+            return Err(WasmApiCryptoError::InvalidPubkeyFormat);
+        }
+
+        secp256k1::PublicKey::from_slice(data).map_err(|err| {
+            debug!("secp256k1_batch_verify() malformed pubkey: {:?}", err);
+            WasmApiCryptoError::GenericErr
+        })
+    };
+
+    let mut parsed_messages = message_hashes
+        .iter()
+        .map(|m| parse_message(m))
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut parsed_pubkeys = pubkeys
+        .iter()
+        .map(|p| parse_pubkey(p))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut parsed_signatures = Vec::with_capacity(count);
+    for (i, signature) in signatures.iter().enumerate() {
+        if signature.len() != 64 {
+            // https://github.com/CosmWasm/cosmwasm/blob/v1.0.0-beta5/packages/crypto/src/errors.rs#L94
+            return Err(WasmApiCryptoError::InvalidSignatureFormat);
+        }
+
+        let sig = secp256k1::ecdsa::Signature::from_compact(signature).map_err(|err| {
+            debug!(
+                "secp256k1_batch_verify() malformed signatures[{}]: {:?}",
+                i, err
+            );
+            WasmApiCryptoError::GenericErr
+        })?;
+        parsed_signatures.push(sig);
+    }
+
+    if parsed_messages.len() == 1 {
+        parsed_messages = parsed_messages.repeat(count);
+    }
+    if parsed_pubkeys.len() == 1 {
+        parsed_pubkeys = parsed_pubkeys.repeat(count);
+    }
+
+    let batch: Vec<_> = parsed_messages
+        .into_iter()
+        .zip(parsed_signatures)
+        .zip(parsed_pubkeys)
+        .collect();
+    let results = parallel_verify(batch, threads, |((message, signature), pubkey)| {
+        SECP256K1_VERIFIER.verify_ecdsa(message, signature, pubkey)
+    });
+
+    if let Some(i) = results.iter().position(|result| result.is_err()) {
+        debug!(
+            "secp256k1_batch_verify() failed to verify signatures[{}]: {:?}",
+            i, results[i]
+        );
+        return Ok(false);
+    }
+
+    Ok(true)
+}
+
+fn host_verify_double_sign_evidence(
+    context: &mut Context,
+    instance: &wasm3::Instance<Context>,
+    (votes_ptr, signatures_ptr, public_key_ptr): (i32, i32, i32),
+) -> WasmEngineResult<i64> {
+    let used_gas = context.gas_costs.external_verify_double_sign_evidence as u64;
+    use_gas(instance, used_gas)?;
+
+    let votes = decode_sections_from_memory(instance, votes_ptr as u32)
+        .map_err(debug_err!(err => "verify_double_sign_evidence error while trying to read votes from wasm memory: {err}"))?;
+    let signatures = decode_sections_from_memory(instance, signatures_ptr as u32)
+        .map_err(debug_err!(err => "verify_double_sign_evidence error while trying to read signatures from wasm memory: {err}"))?;
+    let public_key = read_from_memory(instance, public_key_ptr as u32)
+        .map_err(debug_err!(err => "verify_double_sign_evidence error while trying to read public_key from wasm memory: {err}"))?;
+
+    if votes.len() != 2 || signatures.len() != 2 {
+        debug!(
+            "verify_double_sign_evidence() expects exactly 2 votes ({}) and 2 signatures ({})",
+            votes.len(),
+            signatures.len(),
+        );
+
+        // https://github.com/CosmWasm/cosmwasm/blob/v1.0.0-beta5/packages/crypto/src/errors.rs#L97
+        return Ok(to_high_half(WasmApiCryptoError::BatchErr as u32) as i64);
+    }
+
+    match verify_double_sign_evidence(
+        &votes[0],
+        &signatures[0],
+        &votes[1],
+        &signatures[1],
+        &public_key,
+    ) {
+        Err(err) => Ok(to_high_half(err as u32) as i64),
+        Ok(offender) => {
+            let ptr_to_region_in_wasm_vm = write_to_memory(instance, &offender).map_err(|err| {
+                debug!(
+                    "verify_double_sign_evidence() error while trying to allocate and write the answer {:?} to the WASM VM",
+                    &offender,
+                );
+                err
+            })?;
+
+            // Return pointer to the allocated buffer with the value written to it
+            Ok(to_low_half(ptr_to_region_in_wasm_vm) as i64)
+        }
+    }
+}
+
+fn host_verify_jwt(
+    context: &mut Context,
+    instance: &wasm3::Instance<Context>,
+    (token_ptr, jwk_ptr): (i32, i32),
+) -> WasmEngineResult<i64> {
+    let used_gas = context.gas_costs.external_verify_jwt as u64;
+    use_gas(instance, used_gas)?;
+
+    let token = read_from_memory(instance, token_ptr as u32)
+        .map_err(debug_err!(err => "verify_jwt error while trying to read token from wasm memory: {err}"))?;
+    let jwk = read_from_memory(instance, jwk_ptr as u32)
+        .map_err(debug_err!(err => "verify_jwt error while trying to read jwk from wasm memory: {err}"))?;
+
+    match verify_jwt(&token, &jwk, context.timestamp) {
+        Err(err) => Ok(to_high_half(err as u32) as i64),
+        Ok(claims) => {
+            let ptr_to_region_in_wasm_vm = write_to_memory(instance, &claims).map_err(|err| {
+                debug!(
+                    "verify_jwt() error while trying to allocate and write the answer {:?} to the WASM VM",
+                    &claims,
+                );
+                err
+            })?;
+
+            // Return pointer to the allocated buffer with the value written to it
+            Ok(to_low_half(ptr_to_region_in_wasm_vm) as i64)
+        }
+    }
+}
+
+fn host_verify_signed_payload(
+    context: &mut Context,
+    instance: &wasm3::Instance<Context>,
+    (payload_ptr, signature_ptr, pubkey_ptr, max_age, signer_time): (i32, i32, i32, i64, i64),
+) -> WasmEngineResult<i64> {
+    let used_gas = context.gas_costs.external_verify_signed_payload as u64;
+    use_gas(instance, used_gas)?;
+
+    let payload = read_from_memory(instance, payload_ptr as u32)
+        .map_err(debug_err!(err => "verify_signed_payload error while trying to read payload from wasm memory: {err}"))?;
+    let signature = read_from_memory(instance, signature_ptr as u32)
+        .map_err(debug_err!(err => "verify_signed_payload error while trying to read signature from wasm memory: {err}"))?;
+    let pubkey = read_from_memory(instance, pubkey_ptr as u32)
+        .map_err(debug_err!(err => "verify_signed_payload error while trying to read pubkey from wasm memory: {err}"))?;
+
+    match verify_signed_payload(
+        &payload,
+        &signature,
+        &pubkey,
+        max_age as u64,
+        signer_time as u64,
+        context.timestamp,
+    ) {
+        Err(err) => Ok(to_high_half(err as u32) as i64),
+        Ok(payload) => {
+            let ptr_to_region_in_wasm_vm = write_to_memory(instance, &payload).map_err(
+                debug_err!(err => "verify_signed_payload error while trying to write the payload to wasm memory: {err}"),
+            )?;
+
+            Ok(to_low_half(ptr_to_region_in_wasm_vm) as i64)
+        }
+    }
+}
+
+fn host_secp256k1_verify_der(
+    context: &mut Context,
+    instance: &wasm3::Instance<Context>,
+    (message_hash_ptr, signature_ptr, public_key_ptr): (i32, i32, i32),
+) -> WasmEngineResult<i32> {
+    let used_gas = context.gas_costs.external_secp256k1_verify_der as u64;
+    use_gas(instance, used_gas)?;
+
+    let message_hash = read_from_memory(instance, message_hash_ptr as u32)
+        .map_err(debug_err!(err => "secp256k1_verify_der error while trying to read message_hash from wasm memory: {err}"))?;
+    let signature = read_from_memory(instance, signature_ptr as u32)
+        .map_err(debug_err!(err => "secp256k1_verify_der error while trying to read signature from wasm memory: {err}"))?;
+    let public_key = read_from_memory(instance, public_key_ptr as u32)
+        .map_err(debug_err!(err => "secp256k1_verify_der error while trying to read public_key from wasm memory: {err}"))?;
+
+    // same convention as secp256k1_verify: 0 == valid, 1 == invalid, otherwise an error code
+    match secp256k1_verify_der(&message_hash, &signature, &public_key) {
+        Err(err) => Ok(err as i32),
+        Ok(true) => Ok(0),
+        Ok(false) => Ok(1),
+    }
+}
+
+fn host_secp256r1_verify(
+    context: &mut Context,
+    instance: &wasm3::Instance<Context>,
+    (message_hash_ptr, signature_ptr, public_key_ptr): (i32, i32, i32),
+) -> WasmEngineResult<i32> {
+    let used_gas = context.gas_costs.external_secp256r1_verify as u64;
+    use_gas(instance, used_gas)?;
+
+    let message_hash = read_from_memory(instance, message_hash_ptr as u32)
+        .map_err(debug_err!(err => "secp256r1_verify error while trying to read message_hash from wasm memory: {err}"))?;
+    let signature = read_from_memory(instance, signature_ptr as u32)
+        .map_err(debug_err!(err => "secp256r1_verify error while trying to read signature from wasm memory: {err}"))?;
+    let public_key = read_from_memory(instance, public_key_ptr as u32)
+        .map_err(debug_err!(err => "secp256r1_verify error while trying to read public_key from wasm memory: {err}"))?;
+
+    // same convention as secp256k1_verify: 0 == valid, 1 == invalid, otherwise an error code
+    match secp256r1_verify(&message_hash, &signature, &public_key) {
+        Err(err) => Ok(err as i32),
+        Ok(true) => Ok(0),
+        Ok(false) => Ok(1),
+    }
+}
+
+fn host_verify_eth_personal_sign(
+    context: &mut Context,
+    instance: &wasm3::Instance<Context>,
+    (message_ptr, signature_ptr, eth_address_ptr): (i32, i32, i32),
+) -> WasmEngineResult<i32> {
+    let used_gas = context.gas_costs.external_verify_eth_personal_sign as u64;
+    use_gas(instance, used_gas)?;
+
+    let message = read_from_memory(instance, message_ptr as u32)
+        .map_err(debug_err!(err => "verify_eth_personal_sign error while trying to read message from wasm memory: {err}"))?;
+    let signature = read_from_memory(instance, signature_ptr as u32)
+        .map_err(debug_err!(err => "verify_eth_personal_sign error while trying to read signature from wasm memory: {err}"))?;
+    let eth_address = read_from_memory(instance, eth_address_ptr as u32)
+        .map_err(debug_err!(err => "verify_eth_personal_sign error while trying to read eth_address from wasm memory: {err}"))?;
+
+    // same convention as secp256k1_verify: 0 == valid, 1 == invalid, otherwise an error code
+    match verify_eth_personal_sign(&message, &signature, &eth_address) {
+        Err(err) => Ok(err as i32),
+        Ok(true) => Ok(0),
+        Ok(false) => Ok(1),
+    }
+}
 
-                // https://github.com/CosmWasm/cosmwasm/blob/v1.0.0-beta5/packages/crypto/src/errors.rs#L94
-                return Ok(WasmApiCryptoError::InvalidSignatureFormat as i32);
-            }
-        };
+fn host_verify_webauthn(
+    context: &mut Context,
+    instance: &wasm3::Instance<Context>,
+    (auth_data_ptr, client_data_ptr, signature_ptr, public_key_ptr): (i32, i32, i32, i32),
+) -> WasmEngineResult<i32> {
+    let used_gas = context.gas_costs.external_verify_webauthn as u64;
+    use_gas(instance, used_gas)?;
 
-        let pubkey = match ed25519_zebra::VerificationKeyBytes::try_from(pubkeys[i]) {
-            Ok(x) => x,
-            Err(err) => {
-                debug!(
-                        "ed25519_batch_verify() failed to create an ed25519 VerificationKey from public_keys[{}]: {:?}",
-                        i, err
-                    );
+    let authenticator_data = read_from_memory(instance, auth_data_ptr as u32)
+        .map_err(debug_err!(err => "verify_webauthn error while trying to read authenticator_data from wasm memory: {err}"))?;
+    let client_data_json = read_from_memory(instance, client_data_ptr as u32)
+        .map_err(debug_err!(err => "verify_webauthn error while trying to read client_data_json from wasm memory: {err}"))?;
+    let signature = read_from_memory(instance, signature_ptr as u32)
+        .map_err(debug_err!(err => "verify_webauthn error while trying to read signature from wasm memory: {err}"))?;
+    let public_key = read_from_memory(instance, public_key_ptr as u32)
+        .map_err(debug_err!(err => "verify_webauthn error while trying to read public_key from wasm memory: {err}"))?;
 
-                // https://github.com/CosmWasm/cosmwasm/blob/v1.0.0-beta5/packages/crypto/src/errors.rs#L95
-                return Ok(WasmApiCryptoError::InvalidPubkeyFormat as i32);
-            }
-        };
+    // same convention as secp256k1_verify: 0 == valid, 1 == invalid, otherwise an error code
+    match verify_webauthn(
+        &authenticator_data,
+        &client_data_json,
+        &signature,
+        &public_key,
+    ) {
+        Err(err) => Ok(err as i32),
+        Ok(true) => Ok(0),
+        Ok(false) => Ok(1),
+    }
+}
 
-        batch.queue((pubkey, signature, messages[i]));
+fn host_verify_adr036(
+    context: &mut Context,
+    instance: &wasm3::Instance<Context>,
+    (data_ptr, signature_ptr, pubkey_ptr, signer_addr_ptr): (i32, i32, i32, i32),
+) -> WasmEngineResult<i32> {
+    let used_gas = context.gas_costs.external_verify_adr036 as u64;
+    use_gas(instance, used_gas)?;
+
+    let data = read_from_memory(instance, data_ptr as u32)
+        .map_err(debug_err!(err => "verify_adr036 error while trying to read data from wasm memory: {err}"))?;
+    let signature = read_from_memory(instance, signature_ptr as u32)
+        .map_err(debug_err!(err => "verify_adr036 error while trying to read signature from wasm memory: {err}"))?;
+    let pubkey = read_from_memory(instance, pubkey_ptr as u32)
+        .map_err(debug_err!(err => "verify_adr036 error while trying to read pubkey from wasm memory: {err}"))?;
+    let signer_addr = read_from_memory(instance, signer_addr_ptr as u32)
+        .map_err(debug_err!(err => "verify_adr036 error while trying to read signer_addr from wasm memory: {err}"))?;
+
+    // same convention as secp256k1_verify: 0 == valid, 1 == invalid, otherwise an error code
+    match verify_adr036(&data, &signature, &pubkey, &signer_addr) {
+        Err(err) => Ok(err as i32),
+        Ok(true) => Ok(0),
+        Ok(false) => Ok(1),
     }
+}
 
-    // Assaf:
-    // To verify a batch of ed25519 signatures we need to provide an RNG source.
-    // In theory this doesn't have to be deterministic because the same signatures
-    // should produce the same output (true/false) regardless of the RNG being used.
-    // In practice I'm too afraid to do something non-deterministic in concensus code
-    // So I've decided to use a PRNG instead.
-    // For entropy I'm using the entire ed25519 batch verify input data + the gas consumed
-    // up until now in this WASM call. This will be deterministic, but also kinda-random in
-    // different situations. Note that the gas includes every WASM opcode and
-    // every WASM memory allocation up until now.
-    // Secret data from the enclave can also be used here but I'm not sure if that's necessary.
-    // A few more notes:
-    // 1. The vanilla CosmWasm v1 implementation is using RNG from the OS,
-    // meaning that different values are used in differents nodes for the same operation inside
-    // consensus code, but the output should be the same (https://github.com/CosmWasm/cosmwasm/blob/v1.0.0-beta5/packages/crypto/src/ed25519.rs#L108)
-    // 2. In Zcash (zebra) this is also used with RNG from the OS, however Zcash is a PoW chain
-    // and therefore there's no risk of consensus breaking (https://github.com/ZcashFoundation/zebra/blob/00aa5d96a30539a609bfdd17146b223c4e6cf424/tower-batch/tests/ed25519.rs#L72-L83).
-    // 3. In dalek-ed25519 they warn agains using deterministic RNG, as an attacker can derive a falsy signature from the right signature. For me this is an acceptable risk compared to breaking consensus (https://docs.rs/ed25519-dalek/1.0.1/ed25519_dalek/fn.verify_batch.html#on-deterministic-nonces and https://github.com/dalek-cryptography/ed25519-dalek/pull/147).
-    let mut rng_entropy: Vec<u8> = vec![];
-    rng_entropy.append(&mut messages_data.into_iter().flatten().collect());
-    rng_entropy.append(&mut signatures_data.into_iter().flatten().collect());
-    rng_entropy.append(&mut pubkeys_data.into_iter().flatten().collect());
-    let remaining_gas = get_remaining_gas(instance);
-    let used_gas = context.gas_limit.saturating_sub(remaining_gas);
-    rng_entropy.append(&mut used_gas.to_be_bytes().to_vec());
+fn host_verify_legacy_multisig(
+    context: &mut Context,
+    instance: &wasm3::Instance<Context>,
+    (pubkey_ptr, bitarray_ptr, signatures_ptr, msg_ptr): (i32, i32, i32, i32),
+) -> WasmEngineResult<i32> {
+    let base_cost = context.gas_costs.external_verify_legacy_multisig_base as u64;
+    use_gas(instance, base_cost)?;
+
+    let pubkey = read_from_memory(instance, pubkey_ptr as u32)
+        .map_err(debug_err!(err => "verify_legacy_multisig error while trying to read pubkey from wasm memory: {err}"))?;
+    let bitarray = read_from_memory(instance, bitarray_ptr as u32)
+        .map_err(debug_err!(err => "verify_legacy_multisig error while trying to read bitarray from wasm memory: {err}"))?;
+    let signatures = read_from_memory(instance, signatures_ptr as u32)
+        .map_err(debug_err!(err => "verify_legacy_multisig error while trying to read signatures from wasm memory: {err}"))?;
+    let msg = read_from_memory(instance, msg_ptr as u32)
+        .map_err(debug_err!(err => "verify_legacy_multisig error while trying to read msg from wasm memory: {err}"))?;
+
+    let multisig = match LegacyMultisig::parse(&pubkey, &bitarray, &signatures) {
+        Ok(multisig) => multisig,
+        Err(err) => return Ok(err as i32),
+    };
 
-    let rng_seed: [u8; 32] = sha_256(&rng_entropy);
-    let mut rng = ChaChaRng::from_seed(rng_seed);
+    // every member signature is a secp256k1 verification
+    let each_cost = context.gas_costs.external_verify_legacy_multisig_each as u64;
+    use_gas(instance, (multisig.signature_count() as u64) * each_cost)?;
 
-    match batch.verify(&mut rng) {
-        Err(err) => {
-            debug!(
-                "ed25519_batch_verify() failed to verify signatures: {:?}",
-                err
-            );
+    // same convention as secp256k1_verify: 0 == valid, 1 == invalid, otherwise an error code
+    match multisig.verify(&msg) {
+        Err(err) => Ok(err as i32),
+        Ok(true) => Ok(0),
+        Ok(false) => Ok(1),
+    }
+}
 
-            // return 1 == failed, invalid signature
-            // https://github.com/CosmWasm/cosmwasm/blob/v1.0.0-beta5/packages/vm/src/imports.rs#L329
-            Ok(1)
-        }
-        Ok(()) => {
-            // return 0 == success, valid signature
-            // https://github.com/CosmWasm/cosmwasm/blob/v1.0.0-beta5/packages/vm/src/imports.rs#L329
-            Ok(0)
-        }
+fn host_same_session(
+    context: &mut Context,
+    instance: &wasm3::Instance<Context>,
+    (ciphertext_a_ptr, ciphertext_b_ptr): (i32, i32),
+) -> WasmEngineResult<i32> {
+    use_gas(instance, context.gas_costs.external_same_session as u64)?;
+
+    let ciphertext_a = read_from_memory(instance, ciphertext_a_ptr as u32)
+        .map_err(debug_err!(err => "same_session error while trying to read ciphertext_a from wasm memory: {err}"))?;
+    let ciphertext_b = read_from_memory(instance, ciphertext_b_ptr as u32)
+        .map_err(debug_err!(err => "same_session error while trying to read ciphertext_b from wasm memory: {err}"))?;
+
+    // 0 == same session, 1 == different sessions, otherwise an error code
+    match same_session(&ciphertext_a, &ciphertext_b) {
+        Err(err) => Ok(err as i32),
+        Ok(true) => Ok(0),
+        Ok(false) => Ok(1),
     }
 }
 
@@ -1863,6 +3411,57 @@ fn host_ed25519_sign(
     Ok(to_low_half(ptr_to_region_in_wasm_vm) as i64)
 }
 
+fn host_execution_receipt(
+    context: &mut Context,
+    instance: &wasm3::Instance<Context>,
+    output_hash_ptr: i32,
+) -> WasmEngineResult<i64> {
+    let used_gas = context.gas_costs.external_execution_receipt as u64;
+    use_gas(instance, used_gas)?;
+
+    let output_hash_data = read_from_memory(instance, output_hash_ptr as u32).map_err(
+        debug_err!(err => "execution_receipt error while trying to read output_hash from wasm memory: {err}")
+    )?;
+
+    trace!(
+        "execution_receipt() was called from WASM code with output_hash {:x?} (len {:?} should be 32)",
+        &output_hash_data,
+        output_hash_data.len()
+    );
+
+    let output_hash: [u8; HASH_SIZE] = match output_hash_data.as_slice().try_into() {
+        Ok(x) => x,
+        Err(_) => {
+            return Ok(to_high_half(WasmApiCryptoError::InvalidHashFormat as u32) as i64);
+        }
+    };
+
+    let receipt = match generate_execution_receipt(
+        &context.contract_address,
+        &context.input_hash,
+        &output_hash,
+        context.block_height,
+    ) {
+        Ok(x) => x,
+        Err(err) => {
+            debug!("execution_receipt() failed to sign the receipt: {:?}", err);
+
+            return Ok(to_high_half(WasmApiCryptoError::GenericErr as u32) as i64);
+        }
+    };
+
+    let ptr_to_region_in_wasm_vm = write_to_memory(instance, &receipt).map_err(|err| {
+        debug!(
+            "execution_receipt() error while trying to allocate and write the receipt {:?} to the WASM VM",
+            &receipt,
+        );
+        err
+    })?;
+
+    // Return pointer to the allocated buffer with the value written to it
+    Ok(to_low_half(ptr_to_region_in_wasm_vm) as i64)
+}
+
 fn get_encryption_salt(timestamp: u64) -> Vec<u8> {
     let mut encryption_salt: Vec<u8> = vec![];
 
@@ -1881,7 +3480,6 @@ fn host_gas_evaporate(
     instance: &wasm3::Instance<Context>,
     evaporate: i32,
 ) -> WasmEngineResult<i32> {
-    const GAS_MULTIPLIER: u64 = 1000; // (cosmwasm gas : sdk gas)
     let gas_requested = evaporate as u64 * GAS_MULTIPLIER;
 
     use_gas(
@@ -1896,6 +3494,18 @@ fn host_gas_evaporate(
     Ok(0)
 }
 
+/// Sleeping would make execution time depend on the contract rather than on gas, and the
+/// wall-clock isn't something nodes agree on, so contracts can't sleep. `env.block.time` is the
+/// only time contracts get.
+fn host_sleep(
+    _context: &mut Context,
+    _instance: &wasm3::Instance<Context>,
+    ms: i64,
+) -> WasmEngineResult<()> {
+    debug!("contract tried to sleep for {} ms", ms as u64);
+    Err(WasmEngineError::ForbiddenSyscall)
+}
+
 fn host_check_gas_used(
     context: &mut Context,
     instance: &wasm3::Instance<Context>,
@@ -1918,11 +3528,153 @@ fn host_check_gas_used(
     Ok(gas_used as i64)
 }
 
+/// Unlike `check_gas`, which reports sdk gas, this returns the raw WASM gas consumed since
+/// the contract call was entered, including the cost of this call.
+fn host_gas_used_in_call(
+    context: &mut Context,
+    instance: &wasm3::Instance<Context>,
+) -> WasmEngineResult<i64> {
+    let used_gas = context.gas_costs.external_gas_used_in_call as u64;
+    use_gas(instance, used_gas)?;
+
+    let gas_used_in_call = context
+        .gas_limit
+        .saturating_sub(get_remaining_gas(instance));
+
+    debug!(
+        "Reported gas used in call: {:?}, limit: {:?}",
+        gas_used_in_call, context.gas_limit
+    );
+
+    Ok(gas_used_in_call as i64)
+}
+
+/// Writes the result of a fixed-point operation to the WASM VM as a big-endian u64,
+/// or returns the error code in the high half
+fn write_fixed_point_result(
+    instance: &wasm3::Instance<Context>,
+    operation: &str,
+    result: Result<u64, FixedPointError>,
+) -> WasmEngineResult<i64> {
+    match result {
+        Err(err) => {
+            debug!("{}() failed: {:?}", operation, err);
+            Ok(to_high_half(err as u32) as i64)
+        }
+        Ok(value) => {
+            let ptr_to_region_in_wasm_vm = write_to_memory(instance, &value.to_be_bytes())
+                .map_err(|err| {
+                    debug!(
+                        "{}() error while trying to allocate and write the answer {:?} to the WASM VM",
+                        operation, value,
+                    );
+                    err
+                })?;
+
+            // Return pointer to the allocated buffer with the value written to it
+            Ok(to_low_half(ptr_to_region_in_wasm_vm) as i64)
+        }
+    }
+}
+
+fn host_checked_mul_div(
+    context: &mut Context,
+    instance: &wasm3::Instance<Context>,
+    (a, b, c): (i64, i64, i64),
+) -> WasmEngineResult<i64> {
+    let used_gas = context.gas_costs.external_checked_mul_div as u64;
+    use_gas(instance, used_gas)?;
+
+    let result = checked_mul_div(a as u64, b as u64, c as u64);
+    write_fixed_point_result(instance, "checked_mul_div", result)
+}
+
+fn host_sqrt(
+    context: &mut Context,
+    instance: &wasm3::Instance<Context>,
+    (x, decimal_places): (i64, i32),
+) -> WasmEngineResult<i64> {
+    let used_gas = context.gas_costs.external_sqrt as u64;
+    use_gas(instance, used_gas)?;
+
+    let result = sqrt(x as u64, decimal_places as u32);
+    write_fixed_point_result(instance, "sqrt", result)
+}
+
+/// Returns the coins merged by denom, without zero amounts and sorted by denom. See `coins`.
+fn host_normalize_coins(
+    context: &mut Context,
+    instance: &wasm3::Instance<Context>,
+    coins_ptr: i32,
+) -> WasmEngineResult<i64> {
+    let used_gas = context.gas_costs.external_normalize_coins as u64;
+    use_gas(instance, used_gas)?;
+
+    let coins = read_from_memory(instance, coins_ptr as u32)
+        .map_err(debug_err!(err => "normalize_coins error while trying to read coins from wasm memory: {err}"))?;
+
+    match normalize_coins_json(&coins) {
+        Err(err) => Ok(to_high_half(err as u32) as i64),
+        Ok(normalized) => {
+            let ptr_to_region_in_wasm_vm = write_to_memory(instance, &normalized)?;
+
+            // Return pointer to the allocated buffer with the value written to it
+            Ok(to_low_half(ptr_to_region_in_wasm_vm) as i64)
+        }
+    }
+}
+
+/// Aborts the call with `FundsMismatch` unless it was sent exactly the funds in `coins_ptr`, so
+/// contracts can require a multi-denom payment at entry. Returns 0 if the funds match, or an error
+/// code for malformed coins.
+fn host_require_funds(
+    context: &mut Context,
+    instance: &wasm3::Instance<Context>,
+    coins_ptr: i32,
+) -> WasmEngineResult<i32> {
+    use_gas(instance, context.gas_costs.external_require_funds as u64)?;
+
+    let coins = read_from_memory(instance, coins_ptr as u32)
+        .map_err(debug_err!(err => "require_funds error while trying to read coins from wasm memory: {err}"))?;
+
+    let required: Vec<cw_types_v1::coins::Coin> = match serde_json::from_slice(&coins) {
+        Ok(required) => required,
+        Err(err) => {
+            debug!("require_funds() got malformed coins: {:?}", err);
+            return Ok(CoinsError::InvalidCoins as i32);
+        }
+    };
+
+    match funds_match(required, &context.sent_funds) {
+        Err(err) => Ok(err as i32),
+        Ok(true) => Ok(0),
+        Ok(false) => {
+            debug!("require_funds() was sent {:?}", context.sent_funds);
+            Err(WasmEngineError::FundsMismatch)
+        }
+    }
+}
+
 #[cfg(feature = "test")]
 pub mod tests {
-    use super::shuffle_cache;
+    use super::gas::{add_metering, get_remaining_gas, memory_limit_exceeded, set_gas_limit};
+    use super::validation::validate_memory;
+    use super::{
+        check_not_reserved, is_valid_canonical_length, out_of_gas_error, secp256k1_batch_verify,
+        secp256k1_batch_verify_on_threads, shuffle_cache,
+    };
+    use crate::contract_validation::{
+        execution_receipt_public_key, sign_execution_receipt, verify_execution_receipt,
+    };
     use crate::count_failures;
+    use crate::errors::WasmEngineError;
+    use crate::gas::{apply_gas_ceiling, contract_gas_limit, WasmCosts, GAS_MULTIPLIER};
+    use crate::output_log::OUTPUT_LOG_KEY;
     use crate::wasm3::Binary;
+    use enclave_crypto::{sha_256, AESKey, WasmApiCryptoError};
+    use enclave_ffi_types::EnclaveError;
+    use enclave_utils::kv_cache::KvCache;
+    use walrus::{FunctionBuilder, ValType};
 
     pub fn run_tests() {
         println!();
@@ -1930,6 +3682,21 @@ pub mod tests {
 
         count_failures!(failures, {
             cache_shuffle_works();
+            cache_coalesces_writes_into_one_batch();
+            execution_receipt_sign_verify();
+            secp256k1_batch_verify_all_valid();
+            secp256k1_batch_verify_one_invalid();
+            secp256k1_batch_verify_mismatched_lengths();
+            secp256k1_batch_verify_parallel_matches_serial();
+            canonical_address_length_validation();
+            output_log_key_is_reserved();
+            open_ranges_are_capped();
+            contract_gas_ceiling_defaults_to_tx_limit();
+            contract_hits_gas_ceiling_before_tx_limit();
+            contract_gas_ceiling_is_in_sdk_gas();
+            memory_grows_below_limit();
+            memory_grows_up_to_limit();
+            memory_grow_past_limit_traps();
         });
 
         // The test doesn't work for some reason
@@ -1966,4 +3733,285 @@ pub mod tests {
         // Sum should be 0 as we increase and decrease it eventually by the same numbers
         assert_eq!(sum, 0)
     }
+
+    fn cache_coalesces_writes_into_one_batch() {
+        let mut cache = KvCache::new();
+        let mut charged = 0;
+
+        for (key, value) in [
+            (&b"a"[..], &b"1"[..]),
+            (b"b", b"2"),
+            (b"a", b"overwritten"),
+            (b"c", b"3"),
+        ] {
+            charged += cache.write(key, value).1;
+            // reads in between see the last write
+            assert_eq!(cache.read(key), Some(value.to_vec()));
+        }
+        assert_eq!(cache.read(b"a"), Some(b"overwritten".to_vec()));
+
+        // the pseudo gas flush_cache refunds is all the gas the writes were charged
+        assert_eq!(cache.drain_gas_tracker(), charged);
+
+        // flush_cache writes the three keys with a single ocall_multiple_write_db
+        assert_eq!(
+            cache.flush(),
+            vec![
+                (b"a".to_vec(), b"overwritten".to_vec()),
+                (b"b".to_vec(), b"2".to_vec()),
+                (b"c".to_vec(), b"3".to_vec()),
+            ]
+        );
+        assert!(cache.flush().is_empty());
+        assert_eq!(cache.read(b"a"), None);
+    }
+
+    fn execution_receipt_sign_verify() {
+        let secret = AESKey::new_from_slice(&[7u8; 32]);
+        let public_key = execution_receipt_public_key(&secret);
+        let addr: &[u8] = b"contract";
+        let input_hash = [1u8; 32];
+        let output_hash = [2u8; 32];
+
+        let receipt = sign_execution_receipt(&secret, addr, &input_hash, &output_hash, 42);
+        let verify = |addr: &[u8], input: &[u8; 32], output: &[u8; 32], height: u64| {
+            verify_execution_receipt(&public_key, &receipt, addr, input, output, height)
+        };
+
+        assert!(verify(addr, &input_hash, &output_hash, 42));
+
+        // any change to the bound values invalidates the receipt
+        assert!(!verify(b"other", &input_hash, &output_hash, 42));
+        assert!(!verify(addr, &output_hash, &output_hash, 42));
+        assert!(!verify(addr, &input_hash, &input_hash, 42));
+        assert!(!verify(addr, &input_hash, &output_hash, 43));
+
+        // a different secret (e.g. the callback secret) yields a different key
+        let other_secret = AESKey::new_from_slice(&[8u8; 32]);
+        assert_ne!(execution_receipt_public_key(&other_secret), public_key);
+    }
+
+    /// Returns (message_hashes, signatures, public_keys) for `count` distinct signers
+    fn secp256k1_batch(count: u8) -> (Vec<Vec<u8>>, Vec<Vec<u8>>, Vec<Vec<u8>>) {
+        let secp = secp256k1::Secp256k1::new();
+        let mut message_hashes = vec![];
+        let mut signatures = vec![];
+        let mut public_keys = vec![];
+
+        for i in 1..=count {
+            let secret_key = secp256k1::SecretKey::from_slice(&[i; 32]).unwrap();
+            let message_hash = sha_256(&[i]);
+            let message = secp256k1::Message::from_slice(&message_hash).unwrap();
+
+            message_hashes.push(message_hash.to_vec());
+            signatures.push(
+                secp.sign_ecdsa(&message, &secret_key)
+                    .serialize_compact()
+                    .to_vec(),
+            );
+            public_keys.push(
+                secp256k1::PublicKey::from_secret_key(&secp, &secret_key)
+                    .serialize()
+                    .to_vec(),
+            );
+        }
+
+        (message_hashes, signatures, public_keys)
+    }
+
+    fn secp256k1_batch_verify_all_valid() {
+        let (message_hashes, signatures, public_keys) = secp256k1_batch(5);
+        assert!(matches!(
+            secp256k1_batch_verify(&message_hashes, &signatures, &public_keys),
+            Ok(true)
+        ));
+
+        // an empty batch is trivially valid
+        assert!(matches!(secp256k1_batch_verify(&[], &[], &[]), Ok(true)));
+    }
+
+    fn secp256k1_batch_verify_one_invalid() {
+        let (message_hashes, mut signatures, public_keys) = secp256k1_batch(5);
+        signatures.swap(1, 3);
+        assert!(matches!(
+            secp256k1_batch_verify(&message_hashes, &signatures, &public_keys),
+            Ok(false)
+        ));
+
+        let (mut message_hashes, signatures, public_keys) = secp256k1_batch(5);
+        message_hashes[4][0] ^= 1;
+        assert!(matches!(
+            secp256k1_batch_verify(&message_hashes, &signatures, &public_keys),
+            Ok(false)
+        ));
+    }
+
+    fn secp256k1_batch_verify_mismatched_lengths() {
+        let (message_hashes, signatures, public_keys) = secp256k1_batch(3);
+        assert!(matches!(
+            secp256k1_batch_verify(&message_hashes[..2], &signatures, &public_keys),
+            Err(WasmApiCryptoError::BatchErr)
+        ));
+        assert!(matches!(
+            secp256k1_batch_verify(&message_hashes[..1], &signatures, &public_keys[..1]),
+            Err(WasmApiCryptoError::BatchErr)
+        ));
+    }
+
+    fn secp256k1_batch_verify_parallel_matches_serial() {
+        let (message_hashes, signatures, public_keys) = secp256k1_batch(9);
+        let mut swapped_signatures = signatures.clone();
+        swapped_signatures.swap(6, 7);
+
+        for signatures in [&signatures, &swapped_signatures] {
+            let serial =
+                secp256k1_batch_verify_on_threads(&message_hashes, signatures, &public_keys, 1)
+                    .unwrap();
+            // more threads than the enclave allows are capped, and still verify the same
+            for threads in 2..=8 {
+                let parallel = secp256k1_batch_verify_on_threads(
+                    &message_hashes,
+                    signatures,
+                    &public_keys,
+                    threads,
+                );
+                assert_eq!(parallel.unwrap(), serial);
+            }
+        }
+    }
+
+    fn canonical_address_length_validation() {
+        assert!(is_valid_canonical_length(20));
+        assert!(is_valid_canonical_length(32));
+
+        // too short
+        assert!(!is_valid_canonical_length(0));
+        assert!(!is_valid_canonical_length(19));
+        // too long
+        assert!(!is_valid_canonical_length(21));
+        assert!(!is_valid_canonical_length(33));
+        assert!(!is_valid_canonical_length(65));
+    }
+
+    fn output_log_key_is_reserved() {
+        assert!(matches!(
+            check_not_reserved(OUTPUT_LOG_KEY),
+            Err(WasmEngineError::ReservedKey)
+        ));
+        // keys that only share a prefix with it are the contract's
+        assert!(check_not_reserved(&OUTPUT_LOG_KEY[..OUTPUT_LOG_KEY.len() - 1]).is_ok());
+        assert!(check_not_reserved(&[OUTPUT_LOG_KEY, &b"x"[..]].concat()).is_ok());
+    }
+
+    fn open_ranges_are_capped() {
+        assert!(check_open_ranges(0).is_ok());
+        assert!(check_open_ranges(MAX_OPEN_RANGES - 1).is_ok());
+        assert!(matches!(
+            check_open_ranges(MAX_OPEN_RANGES),
+            Err(WasmEngineError::TooManyRanges)
+        ));
+    }
+
+    fn contract_gas_ceiling_defaults_to_tx_limit() {
+        assert_eq!(apply_gas_ceiling(1_000_000, 0), 1_000_000);
+        assert_eq!(apply_gas_ceiling(1_000_000, 5_000_000), 1_000_000);
+
+        // without a binding ceiling, running out of gas is a plain out of gas
+        assert!(matches!(
+            out_of_gas_error(apply_gas_ceiling(1_000_000, 0), 1_000_000),
+            EnclaveError::OutOfGas
+        ));
+        assert!(matches!(
+            out_of_gas_error(apply_gas_ceiling(1_000_000, 1_000_000), 1_000_000),
+            EnclaveError::OutOfGas
+        ));
+    }
+
+    fn contract_hits_gas_ceiling_before_tx_limit() {
+        let tx_gas_limit = 1_000_000;
+        let gas_limit = apply_gas_ceiling(tx_gas_limit, 100_000);
+        assert_eq!(gas_limit, 100_000);
+
+        assert!(matches!(
+            out_of_gas_error(gas_limit, tx_gas_limit),
+            EnclaveError::ContractGasCeilingExceeded
+        ));
+    }
+
+    fn contract_gas_ceiling_is_in_sdk_gas() {
+        assert_eq!(
+            contract_gas_limit(10_000_000, 1_000),
+            1_000 * GAS_MULTIPLIER
+        );
+        assert_eq!(contract_gas_limit(10_000_000, 0), 10_000_000);
+
+        // a ceiling too large to convert is no tighter than the tx gas limit
+        assert_eq!(contract_gas_limit(u64::MAX, u64::MAX), u64::MAX);
+        assert_eq!(contract_gas_limit(10_000_000, u64::MAX), 10_000_000);
+    }
+
+    const TEST_MAX_MEMORY_PAGES: u32 = 4;
+
+    /// Grows the memory of a fresh instance of a metered module with one page of memory by
+    /// `pages`. Returns what memory.grow returned, whether the memory limit was exceeded and the
+    /// gas left.
+    fn grow_memory(pages: u32) -> (Result<i32, wasm3::Error>, bool, u64) {
+        let gas_costs = WasmCosts {
+            max_memory_pages: TEST_MAX_MEMORY_PAGES,
+            ..WasmCosts::default()
+        };
+
+        let mut module = walrus::Module::default();
+        let memory = module.memories.add_local(false, 1, None);
+        module.exports.add("memory", memory);
+        let num_pages = module.locals.add(ValType::I32);
+        let mut grow = FunctionBuilder::new(&mut module.types, &[ValType::I32], &[ValType::I32]);
+        grow.func_body().local_get(num_pages).memory_grow(memory);
+        let grow = grow.finish(vec![num_pages], &mut module.funcs);
+        module.exports.add("grow", grow);
+
+        validate_memory(&mut module, gas_costs.max_memory_pages).unwrap();
+        add_metering(&mut module, &gas_costs);
+        let code = module.emit_wasm();
+
+        let environment = wasm3::Environment::new().unwrap();
+        let runtime = environment
+            .new_runtime::<()>(1024 * 60, Some(gas_costs.max_memory_pages))
+            .unwrap();
+        let module = environment.parse_module(&code).unwrap();
+        let instance = runtime.load_module(module).unwrap();
+        set_gas_limit(&instance, 10_000_000).unwrap();
+
+        let grow = instance.find_function::<u32, i32>("grow").unwrap();
+        let result = grow.call_with_context(&mut (), pages);
+
+        (
+            result,
+            memory_limit_exceeded(&instance),
+            get_remaining_gas(&instance),
+        )
+    }
+
+    fn memory_grows_below_limit() {
+        let (result, exceeded, gas_left) = grow_memory(TEST_MAX_MEMORY_PAGES - 2);
+        // memory.grow returns the previous number of pages
+        assert_eq!(result.unwrap(), 1);
+        assert!(!exceeded);
+        assert_ne!(gas_left, 0);
+    }
+
+    fn memory_grows_up_to_limit() {
+        let (result, exceeded, gas_left) = grow_memory(TEST_MAX_MEMORY_PAGES - 1);
+        assert_eq!(result.unwrap(), 1);
+        assert!(!exceeded);
+        assert_ne!(gas_left, 0);
+    }
+
+    fn memory_grow_past_limit_traps() {
+        let (result, exceeded, gas_left) = grow_memory(TEST_MAX_MEMORY_PAGES);
+        assert!(matches!(result, Err(wasm3::Error::UnreachableExecuted)));
+        assert!(exceeded);
+        // charged as running out of gas
+        assert_eq!(gas_left, 0);
+    }
 }
@@ -20,14 +20,23 @@ pub struct VersionedCode {
     pub code: Vec<u8>,
     pub version: CosmWasmApiVersion,
     pub features: Vec<ContractFeature>,
+    /// Whether the code has floating point operations, which are only allowed in contracts that
+    /// were stored before they were rejected
+    pub has_floats: bool,
 }
 
 impl VersionedCode {
-    pub fn new(code: Vec<u8>, version: CosmWasmApiVersion, features: Vec<ContractFeature>) -> Self {
+    pub fn new(
+        code: Vec<u8>,
+        version: CosmWasmApiVersion,
+        features: Vec<ContractFeature>,
+        has_floats: bool,
+    ) -> Self {
         Self {
             code,
             version,
             features,
+            has_floats,
         }
     }
 }
@@ -53,7 +62,9 @@ pub fn create_module_instance(
     // If the cache is disabled, don't try to use it and just compile the module.
     if cache.cap() == 0 {
         trace!("cache is disabled, building module");
-        return analyze_module(contract_code, gas_costs, operation);
+        let versioned_code = analyze_module(contract_code, gas_costs)?;
+        check_floats(&versioned_code, operation)?;
+        return Ok(versioned_code);
     }
     trace!("cache is enabled");
 
@@ -61,18 +72,21 @@ pub fn create_module_instance(
     let mut code = None;
     let mut api_version = CosmWasmApiVersion::Invalid;
     let mut features = vec![];
+    let mut has_floats = false;
     trace!("peeking in cache");
     let peek_result = cache.peek(&contract_code.hash());
     if let Some(VersionedCode {
         code: cached_code,
         version: cached_ver,
         features: cached_features,
+        has_floats: cached_has_floats,
     }) = peek_result
     {
         trace!("found instance in cache!");
         code = Some(cached_code.clone());
         api_version = *cached_ver;
         features = cached_features.clone();
+        has_floats = *cached_has_floats;
     }
 
     drop(cache); // Release read lock
@@ -80,10 +94,11 @@ pub fn create_module_instance(
     // if we couldn't find the code in the cache, analyze it now
     if code.is_none() {
         trace!("code not found in cache! analyzing now");
-        let versioned_code = analyze_module(contract_code, gas_costs, operation)?;
+        let versioned_code = analyze_module(contract_code, gas_costs)?;
         code = Some(versioned_code.code);
         api_version = versioned_code.version;
         features = versioned_code.features;
+        has_floats = versioned_code.has_floats;
     }
 
     // If we analyzed the code in the previous step, insert it to the LRU cache
@@ -93,7 +108,7 @@ pub fn create_module_instance(
         trace!("storing code in cache");
         cache.put(
             contract_code.hash(),
-            VersionedCode::new(code, api_version, features.clone()),
+            VersionedCode::new(code, api_version, features.clone(), has_floats),
         );
     } else {
         // Touch the cache to update the LRU value
@@ -102,15 +117,34 @@ pub fn create_module_instance(
     }
 
     let code = code.unwrap();
+    let versioned_code = VersionedCode::new(code, api_version, features, has_floats);
+
+    // checked on every call rather than when analyzing, so that whether code is accepted doesn't
+    // depend on what happens to be in this node's cache
+    check_floats(&versioned_code, operation)?;
 
     trace!("returning built instance");
-    Ok(VersionedCode::new(code, api_version, features))
+    Ok(versioned_code)
+}
+
+/// Floating point operations aren't deterministic across hardware, so code that has them can't
+/// be instantiated or migrated to. Contracts that need floats can be built with softfloat, which
+/// replaces them with integer operations.
+fn check_floats(
+    versioned_code: &VersionedCode,
+    operation: ContractOperation,
+) -> Result<(), EnclaveError> {
+    if versioned_code.has_floats && (operation.is_init() || operation.is_migrate()) {
+        debug!("contract was found to contain floating point operations");
+        return Err(EnclaveError::WasmModuleWithFP);
+    }
+
+    Ok(())
 }
 
 pub fn analyze_module(
     contract_code: &ContractCode,
     gas_costs: &WasmCosts,
-    operation: ContractOperation,
 ) -> Result<VersionedCode, EnclaveError> {
     let mut module = walrus::ModuleConfig::new()
         .generate_producers_section(false)
@@ -138,31 +172,31 @@ pub fn analyze_module(
     };
 
     // features
-    let random_enabled = module
-        .exports
-        .iter()
-        .any(|exp| exp.name == features::RANDOM);
-
-    let features = if random_enabled {
-        debug!("Found supported features: random");
-        vec![ContractFeature::Random]
-    } else {
-        vec![]
-    };
+    let mut features = vec![];
+    for (name, feature) in &[
+        (features::RANDOM, ContractFeature::Random),
+        (features::PADDED_OUTPUT, ContractFeature::PaddedOutput),
+    ] {
+        if module.exports.iter().any(|exp| exp.name == *name) {
+            debug!("Found supported feature: {:?}", feature);
+            features.push(*feature);
+        }
+    }
     drop(exports);
 
-    validation::validate_memory(&mut module)?;
+    validation::validate_memory(&mut module, gas_costs.max_memory_pages)?;
 
-    if let ContractOperation::Init = operation {
-        if module.has_floats() {
-            debug!("contract was found to contain floating point operations");
-            return Err(EnclaveError::WasmModuleWithFP);
-        }
-    }
+    // before metering, which doesn't add floats but rewrites the code
+    let has_floats = module.has_floats();
 
     gas::add_metering(&mut module, gas_costs);
 
     let code = module.emit_wasm();
 
-    Ok(VersionedCode::new(code, cosmwasm_api_version, features))
+    Ok(VersionedCode::new(
+        code,
+        cosmwasm_api_version,
+        features,
+        has_floats,
+    ))
 }
@@ -0,0 +1,315 @@
+//! Verification of WebAuthn assertions, backing the `verify_webauthn` host function, so contracts
+//! can authenticate users with passkeys.
+//!
+//! An assertion is an ES256 signature over `authenticator_data ++ sha256(client_data_json)`.
+//! Besides the signature, only what every assertion must satisfy is checked here: that the client
+//! data is of type `webauthn.get` and that the user was present. The challenge, the origin and the
+//! rp id hash are specific to each contract, so contracts check those themselves.
+//!
+//! The public key is either the COSE_Key that the authenticator returned when the credential was
+//! registered, or an uncompressed SEC1 point.
+
+use std::convert::TryFrom;
+
+use log::*;
+use ring::signature::{UnparsedPublicKey, ECDSA_P256_SHA256_ASN1};
+use serde_json::Value;
+
+use enclave_crypto::{sha_256, WasmApiCryptoError};
+
+/// rp id hash (32 bytes) || flags (1 byte) || signature counter (4 bytes) || extensions
+const AUTHENTICATOR_DATA_MIN_SIZE: usize = 37;
+const FLAGS_OFFSET: usize = 32;
+/// UP, set when the user touched the authenticator
+const FLAG_USER_PRESENT: u8 = 0x01;
+
+const ASSERTION_TYPE: &str = "webauthn.get";
+
+/// 0x04 || x (32 bytes) || y (32 bytes)
+const SEC1_UNCOMPRESSED_SIZE: usize = 65;
+const P256_COORDINATE_SIZE: usize = 32;
+
+// COSE_Key labels and values of an ES256 key (RFC 8152)
+const COSE_KTY: i64 = 1;
+const COSE_ALG: i64 = 3;
+const COSE_CRV: i64 = -1;
+const COSE_X: i64 = -2;
+const COSE_Y: i64 = -3;
+const COSE_KTY_EC2: i64 = 2;
+const COSE_ALG_ES256: i64 = -7;
+const COSE_CRV_P256: i64 = 1;
+
+// CBOR major types (RFC 8949)
+const CBOR_UNSIGNED: u8 = 0;
+const CBOR_NEGATIVE: u8 = 1;
+const CBOR_BYTES: u8 = 2;
+const CBOR_TEXT: u8 = 3;
+const CBOR_MAP: u8 = 5;
+
+enum CborValue {
+    Int(i64),
+    Bytes(Vec<u8>),
+    Text,
+}
+
+/// Just enough CBOR to read a COSE_Key: a map of integer labels to integers, bytes or text
+struct CborReader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> CborReader<'a> {
+    fn take(&mut self, len: u64) -> Result<&'a [u8], WasmApiCryptoError> {
+        if (self.data.len() as u64) < len {
+            debug!("cose key ended in the middle of an item");
+            return Err(WasmApiCryptoError::InvalidPubkeyFormat);
+        }
+
+        let (taken, rest) = self.data.split_at(len as usize);
+        self.data = rest;
+        Ok(taken)
+    }
+
+    /// The major type and argument of the next item
+    fn header(&mut self) -> Result<(u8, u64), WasmApiCryptoError> {
+        let initial = self.take(1)?[0];
+        let argument_size = match initial & 0x1f {
+            info @ 0..=23 => return Ok((initial >> 5, info as u64)),
+            24 => 1,
+            25 => 2,
+            26 => 4,
+            27 => 8,
+            // indefinite lengths, which COSE keys don't use
+            info => {
+                debug!("cose key has an unsupported cbor header {}", info);
+                return Err(WasmApiCryptoError::InvalidPubkeyFormat);
+            }
+        };
+
+        let argument = self
+            .take(argument_size)?
+            .iter()
+            .fold(0u64, |acc, byte| (acc << 8) | *byte as u64);
+        Ok((initial >> 5, argument))
+    }
+
+    fn value(&mut self) -> Result<CborValue, WasmApiCryptoError> {
+        let int = |n: u64| i64::try_from(n).map_err(|_| WasmApiCryptoError::InvalidPubkeyFormat);
+
+        match self.header()? {
+            (CBOR_UNSIGNED, n) => Ok(CborValue::Int(int(n)?)),
+            (CBOR_NEGATIVE, n) => Ok(CborValue::Int(-1 - int(n)?)),
+            (CBOR_BYTES, len) => Ok(CborValue::Bytes(self.take(len)?.to_vec())),
+            (CBOR_TEXT, len) => {
+                self.take(len)?;
+                Ok(CborValue::Text)
+            }
+            (major_type, _) => {
+                debug!("cose key has an unsupported cbor type {}", major_type);
+                Err(WasmApiCryptoError::InvalidPubkeyFormat)
+            }
+        }
+    }
+}
+
+/// Converts an ES256 COSE_Key to an uncompressed SEC1 point
+fn cose_key_to_sec1(cose_key: &[u8]) -> Result<Vec<u8>, WasmApiCryptoError> {
+    let mut reader = CborReader { data: cose_key };
+    let pairs = match reader.header()? {
+        (CBOR_MAP, pairs) => pairs,
+        _ => return Err(WasmApiCryptoError::InvalidPubkeyFormat),
+    };
+
+    let (mut kty, mut alg, mut crv, mut x, mut y) = (None, None, None, None, None);
+    for _ in 0..pairs {
+        let label = match reader.value()? {
+            CborValue::Int(label) => label,
+            _ => return Err(WasmApiCryptoError::InvalidPubkeyFormat),
+        };
+
+        match (label, reader.value()?) {
+            (COSE_KTY, CborValue::Int(value)) => kty = Some(value),
+            (COSE_ALG, CborValue::Int(value)) => alg = Some(value),
+            (COSE_CRV, CborValue::Int(value)) => crv = Some(value),
+            (COSE_X, CborValue::Bytes(value)) => x = Some(value),
+            (COSE_Y, CborValue::Bytes(value)) => y = Some(value),
+            (COSE_KTY | COSE_ALG | COSE_CRV | COSE_X | COSE_Y, _) => {
+                debug!("cose key parameter {} has the wrong type", label);
+                return Err(WasmApiCryptoError::InvalidPubkeyFormat);
+            }
+            // other parameters (e.g. kid) don't affect verification
+            _ => {}
+        }
+    }
+    if !reader.data.is_empty() {
+        debug!("cose key has {} trailing bytes", reader.data.len());
+        return Err(WasmApiCryptoError::InvalidPubkeyFormat);
+    }
+
+    if kty != Some(COSE_KTY_EC2)
+        || crv != Some(COSE_CRV_P256)
+        || alg.map_or(false, |alg| alg != COSE_ALG_ES256)
+    {
+        debug!(
+            "cose key is not an ES256 key: {:?} {:?} {:?}",
+            kty, alg, crv
+        );
+        return Err(WasmApiCryptoError::InvalidPubkeyFormat);
+    }
+
+    match (x, y) {
+        (Some(x), Some(y))
+            if x.len() == P256_COORDINATE_SIZE && y.len() == P256_COORDINATE_SIZE =>
+        {
+            let mut pubkey = vec![0x04];
+            pubkey.extend(x);
+            pubkey.extend(y);
+            Ok(pubkey)
+        }
+        _ => Err(WasmApiCryptoError::InvalidPubkeyFormat),
+    }
+}
+
+/// Returns whether `signature` (DER encoded, as authenticators produce it) is a valid assertion
+/// of `authenticator_data` and `client_data_json` by `pubkey`.
+/// Malformed inputs are errors, while a signature that doesn't verify is `Ok(false)`.
+pub fn verify_webauthn(
+    authenticator_data: &[u8],
+    client_data_json: &[u8],
+    signature: &[u8],
+    pubkey: &[u8],
+) -> Result<bool, WasmApiCryptoError> {
+    if authenticator_data.len() < AUTHENTICATOR_DATA_MIN_SIZE {
+        debug!(
+            "authenticator data is too short: {}",
+            authenticator_data.len()
+        );
+        return Err(WasmApiCryptoError::InvalidWebAuthnAssertion);
+    }
+    if authenticator_data[FLAGS_OFFSET] & FLAG_USER_PRESENT == 0 {
+        debug!("authenticator data doesn't have the user present flag");
+        return Err(WasmApiCryptoError::InvalidWebAuthnAssertion);
+    }
+
+    let client_data: Value = serde_json::from_slice(client_data_json).map_err(|err| {
+        debug!("client data is not valid json: {:?}", err);
+        WasmApiCryptoError::InvalidWebAuthnAssertion
+    })?;
+    let assertion_type = client_data.get("type").and_then(Value::as_str);
+    if assertion_type != Some(ASSERTION_TYPE) {
+        debug!("client data has type {:?}", assertion_type);
+        return Err(WasmApiCryptoError::InvalidWebAuthnAssertion);
+    }
+
+    let pubkey = if pubkey.len() == SEC1_UNCOMPRESSED_SIZE && pubkey[0] == 0x04 {
+        pubkey.to_vec()
+    } else {
+        cose_key_to_sec1(pubkey)?
+    };
+
+    let mut signed_data = authenticator_data.to_vec();
+    signed_data.extend_from_slice(&sha_256(client_data_json));
+
+    // ring doesn't tell a malformed signature from a wrong one
+    let pubkey = UnparsedPublicKey::new(&ECDSA_P256_SHA256_ASN1, &pubkey);
+    match pubkey.verify(&signed_data, signature) {
+        Err(_) => {
+            debug!("verify_webauthn() failed to verify signature");
+            Ok(false)
+        }
+        Ok(()) => Ok(true),
+    }
+}
+
+#[cfg(feature = "test")]
+pub mod tests {
+    use super::*;
+
+    // An assertion by a P-256 credential for rp id "localhost", with the user present and verified
+    const AUTHENTICATOR_DATA: &str =
+        "49960de5880e8c687434170f6476605b8fe4aeb9a28632c7995cf3ba831d97630500000001";
+    const CLIENT_DATA_JSON: &str = r#"{"type":"webauthn.get","challenge":"c2VjcmV0IG5ldHdvcmsgd2ViYXV0aG4gY2hhbGxlbmdl","origin":"http://localhost:8080","crossOrigin":false}"#;
+    const SIGNATURE: &str = "3046022100a17f71281a6a9a6fa8abdf7d6d4da7bf9fc8663a15d8c47d59f4a38b9db4d6aa022100aa820985b674d532b9fc1e7cc42a488160f7eaecf2a99faaa11db5ad2809a33b";
+    // The credential's public key, as a COSE_Key and as an uncompressed SEC1 point
+    const COSE_KEY: &str = "a5010203262001215820d347685e8b0d333c08941f9e2760c12139620bfde7db865653547dc4f4e528c4225820d0da0543fd0f11cc85bd0e26d1f721c921607e12c499be77d401f9936605c458";
+    const SEC1_KEY: &str = "04d347685e8b0d333c08941f9e2760c12139620bfde7db865653547dc4f4e528c4d0da0543fd0f11cc85bd0e26d1f721c921607e12c499be77d401f9936605c458";
+
+    fn verify(
+        authenticator_data: &[u8],
+        client_data_json: &[u8],
+        pubkey: &[u8],
+    ) -> Result<bool, WasmApiCryptoError> {
+        let signature = hex::decode(SIGNATURE).unwrap();
+        verify_webauthn(authenticator_data, client_data_json, &signature, pubkey)
+    }
+
+    pub fn test_verify_webauthn_valid_assertion() {
+        let authenticator_data = hex::decode(AUTHENTICATOR_DATA).unwrap();
+        let client_data_json = CLIENT_DATA_JSON.as_bytes();
+
+        for key in [COSE_KEY, SEC1_KEY] {
+            let pubkey = hex::decode(key).unwrap();
+            assert!(verify(&authenticator_data, client_data_json, &pubkey).unwrap());
+        }
+    }
+
+    pub fn test_verify_webauthn_rejects_tampering() {
+        let authenticator_data = hex::decode(AUTHENTICATOR_DATA).unwrap();
+        let client_data_json = CLIENT_DATA_JSON.as_bytes();
+        let pubkey = hex::decode(COSE_KEY).unwrap();
+
+        // a replayed assertion with a bumped signature counter
+        let mut bumped = authenticator_data.clone();
+        bumped[36] += 1;
+        assert!(!verify(&bumped, client_data_json, &pubkey).unwrap());
+
+        let other_challenge = CLIENT_DATA_JSON.replace("c2Vj", "b3Ro");
+        assert!(!verify(&authenticator_data, other_challenge.as_bytes(), &pubkey).unwrap());
+
+        let mut other_key = hex::decode(SEC1_KEY).unwrap();
+        other_key[64] ^= 1;
+        assert!(!verify(&authenticator_data, client_data_json, &other_key).unwrap());
+    }
+
+    pub fn test_verify_webauthn_malformed_inputs() {
+        let authenticator_data = hex::decode(AUTHENTICATOR_DATA).unwrap();
+        let client_data_json = CLIENT_DATA_JSON.as_bytes();
+        let pubkey = hex::decode(COSE_KEY).unwrap();
+
+        assert!(matches!(
+            verify(&authenticator_data[..36], client_data_json, &pubkey),
+            Err(WasmApiCryptoError::InvalidWebAuthnAssertion)
+        ));
+
+        let mut user_absent = authenticator_data.clone();
+        user_absent[FLAGS_OFFSET] &= !FLAG_USER_PRESENT;
+        assert!(matches!(
+            verify(&user_absent, client_data_json, &pubkey),
+            Err(WasmApiCryptoError::InvalidWebAuthnAssertion)
+        ));
+
+        // a registration's client data can't pass for an assertion's
+        let registration = CLIENT_DATA_JSON.replace("webauthn.get", "webauthn.create");
+        assert!(matches!(
+            verify(&authenticator_data, registration.as_bytes(), &pubkey),
+            Err(WasmApiCryptoError::InvalidWebAuthnAssertion)
+        ));
+
+        assert!(matches!(
+            verify(
+                &authenticator_data,
+                client_data_json,
+                &pubkey[..pubkey.len() - 1]
+            ),
+            Err(WasmApiCryptoError::InvalidPubkeyFormat)
+        ));
+
+        // an EdDSA COSE_Key (kty OKP, alg -8)
+        let mut eddsa_key = pubkey;
+        eddsa_key[2] = 0x01;
+        eddsa_key[4] = 0x27;
+        assert!(matches!(
+            verify(&authenticator_data, client_data_json, &eddsa_key),
+            Err(WasmApiCryptoError::InvalidPubkeyFormat)
+        ));
+    }
+}
@@ -8,7 +8,7 @@ use cw_types_v010::types as v010types;
 use cw_types_v010::types::{Env as V010Env, HumanAddr};
 use cw_types_v1::types::Env as V1Env;
 use cw_types_v1::types::MessageInfo as V1MessageInfo;
-use cw_types_v1::types::{self as v1types, Addr};
+use cw_types_v1::types::{self as v1types, Addr, AttestationType, EnclaveInfo};
 use enclave_ffi_types::EnclaveError;
 
 pub const CONTRACT_KEY_LENGTH: usize = 64;
@@ -29,6 +29,7 @@ pub enum CosmWasmApiVersion {
 #[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq)]
 pub enum ContractFeature {
     Random,
+    PaddedOutput,
 }
 
 pub type BaseAddr = HumanAddr;
@@ -146,6 +147,52 @@ impl BaseEnv {
         )
     }
 
+    /// The index of the transaction in its block, `None` outside of transactions
+    pub fn get_tx_index(&self) -> Option<u32> {
+        self.0
+            .transaction
+            .as_ref()
+            .map(|transaction| transaction.index)
+    }
+
+    /// Empty outside of transactions
+    pub fn get_min_gas_price(&self) -> &[v010types::DecCoin] {
+        self.0
+            .transaction
+            .as_ref()
+            .map_or(&[], |transaction| &transaction.min_gas_price)
+    }
+
+    /// Outputs aren't compressed if the chain didn't set the param
+    pub fn get_output_compression(&self) -> v010types::OutputCompression {
+        self.0.output_compression.clone().unwrap_or_default()
+    }
+
+    /// `None` if the chain doesn't limit submessages
+    pub fn get_submsg_limit(&self) -> Option<v010types::SubmsgLimit> {
+        self.0.submsg_limit.clone()
+    }
+
+    /// 0 if the chain doesn't cap the gas of contract calls
+    pub fn get_contract_gas_ceiling(&self) -> u64 {
+        self.0.contract_gas_ceiling.unwrap_or(0)
+    }
+
+    /// 0 if the chain didn't set the query depth, which keeps the enclave's default
+    pub fn get_max_query_depth(&self) -> u32 {
+        self.0.max_query_depth.unwrap_or(0)
+    }
+
+    /// 0 if the chain doesn't limit the length of event types
+    pub fn get_max_event_type_length(&self) -> u32 {
+        self.0.max_event_type_length.unwrap_or(0)
+    }
+
+    /// `None` if the chain didn't pass it
+    pub fn get_next_instance_id(&self) -> Option<u64> {
+        self.0.next_instance_id
+    }
+
     pub fn into_versioned_env(self, api_version: &CosmWasmApiVersion) -> CwEnv {
         match api_version {
             CosmWasmApiVersion::V010 => self.into_v010(),
@@ -187,6 +234,12 @@ impl BaseEnv {
                 contract_key: None,
                 contract_code_hash: self.0.contract_code_hash,
                 transaction: None,
+                output_compression: None,
+                submsg_limit: None,
+                contract_gas_ceiling: None,
+                max_query_depth: None,
+                max_event_type_length: None,
+                next_instance_id: None,
             },
         }
     }
@@ -209,6 +262,7 @@ impl BaseEnv {
                     code_hash: self.0.contract_code_hash,
                 },
                 transaction: self.0.transaction,
+                enclave: None,
             },
             msg_info: v1types::MessageInfo {
                 sender: v1types::Addr::unchecked(self.0.message.sender.0),
@@ -254,6 +308,31 @@ impl CwEnv {
         }
     }
 
+    /// v0.10 contracts have no place for this in their env, so it's only set for v1
+    pub fn set_enclave_info(&mut self, attestation_type: Option<AttestationType>) {
+        match self {
+            CwEnv::V010Env { .. } => {}
+            CwEnv::V1Env { env, .. } => {
+                env.enclave =
+                    attestation_type.map(|attestation_type| EnclaveInfo { attestation_type });
+            }
+        }
+    }
+
+    /// Sets `env.transaction.msg_index` and `msg_count`, dropping whatever the host sent. v0.10
+    /// contracts get no transaction info, so it's only set for v1
+    pub fn set_tx_msg_position(&mut self, msg_index: Option<u32>, msg_count: Option<u32>) {
+        match self {
+            CwEnv::V010Env { .. } => {}
+            CwEnv::V1Env { env, .. } => {
+                if let Some(transaction) = env.transaction.as_mut() {
+                    transaction.msg_index = msg_index;
+                    transaction.msg_count = msg_count;
+                }
+            }
+        }
+    }
+
     #[cfg(feature = "random")]
     pub fn set_random(&mut self, random: Option<Binary>) {
         match self {
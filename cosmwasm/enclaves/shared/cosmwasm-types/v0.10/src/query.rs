@@ -69,6 +69,14 @@ pub enum BankQuery {
     AllBalances { address: HumanAddr },
 }
 
+/// AllBalanceResponse is the expected response to BankQuery::AllBalances
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub struct AllBalanceResponse {
+    /// Returns all non-zero coins held by this account.
+    pub amount: Vec<Coin>,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 #[serde(rename_all = "snake_case")]
 pub enum GovQuery {
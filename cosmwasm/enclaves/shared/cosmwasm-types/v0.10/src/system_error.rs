@@ -23,7 +23,41 @@ pub enum SystemError {
     NoSuchContract { addr: HumanAddr },
     Unknown {},
     UnsupportedRequest { kind: String },
-    ExceededRecursionLimit {},
+    ExceededRecursionLimit {
+        /// The contract that tried to query past the limit. This is omitted when not set,
+        /// so the serialized error stays `{"exceeded_recursion_limit":{}}` for older callers.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        contract_addr: Option<HumanAddr>,
+    },
+}
+
+impl std::fmt::Display for SystemError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SystemError::InvalidRequest { error, request } => write!(
+                f,
+                "Cannot parse request: {} in: {}",
+                error,
+                String::from_utf8_lossy(request.as_slice())
+            ),
+            SystemError::InvalidResponse { error, response } => write!(
+                f,
+                "Cannot parse response: {} in: {}",
+                error,
+                String::from_utf8_lossy(response.as_slice())
+            ),
+            SystemError::NoSuchContract { addr } => write!(f, "No such contract: {}", addr),
+            SystemError::Unknown {} => write!(f, "Unknown system error"),
+            SystemError::UnsupportedRequest { kind } => {
+                write!(f, "Unsupported query type: {}", kind)
+            }
+            // The address is appended so that matching on the message prefix keeps working
+            SystemError::ExceededRecursionLimit { contract_addr } => match contract_addr {
+                Some(addr) => write!(f, "Query recursion limit exceeded (contract: {})", addr),
+                None => write!(f, "Query recursion limit exceeded"),
+            },
+        }
+    }
 }
 
 pub type SystemResult<T> = Result<T, SystemError>;
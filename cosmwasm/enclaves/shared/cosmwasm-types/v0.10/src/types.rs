@@ -123,6 +123,59 @@ pub struct Env {
     pub contract_code_hash: String,
     #[serde(default)]
     pub transaction: Option<TransactionInfo>,
+    /// How the outputs of the call are compressed, which governance sets. Only read by the
+    /// enclave, contracts never see it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_compression: Option<OutputCompression>,
+    /// How many submessages the transaction may still send, which governance limits. Only read
+    /// by the enclave, contracts never see it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub submsg_limit: Option<SubmsgLimit>,
+    /// The most (sdk) gas a single contract call may use, which governance sets. Only read by the
+    /// enclave, contracts never see it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub contract_gas_ceiling: Option<u64>,
+    /// How deep queries between contracts can nest, which governance sets. Only read by the
+    /// enclave, contracts never see it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_query_depth: Option<u32>,
+    /// The longest type in bytes a contract's custom events may have, which governance sets.
+    /// Only read by the enclave, contracts never see it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_event_type_length: Option<u32>,
+    /// The instance id the chain gives to the next contract instantiated on it. Only read by the
+    /// enclave, contracts never see it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_instance_id: Option<u64>,
+}
+
+/// The compression of contract outputs before they're encrypted. Outputs are part of consensus,
+/// so this is a chain param rather than a node setting, and it's the same for every call in a
+/// block.
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq)]
+pub struct OutputCompression {
+    /// The output format, 0 leaves outputs as they are
+    pub version: u32,
+    /// Outputs shorter than this many bytes aren't compressed
+    pub threshold: u64,
+}
+
+/// The limit on the submessages a transaction sends, counting the submessages of every contract
+/// response in it. The count is kept by the chain, as a response's submessages only reach the
+/// enclave once the contract returns.
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq)]
+pub struct SubmsgLimit {
+    /// The most submessages a transaction may send
+    pub max: u32,
+    /// The submessages the transaction sent before this call
+    pub emitted: u32,
+}
+
+impl SubmsgLimit {
+    /// How many submessages the response of this call may still contain
+    pub fn remaining(&self) -> u32 {
+        self.max.saturating_sub(self.emitted)
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
@@ -139,6 +192,26 @@ pub struct TransactionInfo {
     /// hash = sha256(tx_bytes)
     #[serde(default)]
     pub hash: String,
+    /// The chain's minimum gas price, which governance sets. It's the same for every transaction
+    /// in a block.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub min_gas_price: Vec<DecCoin>,
+    /// The position of the message the contract was called with among the transaction's
+    /// messages. Set by the enclave from the signed transaction, never by the host, and only when
+    /// the contract is called by one of the transaction's messages rather than by a contract.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub msg_index: Option<u32>,
+    /// The number of messages in the transaction, set along with `msg_index`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub msg_count: Option<u32>,
+}
+
+/// A coin with a decimal amount, like gas prices
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq)]
+pub struct DecCoin {
+    pub denom: String,
+    /// A decimal number, e.g. "0.0125"
+    pub amount: String,
 }
 
 #[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq)]
@@ -379,6 +452,14 @@ pub struct LogAttribute {
     #[serde(default = "bool_true")]
     #[serde(skip_serializing)]
     pub encrypted: bool,
+    /// Also emit a plaintext copy of an encrypted attribute, right before the encrypted one.
+    /// This is for hybrid transparency: e.g. a contract can make a key like "action" indexable
+    /// by explorers and event subscriptions, while the full attribute still reaches the sender
+    /// encrypted alongside its other private attributes. Ignored for plaintext attributes.
+    /// Only used in deserialization, like `encrypted`.
+    #[serde(default)]
+    #[serde(skip_serializing)]
+    pub plaintext_copy: bool,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
@@ -408,6 +489,7 @@ pub fn log<K: ToString, V: ToString>(key: K, value: V) -> LogAttribute {
         key: key.to_string(),
         value: value.to_string(),
         encrypted: true,
+        plaintext_copy: false,
     }
 }
 
@@ -417,5 +499,6 @@ pub fn plaintext_log<K: ToString, V: ToString>(key: K, value: V) -> LogAttribute
         key: key.to_string(),
         value: value.to_string(),
         encrypted: false,
+        plaintext_copy: false,
     }
 }
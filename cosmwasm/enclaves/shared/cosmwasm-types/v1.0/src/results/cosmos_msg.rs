@@ -91,6 +91,17 @@ pub enum IbcMsg {
 }
 
 pub const REPLY_ENCRYPTION_MAGIC_BYTES: &[u8] = b"REPLY01";
+/// Like `REPLY_ENCRYPTION_MAGIC_BYTES`, for the reply headers of submessages that are only replied
+/// to on success or only on error. All reply headers start with magic bytes of the same length.
+pub const REPLY_ON_SUCCESS_ENCRYPTION_MAGIC_BYTES: &[u8] = b"REPLYS1";
+pub const REPLY_ON_ERROR_ENCRYPTION_MAGIC_BYTES: &[u8] = b"REPLYE1";
+/// Prefixes the gas limit (a big-endian u64, in sdk gas) that a submessage was sent with, so that
+/// the callee can enforce it no matter what gas the host gives it
+pub const SUBMSG_GAS_LIMIT_MAGIC_BYTES: &[u8] = b"GASLIM01";
+/// Prefixes the reply depth (a big-endian u32) of a submessage's callee, or of a reply handler,
+/// when it isn't 0. The depth counts the contracts up the call stack that wait for a reply, whether
+/// or not their reply headers were propagated.
+pub const REPLY_DEPTH_MAGIC_BYTES: &[u8] = b"RDEPTH01";
 
 /// The message types of the staking module.
 ///
@@ -86,6 +86,17 @@ where
     pub events: Vec<Event>,
     /// The binary payload to include in the response.
     pub data: Option<Binary>,
+    /// Nonstandard, emit `data` in plaintext instead of encrypting it, for contracts that return
+    /// public data (e.g. NFT metadata or price feeds). A caller's `reply` gets it as is too.
+    /// Only serialized when set, since it's passed up to the Go layers to build that reply.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub plaintext_data: bool,
+    /// Nonstandard, sort `attributes`, and the attributes of each of `events`, by key and then by
+    /// value before they're encrypted. For contracts that build attributes from a `HashMap`,
+    /// whose order can differ between nodes. Otherwise attributes keep the order they were
+    /// added in. Never serialized, the Go layers don't need it.
+    #[serde(default, skip_serializing)]
+    pub deterministic_attributes: bool,
 }
 
 impl<T> Default for Response<T>
@@ -98,6 +109,8 @@ where
             attributes: vec![],
             events: vec![],
             data: None,
+            plaintext_data: false,
+            deterministic_attributes: false,
         }
     }
 }
@@ -3,7 +3,10 @@ use std::fmt;
 
 use cw_types_v010::encoding::Binary;
 
-use super::{CosmosMsg, Empty, Event};
+use super::{
+    CosmosMsg, Empty, Event, REPLY_ENCRYPTION_MAGIC_BYTES, REPLY_ON_ERROR_ENCRYPTION_MAGIC_BYTES,
+    REPLY_ON_SUCCESS_ENCRYPTION_MAGIC_BYTES,
+};
 
 /// Use this to define when the contract gets a response callback.
 /// If you only need it for errors or success you can select just those in order
@@ -21,6 +24,68 @@ pub enum ReplyOn {
     Never,
 }
 
+impl ReplyOn {
+    /// The magic bytes that start the reply header of a submessage replied to on `self`. `Never`
+    /// has no reply header.
+    pub fn reply_header_magic_bytes(&self) -> Option<&'static [u8]> {
+        match self {
+            ReplyOn::Always => Some(REPLY_ENCRYPTION_MAGIC_BYTES),
+            ReplyOn::Success => Some(REPLY_ON_SUCCESS_ENCRYPTION_MAGIC_BYTES),
+            ReplyOn::Error => Some(REPLY_ON_ERROR_ENCRYPTION_MAGIC_BYTES),
+            ReplyOn::Never => None,
+        }
+    }
+
+    /// The `ReplyOn` of the reply header `msg` starts with, if it starts with one
+    pub fn from_reply_header(msg: &[u8]) -> Option<Self> {
+        [ReplyOn::Always, ReplyOn::Success, ReplyOn::Error]
+            .iter()
+            .find(|reply_on| {
+                reply_on
+                    .reply_header_magic_bytes()
+                    .map_or(false, |magic_bytes| msg.starts_with(magic_bytes))
+            })
+            .cloned()
+    }
+
+    /// Whether a submessage replied to on `self` gets a reply for a successful (`is_ok`) or failed
+    /// result
+    pub fn replies_to(&self, is_ok: bool) -> bool {
+        match self {
+            ReplyOn::Always => true,
+            ReplyOn::Success => is_ok,
+            ReplyOn::Error => !is_ok,
+            ReplyOn::Never => false,
+        }
+    }
+}
+
+/// Which part of the reply chain is attached to a Wasm submessage.
+///
+/// The reply chain is how the enclave routes a result back up through nested submessages: every
+/// contract that is waiting for a reply adds a header (its code hash and the encrypted
+/// submessage id), and a callee's output is encrypted along with the chain it received.
+///
+/// Security: dropping the callers' part of the chain means the callee and its replies can't be
+/// routed past this contract, so a `reply` triggered by this submessage won't be returned as the
+/// reply to this contract's own caller. It also keeps the callee from learning anything about the
+/// calls above this contract beyond what it's explicitly sent. Only use `Immediate` for
+/// submessages whose results don't need to reach further than this contract.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReplyPropagation {
+    /// Attach this contract's reply header followed by the whole chain of callers above it
+    All,
+    /// Attach only this contract's reply header (if it expects a reply)
+    Immediate,
+}
+
+impl Default for ReplyPropagation {
+    fn default() -> Self {
+        ReplyPropagation::All
+    }
+}
+
 fn bool_false() -> bool {
     false
 }
@@ -47,6 +112,10 @@ where
     // Plaintext replies will be encrypted only if the original message was.
     #[serde(default = "bool_false")]
     pub was_msg_encrypted: bool,
+    /// Set by the contract to limit the reply chain attached to this submessage. Only used
+    /// inside the enclave, so it isn't passed on to the chain.
+    #[serde(default, skip_serializing)]
+    pub reply_propagation: ReplyPropagation,
 }
 
 /// The information we get back from a successful sub message execution,
@@ -76,6 +145,19 @@ pub struct Reply {
     pub result: SubMsgResult,
     pub was_orig_msg_encrypted: bool,
     pub is_encrypted: bool,
+    /// Whether the data of an encrypted reply was emitted in plaintext, see
+    /// `Response::plaintext_data`. The callback signature covers it, so a node can't flip it.
+    /// Only serialized when set, so other replies are signed the same as before it existed.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub is_data_plaintext: bool,
+    /// The height of the block the reply was created in, and the index of its transaction in the
+    /// block. The callback signature covers them, so it can't be replayed in another transaction.
+    /// The enclave sets them from its own env when it signs an encrypted reply and again when it
+    /// verifies one, whatever the host sent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub block_height: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tx_index: Option<u32>,
 }
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct DecryptedReply {
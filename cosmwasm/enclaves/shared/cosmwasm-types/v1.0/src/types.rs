@@ -11,6 +11,9 @@ pub struct Env {
     pub block: BlockInfo,
     pub contract: ContractInfo,
     pub transaction: Option<TransactionInfo>,
+    /// Set by the enclave itself, never by the host, and only for queries
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enclave: Option<EnclaveInfo>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
@@ -93,6 +96,22 @@ pub struct MessageInfo {
     pub funds: Vec<Coin>,
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct EnclaveInfo {
+    pub attestation_type: AttestationType,
+}
+
+/// How the node running the contract registered with the network. Nodes registered differently
+/// would disagree on txs that depend on it, so only queries see it.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AttestationType {
+    SgxEpid,
+    SgxDcap,
+    /// Intel TDX, which nodes can't register with yet, see `EndorsedTdxAttestationReport`
+    IntelTdx,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct ContractInfo {
     pub address: Addr,
@@ -31,6 +31,33 @@ impl SIVEncryptable for AESKey {
     }
 }
 
+/// Encrypts many plaintexts with the same key, setting up the cipher once for all of them instead
+/// of once per plaintext. The ciphertexts are the same as the ones `encrypt_siv` returns.
+pub struct SivBatchEncryptor {
+    cipher: Aes128Siv,
+}
+
+impl SivBatchEncryptor {
+    pub fn new(key: &AESKey) -> Self {
+        Self {
+            cipher: Aes128Siv::new(GenericArray::clone_from_slice(key.get())),
+        }
+    }
+
+    pub fn encrypt(
+        &mut self,
+        plaintext: &[u8],
+        ad: Option<&[&[u8]]>,
+    ) -> Result<Vec<u8>, CryptoError> {
+        let ad = ad.unwrap_or(&[&[]]);
+
+        self.cipher.encrypt(ad, plaintext).map_err(|e| {
+            warn!("aes_siv_encrypt error: {:?}", e);
+            CryptoError::EncryptionError
+        })
+    }
+}
+
 fn aes_siv_encrypt(
     plaintext: &[u8],
     ad: Option<&[&[u8]]>,
@@ -146,6 +146,8 @@ pub const RANDOMNESS_ENCRYPTION_KEY_SECRET_DERIVE_ORDER: u32 = 5;
 pub const INITIAL_RANDOMNESS_SEED_SECRET_DERIVE_ORDER: u32 = 6;
 pub const ADMIN_PROOF_SECRET_DERIVE_ORDER: u32 = 7;
 pub const CONTRACT_KEY_PROOF_SECRET_DERIVE_ORDER: u32 = 8;
+pub const EXECUTION_RECEIPT_SECRET_DERIVE_ORDER: u32 = 9;
+pub const STATE_SNAPSHOT_SECRET_DERIVE_ORDER: u32 = 10;
 
 pub const ENCRYPTED_KEY_MAGIC_BYTES: &[u8; 6] = b"secret";
 pub const CONSENSUS_SEED_VERSION: u16 = 2;
@@ -42,4 +42,16 @@ pub enum WasmApiCryptoError {
     BatchErr = 7,
     GenericErr = 10,
     InvalidPrivateKeyFormat = 1000, // Assaf: 1000 to not collide with CosmWasm someday
+    InvalidEvidence = 1001,
+    InvalidJwt = 1002,
+    JwtVerificationFailed = 1003,
+    JwtExpired = 1004,
+    JwtNotYetValid = 1005,
+    StaleFeed = 1006,
+    BadSignature = 1007,
+    InvalidVerifyingKey = 1008,
+    InvalidProofFormat = 1009,
+    InvalidPublicInputs = 1010,
+    InvalidCiphertext = 1011,
+    InvalidWebAuthnAssertion = 1012,
 }
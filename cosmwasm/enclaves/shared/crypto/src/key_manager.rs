@@ -40,6 +40,8 @@ pub struct Keychain {
     registration_key: Option<KeyPair>,
     admin_proof_secret: Option<AESKey>,
     contract_key_proof_secret: Option<AESKey>,
+    execution_receipt_secret: Option<AESKey>,
+    state_snapshot_secret: Option<AESKey>,
 }
 
 #[derive(Clone, Copy, Default)]
@@ -93,6 +95,8 @@ impl Keychain {
             random_encryption_key: None,
             admin_proof_secret: None,
             contract_key_proof_secret: None,
+            execution_receipt_secret: None,
+            state_snapshot_secret: None,
         };
 
         let _ = x.generate_consensus_master_keys();
@@ -214,6 +218,20 @@ impl Keychain {
         })
     }
 
+    pub fn get_execution_receipt_secret(&self) -> Result<AESKey, CryptoError> {
+        self.execution_receipt_secret.ok_or_else(|| {
+            error!("Error accessing execution_receipt_secret (does not exist, or was not initialized)");
+            CryptoError::ParsingError
+        })
+    }
+
+    pub fn get_state_snapshot_secret(&self) -> Result<AESKey, CryptoError> {
+        self.state_snapshot_secret.ok_or_else(|| {
+            error!("Error accessing state_snapshot_secret (does not exist, or was not initialized)");
+            CryptoError::ParsingError
+        })
+    }
+
     pub fn reseal_registration_key(&mut self) -> Result<(), EnclaveError> {
         match Self::unseal_registration_key() {
             Some(kp) => {
@@ -479,6 +497,32 @@ impl Keychain {
             hex::encode(contract_key_proof_secret.get())
         );
 
+        let execution_receipt_secret = self
+            .consensus_seed
+            .unwrap()
+            .current
+            .derive_key_from_this(&EXECUTION_RECEIPT_SECRET_DERIVE_ORDER.to_be_bytes());
+
+        self.execution_receipt_secret = Some(execution_receipt_secret);
+
+        trace!(
+            "execution_receipt_secret: {:?}",
+            hex::encode(execution_receipt_secret.get())
+        );
+
+        let state_snapshot_secret = self
+            .consensus_seed
+            .unwrap()
+            .current
+            .derive_key_from_this(&STATE_SNAPSHOT_SECRET_DERIVE_ORDER.to_be_bytes());
+
+        self.state_snapshot_secret = Some(state_snapshot_secret);
+
+        trace!(
+            "state_snapshot_secret: {:?}",
+            hex::encode(state_snapshot_secret.get())
+        );
+
         Ok(())
     }
 
@@ -27,6 +27,7 @@ mod rng;
 
 pub mod hash;
 
+pub use aes_siv::SivBatchEncryptor;
 pub use errors::{CryptoError, WasmApiCryptoError};
 pub use key_manager::Keychain;
 pub use key_manager::KEY_MANAGER;
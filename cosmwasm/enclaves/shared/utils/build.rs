@@ -0,0 +1,35 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+// Generates ENCLAVE_TCS_NUM from the TCSNum the execute enclave is signed with, so that the
+// number of verification threads in `parallel` follows the enclave's configuration
+fn main() {
+    // the execute Makefile picks the config the same way
+    let config = if env::var_os("CARGO_FEATURE_PRODUCTION").is_some() {
+        "Enclave.config.prod.xml"
+    } else {
+        "Enclave.config.xml"
+    };
+    let mut config_path = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+    config_path.push("../../execute");
+    config_path.push(config);
+    println!("cargo:rerun-if-changed={}", config_path.display());
+
+    let config = fs::read_to_string(&config_path)
+        .unwrap_or_else(|err| panic!("couldn't read {:?}: {}", config_path, err));
+    let tcs_num: usize = config
+        .split("<TCSNum>")
+        .nth(1)
+        .and_then(|rest| rest.split("</TCSNum>").next())
+        .and_then(|tcs_num| tcs_num.trim().parse().ok())
+        .unwrap_or_else(|| panic!("couldn't find TCSNum in {:?}", config_path));
+
+    let mut out_path = PathBuf::from(env::var("OUT_DIR").unwrap());
+    out_path.push("tcs_num.rs");
+    fs::write(
+        &out_path,
+        format!("const ENCLAVE_TCS_NUM: usize = {};\n", tcs_num),
+    )
+    .unwrap_or_else(|err| panic!("couldn't write {:?}: {}", out_path, err));
+}
@@ -42,6 +42,11 @@ impl KvCache {
         }
     }
 
+    /// The writes that weren't flushed to chain state yet
+    pub fn writes(&self) -> impl Iterator<Item = (&Vec<u8>, &Vec<u8>)> {
+        self.writeable_cache.iter()
+    }
+
     pub fn remove(&mut self, key: &[u8]) {
         self.writeable_cache.remove(key);
         self.readable_cache.remove(key);
@@ -11,6 +11,7 @@ pub mod kv_cache;
 pub mod logger;
 pub mod macros;
 pub mod oom_handler;
+pub mod parallel;
 pub mod pointers;
 pub mod recursion_depth;
 mod results;
@@ -0,0 +1,73 @@
+//! Parallel verification of batches across enclave threads.
+//!
+//! The node configures how many threads verify a batch (see `RuntimeConfiguration`), bounded by
+//! `MAX_VERIFICATION_THREADS` since every spawned thread takes one of the enclave's TCSs. Each
+//! item of a batch is verified on its own and the results are returned in the order of the batch,
+//! so they never depend on the number of threads. If a thread can't be spawned, its share of the
+//! batch is verified serially by the calling thread.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+use log::*;
+
+// `ENCLAVE_TCS_NUM`, the `TCSNum` in Enclave.config.xml, as read by build.rs
+include!(concat!(env!("OUT_DIR"), "/tcs_num.rs"));
+
+/// Verification threads take TCSs from concurrent ecalls (e.g. queries), so they only get half
+pub const MAX_VERIFICATION_THREADS: usize = ENCLAVE_TCS_NUM / 2;
+
+static VERIFICATION_THREADS: AtomicUsize = AtomicUsize::new(1);
+
+/// Sets how many threads verify a batch, including the calling one. 0 or 1 verify serially.
+pub fn configure_verification_threads(threads: u32) {
+    let threads = (threads as usize).max(1).min(MAX_VERIFICATION_THREADS);
+    VERIFICATION_THREADS.store(threads, Ordering::Relaxed);
+}
+
+pub fn verification_threads() -> usize {
+    VERIFICATION_THREADS.load(Ordering::Relaxed)
+}
+
+/// Applies `verify` to every item of `items` on up to `threads` threads, and returns the results
+/// in the order of `items`
+pub fn parallel_verify<T, R>(items: Vec<T>, threads: usize, verify: fn(&T) -> R) -> Vec<R>
+where
+    T: Send + Sync + 'static,
+    R: Send + 'static,
+{
+    let threads = threads.min(MAX_VERIFICATION_THREADS).min(items.len());
+    if threads <= 1 {
+        return items.iter().map(verify).collect();
+    }
+
+    // contiguous chunks, the first of which is verified by the calling thread
+    let items = Arc::new(items);
+    let chunk_size = (items.len() + threads - 1) / threads;
+    let chunk = |start: usize| start..(start + chunk_size).min(items.len());
+
+    let workers: Vec<_> = (chunk_size..items.len())
+        .step_by(chunk_size)
+        .map(|start| {
+            let range = chunk(start);
+            let worker_items = items.clone();
+            let worker = thread::Builder::new()
+                .spawn(move || worker_items[range].iter().map(verify).collect::<Vec<R>>())
+                .map_err(|err| warn!("failed to spawn a verification thread: {:?}", err))
+                .ok();
+            (start, worker)
+        })
+        .collect();
+
+    let mut results: Vec<R> = items[chunk(0)].iter().map(verify).collect();
+    for (start, worker) in workers {
+        match worker.map(|worker| worker.join()) {
+            Some(Ok(worker_results)) => results.extend(worker_results),
+            // the thread wasn't spawned or panicked, so its chunk is verified here instead
+            _ => results.extend(items[chunk(start)].iter().map(verify)),
+        }
+    }
+
+    results
+}
@@ -2,7 +2,21 @@
 
 // use enclave_ffi_types::EnclaveError;
 
-const RECURSION_LIMIT: u32 = 10;
+/// How deep queries can nest, counting the query that starts the chain, unless the chain sets
+/// another limit
+pub const DEFAULT_RECURSION_LIMIT: u32 = 10;
+/// Every nested query runs another contract on the same enclave thread, so deeper nesting risks
+/// exhausting its stack
+pub const MAX_RECURSION_LIMIT: u32 = 20;
+
+/// The limit for the chain's `max_query_depth` param. 0 means the param isn't set and keeps the
+/// default, and the enclave never goes above `MAX_RECURSION_LIMIT` whatever the chain says.
+pub fn recursion_limit(max_query_depth: u32) -> u32 {
+    match max_query_depth {
+        0 => DEFAULT_RECURSION_LIMIT,
+        limit => limit.min(MAX_RECURSION_LIMIT),
+    }
+}
 
 // thread_local! {
 //     /// This counter tracks the recursion depth of queries,
@@ -30,8 +44,8 @@ const RECURSION_LIMIT: u32 = 10;
 // }
 
 /// Returns whether or not this is the last possible level of recursion
-pub fn limit_reached(query_depth: u32) -> bool {
-    query_depth >= RECURSION_LIMIT
+pub fn limit_reached(query_depth: u32, max_query_depth: u32) -> bool {
+    query_depth >= recursion_limit(max_query_depth)
 }
 
 // pub struct RecursionGuard {
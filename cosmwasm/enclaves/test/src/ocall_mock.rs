@@ -133,11 +133,64 @@ pub extern "C" fn ocall_read_db(
     unimplemented!()
 }
 
+#[no_mangle]
+pub extern "C" fn ocall_multiple_read_db(
+    _context: Ctx,
+    _vm_error: *mut UntrustedVmError,
+    _gas_used: *mut u64,
+    _values: *mut EnclaveBuffer,
+    _keys: *const u8,
+    _keys_len: usize,
+) -> OcallReturn {
+    unimplemented!()
+}
+
+#[no_mangle]
+pub extern "C" fn ocall_db_scan(
+    _context: Ctx,
+    _vm_error: *mut UntrustedVmError,
+    _gas_used: *mut u64,
+    _iterator_id: *mut u32,
+    _start: *const u8,
+    _start_len: usize,
+    _end: *const u8,
+    _end_len: usize,
+    _order: i32,
+) -> OcallReturn {
+    unimplemented!()
+}
+
+#[no_mangle]
+pub extern "C" fn ocall_db_next(
+    _context: Ctx,
+    _vm_error: *mut UntrustedVmError,
+    _gas_used: *mut u64,
+    _iterator_id: u32,
+    _key: *mut EnclaveBuffer,
+    _value: *mut EnclaveBuffer,
+) -> OcallReturn {
+    unimplemented!()
+}
+
 #[no_mangle]
 pub extern "C" fn ocall_allocate(_buffer: *const u8, _length: usize) -> UserSpaceBuffer {
     unimplemented!()
 }
 
+#[no_mangle]
+pub extern "C" fn ocall_allocate_chunked(_total_length: usize) -> UserSpaceBuffer {
+    unimplemented!()
+}
+
+#[no_mangle]
+pub extern "C" fn ocall_append_chunk(
+    _buffer: UserSpaceBuffer,
+    _chunk: *const u8,
+    _chunk_len: usize,
+) -> OcallReturn {
+    unimplemented!()
+}
+
 #[no_mangle]
 pub extern "C" fn ocall_get_quote_ecdsa_params(
     ret_val: *mut sgx_status_t,
@@ -7,9 +7,13 @@ use log::*;
 use sgx_types::*;
 use sgx_types::{sgx_status_t, SgxResult};
 
-use enclave_ffi_types::{NodeAuthResult, OUTPUT_ENCRYPTED_SEED_SIZE, SINGLE_ENCRYPTED_SEED_SIZE};
+use enclave_ffi_types::{
+    EnclaveError, NodeAuthResult, StateSnapshotResult, OUTPUT_ENCRYPTED_SEED_SIZE,
+    SINGLE_ENCRYPTED_SEED_SIZE,
+};
 
 use crate::enclave::ENCLAVE_DOORBELL;
+use crate::wasmi::recover_buffer;
 
 extern "C" {
     pub fn ecall_get_attestation_report(
@@ -25,6 +29,7 @@ extern "C" {
         cert: *const u8,
         cert_len: u32,
         seed: &mut [u8; OUTPUT_ENCRYPTED_SEED_SIZE as usize],
+        downgrade: &mut NodeAuthResult,
     ) -> sgx_status_t;
     pub fn ecall_get_genesis_seed(
         eid: sgx_enclave_id_t,
@@ -33,6 +38,44 @@ extern "C" {
         pk_len: u32,
         seed: &mut [u8; SINGLE_ENCRYPTED_SEED_SIZE as usize],
     ) -> sgx_status_t;
+    pub fn ecall_import_seed(
+        eid: sgx_enclave_id_t,
+        retval: *mut NodeAuthResult,
+        encrypted_seed: *const u8,
+        encrypted_seed_len: u32,
+        sender_attestation: *const u8,
+        sender_attestation_len: u32,
+        force: u8,
+    ) -> sgx_status_t;
+    pub fn ecall_verify_attestation_expecting(
+        eid: sgx_enclave_id_t,
+        retval: *mut NodeAuthResult,
+        attestation: *const u8,
+        attestation_len: u32,
+        expected_key: &[u8; 32],
+    ) -> sgx_status_t;
+    pub fn ecall_export_contract_state(
+        eid: sgx_enclave_id_t,
+        retval: *mut StateSnapshotResult,
+        contract_address: *const u8,
+        contract_address_len: u32,
+        contract_key: &[u8; 64],
+        entries: *const u8,
+        entries_len: u32,
+        importer_attestation: *const u8,
+        importer_attestation_len: u32,
+    ) -> sgx_status_t;
+    pub fn ecall_import_contract_state(
+        eid: sgx_enclave_id_t,
+        retval: *mut StateSnapshotResult,
+        snapshot: *const u8,
+        snapshot_len: u32,
+        contract_address: *const u8,
+        contract_address_len: u32,
+        contract_key: &[u8; 64],
+        exporter_attestation: *const u8,
+        exporter_attestation_len: u32,
+    ) -> sgx_status_t;
 }
 
 #[no_mangle]
@@ -189,9 +232,14 @@ pub fn create_attestation_report_u(api_key: &[u8], flags: u32) -> SgxResult<()>
     Ok(())
 }
 
+/// The genesis and current seeds, encrypted for a registering node
+pub type EncryptedSeed = [u8; OUTPUT_ENCRYPTED_SEED_SIZE as usize];
+
+/// Returns the seed encrypted for the node `cert` attests to, and why the node was admitted with a
+/// vulnerable platform, if it was
 pub fn untrusted_get_encrypted_seed(
     cert: &[u8],
-) -> SgxResult<Result<[u8; OUTPUT_ENCRYPTED_SEED_SIZE as usize], NodeAuthResult>> {
+) -> SgxResult<Result<(EncryptedSeed, Option<NodeAuthResult>), NodeAuthResult>> {
     // Bind the token to a local variable to ensure its
     // destructor runs in the end of the function
     let enclave_access_token = ENCLAVE_DOORBELL
@@ -202,6 +250,7 @@ pub fn untrusted_get_encrypted_seed(
     let mut retval = NodeAuthResult::Success;
 
     let mut seed = [0u8; OUTPUT_ENCRYPTED_SEED_SIZE as usize];
+    let mut downgrade = NodeAuthResult::Success;
     let status = unsafe {
         ecall_authenticate_new_node(
             eid,
@@ -209,6 +258,7 @@ pub fn untrusted_get_encrypted_seed(
             cert.as_ptr(),
             cert.len() as u32,
             &mut seed,
+            &mut downgrade,
         )
     };
 
@@ -229,7 +279,186 @@ pub fn untrusted_get_encrypted_seed(
         return Err(sgx_status_t::SGX_ERROR_UNEXPECTED);
     }
 
-    Ok(Ok(seed))
+    if downgrade == NodeAuthResult::Success {
+        Ok(Ok((seed, None)))
+    } else {
+        warn!(
+            "Node was admitted with a vulnerable platform: {}",
+            downgrade
+        );
+        Ok(Ok((seed, Some(downgrade))))
+    }
+}
+
+/// Imports a seed that an existing node encrypted for this node, after verifying the sender's
+/// attestation inside the enclave. A seed the node already has is only replaced if `force` is set.
+pub fn untrusted_import_seed(
+    encrypted_seed: &[u8],
+    sender_attestation: &[u8],
+    force: bool,
+) -> SgxResult<Result<(), NodeAuthResult>> {
+    // Bind the token to a local variable to ensure its
+    // destructor runs in the end of the function
+    let enclave_access_token = ENCLAVE_DOORBELL
+        .get_access(1) // This can never be recursive
+        .ok_or(sgx_status_t::SGX_ERROR_BUSY)?;
+    let enclave = (*enclave_access_token)?;
+    let eid = enclave.geteid();
+    let mut retval = NodeAuthResult::Success;
+
+    let status = unsafe {
+        ecall_import_seed(
+            eid,
+            &mut retval,
+            encrypted_seed.as_ptr(),
+            encrypted_seed.len() as u32,
+            sender_attestation.as_ptr(),
+            sender_attestation.len() as u32,
+            force as u8,
+        )
+    };
+
+    if status != sgx_status_t::SGX_SUCCESS {
+        debug!("Error from import seed");
+        return Err(status);
+    }
+
+    if retval != NodeAuthResult::Success {
+        debug!("Error from import seed, bad NodeAuthResult");
+        return Ok(Err(retval));
+    }
+
+    Ok(Ok(()))
+}
+
+/// Verifies a peer's attestation inside the enclave, and that it attests to `expected_key`.
+/// A valid attestation of a different node key is `NodeAuthResult::NodeKeyMismatch`.
+pub fn untrusted_verify_attestation_expecting(
+    attestation: &[u8],
+    expected_key: &[u8; 32],
+) -> SgxResult<Result<(), NodeAuthResult>> {
+    // Bind the token to a local variable to ensure its
+    // destructor runs in the end of the function
+    let enclave_access_token = ENCLAVE_DOORBELL
+        .get_access(1) // This can never be recursive
+        .ok_or(sgx_status_t::SGX_ERROR_BUSY)?;
+    let enclave = (*enclave_access_token)?;
+    let eid = enclave.geteid();
+    let mut retval = NodeAuthResult::Success;
+
+    let status = unsafe {
+        ecall_verify_attestation_expecting(
+            eid,
+            &mut retval,
+            attestation.as_ptr(),
+            attestation.len() as u32,
+            expected_key,
+        )
+    };
+
+    if status != sgx_status_t::SGX_SUCCESS {
+        debug!("Error from verify attestation");
+        return Err(status);
+    }
+
+    if retval != NodeAuthResult::Success {
+        debug!("Error from verify attestation, bad NodeAuthResult");
+        return Ok(Err(retval));
+    }
+
+    Ok(Ok(()))
+}
+
+fn state_snapshot_result_to_result(result: StateSnapshotResult) -> Result<Vec<u8>, EnclaveError> {
+    match result {
+        StateSnapshotResult::Success { output } => {
+            Ok(unsafe { recover_buffer(output) }.unwrap_or_else(Vec::new))
+        }
+        StateSnapshotResult::Failure { err } => Err(err),
+    }
+}
+
+/// Exports the raw state `entries` of a contract, sealed for the peer whose attestation is
+/// `importer_attestation`. The entries are JSON encoded as a list of `[key, value]` byte arrays.
+pub fn untrusted_export_contract_state(
+    contract_address: &[u8],
+    contract_key: &[u8; 64],
+    entries: &[u8],
+    importer_attestation: &[u8],
+) -> SgxResult<Result<Vec<u8>, EnclaveError>> {
+    // Bind the token to a local variable to ensure its
+    // destructor runs in the end of the function
+    let enclave_access_token = ENCLAVE_DOORBELL
+        .get_access(1) // This can never be recursive
+        .ok_or(sgx_status_t::SGX_ERROR_BUSY)?;
+    let enclave = (*enclave_access_token)?;
+    let eid = enclave.geteid();
+    let mut retval = StateSnapshotResult::Failure {
+        err: EnclaveError::Unknown,
+    };
+
+    let status = unsafe {
+        ecall_export_contract_state(
+            eid,
+            &mut retval,
+            contract_address.as_ptr(),
+            contract_address.len() as u32,
+            contract_key,
+            entries.as_ptr(),
+            entries.len() as u32,
+            importer_attestation.as_ptr(),
+            importer_attestation.len() as u32,
+        )
+    };
+
+    if status != sgx_status_t::SGX_SUCCESS {
+        debug!("Error from export contract state");
+        return Err(status);
+    }
+
+    Ok(state_snapshot_result_to_result(retval))
+}
+
+/// Opens a snapshot exported for this node by the peer whose attestation is
+/// `exporter_attestation`, and returns the contract's entries in the same encoding as
+/// [untrusted_export_contract_state] takes them
+pub fn untrusted_import_contract_state(
+    snapshot: &[u8],
+    contract_address: &[u8],
+    contract_key: &[u8; 64],
+    exporter_attestation: &[u8],
+) -> SgxResult<Result<Vec<u8>, EnclaveError>> {
+    // Bind the token to a local variable to ensure its
+    // destructor runs in the end of the function
+    let enclave_access_token = ENCLAVE_DOORBELL
+        .get_access(1) // This can never be recursive
+        .ok_or(sgx_status_t::SGX_ERROR_BUSY)?;
+    let enclave = (*enclave_access_token)?;
+    let eid = enclave.geteid();
+    let mut retval = StateSnapshotResult::Failure {
+        err: EnclaveError::Unknown,
+    };
+
+    let status = unsafe {
+        ecall_import_contract_state(
+            eid,
+            &mut retval,
+            snapshot.as_ptr(),
+            snapshot.len() as u32,
+            contract_address.as_ptr(),
+            contract_address.len() as u32,
+            contract_key,
+            exporter_attestation.as_ptr(),
+            exporter_attestation.len() as u32,
+        )
+    };
+
+    if status != sgx_status_t::SGX_SUCCESS {
+        debug!("Error from import contract state");
+        return Err(status);
+    }
+
+    Ok(state_snapshot_result_to_result(retval))
 }
 
 pub fn untrusted_get_encrypted_genesis_seed(
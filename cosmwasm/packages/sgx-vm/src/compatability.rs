@@ -33,8 +33,20 @@ const SUPPORTED_IMPORTS_V010: &[&str] = &[
 /// This should be updated when new imports are added
 const SUPPORTED_IMPORTS_V1: &[&str] = &[
     "env.db_read",
+    "env.db_read_multiple",
     "env.db_write",
     "env.db_remove",
+    "env.storage_push",
+    "env.storage_pop",
+    "env.storage_len",
+    "env.storage_map_insert",
+    "env.storage_map_remove",
+    "env.storage_map_get",
+    "env.storage_map_len",
+    "env.storage_map_entry",
+    "env.storage_secure_remove",
+    "env.storage_cas",
+    "env.storage_wipe_prefix",
     "env.addr_validate",
     "env.addr_canonicalize",
     "env.addr_humanize",
@@ -43,7 +55,19 @@ const SUPPORTED_IMPORTS_V1: &[&str] = &[
     "env.secp256k1_sign",
     "env.ed25519_verify",
     "env.ed25519_batch_verify",
+    "env.secp256k1_batch_verify",
+    "env.secp256k1_verify_der",
+    "env.secp256r1_verify",
+    "env.bls12_381_aggregate_verify",
+    "env.groth16_verify",
+    "env.verify_double_sign_evidence",
+    "env.verify_jwt",
+    "env.verify_signed_payload",
+    "env.checked_mul_div",
+    "env.sqrt",
     "env.ed25519_sign",
+    // linked only to trap, contracts can't sleep
+    "env.sleep",
     "env.debug",
     "env.query_chain",
     #[cfg(feature = "iterator")]
@@ -51,7 +75,26 @@ const SUPPORTED_IMPORTS_V1: &[&str] = &[
     #[cfg(feature = "iterator")]
     "env.db_next",
     "env.gas_evaporate",
-    "env.check_gas"
+    "env.check_gas",
+    "env.gas_used_in_call",
+    "env.contract_all_balances",
+    "env.enclave_block_view",
+    "env.derive_id",
+    "env.derive_nonce",
+    "env.last_output_hash",
+    "env.verify_eth_personal_sign",
+    "env.verify_webauthn",
+    "env.verify_adr036",
+    "env.verify_legacy_multisig",
+    "env.same_session",
+    "env.normalize_coins",
+    "env.require_funds",
+    "env.parse_ibc_denom",
+    "env.remaining_submsg_budget",
+    "env.min_gas_price",
+    "env.tx_msg_position",
+    "env.predict_instantiate_address",
+    "env.execution_receipt",
 ];
 
 /// Lists all entry points we expect to be present when calling a v0.10 contract.
@@ -22,12 +22,16 @@ extern "C" {
 
 pub struct EnclaveRuntimeConfig {
     pub module_cache_size: u32,
+    pub query_result_inline_threshold: u32,
+    pub verification_threads: u32,
 }
 
 impl EnclaveRuntimeConfig {
     fn to_ffi_type(&self) -> RuntimeConfiguration {
         RuntimeConfiguration {
             module_cache_size: self.module_cache_size,
+            query_result_inline_threshold: self.query_result_inline_threshold,
+            verification_threads: self.verification_threads,
         }
     }
 }
@@ -51,11 +51,15 @@ pub use crate::traits::StorageIterator;
 
 // Secret Network specific exports
 pub use crate::attestation::{
-    create_attestation_report_u, untrusted_get_encrypted_genesis_seed, untrusted_get_encrypted_seed,
+    create_attestation_report_u, untrusted_export_contract_state,
+    untrusted_get_encrypted_genesis_seed, untrusted_get_encrypted_seed,
+    untrusted_import_contract_state, untrusted_import_seed, untrusted_verify_attestation_expecting,
 };
 pub use crate::seed::{
-    untrusted_health_check, untrusted_init_bootstrap, untrusted_init_node, untrusted_key_gen,
-    untrusted_migrate_sealing,
+    untrusted_get_execution_receipt_public_key, untrusted_health_check, untrusted_init_bootstrap,
+    untrusted_init_node, untrusted_key_gen, untrusted_migrate_sealing,
 };
 
-pub use crate::random::untrusted_submit_block_signatures;
+pub use crate::random::{
+    untrusted_submit_block_signatures, untrusted_verify_header_chain, HeaderChainVerdict,
+};
@@ -22,6 +22,14 @@ extern "C" {
         // in_next_validator_set: *const u8,
         // in_next_validator_set_len: u32,
     ) -> sgx_status_t;
+
+    pub fn ecall_verify_header_chain(
+        eid: sgx_enclave_id_t,
+        retval: *mut sgx_status_t,
+        in_chain: *const u8,
+        in_chain_len: u32,
+        trusted_height: &mut u64,
+    ) -> sgx_status_t;
 }
 
 pub fn untrusted_submit_block_signatures(
@@ -104,3 +112,54 @@ fn submit_block_signature_impl(
 
     Ok((retval, random_decrypted, status))
 }
+
+/// The outcome of `untrusted_verify_header_chain`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeaderChainVerdict {
+    /// Every header verified, and this is the height of the last one
+    Trusted(u64),
+    /// The header at `height` didn't verify, so neither it nor the headers after it are trusted
+    Broken { height: u64, status: sgx_status_t },
+}
+
+/// Verifies a chain of headers in one ecall. Each link is a length-prefixed proto `SignedHeader`
+/// followed by a length-prefixed proto `ValidatorSet`, with 4 byte big-endian lengths, and the
+/// first header has to be signed by the validator set the enclave currently trusts.
+///
+/// Nothing calls this yet: go-cosmwasm doesn't export it, so the chain can't reach it until it
+/// gets a binding there.
+pub fn untrusted_verify_header_chain(chain: &[u8]) -> SgxResult<HeaderChainVerdict> {
+    // Bind the token to a local variable to ensure its
+    // destructor runs in the end of the function
+    let enclave_access_token = ENCLAVE_DOORBELL
+        .get_access(1) // This can never be recursive
+        .ok_or(sgx_status_t::SGX_ERROR_BUSY)?;
+    let enclave = (*enclave_access_token)?;
+
+    let mut retval = sgx_status_t::SGX_SUCCESS;
+    let mut height = 0u64;
+
+    let status = unsafe {
+        ecall_verify_header_chain(
+            enclave.geteid(),
+            &mut retval,
+            chain.as_ptr(),
+            chain.len() as u32,
+            &mut height,
+        )
+    };
+
+    if status != sgx_status_t::SGX_SUCCESS {
+        return Err(status);
+    }
+
+    if retval != sgx_status_t::SGX_SUCCESS {
+        warn!("Header chain is broken at height {}: {:?}", height, retval);
+        return Ok(HeaderChainVerdict::Broken {
+            height,
+            status: retval,
+        });
+    }
+
+    Ok(HeaderChainVerdict::Trusted(height))
+}
@@ -43,6 +43,12 @@ extern "C" {
         eid: sgx_enclave_id_t,
         retval: *mut HealthCheckResult,
     ) -> sgx_status_t;
+
+    pub fn ecall_get_execution_receipt_public_key(
+        eid: sgx_enclave_id_t,
+        retval: *mut sgx_status_t,
+        public_key: &mut [u8; 32],
+    ) -> sgx_status_t;
 }
 
 pub fn untrusted_health_check() -> SgxResult<HealthCheckResult> {
@@ -69,6 +75,34 @@ pub fn untrusted_health_check() -> SgxResult<HealthCheckResult> {
     Ok(ret)
 }
 
+/// The public key that verifies the receipts of the `execution_receipt` host function, which is
+/// the same on every node
+pub fn untrusted_get_execution_receipt_public_key() -> SgxResult<[u8; 32]> {
+    // Bind the token to a local variable to ensure its
+    // destructor runs in the end of the function
+    let enclave_access_token = ENCLAVE_DOORBELL
+        .get_access(1) // This can never be recursive
+        .ok_or(sgx_status_t::SGX_ERROR_BUSY)?;
+    let enclave = (*enclave_access_token)?;
+
+    let eid = enclave.geteid();
+    let mut retval = sgx_status_t::SGX_SUCCESS;
+    let mut public_key = [0u8; 32];
+
+    let status =
+        unsafe { ecall_get_execution_receipt_public_key(eid, &mut retval, &mut public_key) };
+
+    if status != sgx_status_t::SGX_SUCCESS {
+        return Err(status);
+    }
+
+    if retval != sgx_status_t::SGX_SUCCESS {
+        return Err(retval);
+    }
+
+    Ok(public_key)
+}
+
 pub fn untrusted_init_node(
     master_key: &[u8],
     encrypted_seed: &[u8],
@@ -4,8 +4,15 @@ use sgx_types::SgxResult;
 
 use enclave_ffi_types::{Ctx, EnclaveBuffer, OcallReturn, UntrustedVmError, UserSpaceBuffer};
 
+#[cfg(feature = "iterator")]
+use std::convert::TryFrom;
+
+#[cfg(feature = "iterator")]
+use cosmwasm_std::Order;
 use cosmwasm_std::{Binary, StdResult, SystemResult};
 
+#[cfg(feature = "iterator")]
+use crate::context::{add_iterator, with_iterator_from_context};
 use crate::context::{with_querier_from_context, with_storage_from_context};
 use crate::{Querier, Storage, VmError, VmResult};
 
@@ -25,6 +32,47 @@ fn ocall_allocate_impl(buffer: *const u8, length: usize) -> UserSpaceBuffer {
     }
 }
 
+#[no_mangle]
+pub extern "C" fn ocall_allocate_chunked(total_length: usize) -> UserSpaceBuffer {
+    ocall_allocate_chunked_impl(total_length)
+}
+
+/// Allocate an empty buffer that the enclave fills using `ocall_append_chunk`.
+/// This is used for outputs that are too large to copy out in a single `ocall_allocate`.
+/// The returned pointer is recovered with `recover_buffer`, same as for `ocall_allocate`.
+fn ocall_allocate_chunked_impl(total_length: usize) -> UserSpaceBuffer {
+    let boxed_vector = Box::new(Vec::<u8>::with_capacity(total_length));
+    let heap_pointer = Box::into_raw(boxed_vector);
+    UserSpaceBuffer {
+        ptr: heap_pointer as *mut c_void,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn ocall_append_chunk(
+    buffer: UserSpaceBuffer,
+    chunk: *const u8,
+    chunk_len: usize,
+) -> OcallReturn {
+    ocall_append_chunk_impl(buffer, chunk, chunk_len)
+}
+
+/// Copy a chunk from the enclave memory space to the end of a buffer
+/// that was returned by `ocall_allocate_chunked`.
+fn ocall_append_chunk_impl(
+    buffer: UserSpaceBuffer,
+    chunk: *const u8,
+    chunk_len: usize,
+) -> OcallReturn {
+    if buffer.ptr.is_null() {
+        return OcallReturn::Failure;
+    }
+    let vector = unsafe { &mut *(buffer.ptr as *mut Vec<u8>) };
+    let chunk = unsafe { std::slice::from_raw_parts(chunk, chunk_len) };
+    vector.extend_from_slice(chunk);
+    OcallReturn::Success
+}
+
 /// Take a pointer as returned by `ocall_allocate` and recover the Vec<u8> inside of it.
 pub unsafe fn recover_buffer(ptr: UserSpaceBuffer) -> Option<Vec<u8>> {
     if ptr.ptr.is_null() {
@@ -97,6 +145,226 @@ fn ocall_read_db_concrete(
         .unwrap_or(OcallReturn::Panic)
 }
 
+#[no_mangle]
+pub extern "C" fn ocall_multiple_read_db(
+    context: Ctx,
+    vm_error: *mut UntrustedVmError,
+    gas_used: *mut u64,
+    values: *mut EnclaveBuffer,
+    keys: *const u8,
+    keys_len: usize,
+) -> OcallReturn {
+    ocall_multiple_read_db_concrete(
+        super::allocate_enclave_buffer,
+        context,
+        vm_error,
+        gas_used,
+        values,
+        keys,
+        keys_len,
+    )
+}
+
+/// Read several keys from the contracts key-value store in one ocall.
+/// `keys` is each key prefixed by its length, see `decode_read_keys`, and `values` is set to
+/// one entry per key, see `encode_read_values`. `gas_used` is the sum of what each read costs.
+fn ocall_multiple_read_db_concrete(
+    alloc_impl: fn(&[u8]) -> SgxResult<EnclaveBuffer>,
+    context: Ctx,
+    vm_error: *mut UntrustedVmError,
+    gas_used: *mut u64,
+    values: *mut EnclaveBuffer,
+    keys: *const u8,
+    keys_len: usize,
+) -> OcallReturn {
+    let keys = unsafe { std::slice::from_raw_parts(keys, keys_len) };
+    let keys = match decode_read_keys(keys) {
+        Some(keys) => keys,
+        None => {
+            let err = VmError::generic_err("malformed keys in ocall_multiple_read_db");
+            unsafe { store_vm_error(err, vm_error) };
+            return OcallReturn::Failure;
+        }
+    };
+
+    let implementation = unsafe { get_implementations_from_context(&context).read_multiple_db };
+
+    std::panic::catch_unwind(|| implementation(context, &keys))
+        // Get either an error(`OcallReturn`), or a response(`EnclaveBuffer`)
+        // which will be converted to a success status.
+        .map(|result| -> Result<EnclaveBuffer, OcallReturn> {
+            match result {
+                Ok((read_values, gas_cost)) => {
+                    unsafe { *gas_used = gas_cost };
+                    alloc_impl(&encode_read_values(&read_values)).map_err(|_| OcallReturn::Failure)
+                }
+                Err(err) => {
+                    unsafe { store_vm_error(err, vm_error) };
+                    Err(OcallReturn::Failure)
+                }
+            }
+        })
+        // Return the result or report the error
+        .map(|result| match result {
+            Ok(enclave_buffer) => {
+                unsafe { *values = enclave_buffer };
+                OcallReturn::Success
+            }
+            Err(err) => err,
+        })
+        // This will happen only when `catch_unwind` returns `Err`, which indicates a caught panic
+        .unwrap_or(OcallReturn::Panic)
+}
+
+/// Splits the keys of `ocall_multiple_read_db`, each one a 4 byte big-endian length followed by
+/// the key. Returns `None` if the bytes don't split into whole keys.
+fn decode_read_keys(mut bytes: &[u8]) -> Option<Vec<Vec<u8>>> {
+    let mut keys = vec![];
+
+    while !bytes.is_empty() {
+        let len = bytes.get(..4)?;
+        let len = 4 + u32::from_be_bytes([len[0], len[1], len[2], len[3]]) as usize;
+        keys.push(bytes.get(4..len)?.to_vec());
+        bytes = &bytes[len..];
+    }
+
+    Some(keys)
+}
+
+/// Encodes the values of `ocall_multiple_read_db`, in the same order as the keys. A missing key
+/// is a single 0 byte, an existing one a 1 byte followed by the value's 4 byte big-endian length
+/// and the value, so an empty value is distinguishable from a missing key.
+fn encode_read_values(values: &[Option<Vec<u8>>]) -> Vec<u8> {
+    let mut bytes = vec![];
+
+    for value in values {
+        match value {
+            None => bytes.push(0),
+            Some(value) => {
+                bytes.push(1);
+                bytes.extend_from_slice(&(value.len() as u32).to_be_bytes());
+                bytes.extend_from_slice(value);
+            }
+        }
+    }
+
+    bytes
+}
+
+#[no_mangle]
+pub extern "C" fn ocall_db_scan(
+    context: Ctx,
+    vm_error: *mut UntrustedVmError,
+    gas_used: *mut u64,
+    iterator_id: *mut u32,
+    start: *const u8,
+    start_len: usize,
+    end: *const u8,
+    end_len: usize,
+    order: i32,
+) -> OcallReturn {
+    let start = unsafe { bound_from_raw_parts(start, start_len) };
+    let end = unsafe { bound_from_raw_parts(end, end_len) };
+
+    let implementation = unsafe { get_implementations_from_context(&context).scan_db };
+
+    std::panic::catch_unwind(|| match implementation(context, start, end, order) {
+        Ok((new_iterator_id, gas_cost)) => {
+            unsafe {
+                *gas_used = gas_cost;
+                *iterator_id = new_iterator_id;
+            }
+            OcallReturn::Success
+        }
+        Err(err) => {
+            unsafe { store_vm_error(err, vm_error) };
+            OcallReturn::Failure
+        }
+    })
+    // This will happen only when `catch_unwind` returns `Err`, which indicates a caught panic
+    .unwrap_or(OcallReturn::Panic)
+}
+
+/// A bound of `ocall_db_scan`, where a null pointer is an open bound
+unsafe fn bound_from_raw_parts<'a>(bound: *const u8, bound_len: usize) -> Option<&'a [u8]> {
+    if bound.is_null() {
+        None
+    } else {
+        Some(std::slice::from_raw_parts(bound, bound_len))
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn ocall_db_next(
+    context: Ctx,
+    vm_error: *mut UntrustedVmError,
+    gas_used: *mut u64,
+    iterator_id: u32,
+    key: *mut EnclaveBuffer,
+    value: *mut EnclaveBuffer,
+) -> OcallReturn {
+    ocall_db_next_concrete(
+        super::allocate_enclave_buffer,
+        context,
+        vm_error,
+        gas_used,
+        iterator_id,
+        key,
+        value,
+    )
+}
+
+/// Read the next entry of an iterator `ocall_db_scan` opened. Past the end of the iterator, `key`
+/// and `value` are set to empty buffers.
+fn ocall_db_next_concrete(
+    alloc_impl: fn(&[u8]) -> SgxResult<EnclaveBuffer>,
+    context: Ctx,
+    vm_error: *mut UntrustedVmError,
+    gas_used: *mut u64,
+    iterator_id: u32,
+    key: *mut EnclaveBuffer,
+    value: *mut EnclaveBuffer,
+) -> OcallReturn {
+    let implementation = unsafe { get_implementations_from_context(&context).next_db };
+
+    std::panic::catch_unwind(|| implementation(context, iterator_id))
+        // Get either an error(`OcallReturn`), or a response(`EnclaveBuffer`s)
+        // which will be converted to a success status.
+        .map(
+            |result| -> Result<(EnclaveBuffer, EnclaveBuffer), OcallReturn> {
+                match result {
+                    Ok((entry, gas_cost)) => {
+                        unsafe { *gas_used = gas_cost };
+                        match entry {
+                            Some((k, v)) => Ok((
+                                alloc_impl(&k).map_err(|_| OcallReturn::Failure)?,
+                                alloc_impl(&v).map_err(|_| OcallReturn::Failure)?,
+                            )),
+                            None => Ok((EnclaveBuffer::default(), EnclaveBuffer::default())),
+                        }
+                    }
+                    Err(err) => {
+                        unsafe { store_vm_error(err, vm_error) };
+                        Err(OcallReturn::Failure)
+                    }
+                }
+            },
+        )
+        // Return the result or report the error
+        .map(|result| match result {
+            Ok((key_buffer, value_buffer)) => {
+                unsafe {
+                    *key = key_buffer;
+                    *value = value_buffer;
+                }
+                OcallReturn::Success
+            }
+            Err(err) => err,
+        })
+        // This will happen only when `catch_unwind` returns `Err`, which indicates a caught panic
+        .unwrap_or(OcallReturn::Panic)
+}
+
 #[no_mangle]
 pub extern "C" fn ocall_query_chain(
     context: Ctx,
@@ -319,6 +587,14 @@ unsafe fn store_vm_error(vm_err: VmError, location: *mut UntrustedVmError) {
 #[allow(clippy::type_complexity)]
 struct ExportImplementations {
     read_db: fn(context: Ctx, key: &[u8]) -> VmResult<(Option<Vec<u8>>, u64)>,
+    read_multiple_db: fn(context: Ctx, keys: &[Vec<u8>]) -> VmResult<(Vec<Option<Vec<u8>>>, u64)>,
+    scan_db: fn(
+        context: Ctx,
+        start: Option<&[u8]>,
+        end: Option<&[u8]>,
+        order: i32,
+    ) -> VmResult<(u32, u64)>,
+    next_db: fn(context: Ctx, iterator_id: u32) -> VmResult<(Option<(Vec<u8>, Vec<u8>)>, u64)>,
     query_chain: fn(
         context: Ctx,
         query: &[u8],
@@ -338,6 +614,9 @@ impl ExportImplementations {
     {
         Self {
             read_db: ocall_read_db_impl::<S, Q>,
+            read_multiple_db: ocall_read_multiple_db_impl::<S, Q>,
+            scan_db: ocall_db_scan_impl::<S, Q>,
+            next_db: ocall_db_next_impl::<S, Q>,
             query_chain: ocall_query_chain_impl::<S, Q>,
             remove_db: ocall_remove_db_impl::<S, Q>,
             write_db: ocall_write_db_impl::<S, Q>,
@@ -387,6 +666,101 @@ where
     })
 }
 
+fn ocall_read_multiple_db_impl<S, Q>(
+    mut context: Ctx,
+    keys: &[Vec<u8>],
+) -> VmResult<(Vec<Option<Vec<u8>>>, u64)>
+where
+    S: Storage,
+    Q: Querier,
+{
+    with_storage_from_context::<S, Q, _, _>(&mut context, |storage: &mut S| {
+        let mut values = Vec::with_capacity(keys.len());
+        let mut total_gas = 0;
+
+        for key in keys {
+            let (ffi_result, gas_info) = storage.get(key);
+            total_gas += gas_info.externally_used;
+            values.push(ffi_result?);
+        }
+
+        Ok((values, total_gas))
+    })
+}
+
+#[cfg(feature = "iterator")]
+fn ocall_db_scan_impl<S, Q>(
+    mut context: Ctx,
+    start: Option<&[u8]>,
+    end: Option<&[u8]>,
+    order: i32,
+) -> VmResult<(u32, u64)>
+where
+    S: Storage,
+    Q: Querier,
+{
+    let order = Order::try_from(order)
+        .map_err(|_| VmError::generic_err(format!("invalid order {} in ocall_db_scan", order)))?;
+
+    let (ffi_result, gas_info) =
+        with_storage_from_context::<S, Q, _, _>(&mut context, |storage: &mut S| {
+            Ok(storage.range(start, end, order))
+        })?;
+    let iterator = ffi_result?;
+
+    Ok((
+        add_iterator::<S, Q>(&mut context, iterator),
+        gas_info.externally_used,
+    ))
+}
+
+#[cfg(not(feature = "iterator"))]
+fn ocall_db_scan_impl<S, Q>(
+    _context: Ctx,
+    _start: Option<&[u8]>,
+    _end: Option<&[u8]>,
+    _order: i32,
+) -> VmResult<(u32, u64)>
+where
+    S: Storage,
+    Q: Querier,
+{
+    Err(VmError::generic_err(
+        "ocall_db_scan needs the iterator feature",
+    ))
+}
+
+#[cfg(feature = "iterator")]
+fn ocall_db_next_impl<S, Q>(
+    mut context: Ctx,
+    iterator_id: u32,
+) -> VmResult<(Option<(Vec<u8>, Vec<u8>)>, u64)>
+where
+    S: Storage,
+    Q: Querier,
+{
+    with_iterator_from_context::<S, Q, _, _>(&mut context, iterator_id, |iterator| {
+        let (ffi_result, gas_info) = iterator.next();
+        ffi_result
+            .map(|entry| (entry, gas_info.externally_used))
+            .map_err(Into::into)
+    })
+}
+
+#[cfg(not(feature = "iterator"))]
+fn ocall_db_next_impl<S, Q>(
+    _context: Ctx,
+    _iterator_id: u32,
+) -> VmResult<(Option<(Vec<u8>, Vec<u8>)>, u64)>
+where
+    S: Storage,
+    Q: Querier,
+{
+    Err(VmError::generic_err(
+        "ocall_db_next needs the iterator feature",
+    ))
+}
+
 fn ocall_query_chain_impl<S, Q>(
     mut context: Ctx,
     query: &[u8],
@@ -456,3 +830,82 @@ where
         Ok(total_gas)
     })
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn chunked_buffer_matches_inline_buffer() {
+        let output: Vec<u8> = (0..1000).map(|i| i as u8).collect();
+
+        let inline = unsafe { recover_buffer(ocall_allocate_impl(output.as_ptr(), output.len())) };
+
+        let handle = ocall_allocate_chunked_impl(output.len());
+        let handle_ptr = handle.ptr;
+        for chunk in output.chunks(300) {
+            let result = ocall_append_chunk_impl(
+                UserSpaceBuffer { ptr: handle_ptr },
+                chunk.as_ptr(),
+                chunk.len(),
+            );
+            assert!(matches!(result, OcallReturn::Success));
+        }
+        let chunked = unsafe { recover_buffer(handle) };
+
+        assert_eq!(chunked, Some(output));
+        assert_eq!(chunked, inline);
+    }
+
+    #[test]
+    fn append_chunk_to_null_buffer_fails() {
+        let chunk = [1u8, 2, 3];
+        let result = ocall_append_chunk_impl(
+            UserSpaceBuffer {
+                ptr: std::ptr::null_mut(),
+            },
+            chunk.as_ptr(),
+            chunk.len(),
+        );
+        assert!(matches!(result, OcallReturn::Failure));
+    }
+
+    #[test]
+    fn decode_read_keys_splits_length_prefixed_keys() {
+        let keys = [
+            &[0, 0, 0, 1, b'a'][..],
+            &[0, 0, 0, 0],
+            &[0, 0, 0, 2, b'b', b'c'],
+        ]
+        .concat();
+        assert_eq!(
+            decode_read_keys(&keys),
+            Some(vec![b"a".to_vec(), vec![], b"bc".to_vec()])
+        );
+        assert_eq!(decode_read_keys(&[]), Some(vec![]));
+
+        // a length that's longer than the remaining bytes, and a cut off length
+        assert_eq!(decode_read_keys(&[0, 0, 0, 2, b'a']), None);
+        assert_eq!(decode_read_keys(&[0, 0, 0, 1, b'a', 0, 0]), None);
+    }
+
+    #[test]
+    fn null_scan_bound_is_open() {
+        let bound = [1u8, 2];
+        unsafe {
+            assert_eq!(bound_from_raw_parts(std::ptr::null(), 0), None);
+            assert_eq!(
+                bound_from_raw_parts(bound.as_ptr(), bound.len()),
+                Some(&bound[..])
+            );
+            // an empty bound isn't open
+            assert_eq!(bound_from_raw_parts(bound.as_ptr(), 0), Some(&[][..]));
+        }
+    }
+
+    #[test]
+    fn encode_read_values_marks_missing_keys() {
+        let values = encode_read_values(&[Some(b"ab".to_vec()), None, Some(vec![])]);
+        assert_eq!(values, vec![1, 0, 0, 0, 2, b'a', b'b', 0, 1, 0, 0, 0, 0]);
+    }
+}
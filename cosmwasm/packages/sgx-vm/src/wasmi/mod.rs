@@ -4,6 +4,6 @@ mod results;
 mod utils;
 mod wrapper;
 
-pub(crate) use exports::FullContext;
+pub(crate) use exports::{recover_buffer, FullContext};
 pub use imports::*;
 pub use wrapper::*;
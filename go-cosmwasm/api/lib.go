@@ -46,6 +46,18 @@ func HealthCheck() ([]byte, error) {
 	return receiveVector(res), nil
 }
 
+// GetExecutionReceiptPublicKey returns the ed25519 public key that verifies the receipts
+// contracts get from the execution_receipt host function. It's the same on every node.
+func GetExecutionReceiptPublicKey() ([]byte, error) {
+	errmsg := C.Buffer{}
+
+	res, err := C.get_execution_receipt_public_key(&errmsg)
+	if err != nil {
+		return nil, errorWithMessage(err, errmsg)
+	}
+	return receiveVector(res), nil
+}
+
 func SubmitBlockSignatures(header []byte, commit []byte, txs []byte, encRandom []byte /* valSet []byte, nextValSet []byte */) ([]byte, error) {
 	errmsg := C.Buffer{}
 	spidSlice := sendSlice(header)
@@ -125,11 +137,13 @@ func ReleaseCache(cache Cache) {
 	C.release_cache(cache.ptr)
 }
 
-func InitEnclaveRuntime(moduleCacheSize uint16) error {
+func InitEnclaveRuntime(moduleCacheSize uint16, queryResultInlineThreshold uint32, verificationThreads uint32) error {
 	errmsg := C.Buffer{}
 
 	config := C.EnclaveRuntimeConfig{
-		module_cache_size: u32(moduleCacheSize),
+		module_cache_size:             u32(moduleCacheSize),
+		query_result_inline_threshold: u32(queryResultInlineThreshold),
+		verification_threads:          u32(verificationThreads),
 	}
 	_, err := C.configure_enclave_runtime(config, &errmsg)
 	if err != nil {
@@ -455,15 +469,18 @@ func CreateAttestationReport(apiKey []byte, no_epid bool, no_dcap bool) (bool, e
 	return true, nil
 }
 
-func GetEncryptedSeed(cert []byte) ([]byte, error) {
+// GetEncryptedSeed returns the seed encrypted for the node cert attests to, and why the node was
+// admitted with a vulnerable platform, which is empty if its platform isn't vulnerable
+func GetEncryptedSeed(cert []byte) ([]byte, string, error) {
 	errmsg := C.Buffer{}
+	downgradeReason := C.Buffer{}
 	certSlice := sendSlice(cert)
 	defer freeAfterSend(certSlice)
-	res, err := C.get_encrypted_seed(certSlice, &errmsg)
+	res, err := C.get_encrypted_seed(certSlice, &downgradeReason, &errmsg)
 	if err != nil {
-		return nil, errorWithMessage(err, errmsg)
+		return nil, "", errorWithMessage(err, errmsg)
 	}
-	return receiveVector(res), nil
+	return receiveVector(res), string(receiveVector(downgradeReason)), nil
 }
 
 func GetEncryptedGenesisSeed(pk []byte) ([]byte, error) {
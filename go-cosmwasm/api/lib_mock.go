@@ -35,6 +35,10 @@ func HealthCheck() ([]byte, error) {
 	return nil, nil
 }
 
+func GetExecutionReceiptPublicKey() ([]byte, error) {
+	return nil, nil
+}
+
 func InitBootstrap(spid []byte, apiKey []byte) ([]byte, error) {
 	return nil, nil
 }
@@ -71,7 +75,7 @@ func ReleaseCache(cache Cache) {
 	// C.release_cache(cache.ptr)
 }
 
-func InitEnclaveRuntime(ModuleCacheSize uint16) error {
+func InitEnclaveRuntime(ModuleCacheSize uint16, QueryResultInlineThreshold uint32, VerificationThreads uint32) error {
 	return nil
 }
 
@@ -269,7 +273,7 @@ func CreateAttestationReport(apiKey []byte, no_epid bool, no_dcap bool) (bool, e
 	return true, nil
 }
 
-func GetEncryptedSeed(cert []byte) ([]byte, error) {
+func GetEncryptedSeed(cert []byte) ([]byte, string, error) {
 	//errmsg := C.Buffer{}
 	//certSlice := sendSlice(cert)
 	//defer freeAfterSend(certSlice)
@@ -278,7 +282,7 @@ func GetEncryptedSeed(cert []byte) ([]byte, error) {
 	//	return nil, errorWithMessage(err, errmsg)
 	//}
 	//return receiveVector(res), nil
-	return nil, nil
+	return nil, "", nil
 }
 
 func GetEncryptedGenesisSeed(cert []byte) ([]byte, error) {
@@ -40,12 +40,15 @@ type Wasmer struct {
 // cacheSize sets the size of an optional in-memory LRU cache for prepared VMs.
 // They allow popular contracts to be executed very rapidly (no loading overhead),
 // but require ~32-64MB each in memory usage.
-func NewWasmer(dataDir string, supportedFeatures string, cacheSize uint64, moduleCacheSize uint16) (*Wasmer, error) {
+// queryResultInlineThreshold sets the size in bytes above which query results are streamed
+// out of the enclave in chunks. 0 uses the enclave's default.
+// verificationThreads sets how many enclave threads verify batches of signatures. 0 verifies serially.
+func NewWasmer(dataDir string, supportedFeatures string, cacheSize uint64, moduleCacheSize uint16, queryResultInlineThreshold uint32, verificationThreads uint32) (*Wasmer, error) {
 	cache, err := api.InitCache(dataDir, supportedFeatures, cacheSize)
 	if err != nil {
 		return nil, err
 	}
-	err = api.InitEnclaveRuntime(moduleCacheSize)
+	err = api.InitEnclaveRuntime(moduleCacheSize, queryResultInlineThreshold, verificationThreads)
 	if err != nil {
 		return nil, err
 	}
@@ -182,7 +185,7 @@ func (w *Wasmer) Instantiate(
 
 		if respV010orV1.V010.Ok != nil {
 			if isOutputAddressedToReply {
-				respV010orV1.V010.Ok.Data, err = AppendReplyInternalDataToData(respV010orV1.V010.Ok.Data, respV010orV1.InternaReplyEnclaveSig, respV010orV1.InternalMsgId)
+				respV010orV1.V010.Ok.Data, err = AppendReplyInternalDataToData(respV010orV1.V010.Ok.Data, respV010orV1.InternaReplyEnclaveSig, respV010orV1.InternalMsgId, false)
 				if err != nil {
 					return nil, nil, nil, gasUsed, fmt.Errorf("cannot serialize v0.10 DataWithInternalReplyInfo into binary : %w", err)
 				}
@@ -204,7 +207,7 @@ func (w *Wasmer) Instantiate(
 
 		if respV010orV1.V1.Ok != nil {
 			if isOutputAddressedToReply {
-				respV010orV1.V1.Ok.Data, err = AppendReplyInternalDataToData(respV010orV1.V1.Ok.Data, respV010orV1.InternaReplyEnclaveSig, respV010orV1.InternalMsgId)
+				respV010orV1.V1.Ok.Data, err = AppendReplyInternalDataToData(respV010orV1.V1.Ok.Data, respV010orV1.InternaReplyEnclaveSig, respV010orV1.InternalMsgId, respV010orV1.V1.Ok.PlaintextData)
 				if err != nil {
 					return nil, nil, nil, gasUsed, fmt.Errorf("cannot serialize v1 DataWithInternalReplyInfo into binary: %w", err)
 				}
@@ -217,11 +220,12 @@ func (w *Wasmer) Instantiate(
 	return nil, nil, nil, gasUsed, fmt.Errorf("instantiate: cannot detect response type (v0.10 or v1)")
 }
 
-func AppendReplyInternalDataToData(data []byte, internaReplyEnclaveSig []byte, internalMsgId []byte) ([]byte, error) {
+func AppendReplyInternalDataToData(data []byte, internaReplyEnclaveSig []byte, internalMsgId []byte, isDataPlaintext bool) ([]byte, error) {
 	dataWithInternalReply := v1types.DataWithInternalReplyInfo{
 		InternaReplyEnclaveSig: internaReplyEnclaveSig,
 		InternalMsgId:          internalMsgId,
 		Data:                   data,
+		DataIsPlaintext:        isDataPlaintext,
 	}
 
 	return json.Marshal(dataWithInternalReply)
@@ -278,7 +282,7 @@ func (w *Wasmer) Execute(
 			}, gasUsed, fmt.Errorf("%+v", resp.V010.Err)
 		} else if resp.V010.Ok != nil {
 			if isOutputAddressedToReply {
-				resp.V010.Ok.Data, err = AppendReplyInternalDataToData(resp.V010.Ok.Data, resp.InternaReplyEnclaveSig, resp.InternalMsgId)
+				resp.V010.Ok.Data, err = AppendReplyInternalDataToData(resp.V010.Ok.Data, resp.InternaReplyEnclaveSig, resp.InternalMsgId, false)
 				if err != nil {
 					return nil, gasUsed, fmt.Errorf("cannot serialize v0.10 DataWithInternalReplyInfo into binary : %w", err)
 				}
@@ -299,7 +303,7 @@ func (w *Wasmer) Execute(
 			}, gasUsed, fmt.Errorf("%+v", resp.V1.Err)
 		} else if resp.V1.Ok != nil {
 			if isOutputAddressedToReply {
-				resp.V1.Ok.Data, err = AppendReplyInternalDataToData(resp.V1.Ok.Data, resp.InternaReplyEnclaveSig, resp.InternalMsgId)
+				resp.V1.Ok.Data, err = AppendReplyInternalDataToData(resp.V1.Ok.Data, resp.InternaReplyEnclaveSig, resp.InternalMsgId, resp.V1.Ok.PlaintextData)
 				if err != nil {
 					return nil, gasUsed, fmt.Errorf("cannot serialize v1 DataWithInternalReplyInfo into binary: %w", err)
 				}
@@ -347,6 +351,8 @@ func (w *Wasmer) Execute(
 // Query allows a client to execute a contract-specific query. If the result is not empty, it should be
 // valid json-encoded data to return to the client.
 // The meaning of path and data can be determined by the code. Path is the suffix of the abci.QueryRequest.Path
+// It also returns the nonce of the encrypted query, with results and contract errors alike, which the enclave
+// echoes so a response can be matched to its request without decrypting it.
 func (w *Wasmer) Query(
 	code CodeHash,
 	env types.Env,
@@ -356,35 +362,35 @@ func (w *Wasmer) Query(
 	querier Querier,
 	gasMeter GasMeter,
 	gasLimit uint64,
-) ([]byte, uint64, error) {
+) ([]byte, []byte, uint64, error) {
 	paramBin, err := json.Marshal(env)
 	if err != nil {
-		return nil, 0, err
+		return nil, nil, 0, err
 	}
 	data, gasUsed, err := api.Query(w.cache, code, paramBin, queryMsg, &gasMeter, store, &goapi, &querier, gasLimit)
 	if err != nil {
-		return nil, gasUsed, err
+		return nil, nil, gasUsed, err
 	}
 
 	var resp types.ContractQueryResponse
 	err = json.Unmarshal(data, &resp)
 	if err != nil {
-		return nil, gasUsed, err
+		return nil, nil, gasUsed, err
 	}
 
 	if resp.Query == nil {
-		return nil, gasUsed, fmt.Errorf("query: cannot detect response type")
+		return nil, nil, gasUsed, fmt.Errorf("query: cannot detect response type")
 	}
 
 	if resp.Query.Err != nil {
-		return nil, gasUsed, fmt.Errorf("%v", resp.Query.Err)
+		return nil, resp.Query.Nonce, gasUsed, fmt.Errorf("%v", resp.Query.Err)
 	}
 
 	if resp.Query.Ok != nil {
-		return resp.Query.Ok, gasUsed, nil
+		return resp.Query.Ok, resp.Query.Nonce, gasUsed, nil
 	}
 
-	return nil, gasUsed, fmt.Errorf("query: cannot detect response type")
+	return nil, nil, gasUsed, fmt.Errorf("query: cannot detect response type")
 }
 
 // AnalyzeCode returns a report of static analysis of the wasm contract (uncompiled).
@@ -456,7 +462,7 @@ func (w *Wasmer) Migrate(
 
 		if respV010orV1.V010.Ok != nil {
 			if isOutputAddressedToReply {
-				respV010orV1.V010.Ok.Data, err = AppendReplyInternalDataToData(respV010orV1.V010.Ok.Data, respV010orV1.InternaReplyEnclaveSig, respV010orV1.InternalMsgId)
+				respV010orV1.V010.Ok.Data, err = AppendReplyInternalDataToData(respV010orV1.V010.Ok.Data, respV010orV1.InternaReplyEnclaveSig, respV010orV1.InternalMsgId, false)
 				if err != nil {
 					return nil, nil, nil, gasUsed, fmt.Errorf("cannot serialize v0.10 DataWithInternalReplyInfo into binary : %w", err)
 				}
@@ -478,7 +484,7 @@ func (w *Wasmer) Migrate(
 
 		if respV010orV1.V1.Ok != nil {
 			if isOutputAddressedToReply {
-				respV010orV1.V1.Ok.Data, err = AppendReplyInternalDataToData(respV010orV1.V1.Ok.Data, respV010orV1.InternaReplyEnclaveSig, respV010orV1.InternalMsgId)
+				respV010orV1.V1.Ok.Data, err = AppendReplyInternalDataToData(respV010orV1.V1.Ok.Data, respV010orV1.InternaReplyEnclaveSig, respV010orV1.InternalMsgId, respV010orV1.V1.Ok.PlaintextData)
 				if err != nil {
 					return nil, nil, nil, gasUsed, fmt.Errorf("cannot serialize v1 DataWithInternalReplyInfo into binary: %w", err)
 				}
@@ -26,8 +26,8 @@ use cosmwasm_sgx_vm::{
 };
 use cosmwasm_sgx_vm::{
     create_attestation_report_u, untrusted_get_encrypted_genesis_seed,
-    untrusted_get_encrypted_seed, untrusted_health_check, untrusted_init_node, untrusted_key_gen,
-    untrusted_migrate_sealing,
+    untrusted_get_encrypted_seed, untrusted_get_execution_receipt_public_key,
+    untrusted_health_check, untrusted_init_node, untrusted_key_gen, untrusted_migrate_sealing,
 };
 
 use ctor::ctor;
@@ -65,8 +65,29 @@ pub extern "C" fn get_health_check(err: Option<&mut Buffer>) -> Buffer {
     }
 }
 
+/// Returns the public key that verifies execution receipts
 #[no_mangle]
-pub extern "C" fn get_encrypted_seed(cert: Buffer, err: Option<&mut Buffer>) -> Buffer {
+pub extern "C" fn get_execution_receipt_public_key(err: Option<&mut Buffer>) -> Buffer {
+    match untrusted_get_execution_receipt_public_key() {
+        Err(e) => {
+            set_error(Error::enclave_err(e.to_string()), err);
+            Buffer::default()
+        }
+        Ok(public_key) => {
+            clear_error();
+            Buffer::from_vec(public_key.to_vec())
+        }
+    }
+}
+
+/// Writes why the node was admitted with a vulnerable platform to `downgrade_reason`, and leaves
+/// it empty if the platform isn't vulnerable
+#[no_mangle]
+pub extern "C" fn get_encrypted_seed(
+    cert: Buffer,
+    downgrade_reason: Option<&mut Buffer>,
+    err: Option<&mut Buffer>,
+) -> Buffer {
     trace!("Called get_encrypted_seed");
     let cert_slice = match unsafe { cert.read() } {
         None => {
@@ -87,8 +108,11 @@ pub extern "C" fn get_encrypted_seed(cert: Buffer, err: Option<&mut Buffer>) ->
             set_error(Error::enclave_err(e.to_string()), err);
             Buffer::default()
         }
-        Ok(Ok(seed)) => {
+        Ok(Ok((seed, downgrade))) => {
             clear_error();
+            if let (Some(downgrade), Some(out)) = (downgrade, downgrade_reason) {
+                *out = Buffer::from_vec(downgrade.to_string().into_bytes());
+            }
             Buffer::from_vec(seed.to_vec())
         }
     }
@@ -371,12 +395,16 @@ pub extern "C" fn release_cache(cache: *mut cache_t) {
 #[repr(C)]
 pub struct EnclaveRuntimeConfig {
     pub module_cache_size: u32,
+    pub query_result_inline_threshold: u32,
+    pub verification_threads: u32,
 }
 
 impl EnclaveRuntimeConfig {
     fn to_sgx_vm(&self) -> cosmwasm_sgx_vm::EnclaveRuntimeConfig {
         cosmwasm_sgx_vm::EnclaveRuntimeConfig {
             module_cache_size: self.module_cache_size,
+            query_result_inline_threshold: self.query_result_inline_threshold,
+            verification_threads: self.verification_threads,
         }
     }
 }
@@ -14,6 +14,38 @@ type Env struct {
 	Key         ContractKey      `json:"contract_key"`
 	QueryDepth  uint32           `json:"query_depth"`
 	Transaction *TransactionInfo `json:"transaction,omitempty"`
+	// How the results of the call are compressed before they're encrypted,
+	// set by governance. Only read by the enclave, contracts never see it
+	OutputCompression *OutputCompression `json:"output_compression,omitempty"`
+	// How many submessages the transaction may still send, set by governance.
+	// Only read by the enclave, contracts never see it
+	SubmsgLimit *SubmsgLimit `json:"submsg_limit,omitempty"`
+	// The most (sdk) gas a single contract call may use, set by governance.
+	// 0 leaves contracts bound by the tx gas limit only. Only read by the enclave
+	ContractGasCeiling uint64 `json:"contract_gas_ceiling,omitempty"`
+	// How deep queries between contracts can nest, set by governance.
+	// 0 keeps the enclave's default. Only read by the enclave
+	MaxQueryDepth uint32 `json:"max_query_depth,omitempty"`
+	// The longest type in bytes a contract's custom events may have, set by
+	// governance. 0 doesn't limit them. Only read by the enclave
+	MaxEventTypeLength uint32 `json:"max_event_type_length,omitempty"`
+	// The instance id the next contract instantiated on the chain gets, for
+	// predicting its address. Only read by the enclave
+	NextInstanceID uint64 `json:"next_instance_id,omitempty"`
+}
+
+type OutputCompression struct {
+	// The output format, 0 leaves results as they are
+	Version uint32 `json:"version"`
+	// Results shorter than this many bytes aren't compressed
+	Threshold uint64 `json:"threshold"`
+}
+
+type SubmsgLimit struct {
+	// The most submessages a transaction may send
+	Max uint32 `json:"max"`
+	// The submessages the transaction sent before this call
+	Emitted uint32 `json:"emitted"`
 }
 
 type ContractKey struct {
@@ -33,6 +65,9 @@ type TransactionInfo struct {
 	/// aka txhash or transaction_id
 	/// hash = sha256(tx_bytes)
 	Hash string `json:"hash"`
+	// The chain's min gas price, set by governance. It's the same for every
+	// transaction in a block, and empty if it was never set
+	MinGasPrice []DecCoin `json:"min_gas_price,omitempty"`
 }
 
 type BaseEnv[T Env] struct {
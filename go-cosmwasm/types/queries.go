@@ -9,6 +9,9 @@ import (
 type QueryResponse struct {
 	Ok  []byte    `json:"Ok,omitempty"`
 	Err *StdError `json:"Err,omitempty"`
+	// Nonce echoes the (public) nonce of the encrypted query, so a client can match a
+	// response to its request without decrypting it
+	Nonce []byte `json:"nonce,omitempty"`
 }
 
 type ContractQueryResponse struct {
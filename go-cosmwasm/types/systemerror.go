@@ -84,10 +84,17 @@ func (e UnsupportedRequest) Error() string {
 	return fmt.Sprintf("unsupported request: %s", e.Kind)
 }
 
-type ExceededRecursionLimit struct{}
+// ExceededRecursionLimit is returned to a contract whose query would nest deeper than the
+// enclave's query recursion limit. ContractAddr is the contract that made the query, when known.
+type ExceededRecursionLimit struct {
+	ContractAddr string `json:"contract_addr,omitempty"`
+}
 
 func (e ExceededRecursionLimit) Error() string {
-	return "unknown system error"
+	if e.ContractAddr == "" {
+		return "query recursion limit exceeded"
+	}
+	return fmt.Sprintf("query recursion limit exceeded (contract: %s)", e.ContractAddr)
 }
 
 // ToSystemError will try to convert the given error to a SystemError.
@@ -0,0 +1,34 @@
+package types
+
+import (
+	"encoding/json"
+	"testing"
+
+	"github.com/stretchr/testify/assert"
+	"github.com/stretchr/testify/require"
+)
+
+func TestExceededRecursionLimitFromEnclave(t *testing.T) {
+	for _, test := range []struct {
+		description string
+		serialized  string
+		expected    ExceededRecursionLimit
+	}{
+		{"WithoutContract", `{"exceeded_recursion_limit":{}}`, ExceededRecursionLimit{}},
+		{"WithContract", `{"exceeded_recursion_limit":{"contract_addr":"secret1contract"}}`, ExceededRecursionLimit{ContractAddr: "secret1contract"}},
+	} {
+		t.Run(test.description, func(t *testing.T) {
+			var sysErr SystemError
+			err := json.Unmarshal([]byte(test.serialized), &sysErr)
+			require.NoError(t, err)
+
+			require.NotNil(t, sysErr.ExceededRecursionLimit)
+			assert.Equal(t, test.expected, *sysErr.ExceededRecursionLimit)
+			assert.Equal(t, test.expected.Error(), sysErr.Error())
+
+			bz, err := json.Marshal(sysErr)
+			require.NoError(t, err)
+			assert.JSONEq(t, test.serialized, string(bz))
+		})
+	}
+}
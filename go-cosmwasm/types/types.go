@@ -17,6 +17,12 @@ type Coin struct {
 	Amount string `json:"amount"` // string encoing of decimal value, eg. "12.3456"
 }
 
+// DecCoin is a Coin with a decimal amount, eg. a gas price
+type DecCoin struct {
+	Denom  string `json:"denom"`
+	Amount string `json:"amount"` // string encoding of decimal value, eg. "0.0125"
+}
+
 func NewCoin(amount uint64, denom string) Coin {
 	return Coin{
 		Denom:  denom,
@@ -32,6 +32,8 @@ type Response struct {
 	// custom events (separate from the main one that contains the attributes
 	// above)
 	Events []Event `json:"events"`
+	// whether the contract emitted Data in plaintext instead of encrypting it
+	PlaintextData bool `json:"plaintext_data,omitempty"`
 }
 
 // Used to serialize both the data and the internal reply information in order to keep the api without changes
@@ -39,6 +41,8 @@ type DataWithInternalReplyInfo struct {
 	InternaReplyEnclaveSig []byte `json:"internal_reply_enclave_sig"`
 	InternalMsgId          []byte `json:"internal_msg_id"`
 	Data                   []byte `json:"data,omitempty"`
+	// Data wasn't encrypted, the enclave signed the reply with IsDataPlaintext set
+	DataIsPlaintext bool `json:"data_is_plaintext,omitempty"`
 }
 
 // LogAttributes must encode empty array as []
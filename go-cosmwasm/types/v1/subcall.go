@@ -78,6 +78,7 @@ type Reply struct {
 	Result              SubMsgResult `json:"result"`
 	WasOrigMsgEncrypted bool         `json:"was_orig_msg_encrypted"`
 	IsEncrypted         bool         `json:"is_encrypted"`
+	IsDataPlaintext     bool         `json:"is_data_plaintext,omitempty"`
 }
 
 // SubcallResult is the raw response we return from the sdk -> reply after executing a SubMsg.
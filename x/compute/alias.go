@@ -23,6 +23,8 @@ const (
 	AttributeKeyContractAddr      = types.AttributeKeyContractAddr
 	GasMultiplier                 = types.GasMultiplier
 	MaxGas                        = types.MaxGas
+	DefaultMaxSubmessages         = types.DefaultMaxSubmessages
+	DefaultMaxEventTypeLength     = types.DefaultMaxEventTypeLength
 	QueryListContractByCode       = keeper.QueryListContractByCode
 	QueryGetContract              = keeper.QueryGetContract
 	QueryGetContractState         = keeper.QueryGetContractState
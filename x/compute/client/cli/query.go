@@ -50,6 +50,7 @@ func GetQueryCmd() *cobra.Command {
 		GetCmdQueryLabel(),
 		GetCmdCodeHashByContractAddress(),
 		GetCmdCodeHashByCodeID(),
+		GetCmdExecutionReceiptPubKey(),
 		CmdDecryptText(),
 		GetCmdGetContractHistory(),
 	)
@@ -185,6 +186,35 @@ func GetCmdCodeHashByCodeID() *cobra.Command {
 	return cmd
 }
 
+// GetCmdExecutionReceiptPubKey returns the public key that verifies execution receipts
+func GetCmdExecutionReceiptPubKey() *cobra.Command {
+	cmd := &cobra.Command{
+		Use:   "execution-receipt-pubkey",
+		Short: "Return the public key that verifies execution receipts",
+		Long: "Return the ed25519 public key that verifies the receipts contracts sign with the " +
+			"execution_receipt host function. It's derived from the consensus seed, so it's the same on every node",
+		Args: cobra.ExactArgs(0),
+		RunE: func(cmd *cobra.Command, args []string) error {
+			clientCtx, err := client.GetClientQueryContext(cmd)
+			if err != nil {
+				return err
+			}
+
+			route := fmt.Sprintf("custom/%s/%s", types.QuerierRoute, keeper.QueryExecutionReceiptPubKey)
+			res, _, err := clientCtx.Query(route)
+			if err != nil {
+				return fmt.Errorf("error querying execution receipt public key: %s", err)
+			}
+
+			fmt.Printf("0x%s\n", hex.EncodeToString(res))
+			return nil
+		},
+	}
+
+	flags.AddQueryFlagsToCmd(cmd)
+	return cmd
+}
+
 // GetCmdListContractByCode lists all wasm code uploaded for given code id
 func GetCmdListContractByCode() *cobra.Command {
 	cmd := &cobra.Command{
@@ -21,7 +21,9 @@ func NewCountTXDecorator(storeKey sdk.StoreKey) *CountTXDecorator {
 // global rollback behavior instead of keeping state in the handler itself.
 // The ante handler passes the counter value via sdk.Context upstream. See `types.TXCounter(ctx)` to read the value.
 // Simulations don't get a tx counter value assigned.
+// It also passes a counter of the submessages the tx sends, see `types.SubmsgCounter(ctx)`, which simulations do get.
 func (a CountTXDecorator) AnteHandle(ctx sdk.Context, tx sdk.Tx, simulate bool, next sdk.AnteHandler) (sdk.Context, error) {
+	ctx = types.WithSubmsgCounter(ctx)
 	if simulate {
 		return next(ctx, tx, simulate)
 	}
@@ -31,6 +31,7 @@ import (
 	distrkeeper "github.com/cosmos/cosmos-sdk/x/distribution/keeper"
 	govkeeper "github.com/cosmos/cosmos-sdk/x/gov/keeper"
 	mintkeeper "github.com/cosmos/cosmos-sdk/x/mint/keeper"
+	paramtypes "github.com/cosmos/cosmos-sdk/x/params/types"
 	stakingkeeper "github.com/cosmos/cosmos-sdk/x/staking/keeper"
 	"github.com/tendermint/tendermint/libs/log"
 
@@ -79,9 +80,11 @@ type Keeper struct {
 	messenger        Messenger
 	// queryGasLimit is the max wasm gas that can be spent on executing a query with a contract
 	queryGasLimit uint64
-	HomeDir       string
+	// gasUsedAttribute appends the gas charged for a contract call to its wasm event
+	gasUsedAttribute bool
+	HomeDir          string
 	// authZPolicy   AuthorizationPolicy
-	// paramSpace    subspace.Subspace
+	paramSpace     paramtypes.Subspace
 	LastMsgManager *baseapp.LastMsgMarkerContainer
 }
 
@@ -118,8 +121,13 @@ func NewKeeper(
 	customEncoders *MessageEncoders,
 	customPlugins *QueryPlugins,
 	lastMsgManager *baseapp.LastMsgMarkerContainer,
+	paramSpace paramtypes.Subspace,
 ) Keeper {
-	wasmer, err := wasm.NewWasmer(filepath.Join(homeDir, "wasm"), supportedFeatures, wasmConfig.CacheSize, wasmConfig.EnclaveCacheSize)
+	if !paramSpace.HasKeyTable() {
+		paramSpace = paramSpace.WithKeyTable(types.ParamKeyTable())
+	}
+
+	wasmer, err := wasm.NewWasmer(filepath.Join(homeDir, "wasm"), supportedFeatures, wasmConfig.CacheSize, wasmConfig.EnclaveCacheSize, wasmConfig.QueryResultInlineThreshold, wasmConfig.VerificationThreads)
 	if err != nil {
 		panic(err)
 	}
@@ -143,9 +151,11 @@ func NewKeeper(
 			portSource,
 			cdc,
 		),
-		queryGasLimit:  wasmConfig.SmartQueryGasLimit,
-		HomeDir:        homeDir,
-		LastMsgManager: lastMsgManager,
+		queryGasLimit:    wasmConfig.SmartQueryGasLimit,
+		gasUsedAttribute: wasmConfig.GasUsedAttribute,
+		HomeDir:          homeDir,
+		LastMsgManager:   lastMsgManager,
+		paramSpace:       paramSpace,
 	}
 	keeper.queryPlugins = DefaultQueryPlugins(govKeeper, distKeeper, mintKeeper, bankKeeper, stakingKeeper, queryRouter, &keeper, channelKeeper).Merge(customPlugins)
 
@@ -478,6 +488,13 @@ func (k Keeper) Instantiate(ctx sdk.Context, codeID uint64, creator, admin sdk.A
 		},
 		random,
 	)
+	k.addMinGasPrice(ctx, &env)
+	k.addOutputCompression(ctx, &env)
+	k.addSubmsgLimit(ctx, &env)
+	k.addContractGasCeiling(ctx, &env)
+	k.addMaxQueryDepth(ctx, &env)
+	k.addMaxEventTypeLength(ctx, &env)
+	k.addNextInstanceID(ctx, &env)
 
 	// create prefixed data store
 	// 0x03 | contractAddress (sdk.AccAddress)
@@ -491,8 +508,14 @@ func (k Keeper) Instantiate(ctx sdk.Context, codeID uint64, creator, admin sdk.A
 		Caller:  contractAddress,
 	}
 
-	response, ogContractKey, adminProof, gasUsed, initError := k.wasmer.Instantiate(codeInfo.CodeHash, env, initMsg, prefixStore, cosmwasmAPI, querier, ctx.GasMeter(), gasForContract(ctx), sigInfo, admin)
-	consumeGas(ctx, gasUsed)
+	gasLimit := gasForContract(ctx)
+	response, ogContractKey, adminProof, gasUsed, initError := k.wasmer.Instantiate(codeInfo.CodeHash, env, initMsg, prefixStore, cosmwasmAPI, querier, ctx.GasMeter(), gasLimit, sigInfo, admin)
+	if isOutOfGasForReply(response, initError, gasUsed, gasLimit) {
+		// the error has to reach the caller's reply, so running out of gas can't abort the tx here
+		consumeGasUpToLimit(ctx, gasUsed)
+	} else {
+		consumeGas(ctx, gasUsed)
+	}
 
 	if initError != nil {
 		switch res := response.(type) { //nolint:gocritic
@@ -534,7 +557,7 @@ func (k Keeper) Instantiate(ctx sdk.Context, codeID uint64, creator, admin sdk.A
 			return nil, nil, sdkerrors.Wrap(err, "couldn't convert v0.10 messages to v1 messages")
 		}
 
-		data, err := k.handleContractResponse(ctx, contractAddress, contractInfo.IBCPortID, subMessages, res.Log, []v1wasmTypes.Event{}, res.Data, initMsg, sigInfo)
+		data, err := k.handleContractResponse(ctx, contractAddress, contractInfo.IBCPortID, subMessages, k.withGasUsedAttribute(res.Log, gasUsed), []v1wasmTypes.Event{}, res.Data, initMsg, sigInfo)
 		if err != nil {
 			return nil, nil, sdkerrors.Wrap(err, "dispatch")
 		}
@@ -579,7 +602,7 @@ func (k Keeper) Instantiate(ctx sdk.Context, codeID uint64, creator, admin sdk.A
 		})
 		store.Set(types.GetContractLabelPrefix(label), contractAddress)
 
-		data, err := k.handleContractResponse(ctx, contractAddress, contractInfo.IBCPortID, res.Messages, res.Attributes, res.Events, res.Data, initMsg, sigInfo)
+		data, err := k.handleContractResponse(ctx, contractAddress, contractInfo.IBCPortID, res.Messages, k.withGasUsedAttribute(res.Attributes, gasUsed), res.Events, res.Data, initMsg, sigInfo)
 		if err != nil {
 			return nil, nil, sdkerrors.Wrap(err, "dispatch")
 		}
@@ -638,6 +661,13 @@ func (k Keeper) Execute(ctx sdk.Context, contractAddress sdk.AccAddress, caller
 	}
 
 	env := types.NewEnv(ctx, caller, coins, contractAddress, contractKey, random)
+	k.addMinGasPrice(ctx, &env)
+	k.addOutputCompression(ctx, &env)
+	k.addSubmsgLimit(ctx, &env)
+	k.addContractGasCeiling(ctx, &env)
+	k.addMaxQueryDepth(ctx, &env)
+	k.addMaxEventTypeLength(ctx, &env)
+	k.addNextInstanceID(ctx, &env)
 
 	// prepare querier
 	querier := QueryHandler{
@@ -646,8 +676,14 @@ func (k Keeper) Execute(ctx sdk.Context, contractAddress sdk.AccAddress, caller
 		Caller:  contractAddress,
 	}
 
-	response, gasUsed, execErr := k.wasmer.Execute(codeInfo.CodeHash, env, msg, prefixStore, cosmwasmAPI, querier, gasMeter(ctx), gasForContract(ctx), sigInfo, handleType)
-	consumeGas(ctx, gasUsed)
+	gasLimit := gasForContract(ctx)
+	response, gasUsed, execErr := k.wasmer.Execute(codeInfo.CodeHash, env, msg, prefixStore, cosmwasmAPI, querier, gasMeter(ctx), gasLimit, sigInfo, handleType)
+	if isOutOfGasForReply(response, execErr, gasUsed, gasLimit) {
+		// the error has to reach the caller's reply, so running out of gas can't abort the tx here
+		consumeGasUpToLimit(ctx, gasUsed)
+	} else {
+		consumeGas(ctx, gasUsed)
+	}
 
 	if execErr != nil {
 		var result sdk.Result
@@ -670,7 +706,7 @@ func (k Keeper) Execute(ctx sdk.Context, contractAddress sdk.AccAddress, caller
 			return nil, sdkerrors.Wrap(err, "couldn't convert v0.10 messages to v1 messages")
 		}
 
-		data, err := k.handleContractResponse(ctx, contractAddress, contractInfo.IBCPortID, subMessages, res.Log, []v1wasmTypes.Event{}, res.Data, msg, sigInfo)
+		data, err := k.handleContractResponse(ctx, contractAddress, contractInfo.IBCPortID, subMessages, k.withGasUsedAttribute(res.Log, gasUsed), []v1wasmTypes.Event{}, res.Data, msg, sigInfo)
 		if err != nil {
 			return nil, sdkerrors.Wrap(err, "dispatch")
 		}
@@ -684,7 +720,7 @@ func (k Keeper) Execute(ctx sdk.Context, contractAddress sdk.AccAddress, caller
 			sdk.NewAttribute(types.AttributeKeyContractAddr, contractAddress.String()),
 		))
 
-		data, err := k.handleContractResponse(ctx, contractAddress, contractInfo.IBCPortID, res.Messages, res.Attributes, res.Events, res.Data, msg, sigInfo)
+		data, err := k.handleContractResponse(ctx, contractAddress, contractInfo.IBCPortID, res.Messages, k.withGasUsedAttribute(res.Attributes, gasUsed), res.Events, res.Data, msg, sigInfo)
 		if err != nil {
 			return nil, sdkerrors.Wrap(err, "dispatch")
 		}
@@ -742,8 +778,12 @@ func (k Keeper) querySmartImpl(ctx sdk.Context, contractAddress sdk.AccAddress,
 		[]byte{0}, /* empty because it's unused in queries */
 	)
 	params.QueryDepth = queryDepth
+	k.addOutputCompression(ctx, &params)
+	k.addContractGasCeiling(ctx, &params)
+	k.addMaxQueryDepth(ctx, &params)
+	k.addNextInstanceID(ctx, &params)
 
-	queryResult, gasUsed, qErr := k.wasmer.Query(codeInfo.CodeHash, params, req, prefixStore, cosmwasmAPI, querier, gasMeter(ctx), gasForContract(ctx))
+	queryResult, _, gasUsed, qErr := k.wasmer.Query(codeInfo.CodeHash, params, req, prefixStore, cosmwasmAPI, querier, gasMeter(ctx), gasForContract(ctx))
 	consumeGas(ctx, gasUsed)
 
 	telemetry.SetGauge(float32(gasUsed), "compute", "keeper", "query", contractAddress.String(), "gasUsed")
@@ -835,6 +875,155 @@ func (k Keeper) SetRandomSeed(ctx sdk.Context, random []byte) {
 	store.Set(types.GetRandomKey(ctx.BlockHeight()), random)
 }
 
+// GetMinGasPrice returns the governance-set min gas price, or nil if it was never set
+func (k Keeper) GetMinGasPrice(ctx sdk.Context) sdk.DecCoins {
+	// reading a param shouldn't cost the contract caller gas
+	ctx = ctx.WithGasMeter(sdk.NewInfiniteGasMeter())
+
+	var minGasPrice string
+	k.paramSpace.GetIfExists(ctx, types.KeyMinGasPrice, &minGasPrice)
+
+	// the param is validated when it's set
+	coins, _ := sdk.ParseDecCoins(minGasPrice)
+	return coins
+}
+
+func (k Keeper) SetMinGasPrice(ctx sdk.Context, minGasPrice sdk.DecCoins) {
+	k.paramSpace.Set(ctx, types.KeyMinGasPrice, minGasPrice.String())
+}
+
+// addMinGasPrice sets env.transaction.min_gas_price. Outside of transactions there's nothing to set
+func (k Keeper) addMinGasPrice(ctx sdk.Context, env *wasmTypes.Env) {
+	if env.Transaction == nil {
+		return
+	}
+
+	env.Transaction.MinGasPrice = types.NewWasmDecCoins(k.GetMinGasPrice(ctx))
+}
+
+// GetOutputCompression returns the governance-set compression of contract results, which is
+// version 0 (off) if it was never set
+func (k Keeper) GetOutputCompression(ctx sdk.Context) wasmTypes.OutputCompression {
+	// reading a param shouldn't cost the contract caller gas
+	ctx = ctx.WithGasMeter(sdk.NewInfiniteGasMeter())
+
+	var compression wasmTypes.OutputCompression
+	k.paramSpace.GetIfExists(ctx, types.KeyOutputCompressionVersion, &compression.Version)
+	k.paramSpace.GetIfExists(ctx, types.KeyOutputCompressionThreshold, &compression.Threshold)
+	return compression
+}
+
+func (k Keeper) SetOutputCompression(ctx sdk.Context, compression wasmTypes.OutputCompression) {
+	k.paramSpace.Set(ctx, types.KeyOutputCompressionVersion, compression.Version)
+	k.paramSpace.Set(ctx, types.KeyOutputCompressionThreshold, compression.Threshold)
+}
+
+// addOutputCompression sets env.output_compression, for queries too since their results are
+// compressed like the results of transactions. It's left out while compression is off, so the env
+// stays the same as before the param existed
+func (k Keeper) addOutputCompression(ctx sdk.Context, env *wasmTypes.Env) {
+	compression := k.GetOutputCompression(ctx)
+	if compression.Version == 0 {
+		return
+	}
+
+	env.OutputCompression = &compression
+}
+
+// GetMaxSubmessages returns the governance-set limit on the submessages of a transaction, which is
+// 0 (no limit) if it was never set
+func (k Keeper) GetMaxSubmessages(ctx sdk.Context) uint32 {
+	// reading a param shouldn't cost the contract caller gas
+	ctx = ctx.WithGasMeter(sdk.NewInfiniteGasMeter())
+
+	var maxSubmessages uint32
+	k.paramSpace.GetIfExists(ctx, types.KeyMaxSubmessages, &maxSubmessages)
+	return maxSubmessages
+}
+
+func (k Keeper) SetMaxSubmessages(ctx sdk.Context, maxSubmessages uint32) {
+	k.paramSpace.Set(ctx, types.KeyMaxSubmessages, maxSubmessages)
+}
+
+// addSubmsgLimit sets env.submsg_limit, with the submessages the transaction already sent. It's
+// left out without a limit, so the env stays the same as before the param existed, and outside of
+// transactions, which don't send submessages
+func (k Keeper) addSubmsgLimit(ctx sdk.Context, env *wasmTypes.Env) {
+	maxSubmessages := k.GetMaxSubmessages(ctx)
+	counter := types.SubmsgCounter(ctx)
+	if maxSubmessages == 0 || counter == nil {
+		return
+	}
+
+	env.SubmsgLimit = &wasmTypes.SubmsgLimit{
+		Max:     maxSubmessages,
+		Emitted: *counter,
+	}
+}
+
+// GetContractGasCeiling returns the governance-set ceiling on the (sdk) gas of a contract call,
+// which is 0 (no ceiling) if it was never set
+func (k Keeper) GetContractGasCeiling(ctx sdk.Context) uint64 {
+	// reading a param shouldn't cost the contract caller gas
+	ctx = ctx.WithGasMeter(sdk.NewInfiniteGasMeter())
+
+	var ceiling uint64
+	k.paramSpace.GetIfExists(ctx, types.KeyContractGasCeiling, &ceiling)
+	return ceiling
+}
+
+func (k Keeper) SetContractGasCeiling(ctx sdk.Context, ceiling uint64) {
+	k.paramSpace.Set(ctx, types.KeyContractGasCeiling, ceiling)
+}
+
+// addContractGasCeiling sets env.contract_gas_ceiling, for queries too since the enclave caps
+// them the same way. The enclave converts it to wasm gas, saturating, so any value is safe
+func (k Keeper) addContractGasCeiling(ctx sdk.Context, env *wasmTypes.Env) {
+	env.ContractGasCeiling = k.GetContractGasCeiling(ctx)
+}
+
+// GetMaxQueryDepth returns the governance-set limit on how deep queries between contracts can
+// nest, which is 0 (the enclave's default) if it was never set
+func (k Keeper) GetMaxQueryDepth(ctx sdk.Context) uint32 {
+	// reading a param shouldn't cost the contract caller gas
+	ctx = ctx.WithGasMeter(sdk.NewInfiniteGasMeter())
+
+	var maxQueryDepth uint32
+	k.paramSpace.GetIfExists(ctx, types.KeyMaxQueryDepth, &maxQueryDepth)
+	return maxQueryDepth
+}
+
+func (k Keeper) SetMaxQueryDepth(ctx sdk.Context, maxQueryDepth uint32) {
+	k.paramSpace.Set(ctx, types.KeyMaxQueryDepth, maxQueryDepth)
+}
+
+// addMaxQueryDepth sets env.max_query_depth. Nested queries get an env of their own, so every
+// level of a chain of queries checks its depth against the same param
+func (k Keeper) addMaxQueryDepth(ctx sdk.Context, env *wasmTypes.Env) {
+	env.MaxQueryDepth = k.GetMaxQueryDepth(ctx)
+}
+
+// GetMaxEventTypeLength returns the governance-set limit on the length of the types of contracts'
+// custom events, which is 0 (no limit) if it was never set
+func (k Keeper) GetMaxEventTypeLength(ctx sdk.Context) uint32 {
+	// reading a param shouldn't cost the contract caller gas
+	ctx = ctx.WithGasMeter(sdk.NewInfiniteGasMeter())
+
+	var maxEventTypeLength uint32
+	k.paramSpace.GetIfExists(ctx, types.KeyMaxEventTypeLength, &maxEventTypeLength)
+	return maxEventTypeLength
+}
+
+func (k Keeper) SetMaxEventTypeLength(ctx sdk.Context, maxEventTypeLength uint32) {
+	k.paramSpace.Set(ctx, types.KeyMaxEventTypeLength, maxEventTypeLength)
+}
+
+// addMaxEventTypeLength sets env.max_event_type_length. Queries don't emit events, so they're
+// left without it
+func (k Keeper) addMaxEventTypeLength(ctx sdk.Context, env *wasmTypes.Env) {
+	env.MaxEventTypeLength = k.GetMaxEventTypeLength(ctx)
+}
+
 func (k Keeper) GetContractAddress(ctx sdk.Context, label string) sdk.AccAddress {
 	store := ctx.KVStore(k.storeKey)
 
@@ -1007,6 +1196,11 @@ func (k *Keeper) handleContractResponse(
 		ctx.EventManager().EmitEvents(customEvents)
 	}
 
+	// the enclave already checked the response against what the transaction has left
+	if counter := types.SubmsgCounter(ctx); counter != nil {
+		*counter += uint32(len(msgs))
+	}
+
 	responseHandler := NewContractResponseHandler(NewMessageDispatcher(k.messenger, k))
 	return responseHandler.Handle(ctx, contractAddr, ibcPort, msgs, data, ogTx, ogSigInfo)
 }
@@ -1020,8 +1214,25 @@ func gasForContract(ctx sdk.Context) uint64 {
 	return remaining
 }
 
+// withGasUsedAttribute appends the gas charged for a contract call to the logs of its wasm event,
+// if the node is configured to. Gas is public, so the attribute is plaintext.
+func (k *Keeper) withGasUsedAttribute(logs []v010wasmTypes.LogAttribute, gasUsed uint64) []v010wasmTypes.LogAttribute {
+	if !k.gasUsedAttribute {
+		return logs
+	}
+	return append(logs, v010wasmTypes.LogAttribute{
+		Key:   types.AttributeKeyGasUsed,
+		Value: strconv.FormatUint(chargedGas(gasUsed), 10),
+	})
+}
+
+// chargedGas converts the wasm gas used by a contract to the sdk gas it's charged
+func chargedGas(gas uint64) uint64 {
+	return (gas / types.GasMultiplier) + 1
+}
+
 func consumeGas(ctx sdk.Context, gas uint64) {
-	consumed := (gas / types.GasMultiplier) + 1
+	consumed := chargedGas(gas)
 	ctx.GasMeter().ConsumeGas(consumed, "wasm contract")
 	// throw OutOfGas error if we ran out (got exactly to zero due to better limit enforcing)
 	if ctx.GasMeter().IsOutOfGas() {
@@ -1029,12 +1240,51 @@ func consumeGas(ctx sdk.Context, gas uint64) {
 	}
 }
 
+// consumeGasUpToLimit charges gas like consumeGas, but stops at the gas limit instead of panicking
+func consumeGasUpToLimit(ctx sdk.Context, gas uint64) {
+	meter := ctx.GasMeter()
+	consumed := chargedGas(gas)
+	if remaining := meter.Limit() - meter.GasConsumed(); consumed > remaining {
+		consumed = remaining
+	}
+	meter.ConsumeGas(consumed, "wasm contract")
+}
+
+// isOutOfGasForReply checks whether a contract ran out of gas in a submessage whose caller handles
+// its reply. The enclave then returns an out of gas error signed for the caller's reply, instead of
+// failing the execution.
+func isOutOfGasForReply(response interface{}, execErr error, gasUsed uint64, gasLimit uint64) bool {
+	if execErr == nil || gasUsed < gasLimit {
+		return false
+	}
+	replyInfo, ok := response.(v1wasmTypes.DataWithInternalReplyInfo)
+	return ok && len(replyInfo.InternaReplyEnclaveSig) > 0
+}
+
 // generates a contract address from codeID + instanceID
 func (k Keeper) generateContractAddress(ctx sdk.Context, codeID uint64, creator sdk.AccAddress) sdk.AccAddress {
 	instanceID := k.autoIncrementID(ctx, types.KeyLastInstanceID)
 	return contractAddress(codeID, instanceID, creator)
 }
 
+// PredictContractAddress returns the address that the next contract instantiated by creator
+// will get, as long as no other contract is instantiated before it.
+// The address is derived from the code id, the global instance counter and the creator;
+// the label is not part of the derivation.
+func (k Keeper) PredictContractAddress(ctx sdk.Context, codeID uint64, creator sdk.AccAddress) sdk.AccAddress {
+	instanceID := k.peekAutoIncrementID(ctx, types.KeyLastInstanceID)
+	return contractAddress(codeID, instanceID, creator)
+}
+
+// addNextInstanceID sets env.next_instance_id, which the enclave predicts contract addresses
+// from like PredictContractAddress does
+func (k Keeper) addNextInstanceID(ctx sdk.Context, env *wasmTypes.Env) {
+	// reading the counter shouldn't cost the contract caller gas
+	ctx = ctx.WithGasMeter(sdk.NewInfiniteGasMeter())
+
+	env.NextInstanceID = k.peekAutoIncrementID(ctx, types.KeyLastInstanceID)
+}
+
 func contractAddress(codeID, instanceID uint64, creator sdk.AccAddress) sdk.AccAddress {
 	contractId := codeID<<32 + instanceID
 	hashSourceBytes := make([]byte, 8)
@@ -1168,6 +1418,13 @@ func (k Keeper) reply(ctx sdk.Context, contractAddress sdk.AccAddress, reply v1w
 	random := k.GetRandomSeed(ctx, ctx.BlockHeight())
 
 	env := types.NewEnv(ctx, contractAddress, sdk.Coins{}, contractAddress, contractKey, random)
+	k.addMinGasPrice(ctx, &env)
+	k.addOutputCompression(ctx, &env)
+	k.addSubmsgLimit(ctx, &env)
+	k.addContractGasCeiling(ctx, &env)
+	k.addMaxQueryDepth(ctx, &env)
+	k.addMaxEventTypeLength(ctx, &env)
+	k.addNextInstanceID(ctx, &env)
 
 	// prepare querier
 	querier := QueryHandler{
@@ -1201,7 +1458,7 @@ func (k Keeper) reply(ctx sdk.Context, contractAddress sdk.AccAddress, reply v1w
 			sdk.NewAttribute(types.AttributeKeyContractAddr, contractAddress.String()),
 		))
 
-		data, err := k.handleContractResponse(ctx, contractAddress, contractInfo.IBCPortID, res.Messages, res.Attributes, res.Events, res.Data, ogTx, ogSigInfo)
+		data, err := k.handleContractResponse(ctx, contractAddress, contractInfo.IBCPortID, res.Messages, k.withGasUsedAttribute(res.Attributes, gasUsed), res.Events, res.Data, ogTx, ogSigInfo)
 		if err != nil {
 			return nil, sdkerrors.Wrap(types.ErrReplyFailed, err.Error())
 		}
@@ -1246,6 +1503,13 @@ func (k Keeper) UpdateContractAdmin(ctx sdk.Context, contractAddress, caller, ne
 	}
 
 	env := types.NewEnv(ctx, caller, sdk.Coins{}, contractAddress, contractKey, nil)
+	k.addMinGasPrice(ctx, &env)
+	k.addOutputCompression(ctx, &env)
+	k.addSubmsgLimit(ctx, &env)
+	k.addContractGasCeiling(ctx, &env)
+	k.addMaxQueryDepth(ctx, &env)
+	k.addMaxEventTypeLength(ctx, &env)
+	k.addNextInstanceID(ctx, &env)
 
 	currentAdminAddress, err := sdk.AccAddressFromBech32(contractInfo.Admin)
 	if err != nil {
@@ -1342,6 +1606,13 @@ func (k Keeper) Migrate(ctx sdk.Context, contractAddress sdk.AccAddress, caller
 	random := k.GetRandomSeed(ctx, ctx.BlockHeight())
 
 	env := types.NewEnv(ctx, caller, sdk.Coins{}, contractAddress, contractKey, random)
+	k.addMinGasPrice(ctx, &env)
+	k.addOutputCompression(ctx, &env)
+	k.addSubmsgLimit(ctx, &env)
+	k.addContractGasCeiling(ctx, &env)
+	k.addMaxQueryDepth(ctx, &env)
+	k.addMaxEventTypeLength(ctx, &env)
+	k.addNextInstanceID(ctx, &env)
 
 	adminProof := contractInfo.AdminProof
 	admin := contractInfo.Admin
@@ -1405,14 +1676,14 @@ func (k Keeper) Migrate(ctx sdk.Context, contractAddress sdk.AccAddress, caller
 			return nil, sdkerrors.Wrap(err, "couldn't convert v0.10 messages to v1 messages")
 		}
 
-		data, err := k.handleContractResponse(ctx, contractAddress, contractInfo.IBCPortID, subMessages, res.Log, []v1wasmTypes.Event{}, res.Data, msg, sigInfo)
+		data, err := k.handleContractResponse(ctx, contractAddress, contractInfo.IBCPortID, subMessages, k.withGasUsedAttribute(res.Log, gasUsed), []v1wasmTypes.Event{}, res.Data, msg, sigInfo)
 		if err != nil {
 			return nil, sdkerrors.Wrap(err, "dispatch")
 		}
 
 		return data, nil
 	case *v1wasmTypes.Response:
-		data, err := k.handleContractResponse(ctx, contractAddress, contractInfo.IBCPortID, res.Messages, res.Attributes, res.Events, res.Data, msg, sigInfo)
+		data, err := k.handleContractResponse(ctx, contractAddress, contractInfo.IBCPortID, res.Messages, k.withGasUsedAttribute(res.Attributes, gasUsed), res.Events, res.Data, msg, sigInfo)
 		if err != nil {
 			return nil, sdkerrors.Wrap(err, "dispatch")
 		}
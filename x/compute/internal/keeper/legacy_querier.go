@@ -8,20 +8,22 @@ import (
 
 	sdk "github.com/cosmos/cosmos-sdk/types"
 	sdkerrors "github.com/cosmos/cosmos-sdk/types/errors"
+	"github.com/scrtlabs/SecretNetwork/go-cosmwasm/api"
 	"github.com/scrtlabs/SecretNetwork/x/compute/internal/types"
 	abci "github.com/tendermint/tendermint/abci/types"
 )
 
 const (
-	QueryListContractByCode   = "list-contracts-by-code"
-	QueryGetContract          = "contract-info"
-	QueryGetContractState     = "contract-state"
-	QueryGetCode              = "code"
-	QueryListCode             = "list-code"
-	QueryContractAddress      = "label"
-	QueryContractKey          = "contract-key"
-	QueryContractHash         = "contract-hash"
-	QueryContractHashByCodeID = "contract-hash-by-id"
+	QueryListContractByCode     = "list-contracts-by-code"
+	QueryGetContract            = "contract-info"
+	QueryGetContractState       = "contract-state"
+	QueryGetCode                = "code"
+	QueryListCode               = "list-code"
+	QueryContractAddress        = "label"
+	QueryContractKey            = "contract-key"
+	QueryContractHash           = "contract-hash"
+	QueryContractHashByCodeID   = "contract-hash-by-id"
+	QueryExecutionReceiptPubKey = "execution-receipt-pubkey"
 )
 
 const QueryMethodContractStateSmart = "smart"
@@ -99,6 +101,12 @@ func NewLegacyQuerier(keeper Keeper) sdk.Querier {
 			if err != nil {
 				return nil, sdkerrors.Wrap(sdkerrors.ErrInvalidAddress, err.Error())
 			}
+		case QueryExecutionReceiptPubKey:
+			// the key is derived from the consensus seed, so the node's enclave has the chain's key
+			bz, err = api.GetExecutionReceiptPublicKey()
+			if err != nil {
+				return nil, sdkerrors.Wrap(types.ErrInvalid, err.Error())
+			}
 		default:
 			return nil, sdkerrors.Wrap(sdkerrors.ErrUnknownRequest, fmt.Sprintf("unknown data query endpoint %s", path[0]))
 		}
@@ -339,6 +339,7 @@ func (d MessageDispatcher) DispatchSubmessages(ctx sdk.Context, contractAddr sdk
 			replySigInfo = ogSigInfo
 			reply.ID = dataWithInternalReplyInfo.InternalMsgId
 			reply.IsEncrypted = true
+			reply.IsDataPlaintext = dataWithInternalReplyInfo.DataIsPlaintext
 			replySigInfo.CallbackSignature = dataWithInternalReplyInfo.InternaReplyEnclaveSig
 		}
 
@@ -371,3 +371,24 @@ func TestListContractByCodeOrdering(t *testing.T) {
 		assert.Nil(t, contract.Created)
 	}
 }
+
+func TestQueryExecutionReceiptPubKey(t *testing.T) {
+	encodingConfig := MakeEncodingConfig()
+	var transferPortSource types.ICS20TransferPortSource
+	transferPortSource = MockIBCTransferKeeper{GetPortFn: func(ctx sdk.Context) string {
+		return "myTransferPort"
+	}}
+	encoders := DefaultEncoders(transferPortSource, encodingConfig.Marshaler)
+	ctx, keepers := CreateTestInput(t, false, SupportedFeatures, &encoders, nil)
+
+	q := NewLegacyQuerier(keepers.WasmKeeper)
+	res, err := q(ctx, []string{QueryExecutionReceiptPubKey}, abci.RequestQuery{})
+	require.NoError(t, err)
+	// an ed25519 public key
+	require.Len(t, res, 32)
+
+	// the key doesn't change between queries
+	again, err := q(ctx, []string{QueryExecutionReceiptPubKey}, abci.RequestQuery{})
+	require.NoError(t, err)
+	require.Equal(t, res, again)
+}
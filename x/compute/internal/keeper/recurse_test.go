@@ -398,3 +398,33 @@ func TestLimitRecursiveQueryGas(t *testing.T) {
 		})
 	}
 }
+
+func TestMaxQueryDepthParam(t *testing.T) {
+	contractAddr, _, ctx, keeper := initRecurseContract(t)
+
+	codeHash, err := keeper.GetContractHash(ctx, contractAddr)
+	require.NoError(t, err)
+
+	requireQuery := func(depth uint32, expectRecursionLimit bool) {
+		ctx := ctx.WithGasMeter(sdk.NewGasMeter(10_000_000))
+		msg := buildQuery(t, Recurse{Depth: depth, Work: 1, Contract: contractAddr}, hex.EncodeToString(codeHash))
+
+		_, qErr := queryHelper(t, keeper, ctx, contractAddr, string(msg), true, false, 10_000_000)
+		if !expectRecursionLimit {
+			require.Empty(t, qErr)
+			return
+		}
+		require.NotNil(t, qErr.GenericErr)
+		require.Contains(t, qErr.GenericErr.Msg, "Querier system error: Query recursion limit exceeded")
+	}
+
+	// without the param the enclave's default of 10 applies
+	requireQuery(4, false)
+	requireQuery(11, true)
+
+	keeper.SetMaxQueryDepth(ctx, 3)
+	requireQuery(4, true)
+
+	keeper.SetMaxQueryDepth(ctx, 20)
+	requireQuery(11, false)
+}
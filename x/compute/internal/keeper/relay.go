@@ -48,6 +48,13 @@ func (k Keeper) ibcContractCall(ctx sdk.Context,
 		contractKey,
 		random,
 	)
+	k.addMinGasPrice(ctx, &env)
+	k.addOutputCompression(ctx, &env)
+	k.addSubmsgLimit(ctx, &env)
+	k.addContractGasCeiling(ctx, &env)
+	k.addMaxQueryDepth(ctx, &env)
+	k.addMaxEventTypeLength(ctx, &env)
+	k.addNextInstanceID(ctx, &env)
 
 	// prepare querier
 	querier := QueryHandler{
@@ -2,12 +2,14 @@ package keeper
 
 import (
 	"crypto/sha256"
+	"encoding/asn1"
 	"encoding/base64"
 	"encoding/binary"
 	"encoding/hex"
 	"encoding/json"
 	"fmt"
 	"math"
+	"math/big"
 	"os"
 	"strconv"
 	"strings"
@@ -28,6 +30,9 @@ import (
 	ibctransfertypes "github.com/cosmos/ibc-go/v4/modules/apps/transfer/types"
 	ibcclienttypes "github.com/cosmos/ibc-go/v4/modules/core/02-client/types"
 	ibcchanneltypes "github.com/cosmos/ibc-go/v4/modules/core/04-channel/types"
+	tmed25519 "github.com/tendermint/tendermint/crypto/ed25519"
+	tmproto "github.com/tendermint/tendermint/proto/tendermint/types"
+	tmtypes "github.com/tendermint/tendermint/types"
 )
 
 func setupChainTest(t *testing.T, wasmPath string, additionalCoinsInWallets sdk.Coins, amount uint64) (sdk.Context, Keeper, []uint64, []string, sdk.AccAddress, crypto.PrivKey, sdk.AccAddress, crypto.PrivKey) {
@@ -168,6 +173,37 @@ func TestAddrValidateFunction(t *testing.T) {
 	require.Equal(t, string(data), "\"Apple\"")
 }
 
+func TestAddrHumanizeInvalidLength(t *testing.T) {
+	ctx, keeper, codeID, _, walletA, privKeyA, _, _ := setupTest(t, TestContractPaths[v1Contract], sdk.NewCoins())
+
+	_, _, contractAddress, _, err := initHelper(t, keeper, ctx, codeID, walletA, nil, privKeyA, `{"nop":{}}`, true, true, defaultGasForTests)
+	require.Empty(t, err)
+
+	t.Run("Valid", func(t *testing.T) {
+		canonical := base64.StdEncoding.EncodeToString(walletA)
+		_, _, data, _, _, err := execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, fmt.Sprintf(`{"humanize_address":{"canonical":"%s"}}`, canonical), true, true, defaultGasForTests, 0)
+
+		require.Empty(t, err)
+		require.Equal(t, walletA.String(), string(data))
+	})
+
+	for _, test := range []struct {
+		description string
+		length      int
+	}{
+		{description: "TooShort", length: 10},
+		{description: "TooLong", length: 40},
+	} {
+		t.Run(test.description, func(t *testing.T) {
+			canonical := base64.StdEncoding.EncodeToString(make([]byte, test.length))
+			_, _, _, _, _, err := execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, fmt.Sprintf(`{"humanize_address":{"canonical":"%s"}}`, canonical), true, true, defaultGasForTests, 0)
+
+			require.NotNil(t, err.GenericErr)
+			require.Contains(t, err.GenericErr.Msg, "addr_humanize errored: invalid length")
+		})
+	}
+}
+
 func TestRandomEnv(t *testing.T) {
 	ctx, keeper, codeID, _, walletA, privKeyA, _, _ := setupTest(t, TestContractPaths[randomContract], sdk.NewCoins())
 
@@ -651,86 +687,1321 @@ func TestSecp256k1Verify(t *testing.T) {
 			t.Run("IncorrectLongPubkey", func(t *testing.T) {
 				_, _, _, events, _, err := execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, `{"secp256k1_verify":{"iterations":1,"pubkey":"BISdDHH9J0Bfb9pT8GFn+bW9cEVkgIh4bFsepMWmczXcFWl11YCgu65hzvNDQE2Qo1hwTMQ/42Xif8O/MrxzvxI=","sig":"/hZeEYHs9trj+Akeb+7p3UAtXjcDNYP9/D/hj/ALIUAG9bfrJltxkfpMz/9Jn5K3c5QjLuvaNT2jgr7P/AEW8A==","msg_hash":"ARp3VEHssUlDEwoW8AzdQYGKg90ENy8yWePKcjfjzao="}}`, true, testContract.IsCosmWasmV1, defaultGasForTests, 0)
 
-				require.Empty(t, err)
-				requireEvents(t,
-					[]ContractEvent{
-						{
-							{Key: "contract_address", Value: contractAddress.String()},
-							{Key: "result", Value: "false"},
-						},
+				require.Empty(t, err)
+				requireEvents(t,
+					[]ContractEvent{
+						{
+							{Key: "contract_address", Value: contractAddress.String()},
+							{Key: "result", Value: "false"},
+						},
+					},
+					events,
+				)
+			})
+		})
+	}
+}
+
+func TestEd25519Verify(t *testing.T) {
+	for _, testContract := range testContracts {
+		t.Run(testContract.CosmWasmVersion, func(t *testing.T) {
+			ctx, keeper, codeID, _, walletA, privKeyA, _, _ := setupTest(t, testContract.WasmFilePath, sdk.NewCoins())
+
+			_, _, contractAddress, _, initErr := initHelper(t, keeper, ctx, codeID, walletA, nil, privKeyA, `{"nop":{}}`, true, testContract.IsCosmWasmV1, defaultGasForTests)
+			require.Empty(t, initErr)
+
+			// https://paulmillr.com/noble/
+			t.Run("Correct", func(t *testing.T) {
+				_, _, _, events, _, err := execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, `{"ed25519_verify":{"iterations":1,"pubkey":"LO2+Bt+/FIjomSaPB+I++LXkxgxwfnrKHLyvCic72rA=","sig":"8O7nwhM71/B9srKwe8Ps39z5lAsLMMs6LxdvoPk0HXjEM97TNhKbdU6gEePT2MaaIUSiMEmoG28HIZMgMRTCDg==","msg":"YXNzYWYgd2FzIGhlcmU="}}`, true, testContract.IsCosmWasmV1, defaultGasForTests, 0)
+
+				require.Empty(t, err)
+				requireEvents(t,
+					[]ContractEvent{
+						{
+							{Key: "contract_address", Value: contractAddress.String()},
+							{Key: "result", Value: "true"},
+						},
+					},
+					events,
+				)
+			})
+			t.Run("IncorrectMsg", func(t *testing.T) {
+				_, _, _, events, _, err := execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, `{"ed25519_verify":{"iterations":1,"pubkey":"LO2+Bt+/FIjomSaPB+I++LXkxgxwfnrKHLyvCic72rA=","sig":"8O7nwhM71/B9srKwe8Ps39z5lAsLMMs6LxdvoPk0HXjEM97TNhKbdU6gEePT2MaaIUSiMEmoG28HIZMgMRTCDg==","msg":"YXNzYWYgd2FzIGhlcmUK"}}`, true, testContract.IsCosmWasmV1, defaultGasForTests, 0)
+
+				require.Empty(t, err)
+				requireEvents(t,
+					[]ContractEvent{
+						{
+							{Key: "contract_address", Value: contractAddress.String()},
+							{Key: "result", Value: "false"},
+						},
+					},
+					events,
+				)
+			})
+			t.Run("IncorrectSig", func(t *testing.T) {
+				_, _, _, events, _, err := execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, `{"ed25519_verify":{"iterations":1,"pubkey":"LO2+Bt+/FIjomSaPB+I++LXkxgxwfnrKHLyvCic72rA=","sig":"8O7nwhM71/B9srKwe8Ps39z5lAsLMMs6LxdvoPk0HXjEM97TNhKbdU6gEePT2MaaIUSiMEmoG28HIZMgMRTCDw==","msg":"YXNzYWYgd2FzIGhlcmU="}}`, true, testContract.IsCosmWasmV1, defaultGasForTests, 0)
+
+				require.Empty(t, err)
+				requireEvents(t,
+					[]ContractEvent{
+						{
+							{Key: "contract_address", Value: contractAddress.String()},
+							{Key: "result", Value: "false"},
+						},
+					},
+					events,
+				)
+			})
+			t.Run("IncorrectPubkey", func(t *testing.T) {
+				_, _, _, events, _, err := execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, `{"ed25519_verify":{"iterations":1,"pubkey":"DV1lgRdKw7nt4hvl8XkGZXMzU9S3uM9NLTK0h0qMbUs=","sig":"8O7nwhM71/B9srKwe8Ps39z5lAsLMMs6LxdvoPk0HXjEM97TNhKbdU6gEePT2MaaIUSiMEmoG28HIZMgMRTCDg==","msg":"YXNzYWYgd2FzIGhlcmU="}}`, true, testContract.IsCosmWasmV1, defaultGasForTests, 0)
+
+				require.Empty(t, err)
+				requireEvents(t,
+					[]ContractEvent{
+						{
+							{Key: "contract_address", Value: contractAddress.String()},
+							{Key: "result", Value: "false"},
+						},
+					},
+					events,
+				)
+			})
+		})
+	}
+}
+
+func TestSecp256k1BatchVerify(t *testing.T) {
+	ctx, keeper, codeID, _, walletA, privKeyA, _, _ := setupTest(t, TestContractPaths[v1Contract], sdk.NewCoins())
+
+	_, _, contractAddress, _, initErr := initHelper(t, keeper, ctx, codeID, walletA, nil, privKeyA, `{"nop":{}}`, true, true, defaultGasForTests)
+	require.Empty(t, initErr)
+
+	// https://paulmillr.com/noble/
+	t.Run("AllCorrect", func(t *testing.T) {
+		_, _, _, events, _, err := execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, `{"secp256k1_batch_verify":{"iterations":1,"pubkeys":["A0ZGrlBHMWtCMNAIbIrOxofwCxzZ0dxjT2yzWKwKmo//","A0ZGrlBHMWtCMNAIbIrOxofwCxzZ0dxjT2yzWKwKmo//"],"sigs":["/hZeEYHs9trj+Akeb+7p3UAtXjcDNYP9/D/hj/ALIUAG9bfrJltxkfpMz/9Jn5K3c5QjLuvaNT2jgr7P/AEW8A==","/hZeEYHs9trj+Akeb+7p3UAtXjcDNYP9/D/hj/ALIUAG9bfrJltxkfpMz/9Jn5K3c5QjLuvaNT2jgr7P/AEW8A=="],"msg_hashes":["ARp3VEHssUlDEwoW8AzdQYGKg90ENy8yWePKcjfjzao=","ARp3VEHssUlDEwoW8AzdQYGKg90ENy8yWePKcjfjzao="]}}`, true, true, defaultGasForTests, 0)
+
+		require.Empty(t, err)
+		requireEvents(t,
+			[]ContractEvent{
+				{
+					{Key: "contract_address", Value: contractAddress.String()},
+					{Key: "result", Value: "true"},
+				},
+			},
+			events,
+		)
+	})
+	t.Run("AllCorrectSinglePubkey", func(t *testing.T) {
+		_, _, _, events, _, err := execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, `{"secp256k1_batch_verify":{"iterations":1,"pubkeys":["A0ZGrlBHMWtCMNAIbIrOxofwCxzZ0dxjT2yzWKwKmo//"],"sigs":["/hZeEYHs9trj+Akeb+7p3UAtXjcDNYP9/D/hj/ALIUAG9bfrJltxkfpMz/9Jn5K3c5QjLuvaNT2jgr7P/AEW8A==","/hZeEYHs9trj+Akeb+7p3UAtXjcDNYP9/D/hj/ALIUAG9bfrJltxkfpMz/9Jn5K3c5QjLuvaNT2jgr7P/AEW8A=="],"msg_hashes":["ARp3VEHssUlDEwoW8AzdQYGKg90ENy8yWePKcjfjzao=","ARp3VEHssUlDEwoW8AzdQYGKg90ENy8yWePKcjfjzao="]}}`, true, true, defaultGasForTests, 0)
+
+		require.Empty(t, err)
+		requireEvents(t,
+			[]ContractEvent{
+				{
+					{Key: "contract_address", Value: contractAddress.String()},
+					{Key: "result", Value: "true"},
+				},
+			},
+			events,
+		)
+	})
+	t.Run("OneIncorrect", func(t *testing.T) {
+		_, _, _, events, _, err := execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, `{"secp256k1_batch_verify":{"iterations":1,"pubkeys":["A0ZGrlBHMWtCMNAIbIrOxofwCxzZ0dxjT2yzWKwKmo//","A0ZGrlBHMWtCMNAIbIrOxofwCxzZ0dxjT2yzWKwKmo//"],"sigs":["/hZeEYHs9trj+Akeb+7p3UAtXjcDNYP9/D/hj/ALIUAG9bfrJltxkfpMz/9Jn5K3c5QjLuvaNT2jgr7P/AEW8A==","/hZeEYHs9trj+Akeb+7p3UAtXjcDNYP9/D/hj/ALIUAG9bfrJltxkfpMz/9Jn5K3c5QjLuvaNT2jgr7P/AEW8A=="],"msg_hashes":["ARp3VEHssUlDEwoW8AzdQYGKg90ENy8yWePKcjfjzao=","ARp3VEHssUlDEwoW8AzdQYGKg90ENy8yWePKcjfjzas="]}}`, true, true, defaultGasForTests, 0)
+
+		require.Empty(t, err)
+		requireEvents(t,
+			[]ContractEvent{
+				{
+					{Key: "contract_address", Value: contractAddress.String()},
+					{Key: "result", Value: "false"},
+				},
+			},
+			events,
+		)
+	})
+	t.Run("MismatchedLengths", func(t *testing.T) {
+		_, _, _, _, _, err := execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, `{"secp256k1_batch_verify":{"iterations":1,"pubkeys":["A0ZGrlBHMWtCMNAIbIrOxofwCxzZ0dxjT2yzWKwKmo//"],"sigs":["/hZeEYHs9trj+Akeb+7p3UAtXjcDNYP9/D/hj/ALIUAG9bfrJltxkfpMz/9Jn5K3c5QjLuvaNT2jgr7P/AEW8A==","/hZeEYHs9trj+Akeb+7p3UAtXjcDNYP9/D/hj/ALIUAG9bfrJltxkfpMz/9Jn5K3c5QjLuvaNT2jgr7P/AEW8A=="],"msg_hashes":["ARp3VEHssUlDEwoW8AzdQYGKg90ENy8yWePKcjfjzao="]}}`, true, true, defaultGasForTests, 0)
+
+		require.NotNil(t, err.GenericErr)
+		require.Contains(t, err.GenericErr.Msg, "secp256k1_batch_verify failed with error code 7")
+	})
+}
+
+func TestBls12381AggregateVerify(t *testing.T) {
+	ctx, keeper, codeID, _, walletA, privKeyA, _, _ := setupTest(t, TestContractPaths[v1Contract], sdk.NewCoins())
+
+	_, _, contractAddress, _, initErr := initHelper(t, keeper, ctx, codeID, walletA, nil, privKeyA, `{"nop":{}}`, true, true, defaultGasForTests)
+	require.Empty(t, initErr)
+
+	// keys with secret keys 1000, 1001 and 1002, signing with the BLS_SIG_BLS12381G2_XMD:SHA-256_SSWU_RO_NUL_ ciphersuite
+	pubkeys := []string{
+		"pg51GQ5itqVBQtFHKJpzXEzhGp2ZdUPaU5o9tX3vXtg7pAt05VBl8Cs1qh1QTEBL",
+		"rhIDlFnGBJFnK2pigjVdh2W6YnI4f7kaPpYE+iqBRQzxa4cLtEb8Oj4KGH//b4lF",
+		"lHsyfIoVs5Y0pCavcMBitQYyp0Tt3UG1pGhkFO9M2XRrsR0KU8bC/yG7zzMeB6yS",
+	}
+	msgs := []string{
+		base64.StdEncoding.EncodeToString([]byte("block 1")),
+		base64.StdEncoding.EncodeToString([]byte("block 2")),
+		base64.StdEncoding.EncodeToString([]byte("block 3")),
+	}
+	// each key signing its own message
+	distinctSig := "uUuxAFNpvSFvsKpBTq2wvSbwjZ4NXTjsON5FiM7tJI58f3Xpk12YcQg3gjGOb13EBKL6LYaLX3gT4uKTMuzHs4Blk9h8fZF3Pl4mICBmdmq9XfGg7H5GFWQqpGOsNjkO"
+	// every key signing "block 1"
+	sameSig := "tP4RMCkWi97THmuPYAHH0uaf8J6ShIbKo3p6EzrC3WjZSLDSPD0ALMFdEgCwcfJnBQOLJYLFfrSojPChEf3lOjbyj/klwFmE9qo5iOxeXNv1gACgoK/w+Zrj7kHyYQ3B"
+
+	verifyMsg := func(pubkeys []string, msgs []string, aggSig string) string {
+		msg, err := json.Marshal(map[string]interface{}{
+			"bls12381_aggregate_verify": map[string]interface{}{
+				"iterations": 1,
+				"pubkeys":    pubkeys,
+				"msgs":       msgs,
+				"agg_sig":    aggSig,
+			},
+		})
+		require.NoError(t, err)
+		return string(msg)
+	}
+
+	for _, test := range []struct {
+		description string
+		pubkeys     []string
+		msgs        []string
+		aggSig      string
+		result      string
+	}{
+		{"DistinctMessages", pubkeys, msgs, distinctSig, "true"},
+		{"DistinctMessagesSwapped", pubkeys, []string{msgs[1], msgs[0], msgs[2]}, distinctSig, "false"},
+		{"SameMessage", pubkeys, msgs[:1], sameSig, "true"},
+		{"SameMessageMissingSigner", pubkeys[:2], msgs[:1], sameSig, "false"},
+		{"RepeatedMessages", pubkeys, []string{msgs[0], msgs[0], msgs[0]}, sameSig, "false"},
+	} {
+		t.Run(test.description, func(t *testing.T) {
+			_, _, _, events, _, execErr := execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, verifyMsg(test.pubkeys, test.msgs, test.aggSig), true, true, defaultGasForTests, 0)
+			require.Empty(t, execErr)
+			requireEvents(t,
+				[]ContractEvent{
+					{
+						{Key: "contract_address", Value: contractAddress.String()},
+						{Key: "result", Value: test.result},
+					},
+				},
+				events,
+			)
+		})
+	}
+
+	t.Run("MismatchedLengths", func(t *testing.T) {
+		_, _, _, _, _, execErr := execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, verifyMsg(pubkeys, msgs[:2], distinctSig), true, true, defaultGasForTests, 0)
+		require.NotNil(t, execErr.GenericErr)
+		require.Contains(t, execErr.GenericErr.Msg, "bls12_381_aggregate_verify needs a message per public key, or a single message")
+	})
+}
+
+func TestVerifyDoubleSignEvidence(t *testing.T) {
+	ctx, keeper, codeID, _, walletA, privKeyA, _, _ := setupTest(t, TestContractPaths[v1Contract], sdk.NewCoins())
+
+	_, _, contractAddress, _, initErr := initHelper(t, keeper, ctx, codeID, walletA, nil, privKeyA, `{"nop":{}}`, true, true, defaultGasForTests)
+	require.Empty(t, initErr)
+
+	validatorKey := tmed25519.GenPrivKey()
+	pubkey := base64.StdEncoding.EncodeToString(validatorKey.PubKey().Bytes())
+
+	signVote := func(key tmed25519.PrivKey, height int64, block string) (string, string) {
+		blockHash := sha256.Sum256([]byte(block))
+		vote := tmproto.Vote{
+			Type:   tmproto.PrevoteType,
+			Height: height,
+			Round:  0,
+			BlockID: tmproto.BlockID{
+				Hash:          blockHash[:],
+				PartSetHeader: tmproto.PartSetHeader{Total: 1, Hash: blockHash[:]},
+			},
+			Timestamp: time.Unix(1700000000, 0).UTC(),
+		}
+		signBytes := tmtypes.VoteSignBytes("secret-4", &vote)
+		sig, err := key.Sign(signBytes)
+		require.NoError(t, err)
+		return base64.StdEncoding.EncodeToString(signBytes), base64.StdEncoding.EncodeToString(sig)
+	}
+
+	evidenceMsg := func(voteA, sigA, voteB, sigB string) string {
+		return fmt.Sprintf(`{"verify_double_sign_evidence":{"votes":["%s","%s"],"sigs":["%s","%s"],"pubkey":"%s"}}`, voteA, voteB, sigA, sigB, pubkey)
+	}
+
+	t.Run("ConflictingVotes", func(t *testing.T) {
+		voteA, sigA := signVote(validatorKey, 100, "block a")
+		voteB, sigB := signVote(validatorKey, 100, "block b")
+		_, _, _, events, _, err := execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, evidenceMsg(voteA, sigA, voteB, sigB), true, true, defaultGasForTests, 0)
+
+		require.Empty(t, err)
+		requireEvents(t,
+			[]ContractEvent{
+				{
+					{Key: "contract_address", Value: contractAddress.String()},
+					{Key: "offender", Value: pubkey},
+				},
+			},
+			events,
+		)
+	})
+	t.Run("SameBlock", func(t *testing.T) {
+		voteA, sigA := signVote(validatorKey, 100, "block a")
+		_, _, _, _, _, err := execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, evidenceMsg(voteA, sigA, voteA, sigA), true, true, defaultGasForTests, 0)
+
+		require.NotNil(t, err.GenericErr)
+		require.Contains(t, err.GenericErr.Msg, "verify_double_sign_evidence failed with error code 1001")
+	})
+	t.Run("DifferentHeights", func(t *testing.T) {
+		voteA, sigA := signVote(validatorKey, 100, "block a")
+		voteB, sigB := signVote(validatorKey, 101, "block b")
+		_, _, _, _, _, err := execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, evidenceMsg(voteA, sigA, voteB, sigB), true, true, defaultGasForTests, 0)
+
+		require.NotNil(t, err.GenericErr)
+		require.Contains(t, err.GenericErr.Msg, "verify_double_sign_evidence failed with error code 1001")
+	})
+	t.Run("SignedByAnotherValidator", func(t *testing.T) {
+		voteA, sigA := signVote(validatorKey, 100, "block a")
+		voteB, sigB := signVote(tmed25519.GenPrivKey(), 100, "block b")
+		_, _, _, _, _, err := execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, evidenceMsg(voteA, sigA, voteB, sigB), true, true, defaultGasForTests, 0)
+
+		require.NotNil(t, err.GenericErr)
+		require.Contains(t, err.GenericErr.Msg, "verify_double_sign_evidence failed with error code 1001")
+	})
+}
+
+func TestVerifyJwt(t *testing.T) {
+	ctx, keeper, codeID, _, walletA, privKeyA, _, _ := setupTest(t, TestContractPaths[v1Contract], sdk.NewCoins())
+
+	_, _, contractAddress, _, initErr := initHelper(t, keeper, ctx, codeID, walletA, nil, privKeyA, `{"nop":{}}`, true, true, defaultGasForTests)
+	require.Empty(t, initErr)
+
+	issuerKey := tmed25519.GenPrivKey()
+	jwk := fmt.Sprintf(`{"kty":"OKP","crv":"Ed25519","x":"%s"}`, base64.RawURLEncoding.EncodeToString(issuerKey.PubKey().Bytes()))
+
+	signJwt := func(claims string) string {
+		header := base64.RawURLEncoding.EncodeToString([]byte(`{"alg":"EdDSA","typ":"JWT"}`))
+		signingInput := header + "." + base64.RawURLEncoding.EncodeToString([]byte(claims))
+		sig, err := issuerKey.Sign([]byte(signingInput))
+		require.NoError(t, err)
+		return signingInput + "." + base64.RawURLEncoding.EncodeToString(sig)
+	}
+
+	jwtMsg := func(token string) string {
+		msg, err := json.Marshal(map[string]interface{}{
+			"verify_jwt": map[string]string{"token": token, "jwk": jwk},
+		})
+		require.NoError(t, err)
+		return string(msg)
+	}
+
+	t.Run("Valid", func(t *testing.T) {
+		claims := fmt.Sprintf(`{"sub":"alice","exp":%d}`, ctx.BlockTime().Add(time.Hour).Unix())
+		_, _, _, events, _, err := execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, jwtMsg(signJwt(claims)), true, true, defaultGasForTests, 0)
+
+		require.Empty(t, err)
+		requireEvents(t,
+			[]ContractEvent{
+				{
+					{Key: "contract_address", Value: contractAddress.String()},
+					{Key: "claims", Value: claims},
+				},
+			},
+			events,
+		)
+	})
+	t.Run("Expired", func(t *testing.T) {
+		claims := fmt.Sprintf(`{"sub":"alice","exp":%d}`, ctx.BlockTime().Add(-time.Hour).Unix())
+		_, _, _, _, _, err := execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, jwtMsg(signJwt(claims)), true, true, defaultGasForTests, 0)
+
+		require.NotNil(t, err.GenericErr)
+		require.Contains(t, err.GenericErr.Msg, "verify_jwt failed with error code 1004")
+	})
+}
+
+func TestVerifySignedPayload(t *testing.T) {
+	ctx, keeper, codeID, _, walletA, privKeyA, _, _ := setupTest(t, TestContractPaths[v1Contract], sdk.NewCoins())
+
+	_, _, contractAddress, _, initErr := initHelper(t, keeper, ctx, codeID, walletA, nil, privKeyA, `{"nop":{}}`, true, true, defaultGasForTests)
+	require.Empty(t, initErr)
+
+	oracleKey := tmed25519.GenPrivKey()
+	payload := `{"symbol":"SCRT","price":"0.42"}`
+
+	// the oracle signs the big-endian signer time followed by the payload
+	payloadMsg := func(signedTime int64, signerTime int64) string {
+		signedBytes := make([]byte, 8)
+		binary.BigEndian.PutUint64(signedBytes, uint64(signedTime))
+		sig, err := oracleKey.Sign(append(signedBytes, []byte(payload)...))
+		require.NoError(t, err)
+
+		msg, err := json.Marshal(map[string]interface{}{
+			"verify_signed_payload": map[string]interface{}{
+				"payload":     payload,
+				"signature":   sig,
+				"pubkey":      oracleKey.PubKey().Bytes(),
+				"max_age":     60,
+				"signer_time": signerTime,
+			},
+		})
+		require.NoError(t, err)
+		return string(msg)
+	}
+
+	t.Run("Fresh", func(t *testing.T) {
+		signerTime := ctx.BlockTime().Add(-30 * time.Second).Unix()
+		_, _, _, events, _, err := execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, payloadMsg(signerTime, signerTime), true, true, defaultGasForTests, 0)
+
+		require.Empty(t, err)
+		requireEvents(t,
+			[]ContractEvent{
+				{
+					{Key: "contract_address", Value: contractAddress.String()},
+					{Key: "payload", Value: payload},
+				},
+			},
+			events,
+		)
+	})
+	t.Run("Stale", func(t *testing.T) {
+		signerTime := ctx.BlockTime().Add(-time.Hour).Unix()
+		_, _, _, _, _, err := execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, payloadMsg(signerTime, signerTime), true, true, defaultGasForTests, 0)
+
+		require.NotNil(t, err.GenericErr)
+		require.Contains(t, err.GenericErr.Msg, "verify_signed_payload failed with error code 1006")
+	})
+	t.Run("BadSignature", func(t *testing.T) {
+		// an old payload passed off with a fresh timestamp
+		signedTime := ctx.BlockTime().Add(-time.Hour).Unix()
+		_, _, _, _, _, err := execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, payloadMsg(signedTime, ctx.BlockTime().Unix()), true, true, defaultGasForTests, 0)
+
+		require.NotNil(t, err.GenericErr)
+		require.Contains(t, err.GenericErr.Msg, "verify_signed_payload failed with error code 1007")
+	})
+}
+
+func TestVerifyEthPersonalSign(t *testing.T) {
+	ctx, keeper, codeID, _, walletA, privKeyA, _, _ := setupTest(t, TestContractPaths[v1Contract], sdk.NewCoins())
+
+	_, _, contractAddress, _, initErr := initHelper(t, keeper, ctx, codeID, walletA, nil, privKeyA, `{"nop":{}}`, true, true, defaultGasForTests)
+	require.Empty(t, initErr)
+
+	// personal_sign of "Sign in to Secret Network" by the first Hardhat development account
+	ethAddress, decodeErr := hex.DecodeString("f39fd6e51aad88f6f4ce6ab8827279cfffb92266")
+	require.NoError(t, decodeErr)
+	signature, decodeErr := hex.DecodeString("ed25c1c74368cc1ebcb97e4ec250d0630beabb308746681d6068452f1ce059847f47b41b9da0404308cd979ce357d616bc8f273a2ce67173806b0ff5e9c556cc1c")
+	require.NoError(t, decodeErr)
+
+	for _, test := range []struct {
+		description string
+		message     string
+		signature   []byte
+		valid       string
+	}{
+		{"Valid", "Sign in to Secret Network", signature, "true"},
+		{"OtherMessage", "Sign in to another network", signature, "false"},
+	} {
+		t.Run(test.description, func(t *testing.T) {
+			msg, err := json.Marshal(map[string]interface{}{
+				"verify_eth_personal_sign": map[string]interface{}{
+					"message":     test.message,
+					"signature":   test.signature,
+					"eth_address": ethAddress,
+				},
+			})
+			require.NoError(t, err)
+
+			_, _, _, events, _, execErr := execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, string(msg), true, true, defaultGasForTests, 0)
+			require.Empty(t, execErr)
+			requireEvents(t,
+				[]ContractEvent{
+					{
+						{Key: "contract_address", Value: contractAddress.String()},
+						{Key: "valid", Value: test.valid},
+					},
+				},
+				events,
+			)
+		})
+	}
+
+	t.Run("MalformedSignature", func(t *testing.T) {
+		msg, err := json.Marshal(map[string]interface{}{
+			"verify_eth_personal_sign": map[string]interface{}{
+				"message":     "Sign in to Secret Network",
+				"signature":   signature[:64],
+				"eth_address": ethAddress,
+			},
+		})
+		require.NoError(t, err)
+
+		_, _, _, _, _, execErr := execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, string(msg), true, true, defaultGasForTests, 0)
+		require.NotNil(t, execErr.GenericErr)
+		require.Contains(t, execErr.GenericErr.Msg, "verify_eth_personal_sign failed with error code 4")
+	})
+}
+
+func TestVerifyWebauthn(t *testing.T) {
+	ctx, keeper, codeID, _, walletA, privKeyA, _, _ := setupTest(t, TestContractPaths[v1Contract], sdk.NewCoins())
+
+	_, _, contractAddress, _, initErr := initHelper(t, keeper, ctx, codeID, walletA, nil, privKeyA, `{"nop":{}}`, true, true, defaultGasForTests)
+	require.Empty(t, initErr)
+
+	// an assertion by a P-256 credential for rp id "localhost"
+	authenticatorData, decodeErr := hex.DecodeString("49960de5880e8c687434170f6476605b8fe4aeb9a28632c7995cf3ba831d97630500000001")
+	require.NoError(t, decodeErr)
+	clientDataJSON := `{"type":"webauthn.get","challenge":"c2VjcmV0IG5ldHdvcmsgd2ViYXV0aG4gY2hhbGxlbmdl","origin":"http://localhost:8080","crossOrigin":false}`
+	signature, decodeErr := hex.DecodeString("3046022100a17f71281a6a9a6fa8abdf7d6d4da7bf9fc8663a15d8c47d59f4a38b9db4d6aa022100aa820985b674d532b9fc1e7cc42a488160f7eaecf2a99faaa11db5ad2809a33b")
+	require.NoError(t, decodeErr)
+	coseKey, decodeErr := hex.DecodeString("a5010203262001215820d347685e8b0d333c08941f9e2760c12139620bfde7db865653547dc4f4e528c4225820d0da0543fd0f11cc85bd0e26d1f721c921607e12c499be77d401f9936605c458")
+	require.NoError(t, decodeErr)
+	sec1Key, decodeErr := hex.DecodeString("04d347685e8b0d333c08941f9e2760c12139620bfde7db865653547dc4f4e528c4d0da0543fd0f11cc85bd0e26d1f721c921607e12c499be77d401f9936605c458")
+	require.NoError(t, decodeErr)
+
+	verifyMsg := func(clientDataJSON string, publicKey []byte) string {
+		msg, err := json.Marshal(map[string]interface{}{
+			"verify_webauthn": map[string]interface{}{
+				"authenticator_data": authenticatorData,
+				"client_data_json":   clientDataJSON,
+				"signature":          signature,
+				"public_key":         publicKey,
+			},
+		})
+		require.NoError(t, err)
+		return string(msg)
+	}
+
+	for _, test := range []struct {
+		description    string
+		clientDataJSON string
+		publicKey      []byte
+		valid          string
+	}{
+		{"ValidCoseKey", clientDataJSON, coseKey, "true"},
+		{"ValidSec1Key", clientDataJSON, sec1Key, "true"},
+		{"OtherChallenge", strings.Replace(clientDataJSON, "c2Vj", "b3Ro", 1), coseKey, "false"},
+	} {
+		t.Run(test.description, func(t *testing.T) {
+			_, _, _, events, _, execErr := execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, verifyMsg(test.clientDataJSON, test.publicKey), true, true, defaultGasForTests, 0)
+			require.Empty(t, execErr)
+			requireEvents(t,
+				[]ContractEvent{
+					{
+						{Key: "contract_address", Value: contractAddress.String()},
+						{Key: "valid", Value: test.valid},
+					},
+				},
+				events,
+			)
+		})
+	}
+
+	t.Run("RegistrationClientData", func(t *testing.T) {
+		registration := strings.Replace(clientDataJSON, "webauthn.get", "webauthn.create", 1)
+
+		_, _, _, _, _, execErr := execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, verifyMsg(registration, coseKey), true, true, defaultGasForTests, 0)
+		require.NotNil(t, execErr.GenericErr)
+		require.Contains(t, execErr.GenericErr.Msg, "verify_webauthn failed with error code 1012")
+	})
+}
+
+func TestVerifyAdr036(t *testing.T) {
+	ctx, keeper, codeID, _, walletA, privKeyA, _, _ := setupTest(t, TestContractPaths[v1Contract], sdk.NewCoins())
+
+	_, _, contractAddress, _, initErr := initHelper(t, keeper, ctx, codeID, walletA, nil, privKeyA, `{"nop":{}}`, true, true, defaultGasForTests)
+	require.Empty(t, initErr)
+
+	// a signArbitrary signature, as produced by a wallet's offline signing
+	signer := "secret1ss3eqwuf2e4prm0d2u9dhtn75s5xdzqdj9aun8"
+	pubkey, decodeErr := base64.StdEncoding.DecodeString("Ajs0LIgKgar/3N2bkHyH5rBLjwyriUCgQ7SPpEe8KKrJ")
+	require.NoError(t, decodeErr)
+	signature, decodeErr := base64.StdEncoding.DecodeString("2In8tgiowoeN5Jn5HewOPC+LHVVK9qdS242mf7CRlcMeX/ojPXzZiH9y3IkeMBM47xep3PJ7uTSScEP52i+vcw==")
+	require.NoError(t, decodeErr)
+	data := []byte("Sign in to example.com at block 1234")
+
+	verifyMsg := func(data []byte, signature []byte, signerAddr string) string {
+		msg, err := json.Marshal(map[string]interface{}{
+			"verify_adr036": map[string]interface{}{
+				"data":        data,
+				"signature":   signature,
+				"pubkey":      pubkey,
+				"signer_addr": signerAddr,
+			},
+		})
+		require.NoError(t, err)
+		return string(msg)
+	}
+
+	for _, test := range []struct {
+		description string
+		data        []byte
+		signerAddr  string
+		valid       string
+	}{
+		{"Valid", data, signer, "true"},
+		{"OtherData", []byte("Sign in to example.com at block 1235"), signer, "false"},
+		{"OtherSigner", data, walletA.String(), "false"},
+	} {
+		t.Run(test.description, func(t *testing.T) {
+			_, _, _, events, _, execErr := execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, verifyMsg(test.data, signature, test.signerAddr), true, true, defaultGasForTests, 0)
+			require.Empty(t, execErr)
+			requireEvents(t,
+				[]ContractEvent{
+					{
+						{Key: "contract_address", Value: contractAddress.String()},
+						{Key: "valid", Value: test.valid},
+					},
+				},
+				events,
+			)
+		})
+	}
+
+	t.Run("MalformedSignature", func(t *testing.T) {
+		_, _, _, _, _, execErr := execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, verifyMsg(data, signature[:63], signer), true, true, defaultGasForTests, 0)
+		require.NotNil(t, execErr.GenericErr)
+		require.Contains(t, execErr.GenericErr.Msg, "verify_adr036 failed with error code 4")
+	})
+}
+
+func TestSecp256k1VerifyDer(t *testing.T) {
+	ctx, keeper, codeID, _, walletA, privKeyA, _, _ := setupTest(t, TestContractPaths[v1Contract], sdk.NewCoins())
+
+	_, _, contractAddress, _, initErr := initHelper(t, keeper, ctx, codeID, walletA, nil, privKeyA, `{"nop":{}}`, true, true, defaultGasForTests)
+	require.Empty(t, initErr)
+
+	// the secp256k1 curve order
+	curveOrder, ok := new(big.Int).SetString("fffffffffffffffffffffffffffffffebaaedce6af48a03bbfd25e8cd0364141", 16)
+	require.True(t, ok)
+
+	message := []byte("signed by openssl")
+	messageHash := sha256.Sum256(message)
+	compactSig, err := privKeyA.Sign(message)
+	require.NoError(t, err)
+
+	r := new(big.Int).SetBytes(compactSig[:32])
+	s := new(big.Int).SetBytes(compactSig[32:])
+	derSig := func(r *big.Int, s *big.Int) []byte {
+		der, err := asn1.Marshal(struct{ R, S *big.Int }{r, s})
+		require.NoError(t, err)
+		return der
+	}
+
+	verifyDerMsg := func(signature []byte) string {
+		msg, err := json.Marshal(map[string]interface{}{
+			"secp256k1_verify_der": map[string]interface{}{
+				"message_hash": messageHash[:],
+				"signature":    signature,
+				"public_key":   privKeyA.PubKey().Bytes(),
+			},
+		})
+		require.NoError(t, err)
+		return string(msg)
+	}
+
+	for _, test := range []struct {
+		description string
+		signature   []byte
+		valid       string
+	}{
+		{"Valid", derSig(r, s), "true"},
+		{"HighS", derSig(r, new(big.Int).Sub(curveOrder, s)), "true"},
+		{"OtherSignature", derSig(s, r), "false"},
+	} {
+		t.Run(test.description, func(t *testing.T) {
+			_, _, _, events, _, execErr := execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, verifyDerMsg(test.signature), true, true, defaultGasForTests, 0)
+			require.Empty(t, execErr)
+			requireEvents(t,
+				[]ContractEvent{
+					{
+						{Key: "contract_address", Value: contractAddress.String()},
+						{Key: "valid", Value: test.valid},
+					},
+				},
+				events,
+			)
+		})
+	}
+
+	t.Run("MalformedDer", func(t *testing.T) {
+		_, _, _, _, _, execErr := execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, verifyDerMsg(compactSig), true, true, defaultGasForTests, 0)
+		require.NotNil(t, execErr.GenericErr)
+		require.Contains(t, execErr.GenericErr.Msg, "secp256k1_verify_der failed with error code 4")
+	})
+}
+
+func TestSecp256r1Verify(t *testing.T) {
+	ctx, keeper, codeID, _, walletA, privKeyA, _, _ := setupTest(t, TestContractPaths[v1Contract], sdk.NewCoins())
+
+	_, _, contractAddress, _, initErr := initHelper(t, keeper, ctx, codeID, walletA, nil, privKeyA, `{"nop":{}}`, true, true, defaultGasForTests)
+	require.Empty(t, initErr)
+
+	// RFC 6979, A.2.5: ECDSA with P-256 and SHA-256
+	pubkey, decodeErr := base64.StdEncoding.DecodeString("BGD+1LolWp0xyWHrdMY1bWjASbiSO2H6bOZpYi5g8p+2eQP+EAi4vJmkGunpVii8ZPLxsgwtfp9Rd6PClNRGIpk=")
+	require.NoError(t, decodeErr)
+	compressedPubkey, decodeErr := base64.StdEncoding.DecodeString("A2D+1LolWp0xyWHrdMY1bWjASbiSO2H6bOZpYi5g8p+2")
+	require.NoError(t, decodeErr)
+	// the signature of "sample" has a high S
+	sampleSig, decodeErr := base64.StdEncoding.DecodeString("79SLKqy2qP0RQN2c1F6B1p0sh3tWqvmRw00OqE6vNxb3yxyULWV8QdQ2x6G24p9l8+kA27mv9AZNxKsvhDrNqA==")
+	require.NoError(t, decodeErr)
+	testSig, decodeErr := base64.StdEncoding.DecodeString("8auwI1GDUc1x2IFWex6mY+0+/PbFEys1TyjTsLfTg2cBn0ETdCorFL0lkmtJxkkVXyZ+YNOBS0wMyEJQ5G8Agw==")
+	require.NoError(t, decodeErr)
+	sampleHash := sha256.Sum256([]byte("sample"))
+	testHash := sha256.Sum256([]byte("test"))
+
+	verifyMsg := func(msgHash []byte, sig []byte, pubkey []byte) string {
+		msg, err := json.Marshal(map[string]interface{}{
+			"secp256r1_verify": map[string]interface{}{
+				"iterations": 1,
+				"msg_hash":   msgHash,
+				"sig":        sig,
+				"pubkey":     pubkey,
+			},
+		})
+		require.NoError(t, err)
+		return string(msg)
+	}
+
+	for _, test := range []struct {
+		description string
+		msgHash     []byte
+		sig         []byte
+		pubkey      []byte
+		result      string
+	}{
+		{"SampleLongPubkey", sampleHash[:], sampleSig, pubkey, "true"},
+		{"SampleCompactPubkey", sampleHash[:], sampleSig, compressedPubkey, "true"},
+		{"TestLongPubkey", testHash[:], testSig, pubkey, "true"},
+		{"TestCompactPubkey", testHash[:], testSig, compressedPubkey, "true"},
+		{"IncorrectMsgHash", testHash[:], sampleSig, pubkey, "false"},
+	} {
+		t.Run(test.description, func(t *testing.T) {
+			_, _, _, events, _, execErr := execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, verifyMsg(test.msgHash, test.sig, test.pubkey), true, true, defaultGasForTests, 0)
+			require.Empty(t, execErr)
+			requireEvents(t,
+				[]ContractEvent{
+					{
+						{Key: "contract_address", Value: contractAddress.String()},
+						{Key: "result", Value: test.result},
+					},
+				},
+				events,
+			)
+		})
+	}
+
+	t.Run("MalformedSignature", func(t *testing.T) {
+		_, _, _, _, _, execErr := execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, verifyMsg(sampleHash[:], sampleSig[:63], pubkey), true, true, defaultGasForTests, 0)
+		require.NotNil(t, execErr.GenericErr)
+		require.Contains(t, execErr.GenericErr.Msg, "secp256r1_verify failed with error code 4")
+	})
+}
+
+func TestContractAllBalances(t *testing.T) {
+	ctx, keeper, codeID, _, walletA, privKeyA, _, _ := setupTest(t, TestContractPaths[v1Contract], sdk.NewCoins(sdk.NewInt64Coin("assaf", 5000)))
+
+	_, _, contractAddress, _, initErr := initHelperImpl(t, keeper, ctx, codeID, walletA, nil, privKeyA, `{"nop":{}}`, true, true, defaultGasForTests, -1, sdk.NewCoins(sdk.NewInt64Coin("denom", 2), sdk.NewInt64Coin("assaf", 2)))
+	require.Empty(t, initErr)
+
+	// the funds sent with the message are already included
+	_, _, _, events, _, err := execHelperMultipleCoins(t, keeper, ctx, contractAddress, walletA, privKeyA, `{"contract_all_balances":{}}`, true, true, defaultGasForTests, sdk.NewCoins(sdk.NewInt64Coin("denom", 1), sdk.NewInt64Coin("assaf", 3)), 0)
+
+	require.Empty(t, err)
+	requireEvents(t,
+		[]ContractEvent{
+			{
+				{Key: "contract_address", Value: contractAddress.String()},
+				{Key: "balances", Value: "5assaf,3denom"},
+			},
+		},
+		events,
+	)
+}
+
+func TestStorageList(t *testing.T) {
+	ctx, keeper, codeID, _, walletA, privKeyA, _, _ := setupTest(t, TestContractPaths[v1Contract], sdk.NewCoins())
+
+	_, _, contractAddress, _, initErr := initHelper(t, keeper, ctx, codeID, walletA, nil, privKeyA, `{"nop":{}}`, true, true, defaultGasForTests)
+	require.Empty(t, initErr)
+
+	_, _, _, events, _, err := execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, `{"storage_push":{"key":"list","values":["a","b","c"]}}`, true, true, defaultGasForTests, 0)
+	require.Empty(t, err)
+	requireEvents(t,
+		[]ContractEvent{
+			{
+				{Key: "contract_address", Value: contractAddress.String()},
+				{Key: "length", Value: "3"},
+			},
+		},
+		events,
+	)
+
+	queryRes, qErr := queryHelper(t, keeper, ctx, contractAddress, `{"storage_len":{"key":"list"}}`, true, true, math.MaxUint64)
+	require.Empty(t, qErr)
+	require.Equal(t, "3", queryRes)
+
+	// items come out in reverse order, and popping an empty list returns nothing
+	_, _, _, events, _, err = execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, `{"storage_pop":{"key":"list","count":4}}`, true, true, defaultGasForTests, 0)
+	require.Empty(t, err)
+	requireEvents(t,
+		[]ContractEvent{
+			{
+				{Key: "contract_address", Value: contractAddress.String()},
+				{Key: "popped", Value: "c,b,a,<empty>"},
+			},
+		},
+		events,
+	)
+
+	queryRes, qErr = queryHelper(t, keeper, ctx, contractAddress, `{"storage_len":{"key":"list"}}`, true, true, math.MaxUint64)
+	require.Empty(t, qErr)
+	require.Equal(t, "0", queryRes)
+}
+
+func TestStorageSecureRemove(t *testing.T) {
+	ctx, keeper, codeID, _, walletA, privKeyA, _, _ := setupTest(t, TestContractPaths[v1Contract], sdk.NewCoins())
+
+	_, _, contractAddress, _, initErr := initHelper(t, keeper, ctx, codeID, walletA, nil, privKeyA, `{"nop":{}}`, true, true, defaultGasForTests)
+	require.Empty(t, initErr)
+
+	_, _, _, _, _, err := execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, `{"set_state":{"key":"secret","value":"a"}}`, true, true, defaultGasForTests, 0)
+	require.Empty(t, err)
+
+	// set_state keeps its keys in the "my_prefix" namespace, which prefixes them with its length
+	_, _, _, events, _, err := execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, `{"storage_secure_remove":{"key":"\u0000\tmy_prefixsecret"}}`, true, true, defaultGasForTests, 0)
+	require.Empty(t, err)
+	requireEvents(t,
+		[]ContractEvent{
+			{
+				{Key: "contract_address", Value: contractAddress.String()},
+				{Key: "removed", Value: "true"},
+			},
+		},
+		events,
+	)
+
+	// the key is gone in later calls as well
+	_, _, data, _, _, err := execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, `{"get_state":{"key":"secret"}}`, true, true, defaultGasForTests, 0)
+	require.Empty(t, err)
+	require.Empty(t, data)
+}
+
+func TestStorageCas(t *testing.T) {
+	ctx, keeper, codeID, _, walletA, privKeyA, _, _ := setupTest(t, TestContractPaths[v1Contract], sdk.NewCoins())
+
+	_, _, contractAddress, _, initErr := initHelper(t, keeper, ctx, codeID, walletA, nil, privKeyA, `{"nop":{}}`, true, true, defaultGasForTests)
+	require.Empty(t, initErr)
+
+	for _, test := range []struct {
+		description string
+		msg         string
+		swapped     string
+	}{
+		{"CreateMissingKey", `{"storage_cas":{"key":"counter","expected":null,"new":"1"}}`, "true"},
+		{"KeyAlreadyExists", `{"storage_cas":{"key":"counter","expected":null,"new":"5"}}`, "false"},
+		{"ExpectedValue", `{"storage_cas":{"key":"counter","expected":"1","new":"2"}}`, "true"},
+		{"StaleValue", `{"storage_cas":{"key":"counter","expected":"1","new":"3"}}`, "false"},
+		// the failed swaps above didn't write anything
+		{"UnchangedAfterFailure", `{"storage_cas":{"key":"counter","expected":"2","new":"3"}}`, "true"},
+	} {
+		t.Run(test.description, func(t *testing.T) {
+			_, _, _, events, _, err := execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, test.msg, true, true, defaultGasForTests, 0)
+			require.Empty(t, err)
+			requireEvents(t,
+				[]ContractEvent{
+					{
+						{Key: "contract_address", Value: contractAddress.String()},
+						{Key: "swapped", Value: test.swapped},
+					},
+				},
+				events,
+			)
+		})
+	}
+}
+
+func TestStorageWipePrefix(t *testing.T) {
+	ctx, keeper, codeID, _, walletA, privKeyA, _, _ := setupTest(t, TestContractPaths[v1Contract], sdk.NewCoins())
+
+	_, _, contractAddress, _, initErr := initHelper(t, keeper, ctx, codeID, walletA, nil, privKeyA, `{"nop":{}}`, true, true, defaultGasForTests)
+	require.Empty(t, initErr)
+	_, _, otherContractAddress, _, initErr := initHelper(t, keeper, ctx, codeID, walletA, nil, privKeyA, `{"nop":{}}`, true, true, defaultGasForTests)
+	require.Empty(t, initErr)
+
+	for _, key := range []string{"my_prefix/a", "my_prefix/b", "my_prefix/c", "other"} {
+		_, _, _, _, _, err := execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, fmt.Sprintf(`{"set_state":{"key":"%s","value":"v"}}`, key), true, true, defaultGasForTests, 0)
+		require.Empty(t, err)
+	}
+	_, _, _, _, _, err := execHelper(t, keeper, ctx, otherContractAddress, walletA, privKeyA, `{"set_state":{"key":"my_prefix/a","value":"v"}}`, true, true, defaultGasForTests, 0)
+	require.Empty(t, err)
+
+	// set_state keeps its keys in the "my_prefix" namespace, which prefixes them with its length
+	_, _, _, events, _, err := execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, `{"storage_wipe_prefix":{"prefix":"\u0000\tmy_prefixmy_prefix"}}`, true, true, defaultGasForTests, 0)
+	require.Empty(t, err)
+	requireEvents(t,
+		[]ContractEvent{
+			{
+				{Key: "contract_address", Value: contractAddress.String()},
+				{Key: "removed", Value: "3"},
+			},
+		},
+		events,
+	)
+
+	getState := func(contractAddress sdk.AccAddress, key string) []byte {
+		_, _, data, _, _, err := execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, fmt.Sprintf(`{"get_state":{"key":"%s"}}`, key), true, true, defaultGasForTests, 0)
+		require.Empty(t, err)
+		return data
+	}
+
+	for _, key := range []string{"my_prefix/a", "my_prefix/b", "my_prefix/c"} {
+		require.Empty(t, getState(contractAddress, key))
+	}
+	// keys outside the prefix, and the other contract's keys under it, are untouched
+	require.Equal(t, "v", string(getState(contractAddress, "other")))
+	require.Equal(t, "v", string(getState(otherContractAddress, "my_prefix/a")))
+}
+
+func TestDbReadMultiple(t *testing.T) {
+	ctx, keeper, codeID, _, walletA, privKeyA, _, _ := setupTest(t, TestContractPaths[v1Contract], sdk.NewCoins())
+
+	_, _, contractAddress, _, initErr := initHelper(t, keeper, ctx, codeID, walletA, nil, privKeyA, `{"nop":{}}`, true, true, defaultGasForTests)
+	require.Empty(t, initErr)
+
+	for key, value := range map[string]string{"a": "1", "b": "2", "empty": ""} {
+		_, _, _, _, _, err := execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, fmt.Sprintf(`{"set_state":{"key":"%s","value":"%s"}}`, key, value), true, true, defaultGasForTests, 0)
+		require.Empty(t, err)
+	}
+
+	// values come back in the order of the keys, and a missing key isn't mistaken for an empty value
+	_, _, _, events, _, err := execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, `{"get_states":{"keys":["b","missing","empty","a"]}}`, true, true, defaultGasForTests, 0)
+	require.Empty(t, err)
+	requireEvents(t,
+		[]ContractEvent{
+			{
+				{Key: "contract_address", Value: contractAddress.String()},
+				{Key: "values", Value: "b=2,missing=<none>,empty=,a=1"},
+			},
+		},
+		events,
+	)
+}
+
+func TestStorageMapIterationOrder(t *testing.T) {
+	ctx, keeper, codeID, _, walletA, privKeyA, _, _ := setupTest(t, TestContractPaths[v1Contract], sdk.NewCoins())
+
+	_, _, contractAddress, _, initErr := initHelper(t, keeper, ctx, codeID, walletA, nil, privKeyA, `{"nop":{}}`, true, true, defaultGasForTests)
+	require.Empty(t, initErr)
+
+	// deliberately out of lexical order
+	for _, key := range []string{"zebra", "apple", "mango", "kiwi"} {
+		_, _, _, _, _, err := execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, fmt.Sprintf(`{"storage_map_insert":{"map":"fruits","key":"%s","value":"%s1"}}`, key, key[:1]), true, true, defaultGasForTests, 0)
+		require.Empty(t, err)
+	}
+
+	// replacing a value keeps its position
+	_, _, _, events, _, err := execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, `{"storage_map_insert":{"map":"fruits","key":"zebra","value":"z2"}}`, true, true, defaultGasForTests, 0)
+	require.Empty(t, err)
+	requireEvents(t,
+		[]ContractEvent{
+			{
+				{Key: "contract_address", Value: contractAddress.String()},
+				{Key: "new", Value: "false"},
+			},
+		},
+		events,
+	)
+
+	// the last entry takes the removed entry's position
+	_, _, _, events, _, err = execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, `{"storage_map_remove":{"map":"fruits","key":"apple"}}`, true, true, defaultGasForTests, 0)
+	require.Empty(t, err)
+	requireEvents(t,
+		[]ContractEvent{
+			{
+				{Key: "contract_address", Value: contractAddress.String()},
+				{Key: "removed", Value: "a1"},
+			},
+		},
+		events,
+	)
+
+	// iterating twice, in separate transactions, gives the same order
+	for i := 0; i < 2; i++ {
+		_, _, _, events, _, err = execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, `{"storage_map_entries":{"map":"fruits"}}`, true, true, defaultGasForTests, 0)
+		require.Empty(t, err)
+		requireEvents(t,
+			[]ContractEvent{
+				{
+					{Key: "contract_address", Value: contractAddress.String()},
+					{Key: "entries", Value: "zebra=z2,kiwi=k1,mango=m1"},
+					{Key: "len", Value: "3"},
+				},
+			},
+			events,
+		)
+	}
+}
+
+func TestMinGasPrice(t *testing.T) {
+	ctx, keeper, codeID, _, walletA, privKeyA, _, _ := setupTest(t, TestContractPaths[v1Contract], sdk.NewCoins())
+
+	_, _, contractAddress, _, initErr := initHelper(t, keeper, ctx, codeID, walletA, nil, privKeyA, `{"nop":{}}`, true, true, defaultGasForTests)
+	require.Empty(t, initErr)
+
+	for _, test := range []struct {
+		description string
+		minGasPrice string
+		expected    string
+	}{
+		{"NotSet", "", "none"},
+		{"SingleDenom", "0.1uscrt", "0.100000000000000000uscrt"},
+		// sorted by denom, like sdk.DecCoins
+		{"MultipleDenoms", "0.0125uscrt,1.5uatom", "1.500000000000000000uatom,0.012500000000000000uscrt"},
+	} {
+		t.Run(test.description, func(t *testing.T) {
+			minGasPrice, err := sdk.ParseDecCoins(test.minGasPrice)
+			require.NoError(t, err)
+			if test.minGasPrice != "" {
+				keeper.SetMinGasPrice(ctx, minGasPrice)
+			}
+
+			_, _, _, events, _, execErr := execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, `{"min_gas_price":{}}`, true, true, defaultGasForTests, 0)
+			require.Empty(t, execErr)
+
+			requireEvents(t,
+				[]ContractEvent{
+					{
+						{Key: "contract_address", Value: contractAddress.String()},
+						{Key: "min_gas_price", Value: test.expected},
+					},
+				},
+				events,
+			)
+		})
+	}
+}
+
+func TestTxMsgPosition(t *testing.T) {
+	ctx, keeper, codeID, _, walletA, privKeyA, _, _ := setupTest(t, TestContractPaths[v1Contract], sdk.NewCoins())
+
+	_, _, contractAddress, _, initErr := initHelper(t, keeper, ctx, codeID, walletA, nil, privKeyA, `{"nop":{}}`, true, true, defaultGasForTests)
+	require.Empty(t, initErr)
+
+	_, _, _, events, _, execErr := execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, `{"tx_msg_position":{}}`, true, true, defaultGasForTests, 0)
+	require.Empty(t, execErr)
+
+	requireEvents(t,
+		[]ContractEvent{
+			{
+				{Key: "contract_address", Value: contractAddress.String()},
+				{Key: "msg_index", Value: "0"},
+				{Key: "msg_count", Value: "1"},
+			},
+		},
+		events,
+	)
+
+	msgs := []string{`{"tx_msg_position":{}}`, `{"tx_msg_position":{}}`, `{"tx_msg_position":{}}`}
+	results, err := execHelperMultipleMsgs(t, keeper, ctx, contractAddress, walletA, privKeyA, msgs, true, true, math.MaxUint64, 0)
+	require.Nil(t, err)
+	require.Len(t, results, 3)
+
+	for i, result := range results {
+		requireEvents(t,
+			[]ContractEvent{
+				{
+					{Key: "contract_address", Value: contractAddress.String()},
+					{Key: "msg_index", Value: fmt.Sprint(i)},
+					{Key: "msg_count", Value: "3"},
+				},
+			},
+			result.WasmEvents,
+		)
+	}
+}
+
+func TestLastOutputHashDetectsReprocessing(t *testing.T) {
+	ctx, keeper, codeID, _, walletA, privKeyA, _, _ := setupTest(t, TestContractPaths[v1Contract], sdk.NewCoins())
+
+	_, _, contractAddress, _, initErr := initHelper(t, keeper, ctx, codeID, walletA, nil, privKeyA, `{"nop":{}}`, true, true, defaultGasForTests)
+	require.Empty(t, initErr)
+
+	for _, test := range []struct {
+		description      string
+		requestID        string
+		alreadyProcessed string
+	}{
+		{"FirstRequest", "request-1", "false"},
+		{"SameRequestAgain", "request-1", "true"},
+		{"OtherRequest", "request-2", "false"},
+	} {
+		t.Run(test.description, func(t *testing.T) {
+			msg := fmt.Sprintf(`{"process_once":{"request_id":"%s"}}`, test.requestID)
+			_, _, _, events, _, err := execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, msg, true, true, defaultGasForTests, 0)
+			require.Empty(t, err)
+			requireEvents(t,
+				[]ContractEvent{
+					{
+						{Key: "contract_address", Value: contractAddress.String()},
+						{Key: "already_processed", Value: test.alreadyProcessed},
+					},
+				},
+				events,
+			)
+		})
+	}
+}
+
+func TestLastOutputHashRecordsInit(t *testing.T) {
+	ctx, keeper, codeID, _, walletA, privKeyA, _, _ := setupTest(t, TestContractPaths[v1Contract], sdk.NewCoins())
+
+	_, _, contractAddress, initEvents, initErr := initHelper(t, keeper, ctx, codeID, walletA, nil, privKeyA, `{"process_once":{"request_id":"request-1"}}`, true, true, defaultGasForTests)
+	require.Empty(t, initErr)
+	requireEvents(t,
+		[]ContractEvent{
+			{
+				{Key: "contract_address", Value: contractAddress.String()},
+				{Key: "already_processed", Value: "false"},
+			},
+		},
+		initEvents,
+	)
+
+	// the request init claimed is already processed when the contract is executed
+	_, _, _, events, _, err := execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, `{"process_once":{"request_id":"request-1"}}`, true, true, defaultGasForTests, 0)
+	require.Empty(t, err)
+	requireEvents(t,
+		[]ContractEvent{
+			{
+				{Key: "contract_address", Value: contractAddress.String()},
+				{Key: "already_processed", Value: "true"},
+			},
+		},
+		events,
+	)
+}
+
+func TestRequireFundsRejectsMismatchedFunds(t *testing.T) {
+	ctx, keeper, codeID, _, walletA, privKeyA, _, _ := setupTest(t, TestContractPaths[v1Contract], sdk.NewCoins(sdk.NewInt64Coin("assaf", 5000)))
+
+	_, _, contractAddress, _, initErr := initHelper(t, keeper, ctx, codeID, walletA, nil, privKeyA, `{"nop":{}}`, true, true, defaultGasForTests)
+	require.Empty(t, initErr)
+
+	msg := `{"require_funds":{"funds":[{"denom":"denom","amount":"10"},{"denom":"assaf","amount":"5"}]}}`
+
+	for _, test := range []struct {
+		description string
+		funds       sdk.Coins
+		isMatch     bool
+	}{
+		{"ExactFunds", sdk.NewCoins(sdk.NewInt64Coin("denom", 10), sdk.NewInt64Coin("assaf", 5)), true},
+		{"PartialPayment", sdk.NewCoins(sdk.NewInt64Coin("denom", 10)), false},
+		{"WrongDenomMix", sdk.NewCoins(sdk.NewInt64Coin("denom", 5), sdk.NewInt64Coin("assaf", 10)), false},
+		{"ExtraFunds", sdk.NewCoins(sdk.NewInt64Coin("denom", 11), sdk.NewInt64Coin("assaf", 5)), false},
+	} {
+		t.Run(test.description, func(t *testing.T) {
+			_, _, _, _, _, execErr := execHelperMultipleCoins(t, keeper, ctx, contractAddress, walletA, privKeyA, msg, test.isMatch, true, defaultGasForTests, test.funds, -1)
+
+			if test.isMatch {
+				require.Empty(t, execErr)
+			} else {
+				require.NotNil(t, execErr.GenericErr)
+				require.Contains(t, execErr.GenericErr.Msg, "the funds sent don't match the funds the contract requires")
+			}
+		})
+	}
+}
+
+func TestSameSession(t *testing.T) {
+	ctx, keeper, codeID, _, walletA, privKeyA, _, _ := setupTest(t, TestContractPaths[v1Contract], sdk.NewCoins())
+
+	_, _, contractAddress, _, initErr := initHelper(t, keeper, ctx, codeID, walletA, nil, privKeyA, `{"nop":{}}`, true, true, defaultGasForTests)
+	require.Empty(t, initErr)
+
+	// every encryption uses a fresh nonce, but the same key pair, and so the same session
+	first, err := wasmCtx.Encrypt([]byte(`{"first":{}}`))
+	require.NoError(t, err)
+	second, err := wasmCtx.Encrypt([]byte(`{"second":{}}`))
+	require.NoError(t, err)
+
+	// the first message, as if another key pair had sent it
+	otherSession := append([]byte{}, first...)
+	otherSession[32] ^= 0xff
+
+	for _, test := range []struct {
+		description string
+		ciphertextA []byte
+		ciphertextB []byte
+		sameSession string
+	}{
+		{"SameMessage", first, first, "true"},
+		{"DistinctMessagesSameKey", first, second, "true"},
+		{"DifferentSessions", first, otherSession, "false"},
+	} {
+		t.Run(test.description, func(t *testing.T) {
+			msg := fmt.Sprintf(`{"same_session":{"ciphertext_a":"%s","ciphertext_b":"%s"}}`, base64.StdEncoding.EncodeToString(test.ciphertextA), base64.StdEncoding.EncodeToString(test.ciphertextB))
+			_, _, _, events, _, execErr := execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, msg, true, true, defaultGasForTests, 0)
+			require.Empty(t, execErr)
+			requireEvents(t,
+				[]ContractEvent{
+					{
+						{Key: "contract_address", Value: contractAddress.String()},
+						{Key: "same_session", Value: test.sameSession},
 					},
-					events,
-				)
-			})
+				},
+				events,
+			)
 		})
 	}
+
+	_, _, _, _, _, execErr := execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, fmt.Sprintf(`{"same_session":{"ciphertext_a":"%s","ciphertext_b":""}}`, base64.StdEncoding.EncodeToString(first)), true, true, defaultGasForTests, 0)
+	require.NotNil(t, execErr.GenericErr)
+	require.Contains(t, execErr.GenericErr.Msg, "same_session failed with error code 1011")
 }
 
-func TestEd25519Verify(t *testing.T) {
-	for _, testContract := range testContracts {
-		t.Run(testContract.CosmWasmVersion, func(t *testing.T) {
-			ctx, keeper, codeID, _, walletA, privKeyA, _, _ := setupTest(t, testContract.WasmFilePath, sdk.NewCoins())
+func TestEnclaveBlockView(t *testing.T) {
+	ctx, keeper, codeID, _, walletA, privKeyA, _, _ := setupTest(t, TestContractPaths[v1Contract], sdk.NewCoins())
 
-			_, _, contractAddress, _, initErr := initHelper(t, keeper, ctx, codeID, walletA, nil, privKeyA, `{"nop":{}}`, true, testContract.IsCosmWasmV1, defaultGasForTests)
-			require.Empty(t, initErr)
+	_, _, contractAddress, _, initErr := initHelper(t, keeper, ctx, codeID, walletA, nil, privKeyA, `{"nop":{}}`, true, true, defaultGasForTests)
+	require.Empty(t, initErr)
 
-			// https://paulmillr.com/noble/
-			t.Run("Correct", func(t *testing.T) {
-				_, _, _, events, _, err := execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, `{"ed25519_verify":{"iterations":1,"pubkey":"LO2+Bt+/FIjomSaPB+I++LXkxgxwfnrKHLyvCic72rA=","sig":"8O7nwhM71/B9srKwe8Ps39z5lAsLMMs6LxdvoPk0HXjEM97TNhKbdU6gEePT2MaaIUSiMEmoG28HIZMgMRTCDg==","msg":"YXNzYWYgd2FzIGhlcmU="}}`, true, testContract.IsCosmWasmV1, defaultGasForTests, 0)
+	// the contract itself fails the query if the view doesn't match its env
+	queryRes, qErr := queryHelper(t, keeper, ctx, contractAddress, `{"enclave_block_view":{}}`, true, true, math.MaxUint64)
+	require.Empty(t, qErr)
 
-				require.Empty(t, err)
-				requireEvents(t,
-					[]ContractEvent{
-						{
-							{Key: "contract_address", Value: contractAddress.String()},
-							{Key: "result", Value: "true"},
-						},
-					},
-					events,
-				)
-			})
-			t.Run("IncorrectMsg", func(t *testing.T) {
-				_, _, _, events, _, err := execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, `{"ed25519_verify":{"iterations":1,"pubkey":"LO2+Bt+/FIjomSaPB+I++LXkxgxwfnrKHLyvCic72rA=","sig":"8O7nwhM71/B9srKwe8Ps39z5lAsLMMs6LxdvoPk0HXjEM97TNhKbdU6gEePT2MaaIUSiMEmoG28HIZMgMRTCDg==","msg":"YXNzYWYgd2FzIGhlcmUK"}}`, true, testContract.IsCosmWasmV1, defaultGasForTests, 0)
+	var blockView map[string]interface{}
+	require.NoError(t, json.Unmarshal([]byte(queryRes), &blockView))
+	require.Equal(t, float64(ctx.BlockHeight()), blockView["height"])
+	require.Equal(t, strconv.FormatInt(ctx.BlockTime().UnixNano(), 10), blockView["time"])
+	// nothing in the view may depend on how the enclave was built
+	require.NotContains(t, blockView, "hash")
+}
 
-				require.Empty(t, err)
-				requireEvents(t,
-					[]ContractEvent{
-						{
-							{Key: "contract_address", Value: contractAddress.String()},
-							{Key: "result", Value: "false"},
-						},
-					},
-					events,
-				)
-			})
-			t.Run("IncorrectSig", func(t *testing.T) {
-				_, _, _, events, _, err := execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, `{"ed25519_verify":{"iterations":1,"pubkey":"LO2+Bt+/FIjomSaPB+I++LXkxgxwfnrKHLyvCic72rA=","sig":"8O7nwhM71/B9srKwe8Ps39z5lAsLMMs6LxdvoPk0HXjEM97TNhKbdU6gEePT2MaaIUSiMEmoG28HIZMgMRTCDw==","msg":"YXNzYWYgd2FzIGhlcmU="}}`, true, testContract.IsCosmWasmV1, defaultGasForTests, 0)
+func TestReplyDepthLimit(t *testing.T) {
+	ctx, keeper, codeID, _, walletA, privKeyA, _, _ := setupTest(t, TestContractPaths[v1Contract], sdk.NewCoins())
 
-				require.Empty(t, err)
-				requireEvents(t,
-					[]ContractEvent{
-						{
-							{Key: "contract_address", Value: contractAddress.String()},
-							{Key: "result", Value: "false"},
-						},
-					},
-					events,
-				)
-			})
-			t.Run("IncorrectPubkey", func(t *testing.T) {
-				_, _, _, events, _, err := execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, `{"ed25519_verify":{"iterations":1,"pubkey":"DV1lgRdKw7nt4hvl8XkGZXMzU9S3uM9NLTK0h0qMbUs=","sig":"8O7nwhM71/B9srKwe8Ps39z5lAsLMMs6LxdvoPk0HXjEM97TNhKbdU6gEePT2MaaIUSiMEmoG28HIZMgMRTCDg==","msg":"YXNzYWYgd2FzIGhlcmU="}}`, true, testContract.IsCosmWasmV1, defaultGasForTests, 0)
+	_, _, contractAddress, _, initErr := initHelper(t, keeper, ctx, codeID, walletA, nil, privKeyA, `{"nop":{}}`, true, true, defaultGasForTests)
+	require.Empty(t, initErr)
 
-				require.Empty(t, err)
-				requireEvents(t,
-					[]ContractEvent{
-						{
-							{Key: "contract_address", Value: contractAddress.String()},
-							{Key: "result", Value: "false"},
-						},
+	// every level waits for a reply from the next one, growing the reply chain by one
+	_, _, _, _, _, err := execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, `{"nested_recursive_reply":{"depth":5}}`, true, true, math.MaxUint64, 0)
+	require.Empty(t, err)
+
+	_, _, _, _, _, err = execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, `{"nested_recursive_reply":{"depth":20}}`, true, true, math.MaxUint64, 0)
+	require.NotEmpty(t, err)
+	require.Contains(t, err.Error(), "reply depth exceeded")
+}
+
+func TestNestedSubMsgGasLimitAboveRemainingGas(t *testing.T) {
+	ctx, keeper, codeID, _, walletA, privKeyA, _, _ := setupTest(t, TestContractPaths[v1Contract], sdk.NewCoins())
+
+	_, _, contractAddress, _, initErr := initHelper(t, keeper, ctx, codeID, walletA, nil, privKeyA, `{"nop":{}}`, true, true, defaultGasForTests)
+	require.Empty(t, initErr)
+
+	// every level declares more gas for the next one than the whole tx has, which is capped to the gas it has left
+	_, _, _, _, _, err := execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, `{"nested_recursive_reply":{"depth":3,"gas_limit":10000000}}`, true, true, defaultGasForTests, 0)
+	require.Empty(t, err)
+
+	// a limit below what the next level needs still holds
+	_, _, _, _, _, err = execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, `{"nested_recursive_reply":{"depth":3,"gas_limit":1}}`, true, true, defaultGasForTests, 0)
+	require.NotEmpty(t, err)
+}
+
+func TestDeriveID(t *testing.T) {
+	ctx, keeper, codeID, _, walletA, privKeyA, _, _ := setupTest(t, TestContractPaths[v1Contract], sdk.NewCoins())
+
+	_, _, contractAddress, _, initErr := initHelper(t, keeper, ctx, codeID, walletA, nil, privKeyA, `{"nop":{}}`, true, true, defaultGasForTests)
+	require.Empty(t, initErr)
+
+	queryRes, qErr := queryHelper(t, keeper, ctx, contractAddress, `{"derive_ids":{"seed":"order","count":2}}`, true, true, math.MaxUint64)
+	require.Empty(t, qErr)
+
+	var ids [][]byte
+	require.NoError(t, json.Unmarshal([]byte(queryRes), &ids))
+	require.Len(t, ids, 2)
+	require.Len(t, ids[0], 16)
+	// the same seed twice in one call gets different ids
+	require.NotEqual(t, ids[0], ids[1])
+
+	// replaying the call derives the same ids
+	replayRes, qErr := queryHelper(t, keeper, ctx, contractAddress, `{"derive_ids":{"seed":"order","count":2}}`, true, true, math.MaxUint64)
+	require.Empty(t, qErr)
+	require.Equal(t, queryRes, replayRes)
+}
+
+func TestDeriveNonces(t *testing.T) {
+	ctx, keeper, codeID, _, walletA, privKeyA, _, _ := setupTest(t, TestContractPaths[v1Contract], sdk.NewCoins())
+
+	_, _, contractAddress, _, initErr := initHelper(t, keeper, ctx, codeID, walletA, nil, privKeyA, `{"nop":{}}`, true, true, defaultGasForTests)
+	require.Empty(t, initErr)
+
+	deriveNonces := func() []string {
+		_, _, _, events, _, err := execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, `{"derive_nonces":{"count":2}}`, true, true, defaultGasForTests, 0)
+		require.Empty(t, err)
+
+		for _, attr := range events[0] {
+			if attr.Key == "nonces" {
+				return strings.Split(attr.Value, ",")
+			}
+		}
+		require.Fail(t, "no nonces attribute")
+		return nil
+	}
+
+	first := deriveNonces()
+	require.Len(t, first, 2)
+	nonce, err := base64.StdEncoding.DecodeString(first[0])
+	require.NoError(t, err)
+	require.Len(t, nonce, 32)
+	// two encryptions in one call get different nonces
+	require.NotEqual(t, first[0], first[1])
+
+	// the same message sent again in the same block is a different call
+	second := deriveNonces()
+	require.NotContains(t, second, first[0])
+	require.NotContains(t, second, first[1])
+}
+
+func TestFixedPointMath(t *testing.T) {
+	ctx, keeper, codeID, _, walletA, privKeyA, _, _ := setupTest(t, TestContractPaths[v1Contract], sdk.NewCoins())
+
+	_, _, contractAddress, _, initErr := initHelper(t, keeper, ctx, codeID, walletA, nil, privKeyA, `{"nop":{}}`, true, true, defaultGasForTests)
+	require.Empty(t, initErr)
+
+	for _, test := range []struct {
+		description string
+		msg         string
+		result      string
+		errorCode   int
+	}{
+		{"MulDivLargerThanU64Intermediate", `{"checked_mul_div":{"a":18446744073709551615,"b":3,"c":6}}`, "9223372036854775807", 0},
+		{"MulDivDecimals", `{"checked_mul_div":{"a":1500000000000000000,"b":2500000000000000000,"c":1000000000000000000}}`, "3750000000000000000", 0},
+		{"MulDivOverflow", `{"checked_mul_div":{"a":18446744073709551615,"b":2,"c":1}}`, "", 1},
+		{"MulDivUnderflow", `{"checked_mul_div":{"a":1,"b":1,"c":2}}`, "", 2},
+		{"MulDivByZero", `{"checked_mul_div":{"a":1,"b":1,"c":0}}`, "", 3},
+		{"SqrtInteger", `{"sqrt":{"x":17,"decimal_places":0}}`, "4", 0},
+		{"SqrtDecimal", `{"sqrt":{"x":2000000000000000000,"decimal_places":18}}`, "1414213562373095048", 0},
+		{"SqrtTooManyDecimalPlaces", `{"sqrt":{"x":2,"decimal_places":19}}`, "", 4},
+	} {
+		t.Run(test.description, func(t *testing.T) {
+			_, _, _, events, _, err := execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, test.msg, true, true, defaultGasForTests, 0)
+
+			if test.errorCode != 0 {
+				require.NotNil(t, err.GenericErr)
+				require.Contains(t, err.GenericErr.Msg, fmt.Sprintf("failed with error code %d", test.errorCode))
+				return
+			}
+
+			require.Empty(t, err)
+			requireEvents(t,
+				[]ContractEvent{
+					{
+						{Key: "contract_address", Value: contractAddress.String()},
+						{Key: "result", Value: test.result},
 					},
-					events,
-				)
-			})
+				},
+				events,
+			)
 		})
 	}
 }
@@ -1029,7 +2300,13 @@ func TestSleep(t *testing.T) {
 
 			require.Error(t, execErr)
 			require.Error(t, execErr.GenericErr)
-			require.Contains(t, execErr.GenericErr.Msg, "the contract panicked")
+			if testContract.IsCosmWasmV1 {
+				// the v1 contract calls the host to sleep, which traps
+				require.Contains(t, execErr.GenericErr.Msg, "contract called a forbidden syscall")
+			} else {
+				// the v0.10 contract sleeps with std, which panics on wasm
+				require.Contains(t, execErr.GenericErr.Msg, "the contract panicked")
+			}
 		})
 	}
 }
@@ -1049,7 +2326,7 @@ func TestAllocateOnHeapFailBecauseMemoryLimit(t *testing.T) {
 			require.Empty(t, data)
 
 			require.NotNil(t, execErr.GenericErr)
-			require.Contains(t, execErr.GenericErr.Msg, "the contract panicked")
+			require.Contains(t, execErr.GenericErr.Msg, "contract exceeded its memory limit")
 		})
 	}
 }
@@ -1101,7 +2378,7 @@ func TestAllocateOnHeapMoreThanSGXHasFailBecauseMemoryLimit(t *testing.T) {
 			require.Empty(t, data)
 
 			require.NotNil(t, execErr.GenericErr)
-			require.Contains(t, execErr.GenericErr.Msg, "the contract panicked")
+			require.Contains(t, execErr.GenericErr.Msg, "contract exceeded its memory limit")
 		})
 	}
 }
@@ -1247,6 +2524,93 @@ func TestBankMsgSend(t *testing.T) {
 	}
 }
 
+func TestNormalizeCoins(t *testing.T) {
+	ctx, keeper, codeID, _, walletA, privKeyA, walletB, _ := setupTest(t, TestContractPaths[v1Contract], sdk.NewCoins(sdk.NewInt64Coin("assaf", 5000)))
+
+	_, _, contractAddress, _, initErr := initHelperImpl(t, keeper, ctx, codeID, walletA, nil, privKeyA, `{"nop":{}}`, true, true, defaultGasForTests, -1, sdk.NewCoins(sdk.NewInt64Coin("denom", 10), sdk.NewInt64Coin("assaf", 10)))
+	require.Empty(t, initErr)
+
+	walletBCoinsBefore := keeper.bankKeeper.GetAllBalances(ctx, walletB)
+	require.Equal(t, "5000assaf,5000denom", walletBCoinsBefore.String())
+
+	// duplicate denoms are merged, zero amounts dropped and the rest sorted, so the bank module accepts it
+	amount := `[{"amount":"2","denom":"denom"},{"amount":"0","denom":"zero"},{"amount":"3","denom":"assaf"},{"amount":"4","denom":"denom"}]`
+	_, _, _, _, _, err := execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, fmt.Sprintf(`{"normalized_bank_msg_send":{"to":"%s","amount":%s}}`, walletB.String(), amount), true, true, math.MaxUint64, 0)
+	require.Empty(t, err)
+
+	walletBCoinsAfter := keeper.bankKeeper.GetAllBalances(ctx, walletB)
+	require.Equal(t, "5003assaf,5006denom", walletBCoinsAfter.String())
+
+	_, _, _, _, _, err = execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, fmt.Sprintf(`{"normalized_bank_msg_send":{"to":"%s","amount":[{"amount":"1","denom":"x"}]}}`, walletB.String()), true, true, math.MaxUint64, 0)
+	require.NotNil(t, err.GenericErr)
+	require.Contains(t, err.GenericErr.Msg, "normalize_coins failed with error code 2")
+}
+
+func TestRemainingSubmsgBudget(t *testing.T) {
+	ctx, keeper, codeID, _, walletA, privKeyA, _, _ := setupTest(t, TestContractPaths[v1Contract], sdk.NewCoins())
+
+	_, _, contractAddress, _, initErr := initHelper(t, keeper, ctx, codeID, walletA, nil, privKeyA, `{"nop":{}}`, true, true, defaultGasForTests)
+	require.Empty(t, initErr)
+
+	// the limit is off until governance (or the upgrade) sets it, which the contract can tell
+	_, _, _, _, _, err := execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, `{"fill_submsg_budget":{"extra":0}}`, true, true, math.MaxUint64, 0)
+	require.NotNil(t, err.GenericErr)
+	require.Contains(t, err.GenericErr.Msg, "submessages aren't limited")
+
+	keeper.SetMaxSubmessages(ctx, types.DefaultMaxSubmessages)
+
+	// the whole budget can be used
+	_, _, _, events, _, err = execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, `{"fill_submsg_budget":{"extra":0}}`, true, true, math.MaxUint64, 512)
+	require.Empty(t, err)
+	requireEvents(t,
+		[]ContractEvent{
+			{
+				{Key: "contract_address", Value: contractAddress.String()},
+				{Key: "submessages", Value: "512"},
+			},
+		},
+		events,
+	)
+
+	// one more than the budget is rejected
+	_, _, _, _, _, err = execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, `{"fill_submsg_budget":{"extra":1}}`, true, true, math.MaxUint64, 513)
+	require.NotEmpty(t, err)
+	require.Contains(t, err.Error(), "too many submessages")
+
+	// the budget follows the param
+	keeper.SetMaxSubmessages(ctx, 100)
+	_, _, _, events, _, err = execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, `{"fill_submsg_budget":{"extra":0}}`, true, true, math.MaxUint64, 100)
+	require.Empty(t, err)
+	requireEvents(t,
+		[]ContractEvent{
+			{
+				{Key: "contract_address", Value: contractAddress.String()},
+				{Key: "submessages", Value: "100"},
+			},
+		},
+		events,
+	)
+}
+
+func TestMaxEventTypeLength(t *testing.T) {
+	ctx, keeper, codeID, _, walletA, privKeyA, _, _ := setupTest(t, TestContractPaths[v1Contract], sdk.NewCoins())
+
+	_, _, contractAddress, _, initErr := initHelper(t, keeper, ctx, codeID, walletA, nil, privKeyA, `{"nop":{}}`, true, true, defaultGasForTests)
+	require.Empty(t, initErr)
+
+	// the limit is off until governance (or the upgrade) sets it, which the contract can tell
+	_, _, _, _, _, err := execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, `{"emit_event_type":{"length":1024}}`, true, true, defaultGasForTests, 0)
+	require.Empty(t, err)
+
+	keeper.SetMaxEventTypeLength(ctx, types.DefaultMaxEventTypeLength)
+	_, _, _, _, _, err = execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, `{"emit_event_type":{"length":128}}`, true, true, defaultGasForTests, 0)
+	require.Empty(t, err)
+
+	_, _, _, _, _, err = execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, `{"emit_event_type":{"length":129}}`, true, true, defaultGasForTests, 0)
+	require.NotEmpty(t, err)
+	require.Contains(t, err.Error(), "event type in the contract's response is too long")
+}
+
 func TestSendFunds(t *testing.T) {
 	for _, callTypes := range multisetsFrom([]string{"init", "exec", "user"}, 2) {
 		originType, destinationType := callTypes[0], callTypes[1]
@@ -1718,9 +3082,17 @@ func TestCodeHashExecCallExec(t *testing.T) {
 				require.NotEmpty(t, err)
 				require.Contains(t,
 					err.Error(),
-					"failed to validate transaction",
+					"failed to validate transaction: code hash doesn't match the contract's",
 				)
 			})
+			t.Run("ContractNotFound", func(t *testing.T) {
+				// the wallet is an account, not a contract
+				_, _, _, _, _, err := execHelper(t, keeper, ctx, addr, walletA, privKeyA, fmt.Sprintf(`{"call_to_exec":{"addr":"%s","code_hash":"%s","msg":"%s"}}`, walletA, codeHash, `{\"c\":{\"x\":1,\"y\":1}}`), false, testContract.IsCosmWasmV1, defaultGasForTests, 0)
+
+				require.NotEmpty(t, err)
+				require.Contains(t, err.Error(), "not found")
+				require.NotContains(t, err.Error(), "code hash")
+			})
 		})
 	}
 }
@@ -2234,6 +3606,22 @@ func TestV1ReplyChainWithError(t *testing.T) {
 	require.Equal(t, expectedFlow, string(data))
 }
 
+func TestV1ReplyDataIsDecryptedForParent(t *testing.T) {
+	ctx, keeper, codeIds, codeHashes, walletA, privKeyA, _, _ := setupChainTest(t, TestContractPaths[v1Contract], sdk.NewCoins(), 2)
+
+	_, _, contractA, _, err := initHelper(t, keeper, ctx, codeIds[0], walletA, nil, privKeyA, `{"nop":{}}`, true, true, defaultGasForTests)
+	require.Empty(t, err)
+	_, _, contractB, _, err := initHelper(t, keeper, ctx, codeIds[1], walletA, nil, privKeyA, `{"nop":{}}`, true, true, defaultGasForTests)
+	require.Empty(t, err)
+
+	msg := fmt.Sprintf(`{"send_echo_with_reply":{"to":"%s","code_hash":"%s","data":"%s"}}`, contractB.String(), codeHashes[1], base64.StdEncoding.EncodeToString([]byte("secret data")))
+	_, _, data, _, _, err := execHelper(t, keeper, ctx, contractA, walletA, privKeyA, msg, true, true, defaultGasForTests, 0)
+	require.Empty(t, err)
+
+	// A's reply handler prefixes whatever it got from B, so this only matches if the data was decrypted for A
+	require.Equal(t, "reply: secret data", string(data))
+}
+
 func TestEvaporateGas(t *testing.T) {
 	ctx, keeper, codeID, _, walletA, privKeyA, _, _ := setupTest(t, TestContractPaths[evaporateContract], sdk.NewCoins())
 	_, _, contractAddress, _, initErr := initHelper(t, keeper, ctx, codeID, walletA, nil, privKeyA, `{"Nop":{}}`, true, true, defaultGasForTests)
@@ -2310,6 +3698,71 @@ func TestEvaporateGas(t *testing.T) {
 	}
 }
 
+func TestContractGasCeiling(t *testing.T) {
+	ctx, keeper, codeID, _, walletA, privKeyA, _, _ := setupTest(t, TestContractPaths[evaporateContract], sdk.NewCoins())
+	_, _, contractAddress, _, initErr := initHelper(t, keeper, ctx, codeID, walletA, nil, privKeyA, `{"Nop":{}}`, true, true, defaultGasForTests)
+	require.Empty(t, initErr)
+
+	// without a ceiling only the tx gas limit binds
+	_, _, _, _, _, err := execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, `{"evaporate":{"amount":200000}}`, true, true, defaultGasForTests, 0)
+	require.Empty(t, err)
+
+	// the ceiling is in sdk gas, like the tx gas limit
+	keeper.SetContractGasCeiling(ctx, 100_000)
+	_, _, _, _, _, err = execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, `{"evaporate":{"amount":200000}}`, true, true, defaultGasForTests, 0)
+	require.NotEmpty(t, err)
+	require.Contains(t, err.Error(), "contract gas ceiling exceeded")
+
+	_, _, _, _, _, err = execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, `{"evaporate":{"amount":50000}}`, true, true, defaultGasForTests, 0)
+	require.Empty(t, err)
+
+	// a ceiling too large to convert to wasm gas doesn't wrap around to a tiny one
+	keeper.SetContractGasCeiling(ctx, math.MaxUint64)
+	_, _, _, _, _, err = execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, `{"evaporate":{"amount":200000}}`, true, true, defaultGasForTests, 0)
+	require.Empty(t, err)
+}
+
+func TestGasUsedAttribute(t *testing.T) {
+	ctx, keeper, codeID, _, walletA, privKeyA, _, _ := setupTest(t, TestContractPaths[evaporateContract], sdk.NewCoins())
+	_, _, contractAddress, _, initErr := initHelper(t, keeper, ctx, codeID, walletA, nil, privKeyA, `{"Nop":{}}`, true, true, defaultGasForTests)
+	require.Empty(t, initErr)
+
+	gasUsedAttribute := func(events []ContractEvent) (uint64, bool) {
+		for _, attr := range events[0] {
+			if attr.Key == types.AttributeKeyGasUsed {
+				gasUsed, err := strconv.ParseUint(attr.Value, 10, 64)
+				require.NoError(t, err)
+				return gasUsed, true
+			}
+		}
+		return 0, false
+	}
+
+	_, _, _, events, _, err := execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, `{"evaporate":{"amount":0}}`, true, true, defaultGasForTests, 0)
+	require.Empty(t, err)
+	_, found := gasUsedAttribute(events)
+	require.False(t, found, "gas_used is opt-in")
+
+	keeper.gasUsedAttribute = true
+
+	_, _, _, events, baseTxGas, err := execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, `{"evaporate":{"amount":0}}`, true, true, defaultGasForTests, 0)
+	require.Empty(t, err)
+	baseGasUsed, found := gasUsedAttribute(events)
+	require.True(t, found)
+	require.Greater(t, baseGasUsed, uint64(0))
+	require.Less(t, baseGasUsed, baseTxGas)
+
+	for _, amount := range []uint64{9, 1200, 400000} {
+		_, _, _, events, txGas, err := execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, fmt.Sprintf(`{"evaporate":{"amount":%d}}`, amount), true, true, defaultGasForTests, 0)
+		require.Empty(t, err)
+		gasUsed, found := gasUsedAttribute(events)
+		require.True(t, found)
+
+		// everything the tx was charged beyond the base call was charged by the contract
+		require.Equal(t, txGas-baseTxGas, gasUsed-baseGasUsed, "evaporate %d", amount)
+	}
+}
+
 func TestCheckGas(t *testing.T) {
 	ctx, keeper, codeID, _, walletA, privKeyA, _, _ := setupTest(t, TestContractPaths[evaporateContract], sdk.NewCoins())
 	_, _, contractAddress, _, initErr := initHelper(t, keeper, ctx, codeID, walletA, nil, privKeyA, `{"Nop":{}}`, true, true, defaultGasForTests)
@@ -2329,6 +3782,28 @@ func TestCheckGas(t *testing.T) {
 	require.Equal(t, baseGasUsed-baseContractUsage, gasUsed+1)
 }
 
+func TestGasUsedInCall(t *testing.T) {
+	ctx, keeper, codeID, _, walletA, privKeyA, _, _ := setupTest(t, TestContractPaths[evaporateContract], sdk.NewCoins())
+	_, _, contractAddress, _, initErr := initHelper(t, keeper, ctx, codeID, walletA, nil, privKeyA, `{"Nop":{}}`, true, true, defaultGasForTests)
+	require.Empty(t, initErr)
+
+	_, _, _, events, _, err := execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, `{"check_gas_used_in_call":{"evaporate":100}}`, true, true, defaultGasForTests, 0)
+	require.Empty(t, err)
+
+	execEvent := events[0]
+	require.Equal(t, "before", execEvent[1].Key)
+	require.Equal(t, "after", execEvent[2].Key)
+
+	before, err2 := strconv.ParseUint(execEvent[1].Value, 10, 64)
+	require.Empty(t, err2)
+	after, err2 := strconv.ParseUint(execEvent[2].Value, 10, 64)
+	require.Empty(t, err2)
+
+	require.Greater(t, before, uint64(0))
+	// evaporate is in sdk gas (1000 cosmwasm gas each), and gas_used_in_call charges for itself
+	require.GreaterOrEqual(t, after-before, uint64(100*1000))
+}
+
 func TestConsumeExact(t *testing.T) {
 	ctx, keeper, codeID, _, walletA, privKeyA, _, _ := setupTest(t, TestContractPaths[evaporateContract], sdk.NewCoins())
 	_, _, contractAddress, _, initErr := initHelper(t, keeper, ctx, codeID, walletA, nil, privKeyA, `{"Nop":{}}`, true, true, defaultGasForTests)
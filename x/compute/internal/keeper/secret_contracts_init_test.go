@@ -458,6 +458,53 @@ func TestExecCallbackToInit(t *testing.T) {
 	}
 }
 
+func TestPredictContractAddressMatchesCallbackToInit(t *testing.T) {
+	for _, testContract := range testContracts {
+		t.Run(testContract.CosmWasmVersion, func(t *testing.T) {
+			ctx, keeper, codeID, codeHash, walletA, privKeyA, _, _ := setupTest(t, testContract.WasmFilePath, sdk.NewCoins())
+
+			predictedFirst := keeper.PredictContractAddress(ctx, codeID, walletA)
+			_, _, contractAddress, _, initErr := initHelper(t, keeper, ctx, codeID, walletA, nil, privKeyA, `{"nop":{}}`, true, testContract.IsCosmWasmV1, defaultGasForTests)
+			require.Empty(t, initErr)
+			require.Equal(t, predictedFirst, contractAddress)
+
+			// the contract is the creator of the contract it instantiates
+			predictedSecond := keeper.PredictContractAddress(ctx, codeID, contractAddress)
+			_, _, _, execEvents, _, execErr := execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, fmt.Sprintf(`{"callback_to_init":{"code_id":%d, "code_hash":"%s"}}`, codeID, codeHash), true, testContract.IsCosmWasmV1, defaultGasForTests, 0)
+			require.Empty(t, execErr)
+			require.Equal(t, 2, len(execEvents))
+			require.Contains(t,
+				execEvents[1],
+				v010cosmwasm.LogAttribute{Key: "contract_address", Value: predictedSecond.String()},
+			)
+		})
+	}
+}
+
+func TestPredictInstantiateAddressHostFunction(t *testing.T) {
+	ctx, keeper, codeID, codeHash, walletA, privKeyA, _, _ := setupTest(t, TestContractPaths[v1Contract], sdk.NewCoins())
+
+	_, _, contractAddress, _, initErr := initHelper(t, keeper, ctx, codeID, walletA, nil, privKeyA, `{"nop":{}}`, true, true, defaultGasForTests)
+	require.Empty(t, initErr)
+
+	// the enclave derives the address like the keeper does
+	predicted := keeper.PredictContractAddress(ctx, codeID, contractAddress).String()
+	_, _, _, execEvents, _, execErr := execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, fmt.Sprintf(`{"predict_instantiate_address":{"code_id":%d}}`, codeID), true, true, defaultGasForTests, 0)
+	require.Empty(t, execErr)
+	require.Contains(t,
+		execEvents[0],
+		v010cosmwasm.LogAttribute{Key: "predicted_address", Value: predicted},
+	)
+
+	_, _, _, execEvents, _, execErr = execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, fmt.Sprintf(`{"callback_to_init":{"code_id":%d, "code_hash":"%s"}}`, codeID, codeHash), true, true, defaultGasForTests, 0)
+	require.Empty(t, execErr)
+	require.Equal(t, 2, len(execEvents))
+	require.Contains(t,
+		execEvents[1],
+		v010cosmwasm.LogAttribute{Key: "contract_address", Value: predicted},
+	)
+}
+
 func TestCallbackFromInitAndCallbackEvents(t *testing.T) {
 	for _, testContract := range testContracts {
 		t.Run(testContract.CosmWasmVersion, func(t *testing.T) {
@@ -553,6 +600,24 @@ func TestWasmWithFloatingPoints(t *testing.T) {
 	}
 }
 
+func TestV1WasmWithFloatingPoints(t *testing.T) {
+	ctx, keeper, codeID, _, walletA, privKeyA, _, _ := setupTest(t, TestContractPaths[v1Contract], sdk.NewCoins())
+	floatsCodeID, _ := uploadCode(ctx, t, keeper, TestContractPaths[v1WithFloats], walletA)
+
+	// the same contract without floats is accepted
+	_, _, contractAddress, _, initErr := initHelper(t, keeper, ctx, codeID, walletA, walletA, privKeyA, `{"nop":{}}`, true, true, defaultGasForTests)
+	require.Empty(t, initErr)
+
+	_, _, _, _, initErr = initHelper(t, keeper, ctx, floatsCodeID, walletA, nil, privKeyA, `{"nop":{}}`, false, true, defaultGasForTests)
+	require.NotNil(t, initErr.GenericErr)
+	require.Contains(t, initErr.GenericErr.Msg, "found floating point operation in module code")
+
+	// nor can a contract be migrated to code with floats
+	_, migrateErr := migrateHelper(t, keeper, ctx, floatsCodeID, contractAddress, walletA, privKeyA, `{"nop":{}}`, false, true, math.MaxUint64)
+	require.NotNil(t, migrateErr.GenericErr)
+	require.Contains(t, migrateErr.GenericErr.Msg, "found floating point operation in module code")
+}
+
 func TestCodeHashInvalid(t *testing.T) {
 	for _, testContract := range testContracts {
 		t.Run(testContract.CosmWasmVersion, func(t *testing.T) {
@@ -967,7 +967,7 @@ func TestAllocateOnHeapFailBecauseMemoryLimitAfterMigrate(t *testing.T) {
 			require.Empty(t, data)
 
 			require.NotNil(t, execErr)
-			require.Contains(t, execErr.Error(), "the contract panicked")
+			require.Contains(t, execErr.Error(), "contract exceeded its memory limit")
 		})
 	}
 }
@@ -1027,7 +1027,7 @@ func TestAllocateOnHeapMoreThanSGXHasFailBecauseMemoryLimitAfterMigrate(t *testi
 			require.Empty(t, data)
 
 			require.NotNil(t, execErr)
-			require.Contains(t, execErr.Error(), "the contract panicked")
+			require.Contains(t, execErr.Error(), "contract exceeded its memory limit")
 		})
 	}
 }
@@ -3552,7 +3552,7 @@ func TestAllocateOnHeapFailBecauseMemoryLimitDuringMigrate(t *testing.T) {
 			require.Empty(t, res.Data)
 
 			require.NotNil(t, err.CosmWasm.GenericErr)
-			require.Contains(t, err.CosmWasm.GenericErr.Msg, "the contract panicked")
+			require.Contains(t, err.CosmWasm.GenericErr.Msg, "contract exceeded its memory limit")
 		})
 	}
 }
@@ -3608,7 +3608,7 @@ func TestAllocateOnHeapMoreThanSGXHasFailBecauseMemoryLimitDuringMigrate(t *test
 			require.Empty(t, res.Data)
 
 			require.NotNil(t, execErr.CosmWasm.GenericErr)
-			require.Contains(t, execErr.CosmWasm.GenericErr.Msg, "the contract panicked")
+			require.Contains(t, execErr.CosmWasm.GenericErr.Msg, "contract exceeded its memory limit")
 		})
 	}
 }
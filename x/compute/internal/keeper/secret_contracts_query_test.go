@@ -12,7 +12,12 @@ import (
 
 	"github.com/stretchr/testify/require"
 
+	multisigkeys "github.com/cosmos/cosmos-sdk/crypto/keys/multisig"
+	"github.com/cosmos/cosmos-sdk/crypto/keys/secp256k1"
+	crypto "github.com/cosmos/cosmos-sdk/crypto/types"
 	sdk "github.com/cosmos/cosmos-sdk/types"
+
+	"github.com/scrtlabs/SecretNetwork/x/compute/internal/types"
 )
 
 func TestQueryInputParamError(t *testing.T) {
@@ -31,6 +36,40 @@ func TestQueryInputParamError(t *testing.T) {
 	require.Equal(t, "canonicalize_address errored: invalid length", qErr.GenericErr.Msg)
 }
 
+func TestQueryEchoesNonce(t *testing.T) {
+	ctx, keeper, codeID, codeHash, walletA, privKeyA, walletB, _ := setupTest(t, filepath.Join(".", contractPath, "erc20.wasm"), sdk.NewCoins())
+
+	initMsg := fmt.Sprintf(`{"decimals":10,"initial_balances":[{"address":"%s","amount":"108"},{"address":"%s","amount":"53"}],"name":"ReuvenPersonalRustCoin","symbol":"RPRC"}`, walletA.String(), walletB.String())
+	_, _, contractAddress, _, initErr := initHelper(t, keeper, ctx, codeID, walletA, nil, privKeyA, initMsg, true, false, defaultGasForTests)
+	require.Empty(t, initErr)
+
+	ctx = ctx.WithGasMeter(sdk.NewGasMeter(defaultGasForTests))
+	_, codeInfo, prefixStore, err := keeper.contractInstance(ctx, contractAddress)
+	require.NoError(t, err)
+	contractKey, err := keeper.GetContractKey(ctx, contractAddress)
+	require.NoError(t, err)
+
+	env := types.NewEnv(ctx, sdk.AccAddress{}, sdk.NewCoins(), contractAddress, contractKey, []byte{0})
+	env.QueryDepth = 1
+	querier := QueryHandler{
+		Ctx:     ctx,
+		Plugins: keeper.queryPlugins,
+		Caller:  contractAddress,
+	}
+
+	// the nonce is echoed for results and contract errors alike
+	for _, input := range []string{
+		fmt.Sprintf(`{"balance":{"address":"%s"}}`, walletA.String()),
+		`{"balance":{"address":"blabla"}}`,
+	} {
+		queryBz, err := wasmCtx.Encrypt(types.NewSecretMsg([]byte(codeHash), []byte(input)).Serialize())
+		require.NoError(t, err)
+
+		_, nonce, _, _ := keeper.wasmer.Query(codeInfo.CodeHash, env, queryBz, prefixStore, cosmwasmAPI, querier, gasMeter(ctx), gasForContract(ctx))
+		require.Equal(t, queryBz[0:32], nonce)
+	}
+}
+
 func TestQueryContractError(t *testing.T) {
 	for _, testContract := range testContracts {
 		t.Run(testContract.CosmWasmVersion, func(t *testing.T) {
@@ -291,6 +330,25 @@ func TestInfiniteQueryLoopKilledGracefullyByOOM(t *testing.T) {
 	}
 }
 
+func TestInfiniteQueryLoopStoppedByCycleDetection(t *testing.T) {
+	for _, testContract := range testContracts {
+		t.Run(testContract.CosmWasmVersion, func(t *testing.T) {
+			ctx, keeper, codeID, codeHash, walletA, privKeyA, _, _ := setupTest(t, testContract.WasmFilePath, sdk.NewCoins())
+
+			_, _, addr, _, err := initHelper(t, keeper, ctx, codeID, walletA, nil, privKeyA, `{"nop":{}}`, true, testContract.IsCosmWasmV1, defaultGasForTests)
+			require.Empty(t, err)
+
+			// the contract answers the query by sending it to itself again, which is caught as soon
+			// as the nested query sends it a second time, long before the recursion limit
+			data, err := queryHelperImpl(t, keeper, ctx, addr, fmt.Sprintf(`{"send_external_query_infinite_loop":{"to":"%s","code_hash":"%s"}}`, addr.String(), codeHash), true, testContract.IsCosmWasmV1, defaultGasForTests, 2)
+
+			require.Empty(t, data)
+			require.NotNil(t, err.GenericErr)
+			require.Contains(t, err.GenericErr.Msg, "query cycle detected")
+		})
+	}
+}
+
 func TestQueryRecursionLimitEnforcedInQueries(t *testing.T) {
 	for _, testContract := range testContracts {
 		t.Run(testContract.CosmWasmVersion, func(t *testing.T) {
@@ -382,6 +440,28 @@ func TestRemoveFromStorageDuringQuery(t *testing.T) {
 	}
 }
 
+func TestV1QueryStorageIsReadOnly(t *testing.T) {
+	ctx, keeper, codeID, _, walletA, privKeyA, _, _ := setupTest(t, TestContractPaths[v1Contract], sdk.NewCoins())
+
+	_, _, contractAddress, _, initErr := initHelper(t, keeper, ctx, codeID, walletA, nil, privKeyA, `{"counter":{"counter":10, "expires":100}}`, true, true, defaultGasForTests)
+	require.Empty(t, initErr)
+
+	for _, query := range []string{`{"write_to_storage":{}}`, `{"remove_from_storage":{}}`} {
+		_, qErr := queryHelper(t, keeper, ctx, contractAddress, query, false, true, defaultGasForTests)
+		require.NotNil(t, qErr.GenericErr)
+		require.Contains(t, qErr.GenericErr.Msg, "contract tried to write to storage during a query")
+	}
+
+	// reads still work, and see the state from before the failed writes
+	queryRes, qErr := queryHelper(t, keeper, ctx, contractAddress, `{"get":{}}`, true, true, defaultGasForTests)
+	require.Empty(t, qErr)
+
+	var resp v1QueryResponse
+	e := json.Unmarshal([]byte(queryRes), &resp)
+	require.NoError(t, e)
+	require.Equal(t, uint32(10), resp.Get.Count)
+}
+
 func TestGasIsChargedForExecExternalQuery(t *testing.T) {
 	t.SkipNow() // as of v0.10 CosmWasm are overriding the default gas meter
 
@@ -723,3 +803,76 @@ func TestV1QueryV010Contract(t *testing.T) {
 	require.Empty(t, err)
 	require.Equal(t, uint32(190), binary.BigEndian.Uint32(data))
 }
+
+func TestV1VerifyMultisig(t *testing.T) {
+	ctx, keeper, codeID, _, walletA, privKeyA, _, _ := setupTest(t, TestContractPaths[v1Contract], sdk.NewCoins())
+
+	_, _, contractAddress, _, initErr := initHelper(t, keeper, ctx, codeID, walletA, nil, privKeyA, `{"nop":{}}`, true, true, defaultGasForTests)
+	require.Empty(t, initErr)
+
+	// a 2-of-3 multisig with fixed member keys
+	var members []*secp256k1.PrivKey
+	var pubKeys []crypto.PubKey
+	for i := byte(1); i <= 3; i++ {
+		member := secp256k1.GenPrivKeyFromSecret([]byte{i})
+		members = append(members, member)
+		pubKeys = append(pubKeys, member.PubKey())
+	}
+	pubkey, err := multisigkeys.NewLegacyAminoPubKey(2, pubKeys).Marshal()
+	require.NoError(t, err)
+
+	msg := []byte("a message for the multisig")
+
+	// the signatures of the members set in bits, in the order of the bits
+	verifyMsg := func(bits []bool, signed []byte) string {
+		bitArray := crypto.NewCompactBitArray(len(bits))
+		var signatures [][]byte
+		for i, bit := range bits {
+			bitArray.SetIndex(i, bit)
+			if bit {
+				signature, err := members[i].Sign(signed)
+				require.NoError(t, err)
+				signatures = append(signatures, signature)
+			}
+		}
+		bitarray, err := bitArray.Marshal()
+		require.NoError(t, err)
+		multiSignature, err := (&crypto.MultiSignature{Signatures: signatures}).Marshal()
+		require.NoError(t, err)
+
+		query, err := json.Marshal(map[string]interface{}{
+			"verify_multisig": map[string]interface{}{
+				"pubkey":     pubkey,
+				"bitarray":   bitarray,
+				"signatures": multiSignature,
+				"msg":        msg,
+			},
+		})
+		require.NoError(t, err)
+		return string(query)
+	}
+
+	for _, test := range []struct {
+		description string
+		bits        []bool
+		signed      []byte
+		valid       string
+	}{
+		{"TwoOfThree", []bool{true, false, true}, msg, "true"},
+		{"AllThree", []bool{true, true, true}, msg, "true"},
+		{"BelowThreshold", []bool{false, true, false}, msg, "false"},
+		{"OtherMessage", []bool{true, true, false}, []byte("another message"), "false"},
+	} {
+		t.Run(test.description, func(t *testing.T) {
+			queryRes, qErr := queryHelper(t, keeper, ctx, contractAddress, verifyMsg(test.bits, test.signed), true, true, defaultGasForTests)
+			require.Empty(t, qErr)
+			require.Equal(t, test.valid, queryRes)
+		})
+	}
+
+	t.Run("BitArrayLengthMismatch", func(t *testing.T) {
+		_, qErr := queryHelper(t, keeper, ctx, contractAddress, verifyMsg([]bool{true, false, true, false}, msg), true, true, defaultGasForTests)
+		require.NotNil(t, qErr.GenericErr)
+		require.Contains(t, qErr.GenericErr.Msg, "verify_legacy_multisig failed with error code 4")
+	})
+}
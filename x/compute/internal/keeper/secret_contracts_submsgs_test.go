@@ -3,6 +3,7 @@ package keeper
 import (
 	"encoding/binary"
 	"encoding/json"
+	"fmt"
 	"math"
 	"testing"
 
@@ -1496,3 +1497,46 @@ func TestReplyGasExceedingMessageGas(t *testing.T) {
 	}()
 	_, _, _, _, _ = initHelper(t, keeper, ctx, codeID, walletA, nil, privKeyA, `{"measure_gas_for_submessage":{"id":2600}}`, false, true, defaultGasForTests)
 }
+
+func TestSubmessageOutOfGasIsRepliedAsError(t *testing.T) {
+	ctx, keeper, codeID, _, walletA, privKeyA, _, _ := setupTest(t, TestContractPaths[v1Contract], sdk.NewCoins())
+
+	_, _, contractAddress, _, initErr := initHelper(t, keeper, ctx, codeID, walletA, nil, privKeyA, `{"nop":{}}`, true, true, defaultGasForTests)
+	require.Empty(t, initErr)
+
+	// the submessage loops until it runs out of its gas limit, and the reply gets the error
+	// instead of the whole tx running out of gas
+	_, _, _, events, _, execErr := execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, `{"out_of_gas_in_submsg":{}}`, true, true, defaultGasForTests, 0)
+	require.Empty(t, execErr)
+	requireEvents(t,
+		[]ContractEvent{
+			{
+				{Key: "contract_address", Value: contractAddress.String()},
+				{Key: "submsg_error", Value: `{"out_of_gas":{}}`},
+			},
+		},
+		events,
+	)
+}
+
+func TestInitSubmessageOutOfGasIsRepliedAsError(t *testing.T) {
+	ctx, keeper, codeID, codeHash, walletA, privKeyA, _, _ := setupTest(t, TestContractPaths[v1Contract], sdk.NewCoins())
+
+	_, _, contractAddress, _, initErr := initHelper(t, keeper, ctx, codeID, walletA, nil, privKeyA, `{"nop":{}}`, true, true, defaultGasForTests)
+	require.Empty(t, initErr)
+
+	// the instantiated contract loops until it runs out of its gas limit, and the reply gets the
+	// error instead of the whole tx running out of gas
+	msg := fmt.Sprintf(`{"out_of_gas_in_init_submsg":{"code_id":%d,"code_hash":"%s"}}`, codeID, codeHash)
+	_, _, _, events, _, execErr := execHelper(t, keeper, ctx, contractAddress, walletA, privKeyA, msg, true, true, defaultGasForTests, 0)
+	require.Empty(t, execErr)
+	requireEvents(t,
+		[]ContractEvent{
+			{
+				{Key: "contract_address", Value: contractAddress.String()},
+				{Key: "submsg_error", Value: `{"out_of_gas":{}}`},
+			},
+		},
+		events,
+	)
+}
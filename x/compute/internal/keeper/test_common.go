@@ -120,6 +120,7 @@ const (
 	plaintextLogsContract       = "plaintext_logs.wasm"
 	ibcContract                 = "ibc.wasm"
 	v010WithFloats              = "contract_with_floats.wasm"
+	v1WithFloats                = "v1-contract-with-floats.wasm"
 	tooHighMemoryContract       = "too-high-initial-memory.wasm"
 	staticTooHighMemoryContract = "static-too-high-initial-memory.wasm"
 	evaporateContract           = "evaporate.wasm"
@@ -140,6 +141,7 @@ var TestContractPaths = map[string]string{
 	plaintextLogsContract:       filepath.Join(".", contractPath, plaintextLogsContract),
 	ibcContract:                 filepath.Join(".", contractPath, ibcContract),
 	v010WithFloats:              filepath.Join(".", contractPath, v010WithFloats),
+	v1WithFloats:                filepath.Join(".", contractPath, v1WithFloats),
 	tooHighMemoryContract:       filepath.Join(".", contractPath, tooHighMemoryContract),
 	staticTooHighMemoryContract: filepath.Join(".", contractPath, staticTooHighMemoryContract),
 	benchContract:               filepath.Join(".", contractPath, benchContract),
@@ -570,6 +572,7 @@ func CreateTestInput(t *testing.T, isCheckTx bool, supportedFeatures string, enc
 		encoders,
 		queriers,
 		&bappTxMngr,
+		paramsKeeper.Subspace(wasmtypes.ModuleName),
 	)
 	// keeper.setParams(ctx, wasmtypes.DefaultParams())
 	// add wasm handler so we can loop-back (contracts calling contracts)
@@ -692,6 +695,7 @@ func PrepareExecSignedTxWithMultipleMsgs(
 
 	ctx = ctx.WithTxBytes(txBytes)
 	ctx = wasmtypes.WithTXCounter(ctx, 1)
+	ctx = wasmtypes.WithSubmsgCounter(ctx)
 	// updateLightClientHelper(t, ctx)
 	return ctx
 }
@@ -713,6 +717,7 @@ func PrepareExecSignedTx(t *testing.T, keeper Keeper, ctx sdk.Context, sender sd
 
 	ctx = ctx.WithTxBytes(txBytes)
 	ctx = wasmtypes.WithTXCounter(ctx, 1)
+	ctx = wasmtypes.WithSubmsgCounter(ctx)
 	// updateLightClientHelper(t, ctx)
 	return ctx
 }
@@ -736,6 +741,7 @@ func PrepareInitSignedTx(t *testing.T, keeper Keeper, ctx sdk.Context, creator,
 
 	ctx = ctx.WithTxBytes(txBytes)
 	ctx = wasmtypes.WithTXCounter(ctx, 1)
+	ctx = wasmtypes.WithSubmsgCounter(ctx)
 	// updateLightClientHelper(t, ctx)
 	return ctx
 }
@@ -756,6 +762,7 @@ func prepareMigrateSignedTx(t *testing.T, keeper Keeper, ctx sdk.Context, contra
 
 	ctx = ctx.WithTxBytes(txBytes)
 	ctx = wasmtypes.WithTXCounter(ctx, 1)
+	ctx = wasmtypes.WithSubmsgCounter(ctx)
 	// updateLightClientHelper(t, ctx)
 	return ctx
 }
@@ -775,6 +782,7 @@ func prepareUpdateAdminSignedTx(t *testing.T, keeper Keeper, ctx sdk.Context, co
 
 	ctx = ctx.WithTxBytes(txBytes)
 	ctx = wasmtypes.WithTXCounter(ctx, 1)
+	ctx = wasmtypes.WithSubmsgCounter(ctx)
 	// updateLightClientHelper(t, ctx)
 	return ctx
 }
@@ -793,6 +801,7 @@ func prepareClearAdminSignedTx(t *testing.T, keeper Keeper, ctx sdk.Context, con
 
 	ctx = ctx.WithTxBytes(txBytes)
 	ctx = wasmtypes.WithTXCounter(ctx, 1)
+	ctx = wasmtypes.WithSubmsgCounter(ctx)
 	// updateLightClientHelper(t, ctx)
 	return ctx
 }
@@ -814,6 +823,7 @@ func PrepareSignedTx(t *testing.T,
 
 	ctx = ctx.WithTxBytes(txBytes)
 	ctx = wasmtypes.WithTXCounter(ctx, 1)
+	ctx = wasmtypes.WithSubmsgCounter(ctx)
 	// updateLightClientHelper(t, ctx)
 	return ctx
 }
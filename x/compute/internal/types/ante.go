@@ -9,6 +9,7 @@ type contextKey int
 const (
 	// private type creates an interface key for Context that cannot be accessed by any other package
 	contextKeyTXCount contextKey = iota
+	contextKeySubmsgCount
 )
 
 // WithTXCounter stores a transaction counter value in the context
@@ -22,3 +23,17 @@ func TXCounter(ctx sdk.Context) (uint32, bool) {
 	val, ok := ctx.Value(contextKeyTXCount).(uint32)
 	return val, ok
 }
+
+// WithSubmsgCounter stores a counter of the submessages a transaction sent in the context. It's
+// shared by every contract call of the transaction, which all get a context derived from this one.
+func WithSubmsgCounter(ctx sdk.Context) sdk.Context {
+	var counter uint32
+	return ctx.WithValue(contextKeySubmsgCount, &counter)
+}
+
+// SubmsgCounter returns the counter of the submessages the transaction sent so far, which is nil
+// for queries or block hooks, where there's no transaction.
+func SubmsgCounter(ctx sdk.Context) *uint32 {
+	counter, _ := ctx.Value(contextKeySubmsgCount).(*uint32)
+	return counter
+}
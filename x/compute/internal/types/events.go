@@ -25,4 +25,5 @@ const (
 	AttributeKeyCodeID       = "code_id"
 	AttributeKeySigner       = "signer"
 	AttributeKeyNewAdmin     = "new_admin_address"
+	AttributeKeyGasUsed      = "gas_used"
 )
@@ -0,0 +1,145 @@
+package types
+
+import (
+	"fmt"
+	"math"
+
+	sdk "github.com/cosmos/cosmos-sdk/types"
+	paramtypes "github.com/cosmos/cosmos-sdk/x/params/types"
+)
+
+// KeyMinGasPrice is the min gas price contracts see in env.transaction.min_gas_price, as a
+// DecCoins string (e.g. "0.1uscrt"). It's set by governance rather than read from the node's
+// config, so every node passes the same value to the contract.
+var KeyMinGasPrice = []byte("MinGasPrice")
+
+// KeyOutputCompressionVersion is the format contract results are compressed with before they're
+// encrypted. Results are part of consensus, so it's set by governance, and 0 (the default) leaves
+// them as they are. A version the enclave doesn't know fails every contract call.
+var KeyOutputCompressionVersion = []byte("OutputCompressionVersion")
+
+// KeyOutputCompressionThreshold is the size in bytes below which results aren't compressed
+var KeyOutputCompressionThreshold = []byte("OutputCompressionThreshold")
+
+// MaxOutputCompressionVersion is the latest output compression version the enclave knows
+const MaxOutputCompressionVersion uint32 = 1
+
+// KeyMaxSubmessages is the most submessages the contract calls of a transaction may send between
+// them, counting the submessages of every response. 0 (the default) doesn't limit them. It's part
+// of consensus, so it's set by governance, and turned on by an upgrade.
+var KeyMaxSubmessages = []byte("MaxSubmessages")
+
+// DefaultMaxSubmessages is the limit the upgrade that introduced it sets
+const DefaultMaxSubmessages uint32 = 512
+
+// KeyContractGasCeiling is the most (sdk) gas a single contract call may use, regardless of the
+// tx gas limit. It changes which calls run out of gas, so it's set by governance, and 0 (the
+// default) leaves contracts bound by the tx gas limit only.
+var KeyContractGasCeiling = []byte("ContractGasCeiling")
+
+// KeyMaxQueryDepth is how deep queries between contracts can nest. It changes which queries
+// succeed, so it's set by governance, and 0 (the default) keeps the enclave's limit of 10.
+var KeyMaxQueryDepth = []byte("MaxQueryDepth")
+
+// maxQueryDepthLimit matches the enclave's cap: nested queries share one enclave thread, and
+// deeper nesting risks exhausting its stack
+const maxQueryDepthLimit = 20
+
+// KeyMaxEventTypeLength is the longest type in bytes a contract's custom events may have, before
+// "wasm-" is prepended to it. Events are part of consensus, so it's set by governance, and 0 (the
+// default) doesn't limit them. It's turned on by an upgrade.
+var KeyMaxEventTypeLength = []byte("MaxEventTypeLength")
+
+// DefaultMaxEventTypeLength is the limit the upgrade that introduced it sets
+const DefaultMaxEventTypeLength uint32 = 128
+
+func ParamKeyTable() paramtypes.KeyTable {
+	return paramtypes.NewKeyTable(
+		paramtypes.NewParamSetPair(KeyMinGasPrice, "", validateMinGasPrice),
+		paramtypes.NewParamSetPair(KeyOutputCompressionVersion, uint32(0), validateOutputCompressionVersion),
+		paramtypes.NewParamSetPair(KeyOutputCompressionThreshold, uint64(0), validateOutputCompressionThreshold),
+		paramtypes.NewParamSetPair(KeyMaxSubmessages, uint32(0), validateMaxSubmessages),
+		paramtypes.NewParamSetPair(KeyContractGasCeiling, uint64(0), validateContractGasCeiling),
+		paramtypes.NewParamSetPair(KeyMaxQueryDepth, uint32(0), validateMaxQueryDepth),
+		paramtypes.NewParamSetPair(KeyMaxEventTypeLength, uint32(0), validateMaxEventTypeLength),
+	)
+}
+
+func validateMinGasPrice(i interface{}) error {
+	v, ok := i.(string)
+	if !ok {
+		return fmt.Errorf("invalid parameter type for min gas price: %T", i)
+	}
+
+	// Empty strings are valid for unsetting the param
+	if v == "" {
+		return nil
+	}
+
+	_, err := sdk.ParseDecCoins(v)
+	return err
+}
+
+func validateOutputCompressionVersion(i interface{}) error {
+	v, ok := i.(uint32)
+	if !ok {
+		return fmt.Errorf("invalid parameter type for output compression version: %T", i)
+	}
+
+	if v > MaxOutputCompressionVersion {
+		return fmt.Errorf("unknown output compression version: %d", v)
+	}
+
+	return nil
+}
+
+func validateOutputCompressionThreshold(i interface{}) error {
+	if _, ok := i.(uint64); !ok {
+		return fmt.Errorf("invalid parameter type for output compression threshold: %T", i)
+	}
+
+	return nil
+}
+
+func validateMaxSubmessages(i interface{}) error {
+	v, ok := i.(uint32)
+	if !ok {
+		return fmt.Errorf("invalid parameter type for max submessages: %T", i)
+	}
+
+	// the enclave hands contracts what's left as an i32
+	if v > math.MaxInt32 {
+		return fmt.Errorf("max submessages can't be more than %d: %d", math.MaxInt32, v)
+	}
+
+	return nil
+}
+
+func validateContractGasCeiling(i interface{}) error {
+	if _, ok := i.(uint64); !ok {
+		return fmt.Errorf("invalid parameter type for contract gas ceiling: %T", i)
+	}
+
+	return nil
+}
+
+func validateMaxQueryDepth(i interface{}) error {
+	v, ok := i.(uint32)
+	if !ok {
+		return fmt.Errorf("invalid parameter type for max query depth: %T", i)
+	}
+
+	if v > maxQueryDepthLimit {
+		return fmt.Errorf("max query depth can't be more than %d: %d", maxQueryDepthLimit, v)
+	}
+
+	return nil
+}
+
+func validateMaxEventTypeLength(i interface{}) error {
+	if _, ok := i.(uint32); !ok {
+		return fmt.Errorf("invalid parameter type for max event type length: %T", i)
+	}
+
+	return nil
+}
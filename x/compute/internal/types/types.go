@@ -17,9 +17,11 @@ import (
 )
 
 const (
-	defaultLRUCacheSize        = uint64(0)
-	defaultEnclaveLRUCacheSize = uint16(100)
-	defaultQueryGasLimit       = uint64(10_000_000)
+	defaultLRUCacheSize               = uint64(0)
+	defaultEnclaveLRUCacheSize        = uint16(100)
+	defaultQueryGasLimit              = uint64(10_000_000)
+	defaultQueryResultInlineThreshold = uint32(0) // 0 lets the enclave pick its default
+	defaultVerificationThreads        = uint32(0) // 0 verifies batches serially
 )
 
 func (m Model) ValidateBasic() error {
@@ -179,6 +181,17 @@ func NewWasmCoins(cosmosCoins sdk.Coins) (wasmCoins []wasmTypes.Coin) {
 	return wasmCoins
 }
 
+// NewWasmDecCoins translates between Cosmos SDK dec coins and Wasm dec coins
+func NewWasmDecCoins(cosmosCoins sdk.DecCoins) (wasmCoins []wasmTypes.DecCoin) {
+	for _, coin := range cosmosCoins {
+		wasmCoins = append(wasmCoins, wasmTypes.DecCoin{
+			Denom:  coin.Denom,
+			Amount: coin.Amount.String(),
+		})
+	}
+	return wasmCoins
+}
+
 // ParseEvents converts wasm LogAttributes into an sdk.Events (with 0 or 1 elements)
 func ContractLogsToSdkEvents(logs []wasmTypesV010.LogAttribute, contractAddr sdk.AccAddress) sdk.Events {
 	// we always tag with the contract address issuing this event
@@ -241,17 +254,22 @@ func contractSDKEventAttributes(customAttributes []wasmTypesV010.LogAttribute, c
 
 // WasmConfig is the extra config required for wasm
 type WasmConfig struct {
-	SmartQueryGasLimit uint64
-	CacheSize          uint64
-	EnclaveCacheSize   uint16
+	SmartQueryGasLimit         uint64
+	CacheSize                  uint64
+	EnclaveCacheSize           uint16
+	QueryResultInlineThreshold uint32
+	VerificationThreads        uint32
+	GasUsedAttribute           bool
 }
 
 // DefaultWasmConfig returns the default settings for WasmConfig
 func DefaultWasmConfig() *WasmConfig {
 	return &WasmConfig{
-		SmartQueryGasLimit: defaultQueryGasLimit,
-		CacheSize:          defaultLRUCacheSize,
-		EnclaveCacheSize:   defaultEnclaveLRUCacheSize,
+		SmartQueryGasLimit:         defaultQueryGasLimit,
+		CacheSize:                  defaultLRUCacheSize,
+		EnclaveCacheSize:           defaultEnclaveLRUCacheSize,
+		QueryResultInlineThreshold: defaultQueryResultInlineThreshold,
+		VerificationThreads:        defaultVerificationThreads,
 	}
 }
 
@@ -305,6 +323,18 @@ func GetConfig(appOpts servertypes.AppOptions) *WasmConfig {
 		config.EnclaveCacheSize = enclaveCacheSize
 	}
 
+	queryResultInlineThreshold := cast.ToUint32(appOpts.Get("wasm.contract-query-result-inline-threshold"))
+	if queryResultInlineThreshold > 0 {
+		config.QueryResultInlineThreshold = queryResultInlineThreshold
+	}
+
+	verificationThreads := cast.ToUint32(appOpts.Get("wasm.contract-verification-threads"))
+	if verificationThreads > 0 {
+		config.VerificationThreads = verificationThreads
+	}
+
+	config.GasUsedAttribute = cast.ToBool(appOpts.Get("wasm.contract-gas-used-attribute"))
+
 	return config
 }
 
@@ -321,6 +351,18 @@ contract-memory-cache-size = "{{ .WASMConfig.CacheSize }}"
 
 # The WASM VM memory cache size in number of cached modules. Can safely go up to 15, but not recommended for validators
 contract-memory-enclave-cache-size = "{{ .WASMConfig.EnclaveCacheSize }}"
+
+# Query results larger than this many bytes are streamed out of the enclave in chunks.
+# 0 uses the enclave's default (1 MiB)
+contract-query-result-inline-threshold = "{{ .WASMConfig.QueryResultInlineThreshold }}"
+
+# How many enclave threads verify batches of signatures (e.g. secp256k1_batch_verify) in parallel.
+# Capped by the enclave's thread count. 0 verifies serially. Results are the same either way.
+contract-verification-threads = "{{ .WASMConfig.VerificationThreads }}"
+
+# Appends a plaintext gas_used attribute, the gas charged for the call, to the wasm event of every
+# instantiate, execute, migrate and reply. Events aren't part of consensus, so this is safe to set per node
+contract-gas-used-attribute = {{ .WASMConfig.GasUsedAttribute }}
 `
 
 // ZeroSender is a valid 20 byte canonical address that's used to bypass the x/compute checks
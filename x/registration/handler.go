@@ -15,6 +15,9 @@ const (
 	AttributeSigner        = "signer"
 	AttributeEncryptedSeed = "encrypted_seed"
 	AttributeNodeID        = "node_id"
+	// AttributeDowngradeReason is why a node was admitted with a vulnerable platform, which only
+	// non-production enclaves do. It's left out for other nodes.
+	AttributeDowngradeReason = "downgrade_reason"
 )
 
 // NewHandler returns a handler for "bank" type messages.
@@ -58,19 +61,23 @@ func handleRaAuthenticate(ctx sdk.Context, k Keeper, msg *types.RaAuthenticate)
 		return nil, err
 	}
 
-	encSeed, err := k.RegisterNode(ctx, msg.Certificate)
+	encSeed, downgradeReason, err := k.RegisterNode(ctx, msg.Certificate)
 	if err != nil {
 		return nil, err
 	}
 
+	attributes := []sdk.Attribute{
+		sdk.NewAttribute(sdk.AttributeKeyModule, ModuleName),
+		sdk.NewAttribute(AttributeSigner, msg.Sender.String()),
+		sdk.NewAttribute(AttributeEncryptedSeed, fmt.Sprintf("0x%02x", encSeed)),
+		sdk.NewAttribute(AttributeNodeID, fmt.Sprintf("0x%s", hex.EncodeToString(pubkey))),
+	}
+	if downgradeReason != "" {
+		attributes = append(attributes, sdk.NewAttribute(AttributeDowngradeReason, downgradeReason))
+	}
+
 	ctx.EventManager().EmitEvents(sdk.Events{
-		sdk.NewEvent(
-			sdk.EventTypeMessage,
-			sdk.NewAttribute(sdk.AttributeKeyModule, ModuleName),
-			sdk.NewAttribute(AttributeSigner, msg.Sender.String()),
-			sdk.NewAttribute(AttributeEncryptedSeed, fmt.Sprintf("0x%02x", encSeed)),
-			sdk.NewAttribute(AttributeNodeID, fmt.Sprintf("0x%s", hex.EncodeToString(pubkey))),
-		),
+		sdk.NewEvent(sdk.EventTypeMessage, attributes...),
 	})
 
 	return &sdk.Result{
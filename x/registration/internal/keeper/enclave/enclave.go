@@ -10,7 +10,7 @@ func (Api) LoadSeed(masterKey []byte, seed []byte, apiKey []byte) (bool, error)
 	return api.LoadSeedToEnclave(masterKey, seed, apiKey)
 }
 
-func (Api) GetEncryptedSeed(masterCert []byte) ([]byte, error) {
+func (Api) GetEncryptedSeed(masterCert []byte) ([]byte, string, error) {
 	return api.GetEncryptedSeed(masterCert)
 }
 
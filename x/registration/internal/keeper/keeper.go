@@ -189,10 +189,14 @@ func InitializeNode(homeDir string, enclave EnclaveInterface) {
 	}
 }
 
-func (k Keeper) RegisterNode(ctx sdk.Context, certificate ra.Certificate) ([]byte, error) {
+// RegisterNode returns the seed encrypted for the node certificate attests to, and why the node was
+// admitted with a vulnerable platform, which is empty if its platform isn't vulnerable or it was
+// already registered
+func (k Keeper) RegisterNode(ctx sdk.Context, certificate ra.Certificate) ([]byte, string, error) {
 	// fmt.Println("RegisterNode")
 	var encSeed []byte
 	var publicKey []byte
+	var downgradeReason string
 
 	if isSimulationMode(ctx) {
 		// any sha256 hash is good enough
@@ -201,23 +205,26 @@ func (k Keeper) RegisterNode(ctx sdk.Context, certificate ra.Certificate) ([]byt
 
 		publicKey_, err := ra.VerifyCombinedCert(certificate)
 		if err != nil {
-			return nil, sdkerrors.Wrap(types.ErrAuthenticateFailed, err.Error())
+			return nil, "", sdkerrors.Wrap(types.ErrAuthenticateFailed, err.Error())
 		}
 
 		publicKey = publicKey_
 
 		isAuth, err := k.isNodeAuthenticated(ctx, publicKey)
 		if err != nil {
-			return nil, sdkerrors.Wrap(types.ErrAuthenticateFailed, err.Error())
+			return nil, "", sdkerrors.Wrap(types.ErrAuthenticateFailed, err.Error())
 		}
 		if isAuth {
-			return k.getRegistrationInfo(ctx, publicKey).EncryptedSeed, nil
+			return k.getRegistrationInfo(ctx, publicKey).EncryptedSeed, "", nil
 		}
 
-		encSeed, err = k.enclave.GetEncryptedSeed(certificate)
+		encSeed, downgradeReason, err = k.enclave.GetEncryptedSeed(certificate)
 		if err != nil {
 			// return 0, sdkerrors.Wrap(err, "cosmwasm create")
-			return nil, sdkerrors.Wrap(types.ErrAuthenticateFailed, err.Error())
+			return nil, "", sdkerrors.Wrap(types.ErrAuthenticateFailed, err.Error())
+		}
+		if downgradeReason != "" {
+			ctx.Logger().Info("Registered a node with a vulnerable platform", "node_id", hex.EncodeToString(publicKey), "reason", downgradeReason)
 		}
 	}
 	fmt.Println("Done RegisterNode")
@@ -233,7 +240,7 @@ func (k Keeper) RegisterNode(ctx sdk.Context, certificate ra.Certificate) ([]byt
 		k.SetRegistrationInfo_Verified(ctx, regInfo, publicKey)
 	}
 
-	return encSeed, nil
+	return encSeed, downgradeReason, nil
 }
 
 // returns true when simulation mode used by gas=auto queries
@@ -8,8 +8,8 @@ func (MockEnclaveApi) LoadSeed(_ []byte, _ []byte, _ []byte) (bool, error) {
 	return true, nil
 }
 
-func (MockEnclaveApi) GetEncryptedSeed(_ []byte) ([]byte, error) {
-	return []byte(""), nil
+func (MockEnclaveApi) GetEncryptedSeed(_ []byte) ([]byte, string, error) {
+	return []byte(""), "", nil
 }
 
 func (MockEnclaveApi) GetEncryptedGenesisSeed(_ []byte) ([]byte, error) {